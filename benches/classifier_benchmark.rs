@@ -0,0 +1,62 @@
+// Criterion benchmarks for question type classification
+//
+// Run with: cargo bench --bench classifier_benchmark
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use md2db::classifier::{classify, Language, NlpClassifier};
+use md2db::models::QuestionType;
+use md2db::parser::parse_markdown;
+use md2db::sample::{generate_markdown, SampleConfig};
+
+/// Stems and option texts of a generated bank with every question type
+fn sample_inputs(count: usize) -> Vec<(String, Vec<String>)> {
+    let types = vec![
+        QuestionType::Choice,
+        QuestionType::MultipleChoice,
+        QuestionType::TrueFalse,
+        QuestionType::FillInTheBlank,
+        QuestionType::Subjective,
+    ];
+    let markdown = generate_markdown(&SampleConfig::default().with_count(count).with_types(types));
+    parse_markdown(&markdown)
+        .expect("sample bank parses")
+        .into_iter()
+        .map(|q| (q.stem, q.options.into_iter().map(|o| o.content).collect()))
+        .collect()
+}
+
+/// Cost of classifying one question, through each entry point
+fn bench_classify(c: &mut Criterion) {
+    let inputs = sample_inputs(1000);
+    let mut group = c.benchmark_group("classify");
+    group.throughput(Throughput::Elements(inputs.len() as u64));
+
+    group.bench_function("all_levels", |b| {
+        b.iter(|| {
+            for (stem, options) in &inputs {
+                black_box(classify(black_box(stem), options));
+            }
+        })
+    });
+
+    group.bench_function("nlp_only", |b| {
+        b.iter(|| {
+            for (stem, options) in &inputs {
+                black_box(NlpClassifier::classify(black_box(stem), options));
+            }
+        })
+    });
+
+    group.bench_function("nlp_chinese_keywords", |b| {
+        b.iter(|| {
+            for (stem, options) in &inputs {
+                black_box(NlpClassifier::classify_with_language(black_box(stem), options, Some(Language::Zh)));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_classify);
+criterion_main!(benches);
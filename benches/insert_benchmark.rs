@@ -0,0 +1,70 @@
+// Criterion benchmarks comparing PostgreSQL insert paths
+//
+// Run against a scratch database (rows are deleted again after each run):
+//   MD2DB_BENCH_DATABASE_URL=postgres://localhost/md2db_bench cargo bench --bench insert_benchmark
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use md2db::database::postgres::PostgresRepository;
+use md2db::database::{DatabaseConfig, InsertMode, QuestionRepository};
+use md2db::models::Question;
+use md2db::parser::parse_markdown_with_options;
+use md2db::presets::preset;
+use md2db::sample::generate_numbered;
+use uuid::Uuid;
+
+/// Parsed sample questions; ids are refreshed per iteration so every row is new
+fn sample_questions(count: usize) -> Vec<Question> {
+    let options = preset("generic-numbered").unwrap();
+    let questions = parse_markdown_with_options(&generate_numbered(count), &options).expect("sample bank parses");
+    assert_eq!(questions.len(), count);
+    questions
+}
+
+fn fresh_ids(questions: &[Question]) -> Vec<Question> {
+    questions
+        .iter()
+        .map(|q| Question {
+            id: Uuid::new_v4(),
+            ..q.clone()
+        })
+        .collect()
+}
+
+fn bench_insert_modes(c: &mut Criterion) {
+    let Ok(url) = std::env::var("MD2DB_BENCH_DATABASE_URL") else {
+        eprintln!("MD2DB_BENCH_DATABASE_URL not set; skipping insert benchmarks");
+        return;
+    };
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("insert_modes");
+    group.sample_size(10);
+
+    for mode in [InsertMode::MultiRow, InsertMode::Copy] {
+        let config = DatabaseConfig::default().with_insert_mode(mode);
+        let repo = rt.block_on(async {
+            let repo = PostgresRepository::connect(&url, &config).await.unwrap();
+            repo.migrate().await.unwrap();
+            repo
+        });
+
+        for size in [1_000, 10_000] {
+            let questions = sample_questions(size);
+            let mut saved = Vec::new();
+            group.throughput(Throughput::Elements(size as u64));
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", mode), size), &questions, |b, questions| {
+                b.iter_batched(
+                    || fresh_ids(questions),
+                    |batch| saved.extend(rt.block_on(repo.save_batch(&batch)).unwrap()),
+                    BatchSize::LargeInput,
+                )
+            });
+            rt.block_on(repo.delete_batch(&saved)).unwrap();
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert_modes);
+criterion_main!(benches);
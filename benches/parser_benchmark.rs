@@ -4,63 +4,11 @@
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use md2db::parser::parse_markdown;
+use md2db::sample::generate_numbered;
 
 /// Generate test markdown content with specified number of questions
 fn generate_test_questions(count: usize) -> String {
-    let mut questions = String::new();
-
-    for i in 0..count {
-        let q_type = i % 4;
-        match q_type {
-            0 => {
-                // Multiple Choice
-                questions.push_str(&format!(
-                    "{}. What is {} + {}?\n\n\
-                     A. {}\n\
-                     B. {}\n\
-                     C. {}\n\
-                     D. {}\n\n\
-                     Answer: B\n\n",
-                    i + 1,
-                    i,
-                    i,
-                    i,
-                    i * 2,
-                    i * 3,
-                    i * 4
-                ));
-            }
-            1 => {
-                // True/False
-                questions.push_str(&format!(
-                    "{}. The value of {} + {} equals {}.\n\n\
-                     True\n\n",
-                    i + 1,
-                    i,
-                    i,
-                    i * 2
-                ));
-            }
-            2 => {
-                // Fill in the blank
-                questions.push_str(&format!(
-                    "{}. The capital of country {} is _____.\n\n\
-                     Answer: Capital {}\n\n",
-                    i + 1, i, i
-                ));
-            }
-            _ => {
-                // Subjective with LaTeX
-                questions.push_str(&format!(
-                    "{}. Explain the mathematical formula: $x^2 + y^2 = z^2$\n\n\
-                     Answer: This is the Pythagorean theorem.\n\n",
-                    i + 1
-                ));
-            }
-        }
-    }
-
-    questions
+    generate_numbered(count)
 }
 
 /// Benchmark parsing with different file sizes
@@ -153,11 +101,13 @@ fn bench_memory_allocation(c: &mut Criterion) {
 /// Compare single-threaded vs multi-threaded parsing (if parallel features exist)
 #[cfg(feature = "parallel")]
 fn bench_parallel_parsing(c: &mut Criterion) {
+    use md2db::parser::{parse_markdown_parallel, ParserOptions};
+    use md2db::sample::{generate_markdown, SampleConfig};
     use rayon::prelude::*;
 
     let mut group = c.benchmark_group("parallel_parsing");
 
-    let contents: Vec<String> = (0..10).map(|i| generate_test_questions(100)).collect();
+    let contents: Vec<String> = (0..10).map(|_| generate_test_questions(100)).collect();
 
     group.bench_function("sequential_10_files", |b| {
         b.iter(|| {
@@ -177,6 +127,16 @@ fn bench_parallel_parsing(c: &mut Criterion) {
         })
     });
 
+    // One large file, whole and split into blocks at question headings
+    let large = generate_markdown(&SampleConfig::default().with_count(5000));
+    let options = ParserOptions::default();
+    group.bench_function("single_file_5000", |b| {
+        b.iter(|| parse_markdown(black_box(&large)).unwrap())
+    });
+    group.bench_function("split_file_5000", |b| {
+        b.iter(|| parse_markdown_parallel(black_box(&large), &options, 64 * 1024).unwrap())
+    });
+
     group.finish();
 }
 
@@ -191,4 +151,7 @@ criterion_group!(
 #[cfg(feature = "parallel")]
 criterion_group!(parallel_benches, bench_parallel_parsing);
 
+#[cfg(feature = "parallel")]
+criterion_main!(benches, parallel_benches);
+#[cfg(not(feature = "parallel"))]
 criterion_main!(benches);
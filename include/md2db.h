@@ -0,0 +1,48 @@
+/* md2db C API. Strings returned by md2db_* functions are freed with md2db_string_free. */
+
+#ifndef MD2DB_H
+#define MD2DB_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Version of the library, as a static string the caller must not free
+const char *md2db_version(void);
+
+// Parse Markdown into a JSON array of questions
+//
+// Returns NULL if `markdown` is NULL or not UTF-8, or if parsing fails;
+// [`md2db_last_error`] then says why. Free the result with
+// [`md2db_string_free`].
+//
+// # Safety
+//
+// `markdown` must be NULL or point to a NUL-terminated string that stays
+// valid for the duration of the call.
+char *md2db_parse_markdown(const char *markdown);
+
+// Message of the last call on this thread that returned NULL
+//
+// Returns NULL if no call has failed yet. Free the result with
+// [`md2db_string_free`].
+char *md2db_last_error(void);
+
+// Release a string returned by this library
+//
+// # Safety
+//
+// `s` must be NULL or a pointer returned by an md2db function that has not
+// been freed yet.
+void md2db_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MD2DB_H */
@@ -0,0 +1,15 @@
+-- Base questions table.
+-- JSON-valued columns (type, options, latex) are stored as serialized text.
+CREATE TABLE IF NOT EXISTS questions (
+    id          UUID PRIMARY KEY,
+    type        TEXT NOT NULL,
+    stem        TEXT NOT NULL,
+    answer      TEXT,
+    analysis    TEXT,
+    options     TEXT NOT NULL DEFAULT '[]',
+    latex       TEXT NOT NULL DEFAULT '[]',
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_questions_type ON questions (type);
+CREATE INDEX IF NOT EXISTS idx_questions_created_at ON questions (created_at, id);
@@ -0,0 +1,6 @@
+-- Topic tags, text direction and import provenance.
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS tags TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS text_direction TEXT NOT NULL DEFAULT '"ltr"';
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS provenance TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_questions_tags ON questions USING GIN ((tags::jsonb));
@@ -0,0 +1,21 @@
+-- Relational option and image tables used by the normalized schema mode.
+-- In that mode questions.options stays '[]' and options live here instead.
+CREATE TABLE IF NOT EXISTS question_options (
+    question_id UUID NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    position    INTEGER NOT NULL,
+    content     TEXT NOT NULL,
+    sort_order  INTEGER NOT NULL DEFAULT 0,
+    is_correct  BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (question_id, position)
+);
+
+CREATE TABLE IF NOT EXISTS question_images (
+    question_id   UUID NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    position      INTEGER NOT NULL,
+    url           TEXT,
+    hash          TEXT,
+    original_path TEXT,
+    PRIMARY KEY (question_id, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_question_images_hash ON question_images (hash);
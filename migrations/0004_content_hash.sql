@@ -0,0 +1,5 @@
+-- Normalized content hash for import deduplication.
+-- Rows saved before this migration keep a NULL hash until they are re-saved.
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS content_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_questions_content_hash ON questions (content_hash);
@@ -0,0 +1,11 @@
+-- Prior versions of edited questions, for content review.
+-- `snapshot` holds the full question as serialized JSON text, including its
+-- options and images regardless of the schema mode.
+CREATE TABLE IF NOT EXISTS question_revisions (
+    question_id UUID NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    revision    INTEGER NOT NULL,
+    snapshot    TEXT NOT NULL,
+    actor       TEXT,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (question_id, revision)
+);
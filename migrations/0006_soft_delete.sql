@@ -0,0 +1,4 @@
+-- Soft delete: deleted questions keep their row until purged.
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_questions_deleted_at ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,13 @@
+-- Question banks group the questions of one exam, chapter or vendor export.
+-- `tags` is a JSON array stored as serialized text, like questions.tags.
+CREATE TABLE IF NOT EXISTS question_banks (
+    id          UUID PRIMARY KEY,
+    name        TEXT NOT NULL,
+    description TEXT,
+    tags        TEXT NOT NULL DEFAULT '[]',
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS bank_id UUID REFERENCES question_banks (id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_questions_bank_id ON questions (bank_id);
@@ -0,0 +1,2 @@
+-- Location of a persisted image in the media store, relative to its root.
+ALTER TABLE question_images ADD COLUMN IF NOT EXISTS stored_path TEXT;
@@ -0,0 +1,3 @@
+-- Position of a question within its import (archive entry order, then
+-- document order), so exam papers can be listed in their original numbering.
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS sequence BIGINT;
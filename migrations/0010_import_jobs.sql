@@ -0,0 +1,14 @@
+-- Background import jobs, so their status and result outlive the process.
+-- `sources` and `result` are JSON stored as serialized text, like questions.tags.
+CREATE TABLE IF NOT EXISTS import_jobs (
+    id          UUID PRIMARY KEY,
+    status      TEXT NOT NULL,
+    sources     TEXT NOT NULL DEFAULT '[]',
+    progress    DOUBLE PRECISION NOT NULL DEFAULT 0,
+    result      TEXT,
+    error       TEXT,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_import_jobs_created_at ON import_jobs (created_at DESC);
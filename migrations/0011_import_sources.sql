@@ -0,0 +1,9 @@
+-- Content hash of each file last imported into a bank, so incremental
+-- re-imports can skip files that have not changed.
+CREATE TABLE IF NOT EXISTS import_sources (
+    bank_id       UUID NOT NULL REFERENCES question_banks (id) ON DELETE CASCADE,
+    source        TEXT NOT NULL,
+    content_hash  TEXT NOT NULL,
+    imported_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (bank_id, source)
+);
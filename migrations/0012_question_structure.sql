@@ -0,0 +1,4 @@
+-- Section title and original number of a question in its document
+-- ("一、单选题", "12"), so exported papers keep their structure.
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS section TEXT;
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS number TEXT;
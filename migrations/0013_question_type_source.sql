@@ -0,0 +1,3 @@
+-- What decided a question's type ("section", "classifier", "default"),
+-- stored as JSON like the type itself.
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS type_source TEXT;
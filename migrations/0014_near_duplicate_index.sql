@@ -0,0 +1,3 @@
+-- Near-duplicate checks look up live questions of one type by stem length.
+CREATE INDEX IF NOT EXISTS idx_questions_type_stem_length
+    ON questions (type, char_length(stem)) WHERE deleted_at IS NULL;
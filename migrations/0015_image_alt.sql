@@ -0,0 +1,2 @@
+-- Alt text of an image link, if it had any.
+ALTER TABLE question_images ADD COLUMN IF NOT EXISTS alt TEXT;
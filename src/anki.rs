@@ -0,0 +1,648 @@
+//! Anki package export
+//!
+//! [`AnkiExporter`] writes questions as an Anki package (`.apkg`) so students
+//! can drill an imported bank with spaced repetition. A package is a ZIP
+//! holding a `collection.anki2` SQLite database, a `media` index and the
+//! media files it names.
+//!
+//! Each question type has its own note type ("MD2DB Choice", "MD2DB
+//! True/False", ...) whose card shows the stem (and options) on the front and
+//! the answer and analysis on the back. Markdown is rendered to HTML and
+//! `$…$` formulas become MathJax `\(…\)`, which Anki renders natively.
+//! Local images are read from the [`MediaStore`] and bundled; remote images
+//! stay links. Note GUIDs are the question IDs, so importing a newer export
+//! of the same bank updates the existing notes instead of duplicating them.
+
+use crate::bidi::split_math;
+use crate::media::{validate_image, MediaStore};
+use crate::models::{ImageRef, Question, QuestionType};
+use crate::render::strip_option_prefix;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use pulldown_cmark::{html, Options, Parser};
+use rusqlite::{params, Connection};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+
+/// Deck used when none is named
+pub const DEFAULT_DECK: &str = "MD2DB";
+
+/// Schema of an Anki 2.1 collection (version 11), as written by Anki itself
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null, scm integer not null,
+    ver integer not null, dty integer not null, usn integer not null, ls integer not null,
+    conf text not null, models text not null, decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null, mod integer not null,
+    usn integer not null, tags text not null, flds text not null, sfld integer not null,
+    csum integer not null, flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null, ord integer not null,
+    mod integer not null, usn integer not null, type integer not null, queue integer not null,
+    due integer not null, ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null, odid integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null, ease integer not null,
+    ivl integer not null, lastIvl integer not null, factor integer not null, time integer not null,
+    type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+/// Card styling shared by every note type
+const CSS: &str = ".card { font-family: arial; font-size: 20px; text-align: left; color: black; background-color: white; }
+.options ol { list-style-type: upper-alpha; }
+.answer { font-weight: bold; }
+.analysis { margin-top: 1em; color: #555; }
+img { max-width: 100%; }";
+
+/// An exported package
+#[derive(Debug)]
+pub struct AnkiPackage {
+    /// The `.apkg` file
+    pub data: Vec<u8>,
+    /// Notes (one card each) in the package
+    pub notes: usize,
+    /// Media files bundled
+    pub media_files: usize,
+    /// Images that could not be bundled
+    pub warnings: Vec<String>,
+}
+
+/// Writes questions as an Anki package
+pub struct AnkiExporter {
+    deck: String,
+    media_store: Option<Arc<dyn MediaStore>>,
+}
+
+impl Default for AnkiExporter {
+    fn default() -> Self {
+        Self::new(DEFAULT_DECK)
+    }
+}
+
+impl AnkiExporter {
+    /// Exporter putting every card in `deck` (`::` separates subdecks)
+    pub fn new(deck: impl Into<String>) -> Self {
+        Self {
+            deck: deck.into(),
+            media_store: None,
+        }
+    }
+
+    /// Bundle local images from `store`
+    pub fn with_media_store(mut self, store: Arc<dyn MediaStore>) -> Self {
+        self.media_store = Some(store);
+        self
+    }
+
+    /// Build a package holding `questions`
+    pub async fn export(&self, questions: &[Question]) -> Result<AnkiPackage> {
+        let mut media = MediaFiles::default();
+        let mut warnings = Vec::new();
+        let mut notes = Vec::with_capacity(questions.len());
+        for question in questions {
+            let images = self.images(question, &mut media, &mut warnings).await;
+            notes.push(Note::new(question, &images));
+        }
+
+        let deck = self.deck.clone();
+        let note_count = notes.len();
+        let media_count = media.files.len();
+        let data = tokio::task::spawn_blocking(move || write_package(&deck, &notes, &media))
+            .await
+            .context("Failed to write Anki package")??;
+
+        Ok(AnkiPackage {
+            data,
+            notes: note_count,
+            media_files: media_count,
+            warnings,
+        })
+    }
+
+    /// `<img>` sources for a question's images, bundling local ones
+    async fn images(&self, question: &Question, media: &mut MediaFiles, warnings: &mut Vec<String>) -> Vec<String> {
+        let mut sources = Vec::with_capacity(question.images.len());
+        for image in &question.images {
+            match image {
+                ImageRef::Remote { url, .. } => sources.push(url.clone()),
+                ImageRef::Local { hash, original_path, stored_path, .. } => {
+                    if let Some(name) = media.names.get(hash) {
+                        sources.push(name.clone());
+                        continue;
+                    }
+                    let data = match &self.media_store {
+                        Some(store) if !hash.is_empty() => store.get(hash).await,
+                        _ => Ok(None),
+                    };
+                    match data {
+                        Ok(Some(data)) => {
+                            let extension = stored_path
+                                .as_deref()
+                                .and_then(|p| p.rsplit_once('.'))
+                                .map(|(_, ext)| ext.to_string())
+                                .or_else(|| validate_image(&data).ok().map(|info| info.format.to_string()))
+                                .unwrap_or_else(|| "bin".to_string());
+                            let name = format!("{}.{}", hash, extension);
+                            media.names.insert(hash.clone(), name.clone());
+                            media.files.push((name.clone(), data));
+                            sources.push(name);
+                        }
+                        Ok(None) => warnings.push(format!(
+                            "{}: image {} is not in the media store",
+                            question.id, original_path
+                        )),
+                        Err(e) => warnings.push(format!(
+                            "{}: failed to read image {}: {}",
+                            question.id, original_path, e
+                        )),
+                    }
+                }
+            }
+        }
+        sources
+    }
+}
+
+/// Media bundled so far, by content hash
+#[derive(Default)]
+struct MediaFiles {
+    names: HashMap<String, String>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+/// Anki note type for a question type
+#[derive(Debug, Clone, Copy)]
+struct NoteType {
+    id: i64,
+    name: &'static str,
+    fields: &'static [&'static str],
+}
+
+const CHOICE_FIELDS: &[&str] = &["Stem", "Options", "Answer", "Analysis"];
+const PLAIN_FIELDS: &[&str] = &["Stem", "Answer", "Analysis"];
+
+impl NoteType {
+    /// Note type of each question type; IDs are fixed so re-imports reuse them
+    fn of(qtype: QuestionType) -> Self {
+        let (id, name, fields) = match qtype {
+            QuestionType::Choice => (1_714_000_000_001, "MD2DB Choice", CHOICE_FIELDS),
+            QuestionType::MultipleChoice => (1_714_000_000_002, "MD2DB Multiple Choice", CHOICE_FIELDS),
+            QuestionType::TrueFalse => (1_714_000_000_003, "MD2DB True/False", PLAIN_FIELDS),
+            QuestionType::FillInTheBlank => (1_714_000_000_004, "MD2DB Fill in the Blank", PLAIN_FIELDS),
+            QuestionType::Subjective => (1_714_000_000_005, "MD2DB Subjective", PLAIN_FIELDS),
+        };
+        Self { id, name, fields }
+    }
+
+    /// Model definition stored in the collection's `models` column
+    fn model(&self, deck_id: i64, modified: i64) -> serde_json::Value {
+        let front = if self.fields.contains(&"Options") {
+            "{{Stem}}<div class=\"options\">{{Options}}</div>"
+        } else {
+            "{{Stem}}"
+        };
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "type": 0,
+            "mod": modified,
+            "usn": -1,
+            "sortf": 0,
+            "did": deck_id,
+            "tmpls": [{
+                "name": "Card 1",
+                "ord": 0,
+                "qfmt": front,
+                "afmt": "{{FrontSide}}<hr id=\"answer\"><div class=\"answer\">{{Answer}}</div>\
+                         {{#Analysis}}<div class=\"analysis\">{{Analysis}}</div>{{/Analysis}}",
+                "bqfmt": "",
+                "bafmt": "",
+                "did": null,
+                "bfont": "",
+                "bsize": 0,
+            }],
+            "flds": self.fields.iter().enumerate().map(|(ord, name)| json!({
+                "name": name,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            })).collect::<Vec<_>>(),
+            "css": CSS,
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\
+                         \\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "latexsvg": false,
+            "req": [[0, "any", [0]]],
+            "tags": [],
+            "vers": [],
+        })
+    }
+}
+
+/// One question as note fields
+struct Note {
+    guid: String,
+    note_type: NoteType,
+    fields: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl Note {
+    fn new(question: &Question, images: &[String]) -> Self {
+        let note_type = NoteType::of(question.qtype);
+        let mut stem = to_html(&question.stem);
+        for src in images {
+            stem.push_str(&format!("<div><img src=\"{}\"></div>", escape_html(src)));
+        }
+        if question.text_direction != crate::models::TextDirection::Ltr {
+            stem = format!("<div dir=\"rtl\">{}</div>", stem);
+        }
+
+        let letter = |i: usize| (b'A' + (i % 26) as u8) as char;
+        let correct: Vec<String> = question
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.is_correct)
+            .map(|(i, o)| format!("{}. {}", letter(i), to_html(strip_option_prefix(&o.content, i))))
+            .collect();
+        let answer = if correct.is_empty() {
+            question.answer.as_deref().map(to_html).unwrap_or_default()
+        } else {
+            correct.join("<br>")
+        };
+        let analysis = question.analysis.as_deref().map(to_html).unwrap_or_default();
+
+        let mut fields = vec![stem];
+        if note_type.fields.contains(&"Options") {
+            let items: String = question
+                .options
+                .iter()
+                .enumerate()
+                .map(|(i, o)| format!("<li>{}</li>", to_html(strip_option_prefix(&o.content, i))))
+                .collect();
+            fields.push(format!("<ol>{}</ol>", items));
+        }
+        fields.push(answer);
+        fields.push(analysis);
+
+        Self {
+            guid: question.id.simple().to_string(),
+            note_type,
+            fields,
+            // Anki tags are space-separated
+            tags: question
+                .tags
+                .iter()
+                .map(|t| t.split_whitespace().collect::<Vec<_>>().join("_"))
+                .filter(|t| !t.is_empty())
+                .collect(),
+        }
+    }
+}
+
+/// Write the collection and media into a `.apkg` ZIP
+fn write_package(deck: &str, notes: &[Note], media: &MediaFiles) -> Result<Vec<u8>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("collection.anki2");
+    write_collection(&Connection::open(&path)?, deck, notes)?;
+    let collection = std::fs::read(&path)?;
+
+    let mut data = Vec::new();
+    {
+        let mut writer = ::zip::ZipWriter::new(Cursor::new(&mut data));
+        let options = ::zip::write::SimpleFileOptions::default();
+        writer.start_file("collection.anki2", options)?;
+        writer.write_all(&collection)?;
+
+        let index: serde_json::Map<String, serde_json::Value> = media
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (i.to_string(), json!(name)))
+            .collect();
+        writer.start_file("media", options)?;
+        writer.write_all(serde_json::to_string(&index)?.as_bytes())?;
+        for (i, (_, content)) in media.files.iter().enumerate() {
+            writer.start_file(i.to_string(), options)?;
+            writer.write_all(content)?;
+        }
+        writer.finish()?;
+    }
+    Ok(data)
+}
+
+/// Fill a fresh collection with one new card per note
+fn write_collection(conn: &Connection, deck: &str, notes: &[Note]) -> Result<()> {
+    conn.execute_batch(SCHEMA)?;
+    let now = Utc::now();
+    let (seconds, millis) = (now.timestamp(), now.timestamp_millis());
+    let deck_id = deck_id(deck);
+
+    let models: serde_json::Map<String, serde_json::Value> = QuestionType::ALL
+        .iter()
+        .map(|&qtype| NoteType::of(qtype))
+        .map(|note_type| (note_type.id.to_string(), note_type.model(deck_id, seconds)))
+        .collect();
+    let deck_entry = |id: i64, name: &str| {
+        json!({
+            "id": id,
+            "name": name,
+            "mod": seconds,
+            "usn": -1,
+            "lrnToday": [0, 0],
+            "revToday": [0, 0],
+            "newToday": [0, 0],
+            "timeToday": [0, 0],
+            "collapsed": false,
+            "browserCollapsed": false,
+            "desc": "",
+            "dyn": 0,
+            "conf": 1,
+            "extendNew": 0,
+            "extendRev": 0,
+        })
+    };
+    let decks = json!({
+        "1": deck_entry(1, "Default"),
+        deck_id.to_string(): deck_entry(deck_id, deck),
+    });
+    let conf = json!({
+        "activeDecks": [1],
+        "curDeck": 1,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": null,
+        "nextPos": notes.len() + 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    });
+    let dconf = json!({
+        "1": {
+            "id": 1,
+            "name": "Default",
+            "mod": 0,
+            "usn": 0,
+            "maxTaken": 60,
+            "autoplay": true,
+            "timer": 0,
+            "replayq": true,
+            "dyn": false,
+            "new": { "delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500, "separate": true, "order": 1, "perDay": 20, "bury": true },
+            "rev": { "perDay": 100, "ease4": 1.3, "fuzz": 0.05, "minSpace": 1, "ivlFct": 1, "maxIvl": 36500, "bury": true },
+            "lapse": { "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 0 },
+        }
+    });
+    conn.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            seconds - seconds % 86_400,
+            millis,
+            conf.to_string(),
+            serde_json::Value::Object(models).to_string(),
+            decks.to_string(),
+            dconf.to_string(),
+        ],
+    )?;
+
+    for (i, note) in notes.iter().enumerate() {
+        let id = millis + i as i64;
+        let sort_field = strip_html(&note.fields[0]);
+        let tags = if note.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", note.tags.join(" "))
+        };
+        conn.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+            params![id, note.guid, note.note_type.id, seconds, tags, note.fields.join("\x1f"), sort_field, checksum(&sort_field)],
+        )?;
+        conn.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            params![id, deck_id, seconds, i as i64 + 1],
+        )?;
+    }
+    Ok(())
+}
+
+/// Stable deck ID for a deck name, so re-exports land in the same deck
+fn deck_id(name: &str) -> i64 {
+    let digest = Sha1::digest(name.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes[2..].copy_from_slice(&digest[..6]);
+    i64::from_be_bytes(bytes) + 2
+}
+
+/// Anki's duplicate-check checksum: the first 32 bits of the field's SHA-1
+fn checksum(field: &str) -> i64 {
+    let digest = Sha1::digest(field.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as i64
+}
+
+/// Render Markdown to HTML, with `$…$` / `$$…$$` as MathJax delimiters
+///
+/// A lone paragraph is unwrapped so short fields such as options stay inline.
+fn to_html(markdown: &str) -> String {
+    let mut formulas = Vec::new();
+    let mut source = String::with_capacity(markdown.len());
+    for (segment, is_math) in split_math(markdown) {
+        if is_math {
+            // Private-use placeholders keep formulas away from Markdown
+            source.push_str(&format!("\u{e000}{}\u{e001}", formulas.len()));
+            formulas.push(segment);
+        } else {
+            source.push_str(segment);
+        }
+    }
+
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, Parser::new_ext(&source, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH));
+    for (i, formula) in formulas.iter().enumerate() {
+        let mathjax = match formula.strip_prefix("$$").and_then(|f| f.strip_suffix("$$")) {
+            Some(display) => format!("\\[{}\\]", escape_html(display)),
+            None => format!("\\({}\\)", escape_html(&formula[1..formula.len() - 1])),
+        };
+        rendered = rendered.replace(&format!("\u{e000}{}\u{e001}", i), &mathjax);
+    }
+
+    let rendered = rendered.trim_end();
+    match rendered.strip_prefix("<p>").and_then(|r| r.strip_suffix("</p>")) {
+        Some(inner) if !inner.contains("<p>") => inner.to_string(),
+        _ => rendered.to_string(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Field text without tags, as Anki stores the sort field
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::FsMediaStore;
+    use crate::models::QuestionOption;
+    use std::io::Read;
+
+    fn choice(stem: &str, options: &[&str], correct: usize) -> Question {
+        Question {
+            qtype: QuestionType::Choice,
+            stem: stem.to_string(),
+            options: options
+                .iter()
+                .enumerate()
+                .map(|(i, content)| QuestionOption {
+                    content: content.to_string(),
+                    sort_order: i as i32,
+                    is_correct: i == correct,
+                })
+                .collect(),
+            answer: Some(((b'A' + correct as u8) as char).to_string()),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(to_html("Solve $x_1 < 2$ **now**"), "Solve \\(x_1 &lt; 2\\) <strong>now</strong>");
+        assert_eq!(to_html("$$a_b$$"), "\\[a_b\\]");
+        assert_eq!(to_html("one\n\ntwo"), "<p>one</p>\n<p>two</p>");
+        assert_eq!(strip_html("Solve \\(x &lt; 2\\) <b>now</b>"), "Solve \\(x < 2\\) now");
+    }
+
+    #[tokio::test]
+    async fn test_export_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(FsMediaStore::new(dir.path()).unwrap());
+        let png = crate::media::test_png(2, 2);
+        let stored = store.put(&png).await.unwrap();
+
+        let mut with_image = choice("Which shape is shown?", &["A. Circle", "B. Square"], 1);
+        with_image.tags = vec!["geometry basics".to_string()];
+        with_image.images = vec![
+            ImageRef::Local {
+                hash: stored.hash.clone(),
+                original_path: "img/shape.png".to_string(),
+                stored_path: Some(stored.path.clone()),
+                alt: None,
+            },
+            ImageRef::Local {
+                hash: "ffff".to_string(),
+                original_path: "img/missing.png".to_string(),
+                stored_path: None,
+                alt: None,
+            },
+        ];
+        let essay = Question {
+            stem: "Describe $E = mc^2$".to_string(),
+            analysis: Some("Energy and mass are equivalent.".to_string()),
+            ..Question::default()
+        };
+
+        let package = AnkiExporter::new("Physics::Midterm")
+            .with_media_store(store)
+            .export(&[with_image.clone(), essay])
+            .await
+            .unwrap();
+        assert_eq!(package.notes, 2);
+        assert_eq!(package.media_files, 1);
+        assert_eq!(package.warnings.len(), 1);
+        assert!(package.warnings[0].contains("img/missing.png"));
+
+        let mut archive = ::zip::ZipArchive::new(Cursor::new(package.data)).unwrap();
+        let mut index = String::new();
+        archive.by_name("media").unwrap().read_to_string(&mut index).unwrap();
+        let media_name = format!("{}.png", stored.hash);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&index).unwrap(), json!({ "0": media_name }));
+        assert_eq!(archive.by_name("0").unwrap().size(), png.len() as u64);
+
+        let mut collection = Vec::new();
+        archive.by_name("collection.anki2").unwrap().read_to_end(&mut collection).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.anki2");
+        std::fs::write(&path, collection).unwrap();
+        let conn = Connection::open(&path).unwrap();
+
+        let (guid, mid, tags, flds, sfld): (String, i64, String, String, String) = conn
+            .query_row("SELECT guid, mid, tags, flds, sfld FROM notes ORDER BY id LIMIT 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })
+            .unwrap();
+        assert_eq!(guid, with_image.id.simple().to_string());
+        assert_eq!(mid, NoteType::of(QuestionType::Choice).id);
+        assert_eq!(tags, " geometry_basics ");
+        assert_eq!(sfld, "Which shape is shown?");
+        let fields: Vec<_> = flds.split('\x1f').collect();
+        assert_eq!(
+            fields,
+            [
+                format!("Which shape is shown?<div><img src=\"{}\"></div>", media_name).as_str(),
+                "<ol><li>Circle</li><li>Square</li></ol>",
+                "B. Square",
+                "",
+            ]
+        );
+
+        let essay_fields: String = conn
+            .query_row("SELECT flds FROM notes ORDER BY id DESC LIMIT 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(essay_fields, "Describe \\(E = mc^2\\)\x1f\x1fEnergy and mass are equivalent.");
+
+        let (cards, deck): (i64, i64) = conn
+            .query_row("SELECT COUNT(*), MIN(did) FROM cards", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(cards, 2);
+        let decks: String = conn.query_row("SELECT decks FROM col", [], |row| row.get(0)).unwrap();
+        let decks: serde_json::Value = serde_json::from_str(&decks).unwrap();
+        assert_eq!(decks[deck.to_string()]["name"], "Physics::Midterm");
+        let models: String = conn.query_row("SELECT models FROM col", [], |row| row.get(0)).unwrap();
+        let models: serde_json::Value = serde_json::from_str(&models).unwrap();
+        assert_eq!(models.as_object().unwrap().len(), QuestionType::ALL.len());
+    }
+}
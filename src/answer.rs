@@ -0,0 +1,443 @@
+//! Answer validation against parsed options
+//!
+//! After the parser extracts an answer, this module checks that it is
+//! consistent with the question's options: letter answers must reference
+//! existing options and true/false answers must match a binary option pair.
+//! Answers keyed into the stem ("下列正确的是（ B ）") are moved out of it when
+//! no other answer was given.
+//! Common numbering mistakes are corrected when the intent is unambiguous;
+//! everything else is reported as a warning so inconsistent answers do not
+//! flow silently into the database.
+
+use crate::models::Question;
+use std::ops::Range;
+
+/// Kind of answer problem detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnswerIssueKind {
+    /// Options exist but no answer was found
+    Missing,
+    /// The answer does not reference any valid option
+    Invalid,
+    /// The answer was rewritten to reference options by letter
+    Corrected,
+    /// Letters in the stem's parentheses were left there, because they name
+    /// no options or an answer was given as well
+    StemLetters,
+}
+
+impl AnswerIssueKind {
+    /// Warning code used in reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            AnswerIssueKind::Missing => "MISSING_ANSWER",
+            AnswerIssueKind::Invalid => "INVALID_ANSWER",
+            AnswerIssueKind::Corrected => "CORRECTED_ANSWER",
+            AnswerIssueKind::StemLetters => "STEM_LETTERS",
+        }
+    }
+}
+
+/// A problem (or correction) found for a single question
+#[derive(Debug, Clone)]
+pub struct AnswerIssue {
+    pub kind: AnswerIssueKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for AnswerIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind.code(), self.message)
+    }
+}
+
+pub(crate) const TRUE_WORDS: &[&str] = &["正确", "对", "是", "√", "✓", "t", "true", "yes", "right", "correct"];
+pub(crate) const FALSE_WORDS: &[&str] = &["错误", "错", "否", "×", "✗", "x", "f", "false", "no", "wrong", "incorrect"];
+
+/// Validate every question, returning formatted warnings
+pub fn validate_answers(questions: &mut [Question]) -> Vec<String> {
+    questions
+        .iter_mut()
+        .filter_map(|q| validate_answer(q).map(|issue| format!("{} (question: {})", issue, preview(&q.stem))))
+        .collect()
+}
+
+/// Validate (and possibly correct) a question's answer against its options
+///
+/// On success the answer is normalized to option letters (e.g. "AC") and the
+/// matching options are flagged `is_correct`. When no answer was given,
+/// option letters keyed into the stem's last parentheses ("（ B ）") are
+/// taken out of it as the answer; letters it leaves there are reported.
+pub fn validate_answer(question: &mut Question) -> Option<AnswerIssue> {
+    if question.options.is_empty() {
+        return None;
+    }
+
+    let mut stem_issue = None;
+    if let Some((blank, letters)) = stem_answer(&question.stem) {
+        let answered = question.answer.as_deref().is_some_and(|a| !a.trim().is_empty());
+        if !answered && names_options(&letters, question.options.len()) {
+            question.stem.replace_range(blank, " ");
+            question.answer = Some(letters);
+        } else {
+            let reason = if answered { "an answer is given as well" } else { "they name no options" };
+            stem_issue = Some(AnswerIssue {
+                kind: AnswerIssueKind::StemLetters,
+                message: format!("'{}' left in the stem's parentheses: {}", letters, reason),
+            });
+        }
+    }
+
+    let raw = match question.answer.as_deref().map(str::trim) {
+        Some(a) if !a.is_empty() => a.to_string(),
+        _ => {
+            return Some(AnswerIssue {
+                kind: AnswerIssueKind::Missing,
+                message: format!("{} options but no answer", question.options.len()),
+            })
+        }
+    };
+
+    let option_count = question.options.len();
+    let (indices, corrected) = match resolve(&raw, question) {
+        Some(resolved) => resolved,
+        None => {
+            return Some(AnswerIssue {
+                kind: AnswerIssueKind::Invalid,
+                message: format!("answer '{}' does not match any of {} options", raw, option_count),
+            })
+        }
+    };
+
+    let letters: String = indices.iter().map(|&i| letter(i)).collect();
+    for (idx, option) in question.options.iter_mut().enumerate() {
+        option.is_correct = indices.contains(&idx);
+    }
+    question.answer = Some(letters.clone());
+
+    if corrected {
+        Some(AnswerIssue {
+            kind: AnswerIssueKind::Corrected,
+            message: format!("answer '{}' interpreted as '{}'", raw, letters),
+        })
+    } else {
+        stem_issue
+    }
+}
+
+/// Resolve an answer to option indices; the flag reports whether it was reinterpreted
+fn resolve(raw: &str, question: &Question) -> Option<(Vec<usize>, bool)> {
+    let option_count = question.options.len();
+    let is_separator = |c: char| c.is_whitespace() || matches!(c, ',' | '，' | '、' | ';' | '；' | '.' | '。');
+    let tokens: Vec<char> = raw.chars().filter(|&c| !is_separator(c)).collect();
+
+    // Letter answers: "B", "a c", "A、C"
+    if !tokens.is_empty() && tokens.iter().all(|c| c.is_ascii_alphabetic()) && tokens.len() <= option_count {
+        let indices: Vec<usize> = tokens
+            .iter()
+            .map(|c| (c.to_ascii_uppercase() as u8 - b'A') as usize)
+            .collect();
+        if indices.iter().all(|&i| i < option_count) {
+            return Some((dedup(indices), false));
+        }
+    }
+
+    // Numeric answers: 1-based ("2" -> B), or 0-based when a 0 is present.
+    // Digits run together are one number, so "12" is the twelfth option
+    if !tokens.is_empty() && tokens.iter().all(|c| c.is_ascii_digit()) {
+        let numbers: Vec<usize> = raw
+            .split(is_separator)
+            .filter(|t| !t.is_empty())
+            .map(|t| t.parse().unwrap_or(usize::MAX))
+            .collect();
+        let indices = if numbers.contains(&0) {
+            numbers
+        } else {
+            numbers.iter().map(|n| n - 1).collect()
+        };
+        if indices.iter().all(|&i| i < option_count) {
+            return Some((dedup(indices), true));
+        }
+        return None;
+    }
+
+    // True/false words against a binary option pair
+    let lower = raw.trim().to_lowercase();
+    let truthy = TRUE_WORDS.contains(&lower.as_str());
+    let falsy = FALSE_WORDS.contains(&lower.as_str());
+    if (truthy || falsy) && option_count == 2 {
+        let words = if truthy { TRUE_WORDS } else { FALSE_WORDS };
+        let matching: Vec<usize> = question
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| {
+                let content = strip_letter(&o.content).to_lowercase();
+                words.iter().any(|w| content == *w || content.starts_with(w))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if matching.len() == 1 {
+            return Some((matching, true));
+        }
+        return None;
+    }
+
+    // Answer text equal to exactly one option's content
+    let matching: Vec<usize> = question
+        .options
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| strip_letter(&o.content).eq_ignore_ascii_case(raw.trim()))
+        .map(|(i, _)| i)
+        .collect();
+    if matching.len() == 1 {
+        return Some((matching, true));
+    }
+
+    None
+}
+
+/// Letters keyed into the stem's last parentheses ("下列正确的是（ B ）")
+///
+/// Exports that key the answer into the stem leave it where students write
+/// theirs, the blank that ends the stem. Returns the text inside the
+/// parentheses and their letters ("（ B ）", "(A、C)", "（Ｂ）" as "B", "AC",
+/// "B"); `None` when they hold anything else.
+fn stem_answer(stem: &str) -> Option<(Range<usize>, String)> {
+    let open = stem.rfind(['(', '（'])?;
+    let inner_start = open + stem[open..].chars().next().map_or(1, char::len_utf8);
+    let close = inner_start + stem[inner_start..].find([')', '）'])?;
+    // "P(A)" and "f(X)" are formulas, not answers
+    if stem[..open].chars().next_back().is_some_and(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let letters: String = stem[inner_start..close]
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, ',' | '，' | '、'))
+        .map(|c| {
+            // Full-width capitals ("Ｂ") as ASCII
+            let c = match c {
+                'Ａ'..='Ｚ' => char::from_u32(c as u32 - 'Ａ' as u32 + 'A' as u32).unwrap_or(c),
+                c => c,
+            };
+            c.is_ascii_uppercase().then_some(c)
+        })
+        .collect::<Option<_>>()?;
+    (!letters.is_empty()).then_some((inner_start..close, letters))
+}
+
+/// Whether `letters` name distinct options among `option_count`
+fn names_options(letters: &str, option_count: usize) -> bool {
+    let mut seen = vec![false; option_count];
+    letters.bytes().all(|b| {
+        let idx = usize::from(b - b'A');
+        idx < option_count && !std::mem::replace(&mut seen[idx], true)
+    })
+}
+
+fn dedup(mut indices: Vec<usize>) -> Vec<usize> {
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+/// Remove a leading "A." style marker from option text
+pub(crate) fn strip_letter(content: &str) -> &str {
+    let trimmed = content.trim();
+    let mut chars = trimmed.char_indices();
+    if let (Some((_, l)), Some((idx, sep))) = (chars.next(), chars.next()) {
+        if l.is_ascii_alphabetic() && matches!(sep, '.' | '、' | ')' | '．' | ':') {
+            return trimmed[idx + sep.len_utf8()..].trim();
+        }
+    }
+    trimmed
+}
+
+fn letter(idx: usize) -> char {
+    (b'A' + (idx % 26) as u8) as char
+}
+
+pub(crate) fn preview(stem: &str) -> String {
+    let mut preview: String = stem.chars().take(30).collect();
+    if stem.chars().count() > 30 {
+        preview.push('…');
+    }
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    fn question(options: &[&str], answer: Option<&str>) -> Question {
+        Question {
+            stem: "Question".to_string(),
+            options: options
+                .iter()
+                .enumerate()
+                .map(|(i, c)| QuestionOption {
+                    content: c.to_string(),
+                    sort_order: i as i32,
+                    is_correct: false,
+                })
+                .collect(),
+            answer: answer.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_valid_letter_answer() {
+        let mut q = question(&["A. 1", "B. 2", "C. 3"], Some("b"));
+        assert!(validate_answer(&mut q).is_none());
+        assert_eq!(q.answer.as_deref(), Some("B"));
+        assert!(q.options[1].is_correct);
+        assert!(!q.options[0].is_correct);
+    }
+
+    #[test]
+    fn test_multiple_letters() {
+        let mut q = question(&["A. 1", "B. 2", "C. 3", "D. 4"], Some("C、A"));
+        assert!(validate_answer(&mut q).is_none());
+        assert_eq!(q.answer.as_deref(), Some("AC"));
+    }
+
+    #[test]
+    fn test_answer_in_stem_parentheses() {
+        let mut q = question(&["A. 1", "B. 2", "C. 3"], None);
+        q.stem = "下列正确的是（ B ）".to_string();
+        assert!(validate_answer(&mut q).is_none());
+        assert_eq!(q.stem, "下列正确的是（ ）");
+        assert_eq!(q.answer.as_deref(), Some("B"));
+        assert!(q.options[1].is_correct);
+
+        // Formulas are skipped; several letters and full-width ones count
+        q.stem = "f(x) = P(A) 的解有 (Ａ、C)".to_string();
+        q.answer = None;
+        assert!(validate_answer(&mut q).is_none());
+        assert_eq!(q.stem, "f(x) = P(A) 的解有 ( )");
+        assert_eq!(q.answer.as_deref(), Some("AC"));
+
+        // An answer given apart from the stem is kept, and the stem too
+        q.stem = "下列正确的是（C）".to_string();
+        q.answer = Some("A".to_string());
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::StemLetters);
+        assert_eq!(q.stem, "下列正确的是（C）");
+        assert_eq!(q.answer.as_deref(), Some("A"));
+
+        // Letters that name no option, or one twice, stay in the stem
+        for stem in ["下列正确的是（ E ）", "下列正确的是（AA）"] {
+            q.stem = stem.to_string();
+            q.answer = None;
+            assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Missing);
+            assert_eq!(q.stem, stem);
+        }
+
+        // Stems of questions without options are left alone
+        let mut subjective = question(&[], None);
+        subjective.stem = "维生素（B）的作用".to_string();
+        assert!(validate_answer(&mut subjective).is_none());
+        assert_eq!(subjective.stem, "维生素（B）的作用");
+    }
+
+    #[test]
+    fn test_stem_acronyms_and_numerals_are_kept() {
+        let options = ["A. 1", "B. 2", "C. 3", "D. 4"];
+        // Only the last parentheses can hold the answer
+        let mut q = question(&options, None);
+        q.stem = "下列关于中央处理器（CPU）的说法正确的是（ ）".to_string();
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Missing);
+        assert_eq!(q.stem, "下列关于中央处理器（CPU）的说法正确的是（ ）");
+
+        let mut q = question(&options, Some("B"));
+        q.stem = "(I) 和 (II) 哪个正确".to_string();
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::StemLetters);
+        assert_eq!(q.stem, "(I) 和 (II) 哪个正确");
+
+        let mut q = question(&options, Some("B"));
+        q.stem = "中央处理器（CPU）的主频".to_string();
+        let issue = validate_answer(&mut q).unwrap();
+        assert_eq!(issue.kind, AnswerIssueKind::StemLetters);
+        assert!(issue.message.contains("'CPU'"));
+        assert_eq!(q.stem, "中央处理器（CPU）的主频");
+        assert!(q.options[1].is_correct);
+    }
+
+    #[test]
+    fn test_out_of_range_letter_is_invalid() {
+        let mut q = question(&["A. 1", "B. 2"], Some("D"));
+        let issue = validate_answer(&mut q).unwrap();
+        assert_eq!(issue.kind, AnswerIssueKind::Invalid);
+    }
+
+    #[test]
+    fn test_missing_answer() {
+        let mut q = question(&["A. 1", "B. 2"], None);
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Missing);
+
+        let mut subjective = question(&[], None);
+        assert!(validate_answer(&mut subjective).is_none());
+    }
+
+    #[test]
+    fn test_numeric_answers_corrected() {
+        let mut q = question(&["A. 1", "B. 2", "C. 3"], Some("2"));
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Corrected);
+        assert_eq!(q.answer.as_deref(), Some("B"));
+
+        let mut q = question(&["A. 1", "B. 2", "C. 3"], Some("0"));
+        validate_answer(&mut q);
+        assert_eq!(q.answer.as_deref(), Some("A"));
+
+        let mut q = question(&["A. 1", "B. 2"], Some("5"));
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Invalid);
+    }
+
+    #[test]
+    fn test_run_together_digits_are_one_number() {
+        let options = ["A. 1", "B. 2", "C. 3", "D. 4"];
+        for raw in ["12", "10", "99999999999999999999999"] {
+            let mut q = question(&options, Some(raw));
+            assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Invalid, "{}", raw);
+            assert!(q.options.iter().all(|o| !o.is_correct));
+        }
+
+        let mut q = question(&options, Some("1, 2"));
+        validate_answer(&mut q);
+        assert_eq!(q.answer.as_deref(), Some("AB"));
+
+        let options: Vec<String> = (1..=12).map(|n| format!("{}. {}", letter(n - 1), n)).collect();
+        let options: Vec<&str> = options.iter().map(String::as_str).collect();
+        let mut q = question(&options, Some("10"));
+        validate_answer(&mut q);
+        assert_eq!(q.answer.as_deref(), Some("J"));
+    }
+
+    #[test]
+    fn test_true_false_answers() {
+        let mut q = question(&["A. 正确", "B. 错误"], Some("错"));
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Corrected);
+        assert_eq!(q.answer.as_deref(), Some("B"));
+        assert!(q.options[1].is_correct);
+
+        let mut q = question(&["A. 1", "B. 2", "C. 3"], Some("√"));
+        assert_eq!(validate_answer(&mut q).unwrap().kind, AnswerIssueKind::Invalid);
+    }
+
+    #[test]
+    fn test_answer_matching_option_text() {
+        let mut q = question(&["A. Paris", "B. London"], Some("paris"));
+        validate_answer(&mut q);
+        assert_eq!(q.answer.as_deref(), Some("A"));
+    }
+
+    #[test]
+    fn test_validate_answers_formats_warnings() {
+        let mut questions = vec![question(&["A. 1"], None), question(&["A. 1"], Some("A"))];
+        let warnings = validate_answers(&mut questions);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("MISSING_ANSWER"));
+    }
+}
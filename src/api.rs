@@ -2,22 +2,82 @@
 //!
 //! This module provides REST API endpoints using Axum.
 
-use crate::database::QuestionRepository;
-use crate::models::Question;
-use crate::parser::parse_markdown;
-use crate::zip::ZipProcessor;
+#[cfg(feature = "anki")]
+use crate::anki::{self, AnkiExporter};
+use crate::answer::{validate_answer, AnswerIssueKind};
+use crate::archive::{supported_extensions, ArchiveFormat};
+use crate::body_limit::{check_body, BodyKind};
+use crate::classifier::{apply_classification, Language};
+use crate::consistency::{ConsistencyChecker, ConsistencyReport};
+use crate::database::{ListParams, Page, QuestionRepository, MAX_PAGE_SIZE};
+use crate::dedup::{DedupOptions, SaveOutcome};
+use crate::detect::FormatDetector;
+use crate::error::Md2DbError;
+use crate::export::{export, ExportFormat, ExportPages};
+#[cfg(feature = "latex-render")]
+use crate::formula::{FormulaFormat, FormulaRenderer, RenderedFormula};
+use crate::idempotency::{idempotent, IdempotencyStore};
+#[cfg(feature = "docx")]
+use crate::docx::{DocxWriter, PaperLayout};
+use crate::gift::write_gift;
+use crate::import::{ArchiveReport, ImportJob, ImportReport};
+use crate::jobs::{JobEvent, JobManager, JobStatus};
+use crate::jsonl::{export_jsonl, read_jsonl, ExportFilter};
+use crate::markdown::MarkdownRenderer;
+use crate::media::{media_type, MediaStore};
+use crate::ocr::FormulaOcr;
+use crate::models::{ClassificationResult, Question, QuestionBank, QuestionPatch, QuestionRevision, QuestionType, TypeSource};
+use crate::parser::{parse_markdown_with_options, ParserOptions};
+use crate::render::{render, RenderFormat};
+use crate::report::{render_job_report, QuestionBreakdown, ReportFormat};
+use crate::typeset::{self, ExportLocale, TypesetOptions};
+use crate::validate::QuestionValidator;
+use crate::presets;
+use crate::preview::{preview_markdown, Preview};
+use crate::stats::QuestionStats;
+use crate::search::{SearchHit, SearchQuery, SearchResults};
+use crate::similar::{SimilarQuestion, SimilarityIndex};
+use crate::processor::{
+    InputSource, NoProgress, ProcessResult, ProcessStage, ProgressReporter, SingleMachineProcessor,
+};
+#[cfg(any(feature = "csv", feature = "xlsx"))]
+use crate::tabular::{self, ColumnMapping, TableFormat, TabularImporter};
+use crate::zip::{SpillConfig, ZipFileResult, ZipLimits, ZipProcessor};
 use axum::{
     body::Body,
-    extract::{Multipart, State},
-    http::{header, StatusCode},
+    extract::{
+        multipart::MultipartError,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        DefaultBodyLimit, Extension, Multipart, Path, Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::{IntoResponse, Json, Response},
-    routing::{get, post},
+    routing::{get, post, MethodRouter},
     Router,
 };
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use tokio_util::io::{ReaderStream, StreamReader};
 use uuid::Uuid;
 
+/// Questions returned by `/questions/:id/similar` when no limit is given
+const DEFAULT_SIMILAR_LIMIT: usize = 10;
+
+/// Matches returned by `/search` when no limit is given
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// Archives extracted concurrently per `/parse-zip` request
+const MAX_CONCURRENT_ZIPS: usize = 4;
+
+/// Longest `/health` waits for the database to answer
+const HEALTH_PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Request header naming who made an edit, recorded in question history
+const ACTOR_HEADER: &str = "x-actor";
+
 /// API error type
 #[derive(Debug)]
 pub enum ApiError {
@@ -25,6 +85,13 @@ pub enum ApiError {
     DatabaseError(String),
     InvalidFile(String),
     MultipartError(String),
+    NotFound(String),
+    Conflict(String),
+    Unauthorized(String),
+    RateLimited(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    Unprocessable(String),
 }
 
 impl IntoResponse for ApiError {
@@ -34,6 +101,13 @@ impl IntoResponse for ApiError {
             ApiError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, "database_error", msg),
             ApiError::InvalidFile(msg) => (StatusCode::BAD_REQUEST, "invalid_file", msg),
             ApiError::MultipartError(msg) => (StatusCode::BAD_REQUEST, "multipart_error", msg),
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, "conflict", msg),
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, "unauthorized", msg),
+            ApiError::RateLimited(msg) => (StatusCode::TOO_MANY_REQUESTS, "rate_limited", msg),
+            ApiError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", msg),
+            ApiError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", msg),
+            ApiError::Unprocessable(msg) => (StatusCode::UNPROCESSABLE_ENTITY, "validation_error", msg),
         };
 
         let body = Json(serde_json::json!({
@@ -45,16 +119,134 @@ impl IntoResponse for ApiError {
     }
 }
 
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::ParseError(msg)
+            | ApiError::DatabaseError(msg)
+            | ApiError::InvalidFile(msg)
+            | ApiError::MultipartError(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::Conflict(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::RateLimited(msg)
+            | ApiError::PayloadTooLarge(msg)
+            | ApiError::UnsupportedMediaType(msg)
+            | ApiError::Unprocessable(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
         ApiError::ParseError(err.to_string())
     }
 }
 
+impl From<Md2DbError> for ApiError {
+    fn from(err: Md2DbError) -> Self {
+        match err {
+            Md2DbError::ParseError { .. } => ApiError::ParseError(err.to_string()),
+            Md2DbError::ZipError { .. } => ApiError::InvalidFile(err.to_string()),
+            Md2DbError::ValidationError { .. } => ApiError::Unprocessable(err.to_string()),
+            Md2DbError::MediaError { .. } | Md2DbError::RepositoryError { .. } => {
+                ApiError::DatabaseError(err.to_string())
+            }
+        }
+    }
+}
+
 /// Parse request for single markdown file
 #[derive(Debug, Deserialize)]
 pub struct ParseRequest {
     pub markdown: String,
+    /// Skip questions whose content is already stored
+    #[serde(default, alias = "dedupe")]
+    pub dedup: bool,
+    /// Parser preset for the input format (see [`crate::presets`])
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Question bank to import into
+    #[serde(default)]
+    pub bank_id: Option<Uuid>,
+    /// Set each question's type with the classifier
+    #[serde(default)]
+    pub classify: bool,
+    /// Language of the classifier keywords (`zh` or `en`; both by default)
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Type of questions the classifier cannot place, or when not
+    /// classifying of every question outside a typed section
+    #[serde(default)]
+    pub default_type: Option<QuestionType>,
+    /// Parse without saving, like `?dry_run=true`
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Request for a live preview of Markdown
+#[derive(Debug, Deserialize)]
+pub struct PreviewRequest {
+    pub markdown: String,
+    /// Parser preset for the input format (see [`crate::presets`])
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Language of the classifier keywords (`zh` or `en`; both by default)
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Type of questions the classifier cannot place
+    #[serde(default)]
+    pub default_type: Option<QuestionType>,
+}
+
+/// Questions to audit with `POST /validate`; every stored question when
+/// nothing is named
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ValidateRequest {
+    /// Questions to check as given, such as those returned by a dry run
+    pub questions: Vec<Question>,
+    /// Stored questions to check
+    pub question_ids: Vec<Uuid>,
+    /// Check the questions in this bank
+    pub bank_id: Option<Uuid>,
+    /// Check the questions with this tag
+    pub tag: Option<String>,
+}
+
+/// Consistency report of `POST /validate`
+#[derive(Debug, Serialize)]
+pub struct ValidateResponse {
+    /// Number of questions checked
+    pub checked: usize,
+    /// Number of questions with at least one issue
+    pub inconsistent: usize,
+    /// One report per question checked, in order
+    pub reports: Vec<ConsistencyReport>,
+}
+
+/// Request to create a question bank
+#[derive(Debug, Deserialize)]
+pub struct CreateBankRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Request to move stored questions into a bank
+#[derive(Debug, Deserialize)]
+pub struct AssignBankRequest {
+    pub question_ids: Vec<Uuid>,
+}
+
+/// Response after assigning questions to a bank
+#[derive(Debug, Serialize)]
+pub struct AssignBankResponse {
+    pub assigned: u64,
 }
 
 /// Response after parsing
@@ -64,23 +256,200 @@ pub struct ParseResponse {
     pub question_ids: Vec<Uuid>,
     pub questions: Vec<Question>,
     pub warnings: Vec<String>,
+    /// Per-question save outcomes (only when dedup was requested)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub outcomes: Vec<SaveOutcome>,
+    /// Import session the questions were committed in (absent on a dry run)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Uuid>,
+    /// Whether this was a dry run that saved nothing
+    pub dry_run: bool,
+    /// Detected type and confidence per question (only when classifying)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub classifications: Vec<QuestionClassification>,
 }
 
-/// ZIP parse response
+/// Classifier result for one parsed question
 #[derive(Debug, Serialize)]
+pub struct QuestionClassification {
+    pub question_id: Uuid,
+    #[serde(flatten)]
+    pub result: ClassificationResult,
+}
+
+/// ZIP parse response
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ParseZipResponse {
     pub count: usize,
     pub question_ids: Vec<Uuid>,
     pub questions: Vec<Question>,
     pub images_processed: usize,
+    pub files: Vec<ZipFileResult>,
+    pub archives: Vec<ArchiveReport>,
+    pub duplicates_skipped: usize,
     pub warnings: Vec<String>,
+    /// Per-question save outcomes (only when dedup was requested)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outcomes: Vec<SaveOutcome>,
+    /// Import session the questions were committed in (absent on a dry run)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Uuid>,
+    /// Whether this was a dry run that saved nothing
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Background import jobs run by the server
+pub type ImportJobs = JobManager<Arc<dyn QuestionRepository>>;
+
+/// Response to a queued background import
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubmittedImport {
+    pub job_id: Uuid,
+}
+
+/// Status of a background import, without its result
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportStatus {
+    pub id: Uuid,
+    pub status: JobStatus,
+    pub sources: Vec<String>,
+    /// Stage of a running import
+    pub stage: Option<ProcessStage>,
+    /// Estimated completion, from 0 to 100
+    pub progress: f64,
+    /// Number of questions saved (completed imports only)
+    pub count: Option<usize>,
+    /// Why a failed import failed
+    pub error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Query parameters for import reports
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    /// Output format (`markdown` or `html`, default `markdown`)
+    pub format: Option<String>,
+}
+
+/// Query parameters for question rendering
+#[derive(Debug, Deserialize)]
+pub struct RenderQuery {
+    /// Output format (currently only `text`)
+    pub format: Option<String>,
+    /// Apply locale typesetting rules (`zh` or `en`) before rendering
+    pub locale: Option<String>,
+}
+
+/// Query parameters for formula rendering
+#[cfg(feature = "latex-render")]
+#[derive(Debug, Deserialize)]
+pub struct FormulaQuery {
+    /// Output format (`mathml` or `svg`, default `mathml`)
+    pub format: Option<String>,
+}
+
+/// Query parameters of [`search_endpoint`]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchParams {
+    /// Search terms, separated by spaces
+    pub q: String,
+    /// Only questions in this bank
+    pub bank_id: Option<Uuid>,
+    /// Only questions with this tag (ignored when `bank_id` is given)
+    pub tag: Option<String>,
+    /// Only questions of this type
+    #[serde(rename = "type")]
+    pub qtype: Option<QuestionType>,
+    /// Maximum number of matches (defaults to 20, capped like list pages)
+    pub limit: Option<usize>,
+}
+
+/// Query parameters of [`similar_questions_endpoint`]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SimilarParams {
+    /// Number of questions to return (defaults to 10, capped like list pages)
+    pub limit: Option<usize>,
+    /// Only compare with questions in this bank
+    pub bank_id: Option<Uuid>,
+    /// Only compare with questions of this type
+    #[serde(rename = "type")]
+    pub qtype: Option<QuestionType>,
+}
+
+/// Query parameters selecting the questions to export
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    /// Only questions in this bank
+    pub bank_id: Option<Uuid>,
+    /// Only questions with this tag (ignored when `bank_id` is given)
+    pub tag: Option<String>,
+    /// Anki deck name (defaults to the bank's name)
+    pub deck: Option<String>,
+}
+
+/// Query parameters of [`export_endpoint`]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BulkExportQuery {
+    /// `jsonl`, `gift`, `qti` or `xlsx`; negotiated from `Accept` when absent
+    pub format: Option<String>,
+    /// Which questions to export
+    #[serde(flatten)]
+    pub filter: ExportFilter,
+}
+
+/// Request body for a Word exam paper
+#[cfg(feature = "docx")]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportDocxRequest {
+    /// Questions in paper order; when empty, `bank_id` or `tag` selects them
+    pub question_ids: Vec<Uuid>,
+    /// Only questions in this bank
+    pub bank_id: Option<Uuid>,
+    /// Only questions with this tag (ignored when `bank_id` is given)
+    pub tag: Option<String>,
+    /// Title, answer key and text size (the title defaults to the bank's name)
+    pub layout: PaperLayout,
+}
+
+/// Query parameters shared by the upload import endpoints
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ImportQuery {
+    /// Parse and check everything but save nothing
+    pub dry_run: bool,
+}
+
+/// Query parameters for JSON Lines import
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ImportJsonlQuery {
+    /// Skip questions whose content is already stored
+    pub dedup: bool,
+    /// Question bank to import into, replacing the banks in the file
+    pub bank_id: Option<Uuid>,
+    /// Keep the question IDs from the file instead of assigning new ones
+    pub keep_ids: bool,
+    /// Parse and check everything but save nothing
+    pub dry_run: bool,
 }
 
 /// Health check response
 #[derive(Debug, Serialize)]
 pub struct HealthResponse {
+    /// "ok" when the database is reachable, "unavailable" otherwise
     pub status: String,
     pub version: String,
+    /// Database readiness ("ok" or "unavailable")
+    pub database: String,
+    /// Why the database check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 /// Error response structure
@@ -92,125 +461,1982 @@ pub struct ErrorResponse {
 
 /// Create the API router
 pub fn create_router() -> Router<Arc<dyn QuestionRepository>> {
-    Router::new()
-        .route("/parse", post(parse_markdown_endpoint))
-        .route("/parse-zip", post(parse_zip_endpoint))
+    let imports = Arc::new(IdempotencyStore::new());
+    let router = Router::new()
+        .route("/parse", with_body(BodyKind::Json, with_idempotency(&imports, post(parse_markdown_endpoint))))
+        .route(
+            "/parse-archive",
+            with_body(BodyKind::Multipart, with_idempotency(&imports, post(parse_archive_endpoint))),
+        )
+        // Kept for clients written before other archive formats were accepted
+        .route(
+            "/parse-zip",
+            with_body(BodyKind::Multipart, with_idempotency(&imports, post(parse_archive_endpoint))),
+        )
+        .route("/preview", with_body(BodyKind::Json, post(preview_endpoint)))
+        .route("/validate", with_body(BodyKind::Json, post(validate_endpoint)))
+        .route("/parse-files", with_body(BodyKind::Multipart, post(parse_files_endpoint)))
+        .route("/import", with_body(BodyKind::Multipart, post(import_endpoint)))
+        .route(
+            "/imports",
+            with_body(BodyKind::Multipart, with_idempotency(&imports, post(submit_import_endpoint))),
+        )
+        .route("/imports/:id", get(import_status_endpoint))
+        .route("/imports/:id/result", get(import_result_endpoint))
+        .route("/imports/:id/report", get(import_report_endpoint))
+        .route("/imports/:id/ws", get(import_events_endpoint));
+    #[cfg(feature = "docx")]
+    let router = router.route("/parse-docx", with_body(BodyKind::Multipart, post(parse_docx_endpoint)));
+    #[cfg(feature = "pdf")]
+    let router = router.route("/parse-pdf", with_body(BodyKind::Multipart, post(parse_pdf_endpoint)));
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    let router = router.route("/import/table", with_body(BodyKind::Multipart, post(import_table_endpoint)));
+    #[cfg(feature = "docx")]
+    let router = router.route("/export/docx", with_body(BodyKind::Json, post(export_docx_endpoint)));
+    #[cfg(feature = "xlsx")]
+    let router = router.route("/export/xlsx", get(export_xlsx_endpoint));
+    #[cfg(feature = "anki")]
+    let router = router.route("/export/anki", get(export_anki_endpoint));
+    #[cfg(feature = "latex-render")]
+    let router = router.route("/questions/:id/formulas", get(question_formulas_endpoint));
+    router
+        .route("/questions", get(list_questions_endpoint))
+        .route(
+            "/questions/:id",
+            with_body(
+                BodyKind::Json,
+                get(get_question_endpoint)
+                    .put(update_question_endpoint)
+                    .patch(patch_question_endpoint)
+                    .delete(delete_question_endpoint),
+            ),
+        )
+        .route("/questions/:id/history", get(question_history_endpoint))
+        .route("/questions/:id/restore", post(restore_question_endpoint))
+        .route("/questions/:id/render", get(render_question_endpoint))
+        .route("/questions/:id/markdown", get(question_markdown_endpoint))
+        .route("/questions/:id/similar", get(similar_questions_endpoint))
+        .route("/banks", with_body(BodyKind::Json, get(list_banks_endpoint).post(create_bank_endpoint)))
+        .route("/banks/:id", get(get_bank_endpoint))
+        .route(
+            "/banks/:id/questions",
+            with_body(BodyKind::Json, get(bank_questions_endpoint).post(assign_bank_endpoint)),
+        )
+        .route("/export/gift", get(export_gift_endpoint))
+        .route("/export/markdown", get(export_markdown_endpoint))
+        .route("/export", get(export_endpoint))
+        .route("/export/jsonl", get(export_jsonl_endpoint))
+        .route("/import/jsonl", with_body(BodyKind::JsonLines, post(import_jsonl_endpoint)))
+        .route("/media/:hash", get(media_endpoint))
+        .route("/stats", get(stats_endpoint))
+        .route("/search", get(search_endpoint))
         .route("/health", get(health_check))
         .route("/", get(root_handler))
 }
 
+/// Replay `route`'s responses for repeated idempotency keys (see [`crate::idempotency`])
+fn with_idempotency(
+    store: &Arc<IdempotencyStore>,
+    route: MethodRouter<Arc<dyn QuestionRepository>>,
+) -> MethodRouter<Arc<dyn QuestionRepository>> {
+    route.layer(middleware::from_fn_with_state(store.clone(), idempotent))
+}
+
+/// Check the body of `route` as `kind` (see [`crate::body_limit`])
+fn with_body(kind: BodyKind, route: MethodRouter<Arc<dyn QuestionRepository>>) -> MethodRouter<Arc<dyn QuestionRepository>> {
+    // check_body applies the configured cap instead of axum's default
+    route
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::from_fn_with_state(kind, check_body))
+}
+
+/// Endpoints listed by [`root_handler`], with what each does
+const ENDPOINTS: &[(&str, &str)] = &[
+    ("POST /parse", "Parse a single markdown text (dedup=true skips stored questions, preset selects the input format, bank_id picks the question bank, classify/language/default_type set question types; dry_run=true parses without saving)"),
+    ("POST /preview", "Parse markdown without saving, returning each question with its source lines, classification and diagnostics (preset, language, default_type)"),
+    ("POST /validate", "Check questions for answers that do not fit their type (questions, question_ids, bank_id or tag; every stored question by default), one report per question"),
+    ("POST /parse-archive", "Parse one or more archives (ZIP, tar.gz, 7z, RAR) containing markdown files as one import (dedup, preset, bank_id, strip_metadata; ?dry_run=true parses without saving); also served at /parse-zip"),
+    ("POST /parse-files", "Parse several uploaded Markdown files as one import (file fields, dedup, preset, bank_id; ?dry_run=true parses without saving)"),
+    ("POST /parse-docx", "Parse one or more Word (.docx) documents as one import (same fields as /parse-archive)"),
+    ("POST /parse-pdf", "Parse the text layer of one or more PDFs as one import (same fields as /parse-archive; scanned pages are reported as warnings)"),
+    ("POST /import", "Import any supported file, recognized by its content: archive, Word, PDF, Markdown, text, GIFT or spreadsheet (fields of /parse-archive, plus mapping and sheet)"),
+    ("POST /imports", "Queue an import in the background and return its job_id at once (fields of /import; ?dry_run=true)"),
+    ("GET /imports/:id", "Status, stage and progress of a background import"),
+    ("GET /imports/:id/ws", "WebSocket streaming a background import's progress as JSON events until it finishes"),
+    ("GET /imports/:id/result", "Result of a completed background import, as /parse-archive returns it"),
+    ("GET /imports/:id/report", "Report of a finished background import: counts per type, grouped warnings, low-confidence and rejected questions (?format=markdown|html)"),
+    ("POST /import/table", "Import a CSV/TSV or Excel spreadsheet with one question per row (file, mapping as JSON, sheet, dedup, bank_id; ?dry_run=true)"),
+    ("GET /questions", "List stored questions (type, limit, offset, cursor, order)"),
+    ("GET /questions/:id", "Fetch a stored question"),
+    ("PUT /questions/:id", "Replace a stored question (X-Actor header is recorded in its history)"),
+    ("PATCH /questions/:id", "Change some fields of a stored question (stem, type, options, answer, analysis, tags), checked against the rules of its type"),
+    ("GET /questions/:id/history", "Prior versions of a stored question, newest first"),
+    ("DELETE /questions/:id", "Delete a stored question (restorable until purged)"),
+    ("POST /questions/:id/restore", "Restore a deleted question"),
+    ("GET /questions/:id/render", "Render a stored question (format=text, locale=zh|en)"),
+    ("GET /questions/:id/markdown", "A stored question as Markdown that can be edited and parsed again"),
+    ("GET /questions/:id/formulas", "A stored question's formulas rendered as MathML or SVG (format=mathml|svg), kept in the media store when one is configured"),
+    ("GET /questions/:id/similar", "Stored questions whose stems are most similar to this one (TF-IDF over stem bigrams), most similar first (limit, type, bank_id)"),
+    ("POST /banks", "Create a question bank"),
+    ("GET /banks", "List question banks"),
+    ("GET /banks/:id", "Get a question bank"),
+    ("GET /banks/:id/questions", "List the questions in a bank"),
+    ("POST /banks/:id/questions", "Move stored questions into a bank"),
+    ("GET /export/gift", "Export stored questions as Moodle GIFT (bank_id or tag to narrow)"),
+    ("GET /export/markdown", "Export stored questions as a ZIP of Markdown and images for editing and re-import (bank_id or tag to narrow)"),
+    ("GET /export", "Stream stored questions as jsonl, gift, qti or xlsx (format, or negotiated from Accept; type, bank_id or tag to narrow)"),
+    ("GET /export/jsonl", "Stream stored questions as JSON Lines, one question per line (bank_id or tag to narrow)"),
+    ("POST /import/jsonl", "Import questions from a JSON Lines body (dedup, bank_id, keep_ids, dry_run)"),
+    ("POST /export/docx", "Export questions as a printable Word exam paper (question_ids, or bank_id or tag; layout with title, instructions, answer_key, include_analysis, answer_lines, font_size)"),
+    ("GET /export/xlsx", "Export stored questions as an Excel workbook for review, with detected type and confidence (bank_id or tag to narrow)"),
+    ("GET /export/anki", "Export stored questions as an Anki package (bank_id or tag to narrow, deck names the deck)"),
+    ("GET /media/:hash", "Serve a stored image by its SHA-256 hash, with an ETag and long-lived cache headers"),
+    ("GET /search", "Full-text search of stored questions, most relevant first (q, type, bank_id or tag to narrow, limit); Latin terms also match Chinese text by its pinyin in builds with the pinyin feature"),
+    ("GET /stats", "Counts of stored questions by type, bank, needs_review and language, with imports per day"),
+    ("GET /health", "Health check endpoint (503 when the database is unreachable)"),
+];
+
 /// Root handler with API information
 pub async fn root_handler() -> Json<serde_json::Value> {
+    let endpoints: serde_json::Map<String, serde_json::Value> = ENDPOINTS
+        .iter()
+        .map(|(route, description)| (route.to_string(), (*description).into()))
+        .collect();
     Json(serde_json::json!({
         "name": "MD2DB API",
         "version": env!("CARGO_PKG_VERSION"),
         "description": "Markdown to Database converter - High performance Rust implementation",
-        "endpoints": {
-            "POST /parse": "Parse a single markdown text",
-            "POST /parse-zip": "Parse a ZIP file containing markdown files",
-            "GET /health": "Health check endpoint",
-        }
+        "endpoints": endpoints,
     }))
 }
 
 /// Parse markdown endpoint
 pub async fn parse_markdown_endpoint(
     State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(query): Query<ImportQuery>,
     Json(req): Json<ParseRequest>,
 ) -> Result<Json<ParseResponse>, ApiError> {
-    let questions = parse_markdown(&req.markdown)?;
+    let options = match &req.preset {
+        Some(name) => presets::preset(name)?,
+        None => ParserOptions::default(),
+    };
+    let language = req.language.as_deref().map(str::parse::<Language>).transpose()?;
+    let mut questions = parse_markdown_with_options(&req.markdown, &options)?;
+    let classifications = if req.classify {
+        classify_questions(&mut questions, language, req.default_type)
+    } else {
+        if let Some(qtype) = req.default_type {
+            for question in questions.iter_mut().filter(|q| q.type_source.is_none()) {
+                question.qtype = qtype;
+                question.type_source = Some(TypeSource::Default);
+            }
+        }
+        Vec::new()
+    };
+    assign_bank(repo.as_ref(), req.bank_id, &mut questions).await?;
 
-    let ids = repo.save_batch(&questions).await
-        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+    let saved = save_questions(repo.as_ref(), &questions, req.dedup, query.dry_run || req.dry_run).await?;
 
     Ok(Json(ParseResponse {
-        count: ids.len(),
-        question_ids: ids,
+        count: saved.ids.len(),
+        question_ids: saved.ids,
         questions,
         warnings: Vec::new(),
+        outcomes: saved.outcomes,
+        session_id: saved.session_id,
+        dry_run: saved.dry_run,
+        classifications,
     }))
 }
 
-/// Parse ZIP endpoint - handles multipart file upload
-pub async fn parse_zip_endpoint(
+/// Live preview endpoint for editors; never saves
+pub async fn preview_endpoint(Json(req): Json<PreviewRequest>) -> Result<Json<Preview>, ApiError> {
+    let options = match &req.preset {
+        Some(name) => presets::preset(name)?,
+        None => ParserOptions::default(),
+    };
+    let language = req.language.as_deref().map(str::parse::<Language>).transpose()?;
+    Ok(Json(preview_markdown(&req.markdown, &options, language, req.default_type)?))
+}
+
+/// Audit questions for answers inconsistent with their type; never saves
+pub async fn validate_endpoint(
     State(repo): State<Arc<dyn QuestionRepository>>,
+    Json(req): Json<ValidateRequest>,
+) -> Result<Json<ValidateResponse>, ApiError> {
+    let mut questions = req.questions;
+    let database = |e: Md2DbError| ApiError::DatabaseError(e.to_string());
+    for id in &req.question_ids {
+        let question = repo.find_by_id(*id).await.map_err(database)?;
+        questions.push(question.ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?);
+    }
+    if let Some(bank_id) = req.bank_id {
+        require_bank(repo.as_ref(), bank_id).await?;
+        questions.extend(repo.find_by_bank(bank_id).await.map_err(database)?);
+    }
+    if let Some(tag) = &req.tag {
+        questions.extend(repo.find_by_tag(tag).await.map_err(database)?);
+    }
+    let selected = !req.question_ids.is_empty() || req.bank_id.is_some() || req.tag.is_some();
+    if questions.is_empty() && !selected {
+        questions = all_questions(repo.as_ref()).await.map_err(database)?;
+    }
+
+    let reports = ConsistencyChecker::new().audit(&questions);
+    Ok(Json(ValidateResponse {
+        checked: reports.len(),
+        inconsistent: reports.iter().filter(|r| !r.is_consistent()).count(),
+        reports,
+    }))
+}
+
+/// Set each question's type from the classifier
+///
+/// Questions it cannot place get `default_type`, or keep their type, and
+/// are reported with zero confidence.
+fn classify_questions(
+    questions: &mut [Question],
+    language: Option<Language>,
+    default_type: Option<QuestionType>,
+) -> Vec<QuestionClassification> {
+    questions
+        .iter_mut()
+        .map(|question| QuestionClassification {
+            result: apply_classification(question, language, default_type),
+            question_id: question.id,
+        })
+        .collect()
+}
+
+/// Questions committed by [`save_questions`]
+struct SavedQuestions {
+    /// IDs of the records written, or that would be on a dry run
+    ids: Vec<Uuid>,
+    /// One outcome per question (dedup only)
+    outcomes: Vec<SaveOutcome>,
+    /// Import session the questions were committed in
+    session_id: Option<Uuid>,
+    /// Whether nothing was saved
+    dry_run: bool,
+}
+
+/// Error for a failed multipart read, answering 413 when the body was over its cap
+fn multipart_error(context: impl std::fmt::Display) -> impl FnOnce(MultipartError) -> ApiError {
+    move |e| {
+        let message = format!("{}: {}", context, e);
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            ApiError::PayloadTooLarge(message)
+        } else {
+            ApiError::MultipartError(message)
+        }
+    }
+}
+
+/// Look up a question bank, answering 404 if it does not exist
+async fn require_bank(repo: &dyn QuestionRepository, id: Uuid) -> Result<QuestionBank, ApiError> {
+    repo.find_bank(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Question bank {} not found", id)))
+}
+
+/// Put imported questions into `bank_id`, which must exist
+async fn assign_bank(
+    repo: &dyn QuestionRepository,
+    bank_id: Option<Uuid>,
+    questions: &mut [Question],
+) -> Result<(), ApiError> {
+    let Some(bank_id) = bank_id else {
+        return Ok(());
+    };
+    require_bank(repo, bank_id).await?;
+
+    for question in questions {
+        question.bank_id = Some(bank_id);
+    }
+    Ok(())
+}
+
+/// Find the question bank called `name`, creating it if there is none
+///
+/// On a dry run a missing bank is not created and `None` is returned.
+async fn bank_named(repo: &dyn QuestionRepository, name: &str, dry_run: bool) -> Result<Option<Uuid>, ApiError> {
+    let banks = repo.list_banks().await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+    if let Some(bank) = banks.iter().find(|b| b.name == name) {
+        return Ok(Some(bank.id));
+    }
+    if dry_run {
+        return Ok(None);
+    }
+    repo.create_bank(&QuestionBank::new(name)).await
+        .map(Some)
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))
+}
+
+/// Save parsed questions in one import session, optionally skipping those already stored
+///
+/// Either every question is committed or, on error, the session is rolled
+/// back and nothing is stored. A dry run opens no session and reports the
+/// IDs the questions would be saved under.
+async fn save_questions(
+    repo: &dyn QuestionRepository,
+    questions: &[Question],
+    dedup: bool,
+    dry_run: bool,
+) -> Result<SavedQuestions, ApiError> {
+    if dry_run {
+        return Ok(SavedQuestions {
+            ids: questions.iter().map(|q| q.id).collect(),
+            outcomes: Vec::new(),
+            session_id: None,
+            dry_run,
+        });
+    }
+
+    let mut session = repo.begin_import().await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+    let session_id = session.id();
+
+    let saved = if dedup {
+        session.save_batch_dedup(questions, &DedupOptions::default()).await.map(|outcomes| {
+            let ids = outcomes
+                .iter()
+                .filter(|o| o.is_written())
+                .map(SaveOutcome::stored_id)
+                .collect();
+            (ids, outcomes)
+        })
+    } else {
+        session.save_batch(questions).await.map(|ids| (ids, Vec::new()))
+    };
+
+    let (ids, outcomes) = match saved {
+        Ok(saved) => saved,
+        Err(e) => {
+            if let Err(rollback) = session.rollback().await {
+                tracing::warn!("Failed to roll back import session {}: {}", session_id, rollback);
+            }
+            return Err(ApiError::DatabaseError(format!(
+                "Import session {} rolled back: {}", session_id, e
+            )));
+        }
+    };
+    session.commit().await
+        .map_err(|e| ApiError::DatabaseError(format!("Import session {} failed to commit: {}", session_id, e)))?;
+
+    Ok(SavedQuestions {
+        ids,
+        outcomes,
+        session_id: Some(session_id),
+        dry_run,
+    })
+}
+
+/// Parse archive endpoint - handles multipart file upload
+///
+/// Several `file`/`archive`/`zip` fields may be sent in one request; they are
+/// imported as a single job with cross-archive dedup and one consolidated
+/// report.
+pub async fn parse_archive_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    formula_ocr: Option<Extension<Arc<dyn FormulaOcr>>>,
+    spill: Option<Extension<SpillConfig>>,
+    limits: Option<Extension<ZipLimits>>,
+    Query(query): Query<ImportQuery>,
+    multipart: Multipart,
+) -> Result<Json<ParseZipResponse>, ApiError> {
+    let upload = Upload {
+        kind: "archive",
+        accepts: |name| ArchiveFormat::from_name(name).is_some_and(ArchiveFormat::is_enabled),
+        expected: supported_extensions().join(", "),
+        options: ParserOptions::default(),
+        detect: false,
+        dry_run: query.dry_run,
+    };
+    import_uploads(repo, media_store, formula_ocr, spill, limits, multipart, upload).await
+}
+
+/// Parse Word document endpoint - handles multipart `.docx` upload
+///
+/// Accepts the same fields as `/parse-archive`; several documents may be
+/// sent in one request. Without a `preset`, numbered questions are expected
+/// (see [`crate::docx::default_options`]).
+#[cfg(feature = "docx")]
+pub async fn parse_docx_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    formula_ocr: Option<Extension<Arc<dyn FormulaOcr>>>,
+    spill: Option<Extension<SpillConfig>>,
+    limits: Option<Extension<ZipLimits>>,
+    Query(query): Query<ImportQuery>,
+    multipart: Multipart,
+) -> Result<Json<ParseZipResponse>, ApiError> {
+    let upload = Upload {
+        kind: "document",
+        accepts: |name| name.to_lowercase().ends_with(".docx"),
+        expected: ".docx".to_string(),
+        options: crate::docx::default_options(),
+        detect: false,
+        dry_run: query.dry_run,
+    };
+    import_uploads(repo, media_store, formula_ocr, spill, limits, multipart, upload).await
+}
+
+/// Parse PDF endpoint - handles multipart `.pdf` upload
+///
+/// Only the text layer is read; pages that are scanned images come back as
+/// warnings. Without a `preset`, numbered questions are expected (see
+/// [`crate::pdf::default_options`]).
+#[cfg(feature = "pdf")]
+pub async fn parse_pdf_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    formula_ocr: Option<Extension<Arc<dyn FormulaOcr>>>,
+    spill: Option<Extension<SpillConfig>>,
+    limits: Option<Extension<ZipLimits>>,
+    Query(query): Query<ImportQuery>,
+    multipart: Multipart,
+) -> Result<Json<ParseZipResponse>, ApiError> {
+    let upload = Upload {
+        kind: "PDF",
+        accepts: |name| name.to_lowercase().ends_with(".pdf"),
+        expected: ".pdf".to_string(),
+        options: crate::pdf::default_options(),
+        detect: false,
+        dry_run: query.dry_run,
+    };
+    import_uploads(repo, media_store, formula_ocr, spill, limits, multipart, upload).await
+}
+
+/// Import any supported file - handles multipart upload
+///
+/// Each file is recognized by its content rather than its name: archives,
+/// Word documents, PDFs, Markdown, plain text, GIFT and spreadsheets are all
+/// accepted, and several may be sent in one request. Takes the fields of
+/// `/parse-archive`, plus `mapping` and `sheet` for spreadsheets as in
+/// `/import/table`. Without a `preset`, an import of only Word documents or
+/// only PDFs uses their usual defaults.
+pub async fn import_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    formula_ocr: Option<Extension<Arc<dyn FormulaOcr>>>,
+    spill: Option<Extension<SpillConfig>>,
+    limits: Option<Extension<ZipLimits>>,
+    Query(query): Query<ImportQuery>,
+    multipart: Multipart,
+) -> Result<Json<ParseZipResponse>, ApiError> {
+    let upload = Upload {
+        kind: "file",
+        accepts: |_| true,
+        expected: String::new(),
+        options: ParserOptions::default(),
+        detect: true,
+        dry_run: query.dry_run,
+    };
+    import_uploads(repo, media_store, formula_ocr, spill, limits, multipart, upload).await
+}
+
+/// Queue an import to run in the background
+///
+/// Takes the same fields as `/import` and answers `202 Accepted` with the
+/// job ID as soon as the upload has been read, so large archives do not run
+/// into proxy timeouts. Poll `/imports/:id` for progress and fetch
+/// `/imports/:id/result` once the job has completed.
+#[allow(clippy::too_many_arguments)]
+pub async fn submit_import_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    jobs: Option<Extension<Arc<ImportJobs>>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    formula_ocr: Option<Extension<Arc<dyn FormulaOcr>>>,
+    spill: Option<Extension<SpillConfig>>,
+    limits: Option<Extension<ZipLimits>>,
+    Query(query): Query<ImportQuery>,
+    multipart: Multipart,
+) -> Result<(StatusCode, Json<SubmittedImport>), ApiError> {
+    let jobs = import_jobs(jobs)?;
+    let upload = Upload {
+        kind: "file",
+        accepts: |_| true,
+        expected: String::new(),
+        options: ParserOptions::default(),
+        detect: true,
+        dry_run: query.dry_run,
+    };
+    let services = UploadServices::new(media_store, formula_ocr, spill, limits);
+    let pending = read_uploads(multipart, upload).await?;
+
+    let sources = pending.job.sources();
+    let files = sources.len();
+    let job_id = jobs
+        .submit_task(sources, move |progress| async move {
+            let progress = UploadProgress::new(progress, files);
+            let response = run_uploads(repo, &services, pending, &progress).await?;
+            Ok(job_result(response))
+        })
+        .await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    Ok((StatusCode::ACCEPTED, Json(SubmittedImport { job_id })))
+}
+
+/// Status and progress of a background import
+pub async fn import_status_endpoint(
+    jobs: Option<Extension<Arc<ImportJobs>>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ImportStatus>, ApiError> {
+    let job = find_import(jobs, id).await?;
+    Ok(Json(ImportStatus {
+        id: job.id,
+        status: job.status,
+        sources: job.sources,
+        stage: job.stage,
+        progress: job.progress,
+        count: job.result.map(|r| r.question_ids.len()),
+        error: job.error,
+        created_at: job.created_at,
+        updated_at: job.updated_at,
+    }))
+}
+
+/// Full response of a completed background import
+///
+/// Answers `409 Conflict` while the import is still queued or running, and
+/// `400` with the recorded error if it failed.
+pub async fn import_result_endpoint(
+    jobs: Option<Extension<Arc<ImportJobs>>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ParseZipResponse>, ApiError> {
+    let job = find_import(jobs, id).await?;
+    match (job.status, job.result) {
+        (JobStatus::Completed, Some(result)) => Ok(Json(job_response(result))),
+        (JobStatus::Failed, _) => Err(ApiError::ParseError(
+            job.error.unwrap_or_else(|| format!("Import job {} failed", id)),
+        )),
+        (status, _) => Err(ApiError::Conflict(format!(
+            "Import job {} is {}; its result is not available yet",
+            id,
+            status.as_str()
+        ))),
+    }
+}
+
+/// Download the report of a finished background import
+///
+/// Answers `409 Conflict` while the import is still queued or running. A
+/// failed import gets a report too, stating why it failed.
+pub async fn import_report_endpoint(
+    jobs: Option<Extension<Arc<ImportJobs>>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ReportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let format: ReportFormat = match query.format.as_deref() {
+        Some(name) => name.parse::<ReportFormat>().map_err(|e| ApiError::ParseError(e.to_string()))?,
+        None => ReportFormat::default(),
+    };
+    let job = find_import(jobs, id).await?;
+    if !job.status.is_finished() {
+        return Err(ApiError::Conflict(format!(
+            "Import job {} is {}; its report is not available yet",
+            id,
+            job.status.as_str()
+        )));
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"import-{}.{}\"", id, format.extension()),
+            ),
+        ],
+        render_job_report(&job, format),
+    ))
+}
+
+/// Stream the progress of a background import over a WebSocket
+///
+/// Each message is a JSON [`JobEvent`]: first the job's current status,
+/// then stages, files parsed, percent done and, once it has finished, its
+/// warnings and a `finished` event, after which the socket is closed.
+pub async fn import_events_endpoint(
+    jobs: Option<Extension<Arc<ImportJobs>>>,
+    Path(id): Path<Uuid>,
+    ws: WebSocketUpgrade,
+) -> Result<axum::response::Response, ApiError> {
+    let jobs = import_jobs(jobs)?;
+    // Subscribe first so nothing between the lookup and the stream is lost
+    let events = jobs.subscribe();
+    let job = jobs.get(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Import job {} not found", id)))?;
+
+    Ok(ws.on_upgrade(move |socket| stream_import_events(socket, JobEvent::snapshot(&job), events)))
+}
+
+/// Send `first`, then the events of the same job until it finishes
+async fn stream_import_events(
+    mut socket: WebSocket,
+    first: JobEvent,
+    mut events: tokio::sync::broadcast::Receiver<JobEvent>,
+) {
+    let id = first.job_id();
+    let mut next = Some(first);
+    while let Some(event) = next.take() {
+        let Ok(text) = serde_json::to_string(&event) else {
+            break;
+        };
+        if socket.send(Message::Text(text)).await.is_err() || event.is_final() {
+            break;
+        }
+        next = loop {
+            match events.recv().await {
+                Ok(event) if event.job_id() == id => break Some(event),
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break None,
+            }
+        };
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// The server's job manager, if background imports are enabled
+fn import_jobs(jobs: Option<Extension<Arc<ImportJobs>>>) -> Result<Arc<ImportJobs>, ApiError> {
+    jobs.map(|Extension(jobs)| jobs)
+        .ok_or_else(|| ApiError::NotFound("Background imports are not enabled on this server".to_string()))
+}
+
+/// Look up a background import, answering 404 if it does not exist
+async fn find_import(jobs: Option<Extension<Arc<ImportJobs>>>, id: Uuid) -> Result<crate::jobs::JobRecord, ApiError> {
+    import_jobs(jobs)?.get(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Import job {} not found", id)))
+}
+
+/// Record an upload response as the result of its job
+fn job_result(response: ParseZipResponse) -> ProcessResult {
+    let skipped = response.outcomes.iter().filter(|o| !o.is_written()).count();
+    let updated = response
+        .outcomes
+        .iter()
+        .filter(|o| matches!(o, SaveOutcome::Updated { .. }))
+        .count();
+
+    let mut result = ProcessResult::new();
+    result.total_questions = response.questions.len() + response.duplicates_skipped;
+    result.saved_questions = response.count;
+    result.updated_questions = updated;
+    result.added_questions = response.count - updated;
+    result.duplicate_questions = response.duplicates_skipped + skipped;
+    result.total_images = response.images_processed;
+    for archive in &response.archives {
+        result.stored_images += archive.image_store.stored;
+        result.deduplicated_images += archive.image_store.already_stored;
+        result.failed_images += archive.image_store.failed;
+    }
+    result.warnings = response.warnings.clone();
+    result.outcomes = response.outcomes;
+    result.session_id = response.session_id;
+    result.dry_run = response.dry_run;
+    result.import_report = Some(ImportReport {
+        archives: response.archives,
+        total_questions: response.questions.len(),
+        duplicates_skipped: response.duplicates_skipped,
+        warnings: response.warnings,
+        ..ImportReport::default()
+    });
+    result.breakdown = QuestionBreakdown::new(&response.questions);
+    result.question_ids = response.question_ids;
+    result.questions = response.questions;
+    result
+}
+
+/// Rebuild the upload response stored by [`job_result`]
+fn job_response(result: ProcessResult) -> ParseZipResponse {
+    let report = result.import_report.unwrap_or_default();
+    ParseZipResponse {
+        count: result.question_ids.len(),
+        question_ids: result.question_ids,
+        questions: result.questions,
+        images_processed: result.total_images,
+        files: report.archives.iter().flat_map(|a| a.files.clone()).collect(),
+        duplicates_skipped: report.duplicates_skipped,
+        archives: report.archives,
+        warnings: result.warnings,
+        outcomes: result.outcomes,
+        session_id: result.session_id,
+        dry_run: result.dry_run,
+    }
+}
+
+/// Turns uploads done into a job's percent estimate
+///
+/// Reading the uploads counts for the first half, as in
+/// [`SingleMachineProcessor`] runs; saving is reported as one step.
+struct UploadProgress {
+    inner: Arc<dyn ProgressReporter>,
+    files: usize,
+    done: AtomicUsize,
+}
+
+impl UploadProgress {
+    fn new(inner: Arc<dyn ProgressReporter>, files: usize) -> Self {
+        Self {
+            inner,
+            files: files.max(1),
+            done: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ProgressReporter for UploadProgress {
+    fn on_stage(&self, stage: ProcessStage) {
+        self.inner.on_stage(stage);
+    }
+
+    fn on_file_done(&self, source: &str, questions: usize) {
+        self.inner.on_file_done(source, questions);
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.inner.on_percent(50.0 * done.min(self.files) as f64 / self.files as f64);
+    }
+}
+
+/// Parse several Markdown files uploaded in one multipart form
+///
+/// Every `file` field is a `.md` or `.markdown` file; the files are parsed in
+/// parallel as one batch import, so a folder can be sent without zipping it.
+/// Also takes `dedup`, `preset` and `bank_id` fields. Warnings name the file
+/// they come from.
+///
+/// Runs on the job manager's processor, so the server's workers, limits and
+/// services apply; without one a default processor is started per request.
+pub async fn parse_files_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    jobs: Option<Extension<Arc<ImportJobs>>>,
+    Query(query): Query<ImportQuery>,
     mut multipart: Multipart,
+) -> Result<Json<ProcessResult>, ApiError> {
+    let shared = jobs.map(|Extension(jobs)| jobs.processor().clone());
+    let mut contents = Vec::new();
+    let mut config = shared.as_ref()
+        .map(|processor| processor.config().clone())
+        .unwrap_or_default()
+        .with_dry_run(query.dry_run);
+
+    while let Some(field) = multipart.next_field().await
+        .map_err(multipart_error("Failed to read multipart field"))?
+    {
+        let name = field.name().unwrap_or("unknown").to_string();
+
+        if name == "file" {
+            let filename = field.file_name()
+                .ok_or_else(|| ApiError::InvalidFile("Missing filename".to_string()))?
+                .to_string();
+            let lower = filename.to_lowercase();
+            if !(lower.ends_with(".md") || lower.ends_with(".markdown")) {
+                return Err(ApiError::InvalidFile(format!(
+                    "Invalid file type: expected .md, .markdown file, got: {}",
+                    filename
+                )));
+            }
+            let data = field.bytes().await
+                .map_err(multipart_error("Failed to read file content"))?;
+            let content = String::from_utf8(data.to_vec())
+                .map_err(|_| ApiError::InvalidFile(format!("{} is not valid UTF-8", filename)))?;
+            contents.push((content, filename));
+            continue;
+        }
+
+        let value = field.text().await
+            .map_err(multipart_error(format!("Failed to read {} field", name)))?;
+        match name.as_str() {
+            "dedup" if matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes") => {
+                config = config.with_dedup(DedupOptions::default());
+            }
+            "preset" => config = config.with_parser_options(presets::preset(&value)?),
+            "bank_id" => {
+                let id = value.trim().parse::<Uuid>()
+                    .map_err(|e| ApiError::ParseError(format!("Invalid bank_id: {}", e)))?;
+                require_bank(repo.as_ref(), id).await?;
+                config = config.with_bank_id(id);
+            }
+            _ => {}
+        }
+    }
+
+    if contents.is_empty() {
+        return Err(ApiError::InvalidFile("No file uploaded".to_string()));
+    }
+
+    let processor = match shared {
+        Some(processor) => processor.reconfigured(config),
+        None => SingleMachineProcessor::with_config(repo, config),
+    };
+    let result = processor.process(InputSource::MultipleMarkdown { contents }).await
+        .map_err(import_error)?;
+
+    Ok(Json(result))
+}
+
+/// Map a failed import to a response: storage failures are the server's
+/// fault, anything else is a problem with the upload
+fn import_error(err: anyhow::Error) -> ApiError {
+    match err.downcast::<Md2DbError>() {
+        Ok(err) => err.into(),
+        Err(err) => ApiError::ParseError(err.to_string()),
+    }
+}
+
+/// Import a spreadsheet with one question per row
+///
+/// Multipart fields: `file` (CSV, TSV or workbook), `mapping` (a JSON
+/// [`ColumnMapping`], merged over the default), `sheet`, `dedup` and
+/// `bank_id`.
+#[cfg(any(feature = "csv", feature = "xlsx"))]
+pub async fn import_table_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(query): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> Result<Json<ParseResponse>, ApiError> {
+    let mut file = None;
+    let mut importer = TabularImporter::new();
+    let mut dedup = false;
+    let mut bank_id = None;
+
+    while let Some(field) = multipart.next_field().await
+        .map_err(multipart_error("Failed to read multipart field"))?
+    {
+        let name = field.name().unwrap_or("unknown").to_string();
+
+        if name == "file" {
+            let filename = field.file_name()
+                .ok_or_else(|| ApiError::InvalidFile("Missing filename".to_string()))?
+                .to_string();
+            if !TableFormat::from_name(&filename).is_some_and(TableFormat::is_enabled) {
+                return Err(ApiError::InvalidFile(format!(
+                    "Invalid file type: expected {} file, got: {}",
+                    tabular::supported_extensions().join(", "),
+                    filename
+                )));
+            }
+            let data = field.bytes().await
+                .map_err(multipart_error("Failed to read file content"))?;
+            file = Some((filename, data.to_vec()));
+            continue;
+        }
+
+        let value = field.text().await
+            .map_err(multipart_error(format!("Failed to read {} field", name)))?;
+        match name.as_str() {
+            "mapping" => {
+                let mapping: ColumnMapping = serde_json::from_str(&value)
+                    .map_err(|e| ApiError::ParseError(format!("Invalid mapping: {}", e)))?;
+                importer = importer.with_mapping(mapping);
+            }
+            "sheet" => importer = importer.with_sheet(value.trim()),
+            "dedup" => dedup = matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes"),
+            "bank_id" => {
+                let id = value.trim().parse::<Uuid>()
+                    .map_err(|e| ApiError::ParseError(format!("Invalid bank_id: {}", e)))?;
+                bank_id = Some(id);
+            }
+            _ => {}
+        }
+    }
+
+    let (filename, data) = file.ok_or_else(|| ApiError::InvalidFile("No file uploaded".to_string()))?;
+    let import = tokio::task::spawn_blocking(move || importer.import(&filename, &data))
+        .await
+        .map_err(|e| ApiError::ParseError(format!("Failed to read spreadsheet: {}", e)))?
+        .map_err(|e| ApiError::ParseError(format!("Failed to read spreadsheet: {}", e)))?;
+
+    let mut questions = import.questions;
+    assign_bank(repo.as_ref(), bank_id, &mut questions).await?;
+    let saved = save_questions(repo.as_ref(), &questions, dedup, query.dry_run).await?;
+
+    Ok(Json(ParseResponse {
+        count: saved.ids.len(),
+        question_ids: saved.ids,
+        questions,
+        warnings: import.warnings,
+        outcomes: saved.outcomes,
+        session_id: saved.session_id,
+        dry_run: saved.dry_run,
+        classifications: Vec::new(),
+    }))
+}
+
+/// What an upload endpoint accepts
+struct Upload {
+    /// Noun used in error messages
+    kind: &'static str,
+    /// Whether a filename is accepted
+    accepts: fn(&str) -> bool,
+    /// Accepted extensions, for the error message
+    expected: String,
+    /// Parser options used unless a preset is named
+    options: ParserOptions,
+    /// Recognize each file by its content (see [`crate::detect`])
+    detect: bool,
+    /// Parse and check the uploads but save nothing
+    dry_run: bool,
+}
+
+/// Server-side services used while importing uploads
+#[derive(Clone, Default)]
+struct UploadServices {
+    media_store: Option<Arc<dyn MediaStore>>,
+    formula_ocr: Option<Arc<dyn FormulaOcr>>,
+    spill: Option<SpillConfig>,
+    limits: Option<ZipLimits>,
+}
+
+impl UploadServices {
+    fn new(
+        media_store: Option<Extension<Arc<dyn MediaStore>>>,
+        formula_ocr: Option<Extension<Arc<dyn FormulaOcr>>>,
+        spill: Option<Extension<SpillConfig>>,
+        limits: Option<Extension<ZipLimits>>,
+    ) -> Self {
+        Self {
+            media_store: media_store.map(|Extension(store)| store),
+            formula_ocr: formula_ocr.map(|Extension(ocr)| ocr),
+            spill: spill.map(|Extension(spill)| spill),
+            limits: limits.map(|Extension(limits)| limits),
+        }
+    }
+}
+
+/// Uploads read from a multipart form, ready to be imported
+struct PendingUpload {
+    /// Noun used in error messages
+    kind: &'static str,
+    job: ImportJob,
+    dedup: bool,
+    parser_options: ParserOptions,
+    bank_id: Option<Uuid>,
+    strip_metadata: bool,
+    detect: bool,
+    dry_run: bool,
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    table_importer: TabularImporter,
+}
+
+/// Import every uploaded file as one job and save the questions
+async fn import_uploads(
+    repo: Arc<dyn QuestionRepository>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    formula_ocr: Option<Extension<Arc<dyn FormulaOcr>>>,
+    spill: Option<Extension<SpillConfig>>,
+    limits: Option<Extension<ZipLimits>>,
+    multipart: Multipart,
+    upload: Upload,
 ) -> Result<Json<ParseZipResponse>, ApiError> {
-    let mut zip_data: Option<Vec<u8>> = None;
+    let services = UploadServices::new(media_store, formula_ocr, spill, limits);
+    let pending = read_uploads(multipart, upload).await?;
+    let response = run_uploads(repo, &services, pending, &NoProgress).await?;
+    Ok(Json(response))
+}
+
+/// Read the files and fields of an upload form
+async fn read_uploads(mut multipart: Multipart, upload: Upload) -> Result<PendingUpload, ApiError> {
+    let mut job = ImportJob::new();
+    let mut dedup = false;
+    let mut parser_options = upload.options;
+    let mut bank_id = None;
+    let mut strip_metadata = false;
+    let mut preset_named = false;
+    let mut formats = Vec::new();
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    let mut table_importer = TabularImporter::new();
 
     // Process multipart form data
     while let Some(field) = multipart.next_field().await
-        .map_err(|e| ApiError::MultipartError(format!("Failed to read multipart field: {}", e)))?
+        .map_err(multipart_error("Failed to read multipart field"))?
     {
         let name = field.name().unwrap_or("unknown");
 
-        if name == "file" || name == "zip" {
+        if name == "file" || name == "archive" || name == "zip" {
             let filename = field.file_name()
                 .ok_or_else(|| ApiError::InvalidFile("Missing filename".to_string()))?
                 .to_string();
 
             // Validate file extension
-            if !filename.to_lowercase().ends_with(".zip") {
+            if !(upload.accepts)(&filename) {
                 return Err(ApiError::InvalidFile(format!(
-                    "Invalid file type: expected .zip file, got: {}", filename
+                    "Invalid file type: expected {} file, got: {}",
+                    upload.expected, filename
                 )));
             }
 
             // Read file content
             let data = field.bytes().await
-                .map_err(|e| ApiError::MultipartError(format!("Failed to read file content: {}", e)))?;
+                .map_err(multipart_error("Failed to read file content"))?;
 
-            zip_data = Some(data.to_vec());
+            if upload.detect {
+                formats.push(FormatDetector::detect(&filename, &data));
+            }
+            job.add_archive(data.to_vec(), filename);
+        } else if name == "dedup" {
+            let value = field.text().await
+                .map_err(multipart_error("Failed to read dedup field"))?;
+            dedup = matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes");
+        } else if name == "preset" {
+            let value = field.text().await
+                .map_err(multipart_error("Failed to read preset field"))?;
+            parser_options = presets::preset(&value)?;
+            preset_named = true;
+        } else if upload.detect && (name == "mapping" || name == "sheet") {
+            let name = name.to_string();
+            let value = field.text().await
+                .map_err(multipart_error(format!("Failed to read {} field", name)))?;
+            #[cfg(any(feature = "csv", feature = "xlsx"))]
+            if name == "mapping" {
+                let mapping: ColumnMapping = serde_json::from_str(&value)
+                    .map_err(|e| ApiError::ParseError(format!("Invalid mapping: {}", e)))?;
+                table_importer = table_importer.with_mapping(mapping);
+            } else {
+                table_importer = table_importer.with_sheet(value.trim());
+            }
+            #[cfg(not(any(feature = "csv", feature = "xlsx")))]
+            let _ = (name, value);
+        } else if name == "bank_id" {
+            let value = field.text().await
+                .map_err(multipart_error("Failed to read bank_id field"))?;
+            let id = value.trim().parse::<Uuid>()
+                .map_err(|e| ApiError::ParseError(format!("Invalid bank_id: {}", e)))?;
+            bank_id = Some(id);
+        } else if name == "strip_metadata" {
+            let value = field.text().await
+                .map_err(multipart_error("Failed to read strip_metadata field"))?;
+            strip_metadata = matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes");
         }
     }
 
     // Validate that we received a file
-    let zip_data = zip_data
-        .ok_or_else(|| ApiError::InvalidFile("No file uploaded".to_string()))?;
+    if job.is_empty() {
+        return Err(ApiError::InvalidFile("No file uploaded".to_string()));
+    }
 
-    // Process the ZIP file
-    let processor = ZipProcessor::new();
-    let result = processor.process_zip(zip_data).await
-        .map_err(|e| ApiError::ParseError(format!("Failed to process ZIP: {}", e)))?;
+    // Documents keep their own defaults when all uploads are of one kind
+    if !preset_named {
+        #[cfg(feature = "docx")]
+        if !formats.is_empty() && formats.iter().all(|f| *f == Some(crate::detect::ImportFormat::Docx)) {
+            parser_options = crate::docx::default_options();
+        }
+        #[cfg(feature = "pdf")]
+        if !formats.is_empty() && formats.iter().all(|f| *f == Some(crate::detect::ImportFormat::Pdf)) {
+            parser_options = crate::pdf::default_options();
+        }
+    }
+
+    Ok(PendingUpload {
+        kind: upload.kind,
+        job,
+        dedup,
+        parser_options,
+        bank_id,
+        strip_metadata,
+        detect: upload.detect,
+        dry_run: upload.dry_run,
+        #[cfg(any(feature = "csv", feature = "xlsx"))]
+        table_importer,
+    })
+}
+
+/// Process read uploads and save their questions
+async fn run_uploads(
+    repo: Arc<dyn QuestionRepository>,
+    services: &UploadServices,
+    upload: PendingUpload,
+    progress: &dyn ProgressReporter,
+) -> Result<ParseZipResponse, ApiError> {
+    let dry_run = upload.dry_run;
+
+    // Process the uploads
+    let single = upload.job.len() == 1;
+    let mut processor = ZipProcessor::new()
+        .with_parser_options(upload.parser_options)
+        .with_strip_metadata(upload.strip_metadata)
+        .with_format_detection(upload.detect);
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    {
+        processor = processor.with_table_importer(upload.table_importer);
+    }
+    // A dry run must not write images either
+    if let Some(store) = services.media_store.clone().filter(|_| !dry_run) {
+        processor = processor.with_media_store(store);
+    }
+    if let Some(ocr) = services.formula_ocr.clone() {
+        processor = processor.with_formula_ocr(ocr);
+    }
+    if let Some(spill) = services.spill.clone() {
+        processor = processor.with_spill(spill);
+    }
+    if let Some(limits) = services.limits {
+        processor = processor.with_limits(limits);
+    }
+    progress.on_stage(ProcessStage::Parsing);
+    let result = upload.job.run_with_progress(&processor, MAX_CONCURRENT_ZIPS, progress).await
+        .map_err(|e| ApiError::ParseError(format!("Failed to process {}: {}", upload.kind, e)))?;
+
+    // A single unreadable upload is a bad request rather than an empty import
+    if single {
+        if let Some(error) = &result.report.archives[0].error {
+            return Err(ApiError::ParseError(format!("Failed to process {}: {}", upload.kind, error)));
+        }
+    }
+
+    // An explicit bank_id wins over a bank named in a manifest
+    let mut result = result;
+    let bank_id = match (upload.bank_id, &result.report.manifest.bank) {
+        (None, Some(name)) => {
+            let id = bank_named(repo.as_ref(), name, dry_run).await?;
+            if id.is_none() {
+                result.report.warnings.push(format!("Question bank '{}' would be created", name));
+            }
+            id
+        }
+        (bank_id, _) => bank_id,
+    };
 
     // Save questions to database
-    let ids = repo.save_batch(&result.questions).await
-        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+    progress.on_stage(ProcessStage::Saving);
+    assign_bank(repo.as_ref(), bank_id, &mut result.questions).await?;
+    let saved = save_questions(repo.as_ref(), &result.questions, upload.dedup, dry_run).await?;
+    progress.on_stage(ProcessStage::Done);
 
-    Ok(Json(ParseZipResponse {
-        count: ids.len(),
-        question_ids: ids,
+    let report = result.report;
+    Ok(ParseZipResponse {
+        count: saved.ids.len(),
+        question_ids: saved.ids,
         questions: result.questions,
         images_processed: result.images.len(),
-        warnings: result.warnings,
+        files: report.archives.iter().flat_map(|a| a.files.clone()).collect(),
+        duplicates_skipped: report.duplicates_skipped,
+        archives: report.archives,
+        warnings: report.warnings,
+        outcomes: saved.outcomes,
+        session_id: saved.session_id,
+        dry_run: saved.dry_run,
+    })
+}
+
+/// List stored questions with offset or cursor pagination, optionally of one type
+pub async fn list_questions_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Page<Question>>, ApiError> {
+    if params.cursor.is_some() {
+        params.decode_cursor().map_err(|e| ApiError::ParseError(e.to_string()))?;
+    }
+
+    let page = repo.list(&params).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(page))
+}
+
+/// Fetch one stored question
+pub async fn get_question_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Question>, ApiError> {
+    let question = repo.find_by_id(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?;
+
+    Ok(Json(question))
+}
+
+/// Replace a stored question (the path ID takes precedence over the body)
+///
+/// The replaced version is kept in the question's history, attributed to the
+/// `X-Actor` request header when present.
+pub async fn update_question_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(mut question): Json<Question>,
+) -> Result<Json<Question>, ApiError> {
+    question.id = id;
+    let updated = repo.update_as(&question, actor(&headers)).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    if !updated {
+        return Err(ApiError::NotFound(format!("Question {} not found", id)));
+    }
+
+    Ok(Json(question))
+}
+
+/// Change some fields of a stored question
+///
+/// A new answer or new options are matched against each other as on import,
+/// so `{"answer": "B"}` marks the second option correct. The result is
+/// checked against the rules of its type and rejected with `422` if the
+/// patch made it invalid; problems it already had do not block a fix.
+pub async fn patch_question_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(patch): Json<QuestionPatch>,
+) -> Result<Json<Question>, ApiError> {
+    let mut question = repo.find_by_id(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?;
+
+    let validator = QuestionValidator::new();
+    let before = validator.check(&question);
+    let changes_answer = patch.changes_answer();
+    patch.apply(&mut question);
+    if changes_answer {
+        if let Some(issue) = validate_answer(&mut question) {
+            if issue.kind == AnswerIssueKind::Invalid {
+                return Err(ApiError::Unprocessable(issue.message));
+            }
+        }
+    }
+    let reasons: Vec<String> = validator
+        .check(&question)
+        .into_iter()
+        .filter(|reason| !before.contains(reason))
+        .collect();
+    if !reasons.is_empty() {
+        return Err(ApiError::Unprocessable(format!("Question would be invalid: {}", reasons.join(", "))));
+    }
+
+    let updated = repo.update_as(&question, actor(&headers)).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+    if !updated {
+        return Err(ApiError::NotFound(format!("Question {} not found", id)));
+    }
+
+    Ok(Json(question))
+}
+
+/// Who made an edit, from the `X-Actor` header
+fn actor(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(ACTOR_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+/// List prior versions of a stored question, newest first
+pub async fn question_history_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<QuestionRevision>>, ApiError> {
+    let exists = repo.find_by_id(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .is_some();
+    if !exists {
+        return Err(ApiError::NotFound(format!("Question {} not found", id)));
+    }
+
+    let history = repo.history(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(history))
+}
+
+/// Soft-delete a stored question
+pub async fn delete_question_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = repo.delete(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    if !deleted {
+        return Err(ApiError::NotFound(format!("Question {} not found", id)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Bring back a soft-deleted question
+pub async fn restore_question_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let restored = repo.restore(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    if !restored {
+        return Err(ApiError::NotFound(format!("Deleted question {} not found", id)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Render a stored question for accessible delivery
+pub async fn render_question_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<RenderQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let format: RenderFormat = query
+        .format
+        .as_deref()
+        .unwrap_or("text")
+        .parse()
+        .map_err(|e: anyhow::Error| ApiError::ParseError(e.to_string()))?;
+
+    let question = repo.find_by_id(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?;
+
+    let question = match query.locale.as_deref() {
+        Some(locale) => {
+            let locale: ExportLocale = locale
+                .parse()
+                .map_err(|e: anyhow::Error| ApiError::ParseError(e.to_string()))?;
+            typeset::normalize_question(&question, &TypesetOptions::new(locale))
+        }
+        None => question,
+    };
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        render(&question, format),
+    ))
+}
+
+/// A stored question as Markdown that parses back into the same question
+pub async fn question_markdown_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let question = repo.find_by_id(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+        MarkdownRenderer::new().render(&question),
+    ))
+}
+
+/// A stored question's formulas rendered as MathML or SVG
+///
+/// With a media store each rendering is stored there and returned by hash,
+/// to be fetched from `/media/:hash`; otherwise it is returned inline.
+#[cfg(feature = "latex-render")]
+pub async fn question_formulas_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<FormulaQuery>,
+) -> Result<Json<Vec<RenderedFormula>>, ApiError> {
+    let format: FormulaFormat = query
+        .format
+        .as_deref()
+        .unwrap_or("mathml")
+        .parse()
+        .map_err(|e: anyhow::Error| ApiError::ParseError(e.to_string()))?;
+
+    let question = repo.find_by_id(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?;
+
+    let mut renderer = FormulaRenderer::new(format);
+    if let Some(Extension(store)) = media_store {
+        renderer = renderer.with_media_store(store);
+    }
+    let formulas = renderer
+        .render_question(&question)
+        .await
+        .map_err(|e| ApiError::DatabaseError(format!("Failed to store rendered formulas: {}", e)))?;
+
+    Ok(Json(formulas))
+}
+
+/// The stored questions most similar to a stored question
+///
+/// Every stored question (or those `bank_id` and `type` select) is weighed,
+/// so bigrams common across the bank count for little.
+pub async fn similar_questions_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<SimilarParams>,
+) -> Result<Json<Vec<SimilarQuestion>>, ApiError> {
+    let question = repo.find_by_id(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?;
+    if let Some(bank_id) = params.bank_id {
+        require_bank(repo.as_ref(), bank_id).await?;
+    }
+
+    let filter = ExportFilter { bank_id: params.bank_id, tag: None, qtype: params.qtype };
+    let candidates = ExportPages::new(repo.as_ref(), &filter)
+        .collect()
+        .await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+    let limit = params.limit.unwrap_or(DEFAULT_SIMILAR_LIMIT).clamp(1, MAX_PAGE_SIZE);
+
+    Ok(Json(SimilarityIndex::new(candidates).most_similar(&question, limit)))
+}
+
+/// Create a question bank
+pub async fn create_bank_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Json(req): Json<CreateBankRequest>,
+) -> Result<(StatusCode, Json<QuestionBank>), ApiError> {
+    if req.name.trim().is_empty() {
+        return Err(ApiError::ParseError("Question bank name must not be empty".to_string()));
+    }
+
+    let mut bank = QuestionBank::new(req.name.trim()).with_tags(req.tags);
+    bank.description = req.description;
+    repo.create_bank(&bank).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(bank)))
+}
+
+/// List every question bank
+pub async fn list_banks_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+) -> Result<Json<Vec<QuestionBank>>, ApiError> {
+    let banks = repo.list_banks().await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(banks))
+}
+
+/// Get a question bank
+pub async fn get_bank_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<QuestionBank>, ApiError> {
+    Ok(Json(require_bank(repo.as_ref(), id).await?))
+}
+
+/// List the questions in a bank
+pub async fn bank_questions_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Question>>, ApiError> {
+    require_bank(repo.as_ref(), id).await?;
+    let questions = repo.find_by_bank(id).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(questions))
+}
+
+/// Export stored questions as a Moodle GIFT file
+///
+/// Exports one bank, the questions with a tag, or every stored question.
+pub async fn export_gift_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (questions, _) = export_selection(repo.as_ref(), &query).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"questions.gift.txt\""),
+        ],
+        write_gift(&questions),
+    ))
+}
+
+/// Export stored questions as a ZIP of Markdown and images
+///
+/// Selects questions like [`export_gift_endpoint`]. The archive can be
+/// edited and uploaded to `/parse-archive` again; local images are bundled
+/// from the media store when one is configured.
+pub async fn export_markdown_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (questions, _) = export_selection(repo.as_ref(), &query).await?;
+
+    let mut renderer = MarkdownRenderer::new();
+    if let Some(Extension(store)) = media_store {
+        renderer = renderer.with_media_store(store);
+    }
+    let archive = renderer
+        .archive(&questions)
+        .await
+        .map_err(|e| ApiError::ParseError(format!("Failed to write Markdown archive: {}", e)))?;
+    for warning in &archive.warnings {
+        tracing::warn!("Markdown export: {}", warning);
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"questions.zip\""),
+        ],
+        archive.data,
+    ))
+}
+
+/// Export questions as a printable Word exam paper
+///
+/// Questions listed in `question_ids` are printed in that order; otherwise
+/// they are selected like [`export_gift_endpoint`]. Local images are
+/// embedded from the media store when one is configured.
+#[cfg(feature = "docx")]
+pub async fn export_docx_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    Json(req): Json<ExportDocxRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (questions, bank) = if req.question_ids.is_empty() {
+        let query = ExportQuery {
+            bank_id: req.bank_id,
+            tag: req.tag,
+            ..ExportQuery::default()
+        };
+        export_selection(repo.as_ref(), &query).await?
+    } else {
+        let mut questions = Vec::with_capacity(req.question_ids.len());
+        for &id in &req.question_ids {
+            let question = repo.find_by_id(id).await
+                .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+                .ok_or_else(|| ApiError::NotFound(format!("Question {} not found", id)))?;
+            questions.push(question);
+        }
+        (questions, None)
+    };
+
+    let mut layout = req.layout;
+    if layout.title.is_none() {
+        layout.title = bank.map(|bank| bank.name);
+    }
+    let mut writer = DocxWriter::new(layout);
+    if let Some(Extension(store)) = media_store {
+        writer = writer.with_media_store(store);
+    }
+    let paper = writer
+        .write(&questions)
+        .await
+        .map_err(|e| ApiError::ParseError(format!("Failed to write Word document: {}", e)))?;
+    for warning in &paper.warnings {
+        tracing::warn!("Word export: {}", warning);
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"questions.docx\""),
+        ],
+        paper.data,
+    ))
+}
+
+/// Export stored questions as an Excel workbook for review
+///
+/// Selects questions like [`export_gift_endpoint`]; one row per question.
+#[cfg(feature = "xlsx")]
+pub async fn export_xlsx_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (questions, _) = export_selection(repo.as_ref(), &query).await?;
+    let data = tabular::write_xlsx(&questions)
+        .map_err(|e| ApiError::ParseError(format!("Failed to write workbook: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"questions.xlsx\""),
+        ],
+        data,
+    ))
+}
+
+/// Export stored questions as an Anki package (`.apkg`)
+///
+/// Selects questions like [`export_gift_endpoint`]. Cards go in the `deck`
+/// parameter's deck, else one named after the bank; local images are
+/// bundled from the media store when one is configured.
+#[cfg(feature = "anki")]
+pub async fn export_anki_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (questions, bank) = export_selection(repo.as_ref(), &query).await?;
+    let deck = match (query.deck, bank) {
+        (Some(deck), _) if !deck.trim().is_empty() => deck.trim().to_string(),
+        (_, Some(bank)) => bank.name,
+        _ => anki::DEFAULT_DECK.to_string(),
+    };
+
+    let mut exporter = AnkiExporter::new(deck);
+    if let Some(Extension(store)) = media_store {
+        exporter = exporter.with_media_store(store);
+    }
+    let package = exporter
+        .export(&questions)
+        .await
+        .map_err(|e| ApiError::ParseError(format!("Failed to write Anki package: {}", e)))?;
+    for warning in &package.warnings {
+        tracing::warn!("Anki export: {}", warning);
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"questions.apkg\""),
+        ],
+        package.data,
+    ))
+}
+
+/// Stream stored questions as JSON Lines
+///
+/// The body is written while the repository is paged through, so large
+/// banks are not buffered in memory. An error part-way through is logged
+/// and ends the stream early.
+pub async fn export_jsonl_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(filter): Query<ExportFilter>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(bank_id) = filter.bank_id {
+        require_bank(repo.as_ref(), bank_id).await?;
+    }
+
+    let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Err(e) = export_jsonl(repo.as_ref(), &mut writer, &filter).await {
+            tracing::warn!("JSON Lines export failed: {}", e);
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"questions.jsonl\""),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    ))
+}
+
+/// Stream stored questions in the requested format
+///
+/// `format` picks the exporter; without it the `Accept` header is matched
+/// against each format's media type, falling back to JSON Lines. Pages are
+/// written to the response as they are read, except for Excel workbooks,
+/// which are sent once complete. An error part-way through is logged and
+/// ends the stream early.
+pub async fn export_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(query): Query<BulkExportQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let format = match query.format.as_deref() {
+        Some(format) => format.parse::<ExportFormat>().map_err(|e| ApiError::InvalidFile(e.to_string()))?,
+        None => headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .and_then(ExportFormat::from_accept)
+            .unwrap_or(ExportFormat::Jsonl),
+    };
+    let filter = query.filter;
+    if let Some(bank_id) = filter.bank_id {
+        require_bank(repo.as_ref(), bank_id).await?;
+    }
+
+    let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Err(e) = export(repo.as_ref(), &mut writer, &filter, format).await {
+            tracing::warn!("{} export failed: {}", format.name(), e);
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", format.file_name()),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    ))
+}
+
+/// Serve a stored image by its content hash
+///
+/// The hash doubles as a strong `ETag`, and since the content behind a hash
+/// never changes, responses may be cached indefinitely; a matching
+/// `If-None-Match` gets `304 Not Modified`. Images are sandboxed so a
+/// stored SVG cannot run scripts in the API's origin.
+pub async fn media_endpoint(
+    media_store: Option<Extension<Arc<dyn MediaStore>>>,
+    Path(hash): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let Some(Extension(store)) = media_store else {
+        return Err(ApiError::NotFound("No media store is configured".to_string()));
+    };
+    let hash = hash.to_ascii_lowercase();
+    let not_found = || ApiError::NotFound(format!("Media {} not found", hash));
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(not_found());
+    }
+
+    let etag = format!("\"{}\"", hash);
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "public, max-age=31536000, immutable".to_string()),
+    ];
+    let matches = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag)
+        });
+    let file = store
+        .open(&hash)
+        .await
+        .map_err(|e| ApiError::DatabaseError(format!("Failed to read media: {}", e)))?
+        .ok_or_else(not_found)?;
+    if matches {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    Ok((
+        cache_headers,
+        [
+            (header::CONTENT_TYPE, media_type(&file.extension).to_string()),
+            (header::CONTENT_LENGTH, file.size.to_string()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CONTENT_SECURITY_POLICY, "default-src 'none'; style-src 'unsafe-inline'; sandbox".to_string()),
+        ],
+        Body::from_stream(ReaderStream::new(file.reader)),
+    )
+        .into_response())
+}
+
+/// Import questions from a JSON Lines request body
+///
+/// Questions get new IDs unless `keep_ids` is set. Banks named in the file
+/// that do not exist here are dropped with a warning; a `bank_id` parameter
+/// puts every question in that bank instead.
+pub async fn import_jsonl_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(query): Query<ImportJsonlQuery>,
+    body: Body,
+) -> Result<Json<ParseResponse>, ApiError> {
+    let stream = body.into_data_stream().map_err(std::io::Error::other);
+    let import = read_jsonl(StreamReader::new(stream)).await.map_err(|e| {
+        // A streamed body is cut off at the upload cap
+        if e.chain().any(|e| e.is::<http_body_util::LengthLimitError>()) {
+            ApiError::PayloadTooLarge("Request body is larger than the limit".to_string())
+        } else {
+            ApiError::ParseError(format!("Failed to read JSON Lines: {}", e))
+        }
+    })?;
+    let mut questions = import.questions;
+    let mut warnings = import.warnings;
+
+    if !query.keep_ids {
+        for question in &mut questions {
+            question.id = Uuid::new_v4();
+        }
+    }
+    if query.bank_id.is_some() {
+        assign_bank(repo.as_ref(), query.bank_id, &mut questions).await?;
+    } else {
+        let mut known = std::collections::HashMap::new();
+        for question in &mut questions {
+            let Some(bank_id) = question.bank_id else { continue };
+            let exists = match known.get(&bank_id) {
+                Some(&exists) => exists,
+                None => {
+                    let exists = repo.find_bank(bank_id).await
+                        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+                        .is_some();
+                    if !exists {
+                        warnings.push(format!(
+                            "Question bank {} not found; its questions were imported without a bank",
+                            bank_id
+                        ));
+                    }
+                    known.insert(bank_id, exists);
+                    exists
+                }
+            };
+            if !exists {
+                question.bank_id = None;
+            }
+        }
+    }
+
+    let saved = save_questions(repo.as_ref(), &questions, query.dedup, query.dry_run).await?;
+
+    Ok(Json(ParseResponse {
+        count: saved.ids.len(),
+        question_ids: saved.ids,
+        questions,
+        warnings,
+        outcomes: saved.outcomes,
+        session_id: saved.session_id,
+        dry_run: saved.dry_run,
+        classifications: Vec::new(),
     }))
 }
 
+/// Questions selected by an export query, with the bank it named
+async fn export_selection(
+    repo: &dyn QuestionRepository,
+    query: &ExportQuery,
+) -> Result<(Vec<Question>, Option<QuestionBank>), ApiError> {
+    let (questions, bank) = match (query.bank_id, query.tag.as_deref()) {
+        (Some(bank_id), _) => {
+            let bank = require_bank(repo, bank_id).await?;
+            (repo.find_by_bank(bank_id).await, Some(bank))
+        }
+        (None, Some(tag)) => (repo.find_by_tag(tag).await, None),
+        (None, None) => (all_questions(repo).await, None),
+    };
+    let questions = questions.map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+    Ok((questions, bank))
+}
+
+/// Every stored question, oldest first
+async fn all_questions(repo: &dyn QuestionRepository) -> crate::error::Result<Vec<Question>> {
+    let mut params = ListParams {
+        limit: Some(MAX_PAGE_SIZE),
+        ..ListParams::default()
+    };
+    let mut questions = Vec::new();
+    loop {
+        let page = repo.list(&params).await?;
+        questions.extend(page.items);
+        match page.next_cursor {
+            Some(cursor) => params.cursor = Some(cursor),
+            None => return Ok(questions),
+        }
+    }
+}
+
+/// Move stored questions into a bank
+pub async fn assign_bank_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<AssignBankRequest>,
+) -> Result<Json<AssignBankResponse>, ApiError> {
+    require_bank(repo.as_ref(), id).await?;
+    let assigned = repo.assign_to_bank(id, &req.question_ids).await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(AssignBankResponse { assigned }))
+}
+
+/// Counts of stored questions by type, bank, review flag and language, and
+/// imports per day
+pub async fn stats_endpoint(State(repo): State<Arc<dyn QuestionRepository>>) -> Result<Json<QuestionStats>, ApiError> {
+    repo.stats()
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::DatabaseError(format!("Failed to compute statistics: {}", e)))
+}
+
+/// Search stored questions for every term of `q`, most relevant first
+pub async fn search_endpoint(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<SearchHit>>, ApiError> {
+    let query = SearchQuery::parse(&params.q);
+    if query.is_empty() {
+        return Err(ApiError::ParseError("Search query must not be empty".to_string()));
+    }
+    if let Some(bank_id) = params.bank_id {
+        require_bank(repo.as_ref(), bank_id).await?;
+    }
+
+    let filter = ExportFilter { bank_id: params.bank_id, tag: params.tag, qtype: params.qtype };
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_PAGE_SIZE);
+    let mut results = SearchResults::new(query, limit);
+    let mut pages = ExportPages::new(repo.as_ref(), &filter);
+    while let Some(page) = pages.next_page().await.map_err(|e| ApiError::DatabaseError(e.to_string()))? {
+        results.extend(page);
+    }
+
+    Ok(Json(results.finish()))
+}
+
 /// Health check endpoint
-pub async fn health_check() -> Json<HealthResponse> {
-    Json(HealthResponse {
-        status: "ok".to_string(),
-        version: env!("CARGO_PKG_VERSION").to_string(),
-    })
+///
+/// Pings the repository and answers 503 if the database is not ready, so
+/// load balancers and orchestrators stop routing to an instance that cannot
+/// save anything.
+pub async fn health_check(
+    State(repo): State<Arc<dyn QuestionRepository>>,
+) -> (StatusCode, Json<HealthResponse>) {
+    let ping = match tokio::time::timeout(HEALTH_PING_TIMEOUT, repo.ping()).await {
+        Ok(result) => result,
+        Err(_) => Err(Md2DbError::repository("", format!("database ping timed out after {:?}", HEALTH_PING_TIMEOUT))),
+    };
+
+    let (status, error) = match ping {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => {
+            tracing::warn!("Health check failed: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, Some(e.to_string()))
+        }
+    };
+    let state = if error.is_none() { "ok" } else { "unavailable" };
+
+    (
+        status,
+        Json(HealthResponse {
+            status: state.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            database: state.to_string(),
+            error,
+        }),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::database::MockRepository;
-    use axum::{
-        body::Body,
-        http::{self, StatusCode},
-    };
-    use tower::ServiceExt;
+    use axum::http::StatusCode;
 
     #[tokio::test]
     async fn test_health_check() {
-        let response = health_check().await;
+        let repo = Arc::new(MockRepository::new()) as Arc<dyn QuestionRepository>;
+        let (status, response) = health_check(State(repo)).await;
+        assert_eq!(status, StatusCode::OK);
         assert_eq!(response.status, "ok");
+        assert_eq!(response.database, "ok");
+        assert!(response.error.is_none());
         assert!(!response.version.is_empty());
     }
 
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_health_check_reports_unreachable_database() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(200))
+            .connect_lazy("postgres://md2db@127.0.0.1:1/md2db")
+            .unwrap();
+        let repo = Arc::new(crate::database::postgres::PostgresRepository::with_pool(pool))
+            as Arc<dyn QuestionRepository>;
+
+        let (status, response) = health_check(State(repo)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.status, "unavailable");
+        assert_eq!(response.database, "unavailable");
+        assert!(response.error.is_some());
+    }
+
     #[tokio::test]
     async fn test_root_handler() {
         let response = root_handler().await;
@@ -224,9 +2450,16 @@ mod tests {
         let repo = Arc::new(MockRepository::new()) as Arc<dyn QuestionRepository>;
         let req = ParseRequest {
             markdown: "# Test\n\n* A. Option1\n* B. Option2".to_string(),
+            dedup: false,
+            preset: None,
+            bank_id: None,
+            classify: false,
+            language: None,
+            default_type: None,
+            dry_run: false,
         };
 
-        let result = parse_markdown_endpoint(State(repo), Json(req)).await;
+        let result = parse_markdown_endpoint(State(repo), Query(ImportQuery::default()), Json(req)).await;
         assert!(result.is_ok());
 
         let response = result.unwrap();
@@ -250,9 +2483,16 @@ mod tests {
 * C. Option 3
 * D. Option 4
 "#.to_string(),
+            dedup: false,
+            preset: None,
+            bank_id: None,
+            classify: false,
+            language: None,
+            default_type: None,
+            dry_run: false,
         };
 
-        let result = parse_markdown_endpoint(State(repo), Json(req)).await;
+        let result = parse_markdown_endpoint(State(repo), Query(ImportQuery::default()), Json(req)).await;
         assert!(result.is_ok());
 
         let response = result.unwrap();
@@ -276,6 +2516,14 @@ mod tests {
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[tokio::test]
+    async fn test_not_found_error_into_response() {
+        let error = ApiError::NotFound("missing".to_string());
+        let response = error.into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_invalid_file_error_into_response() {
         let error = ApiError::InvalidFile("Invalid file".to_string());
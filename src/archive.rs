@@ -0,0 +1,509 @@
+//! Archive formats accepted for batch imports
+//!
+//! Uploads are recognized by their leading bytes, so a mislabelled file still
+//! opens with the right reader. Each format has an [`ArchiveProcessor`] that
+//! lists its regular files; extraction limits, nesting and spilling are the
+//! same for all of them and live in [`ZipProcessor`](crate::zip::ZipProcessor).
+//!
+//! ZIP and tar (plain, `.tar.gz` or `.tgz`) are always available. 7z needs
+//! the `sevenz` feature, which is on by default. RAR needs the `rar` feature
+//! and the `bsdtar` command from libarchive, as there is no pure-Rust RAR
+//! decoder.
+
+use crate::zip::{entry_name, ZipEntryMetadata};
+use anyhow::{bail, Result};
+use chrono::DateTime;
+use std::fmt;
+use std::io::{Read, Seek};
+
+/// Bytes needed by [`ArchiveFormat::detect`] to recognize every format
+pub const DETECT_LEN: usize = 262;
+
+/// A supported archive format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// ZIP, including archives written by Windows and macOS
+    Zip,
+    /// Uncompressed tar
+    Tar,
+    /// Gzip-compressed tar (`.tar.gz`, `.tgz`)
+    TarGz,
+    /// 7-Zip
+    SevenZip,
+    /// RAR 4 and 5
+    Rar,
+}
+
+impl ArchiveFormat {
+    /// Every archive format
+    pub const ALL: [ArchiveFormat; 5] = [
+        ArchiveFormat::Zip,
+        ArchiveFormat::Tar,
+        ArchiveFormat::TarGz,
+        ArchiveFormat::SevenZip,
+        ArchiveFormat::Rar,
+    ];
+
+    /// Recognize an archive by its first [`DETECT_LEN`] bytes
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if head.starts_with(b"\x1f\x8b") {
+            Some(ArchiveFormat::TarGz)
+        } else if head.starts_with(b"7z\xbc\xaf\x27\x1c") {
+            Some(ArchiveFormat::SevenZip)
+        } else if head.starts_with(b"Rar!\x1a\x07") {
+            Some(ArchiveFormat::Rar)
+        } else if head.get(257..262) == Some(b"ustar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// Recognize an archive by its file name
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extensions().iter().any(|ext| name.ends_with(ext)))
+    }
+
+    /// File name extensions, with the leading dot
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            ArchiveFormat::Zip => &[".zip"],
+            ArchiveFormat::Tar => &[".tar"],
+            ArchiveFormat::TarGz => &[".tar.gz", ".tgz"],
+            ArchiveFormat::SevenZip => &[".7z"],
+            ArchiveFormat::Rar => &[".rar"],
+        }
+    }
+
+    /// Whether this build can read the format
+    pub fn is_enabled(self) -> bool {
+        match self {
+            ArchiveFormat::SevenZip => cfg!(feature = "sevenz"),
+            ArchiveFormat::Rar => cfg!(feature = "rar"),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArchiveFormat::Zip => "ZIP",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::SevenZip => "7z",
+            ArchiveFormat::Rar => "RAR",
+        })
+    }
+}
+
+/// Extensions of every format this build can read, e.g. for error messages
+pub fn supported_extensions() -> Vec<&'static str> {
+    ArchiveFormat::ALL
+        .into_iter()
+        .filter(|format| format.is_enabled())
+        .flat_map(|format| format.extensions().iter().copied())
+        .collect()
+}
+
+/// A seekable archive source
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// One regular file read from an archive
+pub struct ArchiveFile<'a> {
+    /// Path inside the archive
+    pub name: String,
+    /// Whether the name was recovered as GB18030 rather than read as UTF-8
+    pub gb18030_name: bool,
+    /// Uncompressed size declared by the archive (0 if unknown)
+    pub size: u64,
+    /// Sizes, CRC and modification time, as far as the format records them
+    pub metadata: ZipEntryMetadata,
+    /// The file's content
+    pub reader: &'a mut dyn Read,
+}
+
+/// Reads the files of one archive format
+pub trait ArchiveProcessor: Send + Sync {
+    /// Format read by this processor
+    fn format(&self) -> ArchiveFormat;
+
+    /// Call `visit` for each regular file in `source`, in archive order
+    ///
+    /// Directories, links and other special entries are skipped. Stops at
+    /// the first error from the archive or from `visit`.
+    fn for_each_file(
+        &self,
+        source: &mut dyn ReadSeek,
+        visit: &mut dyn FnMut(ArchiveFile<'_>) -> Result<()>,
+    ) -> Result<()>;
+}
+
+/// The processor for `format`, if this build supports it
+pub fn archive_processor(format: ArchiveFormat) -> Result<Box<dyn ArchiveProcessor>> {
+    match format {
+        ArchiveFormat::Zip => Ok(Box::new(ZipArchiveProcessor)),
+        ArchiveFormat::Tar => Ok(Box::new(TarArchiveProcessor { gzip: false })),
+        ArchiveFormat::TarGz => Ok(Box::new(TarArchiveProcessor { gzip: true })),
+        #[cfg(feature = "sevenz")]
+        ArchiveFormat::SevenZip => Ok(Box::new(SevenZipArchiveProcessor)),
+        #[cfg(feature = "rar")]
+        ArchiveFormat::Rar => Ok(Box::new(RarArchiveProcessor)),
+        #[allow(unreachable_patterns)]
+        other => bail!("{} archives are not supported by this build", other),
+    }
+}
+
+/// ZIP archives, via the `zip` crate
+pub struct ZipArchiveProcessor;
+
+impl ArchiveProcessor for ZipArchiveProcessor {
+    fn format(&self) -> ArchiveFormat {
+        ArchiveFormat::Zip
+    }
+
+    fn for_each_file(
+        &self,
+        source: &mut dyn ReadSeek,
+        visit: &mut dyn FnMut(ArchiveFile<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let mut archive = ::zip::ZipArchive::new(source)?;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let (name, gb18030_name) = entry_name(file.name_raw(), file.name());
+            let metadata = ZipEntryMetadata::from_zip_file(&file);
+            visit(ArchiveFile {
+                name,
+                gb18030_name,
+                size: file.size(),
+                metadata,
+                reader: &mut file,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Tar archives, plain or gzip-compressed
+pub struct TarArchiveProcessor {
+    /// Whether the tar stream is gzip-compressed
+    pub gzip: bool,
+}
+
+impl ArchiveProcessor for TarArchiveProcessor {
+    fn format(&self) -> ArchiveFormat {
+        if self.gzip {
+            ArchiveFormat::TarGz
+        } else {
+            ArchiveFormat::Tar
+        }
+    }
+
+    fn for_each_file(
+        &self,
+        source: &mut dyn ReadSeek,
+        visit: &mut dyn FnMut(ArchiveFile<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let stream: Box<dyn Read + '_> = if self.gzip {
+            Box::new(flate2::read::MultiGzDecoder::new(source))
+        } else {
+            Box::new(source)
+        };
+        let mut archive = tar::Archive::new(stream);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let raw = entry.path_bytes().into_owned();
+            let (name, gb18030_name) = entry_name(&raw, &String::from_utf8_lossy(&raw));
+            let size = entry.size();
+            let metadata = ZipEntryMetadata {
+                compressed_size: size,
+                uncompressed_size: size,
+                crc32: 0,
+                modified_at: entry
+                    .header()
+                    .mtime()
+                    .ok()
+                    .and_then(|mtime| DateTime::from_timestamp(i64::try_from(mtime).ok()?, 0)),
+            };
+            visit(ArchiveFile {
+                name,
+                gb18030_name,
+                size,
+                metadata,
+                reader: &mut entry,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// 7z archives, via `sevenz-rust`
+#[cfg(feature = "sevenz")]
+pub struct SevenZipArchiveProcessor;
+
+#[cfg(feature = "sevenz")]
+impl ArchiveProcessor for SevenZipArchiveProcessor {
+    fn format(&self) -> ArchiveFormat {
+        ArchiveFormat::SevenZip
+    }
+
+    fn for_each_file(
+        &self,
+        source: &mut dyn ReadSeek,
+        visit: &mut dyn FnMut(ArchiveFile<'_>) -> Result<()>,
+    ) -> Result<()> {
+        use sevenz_rust::{Password, SevenZReader};
+        use std::io::SeekFrom;
+
+        let len = source.seek(SeekFrom::End(0))?;
+        source.rewind()?;
+        let mut archive = SevenZReader::new(source, len, Password::empty())
+            .map_err(|e| anyhow::anyhow!("invalid 7z archive: {}", e))?;
+
+        // The reader's callback cannot carry our errors, so stop it and keep
+        // the first one aside
+        let mut failed = None;
+        archive
+            .for_each_entries(|entry, reader| {
+                if failed.is_some() {
+                    return Ok(false);
+                }
+                if entry.is_directory() || entry.is_anti_item {
+                    return Ok(true);
+                }
+                let metadata = ZipEntryMetadata {
+                    compressed_size: entry.compressed_size,
+                    uncompressed_size: entry.size(),
+                    crc32: entry.crc as u32,
+                    modified_at: entry
+                        .has_last_modified_date
+                        .then(|| DateTime::from_timestamp(entry.last_modified_date().to_unix_time(), 0))
+                        .flatten(),
+                };
+                let visited = visit(ArchiveFile {
+                    name: entry.name().to_string(),
+                    gb18030_name: false,
+                    size: entry.size(),
+                    metadata,
+                    reader: &mut *reader,
+                });
+                match visited {
+                    // Files in a solid block follow each other in one stream
+                    Ok(()) => std::io::copy(reader, &mut std::io::sink()).map(|_| true).map_err(Into::into),
+                    Err(e) => {
+                        failed = Some(e);
+                        Ok(false)
+                    }
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("invalid 7z archive: {}", e))?;
+
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Command used to read RAR archives
+#[cfg(feature = "rar")]
+const BSDTAR: &str = "bsdtar";
+
+/// RAR archives, via the `bsdtar` command
+///
+/// The archive is copied to a temporary file, listed, and each file streamed
+/// from `bsdtar -xO`, so the extraction budget still applies. Sizes and
+/// timestamps are not reported.
+#[cfg(feature = "rar")]
+pub struct RarArchiveProcessor;
+
+#[cfg(feature = "rar")]
+impl ArchiveProcessor for RarArchiveProcessor {
+    fn format(&self) -> ArchiveFormat {
+        ArchiveFormat::Rar
+    }
+
+    fn for_each_file(
+        &self,
+        source: &mut dyn ReadSeek,
+        visit: &mut dyn FnMut(ArchiveFile<'_>) -> Result<()>,
+    ) -> Result<()> {
+        use anyhow::Context;
+        use std::process::{Command, Stdio};
+
+        let mut copy = tempfile::NamedTempFile::new()?;
+        std::io::copy(source, &mut copy)?;
+
+        let list = Command::new(BSDTAR)
+            .arg("-tf")
+            .arg(copy.path())
+            .output()
+            .with_context(|| format!("Failed to run {}", BSDTAR))?;
+        if !list.status.success() {
+            bail!("invalid RAR archive: {}", String::from_utf8_lossy(&list.stderr).trim());
+        }
+
+        for name in String::from_utf8_lossy(&list.stdout).lines() {
+            if name.is_empty() || name.ends_with('/') {
+                continue;
+            }
+            let mut child = Command::new(BSDTAR)
+                .arg("-xOqf")
+                .arg(copy.path())
+                .arg(escape_pattern(name))
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .with_context(|| format!("Failed to run {}", BSDTAR))?;
+            let mut stdout = child.stdout.take().context("bsdtar has no stdout")?;
+
+            let visited = visit(ArchiveFile {
+                name: name.to_string(),
+                gb18030_name: false,
+                size: 0,
+                metadata: ZipEntryMetadata::default(),
+                reader: &mut stdout,
+            });
+            drop(stdout);
+            if visited.is_err() {
+                let _ = child.kill();
+            }
+            let status = child.wait()?;
+            visited?;
+            if !status.success() {
+                bail!("{} could not extract {}", BSDTAR, name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escape wildcards so `bsdtar` matches `name` literally
+#[cfg(feature = "rar")]
+fn escape_pattern(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Build a gzip-compressed tarball in memory
+#[cfg(test)]
+pub(crate) fn build_tar_gz(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(1_700_000_000);
+        header.set_cksum();
+        builder.append_data(&mut header, name, *data).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn files(processor: &dyn ArchiveProcessor, data: Vec<u8>) -> Vec<(String, Vec<u8>)> {
+        let mut files = Vec::new();
+        processor
+            .for_each_file(&mut Cursor::new(data), &mut |file| {
+                let mut content = Vec::new();
+                file.reader.read_to_end(&mut content)?;
+                files.push((file.name, content));
+                Ok(())
+            })
+            .unwrap();
+        files
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(ArchiveFormat::detect(b"PK\x03\x04rest"), Some(ArchiveFormat::Zip));
+        assert_eq!(ArchiveFormat::detect(b"\x1f\x8b\x08"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::detect(b"7z\xbc\xaf\x27\x1c\x00\x04"), Some(ArchiveFormat::SevenZip));
+        assert_eq!(ArchiveFormat::detect(b"Rar!\x1a\x07\x01\x00"), Some(ArchiveFormat::Rar));
+        let mut tar = vec![0u8; DETECT_LEN];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(ArchiveFormat::detect(&tar), Some(ArchiveFormat::Tar));
+        assert_eq!(ArchiveFormat::detect(b"# Markdown"), None);
+
+        assert_eq!(ArchiveFormat::from_name("Exam.ZIP"), Some(ArchiveFormat::Zip));
+        assert_eq!(ArchiveFormat::from_name("exam.tar.gz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_name("exam.tgz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_name("exam.tar"), Some(ArchiveFormat::Tar));
+        assert_eq!(ArchiveFormat::from_name("exam.rar"), Some(ArchiveFormat::Rar));
+        assert_eq!(ArchiveFormat::from_name("exam.md"), None);
+        assert!(supported_extensions().contains(&".tgz"));
+    }
+
+    #[test]
+    fn test_tar_gz_files() {
+        let data = build_tar_gz(&[("exam/a.md", b"# Q1"), ("exam/img/a.png", b"png")]);
+        assert_eq!(ArchiveFormat::detect(&data), Some(ArchiveFormat::TarGz));
+
+        let processor = archive_processor(ArchiveFormat::TarGz).unwrap();
+        assert_eq!(
+            files(processor.as_ref(), data),
+            [("exam/a.md".to_string(), b"# Q1".to_vec()), ("exam/img/a.png".to_string(), b"png".to_vec())]
+        );
+    }
+
+    #[cfg(feature = "sevenz")]
+    #[test]
+    fn test_seven_zip_files() {
+        use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+
+        let mut writer = SevenZWriter::new(Cursor::new(Vec::new())).unwrap();
+        for (name, data) in [("exam/a.md", &b"# Q1"[..]), ("exam/b.md", b"# Q2")] {
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = name.to_string();
+            writer.push_archive_entry(entry, Some(data)).unwrap();
+        }
+        let data = writer.finish().unwrap().into_inner();
+        assert_eq!(ArchiveFormat::detect(&data), Some(ArchiveFormat::SevenZip));
+
+        let processor = archive_processor(ArchiveFormat::SevenZip).unwrap();
+        assert_eq!(
+            files(processor.as_ref(), data),
+            [("exam/a.md".to_string(), b"# Q1".to_vec()), ("exam/b.md".to_string(), b"# Q2".to_vec())]
+        );
+    }
+
+    #[cfg(feature = "rar")]
+    #[test]
+    fn test_rar_processor_streams_through_bsdtar() {
+        // bsdtar reads any libarchive format, so a tarball stands in for RAR
+        let data = build_tar_gz(&[("exam/[1] a.md", b"# Q1")]);
+        let processor = archive_processor(ArchiveFormat::Rar).unwrap();
+        assert_eq!(files(processor.as_ref(), data), [("exam/[1] a.md".to_string(), b"# Q1".to_vec())]);
+    }
+
+    #[cfg(not(feature = "rar"))]
+    #[test]
+    fn test_disabled_format_is_reported() {
+        let err = archive_processor(ArchiveFormat::Rar).err().unwrap();
+        assert_eq!(err.to_string(), "RAR archives are not supported by this build");
+    }
+}
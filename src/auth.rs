@@ -0,0 +1,286 @@
+//! API authentication and rate limiting
+//!
+//! [`Authenticator`] checks the credentials of API requests: static API keys,
+//! sent as `Authorization: Bearer <key>` or `X-API-Key: <key>`, and JWTs
+//! signed with a shared HS256 secret, sent as a bearer token. The server
+//! installs [`require_auth`] as middleware once keys or a secret are
+//! configured. Requests that change data always need credentials; reads only
+//! with [`Authenticator::with_protected_reads`]. Each key, or each JWT
+//! subject, can be limited to a number of requests per minute.
+
+use crate::api::ApiError;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Header carrying an API key, as an alternative to a bearer token
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Paths served without credentials, so health checks keep working
+const PUBLIC_PATHS: [&str; 1] = ["/health"];
+
+/// `POST` paths that change nothing and are guarded like reads
+const READ_ONLY_POSTS: [&str; 2] = ["/preview", "/validate"];
+
+/// Who made an authenticated request, added to the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal(pub String);
+
+/// Checks API keys and JWTs and applies per-key rate limits
+#[derive(Debug, Default)]
+pub struct Authenticator {
+    /// SHA-256 of each key, with the key's name
+    keys: Vec<([u8; 32], String)>,
+    jwt_secret: Option<Vec<u8>>,
+    protect_reads: bool,
+    limiter: Option<RateLimiter>,
+}
+
+impl Authenticator {
+    /// Accept no credentials until a key or secret is added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `key`, reporting requests made with it as `name`
+    pub fn with_key(mut self, name: impl Into<String>, key: &str) -> Self {
+        self.keys.push((Sha256::digest(key.as_bytes()).into(), name.into()));
+        self
+    }
+
+    /// Accept JWTs signed with HS256 and `secret`
+    ///
+    /// Tokens must carry an `exp` claim; the `sub` claim names the caller
+    /// for rate limiting.
+    pub fn with_jwt_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.jwt_secret = Some(secret.as_ref().to_vec());
+        self
+    }
+
+    /// Require credentials for reads as well as changes
+    pub fn with_protected_reads(mut self, enabled: bool) -> Self {
+        self.protect_reads = enabled;
+        self
+    }
+
+    /// Allow each caller `per_minute` requests per minute (0 for no limit)
+    pub fn with_rate_limit(mut self, per_minute: u32) -> Self {
+        self.limiter = (per_minute > 0).then(|| RateLimiter::new(per_minute));
+        self
+    }
+
+    /// Whether any credentials are configured
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty() || self.jwt_secret.is_some()
+    }
+
+    /// Name of the caller presenting `token`, if it is a known key or a valid JWT
+    pub fn authenticate(&self, token: &str) -> Option<String> {
+        let digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        // Compare against every key so the time taken does not tell which matched
+        let mut matched = None;
+        for (key, name) in &self.keys {
+            if constant_time_eq(key, &digest) {
+                matched = Some(name.clone());
+            }
+        }
+        if matched.is_some() {
+            return matched;
+        }
+
+        let secret = self.jwt_secret.as_deref()?;
+        verify_jwt(secret, token, chrono::Utc::now().timestamp())
+    }
+
+    /// Whether a request needs credentials
+    fn requires_credentials(&self, method: &Method, path: &str) -> bool {
+        if PUBLIC_PATHS.contains(&path) {
+            return false;
+        }
+        let read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+            || (*method == Method::POST && READ_ONLY_POSTS.contains(&path));
+        !read || self.protect_reads
+    }
+}
+
+/// Middleware rejecting requests without valid credentials
+///
+/// Answers `401` when credentials are needed but missing or invalid and
+/// `429` with `Retry-After` when the caller is over its rate limit.
+pub async fn require_auth(State(auth): State<Arc<Authenticator>>, mut request: Request, next: Next) -> Response {
+    if !auth.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let principal = credential(request.headers()).and_then(|token| auth.authenticate(token));
+    let Some(principal) = principal else {
+        if !auth.requires_credentials(request.method(), request.uri().path()) {
+            return next.run(request).await;
+        }
+        let message = match credential(request.headers()) {
+            Some(_) => "Invalid API key or token",
+            None => "An API key or bearer token is required",
+        };
+        let mut response = ApiError::Unauthorized(message.to_string()).into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+
+    if let Some(limiter) = &auth.limiter {
+        if let Err(wait) = limiter.check(&principal, Instant::now()) {
+            let mut response = ApiError::RateLimited(format!("Rate limit exceeded for {}", principal)).into_response();
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+            return response;
+        }
+    }
+
+    request.extensions_mut().insert(Principal(principal));
+    next.run(request).await
+}
+
+/// The bearer token or API key sent with a request
+fn credential(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    bearer
+        .or_else(|| headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    sub: Option<String>,
+    exp: i64,
+    nbf: Option<i64>,
+}
+
+/// Subject of an HS256 JWT signed with `secret` and valid at `now`
+fn verify_jwt(secret: &[u8], token: &str, now: i64) -> Option<String> {
+    let (signed, signature) = token.rsplit_once('.')?;
+    let (header, claims) = signed.split_once('.')?;
+    if claims.contains('.') {
+        return None;
+    }
+
+    let header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).ok()?).ok()?;
+    if header.alg != "HS256" {
+        return None;
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).ok()?;
+    mac.update(signed.as_bytes());
+    mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?).ok()?;
+
+    let claims: JwtClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).ok()?).ok()?;
+    if claims.exp <= now || claims.nbf.is_some_and(|nbf| nbf > now) {
+        return None;
+    }
+    Some(claims.sub.unwrap_or_else(|| "jwt".to_string()))
+}
+
+/// Token buckets per caller, refilled continuously
+#[derive(Debug)]
+struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+impl RateLimiter {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Take a request from `key`'s bucket, or return how long until one is free
+    fn check(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let capacity = self.per_minute as f64;
+        let per_second = capacity / 60.0;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let (tokens, updated) = buckets.entry(key.to_string()).or_insert((capacity, now));
+        *tokens = (*tokens + now.saturating_duration_since(*updated).as_secs_f64() * per_second).min(capacity);
+        *updated = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - *tokens) / per_second))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt(secret: &[u8], claims: serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(format!("{}.{}", header, claims).as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{}.{}.{}", header, claims, signature)
+    }
+
+    #[test]
+    fn test_keys_and_jwts() {
+        let auth = Authenticator::new().with_key("ci", "k-123").with_jwt_secret("s3cret");
+        assert_eq!(auth.authenticate("k-123").as_deref(), Some("ci"));
+        assert_eq!(auth.authenticate("k-124"), None);
+
+        let now = chrono::Utc::now().timestamp();
+        let token = jwt(b"s3cret", serde_json::json!({ "sub": "alice", "exp": now + 60 }));
+        assert_eq!(auth.authenticate(&token).as_deref(), Some("alice"));
+        let expired = jwt(b"s3cret", serde_json::json!({ "sub": "alice", "exp": now - 1 }));
+        assert_eq!(auth.authenticate(&expired), None);
+        let forged = jwt(b"guess", serde_json::json!({ "sub": "alice", "exp": now + 60 }));
+        assert_eq!(auth.authenticate(&forged), None);
+        let no_expiry = jwt(b"s3cret", serde_json::json!({ "sub": "alice" }));
+        assert_eq!(auth.authenticate(&no_expiry), None);
+
+        assert!(!Authenticator::new().is_enabled());
+        assert!(auth.requires_credentials(&Method::POST, "/parse"));
+        assert!(!auth.requires_credentials(&Method::GET, "/questions"));
+        assert!(!auth.requires_credentials(&Method::POST, "/preview"));
+        let auth = auth.with_protected_reads(true);
+        assert!(auth.requires_credentials(&Method::GET, "/questions"));
+        assert!(!auth.requires_credentials(&Method::GET, "/health"));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limiter = RateLimiter::new(2);
+        let start = Instant::now();
+        assert!(limiter.check("ci", start).is_ok());
+        assert!(limiter.check("ci", start).is_ok());
+        let wait = limiter.check("ci", start).unwrap_err();
+        assert_eq!(wait.as_secs(), 30);
+        // Other keys have their own bucket, and tokens come back over time
+        assert!(limiter.check("alice", start).is_ok());
+        assert!(limiter.check("ci", start + Duration::from_secs(30)).is_ok());
+    }
+}
@@ -0,0 +1,219 @@
+//! Bidirectional text handling
+//!
+//! Arabic and Hebrew question banks mix right-to-left prose with left-to-right
+//! math and Latin identifiers. This module detects the dominant direction of a
+//! question and normalizes text so that exports and previews keep formulas and
+//! Latin runs intact inside RTL paragraphs.
+
+use crate::models::{Question, TextDirection};
+use std::borrow::Cow;
+
+/// Left-to-right isolate (U+2066)
+pub const LRI: char = '\u{2066}';
+/// Pop directional isolate (U+2069)
+pub const PDI: char = '\u{2069}';
+
+/// Whether a character is a strong right-to-left character
+pub fn is_rtl_char(c: char) -> bool {
+    matches!(c as u32,
+        0x0590..=0x05FF   // Hebrew
+        | 0x0600..=0x06FF // Arabic
+        | 0x0700..=0x074F // Syriac
+        | 0x0750..=0x077F // Arabic Supplement
+        | 0x0780..=0x07BF // Thaana
+        | 0x08A0..=0x08FF // Arabic Extended-A
+        | 0xFB1D..=0xFDFF // Hebrew/Arabic presentation forms
+        | 0xFE70..=0xFEFF // Arabic presentation forms B
+    )
+}
+
+/// Whether a character is a strong left-to-right character
+fn is_ltr_char(c: char) -> bool {
+    c.is_alphabetic() && !is_rtl_char(c)
+}
+
+/// Detect the dominant direction of a piece of text
+///
+/// Text is `Rtl` when every strong character is RTL, `Mixed` when RTL characters
+/// are present alongside LTR ones, and `Ltr` otherwise. LaTeX spans are ignored
+/// since formulas are always laid out left to right.
+pub fn detect_direction(text: &str) -> TextDirection {
+    let mut rtl = 0usize;
+    let mut ltr = 0usize;
+
+    for (segment, is_math) in split_math(text) {
+        if is_math {
+            continue;
+        }
+        for c in segment.chars() {
+            if is_rtl_char(c) {
+                rtl += 1;
+            } else if is_ltr_char(c) {
+                ltr += 1;
+            }
+        }
+    }
+
+    match (rtl, ltr) {
+        (0, _) => TextDirection::Ltr,
+        (_, 0) => TextDirection::Rtl,
+        _ => TextDirection::Mixed,
+    }
+}
+
+/// Detect the direction of a whole question from its stem and options
+pub fn detect_question_direction(question: &Question) -> TextDirection {
+    // Most questions have no RTL text at all; skip building the combined text
+    let fields = std::iter::once(&question.stem).chain(question.options.iter().map(|o| &o.content));
+    if !fields.flat_map(|text| text.chars()).any(is_rtl_char) {
+        return TextDirection::Ltr;
+    }
+    let mut text = question.stem.clone();
+    for option in &question.options {
+        text.push(' ');
+        text.push_str(&option.content);
+    }
+    detect_direction(&text)
+}
+
+/// Remove legacy embedding/override controls (U+202A..U+202E)
+///
+/// These are frequently left behind by word processors, are not balanced
+/// across our field boundaries and scramble rendering once text is split into
+/// stem and options. Isolates and marks are kept.
+pub fn strip_embedding_controls(text: &str) -> String {
+    text.chars()
+        .filter(|c| !matches!(*c as u32, 0x202A..=0x202E))
+        .collect()
+}
+
+/// Wrap LaTeX spans in LTR isolates so they render correctly in RTL context
+pub fn isolate_math(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    for (segment, is_math) in split_math(text) {
+        if is_math {
+            out.push(LRI);
+            out.push_str(segment);
+            out.push(PDI);
+        } else {
+            out.push_str(segment);
+        }
+    }
+    out
+}
+
+/// `text` from `question` as exporters write it
+///
+/// Formulas in questions that are not purely left to right are isolated
+/// ([`isolate_math`]), as in previews; [`normalize_question`] removes the
+/// isolates again when the export is imported.
+pub fn export_text<'a>(question: &Question, text: &'a str) -> Cow<'a, str> {
+    if question.text_direction == TextDirection::Ltr {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(isolate_math(text))
+    }
+}
+
+/// Normalize question text in place and record its direction
+pub fn normalize_question(question: &mut Question) {
+    strip_embedding_controls_in_place(&mut question.stem);
+    for option in &mut question.options {
+        strip_embedding_controls_in_place(&mut option.content);
+    }
+    if let Some(analysis) = &mut question.analysis {
+        strip_embedding_controls_in_place(analysis);
+    }
+    question.text_direction = detect_question_direction(question);
+}
+
+/// [`strip_embedding_controls`] without reallocating text that has none
+///
+/// Also drops the isolates [`isolate_math`] puts around formulas.
+fn strip_embedding_controls_in_place(text: &mut String) {
+    let is_control = |c: char| matches!(c as u32, 0x202A..=0x202E);
+    if text.contains(is_control) {
+        text.retain(|c| !is_control(c));
+    }
+    if text.contains(LRI) {
+        *text = text.replace(&format!("{}$", LRI), "$").replace(&format!("${}", PDI), "$");
+    }
+}
+
+/// Split text into (segment, is_math) pieces on `$...$` / `$$...$$` delimiters
+pub(crate) fn split_math(text: &str) -> Vec<(&str, bool)> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        let delim = if rest[start..].starts_with("$$") { "$$" } else { "$" };
+        let body_start = start + delim.len();
+        match rest[body_start..].find(delim) {
+            Some(len) => {
+                let end = body_start + len + delim.len();
+                if start > 0 {
+                    segments.push((&rest[..start], false));
+                }
+                segments.push((&rest[start..end], true));
+                rest = &rest[end..];
+            }
+            None => break,
+        }
+    }
+
+    if !rest.is_empty() {
+        segments.push((rest, false));
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_direction() {
+        assert_eq!(detect_direction("What is 2+2?"), TextDirection::Ltr);
+        assert_eq!(detect_direction("以下哪个正确"), TextDirection::Ltr);
+        assert_eq!(detect_direction("ما هو الجواب؟"), TextDirection::Rtl);
+        assert_eq!(detect_direction("מה התשובה"), TextDirection::Rtl);
+        assert_eq!(detect_direction("احسب $x^2 + y$ الآن"), TextDirection::Rtl);
+        assert_eq!(detect_direction("احسب f(x) الآن"), TextDirection::Mixed);
+    }
+
+    #[test]
+    fn test_isolate_math() {
+        let text = "احسب $x^2$ الآن";
+        assert_eq!(isolate_math(text), "احسب \u{2066}$x^2$\u{2069} الآن");
+        assert_eq!(isolate_math("no math"), "no math");
+    }
+
+    #[test]
+    fn test_export_text_round_trips() {
+        let mut question = Question {
+            stem: "احسب $x^2$ و $$y$$ الآن".to_string(),
+            ..Default::default()
+        };
+        normalize_question(&mut question);
+        let exported = export_text(&question, &question.stem).into_owned();
+        assert_eq!(exported, "احسب \u{2066}$x^2$\u{2069} و \u{2066}$$y$$\u{2069} الآن");
+
+        let mut imported = Question { stem: exported, ..Default::default() };
+        normalize_question(&mut imported);
+        assert_eq!(imported.stem, question.stem);
+
+        let latin = Question { stem: "Compute $x^2$".to_string(), ..Default::default() };
+        assert!(matches!(export_text(&latin, &latin.stem), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_normalize_question() {
+        let mut question = Question {
+            stem: "\u{202B}ما هو الجواب؟\u{202C}".to_string(),
+            ..Default::default()
+        };
+        normalize_question(&mut question);
+        assert_eq!(question.stem, "ما هو الجواب؟");
+        assert_eq!(question.text_direction, TextDirection::Rtl);
+    }
+}
@@ -0,0 +1,95 @@
+//! Synchronous wrappers for callers without an async runtime
+//!
+//! The library is async throughout, which suits the API server but forces
+//! CLI tools and non-Tokio applications to set up a runtime just to import
+//! an archive. The functions here do that for them: each call starts a
+//! small Tokio runtime on the calling thread, runs the async version to
+//! completion and shuts the runtime down again.
+//!
+//! ```no_run
+//! use md2db::blocking::process_zip_blocking;
+//!
+//! let data = std::fs::read("exam.zip").unwrap();
+//! let result = process_zip_blocking(&data).unwrap();
+//! println!("{} questions", result.questions.len());
+//! ```
+//!
+//! Blocking calls made from inside an async runtime would stall it, so they
+//! fail instead; async code should call the async functions directly.
+
+use crate::database::QuestionRepository;
+use crate::error::Md2DbError;
+use crate::processor::{InputSource, ProcessResult, SingleMachineProcessor};
+use crate::zip::{ZipProcessResult, ZipProcessor};
+use std::future::Future;
+
+/// Parse an archive like [`crate::parse_markdown_zip`], blocking until done
+pub fn process_zip_blocking(data: &[u8]) -> Result<ZipProcessResult, Md2DbError> {
+    ZipProcessor::new().process_zip_blocking(data.to_vec())
+}
+
+impl ZipProcessor {
+    /// Blocking version of [`ZipProcessor::process_zip`]
+    pub fn process_zip_blocking(&self, zip_data: Vec<u8>) -> Result<ZipProcessResult, Md2DbError> {
+        block_on(self.process_zip(zip_data)).map_err(|e| Md2DbError::zip("starting a runtime", e))?
+    }
+}
+
+impl<R> SingleMachineProcessor<R>
+where
+    R: QuestionRepository + Send + Sync,
+{
+    /// Blocking version of [`SingleMachineProcessor::process`]
+    pub fn process_blocking(&self, input: InputSource) -> anyhow::Result<ProcessResult> {
+        block_on(self.process(input))?
+    }
+}
+
+/// Run `future` to completion on a runtime of its own
+///
+/// Fails when called from a thread that is already driving a runtime.
+pub fn block_on<F: Future>(future: F) -> std::io::Result<F::Output> {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(std::io::Error::other(
+            "blocking call made from inside an async runtime; use the async API instead",
+        ));
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    Ok(runtime.block_on(future))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MockRepository;
+    use std::io::Write;
+
+    #[test]
+    fn test_blocking_wrappers() {
+        let mut data = Vec::new();
+        {
+            let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(&mut data));
+            writer.start_file("exam.md", ::zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all("# 单选题\n\n1 + 1 = ?\n\n* A. 1\n* B. 2\n".as_bytes()).unwrap();
+            writer.finish().unwrap();
+        }
+        let result = process_zip_blocking(&data).unwrap();
+        assert_eq!(result.questions.len(), 1);
+        assert!(matches!(process_zip_blocking(b"not an archive"), Err(Md2DbError::ZipError { .. })));
+
+        let repo = MockRepository::new();
+        let processor = SingleMachineProcessor::new(repo);
+        let input = InputSource::Markdown {
+            content: "# 简答题\n\nExplain recursion.".to_string(),
+            source: "exam.md".to_string(),
+        };
+        let result = processor.process_blocking(input).unwrap();
+        assert_eq!(result.saved_questions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_inside_runtime_fails() {
+        let error = process_zip_blocking(b"PK").unwrap_err();
+        assert!(error.to_string().contains("inside an async runtime"), "{}", error);
+    }
+}
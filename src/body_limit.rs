@@ -0,0 +1,141 @@
+//! Request body limits and content-type checks
+//!
+//! Each route that takes a body is marked with a [`BodyKind`] in
+//! [`crate::api::create_router`]: JSON requests get a small cap and uploads
+//! (multipart forms and JSON Lines files) a large one, both set by [`BodyLimits`] (added as an extension; the defaults
+//! apply otherwise). [`check_body`] rejects a request whose declared length is
+//! over the cap, or whose `Content-Type` does not match, before any of the
+//! body is read, and stops bodies of unknown length once they pass the cap.
+//! Rejections are the usual JSON errors with `413` or `415`.
+
+use crate::api::ApiError;
+use axum::body::{Body, HttpBody};
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use http_body_util::Limited;
+
+/// Default cap on JSON request bodies
+pub const DEFAULT_MAX_JSON_BYTES: usize = 2 << 20;
+
+/// Default cap on uploads
+pub const DEFAULT_MAX_UPLOAD_BYTES: usize = 100 << 20;
+
+/// Caps on request bodies, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    /// Largest JSON body
+    pub json: usize,
+    /// Largest upload: a multipart form or JSON Lines file
+    pub upload: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            json: DEFAULT_MAX_JSON_BYTES,
+            upload: DEFAULT_MAX_UPLOAD_BYTES,
+        }
+    }
+}
+
+impl BodyLimits {
+    /// Set the JSON body cap
+    pub fn with_json(mut self, bytes: usize) -> Self {
+        self.json = bytes;
+        self
+    }
+
+    /// Set the upload cap
+    pub fn with_upload(mut self, bytes: usize) -> Self {
+        self.upload = bytes;
+        self
+    }
+}
+
+/// What a route expects in its request body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyKind {
+    /// `application/json`, capped at [`BodyLimits::json`]
+    Json,
+    /// `multipart/form-data`, capped at [`BodyLimits::upload`]
+    Multipart,
+    /// JSON Lines (`application/x-ndjson` or `application/jsonl`), capped at
+    /// [`BodyLimits::upload`] like other files
+    JsonLines,
+}
+
+impl BodyKind {
+    fn limit(self, limits: &BodyLimits) -> usize {
+        match self {
+            BodyKind::Json => limits.json,
+            BodyKind::Multipart | BodyKind::JsonLines => limits.upload,
+        }
+    }
+
+    fn accepts(self, content_type: &str) -> bool {
+        let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match self {
+            BodyKind::Json => essence == "application/json" || essence.ends_with("+json"),
+            BodyKind::Multipart => essence == "multipart/form-data",
+            BodyKind::JsonLines => essence == "application/x-ndjson" || essence == "application/jsonl",
+        }
+    }
+
+    fn expected(self) -> &'static str {
+        match self {
+            BodyKind::Json => "application/json",
+            BodyKind::Multipart => "multipart/form-data",
+            BodyKind::JsonLines => "application/x-ndjson",
+        }
+    }
+}
+
+/// Middleware enforcing the body kind and cap of a route
+///
+/// Requests without a body, such as `GET`s on the same path, pass through.
+pub async fn check_body(
+    State(kind): State<BodyKind>,
+    limits: Option<Extension<BodyLimits>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limits = limits.map(|Extension(limits)| limits).unwrap_or_default();
+    let limit = kind.limit(&limits);
+    let hint = request.body().size_hint();
+    if hint.upper() == Some(0) {
+        return next.run(request).await;
+    }
+
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !kind.accepts(content_type) {
+        let found = if content_type.is_empty() { "none" } else { content_type };
+        return ApiError::UnsupportedMediaType(format!("Expected {} content, got {}", kind.expected(), found))
+            .into_response();
+    }
+    if hint.lower() > limit as u64 {
+        return too_large(limit).into_response();
+    }
+
+    let request = request.map(|body| Body::new(Limited::new(body, limit)));
+    let response = next.run(request).await;
+    // Extractors that hit the cap answer with a plain-text 413
+    let json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !json {
+        return too_large(limit).into_response();
+    }
+    response
+}
+
+fn too_large(limit: usize) -> ApiError {
+    ApiError::PayloadTooLarge(format!("Request body is larger than the limit of {} bytes", limit))
+}
@@ -0,0 +1,655 @@
+//! Read-through caching for question repositories
+//!
+//! Exam clients fetch the same question sets over and over. [`CachedRepository`]
+//! wraps any [`QuestionRepository`] and keeps `find_by_id` / `find_by_type`
+//! results in a [`QuestionCache`] for a configurable TTL. Every write through
+//! the decorator invalidates the affected entries, so readers never see a
+//! question older than the last write made through this instance.
+//!
+//! Two backends are provided: [`MemoryCache`] for a single process and, with
+//! the `redis` feature, [`redis::RedisCache`] for caches shared between
+//! instances.
+
+use crate::database::{ImportSession, ListParams, Page, QuestionRepository};
+use crate::dedup::{DedupOptions, SaveOutcome};
+use crate::jobs::JobRecord;
+use crate::models::{ImportedSource, Question, QuestionBank, QuestionRevision, QuestionType};
+use crate::stats::QuestionStats;
+use crate::error::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Default time-to-live of cached entries
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Key-value store used by [`CachedRepository`]
+#[async_trait]
+pub trait QuestionCache: Send + Sync {
+    /// Fetch a value, or `None` if it is missing or expired
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Store a value that expires after `ttl`
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> anyhow::Result<()>;
+
+    /// Remove the given keys
+    async fn delete(&self, keys: &[String]) -> anyhow::Result<()>;
+}
+
+/// In-process cache with per-entry expiry
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: RwLock<HashMap<String, (Instant, Vec<u8>)>>,
+}
+
+impl MemoryCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl QuestionCache for MemoryCache {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let entries = self.entries.read().await;
+        Ok(entries
+            .get(key)
+            .filter(|(expires_at, _)| *expires_at > Instant::now())
+            .map(|(_, value)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> anyhow::Result<()> {
+        let mut entries = self.entries.write().await;
+        let now = Instant::now();
+        entries.retain(|_, (expires_at, _)| *expires_at > now);
+        entries.insert(key.to_string(), (now + ttl, value));
+        Ok(())
+    }
+
+    async fn delete(&self, keys: &[String]) -> anyhow::Result<()> {
+        let mut entries = self.entries.write().await;
+        for key in keys {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+}
+
+/// Cache key layout shared by the decorator and its import sessions
+#[derive(Clone)]
+struct Keys {
+    prefix: String,
+}
+
+impl Keys {
+    fn question(&self, id: Uuid) -> String {
+        format!("{}:question:{}", self.prefix, id)
+    }
+
+    fn by_type(&self, qtype: QuestionType) -> String {
+        let name = serde_json::to_string(&qtype).unwrap_or_default();
+        format!("{}:type:{}", self.prefix, name.trim_matches('"'))
+    }
+
+    /// Keys made stale by writing the given questions
+    fn for_questions<'a>(&self, questions: impl IntoIterator<Item = &'a Question>) -> Vec<String> {
+        let mut keys = Vec::new();
+        let mut types = Vec::new();
+        for q in questions {
+            keys.push(self.question(q.id));
+            if !types.contains(&q.qtype) {
+                types.push(q.qtype);
+            }
+        }
+        keys.extend(types.into_iter().map(|t| self.by_type(t)));
+        keys
+    }
+
+    /// Keys made stale by changing questions whose type is unknown
+    fn for_ids(&self, ids: impl IntoIterator<Item = Uuid>) -> Vec<String> {
+        let mut keys: Vec<String> = ids.into_iter().map(|id| self.question(id)).collect();
+        keys.extend(QuestionType::ALL.iter().map(|t| self.by_type(*t)));
+        keys
+    }
+}
+
+/// Drop stale entries; a failing cache is logged rather than failing the write
+async fn invalidate(cache: &dyn QuestionCache, keys: &[String]) {
+    if keys.is_empty() {
+        return;
+    }
+    if let Err(e) = cache.delete(keys).await {
+        warn!("Failed to invalidate {} cache entries: {}", keys.len(), e);
+    }
+}
+
+/// Repository decorator that caches `find_by_id` and `find_by_type`
+pub struct CachedRepository<R: ?Sized> {
+    inner: Arc<R>,
+    cache: Arc<dyn QuestionCache>,
+    ttl: Duration,
+    keys: Keys,
+}
+
+impl<R: QuestionRepository + ?Sized> CachedRepository<R> {
+    /// Wrap `inner`, caching results in `cache` for [`DEFAULT_CACHE_TTL`]
+    ///
+    /// `R` may be `dyn QuestionRepository`, so any configured backend can be wrapped.
+    pub fn new(inner: Arc<R>, cache: Arc<dyn QuestionCache>) -> Self {
+        Self {
+            inner,
+            cache,
+            ttl: DEFAULT_CACHE_TTL,
+            keys: Keys {
+                prefix: "md2db".to_string(),
+            },
+        }
+    }
+
+    /// Set the time-to-live of cached entries
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the key prefix, to share one cache between deployments
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.keys.prefix = prefix.into();
+        self
+    }
+
+    /// Get the wrapped repository
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Look up a cached value; cache failures and corrupt entries count as misses
+    async fn cached<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
+        match self.cache.get(key).await {
+            Ok(Some(bytes)) => serde_json::from_slice(&bytes).ok(),
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Cache read failed for {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    async fn store<T: serde::Serialize>(&self, key: &str, value: &T) {
+        let Ok(bytes) = serde_json::to_vec(value) else { return };
+        if let Err(e) = self.cache.set(key, bytes, self.ttl).await {
+            warn!("Cache write failed for {}: {}", key, e);
+        }
+    }
+}
+
+#[async_trait]
+impl<R: QuestionRepository + ?Sized> QuestionRepository for CachedRepository<R> {
+    async fn save_batch(&self, questions: &[Question]) -> Result<Vec<Uuid>> {
+        let ids = self.inner.save_batch(questions).await?;
+        invalidate(self.cache.as_ref(), &self.keys.for_questions(questions)).await;
+        Ok(ids)
+    }
+
+    async fn save_batch_dedup(&self, questions: &[Question], options: &DedupOptions) -> Result<Vec<SaveOutcome>> {
+        let outcomes = self.inner.save_batch_dedup(questions, options).await?;
+        invalidate(self.cache.as_ref(), &stale_keys(&self.keys, questions, &outcomes)).await;
+        Ok(outcomes)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Question>> {
+        let key = self.keys.question(id);
+        if let Some(question) = self.cached::<Question>(&key).await {
+            return Ok(Some(question));
+        }
+
+        let question = self.inner.find_by_id(id).await?;
+        if let Some(question) = &question {
+            self.store(&key, question).await;
+        }
+        Ok(question)
+    }
+
+    async fn find_by_type(&self, qtype: &QuestionType) -> Result<Vec<Question>> {
+        let key = self.keys.by_type(*qtype);
+        if let Some(questions) = self.cached::<Vec<Question>>(&key).await {
+            return Ok(questions);
+        }
+
+        let questions = self.inner.find_by_type(qtype).await?;
+        self.store(&key, &questions).await;
+        Ok(questions)
+    }
+
+    async fn find_by_tag(&self, tag: &str) -> Result<Vec<Question>> {
+        self.inner.find_by_tag(tag).await
+    }
+
+    async fn list(&self, params: &ListParams) -> Result<Page<Question>> {
+        self.inner.list(params).await
+    }
+
+    async fn update_as(&self, question: &Question, actor: Option<&str>) -> Result<bool> {
+        let updated = self.inner.update_as(question, actor).await?;
+        // The stored type may differ from the new one, so drop every type list
+        invalidate(self.cache.as_ref(), &self.keys.for_ids([question.id])).await;
+        Ok(updated)
+    }
+
+    async fn history(&self, id: Uuid) -> Result<Vec<QuestionRevision>> {
+        self.inner.history(id).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool> {
+        let deleted = self.inner.delete(id).await?;
+        invalidate(self.cache.as_ref(), &self.keys.for_ids([id])).await;
+        Ok(deleted)
+    }
+
+    async fn delete_batch(&self, ids: &[Uuid]) -> Result<u64> {
+        let deleted = self.inner.delete_batch(ids).await?;
+        invalidate(self.cache.as_ref(), &self.keys.for_ids(ids.iter().copied())).await;
+        Ok(deleted)
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<bool> {
+        let restored = self.inner.restore(id).await?;
+        invalidate(self.cache.as_ref(), &self.keys.for_ids([id])).await;
+        Ok(restored)
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+        // Purged questions were already invalidated when they were deleted
+        self.inner.purge(before).await
+    }
+
+    async fn create_bank(&self, bank: &QuestionBank) -> Result<Uuid> {
+        self.inner.create_bank(bank).await
+    }
+
+    async fn find_bank(&self, id: Uuid) -> Result<Option<QuestionBank>> {
+        self.inner.find_bank(id).await
+    }
+
+    async fn list_banks(&self) -> Result<Vec<QuestionBank>> {
+        self.inner.list_banks().await
+    }
+
+    async fn assign_to_bank(&self, bank_id: Uuid, question_ids: &[Uuid]) -> Result<u64> {
+        let assigned = self.inner.assign_to_bank(bank_id, question_ids).await?;
+        invalidate(self.cache.as_ref(), &self.keys.for_ids(question_ids.iter().copied())).await;
+        Ok(assigned)
+    }
+
+    async fn find_by_bank(&self, bank_id: Uuid) -> Result<Vec<Question>> {
+        self.inner.find_by_bank(bank_id).await
+    }
+
+    async fn begin_import(&self) -> Result<Box<dyn ImportSession>> {
+        Ok(Box::new(CachedImportSession {
+            inner: self.inner.begin_import().await?,
+            cache: self.cache.clone(),
+            keys: self.keys.clone(),
+            stale: Vec::new(),
+        }))
+    }
+
+    async fn save_job(&self, job: &JobRecord) -> Result<()> {
+        self.inner.save_job(job).await
+    }
+
+    async fn find_job(&self, id: Uuid) -> Result<Option<JobRecord>> {
+        self.inner.find_job(id).await
+    }
+
+    async fn list_jobs(&self) -> Result<Vec<JobRecord>> {
+        self.inner.list_jobs().await
+    }
+
+    async fn save_source(&self, source: &ImportedSource) -> Result<()> {
+        self.inner.save_source(source).await
+    }
+
+    async fn list_sources(&self, bank_id: Uuid) -> Result<Vec<ImportedSource>> {
+        self.inner.list_sources(bank_id).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.inner.ping().await
+    }
+
+    async fn stats(&self) -> Result<QuestionStats> {
+        self.inner.stats().await
+    }
+}
+
+/// Keys made stale by a deduplicating save
+fn stale_keys(keys: &Keys, questions: &[Question], outcomes: &[SaveOutcome]) -> Vec<String> {
+    let mut stale = keys.for_questions(questions);
+    stale.extend(
+        outcomes
+            .iter()
+            .filter_map(|o| match o {
+                SaveOutcome::Updated { existing_id, .. } => Some(keys.question(*existing_id)),
+                _ => None,
+            }),
+    );
+    stale
+}
+
+/// Import session that invalidates everything it wrote once committed
+struct CachedImportSession {
+    inner: Box<dyn ImportSession>,
+    cache: Arc<dyn QuestionCache>,
+    keys: Keys,
+    stale: Vec<String>,
+}
+
+#[async_trait]
+impl ImportSession for CachedImportSession {
+    fn id(&self) -> Uuid {
+        self.inner.id()
+    }
+
+    async fn save_batch(&mut self, questions: &[Question]) -> Result<Vec<Uuid>> {
+        let ids = self.inner.save_batch(questions).await?;
+        self.stale.extend(self.keys.for_questions(questions));
+        Ok(ids)
+    }
+
+    async fn save_batch_dedup(&mut self, questions: &[Question], options: &DedupOptions) -> Result<Vec<SaveOutcome>> {
+        let outcomes = self.inner.save_batch_dedup(questions, options).await?;
+        self.stale.extend(stale_keys(&self.keys, questions, &outcomes));
+        Ok(outcomes)
+    }
+
+    async fn commit(self: Box<Self>) -> Result<usize> {
+        let written = self.inner.commit().await?;
+        let mut stale = self.stale;
+        stale.sort();
+        stale.dedup();
+        invalidate(self.cache.as_ref(), &stale).await;
+        Ok(written)
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<()> {
+        self.inner.rollback().await
+    }
+}
+
+/// Redis backend speaking RESP directly over TCP
+#[cfg(feature = "redis")]
+pub mod redis {
+    use super::*;
+    use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+    use tokio::sync::Mutex;
+
+    /// Longest wait for a connection or a reply
+    const IO_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Cache stored in Redis, shared by every instance pointing at it
+    ///
+    /// A single connection is reused and re-established after any I/O error.
+    pub struct RedisCache {
+        address: String,
+        password: Option<String>,
+        database: u32,
+        conn: Mutex<Option<BufReader<TcpStream>>>,
+    }
+
+    /// A decoded RESP reply
+    #[derive(Debug, PartialEq)]
+    pub(crate) enum Reply {
+        Status(String),
+        Integer(i64),
+        Bulk(Option<Vec<u8>>),
+        Array(Vec<Reply>),
+    }
+
+    impl RedisCache {
+        /// Create a cache from a `redis://[:password@]host[:port][/db]` URL
+        ///
+        /// No connection is made until the first command.
+        pub fn new(url: &str) -> anyhow::Result<Self> {
+            let rest = url
+                .strip_prefix("redis://")
+                .ok_or_else(|| anyhow::anyhow!("Unsupported Redis URL (expected redis://): {}", url))?;
+            let (auth, rest) = match rest.rsplit_once('@') {
+                Some((auth, rest)) => (Some(auth), rest),
+                None => (None, rest),
+            };
+            let (host, database) = match rest.split_once('/') {
+                Some((host, db)) if !db.is_empty() => (host, db.parse()?),
+                Some((host, _)) => (host, 0),
+                None => (rest, 0),
+            };
+            let address = if host.contains(':') {
+                host.to_string()
+            } else {
+                format!("{}:6379", host)
+            };
+            let password = auth
+                .map(|a| a.rsplit_once(':').map_or(a, |(_, password)| password))
+                .filter(|p| !p.is_empty())
+                .map(str::to_string);
+
+            Ok(Self {
+                address,
+                password,
+                database,
+                conn: Mutex::new(None),
+            })
+        }
+
+        /// Run one command, reconnecting first if needed
+        async fn command(&self, args: &[&[u8]]) -> anyhow::Result<Reply> {
+            let mut guard = self.conn.lock().await;
+            if guard.is_none() {
+                *guard = Some(self.connect().await?);
+            }
+            let conn = guard.as_mut().expect("connection was just established");
+
+            match tokio::time::timeout(IO_TIMEOUT, round_trip(conn, args)).await {
+                Ok(Ok(reply)) => Ok(reply),
+                Ok(Err(e)) => {
+                    *guard = None;
+                    Err(e)
+                }
+                Err(_) => {
+                    *guard = None;
+                    anyhow::bail!("Redis command timed out after {:?}", IO_TIMEOUT)
+                }
+            }
+        }
+
+        async fn connect(&self) -> anyhow::Result<BufReader<TcpStream>> {
+            let stream = tokio::time::timeout(IO_TIMEOUT, TcpStream::connect(&self.address))
+                .await
+                .map_err(|_| anyhow::anyhow!("Timed out connecting to Redis at {}", self.address))??;
+            let mut conn = BufReader::new(stream);
+
+            if let Some(password) = &self.password {
+                round_trip(&mut conn, &[b"AUTH", password.as_bytes()]).await?;
+            }
+            if self.database != 0 {
+                let db = self.database.to_string();
+                round_trip(&mut conn, &[b"SELECT", db.as_bytes()]).await?;
+            }
+            Ok(conn)
+        }
+    }
+
+    #[async_trait]
+    impl QuestionCache for RedisCache {
+        async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+            match self.command(&[b"GET", key.as_bytes()]).await? {
+                Reply::Bulk(value) => Ok(value),
+                other => anyhow::bail!("Unexpected reply to GET: {:?}", other),
+            }
+        }
+
+        async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> anyhow::Result<()> {
+            let ttl = ttl.as_millis().max(1).to_string();
+            self.command(&[b"SET", key.as_bytes(), &value, b"PX", ttl.as_bytes()])
+                .await?;
+            Ok(())
+        }
+
+        async fn delete(&self, keys: &[String]) -> anyhow::Result<()> {
+            let mut args: Vec<&[u8]> = vec![b"DEL"];
+            args.extend(keys.iter().map(|k| k.as_bytes()));
+            self.command(&args).await?;
+            Ok(())
+        }
+    }
+
+    /// Send a command and read its reply; server errors become `Err`
+    async fn round_trip(conn: &mut BufReader<TcpStream>, args: &[&[u8]]) -> anyhow::Result<Reply> {
+        conn.get_mut().write_all(&encode_command(args)).await?;
+        read_reply(conn).await
+    }
+
+    /// Encode a command as a RESP array of bulk strings
+    pub(crate) fn encode_command(args: &[&[u8]]) -> Vec<u8> {
+        let mut out = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            out.extend_from_slice(arg);
+            out.extend_from_slice(b"\r\n");
+        }
+        out
+    }
+
+    /// Read one RESP reply
+    pub(crate) async fn read_reply<R: AsyncBufRead + Unpin + Send>(reader: &mut R) -> anyhow::Result<Reply> {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            anyhow::bail!("Redis closed the connection");
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (kind, payload) = line.split_at(line.len().min(1));
+
+        match kind {
+            "+" => Ok(Reply::Status(payload.to_string())),
+            "-" => anyhow::bail!("Redis error: {}", payload),
+            ":" => Ok(Reply::Integer(payload.parse()?)),
+            "$" => {
+                let len: i64 = payload.parse()?;
+                if len < 0 {
+                    return Ok(Reply::Bulk(None));
+                }
+                let mut data = vec![0; len as usize + 2];
+                reader.read_exact(&mut data).await?;
+                data.truncate(len as usize);
+                Ok(Reply::Bulk(Some(data)))
+            }
+            "*" => {
+                let len: i64 = payload.parse()?;
+                let mut items = Vec::new();
+                for _ in 0..len.max(0) {
+                    items.push(Box::pin(read_reply(reader)).await?);
+                }
+                Ok(Reply::Array(items))
+            }
+            _ => anyhow::bail!("Malformed Redis reply: {:?}", line),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_url() {
+            let cache = RedisCache::new("redis://:secret@cache.internal:6380/2").unwrap();
+            assert_eq!(cache.address, "cache.internal:6380");
+            assert_eq!(cache.password.as_deref(), Some("secret"));
+            assert_eq!(cache.database, 2);
+
+            let cache = RedisCache::new("redis://localhost").unwrap();
+            assert_eq!(cache.address, "localhost:6379");
+            assert_eq!(cache.password, None);
+            assert_eq!(cache.database, 0);
+
+            assert!(RedisCache::new("http://localhost").is_err());
+        }
+
+        #[tokio::test]
+        async fn test_resp_encoding() {
+            assert_eq!(encode_command(&[b"GET", b"k"]), b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n");
+
+            let mut input: &[u8] = b"+OK\r\n:3\r\n$5\r\na\r\nbc\r\n$-1\r\n*2\r\n$1\r\nx\r\n:1\r\n-ERR nope\r\n";
+            assert_eq!(read_reply(&mut input).await.unwrap(), Reply::Status("OK".to_string()));
+            assert_eq!(read_reply(&mut input).await.unwrap(), Reply::Integer(3));
+            assert_eq!(read_reply(&mut input).await.unwrap(), Reply::Bulk(Some(b"a\r\nbc".to_vec())));
+            assert_eq!(read_reply(&mut input).await.unwrap(), Reply::Bulk(None));
+            assert_eq!(
+                read_reply(&mut input).await.unwrap(),
+                Reply::Array(vec![Reply::Bulk(Some(b"x".to_vec())), Reply::Integer(1)])
+            );
+            assert!(read_reply(&mut input).await.is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MockRepository;
+
+    fn question(stem: &str, qtype: QuestionType) -> Question {
+        Question {
+            stem: stem.to_string(),
+            qtype,
+            ..Question::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_repository_invalidates_on_write() {
+        let repo = CachedRepository::new(Arc::new(MockRepository::new()), Arc::new(MemoryCache::new()));
+        let mut q = question("Original", QuestionType::Choice);
+        repo.save_batch(std::slice::from_ref(&q)).await.unwrap();
+
+        assert_eq!(repo.find_by_id(q.id).await.unwrap().unwrap().stem, "Original");
+        assert_eq!(repo.find_by_type(&QuestionType::Choice).await.unwrap().len(), 1);
+
+        // Writing behind the decorator's back is not seen until the TTL expires...
+        q.stem = "Changed".to_string();
+        repo.inner().update(&q).await.unwrap();
+        assert_eq!(repo.find_by_id(q.id).await.unwrap().unwrap().stem, "Original");
+
+        // ...but writes through it invalidate immediately
+        repo.update(&q).await.unwrap();
+        assert_eq!(repo.find_by_id(q.id).await.unwrap().unwrap().stem, "Changed");
+
+        let mut session = repo.begin_import().await.unwrap();
+        session.save_batch(&[question("Imported", QuestionType::Choice)]).await.unwrap();
+        assert_eq!(repo.find_by_type(&QuestionType::Choice).await.unwrap().len(), 1);
+        session.commit().await.unwrap();
+        assert_eq!(repo.find_by_type(&QuestionType::Choice).await.unwrap().len(), 2);
+
+        repo.delete(q.id).await.unwrap();
+        assert!(repo.find_by_id(q.id).await.unwrap().is_none());
+        assert_eq!(repo.find_by_type(&QuestionType::Choice).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_expiry() {
+        let cache = MemoryCache::new();
+        cache.set("a", b"1".to_vec(), Duration::from_secs(60)).await.unwrap();
+        cache.set("b", b"2".to_vec(), Duration::ZERO).await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), Some(b"1".to_vec()));
+        assert_eq!(cache.get("b").await.unwrap(), None);
+
+        cache.delete(&["a".to_string()]).await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), None);
+    }
+}
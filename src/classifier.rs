@@ -2,8 +2,191 @@
 //!
 //! This module implements a multi-level classification strategy for detecting
 //! question types from parsed content.
+//!
+//! Every keyword any level looks for is compiled into one [`TextScanner`],
+//! so a stem is lowercased and scanned once however many levels it passes
+//! through.
+//!
+//! [`classify_question`] also reads the title of the section a question was
+//! found under ("二、多选题"), which often names the type outright, and
+//! takes it as the default for the whole section.
+
+use crate::models::{ClassificationResult, Question, QuestionType, TypeSource};
+use crate::scan::{ScanHits, TextScanner};
+use anyhow::anyhow;
+use std::sync::LazyLock;
+
+/// Language of the keywords the NLP level looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// Chinese keywords only
+    Zh,
+    /// English keywords only
+    En,
+}
+
+impl Language {
+    fn matches(self, keyword: &str) -> bool {
+        match self {
+            Language::Zh => !keyword.is_ascii(),
+            Language::En => keyword.is_ascii(),
+        }
+    }
+}
+
+impl std::str::FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let lower = s.to_ascii_lowercase().replace('_', "-");
+        match lower.as_str() {
+            "zh" | "cn" | "chinese" => Ok(Language::Zh),
+            "en" | "english" => Ok(Language::En),
+            other if other.starts_with("zh-") => Ok(Language::Zh),
+            other if other.starts_with("en-") => Ok(Language::En),
+            other => Err(anyhow!("Unsupported language: {}", other)),
+        }
+    }
+}
+
+/// Keyword groups of [`SCANNER`]
+mod group {
+    pub const SINGLE_MARKER: usize = 0;
+    pub const MULTIPLE_MARKER: usize = 1;
+    pub const TRUE_FALSE_MARKER: usize = 2;
+    pub const FILL_BLANK_MARKER: usize = 3;
+    pub const MULTIPLE_HINT: usize = 4;
+    pub const MULTIPLE_CHOICE: usize = 5;
+    pub const SINGLE_CHOICE: usize = 6;
+    pub const TRUE_FALSE: usize = 7;
+    pub const FILL_BLANK: usize = 8;
+    pub const PARENS: usize = 9;
+    pub const UNDERSCORES: usize = 10;
+    pub const ALL: usize = 11;
+    pub const ALL_OF_THE_ABOVE: usize = 12;
+    pub const BEST: usize = 13;
+    pub const TRUE_OPTION: usize = 14;
+    pub const FALSE_OPTION: usize = 15;
+    pub const OPEN_BRACKET: usize = 16;
+    pub const CLOSE_BRACKET: usize = 17;
+    pub const EXCLAMATION: usize = 18;
+    pub const QUESTION_MARK: usize = 19;
+}
+
+/// Every keyword the classifiers look for, in the groups of [`group`]
+static SCANNER: LazyLock<TextScanner> = LazyLock::new(|| {
+    TextScanner::new([
+        &["[单选]", "[单选题]"][..],
+        &["[多选]", "[多选题]"],
+        &["[判断]", "[判断题]"],
+        &["[填空]", "[填空题]"],
+        &["多选", "以下全部正确", "都正确"],
+        NlpClassifier::MULTIPLE_CHOICE_KEYWORDS,
+        NlpClassifier::SINGLE_CHOICE_KEYWORDS,
+        NlpClassifier::TRUE_FALSE_KEYWORDS,
+        NlpClassifier::FILL_BLANK_KEYWORDS,
+        &["()", "（）"],
+        &["___"],
+        &["全部", "都"],
+        &["以下全部正确", "all of the above"],
+        &["最佳", "best"],
+        &["正确", "对", "true"],
+        &["错误", "错", "false"],
+        &["["],
+        &["]"],
+        &["!", "！"],
+        &["?", "？"],
+    ])
+});
+
+/// Keywords found in a lowercased stem
+fn scan(stem: &str) -> ScanHits<'static> {
+    SCANNER.scan(&stem.to_lowercase())
+}
 
-use crate::models::{ClassificationResult, QuestionType};
+/// Types a section title can declare, in the order they are tried
+const SECTION_TYPES: [QuestionType; 5] = [
+    QuestionType::MultipleChoice,
+    QuestionType::TrueFalse,
+    QuestionType::FillInTheBlank,
+    QuestionType::Choice,
+    QuestionType::Subjective,
+];
+
+/// Section title keywords, one group per entry of [`SECTION_TYPES`]
+///
+/// "选择题" and "multiple choice" name single choice sections; sections
+/// allowing several answers say so explicitly.
+static SECTION_SCANNER: LazyLock<TextScanner> = LazyLock::new(|| {
+    TextScanner::new([
+        &["多选", "多项选择", "不定项", "复选", "multiple answer", "multiple response", "multiple select", "select all"][..],
+        &["判断", "是非", "对错", "正误", "true/false", "true or false", "true-false"],
+        &["填空", "fill in", "fill-in", "blank"],
+        &["单选", "单项选择", "选择", "single choice", "multiple choice", "multiple-choice"],
+        &["简答", "问答", "论述", "计算", "解答", "分析", "作文", "名词解释", "主观", "short answer", "essay", "subjective"],
+    ])
+});
+
+/// Confidence of a type known only from the section title
+const SECTION_CONFIDENCE: f32 = 0.8;
+
+/// The question type a section title declares ("二、多选题"), if any
+pub fn section_type(title: &str) -> Option<QuestionType> {
+    let hits = SECTION_SCANNER.scan(&title.to_lowercase());
+    (0..SECTION_TYPES.len()).find(|&group| hits.any(group)).map(|group| SECTION_TYPES[group])
+}
+
+/// Classify a parsed question, taking its section title as its default type
+///
+/// A section that declares a type ("三、判断题") decides it for every
+/// question under it, and raises the confidence of a classifier result that
+/// agrees. Only a classifier result more confident than the section itself
+/// overrides it. The result records which of the two decided.
+pub fn classify_question(question: &Question, language: Option<Language>) -> Option<ClassificationResult> {
+    let options: Vec<String> = question.options.iter().map(|o| o.content.clone()).collect();
+    let result = classify_with_language(&question.stem, &options, language);
+    let Some(hint) = question.section.as_deref().and_then(section_type) else {
+        return result.map(|r| r.with_source(TypeSource::Classifier));
+    };
+    let result = match result {
+        Some(result) if result.qtype == hint => ClassificationResult::new(hint, result.confidence.max(SECTION_CONFIDENCE)),
+        Some(result) if result.confidence > SECTION_CONFIDENCE => return Some(result.with_source(TypeSource::Classifier)),
+        _ => ClassificationResult::new(hint, SECTION_CONFIDENCE),
+    };
+    Some(result.with_source(TypeSource::Section))
+}
+
+/// Classify `question` and set its type, falling back to `default_type`
+///
+/// Returns the decision applied. A question neither can place keeps its
+/// type and is reported with zero confidence.
+pub fn apply_classification(
+    question: &mut Question,
+    language: Option<Language>,
+    default_type: Option<QuestionType>,
+) -> ClassificationResult {
+    let result = classify_question(question, language)
+        .or_else(|| default_type.map(|qtype| ClassificationResult::new(qtype, 0.0).with_source(TypeSource::Default)));
+    let Some(result) = result else {
+        return ClassificationResult::new(question.qtype, 0.0);
+    };
+    question.qtype = result.qtype;
+    question.type_source = result.source;
+    result
+}
+
+/// Classify with each level in turn, from fastest to most thorough
+pub fn classify(stem: &str, options: &[String]) -> Option<ClassificationResult> {
+    classify_with_language(stem, options, None)
+}
+
+/// Like [`classify`], with the keyword level limited to `language`
+pub fn classify_with_language(stem: &str, options: &[String], language: Option<Language>) -> Option<ClassificationResult> {
+    let hits = scan(stem);
+    StructuralClassifier::classify_hits(&hits, options)
+        .or_else(|| SemanticRuleClassifier::classify_hits(&hits, options))
+        .or_else(|| NlpClassifier::classify_hits(&hits, options, language))
+}
 
 /// Structural classifier - Fast pattern matching
 pub struct StructuralClassifier;
@@ -11,17 +194,21 @@ pub struct StructuralClassifier;
 impl StructuralClassifier {
     /// Classify based on explicit structural patterns
     pub fn classify(stem: &str, options: &[String]) -> Option<ClassificationResult> {
+        Self::classify_hits(&scan(stem), options)
+    }
+
+    fn classify_hits(hits: &ScanHits, options: &[String]) -> Option<ClassificationResult> {
         // Check for explicit type markers
-        if stem.contains("[单选]") || stem.contains("[单选题]") {
+        if hits.any(group::SINGLE_MARKER) {
             return Some(ClassificationResult::certain(QuestionType::Choice));
         }
-        if stem.contains("[多选]") || stem.contains("[多选题]") {
+        if hits.any(group::MULTIPLE_MARKER) {
             return Some(ClassificationResult::certain(QuestionType::MultipleChoice));
         }
-        if stem.contains("[判断]") || stem.contains("[判断题]") {
+        if hits.any(group::TRUE_FALSE_MARKER) {
             return Some(ClassificationResult::certain(QuestionType::TrueFalse));
         }
-        if stem.contains("[填空]") || stem.contains("[填空题]") {
+        if hits.any(group::FILL_BLANK_MARKER) {
             return Some(ClassificationResult::certain(QuestionType::FillInTheBlank));
         }
 
@@ -31,11 +218,7 @@ impl StructuralClassifier {
         }
 
         // Check for multi-select keywords
-        let stem_lower = stem.to_lowercase();
-        if stem_lower.contains("多选")
-            || stem_lower.contains("以下全部正确")
-            || stem_lower.contains("都正确")
-        {
+        if hits.any(group::MULTIPLE_HINT) {
             return Some(ClassificationResult::new(QuestionType::MultipleChoice, 0.9));
         }
 
@@ -48,12 +231,8 @@ impl StructuralClassifier {
             return false;
         }
 
-        let opt1 = options[0].to_lowercase();
-        let opt2 = options[1].to_lowercase();
-
         // Common binary patterns
-        (opt1.contains("正确") || opt1.contains("对") || opt1.contains("true"))
-            && (opt2.contains("错误") || opt2.contains("错") || opt2.contains("false"))
+        scan(&options[0]).any(group::TRUE_OPTION) && scan(&options[1]).any(group::FALSE_OPTION)
     }
 }
 
@@ -63,8 +242,12 @@ pub struct SemanticRuleClassifier;
 impl SemanticRuleClassifier {
     /// Classify based on semantic context and rules
     pub fn classify(stem: &str, options: &[String]) -> Option<ClassificationResult> {
+        Self::classify_hits(&scan(stem), options)
+    }
+
+    fn classify_hits(hits: &ScanHits, options: &[String]) -> Option<ClassificationResult> {
         // Fill-in-blank vs True-False distinction
-        let has_parens = stem.contains("()") || stem.contains("（）");
+        let has_parens = hits.any(group::PARENS);
         let has_binary_options = options.len() == 2 && StructuralClassifier::is_binary_options(options);
 
         if has_parens && has_binary_options {
@@ -78,7 +261,7 @@ impl SemanticRuleClassifier {
         }
 
         // Underscore patterns indicate fill-in-blank
-        if stem.contains("___") || stem.contains("____") || stem.contains("_____") {
+        if hits.any(group::UNDERSCORES) {
             return Some(ClassificationResult::new(QuestionType::FillInTheBlank, 0.9));
         }
 
@@ -96,8 +279,7 @@ impl SemanticRuleClassifier {
 
             if has_letter_prefixes {
                 // Determine single vs multiple choice based on keywords
-                let stem_lower = stem.to_lowercase();
-                if stem_lower.contains("全部") || stem_lower.contains("都") {
+                if hits.any(group::ALL) {
                     return Some(ClassificationResult::new(QuestionType::MultipleChoice, 0.8));
                 }
                 return Some(ClassificationResult::new(QuestionType::Choice, 0.8));
@@ -205,14 +387,25 @@ impl NlpClassifier {
 
     /// Classify using keyword matching and semantic analysis
     pub fn classify(stem: &str, options: &[String]) -> Option<ClassificationResult> {
-        let stem_lower = stem.to_lowercase();
-        let original_stem = stem;
+        Self::classify_with_language(stem, options, None)
+    }
+
+    /// Classify using only the keywords of `language` (all of them for `None`)
+    pub fn classify_with_language(stem: &str, options: &[String], language: Option<Language>) -> Option<ClassificationResult> {
+        Self::classify_hits(&scan(stem), options, language)
+    }
+
+    fn classify_hits(hits: &ScanHits, options: &[String], language: Option<Language>) -> Option<ClassificationResult> {
+        let score = |group: usize| {
+            let matched = hits.keywords(group).filter(|k| language.is_none_or(|l| l.matches(k)));
+            Self::calculate_score(matched, options)
+        };
 
         // Calculate confidence scores for each question type
-        let mc_score = Self::calculate_score(&stem_lower, Self::MULTIPLE_CHOICE_KEYWORDS, options);
-        let sc_score = Self::calculate_score(&stem_lower, Self::SINGLE_CHOICE_KEYWORDS, options);
-        let tf_score = Self::calculate_score(&stem_lower, Self::TRUE_FALSE_KEYWORDS, options);
-        let fb_score = Self::calculate_score(&stem_lower, Self::FILL_BLANK_KEYWORDS, options);
+        let mc_score = score(group::MULTIPLE_CHOICE);
+        let sc_score = score(group::SINGLE_CHOICE);
+        let tf_score = score(group::TRUE_FALSE);
+        let fb_score = score(group::FILL_BLANK);
 
         // Find the highest scoring type
         let scores = [
@@ -232,7 +425,7 @@ impl NlpClassifier {
             // Boost confidence based on additional patterns
             let adjusted_confidence = Self::adjust_confidence(
                 *best_score,
-                original_stem,
+                hits,
                 options,
                 *best_type,
             );
@@ -242,27 +435,24 @@ impl NlpClassifier {
         }
     }
 
-    /// Calculate confidence score based on keyword matches
-    fn calculate_score(text: &str, keywords: &[&str], options: &[String]) -> f32 {
+    /// Calculate confidence score based on the keywords matched in the stem
+    fn calculate_score<'k>(matched: impl Iterator<Item = &'k str>, options: &[String]) -> f32 {
         let mut score = 0.0;
+        let mut any_matched = false;
 
-        // Check for keyword matches in stem
-        for keyword in keywords {
-            if text.contains(keyword) {
-                // Longer keywords get higher weight
-                let weight = (keyword.len() as f32) / 10.0;
-                score += weight;
-            }
+        for keyword in matched {
+            // Longer keywords get higher weight
+            let weight = (keyword.len() as f32) / 10.0;
+            score += weight;
+            any_matched = true;
         }
 
         // Boost score based on option count patterns
         match options.len() {
             0 => score *= 0.8,  // No options reduces confidence
-            2 => {
-                // Two options might be true/false
-                if !keywords.iter().any(|k| text.contains(k)) {
-                    score *= 0.3; // Low confidence for 2 options without keywords
-                }
+            // Two options might be true/false
+            2 if !any_matched => {
+                score *= 0.3; // Low confidence for 2 options without keywords
             }
             3..=4 => score *= 1.2,  // 3-4 options is typical for choice questions
             5.. => score *= 1.5,  // 5+ options strongly suggest choice/multiple choice
@@ -276,24 +466,24 @@ impl NlpClassifier {
     /// Adjust confidence based on contextual patterns
     fn adjust_confidence(
         base_score: f32,
-        stem: &str,
+        hits: &ScanHits,
         options: &[String],
         qtype: QuestionType,
     ) -> f32 {
         let mut confidence = base_score;
 
         // Check for explicit markers (high confidence boost)
-        if stem.contains("[") && stem.contains("]") {
+        if hits.any(group::OPEN_BRACKET) && hits.any(group::CLOSE_BRACKET) {
             confidence = (confidence + 0.3).min(1.0);
         }
 
         // Check for exclamation marks (often indicate emphasis)
-        if stem.contains('！') || stem.contains('!') {
+        if hits.any(group::EXCLAMATION) {
             confidence = (confidence + 0.1).min(1.0);
         }
 
         // Check for question marks
-        if stem.contains('？') || stem.contains('?') {
+        if hits.any(group::QUESTION_MARK) {
             confidence = (confidence + 0.05).min(1.0);
         }
 
@@ -301,9 +491,7 @@ impl NlpClassifier {
         match qtype {
             QuestionType::MultipleChoice => {
                 // "以下全部正确" is very specific to multiple choice
-                if stem.to_lowercase().contains("以下全部正确")
-                    || stem.to_lowercase().contains("all of the above")
-                {
+                if hits.any(group::ALL_OF_THE_ABOVE) {
                     confidence = (confidence + 0.2).min(1.0);
                 }
                 // Multiple choice typically has more options
@@ -313,9 +501,7 @@ impl NlpClassifier {
             }
             QuestionType::Choice => {
                 // Single choice with "best" keyword is very specific
-                if stem.to_lowercase().contains("最佳")
-                    || stem.to_lowercase().contains("best")
-                {
+                if hits.any(group::BEST) {
                     confidence = (confidence + 0.15).min(1.0);
                 }
             }
@@ -327,10 +513,7 @@ impl NlpClassifier {
             }
             QuestionType::FillInTheBlank => {
                 // Underscores or parentheses indicate fill-in-blank
-                if stem.contains("___")
-                    || stem.contains("（）")
-                    || stem.contains("()")
-                {
+                if hits.any(group::UNDERSCORES) || hits.any(group::PARENS) {
                     confidence = (confidence + 0.2).min(1.0);
                 }
             }
@@ -342,26 +525,18 @@ impl NlpClassifier {
 
     /// Get detailed classification analysis (useful for debugging)
     pub fn analyze(stem: &str, options: &[String]) -> NlpAnalysis {
-        let stem_lower = stem.to_lowercase();
+        let hits = scan(stem);
+        let matches = |group: usize| hits.keywords(group).map(str::to_string).collect();
 
         NlpAnalysis {
-            multiple_choice_matches: Self::find_matches(&stem_lower, Self::MULTIPLE_CHOICE_KEYWORDS),
-            single_choice_matches: Self::find_matches(&stem_lower, Self::SINGLE_CHOICE_KEYWORDS),
-            true_false_matches: Self::find_matches(&stem_lower, Self::TRUE_FALSE_KEYWORDS),
-            fill_blank_matches: Self::find_matches(&stem_lower, Self::FILL_BLANK_KEYWORDS),
+            multiple_choice_matches: matches(group::MULTIPLE_CHOICE),
+            single_choice_matches: matches(group::SINGLE_CHOICE),
+            true_false_matches: matches(group::TRUE_FALSE),
+            fill_blank_matches: matches(group::FILL_BLANK),
             option_count: options.len(),
-            recommended_type: Self::classify(stem, options).map(|r| r.qtype),
+            recommended_type: Self::classify_hits(&hits, options, None).map(|r| r.qtype),
         }
     }
-
-    /// Find matching keywords in text
-    fn find_matches(text: &str, keywords: &[&str]) -> Vec<String> {
-        keywords
-            .iter()
-            .filter(|kw| text.contains(*kw))
-            .map(|s| s.to_string())
-            .collect()
-    }
 }
 
 /// Detailed analysis result from NLP classification
@@ -384,6 +559,7 @@ pub struct NlpAnalysis {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::QuestionOption;
 
     #[test]
     fn test_explicit_markers() {
@@ -407,6 +583,87 @@ mod tests {
         assert_eq!(result.unwrap().qtype, QuestionType::FillInTheBlank);
     }
 
+    #[test]
+    fn test_section_type() {
+        assert_eq!(section_type("一、单选题"), Some(QuestionType::Choice));
+        assert_eq!(section_type("二、多项选择题（每题3分）"), Some(QuestionType::MultipleChoice));
+        assert_eq!(section_type("三、判断题"), Some(QuestionType::TrueFalse));
+        assert_eq!(section_type("四、填空题"), Some(QuestionType::FillInTheBlank));
+        assert_eq!(section_type("五、简答题"), Some(QuestionType::Subjective));
+        assert_eq!(section_type("Part 1. Multiple Choice"), Some(QuestionType::Choice));
+        assert_eq!(section_type("Subjective"), Some(QuestionType::Subjective));
+        assert_eq!(section_type("一、基础知识"), None);
+    }
+
+    #[test]
+    fn test_classify_question_uses_section_hint() {
+        let mut question = Question {
+            stem: "地球是圆的".to_string(),
+            ..Question::default()
+        };
+        assert!(classify_question(&question, None).is_none());
+
+        question.section = Some("三、判断题".to_string());
+        let result = classify_question(&question, None).unwrap();
+        assert_eq!(result.qtype, QuestionType::TrueFalse);
+        assert_eq!(result.source, Some(TypeSource::Section));
+        assert!(!result.needs_review);
+
+        // A classification no more confident than the section does not
+        // override it
+        question.stem = "下列说法是否成立".to_string();
+        question.options = ["A. 成立", "B. 不成立"]
+            .iter()
+            .enumerate()
+            .map(|(i, content)| QuestionOption {
+                content: content.to_string(),
+                sort_order: i as i32,
+                is_correct: false,
+            })
+            .collect();
+        let options: Vec<String> = question.options.iter().map(|o| o.content.clone()).collect();
+        assert_eq!(classify(&question.stem, &options).unwrap().confidence, SECTION_CONFIDENCE);
+        let result = classify_question(&question, None).unwrap();
+        assert_eq!(result.qtype, QuestionType::TrueFalse);
+        assert_eq!(result.source, Some(TypeSource::Section));
+
+        // A more confident classification wins over the section
+        question.stem = "[填空]地球是____的".to_string();
+        question.options.clear();
+        let result = classify_question(&question, None).unwrap();
+        assert_eq!(result.qtype, QuestionType::FillInTheBlank);
+        assert_eq!(result.source, Some(TypeSource::Classifier));
+    }
+
+    #[test]
+    fn test_apply_classification_records_source() {
+        let mut question = Question {
+            stem: "地球是圆的".to_string(),
+            ..Question::default()
+        };
+        let result = apply_classification(&mut question, None, None);
+        assert_eq!(result.confidence, 0.0);
+        assert_eq!(question.type_source, None);
+
+        apply_classification(&mut question, None, Some(QuestionType::TrueFalse));
+        assert_eq!(question.qtype, QuestionType::TrueFalse);
+        assert_eq!(question.type_source, Some(TypeSource::Default));
+
+        question.stem = "[填空]地球是____的".to_string();
+        apply_classification(&mut question, None, Some(QuestionType::TrueFalse));
+        assert_eq!(question.qtype, QuestionType::FillInTheBlank);
+        assert_eq!(question.type_source, Some(TypeSource::Classifier));
+    }
+
+    #[test]
+    fn test_classify_falls_through_levels() {
+        let result = classify("[判断]地球是圆的", &[]).unwrap();
+        assert_eq!(result.qtype, QuestionType::TrueFalse);
+        assert_eq!(result.confidence, 1.0);
+        let result = classify("The capital of France is ____", &[]).unwrap();
+        assert_eq!(result.qtype, QuestionType::FillInTheBlank);
+    }
+
     // NLP Classifier tests
 
     #[test]
@@ -466,6 +723,20 @@ mod tests {
         assert!(result.confidence >= 0.7);
     }
 
+    #[test]
+    fn test_nlp_language() {
+        let options: Vec<String> = ["2", "3", "4", "5"].iter().map(|s| s.to_string()).collect();
+        let stem = "Which numbers are prime? Select all that apply";
+        let result = NlpClassifier::classify_with_language(stem, &options, Some(Language::En)).unwrap();
+        assert_eq!(result.qtype, QuestionType::MultipleChoice);
+        // English keywords are ignored when only Chinese is expected
+        assert!(NlpClassifier::classify_with_language(stem, &options, Some(Language::Zh)).is_none());
+        assert!(NlpClassifier::classify(stem, &options).is_some());
+
+        assert_eq!("zh-CN".parse::<Language>().unwrap(), Language::Zh);
+        assert!("fr".parse::<Language>().is_err());
+    }
+
     #[test]
     fn test_nlp_analysis() {
         let stem = "以下全部正确的是哪些？";
@@ -0,0 +1,635 @@
+//! Application configuration
+//!
+//! [`AppConfig`] gathers every setting of the server and the command line
+//! tools in one typed value, loaded once at startup. Settings are layered:
+//! built-in defaults, then a TOML file, then environment variables, so a
+//! deployment can keep a `config.toml` in its image and still override a
+//! single value per container. The environment variables are the ones the
+//! server has always read (`PORT`, `DATABASE_URL`, `MD2DB_MEDIA_DIR`, ...).
+//!
+//! ```toml
+//! [server]
+//! port = 9000
+//!
+//! [database]
+//! url = "postgres://md2db@localhost/md2db"
+//! schema = "normalized"
+//! max_connections = 20
+//!
+//! [processor]
+//! batch_size = 500
+//! preset = "kaoshibao"
+//!
+//! [limits]
+//! max_extracted_mb = 2048
+//!
+//! [media]
+//! dir = "/var/lib/md2db/media"
+//!
+//! [auth]
+//! rate_limit_per_minute = 120
+//! [auth.keys]
+//! ci = "0b6f3c..."
+//!
+//! [webhooks]
+//! urls = ["https://lms.example.com/hooks/md2db"]
+//! secret = "whsec-..."
+//!
+//! [distributed]
+//! workers = ["http://node1:8080", "http://node2:8080"]
+//! api_key = "0b6f3c..."
+//! ```
+//!
+//! Tests can build a configuration with [`AppConfig::from_toml_str`] and
+//! [`AppConfig::with_env`] without touching the process environment.
+
+use crate::auth::Authenticator;
+use crate::body_limit::{BodyLimits, DEFAULT_MAX_JSON_BYTES, DEFAULT_MAX_UPLOAD_BYTES};
+use crate::database::{DatabaseConfig, InsertMode};
+#[cfg(feature = "distributed")]
+use crate::distributed::Coordinator;
+use crate::presets;
+use crate::processor::ProcessorConfig;
+#[cfg(feature = "webhooks")]
+use crate::webhook::WebhookNotifier;
+use crate::workdir::WorkdirConfig;
+use crate::zip::{ZipLimits, DEFAULT_MAX_EXTRACTED_BYTES, DEFAULT_MAX_NESTING_DEPTH, DEFAULT_SPILL_THRESHOLD};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// File read when no configuration file is named and it exists
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Every setting of the server and command line tools
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    /// Where the HTTP API listens
+    pub server: ServerSettings,
+    /// Repository backend and connection pool
+    pub database: DatabaseSettings,
+    /// Batch processing
+    pub processor: ProcessorSettings,
+    /// Archive extraction limits and scratch space
+    pub limits: LimitSettings,
+    /// Where extracted images are kept
+    pub media: MediaSettings,
+    /// Read cache in front of the repository
+    pub cache: CacheSettings,
+    /// Formula recognition in images
+    pub ocr: OcrSettings,
+    /// API credentials and rate limits
+    pub auth: AuthSettings,
+    /// Notifications sent when imports finish
+    pub webhooks: WebhookSettings,
+    /// Worker nodes for distributed imports
+    pub distributed: DistributedSettings,
+}
+
+/// `[server]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    /// Address to listen on (`HOST`)
+    pub host: String,
+    /// Port to listen on (`PORT`)
+    pub port: u16,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+        }
+    }
+}
+
+/// `[database]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    /// PostgreSQL URL; the in-memory repository is used without one (`DATABASE_URL`)
+    pub url: Option<String>,
+    /// Read replica URL (`DATABASE_URL_RO`)
+    pub replica_url: Option<String>,
+    /// `json` or `normalized` (`MD2DB_SCHEMA`)
+    pub schema: String,
+    /// Apply migrations on startup (`MD2DB_AUTO_MIGRATE`)
+    pub auto_migrate: bool,
+    /// Maximum pooled connections (`MD2DB_DB_MAX_CONNECTIONS`)
+    pub max_connections: u32,
+    /// Seconds to wait for a free connection (`MD2DB_DB_ACQUIRE_TIMEOUT_SECS`)
+    pub acquire_timeout_secs: u64,
+    /// Per-statement limit in milliseconds, 0 for none (`MD2DB_DB_STATEMENT_TIMEOUT_MS`)
+    pub statement_timeout_ms: u64,
+    /// `multi-row` or `copy` (`MD2DB_DB_INSERT_MODE`)
+    pub insert_mode: String,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        let pool = DatabaseConfig::default();
+        Self {
+            url: None,
+            replica_url: None,
+            schema: "json".to_string(),
+            auto_migrate: false,
+            max_connections: pool.max_connections,
+            acquire_timeout_secs: pool.acquire_timeout.as_secs(),
+            statement_timeout_ms: 0,
+            insert_mode: "multi-row".to_string(),
+        }
+    }
+}
+
+/// `[processor]`, used by the command line tools
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProcessorSettings {
+    /// Parsing threads; all cores when unset (`MD2DB_CPU_WORKERS`)
+    pub cpu_workers: Option<usize>,
+    /// Concurrent database writes; twice the cores when unset (`MD2DB_IO_WORKERS`)
+    pub io_workers: Option<usize>,
+    /// Questions per database batch (`MD2DB_BATCH_SIZE`)
+    pub batch_size: Option<usize>,
+    /// Archives processed at once (`MD2DB_MAX_CONCURRENT_ZIPS`)
+    pub max_concurrent_zips: Option<usize>,
+    /// Save each import all-or-nothing (`MD2DB_TRANSACTIONAL`)
+    pub transactional: bool,
+    /// Strip EXIF/GPS and text metadata from images (`MD2DB_STRIP_IMAGE_METADATA`)
+    pub strip_image_metadata: bool,
+    /// Parser preset (`MD2DB_PRESET`)
+    pub preset: Option<String>,
+}
+
+/// `[limits]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitSettings {
+    /// Levels of nested archives opened (`MD2DB_MAX_NESTING_DEPTH`)
+    pub max_nesting_depth: usize,
+    /// Megabytes one upload may extract (`MD2DB_MAX_EXTRACTED_MB`)
+    pub max_extracted_mb: u64,
+    /// Entries above this many megabytes are spilled to disk, 0 to keep
+    /// everything in memory (`MD2DB_SPILL_THRESHOLD_MB`)
+    pub spill_threshold_mb: u64,
+    /// Root of the working directories (`MD2DB_TMPDIR`)
+    pub tmpdir: Option<PathBuf>,
+    /// Free space in megabytes to keep on the scratch disk (`MD2DB_MIN_FREE_MB`)
+    pub min_free_mb: Option<u64>,
+    /// Megabytes one API upload may have (`MD2DB_MAX_UPLOAD_MB`)
+    pub max_upload_mb: u64,
+    /// Megabytes one JSON request body may have (`MD2DB_MAX_JSON_MB`)
+    pub max_json_mb: u64,
+}
+
+impl Default for LimitSettings {
+    fn default() -> Self {
+        Self {
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            max_extracted_mb: DEFAULT_MAX_EXTRACTED_BYTES >> 20,
+            spill_threshold_mb: DEFAULT_SPILL_THRESHOLD >> 20,
+            tmpdir: None,
+            min_free_mb: None,
+            max_upload_mb: (DEFAULT_MAX_UPLOAD_BYTES >> 20) as u64,
+            max_json_mb: (DEFAULT_MAX_JSON_BYTES >> 20) as u64,
+        }
+    }
+}
+
+/// `[media]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MediaSettings {
+    /// Directory of the filesystem media store; images are hashed but not
+    /// kept without one (`MD2DB_MEDIA_DIR`)
+    pub dir: Option<PathBuf>,
+}
+
+/// `[cache]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSettings {
+    /// `memory` or a `redis://` URL; no cache without one (`MD2DB_CACHE_URL`)
+    pub url: Option<String>,
+    /// Entry lifetime in seconds (`MD2DB_CACHE_TTL_SECS`)
+    pub ttl_secs: Option<u64>,
+}
+
+/// `[ocr]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OcrSettings {
+    /// Command that reads an image on stdin and prints LaTeX (`MD2DB_FORMULA_OCR_CMD`)
+    pub command: Option<String>,
+    /// Seconds each run may take (`MD2DB_FORMULA_OCR_TIMEOUT_SECS`)
+    pub timeout_secs: Option<u64>,
+}
+
+/// `[auth]`; the API is open unless keys or a JWT secret are set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    /// API keys by name (`MD2DB_API_KEYS`, as `name:key,name:key`)
+    pub keys: BTreeMap<String, String>,
+    /// Shared secret of HS256-signed JWTs (`MD2DB_JWT_SECRET`)
+    pub jwt_secret: Option<String>,
+    /// Require credentials for reads as well as changes (`MD2DB_AUTH_READS`)
+    pub protect_reads: bool,
+    /// Requests per minute for each key, 0 for no limit (`MD2DB_RATE_LIMIT_PER_MINUTE`)
+    pub rate_limit_per_minute: u32,
+}
+
+/// `[webhooks]`; nothing is sent without URLs
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookSettings {
+    /// URLs notified when an import job finishes (`MD2DB_WEBHOOK_URLS`, comma separated)
+    pub urls: Vec<String>,
+    /// Key signing each notification (`MD2DB_WEBHOOK_SECRET`)
+    pub secret: Option<String>,
+    /// Delivery attempts per URL (`MD2DB_WEBHOOK_MAX_ATTEMPTS`)
+    pub max_attempts: Option<u32>,
+}
+
+/// `[distributed]`; imports stay on this machine without workers
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DistributedSettings {
+    /// Base URLs of the worker nodes (`MD2DB_WORKERS`, comma separated)
+    pub workers: Vec<String>,
+    /// API key the workers accept (`MD2DB_WORKER_API_KEY`)
+    pub api_key: Option<String>,
+}
+
+impl AppConfig {
+    /// Defaults, then `path` (or `config.toml` if it exists), then the environment
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let config = match path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Self::default(),
+        };
+        config.with_env(|name| std::env::var(name).ok())
+    }
+
+    /// Read a TOML configuration file over the defaults
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_toml_str(&source).with_context(|| format!("Invalid configuration in {}", path.display()))
+    }
+
+    /// Parse TOML over the defaults; unknown keys are errors
+    pub fn from_toml_str(source: &str) -> Result<Self> {
+        Ok(toml::from_str(source)?)
+    }
+
+    /// Override settings from environment variables looked up with `var`
+    ///
+    /// Empty variables are ignored; values that do not parse are errors.
+    pub fn with_env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let var = |name: &str| var(name).filter(|v| !v.trim().is_empty());
+        fn parse<T: FromStr>(name: &str, value: String) -> Result<T> {
+            value.trim().parse().map_err(|_| anyhow::anyhow!("Invalid value for {}: {}", name, value))
+        }
+        macro_rules! set {
+            ($field:expr, $name:literal) => {
+                if let Some(value) = var($name) {
+                    $field = parse($name, value)?;
+                }
+            };
+            ($field:expr, $name:literal, Some) => {
+                if let Some(value) = var($name) {
+                    $field = Some(parse($name, value)?);
+                }
+            };
+        }
+
+        set!(self.server.host, "HOST");
+        set!(self.server.port, "PORT");
+
+        set!(self.database.url, "DATABASE_URL", Some);
+        set!(self.database.replica_url, "DATABASE_URL_RO", Some);
+        set!(self.database.schema, "MD2DB_SCHEMA");
+        if let Some(value) = var("MD2DB_AUTO_MIGRATE") {
+            self.database.auto_migrate = matches!(value.to_lowercase().as_str(), "1" | "true" | "yes");
+        }
+        set!(self.database.max_connections, "MD2DB_DB_MAX_CONNECTIONS");
+        set!(self.database.acquire_timeout_secs, "MD2DB_DB_ACQUIRE_TIMEOUT_SECS");
+        set!(self.database.statement_timeout_ms, "MD2DB_DB_STATEMENT_TIMEOUT_MS");
+        set!(self.database.insert_mode, "MD2DB_DB_INSERT_MODE");
+
+        set!(self.processor.cpu_workers, "MD2DB_CPU_WORKERS", Some);
+        set!(self.processor.io_workers, "MD2DB_IO_WORKERS", Some);
+        set!(self.processor.batch_size, "MD2DB_BATCH_SIZE", Some);
+        set!(self.processor.max_concurrent_zips, "MD2DB_MAX_CONCURRENT_ZIPS", Some);
+        set!(self.processor.transactional, "MD2DB_TRANSACTIONAL");
+        set!(self.processor.strip_image_metadata, "MD2DB_STRIP_IMAGE_METADATA");
+        set!(self.processor.preset, "MD2DB_PRESET", Some);
+
+        set!(self.limits.max_nesting_depth, "MD2DB_MAX_NESTING_DEPTH");
+        set!(self.limits.max_extracted_mb, "MD2DB_MAX_EXTRACTED_MB");
+        set!(self.limits.spill_threshold_mb, "MD2DB_SPILL_THRESHOLD_MB");
+        set!(self.limits.tmpdir, "MD2DB_TMPDIR", Some);
+        set!(self.limits.min_free_mb, "MD2DB_MIN_FREE_MB", Some);
+        set!(self.limits.max_upload_mb, "MD2DB_MAX_UPLOAD_MB");
+        set!(self.limits.max_json_mb, "MD2DB_MAX_JSON_MB");
+
+        set!(self.media.dir, "MD2DB_MEDIA_DIR", Some);
+
+        set!(self.cache.url, "MD2DB_CACHE_URL", Some);
+        set!(self.cache.ttl_secs, "MD2DB_CACHE_TTL_SECS", Some);
+
+        set!(self.ocr.command, "MD2DB_FORMULA_OCR_CMD", Some);
+        set!(self.ocr.timeout_secs, "MD2DB_FORMULA_OCR_TIMEOUT_SECS", Some);
+
+        if let Some(value) = var("MD2DB_API_KEYS") {
+            // Keys given without a name are named by position
+            for (i, entry) in value.split(',').map(str::trim).filter(|e| !e.is_empty()).enumerate() {
+                let (name, key) = entry.split_once(':').unwrap_or(("", entry));
+                let name = if name.is_empty() { format!("key{}", i + 1) } else { name.to_string() };
+                self.auth.keys.insert(name, key.to_string());
+            }
+        }
+        set!(self.auth.jwt_secret, "MD2DB_JWT_SECRET", Some);
+        set!(self.auth.protect_reads, "MD2DB_AUTH_READS");
+        set!(self.auth.rate_limit_per_minute, "MD2DB_RATE_LIMIT_PER_MINUTE");
+
+        if let Some(value) = var("MD2DB_WEBHOOK_URLS") {
+            self.webhooks.urls = value.split(',').map(str::trim).filter(|u| !u.is_empty()).map(str::to_string).collect();
+        }
+        set!(self.webhooks.secret, "MD2DB_WEBHOOK_SECRET", Some);
+        set!(self.webhooks.max_attempts, "MD2DB_WEBHOOK_MAX_ATTEMPTS", Some);
+
+        if let Some(value) = var("MD2DB_WORKERS") {
+            self.distributed.workers = value.split(',').map(str::trim).filter(|u| !u.is_empty()).map(str::to_string).collect();
+        }
+        set!(self.distributed.api_key, "MD2DB_WORKER_API_KEY", Some);
+
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Check the settings that are only known to be valid once parsed
+    fn validate(&self) -> Result<()> {
+        self.database_config()?;
+        if !matches!(self.database.schema.to_lowercase().as_str(), "json" | "normalized") {
+            anyhow::bail!("Unknown database schema: {} (expected json or normalized)", self.database.schema);
+        }
+        if let Some(name) = &self.processor.preset {
+            presets::preset(name)?;
+        }
+        if let Some((name, _)) = self.auth.keys.iter().find(|(_, key)| key.trim().is_empty()) {
+            anyhow::bail!("API key {} is empty", name);
+        }
+        if let Some(url) = self.webhooks.urls.iter().find(|u| !u.starts_with("http://") && !u.starts_with("https://")) {
+            anyhow::bail!("Webhook URL must start with http:// or https://: {}", url);
+        }
+        if let Some(url) = self.distributed.workers.iter().find(|u| !u.starts_with("http://") && !u.starts_with("https://")) {
+            anyhow::bail!("Worker URL must start with http:// or https://: {}", url);
+        }
+        Ok(())
+    }
+
+    /// Connection pool settings
+    pub fn database_config(&self) -> Result<DatabaseConfig> {
+        let db = &self.database;
+        Ok(DatabaseConfig::default()
+            .with_max_connections(db.max_connections)
+            .with_acquire_timeout(Duration::from_secs(db.acquire_timeout_secs))
+            .with_statement_timeout(Duration::from_millis(db.statement_timeout_ms))
+            .with_insert_mode(db.insert_mode.parse::<InsertMode>()?))
+    }
+
+    /// Whether the relational layout is selected
+    pub fn normalized_schema(&self) -> bool {
+        self.database.schema.eq_ignore_ascii_case("normalized")
+    }
+
+    /// Processor settings, with `preset` taking precedence over the configured one
+    pub fn processor_config(&self, preset: Option<&str>) -> Result<ProcessorConfig> {
+        let settings = &self.processor;
+        let mut config = ProcessorConfig::default()
+            .with_transactional(settings.transactional)
+            .with_strip_image_metadata(settings.strip_image_metadata)
+            .with_limits(self.zip_limits());
+        if let Some(workers) = settings.cpu_workers {
+            config = config.with_cpu_workers(workers);
+        }
+        if let Some(workers) = settings.io_workers {
+            config = config.with_io_workers(workers);
+        }
+        if let Some(size) = settings.batch_size {
+            config = config.with_batch_size(size);
+        }
+        if let Some(max) = settings.max_concurrent_zips {
+            config = config.with_max_concurrent_zips(max);
+        }
+        if let Some(name) = preset.or(settings.preset.as_deref()) {
+            config = config.with_parser_options(presets::preset(name)?);
+        }
+        Ok(config)
+    }
+
+    /// Archive extraction limits
+    pub fn zip_limits(&self) -> ZipLimits {
+        ZipLimits {
+            max_nesting_depth: self.limits.max_nesting_depth,
+            max_extracted_bytes: self.limits.max_extracted_mb << 20,
+        }
+    }
+
+    /// Bytes above which archive entries are spilled to disk, if spilling is on
+    pub fn spill_threshold(&self) -> Option<u64> {
+        (self.limits.spill_threshold_mb > 0).then_some(self.limits.spill_threshold_mb << 20)
+    }
+
+    /// Caps on API request bodies
+    pub fn body_limits(&self) -> BodyLimits {
+        let bytes = |mb: u64| usize::try_from(mb << 20).unwrap_or(usize::MAX);
+        BodyLimits::default()
+            .with_json(bytes(self.limits.max_json_mb))
+            .with_upload(bytes(self.limits.max_upload_mb))
+    }
+
+    /// Request authentication, if any keys or a JWT secret are configured
+    pub fn authenticator(&self) -> Option<Authenticator> {
+        let auth = &self.auth;
+        let mut authenticator = Authenticator::new()
+            .with_protected_reads(auth.protect_reads)
+            .with_rate_limit(auth.rate_limit_per_minute);
+        for (name, key) in &auth.keys {
+            authenticator = authenticator.with_key(name, key);
+        }
+        if let Some(secret) = &auth.jwt_secret {
+            authenticator = authenticator.with_jwt_secret(secret);
+        }
+        authenticator.is_enabled().then_some(authenticator)
+    }
+
+    /// Notifier posting finished imports, if any webhook URLs are configured
+    #[cfg(feature = "webhooks")]
+    pub fn webhook_notifier(&self) -> Option<WebhookNotifier> {
+        let settings = &self.webhooks;
+        if settings.urls.is_empty() {
+            return None;
+        }
+        let mut notifier = WebhookNotifier::new(settings.urls.clone());
+        if let Some(secret) = &settings.secret {
+            notifier = notifier.with_secret(secret);
+        }
+        if let Some(attempts) = settings.max_attempts {
+            notifier = notifier.with_max_attempts(attempts);
+        }
+        Some(notifier)
+    }
+
+    /// Coordinator for the configured worker nodes, if there are any
+    #[cfg(feature = "distributed")]
+    pub fn coordinator(&self) -> Option<Coordinator> {
+        let settings = &self.distributed;
+        if settings.workers.is_empty() {
+            return None;
+        }
+        let mut coordinator = Coordinator::new(settings.workers.clone());
+        if let Some(key) = &settings.api_key {
+            coordinator = coordinator.with_api_key(key);
+        }
+        Some(coordinator)
+    }
+
+    /// Working directory settings
+    pub fn workdir_config(&self) -> WorkdirConfig {
+        let mut config = WorkdirConfig::default();
+        if let Some(root) = &self.limits.tmpdir {
+            config = config.with_root(root);
+        }
+        if let Some(mb) = self.limits.min_free_mb {
+            config = config.with_min_free_bytes(mb << 20);
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_layers() {
+        let config = AppConfig::from_toml_str(
+            r#"
+            [server]
+            port = 9000
+
+            [database]
+            url = "postgres://file/db"
+            schema = "normalized"
+            max_connections = 20
+
+            [processor]
+            batch_size = 500
+            preset = "kaoshibao"
+
+            [limits]
+            max_extracted_mb = 2
+            spill_threshold_mb = 0
+            "#,
+        )
+        .unwrap()
+        .with_env(env(&[("PORT", "9100"), ("MD2DB_DB_INSERT_MODE", "copy"), ("HOST", ""), ("MD2DB_MAX_UPLOAD_MB", "5")]))
+        .unwrap();
+
+        // The environment wins over the file, which wins over the defaults
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.database.url.as_deref(), Some("postgres://file/db"));
+        assert!(config.normalized_schema());
+        let pool = config.database_config().unwrap();
+        assert_eq!(pool.max_connections, 20);
+        assert_eq!(pool.insert_mode, InsertMode::Copy);
+        assert_eq!(config.zip_limits().max_extracted_bytes, 2 << 20);
+        assert_eq!(config.spill_threshold(), None);
+        assert_eq!(config.body_limits().upload, 5 << 20);
+
+        let processor = config.processor_config(None).unwrap();
+        assert_eq!(processor.batch_size, 500);
+        assert!(processor.parser_options.numbered_questions);
+        let processor = config.processor_config(Some("default")).unwrap();
+        assert!(!processor.parser_options.numbered_questions);
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        assert!(AppConfig::from_toml_str("[server]\nprot = 1").is_err());
+        assert!(AppConfig::default().with_env(env(&[("PORT", "http")])).is_err());
+        assert!(AppConfig::default().with_env(env(&[("MD2DB_SCHEMA", "graph")])).is_err());
+        assert!(AppConfig::default().with_env(env(&[("MD2DB_PRESET", "nope")])).is_err());
+
+        let config = AppConfig::default().with_env(env(&[])).unwrap();
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.database_config().unwrap(), DatabaseConfig::default());
+        assert_eq!(config.zip_limits(), ZipLimits::default());
+        assert_eq!(config.spill_threshold(), Some(DEFAULT_SPILL_THRESHOLD));
+        assert!(config.authenticator().is_none());
+        assert_eq!(config.body_limits(), BodyLimits::default());
+    }
+
+    #[test]
+    fn test_auth_settings() {
+        let config = AppConfig::from_toml_str("[auth.keys]\nci = \"k-file\"")
+            .unwrap()
+            .with_env(env(&[("MD2DB_API_KEYS", "ops:k-ops, k-bare")]))
+            .unwrap();
+        let names: Vec<_> = config.auth.keys.keys().map(String::as_str).collect();
+        assert_eq!(names, ["ci", "key2", "ops"]);
+        let auth = config.authenticator().unwrap();
+        assert_eq!(auth.authenticate("k-file").as_deref(), Some("ci"));
+        assert_eq!(auth.authenticate("k-bare").as_deref(), Some("key2"));
+
+        assert!(AppConfig::from_toml_str("[auth.keys]\nci = \" \"").unwrap().with_env(env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_webhook_settings() {
+        let config = AppConfig::from_toml_str("[webhooks]\nsecret = \"file\"\nmax_attempts = 3")
+            .unwrap()
+            .with_env(env(&[("MD2DB_WEBHOOK_URLS", "https://a.example/hook, http://b.example/hook,")]))
+            .unwrap();
+        assert_eq!(config.webhooks.urls, ["https://a.example/hook", "http://b.example/hook"]);
+        assert_eq!(config.webhooks.max_attempts, Some(3));
+        #[cfg(feature = "webhooks")]
+        assert_eq!(config.webhook_notifier().unwrap().urls().len(), 2);
+
+        assert!(AppConfig::default().with_env(env(&[("MD2DB_WEBHOOK_URLS", "ftp://c.example")])).is_err());
+        #[cfg(feature = "webhooks")]
+        assert!(AppConfig::default().webhook_notifier().is_none());
+    }
+
+    #[test]
+    fn test_distributed_settings() {
+        let config = AppConfig::from_toml_str("[distributed]\napi_key = \"k-file\"")
+            .unwrap()
+            .with_env(env(&[("MD2DB_WORKERS", "http://node1:8080, http://node2:8080")]))
+            .unwrap();
+        assert_eq!(config.distributed.workers, ["http://node1:8080", "http://node2:8080"]);
+        assert_eq!(config.distributed.api_key.as_deref(), Some("k-file"));
+        #[cfg(feature = "distributed")]
+        assert!(config.coordinator().is_some());
+
+        assert!(AppConfig::default().with_env(env(&[("MD2DB_WORKERS", "node3:8080")])).is_err());
+        #[cfg(feature = "distributed")]
+        assert!(AppConfig::default().coordinator().is_none());
+    }
+}
@@ -0,0 +1,274 @@
+//! Consistency audit of answers against question types
+//!
+//! [`ConsistencyChecker`] looks for questions whose answer does not fit
+//! their type: a single choice question with no correct option or several,
+//! a multiple choice question with only one, a true/false question whose
+//! options are not a true/false pair, and answers naming option letters the
+//! question does not have. Unlike [`crate::validate`] it rejects nothing; it
+//! reports, so stored banks can be audited as well as new imports.
+//!
+//! Questions are checked against their stored type, so Markdown imports
+//! should be classified first. Correct options are taken from the options'
+//! `is_correct` flags, or from the answer letters when no option is flagged.
+
+use crate::answer::{strip_letter, FALSE_WORDS, TRUE_WORDS};
+use crate::models::{Question, QuestionType};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Kind of inconsistency found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsistencyIssueKind {
+    /// An objective question with options has no correct one
+    NoCorrectOption,
+    /// A single choice or true/false question has more than one correct option
+    SeveralCorrectOptions,
+    /// A multiple choice question has exactly one correct option
+    SingleCorrectOption,
+    /// A true/false question has options other than a true/false pair
+    NonBinaryOptions,
+    /// The answer names an option letter past the last option
+    UnknownOptionLetter,
+}
+
+impl ConsistencyIssueKind {
+    /// Code used in reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConsistencyIssueKind::NoCorrectOption => "NO_CORRECT_OPTION",
+            ConsistencyIssueKind::SeveralCorrectOptions => "SEVERAL_CORRECT_OPTIONS",
+            ConsistencyIssueKind::SingleCorrectOption => "SINGLE_CORRECT_OPTION",
+            ConsistencyIssueKind::NonBinaryOptions => "NON_BINARY_OPTIONS",
+            ConsistencyIssueKind::UnknownOptionLetter => "UNKNOWN_OPTION_LETTER",
+        }
+    }
+}
+
+/// One inconsistency in a question
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyIssue {
+    pub kind: ConsistencyIssueKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConsistencyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind.code(), self.message)
+    }
+}
+
+/// Inconsistencies found in one question
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    pub question_id: Uuid,
+    #[serde(rename = "type")]
+    pub qtype: QuestionType,
+    /// Empty when the question is consistent
+    pub issues: Vec<ConsistencyIssue>,
+}
+
+impl ConsistencyReport {
+    /// Whether no inconsistency was found
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Audits questions for answers that do not fit their type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConsistencyChecker;
+
+impl ConsistencyChecker {
+    /// Create a checker
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Inconsistencies in `question`; empty if there are none
+    pub fn check(&self, question: &Question) -> Vec<ConsistencyIssue> {
+        let mut issues = Vec::new();
+        let options = question.options.len();
+        let issue = |kind, message: String| ConsistencyIssue { kind, message };
+
+        let letters = answer_letters(question);
+        if let Some(letters) = &letters {
+            let unknown: String = letters
+                .iter()
+                .filter(|&&i| i >= options)
+                .map(|&i| (b'A' + i as u8) as char)
+                .collect();
+            if !unknown.is_empty() {
+                issues.push(issue(
+                    ConsistencyIssueKind::UnknownOptionLetter,
+                    format!("answer names {} but there are only {} options", unknown, options),
+                ));
+            }
+        }
+
+        if question.qtype == QuestionType::TrueFalse && options > 0 {
+            if options != 2 {
+                issues.push(issue(
+                    ConsistencyIssueKind::NonBinaryOptions,
+                    format!("{} options on a true/false question", options),
+                ));
+            } else if !is_true_false_pair(question) {
+                issues.push(issue(
+                    ConsistencyIssueKind::NonBinaryOptions,
+                    "options are not a true/false pair".to_string(),
+                ));
+            }
+        }
+
+        let objective = matches!(
+            question.qtype,
+            QuestionType::Choice | QuestionType::MultipleChoice | QuestionType::TrueFalse
+        );
+        if objective && options > 0 {
+            let correct = correct_options(question, letters.as_deref());
+            match (question.qtype, correct) {
+                (_, 0) => issues.push(issue(
+                    ConsistencyIssueKind::NoCorrectOption,
+                    format!("none of the {} options is marked correct", options),
+                )),
+                (QuestionType::MultipleChoice, 1) => issues.push(issue(
+                    ConsistencyIssueKind::SingleCorrectOption,
+                    "multiple choice question with a single correct option".to_string(),
+                )),
+                (QuestionType::Choice | QuestionType::TrueFalse, n) if n > 1 => issues.push(issue(
+                    ConsistencyIssueKind::SeveralCorrectOptions,
+                    format!("{} correct options on a single-answer question", n),
+                )),
+                _ => {}
+            }
+        }
+        issues
+    }
+
+    /// A report for each question, in order
+    pub fn audit(&self, questions: &[Question]) -> Vec<ConsistencyReport> {
+        questions
+            .iter()
+            .map(|question| ConsistencyReport {
+                question_id: question.id,
+                qtype: question.qtype,
+                issues: self.check(question),
+            })
+            .collect()
+    }
+}
+
+/// Option indices named by a letter answer such as "B" or "A, C"
+///
+/// `None` when the question has no options or answer, or the answer is not
+/// made of letters.
+fn answer_letters(question: &Question) -> Option<Vec<usize>> {
+    let answer = question.answer.as_deref()?.trim();
+    let lower = answer.to_lowercase();
+    // A word such as "True" is not a list of letters
+    if question.options.is_empty() || TRUE_WORDS.contains(&lower.as_str()) || FALSE_WORDS.contains(&lower.as_str()) {
+        return None;
+    }
+    let letters: Vec<char> = answer
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, ',' | '，' | '、' | ';' | '；' | '.' | '。'))
+        .collect();
+    let is_letters = !letters.is_empty() && letters.iter().all(|c| c.is_ascii_alphabetic());
+    if !is_letters || letters.len() > question.options.len() {
+        return None;
+    }
+    let mut indices: Vec<usize> = letters.iter().map(|c| (c.to_ascii_uppercase() as u8 - b'A') as usize).collect();
+    indices.sort_unstable();
+    indices.dedup();
+    Some(indices)
+}
+
+/// Number of correct options: flagged ones, or else those the answer names
+fn correct_options(question: &Question, letters: Option<&[usize]>) -> usize {
+    let flagged = question.options.iter().filter(|o| o.is_correct).count();
+    if flagged > 0 {
+        return flagged;
+    }
+    letters.map_or(0, |letters| letters.iter().filter(|&&i| i < question.options.len()).count())
+}
+
+/// Whether the two options read as one true and one false statement
+fn is_true_false_pair(question: &Question) -> bool {
+    let kind = |content: &str| {
+        let content = strip_letter(content).to_lowercase();
+        let matches = |words: &[&str]| words.iter().any(|w| content == *w);
+        match (matches(TRUE_WORDS), matches(FALSE_WORDS)) {
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            _ => None,
+        }
+    };
+    match &question.options[..] {
+        [a, b] => matches!((kind(&a.content), kind(&b.content)), (Some(x), Some(y)) if x != y),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    fn question(qtype: QuestionType, options: &[&str], answer: &str) -> Question {
+        Question {
+            qtype,
+            stem: "Question".to_string(),
+            options: options
+                .iter()
+                .zip(0..)
+                .map(|(content, sort_order)| QuestionOption {
+                    content: content.to_string(),
+                    sort_order,
+                    is_correct: false,
+                })
+                .collect(),
+            answer: (!answer.is_empty()).then(|| answer.to_string()),
+            ..Question::default()
+        }
+    }
+
+    fn kinds(question: &Question) -> Vec<ConsistencyIssueKind> {
+        ConsistencyChecker::new().check(question).into_iter().map(|i| i.kind).collect()
+    }
+
+    #[test]
+    fn test_choice_answers() {
+        use ConsistencyIssueKind::*;
+        let abcd = ["A. 1", "B. 2", "C. 3", "D. 4"];
+        assert!(kinds(&question(QuestionType::Choice, &abcd, "B")).is_empty());
+        assert_eq!(kinds(&question(QuestionType::Choice, &abcd, "")), [NoCorrectOption]);
+        assert_eq!(kinds(&question(QuestionType::Choice, &abcd, "AC")), [SeveralCorrectOptions]);
+        assert_eq!(kinds(&question(QuestionType::MultipleChoice, &abcd, "C")), [SingleCorrectOption]);
+        assert!(kinds(&question(QuestionType::MultipleChoice, &abcd, "A, D")).is_empty());
+        assert_eq!(kinds(&question(QuestionType::Choice, &abcd[..2], "E")), [UnknownOptionLetter, NoCorrectOption]);
+
+        // Flags win over the answer text
+        let mut flagged = question(QuestionType::Choice, &abcd, "AB");
+        flagged.options[0].is_correct = true;
+        assert!(kinds(&flagged).is_empty());
+
+        // Untyped questions are only checked for unknown letters
+        assert!(kinds(&question(QuestionType::Subjective, &abcd, "AC")).is_empty());
+    }
+
+    #[test]
+    fn test_true_false_options() {
+        use ConsistencyIssueKind::*;
+        assert!(kinds(&question(QuestionType::TrueFalse, &["A. 正确", "B. 错误"], "A")).is_empty());
+        assert!(kinds(&question(QuestionType::TrueFalse, &[], "True")).is_empty());
+        assert_eq!(kinds(&question(QuestionType::TrueFalse, &["A. Red", "B. Blue"], "A")), [NonBinaryOptions]);
+        assert_eq!(
+            kinds(&question(QuestionType::TrueFalse, &["A. True", "B. False", "C. Maybe"], "A")),
+            [NonBinaryOptions]
+        );
+
+        let reports = ConsistencyChecker::new().audit(&[question(QuestionType::TrueFalse, &["True", "False"], "AB")]);
+        assert_eq!(reports[0].issues[0].kind, SeveralCorrectOptions);
+        assert!(!reports[0].is_consistent());
+    }
+}
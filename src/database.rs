@@ -2,154 +2,1915 @@
 //!
 //! This module provides repository abstraction for different database backends.
 
-use crate::models::Question;
+use crate::error::{Md2DbError, Result};
+use crate::dedup::{self, content_hash, DedupOptions, Decision, SaveOutcome};
+use crate::jobs::JobRecord;
+use crate::models::{ImportedSource, Question, QuestionBank, QuestionRevision};
+use crate::stats::{QuestionStats, StatsCollector};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use uuid::Uuid;
 
+/// Default page size for listing
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+/// Maximum page size for listing
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Sort order for listing (by creation time, then id)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Oldest first
+    #[default]
+    Asc,
+    /// Newest first
+    Desc,
+}
+
+/// Parameters for listing stored questions
+///
+/// Either `offset` or `cursor` pagination can be used; when a cursor is given
+/// the offset is ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ListParams {
+    /// Maximum number of items to return (defaults to 50, capped at 1000)
+    pub limit: Option<usize>,
+    /// Number of items to skip
+    pub offset: Option<usize>,
+    /// Opaque cursor returned as `next_cursor` by a previous page
+    pub cursor: Option<String>,
+    /// Sort order
+    pub order: SortOrder,
+    /// Only list questions of this type
+    #[serde(rename = "type")]
+    pub qtype: Option<crate::models::QuestionType>,
+}
+
+impl ListParams {
+    /// Effective page size after applying defaults and caps
+    pub fn effective_limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Decode the cursor, if any
+    pub fn decode_cursor(&self) -> Result<Option<(DateTime<Utc>, Uuid)>> {
+        self.cursor.as_deref().map(decode_cursor).transpose()
+    }
+}
+
+/// A page of results
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    /// Items on this page
+    pub items: Vec<T>,
+    /// Total number of items matching the query
+    pub total: u64,
+    /// Cursor for the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Encode a pagination cursor from the sort key of the last item
+pub fn encode_cursor(created_at: DateTime<Utc>, id: Uuid) -> String {
+    let nanos = created_at
+        .timestamp_nanos_opt()
+        .unwrap_or_else(|| created_at.timestamp_micros().saturating_mul(1000));
+    format!("{}_{}", nanos, id.simple())
+}
+
+/// Decode a pagination cursor produced by [`encode_cursor`]
+pub fn decode_cursor(cursor: &str) -> Result<(DateTime<Utc>, Uuid)> {
+    let (nanos, id) = cursor
+        .split_once('_')
+        .ok_or_else(|| Md2DbError::validation("cursor", format!("Invalid cursor: {}", cursor)))?;
+    let nanos: i64 = nanos
+        .parse()
+        .map_err(|_| Md2DbError::validation("cursor", format!("Invalid cursor: {}", cursor)))?;
+    let created_at = DateTime::from_timestamp_nanos(nanos);
+    let id = Uuid::parse_str(id).map_err(|e| Md2DbError::validation("cursor", e.to_string()))?;
+    Ok((created_at, id))
+}
+
+/// Build a page from a fetched window that may contain one extra row
+fn build_page(mut items: Vec<Question>, limit: usize, total: u64) -> Page<Question> {
+    let next_cursor = if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(|q| encode_cursor(q.created_at, q.id))
+    } else {
+        None
+    };
+    Page { items, total, next_cursor }
+}
+
+/// How new question rows are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InsertMode {
+    /// Multi-row `INSERT ... VALUES` statements
+    #[default]
+    MultiRow,
+    /// `COPY ... FROM STDIN` into a staging table, then one upsert
+    ///
+    /// Faster for imports of thousands of questions.
+    Copy,
+}
+
+impl std::str::FromStr for InsertMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "multi-row" | "multirow" | "values" | "insert" => Ok(InsertMode::MultiRow),
+            "copy" => Ok(InsertMode::Copy),
+            other => anyhow::bail!("Unknown insert mode: {} (expected multi-row or copy)", other),
+        }
+    }
+}
+
+/// Connection pool settings for database-backed repositories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseConfig {
+    /// Maximum number of pooled connections
+    pub max_connections: u32,
+    /// How long to wait for a free connection before failing
+    pub acquire_timeout: Duration,
+    /// Server-side limit for a single statement (`None` keeps the server default)
+    pub statement_timeout: Option<Duration>,
+    /// How question rows are written
+    pub insert_mode: InsertMode,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            acquire_timeout: Duration::from_secs(30),
+            statement_timeout: None,
+            insert_mode: InsertMode::default(),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// Read overrides from `MD2DB_DB_MAX_CONNECTIONS`,
+    /// `MD2DB_DB_ACQUIRE_TIMEOUT_SECS`, `MD2DB_DB_STATEMENT_TIMEOUT_MS` and
+    /// `MD2DB_DB_INSERT_MODE`
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        let mut config = Self::default();
+        if let Some(max) = var("MD2DB_DB_MAX_CONNECTIONS") {
+            config = config.with_max_connections(max as u32);
+        }
+        if let Some(secs) = var("MD2DB_DB_ACQUIRE_TIMEOUT_SECS") {
+            config = config.with_acquire_timeout(Duration::from_secs(secs));
+        }
+        if let Some(ms) = var("MD2DB_DB_STATEMENT_TIMEOUT_MS") {
+            config = config.with_statement_timeout(Duration::from_millis(ms));
+        }
+        if let Some(mode) = std::env::var("MD2DB_DB_INSERT_MODE").ok().and_then(|v| v.parse().ok()) {
+            config = config.with_insert_mode(mode);
+        }
+        config
+    }
+
+    /// Set the maximum number of pooled connections (at least 1)
+    pub fn with_max_connections(mut self, max: u32) -> Self {
+        self.max_connections = max.max(1);
+        self
+    }
+
+    /// Set how long to wait for a free connection
+    pub fn with_acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    /// Set the per-statement timeout; zero disables it
+    pub fn with_statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = (!timeout.is_zero()).then_some(timeout);
+        self
+    }
+
+    /// Set how question rows are written
+    pub fn with_insert_mode(mut self, mode: InsertMode) -> Self {
+        self.insert_mode = mode;
+        self
+    }
+}
+
 /// Trait for question repository operations
 #[async_trait]
 pub trait QuestionRepository: Send + Sync {
     /// Save a batch of questions to the database
-    async fn save_batch(&self, questions: &[Question]) -> anyhow::Result<Vec<Uuid>>;
+    async fn save_batch(&self, questions: &[Question]) -> Result<Vec<Uuid>>;
+
+    /// Save a batch, skipping questions whose content is already stored
+    ///
+    /// Each question is matched by its normalized content hash and, if enabled,
+    /// against near-duplicates of the same type (see [`dedup::decide`]).
+    /// Questions earlier in the batch count as stored. Returns one outcome per
+    /// input question, in order.
+    async fn save_batch_dedup(
+        &self,
+        questions: &[Question],
+        options: &DedupOptions,
+    ) -> Result<Vec<SaveOutcome>>;
+
+    /// Find a question by its ID
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Question>>;
+
+    /// Find all questions of a specific type
+    async fn find_by_type(&self, qtype: &crate::models::QuestionType) -> Result<Vec<Question>>;
+
+    /// Find all questions carrying a specific topic tag
+    async fn find_by_tag(&self, tag: &str) -> Result<Vec<Question>>;
+
+    /// List stored questions page by page
+    async fn list(&self, params: &ListParams) -> Result<Page<Question>>;
+
+    /// Replace a stored question; returns false if it does not exist
+    async fn update(&self, question: &Question) -> Result<bool> {
+        self.update_as(question, None).await
+    }
+
+    /// Replace a stored question, keeping the prior version as a revision
+    /// attributed to `actor`; returns false if it does not exist
+    async fn update_as(&self, question: &Question, actor: Option<&str>) -> Result<bool>;
+
+    /// Prior versions of a question, newest first
+    async fn history(&self, id: Uuid) -> Result<Vec<QuestionRevision>>;
+
+    /// Put a prior version of a question back in place
+    ///
+    /// The current version is kept as a new revision, so a restore can itself
+    /// be undone. Returns the restored question, or `None` if the question or
+    /// revision does not exist.
+    async fn restore_revision(&self, id: Uuid, revision: i64) -> Result<Option<Question>> {
+        let history = self.history(id).await?;
+        let Some(found) = history.into_iter().find(|r| r.revision == revision) else {
+            return Ok(None);
+        };
+
+        let mut question = found.question;
+        question.id = id;
+        if !self.update_as(&question, None).await? {
+            return Ok(None);
+        }
+        Ok(Some(question))
+    }
+
+    /// Soft-delete a question by ID; returns false if it does not exist
+    ///
+    /// Deleted questions are hidden from lookups, listings and deduplication
+    /// until they are restored, and removed for good by [`purge`].
+    ///
+    /// [`purge`]: QuestionRepository::purge
+    async fn delete(&self, id: Uuid) -> Result<bool>;
+
+    /// Soft-delete several questions; returns the number actually deleted
+    async fn delete_batch(&self, ids: &[Uuid]) -> Result<u64>;
+
+    /// Bring back a soft-deleted question; returns false if it is not deleted
+    async fn restore(&self, id: Uuid) -> Result<bool>;
+
+    /// Permanently remove questions soft-deleted before `before`, with their
+    /// history; returns the number removed
+    async fn purge(&self, before: DateTime<Utc>) -> Result<u64>;
+
+    /// Store a new question bank; returns its ID
+    async fn create_bank(&self, bank: &QuestionBank) -> Result<Uuid>;
+
+    /// Find a question bank by its ID
+    async fn find_bank(&self, id: Uuid) -> Result<Option<QuestionBank>>;
+
+    /// List every question bank, oldest first
+    async fn list_banks(&self) -> Result<Vec<QuestionBank>>;
+
+    /// Move stored questions into a bank; returns the number assigned
+    ///
+    /// Fails if the bank does not exist.
+    async fn assign_to_bank(&self, bank_id: Uuid, question_ids: &[Uuid]) -> Result<u64>;
+
+    /// Find all questions in a bank
+    async fn find_by_bank(&self, bank_id: Uuid) -> Result<Vec<Question>>;
+
+    /// Begin a transactional import session
+    async fn begin_import(&self) -> Result<Box<dyn ImportSession>>;
+
+    /// Insert or replace a background job record
+    async fn save_job(&self, job: &JobRecord) -> Result<()>;
+
+    /// Find a background job record by its ID
+    async fn find_job(&self, id: Uuid) -> Result<Option<JobRecord>>;
+
+    /// List every background job record, newest first
+    async fn list_jobs(&self) -> Result<Vec<JobRecord>>;
+
+    /// Insert or replace the record of a file imported into a bank
+    async fn save_source(&self, source: &ImportedSource) -> Result<()>;
+
+    /// Records of every file imported into a bank
+    async fn list_sources(&self, bank_id: Uuid) -> Result<Vec<ImportedSource>>;
+
+    /// Check that the backing store is reachable
+    async fn ping(&self) -> Result<()>;
+
+    /// Counts over every stored question, for dashboards
+    ///
+    /// The default pages through [`list`](QuestionRepository::list), since
+    /// the review and language counts need each question's text.
+    async fn stats(&self) -> Result<QuestionStats> {
+        let mut collector = StatsCollector::new();
+        let mut params = ListParams {
+            limit: Some(MAX_PAGE_SIZE),
+            ..ListParams::default()
+        };
+        loop {
+            let page = self.list(&params).await?;
+            for question in &page.items {
+                collector.add(question);
+            }
+            match page.next_cursor {
+                Some(cursor) => params.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        Ok(collector.finish(&self.list_banks().await?))
+    }
+}
+
+/// Shared repositories, such as the server's `Arc<dyn QuestionRepository>`,
+/// can be handed to a [`SingleMachineProcessor`](crate::processor::SingleMachineProcessor)
+#[async_trait]
+impl<R: QuestionRepository + ?Sized> QuestionRepository for std::sync::Arc<R> {
+    async fn save_batch(&self, questions: &[Question]) -> Result<Vec<Uuid>> {
+        (**self).save_batch(questions).await
+    }
+
+    async fn save_batch_dedup(
+        &self,
+        questions: &[Question],
+        options: &DedupOptions,
+    ) -> Result<Vec<SaveOutcome>> {
+        (**self).save_batch_dedup(questions, options).await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Question>> {
+        (**self).find_by_id(id).await
+    }
+
+    async fn find_by_type(&self, qtype: &crate::models::QuestionType) -> Result<Vec<Question>> {
+        (**self).find_by_type(qtype).await
+    }
+
+    async fn find_by_tag(&self, tag: &str) -> Result<Vec<Question>> {
+        (**self).find_by_tag(tag).await
+    }
+
+    async fn list(&self, params: &ListParams) -> Result<Page<Question>> {
+        (**self).list(params).await
+    }
+
+    async fn update(&self, question: &Question) -> Result<bool> {
+        (**self).update(question).await
+    }
+
+    async fn update_as(&self, question: &Question, actor: Option<&str>) -> Result<bool> {
+        (**self).update_as(question, actor).await
+    }
+
+    async fn history(&self, id: Uuid) -> Result<Vec<QuestionRevision>> {
+        (**self).history(id).await
+    }
+
+    async fn restore_revision(&self, id: Uuid, revision: i64) -> Result<Option<Question>> {
+        (**self).restore_revision(id, revision).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool> {
+        (**self).delete(id).await
+    }
+
+    async fn delete_batch(&self, ids: &[Uuid]) -> Result<u64> {
+        (**self).delete_batch(ids).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<bool> {
+        (**self).restore(id).await
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+        (**self).purge(before).await
+    }
+
+    async fn create_bank(&self, bank: &QuestionBank) -> Result<Uuid> {
+        (**self).create_bank(bank).await
+    }
+
+    async fn find_bank(&self, id: Uuid) -> Result<Option<QuestionBank>> {
+        (**self).find_bank(id).await
+    }
+
+    async fn list_banks(&self) -> Result<Vec<QuestionBank>> {
+        (**self).list_banks().await
+    }
+
+    async fn assign_to_bank(&self, bank_id: Uuid, question_ids: &[Uuid]) -> Result<u64> {
+        (**self).assign_to_bank(bank_id, question_ids).await
+    }
+
+    async fn find_by_bank(&self, bank_id: Uuid) -> Result<Vec<Question>> {
+        (**self).find_by_bank(bank_id).await
+    }
+
+    async fn begin_import(&self) -> Result<Box<dyn ImportSession>> {
+        (**self).begin_import().await
+    }
+
+    async fn save_job(&self, job: &JobRecord) -> Result<()> {
+        (**self).save_job(job).await
+    }
+
+    async fn find_job(&self, id: Uuid) -> Result<Option<JobRecord>> {
+        (**self).find_job(id).await
+    }
+
+    async fn list_jobs(&self) -> Result<Vec<JobRecord>> {
+        (**self).list_jobs().await
+    }
+
+    async fn save_source(&self, source: &ImportedSource) -> Result<()> {
+        (**self).save_source(source).await
+    }
+
+    async fn list_sources(&self, bank_id: Uuid) -> Result<Vec<ImportedSource>> {
+        (**self).list_sources(bank_id).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        (**self).ping().await
+    }
+
+    async fn stats(&self) -> Result<QuestionStats> {
+        (**self).stats().await
+    }
+}
+
+/// An import whose batches are committed or rolled back together
+///
+/// Batches saved through a session only become visible on [`commit`]; rolling
+/// back, or dropping the session without committing, discards all of them, so
+/// an import that fails halfway leaves no orphaned questions behind.
+///
+/// [`commit`]: ImportSession::commit
+#[async_trait]
+pub trait ImportSession: Send {
+    /// Identifier of the session, reported in logs and API responses
+    fn id(&self) -> Uuid;
+
+    /// Save a batch of questions within the session
+    async fn save_batch(&mut self, questions: &[Question]) -> Result<Vec<Uuid>>;
+
+    /// Save a batch within the session, skipping stored questions
+    ///
+    /// Behaves like [`QuestionRepository::save_batch_dedup`]; questions saved
+    /// earlier in the same session count as stored.
+    async fn save_batch_dedup(
+        &mut self,
+        questions: &[Question],
+        options: &DedupOptions,
+    ) -> Result<Vec<SaveOutcome>>;
+
+    /// Make every batch visible; returns the number of questions written
+    async fn commit(self: Box<Self>) -> Result<usize>;
+
+    /// Discard every batch saved in the session
+    async fn rollback(self: Box<Self>) -> Result<()>;
+}
+
+/// A versioned schema migration
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Monotonically increasing version number
+    pub version: i64,
+    /// Short description
+    pub name: &'static str,
+    /// SQL to execute (may contain several statements)
+    pub sql: &'static str,
+}
+
+/// Embedded migrations, in the order they must be applied
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_questions",
+        sql: include_str!("../migrations/0001_create_questions.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "question_metadata",
+        sql: include_str!("../migrations/0002_question_metadata.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "normalized_options_images",
+        sql: include_str!("../migrations/0003_normalized_options_images.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "content_hash",
+        sql: include_str!("../migrations/0004_content_hash.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "question_revisions",
+        sql: include_str!("../migrations/0005_question_revisions.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "soft_delete",
+        sql: include_str!("../migrations/0006_soft_delete.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "question_banks",
+        sql: include_str!("../migrations/0007_question_banks.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "image_stored_path",
+        sql: include_str!("../migrations/0008_image_stored_path.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "question_sequence",
+        sql: include_str!("../migrations/0009_question_sequence.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "import_jobs",
+        sql: include_str!("../migrations/0010_import_jobs.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "import_sources",
+        sql: include_str!("../migrations/0011_import_sources.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "question_structure",
+        sql: include_str!("../migrations/0012_question_structure.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "question_type_source",
+        sql: include_str!("../migrations/0013_question_type_source.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "near_duplicate_index",
+        sql: include_str!("../migrations/0014_near_duplicate_index.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "image_alt",
+        sql: include_str!("../migrations/0015_image_alt.sql"),
+    },
+];
+
+/// PostgreSQL implementation using SQLx
+#[cfg(feature = "postgres")]
+pub mod postgres {
+    use super::*;
+    use crate::models::{ImageRef, QuestionOption};
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgRow};
+    use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder, Row};
+    use std::collections::HashMap;
+
+    /// Encode a value for a JSON text column
+    fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+        serde_json::to_string(value).map_err(|e| Md2DbError::repository("encoding a JSON column", e))
+    }
+
+    /// Decode a JSON text column
+    fn from_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T> {
+        serde_json::from_str(text).map_err(|e| Md2DbError::repository("decoding a JSON column", e))
+    }
+
+    /// How options and images are laid out in the database
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum SchemaMode {
+        /// Options are serialized as JSON text on the `questions` row
+        #[default]
+        Json,
+        /// Options and images live in `question_options` / `question_images`
+        Normalized,
+    }
+
+    /// PostgreSQL repository with optional read replica
+    ///
+    /// Writes, migrations and import sessions always use the primary pool.
+    /// Plain reads (`find_by_*`, `list`, option search) go to the replica when
+    /// one is configured, so they may briefly lag behind recent writes.
+    pub struct PostgresRepository {
+        pool: PgPool,
+        replica: Option<PgPool>,
+        mode: SchemaMode,
+        insert_mode: InsertMode,
+    }
+
+    impl PostgresRepository {
+        /// Create a new PostgreSQL repository
+        pub async fn new(database_url: &str) -> Result<Self> {
+            Self::connect(database_url, &DatabaseConfig::default()).await
+        }
+
+        /// Create a repository with custom pool settings
+        pub async fn connect(database_url: &str, config: &DatabaseConfig) -> Result<Self> {
+            let pool = open_pool(database_url, config).await?;
+            Ok(Self::with_pool(pool).with_insert_mode(config.insert_mode))
+        }
+
+        /// Create a repository that sends reads to a read-only replica
+        pub async fn connect_with_replica(
+            database_url: &str,
+            replica_url: &str,
+            config: &DatabaseConfig,
+        ) -> Result<Self> {
+            let replica = open_pool(replica_url, config).await?;
+            Ok(Self::connect(database_url, config).await?.with_replica(replica))
+        }
+
+        /// Create a repository that uses the normalized relational schema
+        pub async fn new_normalized(database_url: &str) -> Result<Self> {
+            Ok(Self::new(database_url).await?.with_mode(SchemaMode::Normalized))
+        }
+
+        /// Create repository from existing connection pool
+        pub fn with_pool(pool: PgPool) -> Self {
+            Self {
+                pool,
+                replica: None,
+                mode: SchemaMode::default(),
+                insert_mode: InsertMode::default(),
+            }
+        }
+
+        /// Route reads to a replica pool
+        pub fn with_replica(mut self, replica: PgPool) -> Self {
+            self.replica = Some(replica);
+            self
+        }
+
+        /// Whether reads are routed to a replica
+        pub fn has_replica(&self) -> bool {
+            self.replica.is_some()
+        }
+
+        /// Pool used for plain reads
+        fn reader(&self) -> &PgPool {
+            self.replica.as_ref().unwrap_or(&self.pool)
+        }
+
+        /// Set how question rows are written
+        pub fn with_insert_mode(mut self, insert_mode: InsertMode) -> Self {
+            self.insert_mode = insert_mode;
+            self
+        }
+
+        /// Set the schema mode
+        pub fn with_mode(mut self, mode: SchemaMode) -> Self {
+            self.mode = mode;
+            self
+        }
+
+        /// Schema mode in use
+        pub fn mode(&self) -> SchemaMode {
+            self.mode
+        }
+
+        /// Apply all pending schema migrations
+        ///
+        /// Migrations are applied in version order, each in its own transaction,
+        /// and recorded in `schema_migrations`. An advisory lock serializes
+        /// concurrent startups. Returns the versions that were applied.
+        pub async fn migrate(&self) -> Result<Vec<i64>> {
+            let mut conn = self.pool.acquire().await?;
+
+            sqlx::query("SELECT pg_advisory_lock($1)")
+                .bind(MIGRATION_LOCK_ID)
+                .execute(&mut *conn)
+                .await?;
+
+            let result = Self::apply_migrations(&mut conn).await;
+
+            sqlx::query("SELECT pg_advisory_unlock($1)")
+                .bind(MIGRATION_LOCK_ID)
+                .execute(&mut *conn)
+                .await?;
+
+            result
+        }
+
+        async fn apply_migrations(conn: &mut PgConnection) -> Result<Vec<i64>> {
+            sqlx::query(
+                "CREATE TABLE IF NOT EXISTS schema_migrations (\
+                    version BIGINT PRIMARY KEY, \
+                    name TEXT NOT NULL, \
+                    applied_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            let applied: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_migrations")
+                .fetch_all(&mut *conn)
+                .await?;
+
+            let mut newly_applied = Vec::new();
+            for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+                tracing::info!("Applying migration {:04} {}", migration.version, migration.name);
+
+                let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+                sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+                sqlx::query("INSERT INTO schema_migrations (version, name) VALUES ($1, $2)")
+                    .bind(migration.version)
+                    .bind(migration.name)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+
+                newly_applied.push(migration.version);
+            }
+
+            Ok(newly_applied)
+        }
+
+        /// Find questions with at least one option containing `needle` (case-insensitive)
+        pub async fn find_by_option_text(&self, needle: &str) -> Result<Vec<Question>> {
+            let filter = match self.mode {
+                SchemaMode::Json => {
+                    "EXISTS (SELECT 1 FROM jsonb_array_elements(q.options::jsonb) o \
+                     WHERE o->>'content' ILIKE $1)"
+                }
+                SchemaMode::Normalized => {
+                    "EXISTS (SELECT 1 FROM question_options o \
+                     WHERE o.question_id = q.id AND o.content ILIKE $1)"
+                }
+            };
+            let sql = format!("SELECT {} FROM questions q WHERE deleted_at IS NULL AND {}", SELECT_COLUMNS, filter);
+            let rows = sqlx::query(&sql)
+                .bind(like_pattern(needle))
+                .fetch_all(self.reader())
+                .await?;
+
+            self.load(&rows).await
+        }
+
+        /// Convert rows into questions, attaching normalized options and images
+        async fn load(&self, rows: &[PgRow]) -> Result<Vec<Question>> {
+            let mut questions = rows.iter().map(row_to_question).collect::<Result<Vec<_>>>()?;
+            if self.mode == SchemaMode::Normalized && !questions.is_empty() {
+                let mut conn = self.reader().acquire().await?;
+                attach_children(&mut conn, &mut questions).await?;
+            }
+            Ok(questions)
+        }
+    }
+
+    /// Open a connection pool with the given settings
+    async fn open_pool(database_url: &str, config: &DatabaseConfig) -> Result<PgPool> {
+        let mut options: PgConnectOptions = database_url.parse()?;
+        if let Some(timeout) = config.statement_timeout {
+            options = options.options([("statement_timeout", timeout.as_millis().to_string())]);
+        }
+
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(config.acquire_timeout)
+            .connect_with(options)
+            .await?;
+        Ok(pool)
+    }
+
+    /// Convert rows read on `conn` into questions
+    async fn load_on(conn: &mut PgConnection, mode: SchemaMode, rows: &[PgRow]) -> Result<Vec<Question>> {
+        let mut questions = rows.iter().map(row_to_question).collect::<Result<Vec<_>>>()?;
+        if mode == SchemaMode::Normalized && !questions.is_empty() {
+            attach_children(conn, &mut questions).await?;
+        }
+        Ok(questions)
+    }
+
+    async fn attach_children(conn: &mut PgConnection, questions: &mut [Question]) -> Result<()> {
+        let ids: Vec<Uuid> = questions.iter().map(|q| q.id).collect();
+
+        let option_rows = sqlx::query(
+            "SELECT question_id, content, sort_order, is_correct FROM question_options \
+             WHERE question_id = ANY($1) ORDER BY question_id, position",
+        )
+        .bind(&ids)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut options: HashMap<Uuid, Vec<QuestionOption>> = HashMap::new();
+        for row in &option_rows {
+            options.entry(row.try_get("question_id")?).or_default().push(QuestionOption {
+                content: row.try_get("content")?,
+                sort_order: row.try_get("sort_order")?,
+                is_correct: row.try_get("is_correct")?,
+            });
+        }
+
+        let image_rows = sqlx::query(
+            "SELECT question_id, url, hash, original_path, stored_path, alt FROM question_images \
+             WHERE question_id = ANY($1) ORDER BY question_id, position",
+        )
+        .bind(&ids)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut images: HashMap<Uuid, Vec<ImageRef>> = HashMap::new();
+        for row in &image_rows {
+            let url: Option<String> = row.try_get("url")?;
+            let alt = row.try_get("alt")?;
+            let image = match url {
+                Some(url) => ImageRef::Remote { url, alt },
+                None => ImageRef::Local {
+                    hash: row.try_get::<Option<String>, _>("hash")?.unwrap_or_default(),
+                    original_path: row
+                        .try_get::<Option<String>, _>("original_path")?
+                        .unwrap_or_default(),
+                    stored_path: row.try_get("stored_path")?,
+                    alt,
+                },
+            };
+            images.entry(row.try_get("question_id")?).or_default().push(image);
+        }
+
+        for q in questions {
+            q.options = options.remove(&q.id).unwrap_or_default();
+            q.images = images.remove(&q.id).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    /// Stored questions with any of the given content hashes, by hash
+    ///
+    /// Where several share a hash the oldest is returned, as the original.
+    async fn find_by_content_hashes(
+        conn: &mut PgConnection,
+        mode: SchemaMode,
+        hashes: &[String],
+    ) -> Result<HashMap<String, Question>> {
+        let sql = format!(
+            "SELECT {}, content_hash FROM questions WHERE content_hash = ANY($1) AND deleted_at IS NULL \
+             ORDER BY created_at, id",
+            SELECT_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(hashes).fetch_all(&mut *conn).await?;
+        let hashes = rows.iter().map(|row| row.try_get("content_hash")).collect::<Result<Vec<String>, _>>()?;
+
+        let mut found = HashMap::new();
+        for (hash, question) in hashes.into_iter().zip(load_on(conn, mode, &rows).await?) {
+            found.entry(hash).or_insert(question);
+        }
+        Ok(found)
+    }
+
+    /// Stored questions of the same type whose stem length could reach `threshold`
+    ///
+    /// At most [`NEAR_DUPLICATE_CANDIDATES`], those closest in length first,
+    /// so the same store always yields the same candidates. Whether more
+    /// would have matched is returned alongside.
+    async fn near_duplicate_candidates(
+        conn: &mut PgConnection,
+        mode: SchemaMode,
+        question: &Question,
+        threshold: f64,
+    ) -> Result<(Vec<Question>, bool)> {
+        let stem_len = question.stem.chars().count();
+        let (min_len, max_len) = dedup::length_window(stem_len, threshold);
+        let sql = format!(
+            "SELECT {} FROM questions WHERE type = $1 AND deleted_at IS NULL \
+             AND char_length(stem) BETWEEN $2 AND $3 \
+             ORDER BY abs(char_length(stem) - $4), id LIMIT $5",
+            SELECT_COLUMNS
+        );
+        let mut rows = sqlx::query(&sql)
+            .bind(to_json(&question.qtype)?)
+            .bind(min_len as i32)
+            .bind(max_len as i32)
+            .bind(stem_len as i32)
+            .bind(NEAR_DUPLICATE_CANDIDATES + 1)
+            .fetch_all(&mut *conn)
+            .await?;
+        let capped = rows.len() as i64 > NEAR_DUPLICATE_CANDIDATES;
+        rows.truncate(NEAR_DUPLICATE_CANDIDATES as usize);
+
+        Ok((load_on(conn, mode, &rows).await?, capped))
+    }
+
+    /// Advisory lock key used while migrating ("md2db" in ASCII)
+    const MIGRATION_LOCK_ID: i64 = 0x006d_6432_6462;
+
+    /// Rows per multi-row INSERT, kept well under Postgres' 65535 bind limit
+    const INSERT_CHUNK_SIZE: usize = 500;
+
+    /// Maximum stored questions compared when looking for near-duplicates
+    const NEAR_DUPLICATE_CANDIDATES: i64 = 500;
+
+    /// Bytes of `COPY` data buffered before sending to the server
+    const COPY_BUFFER_SIZE: usize = 1 << 20;
+
+    /// Columns written when saving a question row, in [`QuestionRow`] order
+    const INSERT_COLUMNS: &str =
+        "id, type, stem, answer, analysis, options, latex, tags, text_direction, provenance, content_hash, created_at, bank_id, sequence, section, number, type_source";
+
+    /// Conflict handling shared by every question upsert
+    ///
+    /// A re-imported question takes its new type along with what decided it,
+    /// so `type_source` always describes the stored `type`.
+    pub(super) const UPSERT_CLAUSE: &str = " ON CONFLICT (id) DO UPDATE SET \
+        type = EXCLUDED.type, \
+        stem = EXCLUDED.stem, \
+        answer = EXCLUDED.answer, \
+        analysis = EXCLUDED.analysis, \
+        options = EXCLUDED.options, \
+        tags = EXCLUDED.tags, \
+        text_direction = EXCLUDED.text_direction, \
+        provenance = EXCLUDED.provenance, \
+        content_hash = EXCLUDED.content_hash, \
+        bank_id = EXCLUDED.bank_id, \
+        sequence = EXCLUDED.sequence, \
+        section = EXCLUDED.section, \
+        number = EXCLUDED.number, \
+        type_source = EXCLUDED.type_source, \
+        deleted_at = NULL";
+
+    /// Columns selected when loading a question row
+    const SELECT_COLUMNS: &str =
+        "id, type, stem, answer, analysis, options, latex, tags, text_direction, provenance, created_at, bank_id, sequence, section, number, type_source";
+
+    /// Columns selected when loading a question bank row
+    const BANK_COLUMNS: &str = "id, name, description, tags, created_at";
+
+    /// Columns selected when loading an import job row
+    const JOB_COLUMNS: &str = "id, status, sources, progress, result, error, created_at, updated_at";
+
+    /// Build an ILIKE pattern matching `needle` anywhere, escaping wildcards
+    pub(super) fn like_pattern(needle: &str) -> String {
+        let mut pattern = String::with_capacity(needle.len() + 2);
+        pattern.push('%');
+        for c in needle.chars() {
+            if matches!(c, '\\' | '%' | '_') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push('%');
+        pattern
+    }
+
+    /// Serialized column values of a question row
+    struct QuestionRow {
+        id: Uuid,
+        qtype: String,
+        stem: String,
+        answer: Option<String>,
+        analysis: Option<String>,
+        options: String,
+        latex: String,
+        tags: String,
+        text_direction: String,
+        provenance: Option<String>,
+        content_hash: String,
+        created_at: DateTime<Utc>,
+        bank_id: Option<Uuid>,
+        sequence: Option<i64>,
+        section: Option<String>,
+        number: Option<String>,
+        type_source: Option<String>,
+    }
+
+    impl QuestionRow {
+        fn encode(q: &Question, mode: SchemaMode) -> Result<Self> {
+            let options = match mode {
+                SchemaMode::Json => to_json(&q.options)?,
+                SchemaMode::Normalized => "[]".to_string(),
+            };
+            Ok(Self {
+                id: q.id,
+                qtype: to_json(&q.qtype)?,
+                stem: q.stem.clone(),
+                answer: q.answer.clone(),
+                analysis: q.analysis.clone(),
+                options,
+                latex: to_json(&q.latex)?,
+                tags: to_json(&q.tags)?,
+                text_direction: to_json(&q.text_direction)?,
+                provenance: q.provenance.as_ref().map(to_json).transpose()?,
+                content_hash: content_hash(q),
+                created_at: q.created_at,
+                bank_id: q.bank_id,
+                sequence: q.sequence.map(|s| s as i64),
+                section: q.section.clone(),
+                number: q.number.clone(),
+                type_source: q.type_source.as_ref().map(to_json).transpose()?,
+            })
+        }
+
+        /// Append the row in `COPY` text format
+        fn write_copy_line(&self, out: &mut String) {
+            let id = self.id.to_string();
+            let created_at = self.created_at.to_rfc3339();
+            let bank_id = self.bank_id.map(|id| id.to_string());
+            let sequence = self.sequence.map(|s| s.to_string());
+            let fields: [Option<&str>; 17] = [
+                Some(&id),
+                Some(&self.qtype),
+                Some(&self.stem),
+                self.answer.as_deref(),
+                self.analysis.as_deref(),
+                Some(&self.options),
+                Some(&self.latex),
+                Some(&self.tags),
+                Some(&self.text_direction),
+                self.provenance.as_deref(),
+                Some(&self.content_hash),
+                Some(&created_at),
+                bank_id.as_deref(),
+                sequence.as_deref(),
+                self.section.as_deref(),
+                self.number.as_deref(),
+                self.type_source.as_deref(),
+            ];
+            for (i, field) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push('\t');
+                }
+                match field {
+                    Some(value) => push_copy_escaped(out, value),
+                    None => out.push_str("\\N"),
+                }
+            }
+            out.push('\n');
+        }
+    }
+
+    /// Escape a value for `COPY` text format
+    pub(super) fn push_copy_escaped(out: &mut String, value: &str) {
+        for c in value.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// Upsert questions with a single multi-row INSERT
+    async fn insert_questions(
+        conn: &mut PgConnection,
+        questions: &[Question],
+        mode: SchemaMode,
+    ) -> Result<()> {
+        let rows = questions
+            .iter()
+            .map(|q| QuestionRow::encode(q, mode))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut builder =
+            QueryBuilder::<Postgres>::new(format!("INSERT INTO questions ({}) ", INSERT_COLUMNS));
+        builder.push_values(rows, |mut b, row| {
+            b.push_bind(row.id)
+                .push_bind(row.qtype)
+                .push_bind(row.stem)
+                .push_bind(row.answer)
+                .push_bind(row.analysis)
+                .push_bind(row.options)
+                .push_bind(row.latex)
+                .push_bind(row.tags)
+                .push_bind(row.text_direction)
+                .push_bind(row.provenance)
+                .push_bind(row.content_hash)
+                .push_bind(row.created_at)
+                .push_bind(row.bank_id)
+                .push_bind(row.sequence)
+                .push_bind(row.section)
+                .push_bind(row.number)
+                .push_bind(row.type_source);
+        });
+        builder.push(UPSERT_CLAUSE);
+        builder.build().execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Upsert questions by streaming them through `COPY`
+    ///
+    /// New questions (the common case for imports) are copied straight into
+    /// `questions`. `COPY` cannot resolve conflicts, so if any id is already
+    /// stored the rows go through a temporary staging table and are merged
+    /// with one `INSERT ... SELECT`. The staging table is dropped on commit,
+    /// so this must run inside a transaction.
+    async fn copy_questions(
+        conn: &mut PgConnection,
+        questions: &[Question],
+        mode: SchemaMode,
+    ) -> Result<()> {
+        let ids: Vec<Uuid> = questions.iter().map(|q| q.id).collect();
+        let existing: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM questions WHERE id = ANY($1))")
+            .bind(&ids)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        if !existing {
+            return copy_rows(conn, "questions", questions, mode).await;
+        }
+
+        sqlx::query(
+            "CREATE TEMP TABLE IF NOT EXISTS questions_copy \
+             (LIKE questions INCLUDING DEFAULTS) ON COMMIT DROP",
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query("TRUNCATE questions_copy").execute(&mut *conn).await?;
+        copy_rows(conn, "questions_copy", questions, mode).await?;
+
+        let sql = format!(
+            "INSERT INTO questions ({cols}) SELECT {cols} FROM questions_copy{upsert}",
+            cols = INSERT_COLUMNS,
+            upsert = UPSERT_CLAUSE
+        );
+        sqlx::query(&sql).execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Stream question rows into `table` with `COPY ... FROM STDIN`
+    async fn copy_rows(
+        conn: &mut PgConnection,
+        table: &str,
+        questions: &[Question],
+        mode: SchemaMode,
+    ) -> Result<()> {
+        let mut copy = conn
+            .copy_in_raw(&format!("COPY {} ({}) FROM STDIN", table, INSERT_COLUMNS))
+            .await?;
+        let mut buffer = String::new();
+        for q in questions {
+            QuestionRow::encode(q, mode)?.write_copy_line(&mut buffer);
+            if buffer.len() >= COPY_BUFFER_SIZE {
+                copy.send(std::mem::take(&mut buffer).into_bytes()).await?;
+            }
+        }
+        if !buffer.is_empty() {
+            copy.send(buffer.into_bytes()).await?;
+        }
+        copy.finish().await?;
+        Ok(())
+    }
+
+    /// Replace the normalized options and images of the given questions
+    async fn replace_children(conn: &mut PgConnection, questions: &[Question]) -> Result<()> {
+        let ids: Vec<Uuid> = questions.iter().map(|q| q.id).collect();
+        sqlx::query("DELETE FROM question_options WHERE question_id = ANY($1)")
+            .bind(&ids)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM question_images WHERE question_id = ANY($1)")
+            .bind(&ids)
+            .execute(&mut *conn)
+            .await?;
+
+        let options: Vec<(Uuid, i32, &QuestionOption)> = questions
+            .iter()
+            .flat_map(|q| q.options.iter().enumerate().map(move |(i, o)| (q.id, i as i32, o)))
+            .collect();
+        for chunk in options.chunks(INSERT_CHUNK_SIZE) {
+            let mut builder = QueryBuilder::<Postgres>::new(
+                "INSERT INTO question_options (question_id, position, content, sort_order, is_correct) ",
+            );
+            builder.push_values(chunk, |mut b, (id, position, option)| {
+                b.push_bind(*id)
+                    .push_bind(*position)
+                    .push_bind(option.content.clone())
+                    .push_bind(option.sort_order)
+                    .push_bind(option.is_correct);
+            });
+            builder.build().execute(&mut *conn).await?;
+        }
+
+        let images: Vec<(Uuid, i32, &ImageRef)> = questions
+            .iter()
+            .flat_map(|q| q.images.iter().enumerate().map(move |(i, img)| (q.id, i as i32, img)))
+            .collect();
+        for chunk in images.chunks(INSERT_CHUNK_SIZE) {
+            let mut builder = QueryBuilder::<Postgres>::new(
+                "INSERT INTO question_images (question_id, position, url, hash, original_path, stored_path, alt) ",
+            );
+            builder.push_values(chunk, |mut b, (id, position, image)| {
+                let (url, hash, original_path, stored_path) = match image {
+                    ImageRef::Remote { url, .. } => (Some(url.clone()), None, None, None),
+                    ImageRef::Local { hash, original_path, stored_path, .. } => {
+                        (None, Some(hash.clone()), Some(original_path.clone()), stored_path.clone())
+                    }
+                };
+                b.push_bind(*id)
+                    .push_bind(*position)
+                    .push_bind(url)
+                    .push_bind(hash)
+                    .push_bind(original_path)
+                    .push_bind(stored_path)
+                    .push_bind(image.alt());
+            });
+            builder.build().execute(&mut *conn).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Convert a database row into a QuestionBank
+    fn row_to_bank(row: &PgRow) -> Result<QuestionBank> {
+        Ok(QuestionBank {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            description: row.try_get("description")?,
+            tags: from_json(row.try_get("tags")?)?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+
+    /// Convert a database row into a JobRecord
+    fn row_to_job(row: &PgRow) -> Result<JobRecord> {
+        let result: Option<&str> = row.try_get("result")?;
+        Ok(JobRecord {
+            id: row.try_get("id")?,
+            status: row
+                .try_get::<&str, _>("status")?
+                .parse()
+                .map_err(|e: anyhow::Error| Md2DbError::repository("decoding a job status", e))?,
+            sources: from_json(row.try_get("sources")?)?,
+            stage: None,
+            progress: row.try_get("progress")?,
+            result: result.map(from_json).transpose()?,
+            error: row.try_get("error")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+
+    /// Convert a database row into a Question
+    fn row_to_question(row: &PgRow) -> Result<Question> {
+        let qtype: crate::models::QuestionType = from_json(row.try_get("type")?)?;
+        let options: Vec<QuestionOption> = from_json(row.try_get("options")?)?;
+        let latex: Vec<String> = from_json(row.try_get("latex")?)?;
+        let tags: Vec<String> = from_json(row.try_get("tags")?)?;
+        let text_direction: crate::models::TextDirection =
+            from_json(row.try_get("text_direction")?)?;
+        let provenance: Option<String> = row.try_get("provenance")?;
+        let provenance = provenance.map(|p| from_json(&p)).transpose()?;
+        let type_source: Option<String> = row.try_get("type_source")?;
+        let type_source = type_source.map(|s| from_json(&s)).transpose()?;
+
+        Ok(Question {
+            schema_version: crate::models::SCHEMA_VERSION,
+            id: row.try_get("id")?,
+            qtype,
+            stem: row.try_get("stem")?,
+            options,
+            answer: row.try_get("answer")?,
+            analysis: row.try_get("analysis")?,
+            latex,
+            tags,
+            text_direction,
+            provenance,
+            bank_id: row.try_get("bank_id")?,
+            sequence: row.try_get::<Option<i64>, _>("sequence")?.map(|s| s as u64),
+            section: row.try_get("section")?,
+            number: row.try_get("number")?,
+            type_source,
+            images: Vec::new(), // Only persisted in normalized mode
+            created_at: row.try_get("created_at")?,
+        })
+    }
+
+    /// Insert or replace questions on `conn`
+    async fn save_on(
+        conn: &mut PgConnection,
+        mode: SchemaMode,
+        insert_mode: InsertMode,
+        questions: &[Question],
+    ) -> Result<()> {
+        if insert_mode == InsertMode::Copy && !questions.is_empty() {
+            copy_questions(conn, questions, mode).await?;
+        }
+        for chunk in questions.chunks(INSERT_CHUNK_SIZE) {
+            if insert_mode == InsertMode::MultiRow {
+                insert_questions(conn, chunk, mode).await?;
+            }
+            if mode == SchemaMode::Normalized {
+                replace_children(conn, chunk).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deduplicating save on `conn`; lookups see rows written earlier on it
+    async fn save_dedup_on(
+        conn: &mut PgConnection,
+        mode: SchemaMode,
+        insert_mode: InsertMode,
+        questions: &[Question],
+        options: &DedupOptions,
+    ) -> Result<Vec<SaveOutcome>> {
+        let mut outcomes = Vec::with_capacity(questions.len());
+        let mut inserts: Vec<Question> = Vec::new();
+        let mut updates: Vec<(Uuid, &Question)> = Vec::new();
+        // Exact matches for the whole batch in one round trip
+        let hashes: Vec<String> = questions.iter().map(content_hash).collect();
+        let stored = find_by_content_hashes(conn, mode, &hashes).await?;
+
+        for (q, hash) in questions.iter().zip(&hashes) {
+            let exact = match inserts.iter().find(|p| content_hash(p) == *hash) {
+                Some(pending) => Some(pending.clone()),
+                None => stored.get(hash).cloned(),
+            };
+
+            let mut candidates = Vec::new();
+            if let (None, Some(threshold)) = (&exact, options.near_duplicate_threshold) {
+                let capped;
+                (candidates, capped) = near_duplicate_candidates(conn, mode, q, threshold).await?;
+                if capped {
+                    tracing::warn!(
+                        "Near-duplicate check for question {} compared only the {} stored questions closest in length",
+                        q.id,
+                        NEAR_DUPLICATE_CANDIDATES
+                    );
+                }
+                candidates.extend(inserts.iter().filter(|p| p.qtype == q.qtype).cloned());
+            }
+
+            let decision = dedup::decide(q, exact.as_ref(), &candidates, options);
+            match decision {
+                Decision::Insert => inserts.push(q.clone()),
+                Decision::Skip { .. } => {}
+                Decision::Update { existing_id } => {
+                    match inserts.iter_mut().find(|p| p.id == existing_id) {
+                        Some(pending) => {
+                            pending.answer = q.answer.clone();
+                            pending.analysis = q.analysis.clone();
+                        }
+                        None => updates.push((existing_id, q)),
+                    }
+                }
+            }
+            outcomes.push(decision.outcome(q));
+        }
+
+        save_on(conn, mode, insert_mode, &inserts).await?;
+        for (existing_id, q) in updates {
+            record_revision(conn, mode, existing_id, None).await?;
+            sqlx::query("UPDATE questions SET answer = $2, analysis = $3 WHERE id = $1")
+                .bind(existing_id)
+                .bind(&q.answer)
+                .bind(&q.analysis)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Keep the current version of question `id` as its next revision
+    ///
+    /// Locks the question row until the transaction on `conn` ends. Returns
+    /// false if the question does not exist.
+    async fn record_revision(
+        conn: &mut PgConnection,
+        mode: SchemaMode,
+        id: Uuid,
+        actor: Option<&str>,
+    ) -> Result<bool> {
+        let rows = sqlx::query(&format!("SELECT {} FROM questions WHERE id = $1 AND deleted_at IS NULL FOR UPDATE", SELECT_COLUMNS))
+            .bind(id)
+            .fetch_all(&mut *conn)
+            .await?;
+        let Some(current) = load_on(conn, mode, &rows).await?.into_iter().next() else {
+            return Ok(false);
+        };
+
+        sqlx::query(
+            "INSERT INTO question_revisions (question_id, revision, snapshot, actor) \
+             SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3 \
+             FROM question_revisions WHERE question_id = $1",
+        )
+        .bind(id)
+        .bind(to_json(&current)?)
+        .bind(actor)
+        .execute(&mut *conn)
+        .await?;
+        Ok(true)
+    }
+
+    /// Import session backed by one open transaction
+    pub struct PostgresImportSession {
+        id: Uuid,
+        tx: sqlx::Transaction<'static, Postgres>,
+        mode: SchemaMode,
+        insert_mode: InsertMode,
+        written: usize,
+    }
+
+    #[async_trait]
+    impl ImportSession for PostgresImportSession {
+        fn id(&self) -> Uuid {
+            self.id
+        }
+
+        async fn save_batch(&mut self, questions: &[Question]) -> Result<Vec<Uuid>> {
+            save_on(&mut self.tx, self.mode, self.insert_mode, questions).await?;
+            self.written += questions.len();
+            Ok(questions.iter().map(|q| q.id).collect())
+        }
+
+        async fn save_batch_dedup(
+            &mut self,
+            questions: &[Question],
+            options: &DedupOptions,
+        ) -> Result<Vec<SaveOutcome>> {
+            let outcomes = save_dedup_on(&mut self.tx, self.mode, self.insert_mode, questions, options).await?;
+            self.written += outcomes.iter().filter(|o| o.is_written()).count();
+            Ok(outcomes)
+        }
+
+        async fn commit(self: Box<Self>) -> Result<usize> {
+            self.tx.commit().await?;
+            tracing::debug!("Committed import session {} ({} questions)", self.id, self.written);
+            Ok(self.written)
+        }
+
+        async fn rollback(self: Box<Self>) -> Result<()> {
+            self.tx.rollback().await?;
+            tracing::debug!("Rolled back import session {} ({} questions)", self.id, self.written);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl QuestionRepository for PostgresRepository {
+        async fn save_batch(&self, questions: &[Question]) -> Result<Vec<Uuid>> {
+            let mut tx = self.pool.begin().await?;
+            save_on(&mut tx, self.mode, self.insert_mode, questions).await?;
+            tx.commit().await?;
+
+            Ok(questions.iter().map(|q| q.id).collect())
+        }
+
+        async fn save_batch_dedup(
+            &self,
+            questions: &[Question],
+            options: &DedupOptions,
+        ) -> Result<Vec<SaveOutcome>> {
+            let mut tx = self.pool.begin().await?;
+            let outcomes = save_dedup_on(&mut tx, self.mode, self.insert_mode, questions, options).await?;
+            tx.commit().await?;
+
+            Ok(outcomes)
+        }
+
+        async fn begin_import(&self) -> Result<Box<dyn ImportSession>> {
+            let session = PostgresImportSession {
+                id: Uuid::new_v4(),
+                tx: self.pool.begin().await?,
+                mode: self.mode,
+                insert_mode: self.insert_mode,
+                written: 0,
+            };
+            tracing::debug!("Began import session {}", session.id);
+            Ok(Box::new(session))
+        }
+
+        async fn ping(&self) -> Result<()> {
+            sqlx::query("SELECT 1").execute(&self.pool).await?;
+            if let Some(replica) = &self.replica {
+                sqlx::query("SELECT 1")
+                    .execute(replica)
+                    .await
+                    .map_err(|e| Md2DbError::repository("replica", e))?;
+            }
+            Ok(())
+        }
+
+        async fn find_by_id(&self, id: Uuid) -> Result<Option<Question>> {
+            let sql = format!("SELECT {} FROM questions WHERE id = $1 AND deleted_at IS NULL", SELECT_COLUMNS);
+            let row = sqlx::query(&sql)
+                .bind(id)
+                .fetch_optional(self.reader())
+                .await?;
+
+            Ok(self.load(row.as_slice()).await?.pop())
+        }
+
+        async fn find_by_type(
+            &self,
+            qtype: &crate::models::QuestionType,
+        ) -> Result<Vec<Question>> {
+            let qtype_str = to_json(qtype)?;
+            let sql = format!("SELECT {} FROM questions WHERE type = $1 AND deleted_at IS NULL", SELECT_COLUMNS);
+            let rows = sqlx::query(&sql)
+                .bind(&qtype_str)
+                .fetch_all(self.reader())
+                .await?;
+
+            self.load(&rows).await
+        }
+
+        async fn find_by_tag(&self, tag: &str) -> Result<Vec<Question>> {
+            // Tags are stored as a JSON array string
+            let sql = format!("SELECT {} FROM questions WHERE tags::jsonb ? $1 AND deleted_at IS NULL", SELECT_COLUMNS);
+            let rows = sqlx::query(&sql)
+                .bind(tag)
+                .fetch_all(self.reader())
+                .await?;
+
+            self.load(&rows).await
+        }
+
+        async fn list(&self, params: &ListParams) -> Result<Page<Question>> {
+            let limit = params.effective_limit();
+            let (direction, comparison) = match params.order {
+                SortOrder::Asc => ("ASC", ">"),
+                SortOrder::Desc => ("DESC", "<"),
+            };
+
+            // A NULL type matches every question
+            let qtype = params.qtype.as_ref().map(to_json).transpose()?;
+
+            let total: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM questions WHERE deleted_at IS NULL AND ($1::text IS NULL OR type = $1)",
+            )
+            .bind(&qtype)
+            .fetch_one(self.reader())
+            .await?;
+
+            // Fetch one extra row to know whether another page exists
+            let rows = match params.decode_cursor()? {
+                Some((created_at, id)) => {
+                    let sql = format!(
+                        "SELECT {} FROM questions WHERE deleted_at IS NULL AND ($1::text IS NULL OR type = $1) \
+                         AND (created_at, id) {} ($2, $3) \
+                         ORDER BY created_at {dir}, id {dir} LIMIT $4",
+                        SELECT_COLUMNS, comparison, dir = direction
+                    );
+                    sqlx::query(&sql)
+                        .bind(&qtype)
+                        .bind(created_at)
+                        .bind(id)
+                        .bind((limit + 1) as i64)
+                        .fetch_all(self.reader())
+                        .await?
+                }
+                None => {
+                    let sql = format!(
+                        "SELECT {} FROM questions WHERE deleted_at IS NULL AND ($1::text IS NULL OR type = $1) \
+                         ORDER BY created_at {dir}, id {dir} LIMIT $2 OFFSET $3",
+                        SELECT_COLUMNS, dir = direction
+                    );
+                    sqlx::query(&sql)
+                        .bind(&qtype)
+                        .bind((limit + 1) as i64)
+                        .bind(params.offset.unwrap_or(0) as i64)
+                        .fetch_all(self.reader())
+                        .await?
+                }
+            };
+
+            let items = self.load(&rows).await?;
+            Ok(build_page(items, limit, total as u64))
+        }
 
-    /// Find a question by its ID
-    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<Question>>;
+        async fn update_as(&self, q: &Question, actor: Option<&str>) -> Result<bool> {
+            let row = QuestionRow::encode(q, self.mode)?;
+            let mut tx = self.pool.begin().await?;
 
-    /// Find all questions of a specific type
-    async fn find_by_type(&self, qtype: &crate::models::QuestionType) -> anyhow::Result<Vec<Question>>;
-}
+            if !record_revision(&mut tx, self.mode, q.id, actor).await? {
+                return Ok(false);
+            }
 
-/// PostgreSQL implementation using SQLx
-#[cfg(feature = "postgres")]
-pub mod postgres {
-    use super::*;
-    use sqlx::{PgPool, Row};
+            let result = sqlx::query(
+                r#"
+                UPDATE questions SET
+                    type = $2, stem = $3, answer = $4, analysis = $5, options = $6,
+                    latex = $7, tags = $8, text_direction = $9, provenance = $10,
+                    content_hash = $11, bank_id = $12, sequence = $13,
+                    section = $14, number = $15, type_source = $16
+                WHERE id = $1
+                "#
+            )
+            .bind(row.id)
+            .bind(&row.qtype)
+            .bind(&row.stem)
+            .bind(&row.answer)
+            .bind(&row.analysis)
+            .bind(&row.options)
+            .bind(&row.latex)
+            .bind(&row.tags)
+            .bind(&row.text_direction)
+            .bind(&row.provenance)
+            .bind(&row.content_hash)
+            .bind(row.bank_id)
+            .bind(row.sequence)
+            .bind(&row.section)
+            .bind(&row.number)
+            .bind(&row.type_source)
+            .execute(&mut *tx)
+            .await?;
 
-    pub struct PostgresRepository {
-        pool: PgPool,
-    }
+            let updated = result.rows_affected() > 0;
+            if updated && self.mode == SchemaMode::Normalized {
+                replace_children(&mut tx, std::slice::from_ref(q)).await?;
+            }
+            tx.commit().await?;
 
-    impl PostgresRepository {
-        /// Create a new PostgreSQL repository
-        pub async fn new(database_url: &str) -> anyhow::Result<Self> {
-            let pool = PgPool::connect(database_url).await?;
-            Ok(Self { pool })
+            Ok(updated)
         }
 
-        /// Create repository from existing connection pool
-        pub fn with_pool(pool: PgPool) -> Self {
-            Self { pool }
+        async fn history(&self, id: Uuid) -> Result<Vec<QuestionRevision>> {
+            let rows = sqlx::query(
+                "SELECT revision, actor, snapshot, created_at FROM question_revisions \
+                 WHERE question_id = $1 ORDER BY revision DESC",
+            )
+            .bind(id)
+            .fetch_all(self.reader())
+            .await?;
+
+            rows.iter()
+                .map(|row| {
+                    let snapshot: String = row.try_get("snapshot")?;
+                    Ok(QuestionRevision {
+                        question_id: id,
+                        revision: row.try_get::<i32, _>("revision")? as i64,
+                        actor: row.try_get("actor")?,
+                        created_at: row.try_get("created_at")?,
+                        question: from_json(&snapshot)?,
+                    })
+                })
+                .collect()
         }
-    }
 
-    #[async_trait]
-    impl QuestionRepository for PostgresRepository {
-        async fn save_batch(&self, questions: &[Question]) -> anyhow::Result<Vec<Uuid>> {
-            let mut tx = self.pool.begin().await?;
+        async fn delete(&self, id: Uuid) -> Result<bool> {
+            Ok(self.delete_batch(&[id]).await? > 0)
+        }
 
-            for q in questions {
-                let qtype_str = serde_json::to_string(&q.qtype)?;
-                let options_json = serde_json::to_string(&q.options)?;
-                let latex_json = serde_json::to_string(&q.latex)?;
-
-                sqlx::query(
-                    r#"
-                    INSERT INTO questions (id, type, stem, answer, analysis, options, latex, created_at)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                    ON CONFLICT (id) DO UPDATE SET
-                        stem = EXCLUDED.stem,
-                        answer = EXCLUDED.answer,
-                        analysis = EXCLUDED.analysis,
-                        options = EXCLUDED.options
-                    "#
-                )
-                .bind(q.id)
-                .bind(&qtype_str)
-                .bind(&q.stem)
-                .bind(&q.answer)
-                .bind(&q.analysis)
-                .bind(&options_json)
-                .bind(&latex_json)
-                .bind(q.created_at)
-                .execute(&mut *tx)
-                .await?;
-            }
+        async fn delete_batch(&self, ids: &[Uuid]) -> Result<u64> {
+            let result = sqlx::query(
+                "UPDATE questions SET deleted_at = now() WHERE id = ANY($1) AND deleted_at IS NULL",
+            )
+            .bind(ids)
+            .execute(&self.pool)
+            .await?;
 
-            tx.commit().await?;
+            Ok(result.rows_affected())
+        }
 
-            Ok(questions.iter().map(|q| q.id).collect())
+        async fn create_bank(&self, bank: &QuestionBank) -> Result<Uuid> {
+            sqlx::query(
+                "INSERT INTO question_banks (id, name, description, tags, created_at) \
+                 VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(bank.id)
+            .bind(&bank.name)
+            .bind(&bank.description)
+            .bind(to_json(&bank.tags)?)
+            .bind(bank.created_at)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(bank.id)
+        }
+
+        async fn save_job(&self, job: &JobRecord) -> Result<()> {
+            let result = job.result.as_ref().map(to_json).transpose()?;
+            sqlx::query(
+                "INSERT INTO import_jobs (id, status, sources, progress, result, error, created_at, updated_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+                 ON CONFLICT (id) DO UPDATE SET status = EXCLUDED.status, progress = EXCLUDED.progress, \
+                 result = EXCLUDED.result, error = EXCLUDED.error, updated_at = EXCLUDED.updated_at",
+            )
+            .bind(job.id)
+            .bind(job.status.as_str())
+            .bind(to_json(&job.sources)?)
+            .bind(job.progress)
+            .bind(result)
+            .bind(&job.error)
+            .bind(job.created_at)
+            .bind(job.updated_at)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(())
         }
 
-        async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<Question>> {
-            let row = sqlx::query("SELECT id, type, stem, answer, analysis, options, latex, created_at FROM questions WHERE id = $1")
+        async fn find_job(&self, id: Uuid) -> Result<Option<JobRecord>> {
+            // Read from the primary: a job is polled right after it is saved
+            let row = sqlx::query(&format!("SELECT {} FROM import_jobs WHERE id = $1", JOB_COLUMNS))
                 .bind(id)
                 .fetch_optional(&self.pool)
                 .await?;
 
-            match row {
-                Some(row) => {
-                    let qtype: crate::models::QuestionType = serde_json::from_str(row.try_get("type")?)?;
-                    let options: Vec<crate::models::QuestionOption> = serde_json::from_str(row.try_get("options")?)?;
-                    let latex: Vec<String> = serde_json::from_str(row.try_get("latex")?)?;
-
-                    Ok(Some(Question {
-                        id: row.try_get("id")?,
-                        qtype,
-                        stem: row.try_get("stem")?,
-                        options,
-                        answer: row.try_get("answer")?,
-                        analysis: row.try_get("analysis")?,
-                        latex,
-                        images: Vec::new(), // TODO: Handle image references
-                        created_at: row.try_get("created_at")?,
-                    }))
-                }
-                None => Ok(None),
-            }
+            row.as_ref().map(row_to_job).transpose()
         }
 
-        async fn find_by_type(
-            &self,
-            qtype: &crate::models::QuestionType,
-        ) -> anyhow::Result<Vec<Question>> {
-            let qtype_str = serde_json::to_string(qtype)?;
-            let rows = sqlx::query("SELECT id, type, stem, answer, analysis, options, latex, created_at FROM questions WHERE type = $1")
-                .bind(&qtype_str)
-                .fetch_all(&self.pool)
-                .await?;
+        async fn list_jobs(&self) -> Result<Vec<JobRecord>> {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM import_jobs ORDER BY created_at DESC, id",
+                JOB_COLUMNS
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(row_to_job).collect()
+        }
+
+        async fn save_source(&self, source: &ImportedSource) -> Result<()> {
+            sqlx::query(
+                "INSERT INTO import_sources (bank_id, source, content_hash, imported_at) \
+                 VALUES ($1, $2, $3, $4) \
+                 ON CONFLICT (bank_id, source) DO UPDATE SET content_hash = EXCLUDED.content_hash, \
+                 imported_at = EXCLUDED.imported_at",
+            )
+            .bind(source.bank_id)
+            .bind(&source.source)
+            .bind(&source.content_hash)
+            .bind(source.imported_at)
+            .execute(&self.pool)
+            .await?;
 
-            rows.into_iter()
+            Ok(())
+        }
+
+        async fn list_sources(&self, bank_id: Uuid) -> Result<Vec<ImportedSource>> {
+            // Read from the primary, like jobs: the hashes decide what gets written
+            let rows = sqlx::query(
+                "SELECT bank_id, source, content_hash, imported_at FROM import_sources \
+                 WHERE bank_id = $1 ORDER BY source",
+            )
+            .bind(bank_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter()
                 .map(|row| {
-                    let qtype: crate::models::QuestionType = serde_json::from_str(row.try_get("type")?)?;
-                    let options: Vec<crate::models::QuestionOption> = serde_json::from_str(row.try_get("options")?)?;
-                    let latex: Vec<String> = serde_json::from_str(row.try_get("latex")?)?;
-
-                    Ok(Question {
-                        id: row.try_get("id")?,
-                        qtype,
-                        stem: row.try_get("stem")?,
-                        options,
-                        answer: row.try_get("answer")?,
-                        analysis: row.try_get("analysis")?,
-                        latex,
-                        images: Vec::new(),
-                        created_at: row.try_get("created_at")?,
+                    Ok(ImportedSource {
+                        bank_id: row.try_get("bank_id")?,
+                        source: row.try_get("source")?,
+                        content_hash: row.try_get("content_hash")?,
+                        imported_at: row.try_get("imported_at")?,
                     })
                 })
                 .collect()
         }
+
+        async fn find_bank(&self, id: Uuid) -> Result<Option<QuestionBank>> {
+            let row = sqlx::query(&format!("SELECT {} FROM question_banks WHERE id = $1", BANK_COLUMNS))
+                .bind(id)
+                .fetch_optional(self.reader())
+                .await?;
+
+            row.as_ref().map(row_to_bank).transpose()
+        }
+
+        async fn list_banks(&self) -> Result<Vec<QuestionBank>> {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM question_banks ORDER BY created_at, id",
+                BANK_COLUMNS
+            ))
+            .fetch_all(self.reader())
+            .await?;
+
+            rows.iter().map(row_to_bank).collect()
+        }
+
+        async fn assign_to_bank(&self, bank_id: Uuid, question_ids: &[Uuid]) -> Result<u64> {
+            let mut tx = self.pool.begin().await?;
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM question_banks WHERE id = $1)")
+                .bind(bank_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            if !exists {
+                return Err(Md2DbError::repository("", format!("Question bank {} not found", bank_id)));
+            }
+
+            let result = sqlx::query(
+                "UPDATE questions SET bank_id = $1 WHERE id = ANY($2) AND deleted_at IS NULL",
+            )
+            .bind(bank_id)
+            .bind(question_ids)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            Ok(result.rows_affected())
+        }
+
+        async fn find_by_bank(&self, bank_id: Uuid) -> Result<Vec<Question>> {
+            let sql = format!(
+                "SELECT {} FROM questions WHERE bank_id = $1 AND deleted_at IS NULL ORDER BY created_at, id",
+                SELECT_COLUMNS
+            );
+            let rows = sqlx::query(&sql)
+                .bind(bank_id)
+                .fetch_all(self.reader())
+                .await?;
+
+            self.load(&rows).await
+        }
+
+        async fn restore(&self, id: Uuid) -> Result<bool> {
+            let result = sqlx::query(
+                "UPDATE questions SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL",
+            )
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        }
+
+        async fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+            // Revisions and normalized option/image rows go with ON DELETE CASCADE
+            let result = sqlx::query("DELETE FROM questions WHERE deleted_at < $1")
+                .bind(before)
+                .execute(&self.pool)
+                .await?;
+
+            Ok(result.rows_affected())
+        }
+    }
+}
+
+/// Deduplicating save into an in-memory store
+fn dedup_into(store: &mut Vec<Question>, questions: &[Question], options: &DedupOptions) -> Vec<SaveOutcome> {
+    let mut outcomes = Vec::with_capacity(questions.len());
+
+    for q in questions {
+        let hash = content_hash(q);
+        let exact = store.iter().find(|s| content_hash(s) == hash);
+        let candidates = store.iter().filter(|s| s.qtype == q.qtype);
+        let decision = dedup::decide(q, exact, candidates, options);
+
+        match decision {
+            Decision::Insert => store.push(q.clone()),
+            Decision::Skip { .. } => {}
+            Decision::Update { existing_id } => {
+                if let Some(existing) = store.iter_mut().find(|s| s.id == existing_id) {
+                    existing.answer = q.answer.clone();
+                    existing.analysis = q.analysis.clone();
+                }
+            }
+        }
+        outcomes.push(decision.outcome(q));
     }
+
+    outcomes
+}
+
+/// Prior versions of each question, oldest first
+type RevisionStore = std::sync::Arc<tokio::sync::RwLock<std::collections::HashMap<Uuid, Vec<QuestionRevision>>>>;
+
+/// Keep `current` as the next revision of its question
+fn push_revision(revisions: &mut std::collections::HashMap<Uuid, Vec<QuestionRevision>>, current: &Question, actor: Option<&str>) {
+    let history = revisions.entry(current.id).or_default();
+    history.push(QuestionRevision {
+        question_id: current.id,
+        revision: history.len() as i64 + 1,
+        actor: actor.map(str::to_string),
+        created_at: Utc::now(),
+        question: current.clone(),
+    });
 }
 
 /// Mock repository for testing
 pub struct MockRepository {
     questions: std::sync::Arc<tokio::sync::RwLock<Vec<Question>>>,
+    revisions: RevisionStore,
+    /// Soft-deleted questions with their deletion time
+    trash: tokio::sync::RwLock<Vec<(DateTime<Utc>, Question)>>,
+    banks: tokio::sync::RwLock<Vec<QuestionBank>>,
+    jobs: tokio::sync::RwLock<Vec<JobRecord>>,
+    sources: tokio::sync::RwLock<Vec<ImportedSource>>,
 }
 
 impl MockRepository {
     pub fn new() -> Self {
         Self {
             questions: std::sync::Arc::new(tokio::sync::RwLock::new(Vec::new())),
+            revisions: RevisionStore::default(),
+            trash: tokio::sync::RwLock::new(Vec::new()),
+            banks: tokio::sync::RwLock::new(Vec::new()),
+            jobs: tokio::sync::RwLock::new(Vec::new()),
+            sources: tokio::sync::RwLock::new(Vec::new()),
         }
     }
 }
@@ -162,7 +1923,7 @@ impl Default for MockRepository {
 
 #[async_trait]
 impl QuestionRepository for MockRepository {
-    async fn save_batch(&self, questions: &[Question]) -> anyhow::Result<Vec<Uuid>> {
+    async fn save_batch(&self, questions: &[Question]) -> Result<Vec<Uuid>> {
         let mut store = self.questions.write().await;
         for q in questions {
             store.push(q.clone());
@@ -170,7 +1931,19 @@ impl QuestionRepository for MockRepository {
         Ok(questions.iter().map(|q| q.id).collect())
     }
 
-    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<Question>> {
+    async fn save_batch_dedup(
+        &self,
+        questions: &[Question],
+        options: &DedupOptions,
+    ) -> Result<Vec<SaveOutcome>> {
+        // A one-batch session, so refreshed questions get revisions on commit
+        let mut session = self.begin_import().await?;
+        let outcomes = session.save_batch_dedup(questions, options).await?;
+        session.commit().await?;
+        Ok(outcomes)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Question>> {
         let store = self.questions.read().await;
         Ok(store.iter().find(|q| q.id == id).cloned())
     }
@@ -178,10 +1951,290 @@ impl QuestionRepository for MockRepository {
     async fn find_by_type(
         &self,
         qtype: &crate::models::QuestionType,
-    ) -> anyhow::Result<Vec<Question>> {
+    ) -> Result<Vec<Question>> {
         let store = self.questions.read().await;
         Ok(store.iter().filter(|q| &q.qtype == qtype).cloned().collect())
     }
+
+    async fn find_by_tag(&self, tag: &str) -> Result<Vec<Question>> {
+        let store = self.questions.read().await;
+        Ok(store
+            .iter()
+            .filter(|q| q.tags.iter().any(|t| t == tag))
+            .cloned()
+            .collect())
+    }
+
+    async fn list(&self, params: &ListParams) -> Result<Page<Question>> {
+        let limit = params.effective_limit();
+        let cursor = params.decode_cursor()?;
+        let store = self.questions.read().await;
+
+        let mut sorted: Vec<&Question> = store
+            .iter()
+            .filter(|q| params.qtype.is_none_or(|qtype| q.qtype == qtype))
+            .collect();
+        sorted.sort_by_key(|q| (q.created_at, q.id));
+        if params.order == SortOrder::Desc {
+            sorted.reverse();
+        }
+
+        let start = match cursor {
+            Some(key) => sorted
+                .iter()
+                .position(|q| match params.order {
+                    SortOrder::Asc => (q.created_at, q.id) > key,
+                    SortOrder::Desc => (q.created_at, q.id) < key,
+                })
+                .unwrap_or(sorted.len()),
+            None => params.offset.unwrap_or(0).min(sorted.len()),
+        };
+
+        let items = sorted[start..]
+            .iter()
+            .take(limit + 1)
+            .map(|q| (*q).clone())
+            .collect();
+
+        Ok(build_page(items, limit, sorted.len() as u64))
+    }
+
+    async fn update_as(&self, question: &Question, actor: Option<&str>) -> Result<bool> {
+        let mut store = self.questions.write().await;
+        match store.iter_mut().find(|q| q.id == question.id) {
+            Some(existing) => {
+                push_revision(&mut *self.revisions.write().await, existing, actor);
+                // Like the SQL update, which leaves created_at alone
+                *existing = Question { created_at: existing.created_at, ..question.clone() };
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn history(&self, id: Uuid) -> Result<Vec<QuestionRevision>> {
+        let revisions = self.revisions.read().await;
+        Ok(revisions.get(&id).into_iter().flatten().rev().cloned().collect())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool> {
+        Ok(self.delete_batch(&[id]).await? > 0)
+    }
+
+    async fn delete_batch(&self, ids: &[Uuid]) -> Result<u64> {
+        let mut store = self.questions.write().await;
+        let mut trash = self.trash.write().await;
+        let now = Utc::now();
+        let before = store.len();
+        store.retain(|q| {
+            if ids.contains(&q.id) {
+                trash.push((now, q.clone()));
+                return false;
+            }
+            true
+        });
+        Ok((before - store.len()) as u64)
+    }
+
+    async fn create_bank(&self, bank: &QuestionBank) -> Result<Uuid> {
+        let mut banks = self.banks.write().await;
+        if banks.iter().any(|b| b.id == bank.id) {
+            return Err(Md2DbError::repository("", format!("Question bank {} already exists", bank.id)));
+        }
+        banks.push(bank.clone());
+        Ok(bank.id)
+    }
+
+    async fn find_bank(&self, id: Uuid) -> Result<Option<QuestionBank>> {
+        let banks = self.banks.read().await;
+        Ok(banks.iter().find(|b| b.id == id).cloned())
+    }
+
+    async fn list_banks(&self) -> Result<Vec<QuestionBank>> {
+        Ok(self.banks.read().await.clone())
+    }
+
+    async fn assign_to_bank(&self, bank_id: Uuid, question_ids: &[Uuid]) -> Result<u64> {
+        if self.find_bank(bank_id).await?.is_none() {
+            return Err(Md2DbError::repository("", format!("Question bank {} not found", bank_id)));
+        }
+
+        let mut store = self.questions.write().await;
+        let mut assigned = 0;
+        for q in store.iter_mut().filter(|q| question_ids.contains(&q.id)) {
+            q.bank_id = Some(bank_id);
+            assigned += 1;
+        }
+        Ok(assigned)
+    }
+
+    async fn find_by_bank(&self, bank_id: Uuid) -> Result<Vec<Question>> {
+        let store = self.questions.read().await;
+        Ok(store.iter().filter(|q| q.bank_id == Some(bank_id)).cloned().collect())
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<bool> {
+        let mut trash = self.trash.write().await;
+        let Some(pos) = trash.iter().position(|(_, q)| q.id == id) else {
+            return Ok(false);
+        };
+        let (_, question) = trash.remove(pos);
+        self.questions.write().await.push(question);
+        Ok(true)
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+        let mut trash = self.trash.write().await;
+        let mut revisions = self.revisions.write().await;
+        let count = trash.len();
+        trash.retain(|(deleted_at, q)| {
+            if *deleted_at < before {
+                revisions.remove(&q.id);
+                return false;
+            }
+            true
+        });
+        Ok((count - trash.len()) as u64)
+    }
+
+    async fn begin_import(&self) -> Result<Box<dyn ImportSession>> {
+        Ok(Box::new(MockImportSession {
+            id: Uuid::new_v4(),
+            store: self.questions.clone(),
+            revisions: self.revisions.clone(),
+            inserts: Vec::new(),
+            updates: Vec::new(),
+            written: 0,
+        }))
+    }
+
+    async fn save_job(&self, job: &JobRecord) -> Result<()> {
+        let mut jobs = self.jobs.write().await;
+        match jobs.iter_mut().find(|j| j.id == job.id) {
+            Some(stored) => *stored = job.clone(),
+            None => jobs.push(job.clone()),
+        }
+        Ok(())
+    }
+
+    async fn find_job(&self, id: Uuid) -> Result<Option<JobRecord>> {
+        Ok(self.jobs.read().await.iter().find(|j| j.id == id).cloned())
+    }
+
+    async fn list_jobs(&self) -> Result<Vec<JobRecord>> {
+        let mut jobs = self.jobs.read().await.clone();
+        jobs.sort_by_key(|j| std::cmp::Reverse(j.created_at));
+        Ok(jobs)
+    }
+
+    async fn save_source(&self, source: &ImportedSource) -> Result<()> {
+        let mut sources = self.sources.write().await;
+        match sources
+            .iter_mut()
+            .find(|s| s.bank_id == source.bank_id && s.source == source.source)
+        {
+            Some(stored) => *stored = source.clone(),
+            None => sources.push(source.clone()),
+        }
+        Ok(())
+    }
+
+    async fn list_sources(&self, bank_id: Uuid) -> Result<Vec<ImportedSource>> {
+        let mut sources: Vec<_> = self
+            .sources
+            .read()
+            .await
+            .iter()
+            .filter(|s| s.bank_id == bank_id)
+            .cloned()
+            .collect();
+        sources.sort_by(|a, b| a.source.cmp(&b.source));
+        Ok(sources)
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Import session that stages writes in memory until commit
+pub struct MockImportSession {
+    id: Uuid,
+    store: std::sync::Arc<tokio::sync::RwLock<Vec<Question>>>,
+    revisions: RevisionStore,
+    /// New questions, in save order
+    inserts: Vec<Question>,
+    /// Stored questions whose answer/analysis the session refreshed
+    updates: Vec<Question>,
+    written: usize,
+}
+
+#[async_trait]
+impl ImportSession for MockImportSession {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    async fn save_batch(&mut self, questions: &[Question]) -> Result<Vec<Uuid>> {
+        self.inserts.extend_from_slice(questions);
+        self.written += questions.len();
+        Ok(questions.iter().map(|q| q.id).collect())
+    }
+
+    async fn save_batch_dedup(
+        &mut self,
+        questions: &[Question],
+        options: &DedupOptions,
+    ) -> Result<Vec<SaveOutcome>> {
+        // Deduplicate against the store as this session would leave it
+        let mut view = self.store.read().await.clone();
+        for updated in &self.updates {
+            if let Some(q) = view.iter_mut().find(|q| q.id == updated.id) {
+                *q = updated.clone();
+            }
+        }
+        view.extend(self.inserts.iter().cloned());
+
+        let outcomes = dedup_into(&mut view, questions, options);
+        let record = |id: Uuid| view.iter().find(|q| q.id == id).cloned();
+        for outcome in &outcomes {
+            match *outcome {
+                SaveOutcome::Inserted { id } => self.inserts.extend(record(id)),
+                SaveOutcome::Updated { existing_id, .. } => {
+                    let Some(updated) = record(existing_id) else { continue };
+                    match self.inserts.iter_mut().find(|q| q.id == existing_id) {
+                        Some(pending) => *pending = updated,
+                        None => {
+                            self.updates.retain(|u| u.id != existing_id);
+                            self.updates.push(updated);
+                        }
+                    }
+                }
+                SaveOutcome::DuplicateSkipped { .. } => continue,
+            }
+            self.written += 1;
+        }
+
+        Ok(outcomes)
+    }
+
+    async fn commit(self: Box<Self>) -> Result<usize> {
+        let mut store = self.store.write().await;
+        let mut revisions = self.revisions.write().await;
+        for updated in self.updates {
+            if let Some(existing) = store.iter_mut().find(|q| q.id == updated.id) {
+                push_revision(&mut revisions, existing, None);
+                existing.answer = updated.answer;
+                existing.analysis = updated.analysis;
+            }
+        }
+        store.extend(self.inserts);
+        Ok(self.written)
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -193,7 +2246,7 @@ mod tests {
         let repo = MockRepository::new();
         let question = Question::default();
 
-        let ids = repo.save_batch(&[question.clone()]).await.unwrap();
+        let ids = repo.save_batch(std::slice::from_ref(&question)).await.unwrap();
         assert_eq!(ids.len(), 1);
 
         let found = repo.find_by_id(ids[0]).await.unwrap();
@@ -202,4 +2255,293 @@ mod tests {
         let all = repo.find_by_type(&question.qtype).await.unwrap();
         assert_eq!(all.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_mock_repository_list_offset_and_cursor() {
+        let repo = MockRepository::new();
+        let base = Utc::now();
+        let questions: Vec<Question> = (0..5)
+            .map(|i| Question {
+                stem: format!("Q{}", i),
+                created_at: base + chrono::Duration::seconds(i),
+                ..Default::default()
+            })
+            .collect();
+        repo.save_batch(&questions).await.unwrap();
+
+        let params = ListParams { limit: Some(2), offset: Some(1), ..Default::default() };
+        let page = repo.list(&params).await.unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(page.items.iter().map(|q| q.stem.as_str()).collect::<Vec<_>>(), ["Q1", "Q2"]);
+
+        let params = ListParams { limit: Some(2), order: SortOrder::Desc, ..Default::default() };
+        let first = repo.list(&params).await.unwrap();
+        assert_eq!(first.items[0].stem, "Q4");
+
+        let params = ListParams { cursor: first.next_cursor.clone(), ..params };
+        let second = repo.list(&params).await.unwrap();
+        assert_eq!(second.items.iter().map(|q| q.stem.as_str()).collect::<Vec<_>>(), ["Q2", "Q1"]);
+
+        let params = ListParams { cursor: second.next_cursor.clone(), ..params };
+        let last = repo.list(&params).await.unwrap();
+        assert_eq!(last.items.len(), 1);
+        assert!(last.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mock_repository_update_and_delete() {
+        let repo = MockRepository::new();
+        let questions = vec![Question::default(), Question::default(), Question::default()];
+        repo.save_batch(&questions).await.unwrap();
+
+        let mut edited = questions[0].clone();
+        edited.stem = "Fixed typo".to_string();
+        edited.created_at = Utc::now() + chrono::Duration::days(1);
+        assert!(repo.update(&edited).await.unwrap());
+        let stored = repo.find_by_id(edited.id).await.unwrap().unwrap();
+        assert_eq!(stored.stem, "Fixed typo");
+        assert_eq!(stored.created_at, questions[0].created_at);
+        assert!(!repo.update(&Question::default()).await.unwrap());
+
+        assert!(repo.delete(questions[0].id).await.unwrap());
+        assert!(!repo.delete(questions[0].id).await.unwrap());
+        assert_eq!(
+            repo.delete_batch(&[questions[1].id, questions[2].id, Uuid::new_v4()]).await.unwrap(),
+            2
+        );
+        assert_eq!(repo.list(&ListParams::default()).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_mock_repository_save_batch_dedup() {
+        let repo = MockRepository::new();
+        let original = Question {
+            stem: "Which sorting algorithm is stable?".to_string(),
+            answer: Some("A".to_string()),
+            ..Question::default()
+        };
+        repo.save_batch(std::slice::from_ref(&original)).await.unwrap();
+
+        let same = Question { stem: "which sorting  algorithm is stable?".to_string(), ..original.clone() };
+        let same = Question { id: Uuid::new_v4(), ..same };
+        let corrected = Question { id: Uuid::new_v4(), answer: Some("B".to_string()), ..original.clone() };
+        let typo = Question {
+            id: Uuid::new_v4(),
+            stem: "Which sorting algoritm is stable?".to_string(),
+            ..original.clone()
+        };
+        let fresh = Question { id: Uuid::new_v4(), stem: "What is 2+2?".to_string(), ..original.clone() };
+
+        let outcomes = repo
+            .save_batch_dedup(&[same.clone(), corrected.clone(), typo, fresh.clone()], &DedupOptions::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(outcomes[0], SaveOutcome::DuplicateSkipped { existing_id, similarity, .. }
+            if existing_id == original.id && similarity == 1.0));
+        assert_eq!(outcomes[1], SaveOutcome::Updated { id: corrected.id, existing_id: original.id });
+        assert!(matches!(outcomes[2], SaveOutcome::DuplicateSkipped { existing_id, similarity, .. }
+            if existing_id == original.id && similarity < 1.0));
+        assert_eq!(outcomes[3], SaveOutcome::Inserted { id: fresh.id });
+
+        let page = repo.list(&ListParams::default()).await.unwrap();
+        assert_eq!(page.total, 2);
+        let stored = repo.find_by_id(original.id).await.unwrap().unwrap();
+        assert_eq!(stored.answer.as_deref(), Some("B"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_repository_history_and_restore() {
+        let repo = MockRepository::new();
+        let original = Question {
+            stem: "Original".to_string(),
+            answer: Some("A".to_string()),
+            ..Question::default()
+        };
+        repo.save_batch(std::slice::from_ref(&original)).await.unwrap();
+        assert!(repo.history(original.id).await.unwrap().is_empty());
+
+        let edited = Question {
+            stem: "Edited".to_string(),
+            ..original.clone()
+        };
+        assert!(repo.update_as(&edited, Some("alice")).await.unwrap());
+
+        let history = repo.history(original.id).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].revision, 1);
+        assert_eq!(history[0].actor.as_deref(), Some("alice"));
+        assert_eq!(history[0].question.stem, "Original");
+
+        let restored = repo.restore_revision(original.id, 1).await.unwrap().unwrap();
+        assert_eq!(restored.stem, "Original");
+        assert_eq!(repo.find_by_id(original.id).await.unwrap().unwrap().stem, "Original");
+        let history = repo.history(original.id).await.unwrap();
+        assert_eq!(history.iter().map(|r| r.revision).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(history[0].question.stem, "Edited");
+
+        assert!(repo.restore_revision(original.id, 9).await.unwrap().is_none());
+
+        // Answers refreshed by a deduplicating re-import are recorded too
+        let reimport = Question {
+            id: Uuid::new_v4(),
+            answer: Some("B".to_string()),
+            ..original.clone()
+        };
+        repo.save_batch_dedup(&[reimport], &DedupOptions::exact_only()).await.unwrap();
+        let history = repo.history(original.id).await.unwrap();
+        assert_eq!(history[0].revision, 3);
+        assert_eq!(history[0].question.answer.as_deref(), Some("A"));
+
+        // Soft deletes keep the history; purging drops it
+        repo.delete(original.id).await.unwrap();
+        assert_eq!(repo.history(original.id).await.unwrap().len(), 3);
+        repo.purge(Utc::now() + chrono::Duration::seconds(1)).await.unwrap();
+        assert!(repo.history(original.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mock_repository_soft_delete_restore_and_purge() {
+        let repo = MockRepository::new();
+        let kept = Question { stem: "Kept".to_string(), ..Question::default() };
+        let removed = Question { stem: "Removed".to_string(), ..Question::default() };
+        repo.save_batch(&[kept.clone(), removed.clone()]).await.unwrap();
+
+        assert!(repo.delete(removed.id).await.unwrap());
+        assert!(!repo.delete(removed.id).await.unwrap());
+        assert!(repo.find_by_id(removed.id).await.unwrap().is_none());
+        assert_eq!(repo.list(&ListParams::default()).await.unwrap().total, 1);
+        assert!(!repo.update(&removed).await.unwrap());
+
+        // A deleted question no longer counts as a duplicate
+        let copy = Question { id: Uuid::new_v4(), ..removed.clone() };
+        let outcomes = repo.save_batch_dedup(std::slice::from_ref(&copy), &DedupOptions::exact_only()).await.unwrap();
+        assert_eq!(outcomes, vec![SaveOutcome::Inserted { id: copy.id }]);
+        repo.delete(copy.id).await.unwrap();
+
+        assert!(repo.restore(removed.id).await.unwrap());
+        assert!(!repo.restore(removed.id).await.unwrap());
+        assert!(!repo.restore(kept.id).await.unwrap());
+        assert_eq!(repo.find_by_id(removed.id).await.unwrap().unwrap().stem, "Removed");
+
+        assert_eq!(repo.purge(Utc::now() - chrono::Duration::hours(1)).await.unwrap(), 0);
+        assert_eq!(repo.purge(Utc::now() + chrono::Duration::seconds(1)).await.unwrap(), 1);
+        assert!(!repo.restore(copy.id).await.unwrap());
+        assert_eq!(repo.list(&ListParams::default()).await.unwrap().total, 2);
+    }
+
+    #[test]
+    fn test_database_config_builders() {
+        let config = DatabaseConfig::default()
+            .with_max_connections(0)
+            .with_acquire_timeout(Duration::from_secs(5))
+            .with_statement_timeout(Duration::from_millis(1500));
+        assert_eq!(config.max_connections, 1);
+        assert_eq!(config.acquire_timeout, Duration::from_secs(5));
+        assert_eq!(config.statement_timeout, Some(Duration::from_millis(1500)));
+
+        let config = config.with_statement_timeout(Duration::ZERO);
+        assert_eq!(config.statement_timeout, None);
+
+        assert_eq!(config.insert_mode, InsertMode::MultiRow);
+        assert_eq!("COPY".parse::<InsertMode>().unwrap(), InsertMode::Copy);
+        assert_eq!("multi-row".parse::<InsertMode>().unwrap(), InsertMode::MultiRow);
+        assert!("bulk".parse::<InsertMode>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_import_session_commit_and_rollback() {
+        let repo = MockRepository::new();
+        let question = |stem: &str| Question { stem: stem.to_string(), ..Question::default() };
+        repo.save_batch(&[question("Stored")]).await.unwrap();
+
+        // Nothing is visible until commit, and rollback discards every batch
+        let mut session = repo.begin_import().await.unwrap();
+        session.save_batch(&[question("Q1"), question("Q2")]).await.unwrap();
+        session.save_batch(&[question("Q3")]).await.unwrap();
+        assert_eq!(repo.list(&ListParams::default()).await.unwrap().total, 1);
+        session.rollback().await.unwrap();
+        assert_eq!(repo.list(&ListParams::default()).await.unwrap().total, 1);
+
+        // Dropping a session without committing also discards it
+        let mut session = repo.begin_import().await.unwrap();
+        session.save_batch(&[question("Q4")]).await.unwrap();
+        drop(session);
+        assert_eq!(repo.list(&ListParams::default()).await.unwrap().total, 1);
+
+        // Dedup sees stored questions and those saved earlier in the session
+        let mut session = repo.begin_import().await.unwrap();
+        let options = DedupOptions::exact_only();
+        let first = session.save_batch_dedup(&[question("stored"), question("Q5")], &options).await.unwrap();
+        let second = session.save_batch_dedup(&[question("Q5"), question("Q6")], &options).await.unwrap();
+        assert!(matches!(first[0], SaveOutcome::DuplicateSkipped { .. }));
+        assert!(matches!(second[0], SaveOutcome::DuplicateSkipped { .. }));
+        assert_eq!(session.commit().await.unwrap(), 2);
+
+        let stems: Vec<_> = repo.list(&ListParams::default()).await.unwrap().items.into_iter().map(|q| q.stem).collect();
+        assert_eq!(stems, vec!["Stored", "Q5", "Q6"]);
+    }
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        assert!(!MIGRATIONS.is_empty());
+        for (idx, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, idx as i64 + 1);
+            assert!(!migration.sql.trim().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        assert_eq!(decode_cursor(&encode_cursor(now, id)).unwrap(), (now, id));
+        assert!(decode_cursor("garbage").is_err());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_like_pattern_escapes_wildcards() {
+        assert_eq!(postgres::like_pattern("x^2"), "%x^2%");
+        assert_eq!(postgres::like_pattern("50%_a\\b"), "%50\\%\\_a\\\\b%");
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_replica_configuration() {
+        let lazy = |url: &str| sqlx::postgres::PgPoolOptions::new().connect_lazy(url).unwrap();
+        let repo = postgres::PostgresRepository::with_pool(lazy("postgres://md2db@127.0.0.1:1/md2db"));
+        assert!(!repo.has_replica());
+        let repo = repo.with_replica(lazy("postgres://md2db@127.0.0.1:2/md2db"));
+        assert!(repo.has_replica());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_upsert_replaces_type_and_its_source() {
+        assert!(postgres::UPSERT_CLAUSE.contains("type = EXCLUDED.type,"));
+        assert!(postgres::UPSERT_CLAUSE.contains("type_source = EXCLUDED.type_source,"));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_copy_escaping() {
+        let mut out = String::new();
+        postgres::push_copy_escaped(&mut out, "a\tb\nc\\d\r\\N");
+        assert_eq!(out, "a\\tb\\nc\\\\d\\r\\\\N");
+    }
+
+    #[tokio::test]
+    async fn test_mock_repository_find_by_tag() {
+        let repo = MockRepository::new();
+        let tagged = Question {
+            tags: vec!["数学".to_string()],
+            ..Default::default()
+        };
+
+        repo.save_batch(&[tagged, Question::default()]).await.unwrap();
+
+        assert_eq!(repo.find_by_tag("数学").await.unwrap().len(), 1);
+        assert!(repo.find_by_tag("物理").await.unwrap().is_empty());
+    }
 }
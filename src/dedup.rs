@@ -0,0 +1,263 @@
+//! Content-based duplicate detection
+//!
+//! Questions are compared on a normalized form of their stem and options, so
+//! re-importing the same bank (or a copy with whitespace and case changes)
+//! does not create duplicate records. Near-duplicates, such as a stem with a
+//! corrected typo, are found with a character-bigram similarity score.
+
+use crate::models::Question;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Similarity at or above which two questions are considered near-duplicates
+pub const DEFAULT_NEAR_DUPLICATE_THRESHOLD: f64 = 0.9;
+
+/// How duplicates are detected when saving
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedupOptions {
+    /// Minimum similarity for a near-duplicate match; `None` matches exact content only
+    pub near_duplicate_threshold: Option<f64>,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        Self {
+            near_duplicate_threshold: Some(DEFAULT_NEAR_DUPLICATE_THRESHOLD),
+        }
+    }
+}
+
+impl DedupOptions {
+    /// Only skip questions whose normalized content is identical
+    pub fn exact_only() -> Self {
+        Self {
+            near_duplicate_threshold: None,
+        }
+    }
+
+    /// Set the near-duplicate similarity threshold (clamped to 0.0..=1.0)
+    pub fn with_near_duplicate_threshold(mut self, threshold: f64) -> Self {
+        self.near_duplicate_threshold = Some(threshold.clamp(0.0, 1.0));
+        self
+    }
+}
+
+/// What happened to a question passed to `save_batch_dedup`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum SaveOutcome {
+    /// Stored as a new record
+    Inserted { id: Uuid },
+    /// Not stored because an equal or near-equal question already exists
+    DuplicateSkipped {
+        id: Uuid,
+        existing_id: Uuid,
+        similarity: f64,
+    },
+    /// Same content as an existing record whose answer/analysis was refreshed
+    Updated { id: Uuid, existing_id: Uuid },
+}
+
+impl SaveOutcome {
+    /// ID of the record that now holds the question's content
+    pub fn stored_id(&self) -> Uuid {
+        match self {
+            SaveOutcome::Inserted { id } => *id,
+            SaveOutcome::DuplicateSkipped { existing_id, .. } => *existing_id,
+            SaveOutcome::Updated { existing_id, .. } => *existing_id,
+        }
+    }
+
+    /// Whether the question was written to the repository
+    pub fn is_written(&self) -> bool {
+        !matches!(self, SaveOutcome::DuplicateSkipped { .. })
+    }
+}
+
+/// Decision for a single incoming question
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// No match: insert the question
+    Insert,
+    /// Matches an existing question closely enough to be skipped
+    Skip { existing_id: Uuid, similarity: f64 },
+    /// Identical content with a different answer or analysis
+    Update { existing_id: Uuid },
+}
+
+impl Decision {
+    /// Outcome reported for `question` after applying this decision
+    pub fn outcome(&self, question: &Question) -> SaveOutcome {
+        match *self {
+            Decision::Insert => SaveOutcome::Inserted { id: question.id },
+            Decision::Skip {
+                existing_id,
+                similarity,
+            } => SaveOutcome::DuplicateSkipped {
+                id: question.id,
+                existing_id,
+                similarity,
+            },
+            Decision::Update { existing_id } => SaveOutcome::Updated {
+                id: question.id,
+                existing_id,
+            },
+        }
+    }
+}
+
+/// Decide how to save `question` given its exact match and near-duplicate candidates
+///
+/// An exact match is skipped when its answer and analysis are unchanged and
+/// updated otherwise; near-duplicates are always skipped.
+pub fn decide<'a>(
+    question: &Question,
+    exact: Option<&Question>,
+    candidates: impl IntoIterator<Item = &'a Question>,
+    options: &DedupOptions,
+) -> Decision {
+    if let Some(existing) = exact {
+        return if existing.answer == question.answer && existing.analysis == question.analysis {
+            Decision::Skip {
+                existing_id: existing.id,
+                similarity: 1.0,
+            }
+        } else {
+            Decision::Update {
+                existing_id: existing.id,
+            }
+        };
+    }
+
+    let Some(threshold) = options.near_duplicate_threshold else {
+        return Decision::Insert;
+    };
+
+    let key = content_key(question);
+    candidates
+        .into_iter()
+        .filter(|c| c.id != question.id)
+        .map(|c| (c.id, similarity(&key, &content_key(c))))
+        .filter(|(_, score)| *score >= threshold)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(existing_id, similarity)| Decision::Skip {
+            existing_id,
+            similarity,
+        })
+        .unwrap_or(Decision::Insert)
+}
+
+/// Normalized content used to detect duplicate questions
+///
+/// Case and whitespace differences in the stem and options are ignored.
+pub fn content_key(question: &Question) -> String {
+    let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+
+    let mut key = normalize(&question.stem);
+    for option in &question.options {
+        key.push('\u{1f}');
+        key.push_str(&normalize(&option.content));
+    }
+    key
+}
+
+/// Hex SHA-256 of the question's [`content_key`]
+pub fn content_hash(question: &Question) -> String {
+    let digest = Sha256::digest(content_key(question).as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Jaccard similarity of the character bigrams of two strings (0.0..=1.0)
+pub fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+
+    let bigrams = |s: &str| -> HashSet<(char, char)> {
+        let chars: Vec<char> = s.chars().collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+    let (a, b) = (bigrams(a), bigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Stem length range that can still reach `threshold` similarity
+pub fn length_window(stem_len: usize, threshold: f64) -> (usize, usize) {
+    let threshold = threshold.max(0.01);
+    let min = (stem_len as f64 * threshold).floor() as usize;
+    let max = (stem_len as f64 / threshold).ceil() as usize;
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    fn question(stem: &str, answer: Option<&str>) -> Question {
+        Question {
+            stem: stem.to_string(),
+            options: vec![QuestionOption {
+                content: "A. 4".to_string(),
+                sort_order: 0,
+                is_correct: false,
+            }],
+            answer: answer.map(str::to_string),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn test_content_hash_ignores_case_and_whitespace() {
+        let a = question("What is  2 + 2?", None);
+        let b = question("what is 2 + 2?", None);
+        assert_eq!(content_hash(&a), content_hash(&b));
+        assert_eq!(content_hash(&a).len(), 64);
+        assert_ne!(content_hash(&a), content_hash(&question("What is 3 + 3?", None)));
+    }
+
+    #[test]
+    fn test_decide() {
+        let options = DedupOptions::default();
+        let existing = question("Which sorting algorithm is stable?", Some("A"));
+
+        let same = question("Which sorting algorithm is stable?", Some("A"));
+        assert_eq!(
+            decide(&same, Some(&existing), [], &options),
+            Decision::Skip { existing_id: existing.id, similarity: 1.0 }
+        );
+
+        let new_answer = question("Which sorting algorithm is stable?", Some("B"));
+        assert_eq!(
+            decide(&new_answer, Some(&existing), [], &options),
+            Decision::Update { existing_id: existing.id }
+        );
+
+        let typo = question("Which sorting algoritm is stable?", Some("A"));
+        match decide(&typo, None, [&existing], &options) {
+            Decision::Skip { existing_id, similarity } => {
+                assert_eq!(existing_id, existing.id);
+                assert!((DEFAULT_NEAR_DUPLICATE_THRESHOLD..1.0).contains(&similarity));
+            }
+            other => panic!("expected near-duplicate, got {:?}", other),
+        }
+        assert_eq!(decide(&typo, None, [&existing], &DedupOptions::exact_only()), Decision::Insert);
+
+        let different = question("What is the capital of France?", None);
+        assert_eq!(decide(&different, None, [&existing], &options), Decision::Insert);
+    }
+
+    #[test]
+    fn test_similarity_and_length_window() {
+        assert_eq!(similarity("abc", "abc"), 1.0);
+        assert_eq!(similarity("ab", "cd"), 0.0);
+        assert!(similarity("下列说法正确的是", "下列说法中正确的是") > 0.6);
+        assert_eq!(length_window(100, 0.9), (90, 112));
+    }
+}
@@ -0,0 +1,286 @@
+//! Upload format detection
+//!
+//! [`FormatDetector`] recognizes an upload by its content so one endpoint
+//! can accept every supported file and mislabelled files still reach the
+//! right pipeline. Binary formats are recognized by their leading bytes,
+//! and ZIP containers by the parts inside them (a `.docx` or `.xlsx` is a
+//! ZIP too). Text is checked for GIFT answer blocks, Markdown block syntax
+//! and delimited columns, in that order; the file name only decides between
+//! formats the content cannot tell apart, such as plain text and Markdown.
+
+use crate::archive::ArchiveFormat;
+use crate::zip::decode_text;
+use std::fmt;
+use std::io::{Cursor, Read};
+
+/// Bytes at the start of every OLE2 compound file (legacy `.xls`, `.doc`)
+const OLE2_MAGIC: &[u8] = b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1";
+
+/// Lines of text examined by the heuristics
+const SAMPLE_LINES: usize = 50;
+
+/// A format an upload can be imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// An archive of question files
+    Archive(ArchiveFormat),
+    /// Word document
+    Docx,
+    /// PDF with a text layer
+    Pdf,
+    /// Markdown
+    Markdown,
+    /// Plain text, parsed by question numbering
+    Text,
+    /// Moodle GIFT
+    Gift,
+    /// Comma-separated spreadsheet
+    Csv,
+    /// Tab-separated spreadsheet
+    Tsv,
+    /// Excel or OpenDocument workbook
+    Workbook,
+}
+
+impl ImportFormat {
+    /// Whether this build can import the format
+    pub fn is_enabled(self) -> bool {
+        match self {
+            ImportFormat::Archive(format) => format.is_enabled(),
+            ImportFormat::Docx => cfg!(feature = "docx"),
+            ImportFormat::Pdf => cfg!(feature = "pdf"),
+            ImportFormat::Csv | ImportFormat::Tsv => cfg!(feature = "csv"),
+            ImportFormat::Workbook => cfg!(feature = "xlsx"),
+            ImportFormat::Markdown | ImportFormat::Text | ImportFormat::Gift => true,
+        }
+    }
+
+    /// Whether the format is a spreadsheet with one question per row
+    pub fn is_table(self) -> bool {
+        matches!(self, ImportFormat::Csv | ImportFormat::Tsv | ImportFormat::Workbook)
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportFormat::Archive(format) => write!(f, "{} archive", format),
+            ImportFormat::Docx => f.write_str("Word document"),
+            ImportFormat::Pdf => f.write_str("PDF"),
+            ImportFormat::Markdown => f.write_str("Markdown"),
+            ImportFormat::Text => f.write_str("plain text"),
+            ImportFormat::Gift => f.write_str("GIFT"),
+            ImportFormat::Csv => f.write_str("CSV"),
+            ImportFormat::Tsv => f.write_str("TSV"),
+            ImportFormat::Workbook => f.write_str("workbook"),
+        }
+    }
+}
+
+/// Recognizes the format of uploaded files
+pub struct FormatDetector;
+
+impl FormatDetector {
+    /// Format of an upload named `name`, or `None` if it is not recognized
+    pub fn detect(name: &str, data: &[u8]) -> Option<ImportFormat> {
+        let head = &data[..data.len().min(1024)];
+        // Readers accept junk before the header, and so do we
+        if head.windows(5).any(|w| w == b"%PDF-") {
+            return Some(ImportFormat::Pdf);
+        }
+        if data.starts_with(OLE2_MAGIC) {
+            // Only workbooks are readable among the legacy Office formats
+            return Some(ImportFormat::Workbook).filter(|_| !has_extension(name, &["doc", "ppt"]));
+        }
+        match ArchiveFormat::detect(head) {
+            Some(ArchiveFormat::Zip) => return Some(Self::detect_zip(data)),
+            Some(format) => return Some(ImportFormat::Archive(format)),
+            None => {}
+        }
+
+        // Text in any encoding the parsers read, but not binary junk
+        let (text, _) = decode_text(data)?;
+        if text.contains('\0') {
+            return None;
+        }
+        Some(Self::detect_text(name, &text))
+    }
+
+    /// Tell Office documents from archives by the parts they contain
+    fn detect_zip(data: &[u8]) -> ImportFormat {
+        let Ok(mut archive) = ::zip::ZipArchive::new(Cursor::new(data)) else {
+            return ImportFormat::Archive(ArchiveFormat::Zip);
+        };
+        let has = |archive: &::zip::ZipArchive<_>, name: &str| archive.index_for_name(name).is_some();
+        if has(&archive, "[Content_Types].xml") {
+            if has(&archive, "word/document.xml") {
+                return ImportFormat::Docx;
+            }
+            if has(&archive, "xl/workbook.xml") || has(&archive, "xl/workbook.bin") {
+                return ImportFormat::Workbook;
+            }
+        }
+        // OpenDocument names its type in an uncompressed `mimetype` part
+        let mut mimetype = String::new();
+        if let Ok(mut part) = archive.by_name("mimetype") {
+            let _ = part.by_ref().take(128).read_to_string(&mut mimetype);
+        }
+        if mimetype.trim() == "application/vnd.oasis.opendocument.spreadsheet" {
+            return ImportFormat::Workbook;
+        }
+        ImportFormat::Archive(ArchiveFormat::Zip)
+    }
+
+    /// Classify text content, using the file name only as a tie-breaker
+    fn detect_text(name: &str, text: &str) -> ImportFormat {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .take(SAMPLE_LINES)
+            .collect();
+
+        if looks_like_gift(text, &lines) {
+            return ImportFormat::Gift;
+        }
+        if looks_like_markdown(&lines) {
+            return ImportFormat::Markdown;
+        }
+        if consistent_columns(&lines, '\t') {
+            return ImportFormat::Tsv;
+        }
+        if consistent_columns(&lines, ',') {
+            return ImportFormat::Csv;
+        }
+
+        if has_extension(name, &["md", "markdown"]) {
+            ImportFormat::Markdown
+        } else if has_extension(name, &["gift"]) {
+            ImportFormat::Gift
+        } else if has_extension(name, &["csv"]) {
+            ImportFormat::Csv
+        } else if has_extension(name, &["tsv", "tab"]) {
+            ImportFormat::Tsv
+        } else {
+            ImportFormat::Text
+        }
+    }
+}
+
+fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    name.rsplit_once('.')
+        .is_some_and(|(_, ext)| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+/// GIFT: a `$CATEGORY` line, a `::title::`, or an answer block such as
+/// `{=right ~wrong}`, `{T}` or `{#3.14}`
+fn looks_like_gift(text: &str, lines: &[&str]) -> bool {
+    if lines.iter().any(|line| line.starts_with("$CATEGORY:") || line.starts_with("::")) {
+        return true;
+    }
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        // Skip escaped braces
+        if open > 0 && rest.as_bytes()[open - 1] == b'\\' {
+            rest = &rest[open + 1..];
+            continue;
+        }
+        let Some(len) = rest[open + 1..].find('}') else {
+            break;
+        };
+        let block = rest[open + 1..open + 1 + len].trim();
+        if block.starts_with(['=', '~', '#'])
+            || matches!(block.to_ascii_uppercase().as_str(), "T" | "F" | "TRUE" | "FALSE")
+        {
+            return true;
+        }
+        rest = &rest[open + 1 + len..];
+    }
+    false
+}
+
+/// Markdown: ATX headings, bullet lists, images or fenced code
+fn looks_like_markdown(lines: &[&str]) -> bool {
+    let heading = |line: &&str| {
+        let hashes = line.len() - line.trim_start_matches('#').len();
+        (1..=6).contains(&hashes) && line[hashes..].starts_with(' ')
+    };
+    let bullets = lines
+        .iter()
+        .filter(|line| line.starts_with("* ") || line.starts_with("- ") || line.starts_with("+ "))
+        .count();
+    lines.iter().any(heading)
+        || bullets >= 2
+        || lines.iter().any(|line| line.contains("![") || line.starts_with("```"))
+}
+
+/// Delimited rows: at least two lines, each with the same number of
+/// `delimiter`s outside double quotes
+fn consistent_columns(lines: &[&str], delimiter: char) -> bool {
+    let count = |line: &str| {
+        let mut quoted = false;
+        line.chars()
+            .filter(|&c| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == delimiter && !quoted
+            })
+            .count()
+    };
+    let Some(first) = lines.first().map(|line| count(line)) else {
+        return false;
+    };
+    lines.len() >= 2 && first >= 1 && lines.iter().all(|line| count(line) == first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn zip_with(names: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut writer = ::zip::ZipWriter::new(Cursor::new(&mut data));
+            for name in names {
+                writer.start_file(*name, ::zip::write::SimpleFileOptions::default()).unwrap();
+                writer.write_all(b"x").unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_detect_binary_formats() {
+        let detect = |data: &[u8]| FormatDetector::detect("upload", data);
+        assert_eq!(detect(b"%PDF-1.7\n..."), Some(ImportFormat::Pdf));
+        assert_eq!(detect(&zip_with(&["[Content_Types].xml", "word/document.xml"])), Some(ImportFormat::Docx));
+        assert_eq!(detect(&zip_with(&["[Content_Types].xml", "xl/workbook.xml"])), Some(ImportFormat::Workbook));
+        assert_eq!(
+            detect(&zip_with(&["exam.md", "img/a.png"])),
+            Some(ImportFormat::Archive(ArchiveFormat::Zip))
+        );
+        assert_eq!(detect(b"\x1f\x8b\x08\x00"), Some(ImportFormat::Archive(ArchiveFormat::TarGz)));
+        assert_eq!(detect(&[OLE2_MAGIC, b"...."].concat()), Some(ImportFormat::Workbook));
+        assert_eq!(FormatDetector::detect("old.doc", &[OLE2_MAGIC, b"...."].concat()), None);
+        assert_eq!(detect(&[0x00, 0x01, 0x02, 0xff]), None);
+    }
+
+    #[test]
+    fn test_detect_text_formats() {
+        let detect = |name: &str, text: &str| FormatDetector::detect(name, text.as_bytes()).unwrap();
+        assert_eq!(detect("upload", "# Question 1\n\n* A. 1\n* B. 2"), ImportFormat::Markdown);
+        assert_eq!(detect("upload", "\u{feff}::Q1:: 2+2 = {=4 ~3}"), ImportFormat::Gift);
+        assert_eq!(detect("upload", "The sun is a star. {T}"), ImportFormat::Gift);
+        assert_eq!(detect("upload", "stem,A,B,answer\n\"1, 2 or 3?\",1,2,A\n"), ImportFormat::Csv);
+        assert_eq!(detect("upload", "stem\tA\tB\n1+1\t2\t3\n"), ImportFormat::Tsv);
+        assert_eq!(detect("notes.txt", "1. What is 2+2?\nA. 3\nB. 4\n答案：B"), ImportFormat::Text);
+        assert_eq!(detect("notes.md", "1. What is 2+2?\nA. 3\nB. 4"), ImportFormat::Markdown);
+        let gbk = encoding_rs::GBK.encode("1. 下列哪个是质数？\nA. 4\nB. 5").0;
+        assert_eq!(FormatDetector::detect("upload", &gbk), Some(ImportFormat::Text));
+        // An escaped brace is not an answer block
+        assert_eq!(detect("upload", "Write \\{=x\\} literally"), ImportFormat::Text);
+    }
+}
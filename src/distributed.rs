@@ -0,0 +1,390 @@
+//! Distributed processing across worker nodes
+//!
+//! A [`Coordinator`] spreads the archives of an
+//! [`InputSource::MultipleZip`] over worker nodes, each an md2db server,
+//! and merges their answers into one [`ProcessResult`]. Workers are reached
+//! through the ordinary `/parse-archive` endpoint, so any deployed server
+//! can be a worker and the single-machine path is unchanged.
+//!
+//! Archives are assigned largest first to the worker with the fewest bytes
+//! so far, and every worker gets its shard as one request. A shard whose
+//! worker cannot be reached or answers with a server error is sent to the
+//! next worker; a shard rejected as a bad request is not retried. Duplicate
+//! questions are only dropped within a shard, unless dedup is turned on, in
+//! which case each worker skips questions already stored in the database
+//! they share. Workers that require credentials are sent the key given to
+//! [`Coordinator::with_api_key`] as a bearer token.
+
+use crate::api::ParseZipResponse;
+use crate::dedup::SaveOutcome;
+use crate::import::{ArchiveReport, ImportReport};
+use crate::processor::{InputSource, ProcessResult};
+use crate::zip::{ImageDedupReport, ImageStoreReport, StageMetrics};
+use anyhow::{bail, Result};
+use futures::future::join_all;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Default time a worker may take to answer one shard
+pub const DEFAULT_WORKER_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Archives sent to one worker
+#[derive(Debug)]
+struct Shard {
+    /// Index of the worker first tried
+    worker: usize,
+    /// Archive data and upload names
+    files: Vec<(Vec<u8>, String)>,
+}
+
+/// Why a worker did not process a shard
+enum ShardError {
+    /// The worker is unreachable or failing; another may succeed
+    Worker(String),
+    /// The worker rejected the shard itself
+    Rejected(String),
+}
+
+/// Shards archive imports across worker nodes
+pub struct Coordinator {
+    /// Base URLs of the workers, such as `http://node1:3000`
+    workers: Vec<String>,
+    client: reqwest::Client,
+    dedup: bool,
+    preset: Option<String>,
+    bank_id: Option<Uuid>,
+    /// Sent as a bearer token to workers with authentication enabled
+    api_key: Option<String>,
+    timeout: Duration,
+}
+
+impl Coordinator {
+    /// Create a coordinator for the workers at `workers` (base URLs)
+    pub fn new(workers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            workers: workers
+                .into_iter()
+                .map(|url| url.into().trim_end_matches('/').to_string())
+                .collect(),
+            client: reqwest::Client::new(),
+            dedup: false,
+            preset: None,
+            bank_id: None,
+            api_key: None,
+            timeout: DEFAULT_WORKER_TIMEOUT,
+        }
+    }
+
+    /// Ask workers to skip questions already stored
+    pub fn with_dedup(mut self, enabled: bool) -> Self {
+        self.dedup = enabled;
+        self
+    }
+
+    /// Parse with the named preset (see [`crate::presets`])
+    pub fn with_preset(mut self, name: impl Into<String>) -> Self {
+        self.preset = Some(name.into());
+        self
+    }
+
+    /// Import every question into this bank
+    pub fn with_bank_id(mut self, bank_id: Uuid) -> Self {
+        self.bank_id = Some(bank_id);
+        self
+    }
+
+    /// Authenticate with the workers using `key` (see [`crate::auth`])
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Give up on a worker that takes longer than `timeout` for a shard
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Import the archives of `input` on the workers
+    ///
+    /// Accepts [`InputSource::MultipleZip`] and [`InputSource::Zip`]. Fails
+    /// only if there are no workers; archives no worker could process are
+    /// reported with an error in the import report.
+    pub async fn process(&self, input: InputSource) -> Result<ProcessResult> {
+        let files = match input {
+            InputSource::MultipleZip { files } => files,
+            InputSource::Zip { data, source } => vec![(data, source)],
+            _ => bail!("Only archives can be processed by worker nodes"),
+        };
+        if self.workers.is_empty() {
+            bail!("No worker nodes configured");
+        }
+
+        let start = Instant::now();
+        let shards = assign(files, self.workers.len());
+        info!("Sending {} shards to {} workers", shards.len(), self.workers.len());
+        let responses = join_all(shards.into_iter().map(|shard| self.run_shard(shard))).await;
+
+        let mut result = ProcessResult::new();
+        let mut report = ImportReport::default();
+        for (sources, outcome, warnings) in responses {
+            result.warnings.extend(warnings);
+            match outcome {
+                Ok(response) => merge(&mut result, &mut report, response),
+                Err(error) => {
+                    warn!("Shard of {} archives failed: {}", sources.len(), error);
+                    for source in sources {
+                        result.warnings.push(format!("Failed to process archive {}: {}", source, error));
+                        report.archives.push(ArchiveReport {
+                            source,
+                            files: Vec::new(),
+                            questions: 0,
+                            duplicates: 0,
+                            image_dedup: ImageDedupReport::default(),
+                            image_store: ImageStoreReport::default(),
+                            metrics: StageMetrics::default(),
+                            error: Some(error.clone()),
+                        });
+                    }
+                }
+            }
+        }
+        report.warnings = result.warnings.clone();
+        result.import_report = Some(report);
+        result.processing_time_ms = start.elapsed().as_millis() as u64;
+
+        info!(
+            "Distributed import complete: {} questions saved, {} failed in {}ms",
+            result.saved_questions, result.failed_questions, result.processing_time_ms
+        );
+        Ok(result)
+    }
+
+    /// Send a shard to its worker, moving on to the others if it fails
+    ///
+    /// Returns the shard's archive names, the worker's response or the last
+    /// error, and a warning for each worker that failed.
+    async fn run_shard(&self, shard: Shard) -> (Vec<String>, Result<ParseZipResponse, String>, Vec<String>) {
+        let sources: Vec<String> = shard.files.iter().map(|(_, name)| name.clone()).collect();
+        let mut warnings = Vec::new();
+        let mut last_error = String::new();
+        for attempt in 0..self.workers.len() {
+            let worker = &self.workers[(shard.worker + attempt) % self.workers.len()];
+            match self.send(worker, &shard.files).await {
+                Ok(response) => return (sources, Ok(response), warnings),
+                Err(ShardError::Rejected(e)) => return (sources, Err(e), warnings),
+                Err(ShardError::Worker(e)) => {
+                    warn!("Worker {} failed: {}", worker, e);
+                    warnings.push(format!("Worker {} failed, trying another: {}", worker, e));
+                    last_error = e;
+                }
+            }
+        }
+        (sources, Err(last_error), warnings)
+    }
+
+    /// Post archives to a worker's `/parse-archive` endpoint
+    async fn send(&self, worker: &str, files: &[(Vec<u8>, String)]) -> Result<ParseZipResponse, ShardError> {
+        let mut form = reqwest::multipart::Form::new();
+        for (data, name) in files {
+            form = form.part("file", reqwest::multipart::Part::bytes(data.clone()).file_name(name.clone()));
+        }
+        if self.dedup {
+            form = form.text("dedup", "true");
+        }
+        if let Some(preset) = &self.preset {
+            form = form.text("preset", preset.clone());
+        }
+        if let Some(bank_id) = self.bank_id {
+            form = form.text("bank_id", bank_id.to_string());
+        }
+
+        let mut request = self
+            .client
+            .post(format!("{}/parse-archive", worker))
+            .timeout(self.timeout)
+            .multipart(form);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ShardError::Worker(e.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            return response
+                .json()
+                .await
+                .map_err(|e| ShardError::Worker(format!("unreadable response: {}", e)));
+        }
+        let body = response.text().await.unwrap_or_default();
+        let error = format!("{}: {}", status, body);
+        if status.is_client_error() {
+            Err(ShardError::Rejected(error))
+        } else {
+            Err(ShardError::Worker(error))
+        }
+    }
+}
+
+/// Split archives over `workers` workers, balancing their total size
+///
+/// The largest archives are placed first, each with the worker holding the
+/// fewest bytes; every shard keeps its archives in input order.
+fn assign(files: Vec<(Vec<u8>, String)>, workers: usize) -> Vec<Shard> {
+    let mut order: Vec<usize> = (0..files.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(files[i].0.len()));
+
+    let mut load = vec![0usize; workers];
+    let mut owner = vec![0usize; files.len()];
+    for i in order {
+        let worker = (0..workers).min_by_key(|&w| load[w]).unwrap_or_default();
+        load[worker] += files[i].0.len();
+        owner[i] = worker;
+    }
+
+    let mut shards: Vec<Shard> = (0..workers).map(|worker| Shard { worker, files: Vec::new() }).collect();
+    for (file, worker) in files.into_iter().zip(owner) {
+        shards[worker].files.push(file);
+    }
+    shards.retain(|shard| !shard.files.is_empty());
+    shards
+}
+
+/// Add a worker's response to the merged result
+fn merge(result: &mut ProcessResult, report: &mut ImportReport, response: ParseZipResponse) {
+    let duplicates = response.outcomes.iter().filter(|o| !o.is_written()).count();
+    let updated = response
+        .outcomes
+        .iter()
+        .filter(|o| matches!(o, SaveOutcome::Updated { .. }))
+        .count();
+    result.total_questions += response.count + duplicates;
+    result.saved_questions += response.count;
+    result.updated_questions += updated;
+    result.added_questions += response.count - updated;
+    result.duplicate_questions += duplicates;
+    result.total_images += response.images_processed;
+    result.outcomes.extend(response.outcomes);
+    result.warnings.extend(response.warnings);
+
+    for archive in &response.archives {
+        result.stored_images += archive.image_store.stored;
+        result.deduplicated_images += archive.image_store.already_stored;
+        result.failed_images += archive.image_store.failed;
+    }
+    report.total_questions += response.questions.len();
+    report.duplicates_skipped += response.duplicates_skipped;
+    report.archives.extend(response.archives);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::create_router;
+    use crate::database::{ListParams, MockRepository, QuestionRepository};
+    use std::io::Write;
+    use std::sync::Arc;
+
+    fn archive(stem: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(&mut data));
+            writer.start_file("exam.md", ::zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(format!("# {}\n\n* A. 1\n* B. 2", stem).as_bytes()).unwrap();
+            writer.finish().unwrap();
+        }
+        data
+    }
+
+    /// Start a worker on a free port and return its URL and repository
+    async fn worker() -> (String, Arc<MockRepository>) {
+        serve(None).await
+    }
+
+    /// Start a worker, accepting only `key` if one is given
+    async fn serve(key: Option<&str>) -> (String, Arc<MockRepository>) {
+        let repository = Arc::new(MockRepository::new());
+        let state: Arc<dyn QuestionRepository> = repository.clone();
+        let mut app = create_router().with_state(state);
+        if let Some(key) = key {
+            let auth = Arc::new(crate::auth::Authenticator::new().with_key("coordinator", key));
+            app = app.layer(axum::middleware::from_fn_with_state(auth, crate::auth::require_auth));
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (url, repository)
+    }
+
+    #[test]
+    fn test_assign_balances_bytes() {
+        let files = vec![
+            (vec![0; 10], "a.zip".to_string()),
+            (vec![0; 60], "b.zip".to_string()),
+            (vec![0; 30], "c.zip".to_string()),
+            (vec![0; 25], "d.zip".to_string()),
+        ];
+        let shards = assign(files, 2);
+        let names: Vec<Vec<&str>> = shards
+            .iter()
+            .map(|s| s.files.iter().map(|(_, name)| name.as_str()).collect())
+            .collect();
+        assert_eq!(names, [vec!["b.zip"], vec!["a.zip", "c.zip", "d.zip"]]);
+        assert_eq!(assign(vec![(vec![1], "a.zip".to_string())], 3).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_process_across_workers() {
+        let (first, first_repo) = worker().await;
+        let (second, second_repo) = worker().await;
+        // Nothing listens on port 1, so its shard moves to a live worker
+        let coordinator = Coordinator::new([first.as_str(), second.as_str(), "http://127.0.0.1:1"]);
+        let files = ["1+1?", "2+2?", "3+3?"]
+            .iter()
+            .enumerate()
+            .map(|(i, stem)| (archive(stem), format!("{}.zip", i)))
+            .collect();
+
+        let result = coordinator.process(InputSource::MultipleZip { files }).await.unwrap();
+        assert_eq!(result.saved_questions, 3);
+        assert_eq!(result.total_questions, 3);
+        let report = result.import_report.unwrap();
+        assert_eq!(report.archives.len(), 3);
+        assert!(report.archives.iter().all(|a| a.error.is_none()));
+        assert!(result.warnings.iter().any(|w| w.starts_with("Worker http://127.0.0.1:1 failed")));
+
+        let stored = first_repo.list(&ListParams::default()).await.unwrap().total
+            + second_repo.list(&ListParams::default()).await.unwrap().total;
+        assert_eq!(stored, 3);
+
+        let gift = InputSource::Gift {
+            content: String::new(),
+            source: "quiz.gift".to_string(),
+        };
+        assert!(coordinator.process(gift).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_process_on_workers_with_auth() {
+        let (url, repository) = serve(Some("s3cret")).await;
+        let input = || InputSource::Zip {
+            data: archive("1+1?"),
+            source: "exam.zip".to_string(),
+        };
+
+        let result = Coordinator::new([url.as_str()]).process(input()).await.unwrap();
+        assert_eq!(result.saved_questions, 0);
+        let error = result.import_report.unwrap().archives[0].error.clone().unwrap();
+        assert!(error.starts_with("401"), "{}", error);
+
+        let coordinator = Coordinator::new([url.as_str()]).with_api_key("s3cret");
+        let result = coordinator.process(input()).await.unwrap();
+        assert_eq!(result.saved_questions, 1);
+        assert_eq!(repository.list(&ListParams::default()).await.unwrap().total, 1);
+    }
+}
@@ -0,0 +1,1255 @@
+//! Word (.docx) ingestion and export
+//!
+//! A `.docx` file is a ZIP of XML parts. [`DocxParser`] turns
+//! `word/document.xml` into Markdown and hands it to the Markdown parser, so
+//! Word question banks go through the same pipeline as Markdown files:
+//!
+//! * heading styles become headings
+//! * auto-numbered top-level paragraphs (`1.`, `2.`, …) start questions and
+//!   lettered ones (`A.`, `B.`, …) become options
+//! * embedded images become image references to the extracted media
+//! * OMML equations become `$…$` LaTeX
+//!
+//! Numbering typed by hand is kept as text, for the numbered parser presets.
+//!
+//! [`DocxWriter`] goes the other way and lays questions out as a printable
+//! exam paper.
+
+use crate::bidi::{export_text, split_math};
+use crate::media::{validate_image, MediaStore};
+use crate::models::{ImageRef, Question, QuestionType};
+use crate::parser::{escape_markdown, parse_markdown_with_options, ParserOptions};
+use crate::presets;
+use crate::render::{option_letter, strip_option_prefix};
+use anyhow::{anyhow, Context, Result};
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::sync::Arc;
+
+/// Largest XML part or image read from one document (256 MiB)
+pub const MAX_PART_BYTES: u64 = 256 << 20;
+
+/// Deepest element nesting accepted in `word/document.xml`
+///
+/// XML parts are parsed and walked recursively; real documents stay far
+/// below this, even with tables nested in tables.
+pub const MAX_NESTING: usize = 128;
+
+/// A Word document converted to Markdown
+#[derive(Debug, Clone, Default)]
+pub struct DocxDocument {
+    /// Markdown rendering of the document body
+    pub markdown: String,
+    /// Embedded images referenced from the Markdown, as `(path, bytes)`
+    pub images: Vec<(String, Vec<u8>)>,
+    /// Parts of the document that could not be converted
+    pub warnings: Vec<String>,
+}
+
+/// Parser options for converted documents
+///
+/// Word question banks are usually numbered by hand rather than built from
+/// headings, so this is the `generic-numbered` preset; auto-numbered
+/// questions become headings and parse the same way.
+pub fn default_options() -> ParserOptions {
+    presets::preset("generic-numbered").unwrap_or_default()
+}
+
+/// Converts Word documents and parses them into questions
+#[derive(Debug, Clone)]
+pub struct DocxParser {
+    options: ParserOptions,
+}
+
+impl DocxParser {
+    /// Create a parser using [`default_options`]
+    pub fn new() -> Self {
+        Self {
+            options: default_options(),
+        }
+    }
+
+    /// Use these parser options for the converted Markdown
+    pub fn with_options(options: ParserOptions) -> Self {
+        Self { options }
+    }
+
+    /// Parse a document into questions, ignoring its images
+    pub fn parse(&self, data: &[u8]) -> Result<Vec<Question>> {
+        let document = convert(data, "media")?;
+        Ok(parse_markdown_with_options(&document.markdown, &self.options)?)
+    }
+}
+
+impl Default for DocxParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert a document to Markdown
+///
+/// Images are written as `media_dir/name` references and returned alongside,
+/// so the caller can place them where those references resolve.
+pub fn convert(data: &[u8], media_dir: &str) -> Result<DocxDocument> {
+    let mut archive = ::zip::ZipArchive::new(Cursor::new(data)).context("not a Word document")?;
+
+    let body = read_part(&mut archive, "word/document.xml")?
+        .ok_or_else(|| anyhow!("not a Word document: word/document.xml is missing"))?;
+    let rels = read_part(&mut archive, "word/_rels/document.xml.rels")?.unwrap_or_default();
+    let styles = read_part(&mut archive, "word/styles.xml")?.unwrap_or_default();
+    let numbering = read_part(&mut archive, "word/numbering.xml")?.unwrap_or_default();
+
+    let mut converter = Converter {
+        relationships: relationships(&rels)?,
+        headings: heading_styles(&styles)?,
+        numbering: Numbering::parse(&numbering)?,
+        counters: HashMap::new(),
+        media_dir: media_dir.trim_end_matches('/').to_string(),
+        images: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    let document = parse_xml(&body, "word/document.xml")?;
+    let mut paragraphs = Vec::new();
+    if let Some(body) = document.root_element().children().find(|n| is(n, "body")) {
+        converter.block(body, &mut paragraphs);
+    }
+
+    let mut images = Vec::with_capacity(converter.images.len());
+    for (target, name) in &converter.images {
+        match read_binary(&mut archive, target) {
+            Ok(Some(bytes)) => images.push((name.clone(), bytes)),
+            Ok(None) => converter.warnings.push(format!("image {} is missing from the document", target)),
+            Err(e) => converter.warnings.push(format!("image {} could not be read: {}", target, e)),
+        }
+    }
+
+    Ok(DocxDocument {
+        markdown: paragraphs.join("\n\n"),
+        images,
+        warnings: converter.warnings,
+    })
+}
+
+/// Read a part as text, or `None` if the document has no such part
+/// Parse an XML part, refusing pathologically deep nesting first
+///
+/// Both roxmltree and the converter recurse per element, so a crafted part
+/// could otherwise overflow the stack.
+fn parse_xml<'input>(xml: &'input str, part: &str) -> Result<Document<'input>> {
+    if nesting(xml) > MAX_NESTING {
+        anyhow::bail!("invalid {}: elements nested deeper than {} levels", part, MAX_NESTING);
+    }
+    Document::parse(xml).with_context(|| format!("invalid {}", part))
+}
+
+/// Deepest element nesting of an XML text, estimated without parsing it
+///
+/// Markup inside comments and CDATA is counted too, which can only
+/// overestimate.
+fn nesting(xml: &str) -> usize {
+    let bytes = xml.as_bytes();
+    let (mut depth, mut deepest) = (0usize, 0);
+    let mut pos = 0;
+    while let Some(offset) = bytes[pos..].iter().position(|&b| b == b'<') {
+        let start = pos + offset;
+        let end = bytes[start..].iter().position(|&b| b == b'>').map_or(bytes.len(), |e| start + e);
+        match bytes.get(start + 1) {
+            Some(b'/') => depth = depth.saturating_sub(1),
+            Some(b'?' | b'!') => {}
+            _ if end > start + 1 && bytes.get(end - 1) == Some(&b'/') => {}
+            _ => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+        }
+        pos = (end + 1).min(bytes.len());
+    }
+    deepest
+}
+
+fn read_part(archive: &mut ::zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<String>> {
+    Ok(read_binary(archive, name)?.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn read_binary(archive: &mut ::zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<Vec<u8>>> {
+    let file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(::zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut bytes = Vec::new();
+    file.take(MAX_PART_BYTES + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_PART_BYTES {
+        return Err(anyhow!("{} is larger than {} bytes", name, MAX_PART_BYTES));
+    }
+    Ok(Some(bytes))
+}
+
+/// Whether `node` is an element with local name `name`
+fn is(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+/// Attribute by local name, whatever its namespace prefix
+fn attr<'a>(node: &Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(|a| a.name() == name).map(|a| a.value())
+}
+
+/// First child element with local name `name`
+fn child<'a, 'input>(node: &Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| is(n, name))
+}
+
+/// `w:val` of the child element `name`
+fn child_val<'a>(node: &Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|n| attr(&n, "val"))
+}
+
+/// Image relationships: id -> part path inside the package
+fn relationships(xml: &str) -> Result<HashMap<String, String>> {
+    if xml.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let document = parse_xml(xml, "document relationships")?;
+    Ok(document
+        .root_element()
+        .children()
+        .filter(|n| is(n, "Relationship") && attr(n, "TargetMode") != Some("External"))
+        .filter_map(|n| Some((attr(&n, "Id")?.to_string(), part_path(attr(&n, "Target")?))))
+        .collect())
+}
+
+/// Resolve a relationship target against `word/`
+fn part_path(target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut segments = vec!["word"];
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Heading level of each paragraph style, from its name ("heading 2")
+///
+/// Localized Word versions use numeric style ids, so ids alone are not
+/// enough; `HeadingN` ids are still accepted without a styles part.
+fn heading_styles(xml: &str) -> Result<HashMap<String, usize>> {
+    let mut headings = HashMap::new();
+    if xml.is_empty() {
+        return Ok(headings);
+    }
+    let document = parse_xml(xml, "word/styles.xml")?;
+    for style in document.root_element().children().filter(|n| is(n, "style")) {
+        let (Some(id), Some(name)) = (attr(&style, "styleId"), child_val(&style, "name")) else {
+            continue;
+        };
+        let name = name.to_ascii_lowercase();
+        let level = match name.strip_prefix("heading ") {
+            Some(level) => level.parse().ok(),
+            None => (name == "title").then_some(1),
+        };
+        if let Some(level) = level {
+            headings.insert(id.to_string(), level);
+        }
+    }
+    Ok(headings)
+}
+
+/// One level of a numbering definition
+#[derive(Debug, Clone)]
+struct Level {
+    format: String,
+    text: String,
+    start: u32,
+}
+
+/// Numbering definitions: numId -> levels by ilvl
+#[derive(Debug, Default)]
+struct Numbering {
+    lists: HashMap<String, HashMap<u32, Level>>,
+}
+
+impl Numbering {
+    fn parse(xml: &str) -> Result<Self> {
+        if xml.is_empty() {
+            return Ok(Self::default());
+        }
+        let document = parse_xml(xml, "word/numbering.xml")?;
+        let root = document.root_element();
+
+        let mut abstracts = HashMap::new();
+        for abstract_num in root.children().filter(|n| is(n, "abstractNum")) {
+            let Some(id) = attr(&abstract_num, "abstractNumId") else {
+                continue;
+            };
+            let levels: HashMap<u32, Level> = abstract_num
+                .children()
+                .filter(|n| is(n, "lvl"))
+                .filter_map(|lvl| {
+                    let ilvl = attr(&lvl, "ilvl")?.parse().ok()?;
+                    let level = Level {
+                        format: child_val(&lvl, "numFmt").unwrap_or("decimal").to_string(),
+                        text: child_val(&lvl, "lvlText").unwrap_or_default().to_string(),
+                        start: child_val(&lvl, "start").and_then(|s| s.parse().ok()).unwrap_or(1),
+                    };
+                    Some((ilvl, level))
+                })
+                .collect();
+            abstracts.insert(id.to_string(), levels);
+        }
+
+        let lists = root
+            .children()
+            .filter(|n| is(n, "num"))
+            .filter_map(|num| {
+                let id = attr(&num, "numId")?;
+                let levels = abstracts.get(child_val(&num, "abstractNumId")?)?;
+                Some((id.to_string(), levels.clone()))
+            })
+            .collect();
+        Ok(Self { lists })
+    }
+}
+
+/// Walks the document body, emitting one Markdown block per paragraph
+struct Converter {
+    relationships: HashMap<String, String>,
+    headings: HashMap<String, usize>,
+    numbering: Numbering,
+    /// Current count of each (numId, ilvl)
+    counters: HashMap<(String, u32), u32>,
+    media_dir: String,
+    /// Referenced images: part path -> name under `media_dir`
+    images: Vec<(String, String)>,
+    warnings: Vec<String>,
+}
+
+impl Converter {
+    /// Paragraphs of a body, table cell or content control, in order
+    fn block(&mut self, node: Node, out: &mut Vec<String>) {
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "p" => {
+                    if let Some(paragraph) = self.paragraph(child) {
+                        out.push(paragraph);
+                    }
+                }
+                "sectPr" => {}
+                _ => self.block(child, out),
+            }
+        }
+    }
+
+    fn paragraph(&mut self, p: Node) -> Option<String> {
+        let mut text = String::new();
+        self.inline(p, &mut text);
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        let properties = child(&p, "pPr");
+        let style = properties.as_ref().and_then(|pr| child_val(pr, "pStyle"));
+        let heading = style.and_then(|style| {
+            self.headings
+                .get(style)
+                .copied()
+                .or_else(|| style.strip_prefix("Heading").and_then(|l| l.parse().ok()))
+        });
+        if let Some(level) = heading {
+            return Some(format!("{} {}", "#".repeat(level.clamp(1, 6)), text));
+        }
+
+        let numbered = properties.as_ref().and_then(|pr| child(pr, "numPr")).and_then(|num_pr| {
+            let id = child_val(&num_pr, "numId")?.to_string();
+            let ilvl = child_val(&num_pr, "ilvl").and_then(|l| l.parse().ok()).unwrap_or(0);
+            (id != "0").then_some((id, ilvl))
+        });
+        let Some((id, ilvl)) = numbered else {
+            return Some(text.to_string());
+        };
+        let Some(levels) = self.numbering.lists.get(&id) else {
+            return Some(text.to_string());
+        };
+        let Some(level) = levels.get(&ilvl).cloned() else {
+            return Some(text.to_string());
+        };
+
+        // Count this level and restart the ones below it
+        let count = {
+            let counter = self.counters.entry((id.clone(), ilvl)).or_insert(level.start - 1);
+            *counter += 1;
+            *counter
+        };
+        self.counters.retain(|(list, l), _| list != &id || *l <= ilvl);
+
+        Some(match level.format.as_str() {
+            "bullet" => format!("* {}", text),
+            format if ilvl == 0 && format.starts_with("decimal") => format!("# {}", text),
+            "upperLetter" | "lowerLetter" => {
+                format!("* {} {}", self.label(&id, levels, &level, ilvl, count), text)
+            }
+            _ => format!("{}{}", self.label(&id, levels, &level, ilvl, count), text),
+        })
+    }
+
+    /// Render a level's text template ("%1.", "(%2)") with current counts
+    fn label(&self, id: &str, levels: &HashMap<u32, Level>, level: &Level, ilvl: u32, count: u32) -> String {
+        let mut label = level.text.clone();
+        for n in (0..=ilvl).rev() {
+            let placeholder = format!("%{}", n + 1);
+            if !label.contains(&placeholder) {
+                continue;
+            }
+            let value = if n == ilvl {
+                count
+            } else {
+                self.counters.get(&(id.to_string(), n)).copied().unwrap_or(1)
+            };
+            let format = levels.get(&n).map_or("decimal", |l| l.format.as_str());
+            label = label.replace(&placeholder, &format_number(value, format));
+        }
+        label
+    }
+
+    /// Text, breaks, images and equations of a paragraph
+    fn inline(&mut self, node: Node, out: &mut String) {
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "t" => out.push_str(&escape_markdown(child.text().unwrap_or_default())),
+                "tab" if !out.is_empty() => out.push(' '),
+                "br" | "cr" => out.push('\n'),
+                "oMathPara" => {
+                    let latex: Vec<_> = child.children().filter(|n| is(n, "oMath")).map(omml).collect();
+                    out.push_str(&format!("$${}$$", latex.join(" \\\\ ")));
+                }
+                "oMath" => out.push_str(&format!("${}$", omml(child))),
+                "blip" => self.image(attr(&child, "embed"), out),
+                "imagedata" => self.image(attr(&child, "id"), out),
+                // Deleted revisions, field codes and properties hold no visible text
+                "del" | "instrText" | "pPr" | "rPr" | "fallback" | "Fallback" => {}
+                _ => self.inline(child, out),
+            }
+        }
+    }
+
+    fn image(&mut self, id: Option<&str>, out: &mut String) {
+        let Some(target) = id.and_then(|id| self.relationships.get(id)) else {
+            self.warnings.push(format!("image relationship {} not found", id.unwrap_or("?")));
+            return;
+        };
+        let name = match self.images.iter().find(|(t, _)| t == target) {
+            Some((_, name)) => name.clone(),
+            None => {
+                let name = target.rsplit('/').next().unwrap_or(target).to_string();
+                self.images.push((target.clone(), name.clone()));
+                name
+            }
+        };
+        out.push_str(&format!("![]({}/{})", self.media_dir, name));
+    }
+}
+
+/// Format a list counter in a Word number format
+fn format_number(value: u32, format: &str) -> String {
+    let letter = |base: u8| {
+        // Word repeats the letter past Z: AA, BB, ...
+        let index = (value.max(1) - 1) as usize;
+        let c = (base + (index % 26) as u8) as char;
+        c.to_string().repeat(index / 26 + 1)
+    };
+    match format {
+        "upperLetter" => letter(b'A'),
+        "lowerLetter" => letter(b'a'),
+        "upperRoman" => roman(value),
+        "lowerRoman" => roman(value).to_lowercase(),
+        "chineseCounting" | "chineseCountingThousand" | "ideographTraditional" => chinese(value),
+        _ => value.to_string(),
+    }
+}
+
+fn roman(mut value: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut out = String::new();
+    for (n, numeral) in NUMERALS {
+        while value >= n {
+            out.push_str(numeral);
+            value -= n;
+        }
+    }
+    out
+}
+
+/// Chinese numerals up to 99, as used for section numbering (一、二、…)
+fn chinese(value: u32) -> String {
+    const DIGITS: [&str; 10] = ["零", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+    match value {
+        0..=9 => DIGITS[value as usize].to_string(),
+        10..=19 => format!("十{}", if value == 10 { "" } else { DIGITS[(value % 10) as usize] }),
+        20..=99 => format!(
+            "{}十{}",
+            DIGITS[(value / 10) as usize],
+            if value.is_multiple_of(10) { "" } else { DIGITS[(value % 10) as usize] }
+        ),
+        _ => value.to_string(),
+    }
+}
+
+/// Convert an OMML element to LaTeX
+///
+/// Covers what exam papers use: fractions, scripts, radicals, delimiters,
+/// n-ary operators, functions, limits, accents, bars and matrices. Unknown
+/// elements contribute their content.
+fn omml(node: Node) -> String {
+    let part = |name: &str| child(&node, name).map(omml).unwrap_or_default();
+    let properties = |name: &str| child(&node, name);
+
+    match node.tag_name().name() {
+        "r" => node
+            .children()
+            .filter(|n| is(n, "t"))
+            .map(|t| latex_text(t.text().unwrap_or_default()))
+            .collect(),
+        "f" => format!("\\frac{{{}}}{{{}}}", part("num"), part("den")),
+        "sSup" => format!("{{{}}}^{{{}}}", part("e"), part("sup")),
+        "sSub" => format!("{{{}}}_{{{}}}", part("e"), part("sub")),
+        "sSubSup" => format!("{{{}}}_{{{}}}^{{{}}}", part("e"), part("sub"), part("sup")),
+        "sPre" => format!("{{}}_{{{}}}^{{{}}}{{{}}}", part("sub"), part("sup"), part("e")),
+        "rad" => {
+            let degree = part("deg");
+            if degree.is_empty() {
+                format!("\\sqrt{{{}}}", part("e"))
+            } else {
+                format!("\\sqrt[{}]{{{}}}", degree, part("e"))
+            }
+        }
+        "d" => {
+            let pr = properties("dPr");
+            let chr = |name: &str, default: &'static str| {
+                pr.as_ref()
+                    .and_then(|pr| child(pr, name))
+                    .map_or(default.to_string(), |c| attr(&c, "val").unwrap_or_default().to_string())
+            };
+            let (open, close, separator) = (chr("begChr", "("), chr("endChr", ")"), chr("sepChr", "|"));
+            let items: Vec<_> = node.children().filter(|n| is(n, "e")).map(omml).collect();
+            format!(
+                "\\left{}{}\\right{}",
+                delimiter(&open),
+                items.join(&latex_text(&separator)),
+                delimiter(&close)
+            )
+        }
+        "nary" => {
+            let operator = properties("naryPr")
+                .and_then(|pr| child_val(&pr, "chr").map(str::to_string))
+                .unwrap_or_else(|| "∫".to_string());
+            let mut out = latex_text(&operator);
+            let (sub, sup) = (part("sub"), part("sup"));
+            if !sub.is_empty() {
+                out.push_str(&format!("_{{{}}}", sub));
+            }
+            if !sup.is_empty() {
+                out.push_str(&format!("^{{{}}}", sup));
+            }
+            format!("{}{{{}}}", out, part("e"))
+        }
+        "func" => {
+            let name = part("fName");
+            let name = match name.as_str() {
+                "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "log" | "ln" | "lg" | "exp" | "lim" | "max"
+                | "min" | "arcsin" | "arccos" | "arctan" | "sinh" | "cosh" | "tanh" => format!("\\{}", name),
+                _ => name,
+            };
+            format!("{}{{{}}}", name, part("e"))
+        }
+        "limLow" => format!("{}_{{{}}}", limit_name(&part("e")), part("lim")),
+        "limUpp" => format!("{}^{{{}}}", limit_name(&part("e")), part("lim")),
+        "bar" => {
+            let bottom = properties("barPr").and_then(|pr| child_val(&pr, "pos")) == Some("bot");
+            format!("\\{}{{{}}}", if bottom { "underline" } else { "overline" }, part("e"))
+        }
+        "acc" => {
+            let chr = properties("accPr").and_then(|pr| child_val(&pr, "chr").map(str::to_string));
+            let command = match chr.as_deref().and_then(|c| c.chars().next()) {
+                Some('\u{0303}') | Some('~') => "tilde",
+                Some('\u{20D7}') | Some('\u{2192}') => "vec",
+                Some('\u{0305}') | Some('\u{00AF}') => "bar",
+                Some('\u{0307}') | Some('\u{02D9}') => "dot",
+                _ => "hat",
+            };
+            format!("\\{}{{{}}}", command, part("e"))
+        }
+        "m" => {
+            let rows: Vec<_> = node
+                .children()
+                .filter(|n| is(n, "mr"))
+                .map(|row| row.children().filter(|n| is(n, "e")).map(omml).collect::<Vec<_>>().join(" & "))
+                .collect();
+            format!("\\begin{{matrix}}{}\\end{{matrix}}", rows.join(" \\\\ "))
+        }
+        "eqArr" => {
+            let rows: Vec<_> = node.children().filter(|n| is(n, "e")).map(omml).collect();
+            format!("\\begin{{aligned}}{}\\end{{aligned}}", rows.join(" \\\\ "))
+        }
+        name if name.ends_with("Pr") => String::new(),
+        _ => node.children().filter(Node::is_element).map(omml).collect(),
+    }
+}
+
+/// `lim` written as plain text in a limit becomes `\lim`
+fn limit_name(base: &str) -> String {
+    match base {
+        "lim" | "max" | "min" | "sup" | "inf" => format!("\\{}", base),
+        _ => base.to_string(),
+    }
+}
+
+/// LaTeX for a delimiter character ("" means none)
+fn delimiter(chr: &str) -> String {
+    match chr {
+        "" => ".".to_string(),
+        "{" => "\\{".to_string(),
+        "}" => "\\}".to_string(),
+        "〈" | "⟨" => "\\langle ".to_string(),
+        "〉" | "⟩" => "\\rangle ".to_string(),
+        "‖" => "\\|".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// LaTeX for equation text, mapping common symbols to commands
+fn latex_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        let command = match c {
+            '{' => "\\{",
+            '}' => "\\}",
+            '%' => "\\%",
+            '#' => "\\#",
+            '&' => "\\&",
+            '×' => "\\times ",
+            '÷' => "\\div ",
+            '±' => "\\pm ",
+            '∓' => "\\mp ",
+            '·' | '⋅' => "\\cdot ",
+            '≤' => "\\le ",
+            '≥' => "\\ge ",
+            '≠' => "\\ne ",
+            '≈' => "\\approx ",
+            '≡' => "\\equiv ",
+            '∞' => "\\infty ",
+            '→' => "\\to ",
+            '∈' => "\\in ",
+            '∉' => "\\notin ",
+            '⊂' => "\\subset ",
+            '⊆' => "\\subseteq ",
+            '∪' => "\\cup ",
+            '∩' => "\\cap ",
+            '∅' => "\\emptyset ",
+            '∀' => "\\forall ",
+            '∃' => "\\exists ",
+            '∠' => "\\angle ",
+            '⊥' => "\\perp ",
+            '∥' => "\\parallel ",
+            '°' => "^\\circ ",
+            '∑' => "\\sum",
+            '∏' => "\\prod",
+            '∫' => "\\int",
+            '∬' => "\\iint",
+            '∮' => "\\oint",
+            'α' => "\\alpha ",
+            'β' => "\\beta ",
+            'γ' => "\\gamma ",
+            'δ' => "\\delta ",
+            'ε' => "\\varepsilon ",
+            'θ' => "\\theta ",
+            'λ' => "\\lambda ",
+            'μ' => "\\mu ",
+            'π' => "\\pi ",
+            'ρ' => "\\rho ",
+            'σ' => "\\sigma ",
+            'φ' => "\\varphi ",
+            'ω' => "\\omega ",
+            'Δ' => "\\Delta ",
+            'Ω' => "\\Omega ",
+            _ => {
+                out.push(c);
+                continue;
+            }
+        };
+        out.push_str(command);
+    }
+    out
+}
+
+/// Title used when a paper has none
+pub const DEFAULT_TITLE: &str = "Exam";
+
+/// Namespaces declared on the root of the written document
+const WRITE_NS: &str = r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture""#;
+
+/// Widest image on the page: 6 inches in EMU
+const MAX_IMAGE_EMU: u64 = 6 * 914_400;
+
+/// EMU per pixel at 96 DPI
+const EMU_PER_PIXEL: u64 = 9_525;
+
+/// Image formats Word displays
+const PAPER_IMAGE_FORMATS: &[&str] = &["png", "jpg", "gif", "bmp"];
+
+/// How an exported paper is laid out
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PaperLayout {
+    /// Title at the top of the first page
+    pub title: Option<String>,
+    /// Instructions printed under the title
+    pub instructions: Option<String>,
+    /// Append the answers on a new page
+    pub answer_key: bool,
+    /// Include each question's analysis in the answer key
+    pub include_analysis: bool,
+    /// Blank lines left for writing under fill-in and open questions
+    pub answer_lines: usize,
+    /// Body text size in points
+    pub font_size: f32,
+}
+
+impl Default for PaperLayout {
+    fn default() -> Self {
+        Self {
+            title: None,
+            instructions: None,
+            answer_key: false,
+            include_analysis: false,
+            answer_lines: 3,
+            font_size: 11.0,
+        }
+    }
+}
+
+/// A written exam paper
+#[derive(Debug)]
+pub struct DocxPaper {
+    /// The `.docx` file
+    pub data: Vec<u8>,
+    /// Images that could not be embedded
+    pub warnings: Vec<String>,
+}
+
+/// Writes questions as a Word exam paper
+///
+/// Questions are numbered from 1 with their options lettered below them.
+/// Numbers and letters are typed rather than Word list numbering, so the
+/// paper prints the same everywhere. `$…$` formulas are set in Cambria Math
+/// as LaTeX source. Local images are embedded from the [`MediaStore`];
+/// remote images are printed as their URL.
+#[derive(Default)]
+pub struct DocxWriter {
+    layout: PaperLayout,
+    media_store: Option<Arc<dyn MediaStore>>,
+}
+
+/// An image embedded in the paper
+struct PaperImage {
+    rel_id: String,
+    name: String,
+    width: u64,
+    height: u64,
+}
+
+impl DocxWriter {
+    /// Writer using `layout`
+    pub fn new(layout: PaperLayout) -> Self {
+        Self {
+            layout,
+            media_store: None,
+        }
+    }
+
+    /// Embed local images from `store`
+    pub fn with_media_store(mut self, store: Arc<dyn MediaStore>) -> Self {
+        self.media_store = Some(store);
+        self
+    }
+
+    /// Write `questions` as a paper
+    pub async fn write(&self, questions: &[Question]) -> Result<DocxPaper> {
+        let mut warnings = Vec::new();
+        let mut media: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut embedded: HashMap<String, usize> = HashMap::new();
+        let mut body = String::new();
+        let mut drawings = 0;
+
+        let title = self.layout.title.as_deref().filter(|t| !t.trim().is_empty()).unwrap_or(DEFAULT_TITLE);
+        body.push_str(&paragraph(Some("Title"), "", &runs(title)));
+        if let Some(instructions) = self.layout.instructions.as_deref().filter(|t| !t.trim().is_empty()) {
+            body.push_str(&paragraph(Some("Subtitle"), "", &runs(instructions)));
+        }
+
+        for (question, number) in questions.iter().zip(1..) {
+            // Keep the stem on the page with its options
+            body.push_str(&paragraph(
+                Some("Question"),
+                "<w:keepNext/>",
+                &format!("{}{}", run(&format!("{}. ", number)), runs(&export_text(question, &question.stem))),
+            ));
+
+            for image in &question.images {
+                match self.image(question, image, &mut media, &mut embedded, &mut warnings).await {
+                    Some(image) => {
+                        drawings += 1;
+                        body.push_str(&paragraph(None, r#"<w:ind w:left="360"/>"#, &drawing(&image, drawings)));
+                    }
+                    None => {
+                        if let ImageRef::Remote { url, .. } = image {
+                            body.push_str(&paragraph(None, r#"<w:ind w:left="360"/>"#, &run(&format!("[image: {}]", url))));
+                        }
+                    }
+                }
+            }
+
+            for (idx, option) in question.options.iter().enumerate() {
+                let label = format!("{}. ", option_letter(idx));
+                body.push_str(&paragraph(
+                    Some("Option"),
+                    "",
+                    &format!("{}{}", run(&label), runs(&export_text(question, strip_option_prefix(&option.content, idx)))),
+                ));
+            }
+
+            if question.options.is_empty() && matches!(question.qtype, QuestionType::FillInTheBlank | QuestionType::Subjective) {
+                for _ in 0..self.layout.answer_lines {
+                    body.push_str(&paragraph(None, "", ""));
+                }
+            }
+        }
+
+        if self.layout.answer_key {
+            body.push_str(&paragraph(Some("Heading1"), "<w:pageBreakBefore/>", &run("Answer Key")));
+            for (question, number) in questions.iter().zip(1..) {
+                let answer = question.answer.as_deref().filter(|a| !a.trim().is_empty()).unwrap_or("—");
+                body.push_str(&paragraph(
+                    None,
+                    "",
+                    &format!("{}{}", run(&format!("{}. ", number)), runs(answer)),
+                ));
+                if self.layout.include_analysis {
+                    if let Some(analysis) = question.analysis.as_deref().filter(|a| !a.trim().is_empty()) {
+                        body.push_str(&paragraph(Some("Option"), "", &runs(&export_text(question, analysis))));
+                    }
+                }
+            }
+        }
+
+        let data = write_package(&body, &media, self.layout.font_size)?;
+        Ok(DocxPaper { data, warnings })
+    }
+
+    /// Embed a local image, reusing one already embedded with the same hash
+    async fn image(
+        &self,
+        question: &Question,
+        image: &ImageRef,
+        media: &mut Vec<(String, Vec<u8>)>,
+        embedded: &mut HashMap<String, usize>,
+        warnings: &mut Vec<String>,
+    ) -> Option<PaperImage> {
+        let ImageRef::Local { hash, original_path, .. } = image else {
+            return None;
+        };
+        let data = match &self.media_store {
+            Some(store) if !hash.is_empty() => store.get(hash).await,
+            _ => Ok(None),
+        };
+        let data = match data {
+            Ok(Some(data)) => data,
+            Ok(None) => {
+                warnings.push(format!("{}: image {} is not in the media store", question.id, original_path));
+                return None;
+            }
+            Err(e) => {
+                warnings.push(format!("{}: failed to read image {}: {}", question.id, original_path, e));
+                return None;
+            }
+        };
+        let info = match validate_image(&data) {
+            Ok(info) if PAPER_IMAGE_FORMATS.contains(&info.format) => info,
+            _ => {
+                warnings.push(format!("{}: image {} cannot be shown in Word", question.id, original_path));
+                return None;
+            }
+        };
+
+        let index = match embedded.get(hash) {
+            Some(&index) => index,
+            None => {
+                media.push((format!("image{}.{}", media.len() + 1, info.format), data));
+                embedded.insert(hash.clone(), media.len());
+                media.len()
+            }
+        };
+        let (width, height) = match (info.width, info.height) {
+            (Some(w), Some(h)) if w > 0 && h > 0 => (u64::from(w) * EMU_PER_PIXEL, u64::from(h) * EMU_PER_PIXEL),
+            _ => (MAX_IMAGE_EMU / 2, MAX_IMAGE_EMU / 3),
+        };
+        // Shrink wide images to the text width, keeping the aspect ratio
+        let (width, height) = if width > MAX_IMAGE_EMU {
+            (MAX_IMAGE_EMU, height * MAX_IMAGE_EMU / width)
+        } else {
+            (width, height)
+        };
+        Some(PaperImage {
+            rel_id: format!("rIdImage{}", index),
+            name: media[index - 1].0.clone(),
+            width,
+            height,
+        })
+    }
+}
+
+/// Escape text for XML, dropping characters XML cannot hold
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A paragraph with an optional style and extra paragraph properties
+fn paragraph(style: Option<&str>, properties: &str, content: &str) -> String {
+    let style = style.map(|s| format!(r#"<w:pStyle w:val="{}"/>"#, s)).unwrap_or_default();
+    format!("<w:p><w:pPr>{}{}</w:pPr>{}</w:p>", style, properties, content)
+}
+
+/// A plain text run, with line breaks kept
+fn run(text: &str) -> String {
+    styled_run(text, "")
+}
+
+fn styled_run(text: &str, properties: &str) -> String {
+    let content = text
+        .split('\n')
+        .map(|line| format!(r#"<w:t xml:space="preserve">{}</w:t>"#, xml_escape(line)))
+        .collect::<Vec<_>>()
+        .join("<w:br/>");
+    if properties.is_empty() {
+        format!("<w:r>{}</w:r>", content)
+    } else {
+        format!("<w:r><w:rPr>{}</w:rPr>{}</w:r>", properties, content)
+    }
+}
+
+/// Runs for text, with `$…$` formulas set in Cambria Math
+fn runs(text: &str) -> String {
+    split_math(text)
+        .into_iter()
+        .map(|(segment, is_math)| {
+            if is_math {
+                styled_run(
+                    segment.trim_matches('$'),
+                    r#"<w:rFonts w:ascii="Cambria Math" w:hAnsi="Cambria Math"/><w:i/>"#,
+                )
+            } else {
+                run(segment)
+            }
+        })
+        .collect()
+}
+
+/// An inline picture; `id` must be unique within the document
+fn drawing(image: &PaperImage, id: usize) -> String {
+    format!(
+        r#"<w:r><w:drawing><wp:inline><wp:extent cx="{w}" cy="{h}"/><wp:docPr id="{id}" name="{name}"/><a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:pic><pic:nvPicPr><pic:cNvPr id="{id}" name="{name}"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip r:embed="{rel}"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill><pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="{w}" cy="{h}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></pic:spPr></pic:pic></a:graphicData></a:graphic></wp:inline></w:drawing></w:r>"#,
+        w = image.width,
+        h = image.height,
+        id = id,
+        name = image.name,
+        rel = image.rel_id,
+    )
+}
+
+/// Zip the document, styles and images into a `.docx` package
+fn write_package(body: &str, media: &[(String, Vec<u8>)], font_size: f32) -> Result<Vec<u8>> {
+    // Word sizes text in half-points
+    let half_points = (font_size.clamp(6.0, 72.0) * 2.0).round() as u32;
+
+    let image_types: String = PAPER_IMAGE_FORMATS
+        .iter()
+        .filter(|ext| media.iter().any(|(name, _)| name.ends_with(&format!(".{}", ext))))
+        .map(|ext| {
+            let mime = if *ext == "jpg" { "jpeg" } else { ext };
+            format!(r#"<Default Extension="{}" ContentType="image/{}"/>"#, ext, mime)
+        })
+        .collect();
+    let content_types = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/>{}<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/></Types>"#,
+        image_types
+    );
+    let package_rels = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#;
+    let image_rels: String = media
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| {
+            format!(
+                r#"<Relationship Id="rIdImage{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="media/{}"/>"#,
+                i + 1,
+                name
+            )
+        })
+        .collect();
+    let document_rels = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rIdStyles" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>{}</Relationships>"#,
+        image_rels
+    );
+    let styles = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:styles {ns}><w:docDefaults><w:rPrDefault><w:rPr><w:sz w:val="{size}"/><w:szCs w:val="{size}"/></w:rPr></w:rPrDefault><w:pPrDefault><w:pPr><w:spacing w:after="80"/></w:pPr></w:pPrDefault></w:docDefaults><w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/></w:style><w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:pPr><w:jc w:val="center"/><w:spacing w:after="240"/></w:pPr><w:rPr><w:b/><w:sz w:val="{title}"/></w:rPr></w:style><w:style w:type="paragraph" w:styleId="Subtitle"><w:name w:val="Subtitle"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="240"/></w:pPr><w:rPr><w:i/></w:rPr></w:style><w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="160"/></w:pPr><w:rPr><w:b/><w:sz w:val="{heading}"/></w:rPr></w:style><w:style w:type="paragraph" w:styleId="Question"><w:name w:val="Question"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:before="200"/><w:ind w:left="360" w:hanging="360"/></w:pPr></w:style><w:style w:type="paragraph" w:styleId="Option"><w:name w:val="Option"/><w:basedOn w:val="Normal"/><w:pPr><w:ind w:left="720" w:hanging="360"/></w:pPr></w:style></w:styles>"#,
+        ns = WRITE_NS,
+        size = half_points,
+        title = half_points * 2,
+        heading = half_points * 3 / 2,
+    );
+    let document = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:document {}><w:body>{}<w:sectPr><w:pgSz w:w="11906" w:h="16838"/><w:pgMar w:top="1440" w:right="1440" w:bottom="1440" w:left="1440" w:header="720" w:footer="720" w:gutter="0"/></w:sectPr></w:body></w:document>"#,
+        WRITE_NS, body
+    );
+
+    let mut data = Vec::new();
+    {
+        let mut writer = ::zip::ZipWriter::new(Cursor::new(&mut data));
+        let options = ::zip::write::SimpleFileOptions::default();
+        for (name, content) in [
+            ("[Content_Types].xml", content_types.as_bytes()),
+            ("_rels/.rels", package_rels.as_bytes()),
+            ("word/_rels/document.xml.rels", document_rels.as_bytes()),
+            ("word/styles.xml", styles.as_bytes()),
+            ("word/document.xml", document.as_bytes()),
+        ] {
+            writer.start_file(name, options)?;
+            writer.write_all(content)?;
+        }
+        for (name, content) in media {
+            writer.start_file(format!("word/media/{}", name), options)?;
+            writer.write_all(content)?;
+        }
+        writer.finish()?;
+    }
+    Ok(data)
+}
+
+/// Build a minimal `.docx` package in memory
+#[cfg(test)]
+pub(crate) fn build_docx(body: &str, numbering: Option<&str>, image: Option<&[u8]>) -> Vec<u8> {
+    use std::io::Write;
+
+    const NS: &str = r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:m="http://schemas.openxmlformats.org/officeDocument/2006/math" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main""#;
+
+    let mut data = Vec::new();
+    {
+        let mut writer = ::zip::ZipWriter::new(Cursor::new(&mut data));
+        let options = ::zip::write::SimpleFileOptions::default();
+        let mut add = |name: &str, content: &[u8]| {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content).unwrap();
+        };
+        add(
+            "word/document.xml",
+            format!(r#"<?xml version="1.0"?><w:document {}><w:body>{}</w:body></w:document>"#, NS, body).as_bytes(),
+        );
+        add(
+            "word/styles.xml",
+            format!(
+                r#"<w:styles {}><w:style w:type="paragraph" w:styleId="1"><w:name w:val="heading 1"/></w:style></w:styles>"#,
+                NS
+            )
+            .as_bytes(),
+        );
+        if let Some(numbering) = numbering {
+            add("word/numbering.xml", format!("<w:numbering {}>{}</w:numbering>", NS, numbering).as_bytes());
+        }
+        add(
+            "word/_rels/document.xml.rels",
+            br#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId5" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="media/image1.png"/></Relationships>"#,
+        );
+        if let Some(image) = image {
+            add("word/media/image1.png", image);
+        }
+        writer.finish().unwrap();
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Questions numbered 1., 2. with options A., B. under them
+    const NUMBERING: &str = r#"
+        <w:abstractNum w:abstractNumId="0">
+          <w:lvl w:ilvl="0"><w:start w:val="1"/><w:numFmt w:val="decimal"/><w:lvlText w:val="%1."/></w:lvl>
+          <w:lvl w:ilvl="1"><w:start w:val="1"/><w:numFmt w:val="upperLetter"/><w:lvlText w:val="%2."/></w:lvl>
+        </w:abstractNum>
+        <w:abstractNum w:abstractNumId="1">
+          <w:lvl w:ilvl="0"><w:numFmt w:val="chineseCounting"/><w:lvlText w:val="%1、"/></w:lvl>
+        </w:abstractNum>
+        <w:num w:numId="1"><w:abstractNumId w:val="0"/></w:num>
+        <w:num w:numId="2"><w:abstractNumId w:val="1"/></w:num>"#;
+
+    fn numbered(text: &str, num_id: u32, ilvl: u32) -> String {
+        format!(
+            r#"<w:p><w:pPr><w:numPr><w:ilvl w:val="{}"/><w:numId w:val="{}"/></w:numPr></w:pPr><w:r><w:t>{}</w:t></w:r></w:p>"#,
+            ilvl, num_id, text
+        )
+    }
+
+    #[test]
+    fn test_convert_numbered_document() {
+        let body = [
+            r#"<w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:t>期中考试</w:t></w:r></w:p>"#.to_string(),
+            numbered("选择题", 2, 0),
+            numbered("Which is prime?", 1, 0),
+            numbered("4", 1, 1),
+            numbered("7", 1, 1),
+            r#"<w:p><w:r><w:t>答案：B</w:t></w:r></w:p>"#.to_string(),
+            numbered("Pick a_b", 1, 0),
+            numbered("x", 1, 1),
+            r#"<w:p><w:r><w:t xml:space="preserve">  </w:t></w:r></w:p>"#.to_string(),
+        ]
+        .concat();
+        let document = convert(&build_docx(&body, Some(NUMBERING), None), "media").unwrap();
+
+        assert_eq!(
+            document.markdown,
+            "# 期中考试\n\n一、选择题\n\n# Which is prime?\n\n* A. 4\n\n* B. 7\n\n答案：B\n\n# Pick a\\_b\n\n* A. x"
+        );
+
+        let questions = DocxParser::with_options(ParserOptions::default())
+            .parse(&build_docx(&body, Some(NUMBERING), None))
+            .unwrap();
+        let prime = questions.iter().find(|q| q.stem == "Which is prime?").unwrap();
+        assert_eq!(prime.options.len(), 2);
+        assert_eq!(prime.answer.as_deref(), Some("B"));
+        assert!(questions.iter().any(|q| q.stem == "Pick a_b"));
+    }
+
+    #[test]
+    fn test_convert_equations_and_images() {
+        let equation = r#"<w:p><w:r><w:t xml:space="preserve">Solve </w:t></w:r><m:oMath>
+            <m:f><m:num><m:r><m:t>1</m:t></m:r></m:num><m:den><m:sSup><m:e><m:r><m:t>x</m:t></m:r></m:e><m:sup><m:r><m:t>2</m:t></m:r></m:sup></m:sSup></m:den></m:f>
+            <m:r><m:t>≤</m:t></m:r>
+            <m:rad><m:radPr><m:degHide m:val="1"/></m:radPr><m:deg/><m:e><m:r><m:t>π</m:t></m:r></m:e></m:rad>
+            <m:d><m:e><m:r><m:t>a</m:t></m:r></m:e><m:e><m:r><m:t>b</m:t></m:r></m:e></m:d>
+            <m:nary><m:naryPr><m:chr m:val="∑"/></m:naryPr><m:sub><m:r><m:t>i=1</m:t></m:r></m:sub><m:sup><m:r><m:t>n</m:t></m:r></m:sup><m:e><m:r><m:t>i</m:t></m:r></m:e></m:nary>
+            </m:oMath></w:p>"#;
+        let picture = r#"<w:p><w:r><w:t>Shape?</w:t></w:r><w:r><w:drawing><a:graphic><a:graphicData><a:blip r:embed="rId5"/></a:graphicData></a:graphic></w:drawing></w:r></w:p>"#;
+        let png = crate::media::test_png(1, 1);
+        let data = build_docx(&format!("{}{}", equation, picture), None, Some(&png));
+
+        let document = convert(&data, "exam.docx.media").unwrap();
+        assert_eq!(
+            document.markdown,
+            "Solve $\\frac{1}{{x}^{2}}\\le \\sqrt{\\pi }\\left(a|b\\right)\\sum_{i=1}^{n}{i}$\n\n\
+             Shape?![](exam.docx.media/image1.png)"
+        );
+        assert_eq!(document.images, [("image1.png".to_string(), png)]);
+        assert!(document.warnings.is_empty());
+
+        assert!(convert(b"not a zip", "media").is_err());
+        let nested = format!("{}<w:p/>{}", "<w:tbl><w:tr><w:tc>".repeat(50), "</w:tc></w:tr></w:tbl>".repeat(50));
+        let error = convert(&build_docx(&nested, None, None), "media").unwrap_err();
+        assert!(error.to_string().contains("nested deeper than 128 levels"), "{}", error);
+        assert_eq!(format_number(28, "upperLetter"), "BB");
+        assert_eq!(format_number(14, "upperRoman"), "XIV");
+        assert_eq!(format_number(21, "chineseCounting"), "二十一");
+    }
+
+    #[tokio::test]
+    async fn test_write_paper() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(crate::media::FsMediaStore::new(dir.path()).unwrap());
+        let png = crate::media::test_png(2000, 1000);
+        let stored = store.put(&png).await.unwrap();
+        let image = |hash: &str| ImageRef::Local {
+            hash: hash.to_string(),
+            original_path: "img/shape.png".to_string(),
+            stored_path: None,
+            alt: None,
+        };
+
+        let questions = vec![
+            Question {
+                qtype: QuestionType::Choice,
+                stem: "Which shape is <shown>?".to_string(),
+                options: ["A. Circle", "Square"]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, content)| crate::models::QuestionOption {
+                        content: content.to_string(),
+                        sort_order: i as i32,
+                        is_correct: i == 1,
+                    })
+                    .collect(),
+                images: vec![image(&stored.hash), image("ffff")],
+                answer: Some("B".to_string()),
+                analysis: Some("It has four equal sides.".to_string()),
+                ..Question::default()
+            },
+            Question {
+                qtype: QuestionType::FillInTheBlank,
+                stem: "Solve $x^2 = 4$ for positive x".to_string(),
+                answer: Some("2".to_string()),
+                ..Question::default()
+            },
+        ];
+        let layout = PaperLayout {
+            title: Some("Midterm".to_string()),
+            answer_key: true,
+            include_analysis: true,
+            ..PaperLayout::default()
+        };
+
+        let paper = DocxWriter::new(layout).with_media_store(store).write(&questions).await.unwrap();
+        assert_eq!(paper.warnings.len(), 1);
+        assert!(paper.warnings[0].contains("not in the media store"));
+
+        let mut archive = ::zip::ZipArchive::new(Cursor::new(paper.data.as_slice())).unwrap();
+        let document = read_part(&mut archive, "word/document.xml").unwrap().unwrap();
+        assert!(Document::parse(&document).is_ok());
+        // Scaled down to the 6 inch text width
+        assert!(document.contains(r#"<wp:extent cx="5486400" cy="2743200"/>"#));
+        assert!(document.contains(r#"<w:rFonts w:ascii="Cambria Math" w:hAnsi="Cambria Math"/><w:i/></w:rPr><w:t xml:space="preserve">x^2 = 4</w:t>"#));
+
+        let converted = convert(&paper.data, "media").unwrap();
+        assert_eq!(converted.images, [("image1.png".to_string(), png)]);
+        let lines: Vec<_> = converted.markdown.split("\n\n").collect();
+        assert_eq!(lines[0], "# Midterm");
+        assert_eq!(lines[1], "1. Which shape is \\<shown>?");
+        assert_eq!(lines[2], "![](media/image1.png)");
+        assert_eq!(&lines[3..5], ["A. Circle", "B. Square"]);
+        assert!(converted.markdown.contains("# Answer Key\n\n1. B\n\nIt has four equal sides.\n\n2. 2"));
+    }
+}
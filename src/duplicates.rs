@@ -0,0 +1,309 @@
+//! Duplicate questions within one import
+//!
+//! Merged banks often repeat a question in many files. [`BatchDedup`] finds
+//! such repeats before anything is saved: questions are compared on their
+//! stem and options with case, whitespace, punctuation and leading question
+//! numbers removed, and, with [`BatchDedup::with_near_duplicates`], by the
+//! similarity of that text so small edits still match. Near-duplicate
+//! candidates are found with MinHash over character bigrams and confirmed
+//! with [`crate::dedup::similarity`]. Each group of repeats is reported
+//! as a [`DuplicateCluster`]; the first question of a cluster is kept and the
+//! others are either kept and flagged or dropped.
+//!
+//! Unlike [`crate::dedup`], which compares against stored questions, this
+//! only looks at the questions of the current import.
+
+use crate::dedup::similarity;
+use crate::incremental::source_key;
+use crate::models::Question;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// MinHash bands; questions sharing a band are compared
+const BANDS: usize = 8;
+
+/// MinHash values per band
+const ROWS: usize = 4;
+
+/// A question that is part of a [`DuplicateCluster`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterMember {
+    /// Question ID
+    pub id: Uuid,
+    /// File the question was read from
+    pub source: String,
+    /// Question stem
+    pub stem: String,
+}
+
+/// The same question found more than once in an import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    /// First occurrence, which is always kept
+    pub kept: ClusterMember,
+    /// Later occurrences, in input order
+    pub duplicates: Vec<ClusterMember>,
+    /// Whether any duplicate matched by similarity rather than exactly
+    pub near: bool,
+    /// Whether the duplicates were dropped rather than only reported
+    pub dropped: bool,
+}
+
+/// Finds questions repeated within an import
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchDedup {
+    /// Drop repeats instead of only reporting them
+    pub drop: bool,
+    /// Minimum similarity for a near-duplicate; `None` matches exact text only
+    pub near_duplicate_threshold: Option<f64>,
+}
+
+impl BatchDedup {
+    /// Report exact repeats without dropping them
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop (or keep) the repeats in each cluster
+    pub fn with_drop(mut self, drop: bool) -> Self {
+        self.drop = drop;
+        self
+    }
+
+    /// Also match questions at least `threshold` similar (clamped to 0.5..=1.0)
+    ///
+    /// [`crate::dedup::DEFAULT_NEAR_DUPLICATE_THRESHOLD`] is a good start.
+    /// Below about 0.7, MinHash starts to miss pairs.
+    pub fn with_near_duplicates(mut self, threshold: f64) -> Self {
+        self.near_duplicate_threshold = Some(threshold.clamp(0.5, 1.0));
+        self
+    }
+
+    /// Find the clusters in `questions`, dropping repeats if configured
+    ///
+    /// Questions whose text normalizes to nothing, such as image-only
+    /// stems, are never matched.
+    pub fn apply(&self, questions: Vec<Question>) -> (Vec<Question>, Vec<DuplicateCluster>) {
+        let mut exact: HashMap<String, usize> = HashMap::new();
+        let mut buckets: HashMap<(usize, [u64; ROWS]), Vec<usize>> = HashMap::new();
+        // Per kept question: its normalized text and the cluster it heads
+        let mut kept: Vec<(String, Option<usize>)> = Vec::new();
+        let mut kept_index: Vec<usize> = Vec::new();
+        let mut clusters: Vec<DuplicateCluster> = Vec::new();
+        let mut keep = vec![true; questions.len()];
+
+        for (i, question) in questions.iter().enumerate() {
+            let key = normalized_key(question);
+            if key.is_empty() {
+                continue;
+            }
+
+            let mut matched = exact.get(&key).map(|&k| (k, false));
+            let signature = self.near_duplicate_threshold.map(|_| minhash(&key));
+            if let (None, Some(signature), Some(threshold)) = (matched, &signature, self.near_duplicate_threshold) {
+                matched = band_keys(signature)
+                    .filter_map(|band| buckets.get(&band))
+                    .flatten()
+                    .copied()
+                    .find(|&k| similarity(&key, &kept[k].0) >= threshold)
+                    .map(|k| (k, true));
+            }
+
+            let Some((k, near)) = matched else {
+                let k = kept.len();
+                if let Some(signature) = &signature {
+                    for band in band_keys(signature) {
+                        buckets.entry(band).or_default().push(k);
+                    }
+                }
+                exact.insert(key.clone(), k);
+                kept.push((key, None));
+                kept_index.push(i);
+                continue;
+            };
+
+            let cluster = *kept[k].1.get_or_insert_with(|| {
+                clusters.push(DuplicateCluster {
+                    kept: member(&questions[kept_index[k]]),
+                    duplicates: Vec::new(),
+                    near: false,
+                    dropped: self.drop,
+                });
+                clusters.len() - 1
+            });
+            clusters[cluster].duplicates.push(member(question));
+            clusters[cluster].near |= near;
+            keep[i] = !self.drop;
+        }
+
+        let questions = questions
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(question, keep)| keep.then_some(question))
+            .collect();
+        (questions, clusters)
+    }
+}
+
+fn member(question: &Question) -> ClusterMember {
+    ClusterMember {
+        id: question.id,
+        source: source_key(question).to_string(),
+        stem: question.stem.clone(),
+    }
+}
+
+/// Stem and options with numbering, case, whitespace and punctuation removed
+pub fn normalized_key(question: &Question) -> String {
+    let mut key = normalize(strip_numbering(&question.stem));
+    if key.is_empty() {
+        return key;
+    }
+    for option in &question.options {
+        key.push('\u{1f}');
+        key.push_str(&normalize(&option.content));
+    }
+    key
+}
+
+/// Stem with numbering, case, whitespace and punctuation removed
+pub fn normalized_stem(question: &Question) -> String {
+    normalize(strip_numbering(&question.stem))
+}
+
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Drop a leading question number such as `12.`, `3)` or `4、`
+fn strip_numbering(stem: &str) -> &str {
+    let stem = stem.trim_start();
+    let digits = stem.len() - stem.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return stem;
+    }
+    let rest = &stem[digits..];
+    match rest.chars().next() {
+        Some(c @ ('.' | ')' | '、' | '．' | '）')) => &rest[c.len_utf8()..],
+        _ => stem,
+    }
+}
+
+/// MinHash signature of the character bigrams of `text`
+fn minhash(text: &str) -> [u64; BANDS * ROWS] {
+    let chars: Vec<char> = text.chars().collect();
+    let grams: Vec<u64> = if chars.len() < 2 {
+        vec![fnv1a(&chars)]
+    } else {
+        chars.windows(2).map(fnv1a).collect()
+    };
+    let mut signature = [u64::MAX; BANDS * ROWS];
+    for (seed, slot) in signature.iter_mut().enumerate() {
+        for &gram in &grams {
+            *slot = (*slot).min(mix(gram ^ (seed as u64).wrapping_mul(0x9e3779b97f4a7c15)));
+        }
+    }
+    signature
+}
+
+/// The bands of a signature, keyed by their position
+fn band_keys(signature: &[u64; BANDS * ROWS]) -> impl Iterator<Item = (usize, [u64; ROWS])> + '_ {
+    signature.chunks(ROWS).enumerate().map(|(band, rows)| {
+        let mut key = [0; ROWS];
+        key.copy_from_slice(rows);
+        (band, key)
+    })
+}
+
+fn fnv1a(chars: &[char]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &c in chars {
+        for byte in (c as u32).to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// SplitMix64 finalizer, turning one hash into an independent-looking one
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::DEFAULT_NEAR_DUPLICATE_THRESHOLD;
+    use crate::models::{Provenance, QuestionOption};
+
+    fn question(source: &str, stem: &str, options: &[&str]) -> Question {
+        Question {
+            stem: stem.to_string(),
+            options: options
+                .iter()
+                .zip(0..)
+                .map(|(content, sort_order)| QuestionOption {
+                    content: content.to_string(),
+                    sort_order,
+                    is_correct: false,
+                })
+                .collect(),
+            provenance: Some(Provenance {
+                source_file: Some(source.to_string()),
+                ..Provenance::default()
+            }),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn test_exact_clusters() {
+        let questions = vec![
+            question("a.md", "1. Which sorting algorithm is stable?", &["Quick", "Merge"]),
+            question("b.md", "7) which SORTING algorithm is stable", &["quick", "merge"]),
+            question("c.md", "Which sorting algorithm is stable?", &["Heap", "Merge"]),
+            question("c.md", "![](plot.png)", &[]),
+            question("d.md", "![](other.png)", &[]),
+            question("d.md", "Which sorting algorithm is stable?!", &["Quick", "Merge"]),
+        ];
+        let ids: Vec<_> = questions.iter().map(|q| q.id).collect();
+
+        let (kept, clusters) = BatchDedup::new().apply(questions.clone());
+        assert_eq!(kept.len(), 6);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].kept.id, ids[0]);
+        let duplicates: Vec<_> = clusters[0].duplicates.iter().map(|m| m.id).collect();
+        assert_eq!(duplicates, [ids[1], ids[5]]);
+        assert!(!clusters[0].near && !clusters[0].dropped);
+
+        let (kept, clusters) = BatchDedup::new().with_drop(true).apply(questions);
+        let kept: Vec<_> = kept.iter().map(|q| q.id).collect();
+        assert_eq!(kept, [ids[0], ids[2], ids[3], ids[4]]);
+        assert!(clusters[0].dropped);
+    }
+
+    #[test]
+    fn test_near_duplicate_clusters() {
+        let stem = "Which of the following sorting algorithms is stable when sorting records by key";
+        let questions = vec![
+            question("a.md", stem, &["Quick sort", "Merge sort"]),
+            question("b.md", &stem.replace("records", "recods"), &["Quick sort", "Merge sort"]),
+            question("c.md", "What is the capital of France?", &["Paris", "Rome"]),
+        ];
+
+        let (_, clusters) = BatchDedup::new().apply(questions.clone());
+        assert!(clusters.is_empty());
+        let (kept, clusters) = BatchDedup::new()
+            .with_near_duplicates(DEFAULT_NEAR_DUPLICATE_THRESHOLD)
+            .with_drop(true)
+            .apply(questions);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(clusters.len(), 1);
+        assert!(clusters[0].near);
+        assert_eq!(clusters[0].duplicates[0].source, "b.md");
+    }
+}
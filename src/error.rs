@@ -0,0 +1,158 @@
+//! Library error type
+//!
+//! Public functions of the parser, archive processing, media stores and
+//! repositories fail with an [`Md2DbError`], so callers can tell a bad
+//! upload from a database outage without inspecting messages. Each variant
+//! says what was being done when it failed and keeps the underlying error
+//! as its [`source`](std::error::Error::source).
+//!
+//! Internally the crate still collects errors with `anyhow`; they are turned
+//! into an [`Md2DbError`] where they leave the public functions. An
+//! `Md2DbError` passed through `anyhow` keeps its category.
+
+/// Any error kept as the cause of an [`Md2DbError`]
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Result of a public library function
+pub type Result<T, E = Md2DbError> = std::result::Result<T, E>;
+
+/// Why a library call failed
+#[derive(Debug, thiserror::Error)]
+pub enum Md2DbError {
+    /// Source text could not be parsed into questions
+    #[error("{}", describe(context, source))]
+    ParseError {
+        context: String,
+        #[source]
+        source: BoxError,
+    },
+    /// An archive or one of its entries could not be read
+    #[error("{}", describe(context, source))]
+    ZipError {
+        context: String,
+        #[source]
+        source: BoxError,
+    },
+    /// An image could not be stored or read back
+    #[error("{}", describe(context, source))]
+    MediaError {
+        context: String,
+        #[source]
+        source: BoxError,
+    },
+    /// The repository failed or refused the operation
+    #[error("{}", describe(context, source))]
+    RepositoryError {
+        context: String,
+        #[source]
+        source: BoxError,
+    },
+    /// Input was well-formed but broke a rule
+    #[error("{context}: {message}")]
+    ValidationError { context: String, message: String },
+}
+
+impl Md2DbError {
+    /// Parsing failed while doing `context`
+    pub fn parse(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Md2DbError::ParseError { context: context.into(), source: source.into() }
+    }
+
+    /// Reading an archive failed while doing `context`
+    pub fn zip(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Md2DbError::ZipError { context: context.into(), source: source.into() }
+    }
+
+    /// The media store failed while doing `context`
+    pub fn media(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Md2DbError::MediaError { context: context.into(), source: source.into() }
+    }
+
+    /// The repository failed while doing `context`
+    pub fn repository(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Md2DbError::RepositoryError { context: context.into(), source: source.into() }
+    }
+
+    /// `context` is invalid because of `message`
+    pub fn validation(context: impl Into<String>, message: impl Into<String>) -> Self {
+        Md2DbError::ValidationError { context: context.into(), message: message.into() }
+    }
+
+    /// What was being done, or what was invalid
+    pub fn context(&self) -> &str {
+        match self {
+            Md2DbError::ParseError { context, .. }
+            | Md2DbError::ZipError { context, .. }
+            | Md2DbError::MediaError { context, .. }
+            | Md2DbError::RepositoryError { context, .. }
+            | Md2DbError::ValidationError { context, .. } => context,
+        }
+    }
+
+    /// Name of the category, for logs and error codes
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Md2DbError::ParseError { .. } => "parse_error",
+            Md2DbError::ZipError { .. } => "zip_error",
+            Md2DbError::MediaError { .. } => "media_error",
+            Md2DbError::RepositoryError { .. } => "repository_error",
+            Md2DbError::ValidationError { .. } => "validation_error",
+        }
+    }
+
+    /// Turn an internal error into an `Md2DbError`, keeping its category if
+    /// it already was one and otherwise filing it with `make`
+    pub(crate) fn wrap(error: anyhow::Error, make: impl FnOnce(BoxError) -> Self) -> Self {
+        match error.downcast::<Md2DbError>() {
+            Ok(error) => error,
+            Err(error) => make(error.into()),
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::Error> for Md2DbError {
+    fn from(error: sqlx::Error) -> Self {
+        Md2DbError::repository("", error)
+    }
+}
+
+/// The context followed by the cause, or just the cause without a context
+fn describe(context: &str, source: &BoxError) -> String {
+    if context.is_empty() {
+        source.to_string()
+    } else {
+        format!("{}: {}", context, source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_source_and_wrap() {
+        let error = Md2DbError::zip("reading exam.zip", "not an archive");
+        assert_eq!(error.to_string(), "reading exam.zip: not an archive");
+        assert_eq!(error.kind(), "zip_error");
+        assert_eq!(error.context(), "reading exam.zip");
+        assert_eq!(std::error::Error::source(&error).unwrap().to_string(), "not an archive");
+
+        let error = Md2DbError::repository("", "connection refused");
+        assert_eq!(error.to_string(), "connection refused");
+
+        // A categorized error survives a trip through anyhow
+        let internal = anyhow::Error::new(Md2DbError::media("storing a.png", "disk full"));
+        let error = Md2DbError::wrap(internal, |e| Md2DbError::zip("", e));
+        assert!(matches!(error, Md2DbError::MediaError { .. }));
+
+        let internal = anyhow::anyhow!("truncated").context("reading exam.zip");
+        let error = Md2DbError::wrap(internal, |e| Md2DbError::zip("", e));
+        assert!(matches!(error, Md2DbError::ZipError { .. }));
+        assert_eq!(error.to_string(), "reading exam.zip");
+        assert_eq!(format!("{}", std::error::Error::source(&error).unwrap()), "reading exam.zip");
+
+        let error = Md2DbError::validation("question bank", "name is empty");
+        assert_eq!(error.to_string(), "question bank: name is empty");
+    }
+}
@@ -0,0 +1,345 @@
+//! Bulk export in several formats
+//!
+//! [`export`] writes the stored questions selected by an [`ExportFilter`] to
+//! any writer as JSON Lines, GIFT, QTI or an Excel workbook. Questions are
+//! read with [`ExportPages`], a page at a time, and each page is written
+//! before the next is read, so whole banks can be streamed without holding
+//! them in memory. Excel is the exception: a workbook can only be written
+//! once it is complete.
+
+use crate::database::{ListParams, QuestionRepository, MAX_PAGE_SIZE};
+use crate::gift::question_to_gift;
+use crate::models::{Question, QuestionType};
+use crate::qti::{question_to_qti, QTI_FOOTER, QTI_HEADER};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+/// Which stored questions to export
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportFilter {
+    /// Only questions in this bank
+    pub bank_id: Option<Uuid>,
+    /// Only questions with this tag (ignored when `bank_id` is given)
+    pub tag: Option<String>,
+    /// Only questions of this type
+    #[serde(rename = "type")]
+    pub qtype: Option<QuestionType>,
+}
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON question per line
+    Jsonl,
+    /// Moodle GIFT
+    Gift,
+    /// IMS QTI 1.2 object bank
+    Qti,
+    /// Excel review sheet (needs the `xlsx` feature)
+    Xlsx,
+}
+
+impl ExportFormat {
+    /// Every format, in the order they are listed to users
+    pub const ALL: [ExportFormat; 4] = [ExportFormat::Jsonl, ExportFormat::Gift, ExportFormat::Qti, ExportFormat::Xlsx];
+
+    /// Name used in `?format=`
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Gift => "gift",
+            ExportFormat::Qti => "qti",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+
+    /// MIME type of the exported file
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Gift => "text/plain; charset=utf-8",
+            ExportFormat::Qti => "application/xml",
+            ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+
+    /// Suggested file name
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "questions.jsonl",
+            ExportFormat::Gift => "questions.gift.txt",
+            ExportFormat::Qti => "questions.qti.xml",
+            ExportFormat::Xlsx => "questions.xlsx",
+        }
+    }
+
+    /// Whether this build can write the format
+    pub fn is_enabled(self) -> bool {
+        self != ExportFormat::Xlsx || cfg!(feature = "xlsx")
+    }
+
+    /// The format a request's `Accept` header asks for, if any is known
+    ///
+    /// Media types are tried in the order listed; quality values are not
+    /// weighed.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        accept.split(',').find_map(|media| {
+            let media = media.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+            Self::ALL
+                .into_iter()
+                .filter(|format| format.is_enabled())
+                .find(|format| {
+                    let essence = format.content_type().split(';').next().unwrap_or_default();
+                    media == essence || (*format == ExportFormat::Qti && media == "text/xml")
+                })
+        })
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let format = match s.trim().to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" => ExportFormat::Jsonl,
+            "gift" => ExportFormat::Gift,
+            "qti" => ExportFormat::Qti,
+            "xlsx" => ExportFormat::Xlsx,
+            other => return Err(anyhow!("Unknown export format: {} (expected jsonl, gift, qti or xlsx)", other)),
+        };
+        if !format.is_enabled() {
+            return Err(anyhow!("{} export needs the {} feature", format.name(), format.name()));
+        }
+        Ok(format)
+    }
+}
+
+/// The questions selected by a filter, read a page at a time
+///
+/// Bank and tag selections are read in one go and listed in document order:
+/// by source file, then by position in the import. Everything else is paged
+/// through the repository in creation order.
+pub struct ExportPages<'a> {
+    repo: &'a dyn QuestionRepository,
+    filter: ExportFilter,
+    /// Parameters of the next page, `None` once every page has been read
+    next: Option<ListParams>,
+}
+
+impl<'a> ExportPages<'a> {
+    /// Start reading the questions `filter` selects
+    pub fn new(repo: &'a dyn QuestionRepository, filter: &ExportFilter) -> Self {
+        Self {
+            repo,
+            filter: filter.clone(),
+            next: Some(ListParams {
+                limit: Some(MAX_PAGE_SIZE),
+                qtype: filter.qtype,
+                ..ListParams::default()
+            }),
+        }
+    }
+
+    /// The next page of questions, or `None` after the last one
+    pub async fn next_page(&mut self) -> Result<Option<Vec<Question>>> {
+        let Some(params) = self.next.take() else {
+            return Ok(None);
+        };
+        let mut questions = match (self.filter.bank_id, self.filter.tag.as_deref()) {
+            (Some(bank_id), _) => self.repo.find_by_bank(bank_id).await?,
+            (None, Some(tag)) => self.repo.find_by_tag(tag).await?,
+            (None, None) => {
+                let page = self.repo.list(&params).await?;
+                self.next = page.next_cursor.map(|cursor| ListParams {
+                    cursor: Some(cursor),
+                    ..params
+                });
+                return Ok(Some(page.items));
+            }
+        };
+        if let Some(qtype) = self.filter.qtype {
+            questions.retain(|q| q.qtype == qtype);
+        }
+        // Files parsed side by side interleave their creation times
+        questions.sort_by(|a, b| document_order(a).cmp(&document_order(b)));
+        Ok(Some(questions))
+    }
+
+    /// Every remaining question
+    pub async fn collect(mut self) -> Result<Vec<Question>> {
+        let mut questions = Vec::new();
+        while let Some(page) = self.next_page().await? {
+            questions.extend(page);
+        }
+        Ok(questions)
+    }
+}
+
+/// Sort key placing a question where it was in its source document
+fn document_order(question: &Question) -> (Option<&str>, Option<u64>, chrono::DateTime<chrono::Utc>, Uuid) {
+    let source = question.provenance.as_ref().and_then(|p| p.source_file.as_deref());
+    (source, question.sequence, question.created_at, question.id)
+}
+
+/// Write the questions matching `filter` to `writer` as `format`
+///
+/// Returns the number of questions written.
+pub async fn export<W>(repo: &dyn QuestionRepository, writer: &mut W, filter: &ExportFilter, format: ExportFormat) -> Result<usize>
+where
+    W: AsyncWrite + Unpin,
+{
+    if format == ExportFormat::Xlsx {
+        return export_xlsx(repo, writer, filter).await;
+    }
+
+    if format == ExportFormat::Qti {
+        writer.write_all(QTI_HEADER.as_bytes()).await?;
+    }
+    let mut pages = ExportPages::new(repo, filter);
+    let mut written = 0;
+    while let Some(page) = pages.next_page().await? {
+        let mut chunk = String::new();
+        for question in &page {
+            match format {
+                ExportFormat::Jsonl => {
+                    chunk.push_str(&serde_json::to_string(question)?);
+                    chunk.push('\n');
+                }
+                ExportFormat::Gift => {
+                    if written > 0 || !chunk.is_empty() {
+                        chunk.push('\n');
+                    }
+                    chunk.push_str(&question_to_gift(question));
+                }
+                ExportFormat::Qti => chunk.push_str(&question_to_qti(question)),
+                ExportFormat::Xlsx => unreachable!("workbooks are written whole"),
+            }
+        }
+        writer.write_all(chunk.as_bytes()).await?;
+        written += page.len();
+    }
+    if format == ExportFormat::Qti {
+        writer.write_all(QTI_FOOTER.as_bytes()).await?;
+    }
+    writer.flush().await?;
+    Ok(written)
+}
+
+#[cfg(feature = "xlsx")]
+async fn export_xlsx<W>(repo: &dyn QuestionRepository, writer: &mut W, filter: &ExportFilter) -> Result<usize>
+where
+    W: AsyncWrite + Unpin,
+{
+    let questions = ExportPages::new(repo, filter).collect().await?;
+    let data = crate::tabular::write_xlsx(&questions)?;
+    writer.write_all(&data).await?;
+    writer.flush().await?;
+    Ok(questions.len())
+}
+
+#[cfg(not(feature = "xlsx"))]
+async fn export_xlsx<W>(_repo: &dyn QuestionRepository, _writer: &mut W, _filter: &ExportFilter) -> Result<usize>
+where
+    W: AsyncWrite + Unpin,
+{
+    Err(anyhow!("xlsx export needs the xlsx feature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MockRepository;
+    use crate::models::QuestionOption;
+
+    #[tokio::test]
+    async fn test_export_formats() {
+        let repo = MockRepository::new();
+        let choice = Question {
+            qtype: QuestionType::Choice,
+            stem: "2+2?".to_string(),
+            options: ["3", "4"]
+                .iter()
+                .zip(0..)
+                .map(|(content, sort_order)| QuestionOption {
+                    content: content.to_string(),
+                    sort_order,
+                    is_correct: sort_order == 1,
+                })
+                .collect(),
+            ..Question::default()
+        };
+        let essay = Question {
+            stem: "Explain recursion".to_string(),
+            ..Question::default()
+        };
+        repo.save_batch(&[choice, essay]).await.unwrap();
+
+        let mut out = Vec::new();
+        let all = ExportFilter::default();
+        assert_eq!(export(&repo, &mut out, &all, ExportFormat::Jsonl).await.unwrap(), 2);
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+
+        let mut out = Vec::new();
+        export(&repo, &mut out, &all, ExportFormat::Gift).await.unwrap();
+        let gift = String::from_utf8(out).unwrap();
+        assert!(gift.contains("[markdown]2+2? {\n\t~3\n\t=4\n}\n\n[markdown]Explain recursion {}\n"), "{}", gift);
+
+        let choices = ExportFilter {
+            qtype: Some(QuestionType::Choice),
+            ..ExportFilter::default()
+        };
+        let mut out = Vec::new();
+        assert_eq!(export(&repo, &mut out, &choices, ExportFormat::Qti).await.unwrap(), 1);
+        let qti = String::from_utf8(out).unwrap();
+        assert!(qti.starts_with(QTI_HEADER) && qti.ends_with(QTI_FOOTER));
+        assert_eq!(qti.matches("<item ").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bank_export_follows_document_order() {
+        let repo = MockRepository::new();
+        let bank_id = Uuid::new_v4();
+        let question = |source: &str, sequence: u64, stem: &str| Question {
+            stem: stem.to_string(),
+            bank_id: Some(bank_id),
+            sequence: Some(sequence),
+            provenance: Some(crate::models::Provenance {
+                source_file: Some(source.to_string()),
+                ..Default::default()
+            }),
+            ..Question::default()
+        };
+        // Created in the order two files finished parsing side by side
+        repo.save_batch(&[
+            question("b.md", 3, "b1"),
+            question("a.md", 2, "a2"),
+            question("a.md", 1, "a1"),
+            question("b.md", 4, "b2"),
+        ])
+        .await
+        .unwrap();
+
+        let filter = ExportFilter {
+            bank_id: Some(bank_id),
+            ..ExportFilter::default()
+        };
+        let questions = ExportPages::new(&repo, &filter).collect().await.unwrap();
+        let stems: Vec<_> = questions.iter().map(|q| q.stem.as_str()).collect();
+        assert_eq!(stems, ["a1", "a2", "b1", "b2"]);
+    }
+
+    #[test]
+    fn test_format_negotiation() {
+        assert_eq!("QTI".parse::<ExportFormat>().unwrap(), ExportFormat::Qti);
+        assert!("csv".parse::<ExportFormat>().is_err());
+        assert_eq!(
+            ExportFormat::from_accept("text/html, application/xml;q=0.9"),
+            Some(ExportFormat::Qti)
+        );
+        assert_eq!(ExportFormat::from_accept("text/plain"), Some(ExportFormat::Gift));
+        assert_eq!(ExportFormat::from_accept("*/*"), None);
+    }
+}
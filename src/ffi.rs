@@ -0,0 +1,135 @@
+//! C API for embedding the parser (`ffi` feature)
+//!
+//! Exam platforms written in Python, Java or .NET can load md2db as a shared
+//! library and parse Markdown in-process instead of going through the HTTP
+//! API. Text crosses the boundary as NUL-terminated UTF-8 and questions come
+//! back as a JSON array in the same shape the API returns.
+//!
+//! Strings returned by this module are owned by the caller and must be
+//! released with [`md2db_string_free`]. A function that fails returns NULL
+//! and records a message that [`md2db_last_error`] returns, per thread.
+//!
+//! The C declarations live in `include/md2db.h`, generated by `cbindgen`
+//! from this file (see `cbindgen.toml`).
+
+use crate::models::Question;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+thread_local! {
+    /// Message of the last failed call on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Version of the library, as a static string the caller must not free
+#[no_mangle]
+pub extern "C" fn md2db_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Parse Markdown into a JSON array of questions
+///
+/// Returns NULL if `markdown` is NULL or not UTF-8, or if parsing fails;
+/// [`md2db_last_error`] then says why. Free the result with
+/// [`md2db_string_free`].
+///
+/// # Safety
+///
+/// `markdown` must be NULL or point to a NUL-terminated string that stays
+/// valid for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn md2db_parse_markdown(markdown: *const c_char) -> *mut c_char {
+    guard(|| {
+        if markdown.is_null() {
+            return Err("markdown is NULL".to_string());
+        }
+        let markdown = CStr::from_ptr(markdown)
+            .to_str()
+            .map_err(|e| format!("markdown is not valid UTF-8: {}", e))?;
+        let questions = crate::parse_markdown_text(markdown).map_err(|e| format!("{}: {}", e.kind(), e))?;
+        to_json(&questions)
+    })
+}
+
+/// Message of the last call on this thread that returned NULL
+///
+/// Returns NULL if no call has failed yet. Free the result with
+/// [`md2db_string_free`].
+#[no_mangle]
+pub extern "C" fn md2db_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null_mut(), |e| e.clone().into_raw()))
+}
+
+/// Release a string returned by this library
+///
+/// # Safety
+///
+/// `s` must be NULL or a pointer returned by an md2db function that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn md2db_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Questions as a JSON array
+fn to_json(questions: &[Question]) -> Result<CString, String> {
+    let json = serde_json::to_string(questions).map_err(|e| format!("encoding questions: {}", e))?;
+    // JSON escapes control characters, so it never contains a NUL byte
+    CString::new(json).map_err(|e| e.to_string())
+}
+
+/// Run `f`, turning failures and panics into NULL plus a recorded message
+fn guard(f: impl FnOnce() -> Result<CString, String>) -> *mut c_char {
+    let message = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(result)) => return result.into_raw(),
+        Ok(Err(message)) => message,
+        Err(panic) => match panic.downcast_ref::<&str>() {
+            Some(message) => format!("internal error: {}", message),
+            None => match panic.downcast_ref::<String>() {
+                Some(message) => format!("internal error: {}", message),
+                None => "internal error".to_string(),
+            },
+        },
+    };
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    std::ptr::null_mut()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Take ownership of a returned string
+    fn take(s: *mut c_char) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let text = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+        unsafe { md2db_string_free(s) };
+        Some(text)
+    }
+
+    #[test]
+    fn test_parse_markdown_over_c_api() {
+        let markdown = CString::new("# 单选题\n\n1 + 1 = ?\n\n* A. 1\n* B. 2\n").unwrap();
+        let json = take(unsafe { md2db_parse_markdown(markdown.as_ptr()) }).unwrap();
+        let questions: Vec<Question> = serde_json::from_str(&json).unwrap();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].options.len(), 2);
+
+        assert!(take(unsafe { md2db_parse_markdown(std::ptr::null()) }).is_none());
+        assert_eq!(take(md2db_last_error()).as_deref(), Some("markdown is NULL"));
+
+        let invalid = [0xffu8, 0xfe, 0];
+        assert!(take(unsafe { md2db_parse_markdown(invalid.as_ptr().cast()) }).is_none());
+        assert!(take(md2db_last_error()).unwrap().starts_with("markdown is not valid UTF-8"));
+
+        let version = unsafe { CStr::from_ptr(md2db_version()) };
+        assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+        unsafe { md2db_string_free(std::ptr::null_mut()) };
+    }
+}
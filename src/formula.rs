@@ -0,0 +1,1507 @@
+//! Formula rendering to MathML and SVG
+//!
+//! Exam clients without a math engine cannot display the LaTeX kept in
+//! [`Question::latex`]. [`to_mathml`] converts a formula to presentation
+//! MathML, which browsers render natively, and [`to_svg`] lays it out as a
+//! standalone SVG image for clients that only show pictures. The supported
+//! LaTeX is what [`crate::latex::LatexValidator`] accepts: fractions, roots,
+//! scripts, accents, fonts, `\left...\right` and matrix environments.
+//! Unknown commands are rendered as their source rather than dropped.
+//!
+//! [`FormulaRenderer`] renders every formula of a question at export or API
+//! time and, with a [`MediaStore`], keeps the result as a derived artifact
+//! addressed by its hash, so each formula is rendered into the store once and
+//! served like any image.
+
+use crate::latex::split_formula;
+use crate::media::MediaStore;
+use crate::models::Question;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// Output format of a rendered formula
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormulaFormat {
+    /// Presentation MathML
+    MathMl,
+    /// Standalone SVG image
+    Svg,
+}
+
+impl FormulaFormat {
+    /// Extension the rendered file is stored with
+    pub fn extension(self) -> &'static str {
+        match self {
+            FormulaFormat::MathMl => "mml",
+            FormulaFormat::Svg => "svg",
+        }
+    }
+
+    /// MIME type of the rendered file
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FormulaFormat::MathMl => "application/mathml+xml",
+            FormulaFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+impl std::str::FromStr for FormulaFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "mathml" | "mml" => Ok(FormulaFormat::MathMl),
+            "svg" => Ok(FormulaFormat::Svg),
+            other => Err(anyhow!("Unsupported formula format: {}", other)),
+        }
+    }
+}
+
+/// Render `formula`, with or without its delimiters, in `format`
+pub fn render_formula(formula: &str, format: FormulaFormat) -> String {
+    match format {
+        FormulaFormat::MathMl => to_mathml(formula),
+        FormulaFormat::Svg => to_svg(formula),
+    }
+}
+
+/// Convert a formula to a MathML `<math>` element
+///
+/// `$$...$$` and `\[...\]` formulas are displayed as blocks, others inline.
+/// The LaTeX source is kept as an annotation.
+pub fn to_mathml(formula: &str) -> String {
+    let (body, display, _) = split_formula(formula);
+    let node = parse(body);
+    let mut out = format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"{}\"><semantics><mrow>",
+        if display { "block" } else { "inline" }
+    );
+    write_mathml(&node, display, None, &mut out);
+    out.push_str("</mrow><annotation encoding=\"application/x-tex\">");
+    out.push_str(&escape_xml(body));
+    out.push_str("</annotation></semantics></math>");
+    out
+}
+
+/// Lay out a formula as a standalone SVG image
+///
+/// Sizes are in pixels for a 20px font; text is drawn in `currentColor` so
+/// the image follows the surrounding text color when inlined.
+pub fn to_svg(formula: &str) -> String {
+    let (body, display, _) = split_formula(formula);
+    let size = FONT_SIZE;
+    let layout = layout(&parse(body), size, display, None);
+    let pad = 0.2 * size;
+    let width = layout.width + 2.0 * pad;
+    let height = layout.ascent + layout.descent + 2.0 * pad;
+    let (dx, dy) = (pad, layout.ascent + pad);
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" role=\"img\" aria-label=\"{label}\">",
+        w = num(width),
+        h = num(height),
+        label = escape_xml(body),
+    );
+    out.push_str(&format!("<title>{}</title>", escape_xml(body)));
+    out.push_str("<g fill=\"currentColor\" font-family=\"'Latin Modern Math', 'STIX Two Math', 'Cambria Math', serif\">");
+    for item in &layout.items {
+        match item {
+            Item::Glyph { x, y, size, text, italic, bold } => {
+                out.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\"", num(x + dx), num(y + dy), num(*size)));
+                if *italic {
+                    out.push_str(" font-style=\"italic\"");
+                }
+                if *bold {
+                    out.push_str(" font-weight=\"bold\"");
+                }
+                out.push_str(&format!(">{}</text>", escape_xml(text)));
+            }
+            Item::Rule { x1, y1, x2, y2, thickness } => {
+                out.push_str(&format!(
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"currentColor\" stroke-width=\"{}\"/>",
+                    num(x1 + dx),
+                    num(y1 + dy),
+                    num(x2 + dx),
+                    num(y2 + dy),
+                    num(*thickness)
+                ));
+            }
+        }
+    }
+    out.push_str("</g></svg>");
+    out
+}
+
+/// A formula rendered for one question
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderedFormula {
+    /// The formula as written in the question
+    pub formula: String,
+    pub format: FormulaFormat,
+    /// The rendered document, when it was not put in a media store
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Hash of the stored artifact, served at `/media/{hash}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Location of the stored artifact, relative to the store root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Renders the formulas of questions, optionally keeping the results in a
+/// media store
+pub struct FormulaRenderer {
+    format: FormulaFormat,
+    media_store: Option<Arc<dyn MediaStore>>,
+}
+
+impl FormulaRenderer {
+    /// Create a renderer returning formulas inline in `format`
+    pub fn new(format: FormulaFormat) -> Self {
+        Self { format, media_store: None }
+    }
+
+    /// Store rendered formulas in `store` instead of returning them inline
+    pub fn with_media_store(mut self, store: Arc<dyn MediaStore>) -> Self {
+        self.media_store = Some(store);
+        self
+    }
+
+    /// Render every formula in [`Question::latex`], skipping repeats
+    pub async fn render_question(&self, question: &Question) -> Result<Vec<RenderedFormula>> {
+        let mut seen = BTreeSet::new();
+        let mut rendered = Vec::new();
+        for formula in question.latex.iter().filter(|f| seen.insert(f.trim())) {
+            let content = render_formula(formula, self.format);
+            let mut result = RenderedFormula {
+                formula: formula.clone(),
+                format: self.format,
+                content: None,
+                hash: None,
+                path: None,
+            };
+            match &self.media_store {
+                Some(store) => {
+                    let stored = store.put(content.as_bytes()).await?;
+                    result.hash = Some(stored.hash);
+                    result.path = Some(stored.path);
+                }
+                None => result.content = Some(content),
+            }
+            rendered.push(result);
+        }
+        Ok(rendered)
+    }
+}
+
+/// A parsed formula
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// A variable or function name
+    Ident(String),
+    Number(String),
+    Operator(String),
+    /// A big operator such as `\sum`; `limits` puts scripts above and below
+    /// in display formulas
+    LargeOp { text: String, limits: bool },
+    Text(String),
+    Row(Vec<Node>),
+    Frac { num: Box<Node>, den: Box<Node>, line: bool },
+    Sqrt { body: Box<Node>, index: Option<Box<Node>> },
+    Scripts { base: Box<Node>, sub: Option<Box<Node>>, sup: Option<Box<Node>> },
+    Over { base: Box<Node>, over: Box<Node>, accent: bool },
+    Under { base: Box<Node>, under: Box<Node> },
+    Fenced { open: String, body: Box<Node>, close: String },
+    Table { rows: Vec<Vec<Node>>, left_aligned: bool },
+    /// Horizontal space in em
+    Space(f32),
+    /// Content in a font such as `bold` or `double-struck`
+    Styled { variant: &'static str, body: Box<Node> },
+    Phantom(Box<Node>),
+    Boxed(Box<Node>),
+}
+
+impl Node {
+    fn boxed(self) -> Box<Node> {
+        Box::new(self)
+    }
+}
+
+/// Deepest nesting of groups and commands parsed
+///
+/// Parsing and rendering recurse per level, so a crafted formula
+/// ("{{{…}}}", "\\frac\\frac…") could otherwise overflow the stack. The rest
+/// of a formula nested deeper is shown as its source.
+pub const MAX_NESTING: usize = 64;
+
+/// Parse a formula body (without delimiters)
+fn parse(body: &str) -> Node {
+    parse_nested(body, 0)
+}
+
+/// Parse a formula body found `depth` levels deep, such as a root's index
+fn parse_nested(body: &str, depth: usize) -> Node {
+    let chars: Vec<char> = body.chars().collect();
+    let mut pos = 0;
+    let mut nodes = parse_row(&chars, &mut pos, depth);
+    // Stray closing braces, `&` and `\\right` end a row early; keep what
+    // follows them
+    while pos < chars.len() {
+        pos += match command_at(&chars, pos + 1) {
+            Some(name) if chars[pos] == '\\' => 1 + name.len(),
+            _ if chars[pos] == '\\' => 2,
+            _ => 1,
+        };
+        nodes.extend(parse_row(&chars, &mut pos, depth));
+    }
+    Node::Row(nodes)
+}
+
+/// Whether a row ends at `pos`: a closing brace, `&`, `\\`, `\right`,
+/// `\end` or the end of the formula
+fn at_row_end(chars: &[char], pos: usize) -> bool {
+    match chars.get(pos) {
+        None | Some('}') | Some('&') => true,
+        Some('\\') => {
+            chars.get(pos + 1) == Some(&'\\') || command_at(chars, pos + 1).is_some_and(|c| c == "right" || c == "end")
+        }
+        _ => false,
+    }
+}
+
+/// The command name starting at `pos`, if it is made of letters
+fn command_at(chars: &[char], pos: usize) -> Option<String> {
+    let name: String = chars[pos.min(chars.len())..].iter().take_while(|c| c.is_ascii_alphabetic()).collect();
+    (!name.is_empty()).then_some(name)
+}
+
+fn skip_spaces(chars: &[char], pos: &mut usize) {
+    while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+        *pos += 1;
+    }
+}
+
+/// Parse atoms and their scripts up to the end of the row
+///
+/// `\over` and `\choose` split the row into a fraction.
+fn parse_row(chars: &[char], pos: &mut usize, depth: usize) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut infix: Option<(Vec<Node>, bool)> = None;
+    loop {
+        skip_spaces(chars, pos);
+        if at_row_end(chars, *pos) {
+            break;
+        }
+        let infix_command = (chars[*pos] == '\\').then(|| command_at(chars, *pos + 1)).flatten();
+        if let Some(name) = infix_command.filter(|n| n == "over" || n == "choose") {
+            *pos += 1 + name.len();
+            infix = Some((std::mem::take(&mut nodes), name == "over"));
+            continue;
+        }
+        let atom = match chars[*pos] {
+            '^' | '_' => Node::Row(Vec::new()),
+            _ => parse_atom(chars, pos, depth),
+        };
+        nodes.push(parse_scripts(chars, pos, atom, depth));
+    }
+    match infix {
+        Some((num, line)) => {
+            let frac = Node::Frac { num: Node::Row(num).boxed(), den: Node::Row(nodes).boxed(), line };
+            if line {
+                vec![frac]
+            } else {
+                vec![Node::Fenced { open: "(".into(), body: frac.boxed(), close: ")".into() }]
+            }
+        }
+        None => nodes,
+    }
+}
+
+/// Attach any `^` and `_` scripts following `base`
+fn parse_scripts(chars: &[char], pos: &mut usize, base: Node, depth: usize) -> Node {
+    let (mut sub, mut sup) = (None, None);
+    loop {
+        skip_spaces(chars, pos);
+        match chars.get(*pos) {
+            Some('^') if sup.is_none() => {
+                *pos += 1;
+                sup = Some(parse_argument(chars, pos, depth).boxed());
+            }
+            Some('_') if sub.is_none() => {
+                *pos += 1;
+                sub = Some(parse_argument(chars, pos, depth).boxed());
+            }
+            Some('\'') => {
+                // f' is f^{\prime}
+                let mut primes = String::new();
+                while chars.get(*pos) == Some(&'\'') {
+                    primes.push('′');
+                    *pos += 1;
+                }
+                sup = Some(Node::Operator(primes).boxed());
+            }
+            _ => break,
+        }
+    }
+    if sub.is_none() && sup.is_none() {
+        base
+    } else {
+        Node::Scripts { base: base.boxed(), sub, sup }
+    }
+}
+
+/// Parse a command argument: a braced group, one command or one character
+fn parse_argument(chars: &[char], pos: &mut usize, depth: usize) -> Node {
+    skip_spaces(chars, pos);
+    match chars.get(*pos) {
+        Some('{') | Some('\\') => parse_atom(chars, pos, depth),
+        Some(&c) => {
+            *pos += 1;
+            char_node(c)
+        }
+        None => Node::Row(Vec::new()),
+    }
+}
+
+/// The text of a `{...}` argument, read without parsing it
+fn raw_argument(chars: &[char], pos: &mut usize) -> String {
+    skip_spaces(chars, pos);
+    if chars.get(*pos) != Some(&'{') {
+        return match chars.get(*pos) {
+            Some(&c) => {
+                *pos += 1;
+                c.to_string()
+            }
+            None => String::new(),
+        };
+    }
+    *pos += 1;
+    let mut depth = 0;
+    let mut text = String::new();
+    while let Some(&c) = chars.get(*pos) {
+        *pos += 1;
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => break,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        text.push(c);
+    }
+    text
+}
+
+/// The text of a `[...]` optional argument, if there is one
+fn optional_argument(chars: &[char], pos: &mut usize) -> Option<String> {
+    skip_spaces(chars, pos);
+    if chars.get(*pos) != Some(&'[') {
+        return None;
+    }
+    *pos += 1;
+    let mut depth = 0;
+    let mut text = String::new();
+    while let Some(&c) = chars.get(*pos) {
+        *pos += 1;
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ']' if depth == 0 => break,
+            _ => {}
+        }
+        text.push(c);
+    }
+    Some(text)
+}
+
+fn char_node(c: char) -> Node {
+    match c {
+        '0'..='9' | '.' => Node::Number(c.to_string()),
+        c if c.is_alphabetic() => Node::Ident(c.to_string()),
+        '-' => Node::Operator("−".into()),
+        '*' => Node::Operator("∗".into()),
+        c => Node::Operator(c.to_string()),
+    }
+}
+
+fn parse_atom(chars: &[char], pos: &mut usize, depth: usize) -> Node {
+    if depth > MAX_NESTING {
+        let rest = chars[*pos..].iter().collect();
+        *pos = chars.len();
+        return Node::Text(rest);
+    }
+    let c = chars[*pos];
+    *pos += 1;
+    match c {
+        '{' => {
+            let row = parse_row(chars, pos, depth + 1);
+            if chars.get(*pos) == Some(&'}') {
+                *pos += 1;
+            }
+            Node::Row(row)
+        }
+        '\\' => parse_command(chars, pos, depth + 1),
+        '0'..='9' => {
+            let mut number = c.to_string();
+            while let Some(&next) = chars.get(*pos) {
+                let decimal = next == '.' && chars.get(*pos + 1).is_some_and(|c| c.is_ascii_digit());
+                if !next.is_ascii_digit() && !decimal {
+                    break;
+                }
+                number.push(next);
+                *pos += 1;
+            }
+            Node::Number(number)
+        }
+        c => char_node(c),
+    }
+}
+
+/// Parse the command whose name starts at `pos` (after the backslash)
+fn parse_command(chars: &[char], pos: &mut usize, depth: usize) -> Node {
+    let Some(name) = command_at(chars, *pos) else {
+        // Escaped symbol such as \{ or spacing such as \,
+        let Some(&c) = chars.get(*pos) else {
+            return Node::Operator("\\".into());
+        };
+        *pos += 1;
+        return match c {
+            ',' => Node::Space(0.17),
+            ':' | '>' => Node::Space(0.22),
+            ';' => Node::Space(0.28),
+            '!' => Node::Space(-0.17),
+            ' ' => Node::Space(0.25),
+            '|' => Node::Operator("‖".into()),
+            c => Node::Operator(c.to_string()),
+        };
+    };
+    *pos += name.len();
+
+    if let Some(symbol) = symbol(&name) {
+        return symbol;
+    }
+    let argument = |pos: &mut usize| parse_argument(chars, pos, depth).boxed();
+    match name.as_str() {
+        "frac" | "dfrac" | "tfrac" | "cfrac" => {
+            let num = argument(pos);
+            Node::Frac { num, den: argument(pos), line: true }
+        }
+        "binom" | "dbinom" | "tbinom" => {
+            let num = argument(pos);
+            let frac = Node::Frac { num, den: argument(pos), line: false };
+            Node::Fenced { open: "(".into(), body: frac.boxed(), close: ")".into() }
+        }
+        "sqrt" => {
+            let index = optional_argument(chars, pos).map(|index| parse_nested(&index, depth).boxed());
+            Node::Sqrt { body: argument(pos), index }
+        }
+        "text" | "textrm" | "mbox" | "textnormal" => Node::Text(raw_argument(chars, pos)),
+        "textbf" | "textit" | "texttt" => {
+            let variant = font_variant(&name).unwrap_or("normal");
+            Node::Styled { variant, body: Node::Text(raw_argument(chars, pos)).boxed() }
+        }
+        "operatorname" => Node::Ident(raw_argument(chars, pos)),
+        "overset" | "stackrel" => {
+            let over = argument(pos);
+            Node::Over { base: argument(pos), over, accent: false }
+        }
+        "underset" => {
+            let under = argument(pos);
+            Node::Under { base: argument(pos), under }
+        }
+        "underline" => Node::Under { base: argument(pos), under: Node::Operator("_".into()).boxed() },
+        "underbrace" => Node::Under { base: argument(pos), under: Node::Operator("⏟".into()).boxed() },
+        "overbrace" => Node::Over { base: argument(pos), over: Node::Operator("⏞".into()).boxed(), accent: false },
+        "left" => {
+            let open = delimiter(chars, pos);
+            let body = Node::Row(parse_row(chars, pos, depth));
+            let close = if command_at(chars, *pos + 1).as_deref() == Some("right") {
+                *pos += 1 + "right".len();
+                delimiter(chars, pos)
+            } else {
+                String::new()
+            };
+            Node::Fenced { open, body: body.boxed(), close }
+        }
+        "middle" | "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl" | "Bigr" | "biggl" | "biggr" => {
+            Node::Operator(delimiter(chars, pos))
+        }
+        "begin" => parse_environment(chars, pos, depth),
+        "substack" => {
+            skip_spaces(chars, pos);
+            if chars.get(*pos) == Some(&'{') {
+                *pos += 1;
+            }
+            let rows = parse_rows(chars, pos, depth);
+            if chars.get(*pos) == Some(&'}') {
+                *pos += 1;
+            }
+            Node::Table { rows, left_aligned: false }
+        }
+        "hspace" | "kern" | "mkern" => {
+            let width = raw_argument(chars, pos);
+            let em = width.trim_end_matches(|c: char| c.is_alphabetic()).trim().parse().unwrap_or(0.5);
+            Node::Space(if width.ends_with("mu") { em / 18.0 } else { em })
+        }
+        "vspace" | "color" | "cline" => {
+            raw_argument(chars, pos);
+            Node::Row(Vec::new())
+        }
+        "textcolor" => {
+            raw_argument(chars, pos);
+            Node::Row(vec![*argument(pos)])
+        }
+        "phantom" | "hphantom" | "vphantom" => Node::Phantom(argument(pos)),
+        "boxed" => Node::Boxed(argument(pos)),
+        "tag" => Node::Text(format!("  ({})", raw_argument(chars, pos))),
+        "pmod" => Node::Row(vec![Node::Text(" (mod ".into()), *argument(pos), Node::Operator(")".into())]),
+        "not" => {
+            skip_spaces(chars, pos);
+            if *pos >= chars.len() {
+                return Node::Operator("/".into());
+            }
+            match parse_atom(chars, pos, depth) {
+                Node::Operator(op) => Node::Operator(negate(&op)),
+                other => Node::Row(vec![Node::Operator("/".into()), other]),
+            }
+        }
+        "displaystyle" | "textstyle" | "scriptstyle" | "limits" | "nolimits" | "nonumber" | "hline" | "rm" | "bf"
+        | "it" => Node::Row(Vec::new()),
+        _ => {
+            if let Some(accent) = accent(&name) {
+                return Node::Over { base: argument(pos), over: Node::Operator(accent.into()).boxed(), accent: true };
+            }
+            if let Some(variant) = font_variant(&name) {
+                return Node::Styled { variant, body: argument(pos) };
+            }
+            Node::Text(format!("\\{}", name))
+        }
+    }
+}
+
+/// Rows of an environment or `\substack`, split at `&` and `\\`
+fn parse_rows(chars: &[char], pos: &mut usize, depth: usize) -> Vec<Vec<Node>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    loop {
+        let cell = parse_row(chars, pos, depth);
+        row.push(Node::Row(cell));
+        match chars.get(*pos) {
+            Some('&') => *pos += 1,
+            Some('\\') if chars.get(*pos + 1) == Some(&'\\') => {
+                *pos += 2;
+                optional_argument(chars, pos);
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => break,
+        }
+    }
+    // A trailing \\ leaves an empty last row
+    if row.len() > 1 || row.first().is_some_and(|cell| *cell != Node::Row(Vec::new())) {
+        rows.push(row);
+    }
+    rows
+}
+
+/// Parse `\begin{env}...\end{env}` with `pos` after `\begin`
+fn parse_environment(chars: &[char], pos: &mut usize, depth: usize) -> Node {
+    let env = raw_argument(chars, pos);
+    let env = env.trim().trim_end_matches('*');
+    if env == "array" {
+        raw_argument(chars, pos);
+    }
+    let rows = parse_rows(chars, pos, depth);
+    if command_at(chars, *pos + 1).as_deref() == Some("end") {
+        *pos += 1 + "end".len();
+        raw_argument(chars, pos);
+    }
+    let (open, close) = match env {
+        "pmatrix" => ("(", ")"),
+        "bmatrix" => ("[", "]"),
+        "Bmatrix" => ("{", "}"),
+        "vmatrix" => ("|", "|"),
+        "Vmatrix" => ("‖", "‖"),
+        "cases" => ("{", ""),
+        _ => ("", ""),
+    };
+    let left_aligned = matches!(env, "cases" | "aligned" | "align" | "split" | "array");
+    let table = Node::Table { rows, left_aligned };
+    if open.is_empty() && close.is_empty() {
+        table
+    } else {
+        Node::Fenced { open: open.into(), body: table.boxed(), close: close.into() }
+    }
+}
+
+/// Read the delimiter after `\left`, `\right` or `\big`; `.` is none
+fn delimiter(chars: &[char], pos: &mut usize) -> String {
+    skip_spaces(chars, pos);
+    let Some(&c) = chars.get(*pos) else {
+        return String::new();
+    };
+    *pos += 1;
+    match c {
+        '.' => String::new(),
+        '\\' => match command_at(chars, *pos) {
+            Some(name) => {
+                *pos += name.len();
+                match symbol(&name) {
+                    Some(Node::Operator(op)) | Some(Node::Ident(op)) => op,
+                    _ => String::new(),
+                }
+            }
+            None => {
+                let escaped = chars.get(*pos).copied().unwrap_or('\\');
+                *pos += 1;
+                if escaped == '|' { "‖".into() } else { escaped.to_string() }
+            }
+        },
+        c => c.to_string(),
+    }
+}
+
+/// Put a slash through a relation, as `\not` does
+fn negate(op: &str) -> String {
+    match op {
+        "=" => "≠".into(),
+        "∈" => "∉".into(),
+        "⊂" => "⊄".into(),
+        "⊆" => "⊈".into(),
+        "∣" => "∤".into(),
+        "≡" => "≢".into(),
+        op => format!("{}\u{338}", op),
+    }
+}
+
+/// Font selected by a command, as a MathML `mathvariant`
+fn font_variant(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "mathrm" => "normal",
+        "mathbf" | "textbf" | "boldsymbol" | "bm" => "bold",
+        "mathit" | "textit" => "italic",
+        "mathbb" => "double-struck",
+        "mathcal" | "mathscr" => "script",
+        "mathfrak" => "fraktur",
+        "mathsf" => "sans-serif",
+        "mathtt" | "texttt" => "monospace",
+        _ => return None,
+    })
+}
+
+/// Accent drawn over the argument of a command
+fn accent(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "hat" | "widehat" => "^",
+        "bar" | "overline" => "¯",
+        "vec" | "overrightarrow" => "→",
+        "overleftarrow" => "←",
+        "dot" => "˙",
+        "ddot" => "¨",
+        "tilde" | "widetilde" => "~",
+        "acute" => "´",
+        "grave" => "`",
+        "breve" => "˘",
+        "check" => "ˇ",
+        "mathring" => "˚",
+        _ => return None,
+    })
+}
+
+/// Letters, operators and symbols that take no argument
+fn symbol(name: &str) -> Option<Node> {
+    let ident = |s: &str| Some(Node::Ident(s.to_string()));
+    let op = |s: &str| Some(Node::Operator(s.to_string()));
+    let large = |s: &str, limits| Some(Node::LargeOp { text: s.to_string(), limits });
+    match name {
+        // Greek letters
+        "alpha" => ident("α"),
+        "beta" => ident("β"),
+        "gamma" => ident("γ"),
+        "delta" => ident("δ"),
+        "epsilon" => ident("ϵ"),
+        "varepsilon" => ident("ε"),
+        "zeta" => ident("ζ"),
+        "eta" => ident("η"),
+        "theta" => ident("θ"),
+        "vartheta" => ident("ϑ"),
+        "iota" => ident("ι"),
+        "kappa" => ident("κ"),
+        "lambda" => ident("λ"),
+        "mu" => ident("μ"),
+        "nu" => ident("ν"),
+        "xi" => ident("ξ"),
+        "pi" => ident("π"),
+        "varpi" => ident("ϖ"),
+        "rho" => ident("ρ"),
+        "varrho" => ident("ϱ"),
+        "sigma" => ident("σ"),
+        "varsigma" => ident("ς"),
+        "tau" => ident("τ"),
+        "upsilon" => ident("υ"),
+        "phi" => ident("ϕ"),
+        "varphi" => ident("φ"),
+        "chi" => ident("χ"),
+        "psi" => ident("ψ"),
+        "omega" => ident("ω"),
+        "Gamma" => ident("Γ"),
+        "Delta" => ident("Δ"),
+        "Theta" => ident("Θ"),
+        "Lambda" => ident("Λ"),
+        "Xi" => ident("Ξ"),
+        "Pi" => ident("Π"),
+        "Sigma" => ident("Σ"),
+        "Upsilon" => ident("Υ"),
+        "Phi" => ident("Φ"),
+        "Psi" => ident("Ψ"),
+        "Omega" => ident("Ω"),
+        // Big operators
+        "sum" => large("∑", true),
+        "prod" => large("∏", true),
+        "coprod" => large("∐", true),
+        "bigcup" => large("⋃", true),
+        "bigcap" => large("⋂", true),
+        "bigoplus" => large("⨁", true),
+        "bigotimes" => large("⨂", true),
+        "int" => large("∫", false),
+        "iint" => large("∬", false),
+        "iiint" => large("∭", false),
+        "oint" => large("∮", false),
+        "lim" | "liminf" | "limsup" | "max" | "min" | "sup" | "inf" | "det" | "gcd" | "Pr" => {
+            let text = match name {
+                "liminf" => "lim inf",
+                "limsup" => "lim sup",
+                other => other,
+            };
+            large(text, true)
+        }
+        // Functions
+        "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "arcsin" | "arccos" | "arctan" | "sinh" | "cosh" | "tanh"
+        | "coth" | "log" | "ln" | "lg" | "exp" | "dim" | "ker" | "deg" | "arg" | "bmod" | "mod" => ident(name),
+        // Binary operators
+        "pm" => op("±"),
+        "mp" => op("∓"),
+        "times" => op("×"),
+        "div" => op("÷"),
+        "cdot" => op("·"),
+        "ast" => op("∗"),
+        "star" => op("⋆"),
+        "circ" => op("∘"),
+        "bullet" => op("∙"),
+        "oplus" => op("⊕"),
+        "ominus" => op("⊖"),
+        "otimes" => op("⊗"),
+        "odot" => op("⊙"),
+        "dagger" => op("†"),
+        "cup" => op("∪"),
+        "cap" => op("∩"),
+        "setminus" => op("∖"),
+        "wedge" | "land" => op("∧"),
+        "vee" | "lor" => op("∨"),
+        "neg" | "lnot" => op("¬"),
+        // Relations
+        "eq" => op("="),
+        "neq" | "ne" => op("≠"),
+        "leq" | "le" | "leqslant" => op("≤"),
+        "geq" | "ge" | "geqslant" => op("≥"),
+        "ll" => op("≪"),
+        "gg" => op("≫"),
+        "lt" => op("<"),
+        "gt" => op(">"),
+        "approx" => op("≈"),
+        "sim" => op("∼"),
+        "simeq" => op("≃"),
+        "cong" => op("≅"),
+        "equiv" => op("≡"),
+        "propto" => op("∝"),
+        "doteq" => op("≐"),
+        "triangleq" => op("≜"),
+        "in" => op("∈"),
+        "notin" => op("∉"),
+        "ni" => op("∋"),
+        "subset" => op("⊂"),
+        "subseteq" => op("⊆"),
+        "subsetneq" => op("⊊"),
+        "supset" => op("⊃"),
+        "supseteq" => op("⊇"),
+        "supsetneq" => op("⊋"),
+        "perp" => op("⊥"),
+        "parallel" => op("∥"),
+        "mid" => op("∣"),
+        "nmid" => op("∤"),
+        "prec" => op("≺"),
+        "succ" => op("≻"),
+        "preceq" => op("⪯"),
+        "succeq" => op("⪰"),
+        // Arrows
+        "to" | "rightarrow" => op("→"),
+        "gets" | "leftarrow" => op("←"),
+        "leftrightarrow" => op("↔"),
+        "Rightarrow" => op("⇒"),
+        "Leftarrow" => op("⇐"),
+        "Leftrightarrow" => op("⇔"),
+        "longrightarrow" | "xrightarrow" => op("⟶"),
+        "longleftarrow" | "xleftarrow" => op("⟵"),
+        "Longrightarrow" | "implies" => op("⟹"),
+        "Longleftarrow" | "impliedby" => op("⟸"),
+        "longleftrightarrow" | "iff" => op("⟺"),
+        "mapsto" => op("↦"),
+        "uparrow" => op("↑"),
+        "downarrow" => op("↓"),
+        "updownarrow" => op("↕"),
+        "Uparrow" => op("⇑"),
+        "Downarrow" => op("⇓"),
+        "nearrow" => op("↗"),
+        "searrow" => op("↘"),
+        "rightleftharpoons" => op("⇌"),
+        "rightharpoonup" => op("⇀"),
+        // Delimiters
+        "langle" => op("⟨"),
+        "rangle" => op("⟩"),
+        "lfloor" => op("⌊"),
+        "rfloor" => op("⌋"),
+        "lceil" => op("⌈"),
+        "rceil" => op("⌉"),
+        "vert" | "lvert" | "rvert" => op("|"),
+        "Vert" | "lVert" | "rVert" => op("‖"),
+        "lbrace" => op("{"),
+        "rbrace" => op("}"),
+        "backslash" => op("∖"),
+        // Other symbols
+        "infty" => ident("∞"),
+        "partial" => ident("∂"),
+        "nabla" => ident("∇"),
+        "forall" => op("∀"),
+        "exists" => op("∃"),
+        "nexists" => op("∄"),
+        "emptyset" => ident("∅"),
+        "varnothing" => ident("⌀"),
+        "angle" => ident("∠"),
+        "measuredangle" => ident("∡"),
+        "triangle" => ident("△"),
+        "square" | "Box" => ident("□"),
+        "blacksquare" => ident("■"),
+        "degree" => ident("°"),
+        "prime" => ident("′"),
+        "ell" => ident("ℓ"),
+        "hbar" => ident("ℏ"),
+        "imath" => ident("ı"),
+        "jmath" => ident("ȷ"),
+        "Re" => ident("ℜ"),
+        "Im" => ident("ℑ"),
+        "aleph" => ident("ℵ"),
+        "wp" => ident("℘"),
+        "checkmark" => ident("✓"),
+        "cdots" => op("⋯"),
+        "ldots" | "dots" => op("…"),
+        "vdots" => op("⋮"),
+        "ddots" => op("⋱"),
+        "because" => op("∵"),
+        "therefore" => op("∴"),
+        "S" => ident("§"),
+        "P" => ident("¶"),
+        "quad" => Some(Node::Space(1.0)),
+        "qquad" => Some(Node::Space(2.0)),
+        "enspace" => Some(Node::Space(0.5)),
+        "thinspace" => Some(Node::Space(0.17)),
+        _ => None,
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Write `node` as MathML, applying `variant` to its tokens
+fn write_mathml(node: &Node, display: bool, variant: Option<&str>, out: &mut String) {
+    let token = |tag: &str, text: &str, out: &mut String| {
+        match variant {
+            Some(variant) => out.push_str(&format!("<{} mathvariant=\"{}\">", tag, variant)),
+            None => out.push_str(&format!("<{}>", tag)),
+        }
+        out.push_str(&escape_xml(text));
+        out.push_str(&format!("</{}>", tag));
+    };
+    let child = |node: &Node, out: &mut String| {
+        out.push_str("<mrow>");
+        write_mathml(node, display, variant, out);
+        out.push_str("</mrow>");
+    };
+    match node {
+        Node::Ident(text) => token("mi", text, out),
+        Node::Number(text) => token("mn", text, out),
+        Node::Operator(text) => token("mo", text, out),
+        Node::Text(text) => token("mtext", text, out),
+        Node::LargeOp { text, limits } => {
+            let movable = if *limits { "true" } else { "false" };
+            out.push_str(&format!("<mo largeop=\"true\" movablelimits=\"{}\">{}</mo>", movable, escape_xml(text)));
+        }
+        Node::Row(nodes) => {
+            for node in nodes {
+                write_mathml(node, display, variant, out);
+            }
+        }
+        Node::Frac { num, den, line } => {
+            out.push_str(if *line { "<mfrac>" } else { "<mfrac linethickness=\"0\">" });
+            child(num, out);
+            child(den, out);
+            out.push_str("</mfrac>");
+        }
+        Node::Sqrt { body, index: None } => {
+            out.push_str("<msqrt>");
+            write_mathml(body, display, variant, out);
+            out.push_str("</msqrt>");
+        }
+        Node::Sqrt { body, index: Some(index) } => {
+            out.push_str("<mroot>");
+            child(body, out);
+            child(index, out);
+            out.push_str("</mroot>");
+        }
+        Node::Scripts { base, sub, sup } => {
+            let limits = display && matches!(**base, Node::LargeOp { limits: true, .. });
+            let tag = match (sub.is_some(), sup.is_some(), limits) {
+                (true, true, true) => "munderover",
+                (true, false, true) => "munder",
+                (false, true, true) => "mover",
+                (true, true, false) => "msubsup",
+                (true, false, false) => "msub",
+                _ => "msup",
+            };
+            out.push_str(&format!("<{}>", tag));
+            child(base, out);
+            for script in [sub, sup].into_iter().flatten() {
+                child(script, out);
+            }
+            out.push_str(&format!("</{}>", tag));
+        }
+        Node::Over { base, over, accent } => {
+            out.push_str(if *accent { "<mover accent=\"true\">" } else { "<mover>" });
+            child(base, out);
+            child(over, out);
+            out.push_str("</mover>");
+        }
+        Node::Under { base, under } => {
+            out.push_str("<munder>");
+            child(base, out);
+            child(under, out);
+            out.push_str("</munder>");
+        }
+        Node::Fenced { open, body, close } => {
+            let fence = |text: &str, out: &mut String| {
+                if !text.is_empty() {
+                    out.push_str(&format!("<mo fence=\"true\" stretchy=\"true\">{}</mo>", escape_xml(text)));
+                }
+            };
+            out.push_str("<mrow>");
+            fence(open, out);
+            write_mathml(body, display, variant, out);
+            fence(close, out);
+            out.push_str("</mrow>");
+        }
+        Node::Table { rows, left_aligned } => {
+            out.push_str(if *left_aligned { "<mtable columnalign=\"left\">" } else { "<mtable>" });
+            for row in rows {
+                out.push_str("<mtr>");
+                for cell in row {
+                    out.push_str("<mtd>");
+                    write_mathml(cell, display, variant, out);
+                    out.push_str("</mtd>");
+                }
+                out.push_str("</mtr>");
+            }
+            out.push_str("</mtable>");
+        }
+        Node::Space(em) => out.push_str(&format!("<mspace width=\"{}em\"/>", num(*em))),
+        Node::Styled { variant, body } => write_mathml(body, display, Some(variant), out),
+        Node::Phantom(body) => {
+            out.push_str("<mphantom>");
+            write_mathml(body, display, variant, out);
+            out.push_str("</mphantom>");
+        }
+        Node::Boxed(body) => {
+            out.push_str("<menclose notation=\"box\">");
+            write_mathml(body, display, variant, out);
+            out.push_str("</menclose>");
+        }
+    }
+}
+
+/// Font size of SVG output, in pixels
+const FONT_SIZE: f32 = 20.0;
+/// Height of the fraction bar above the baseline, in em
+const AXIS: f32 = 0.25;
+/// Size of scripts relative to their base
+const SCRIPT_SCALE: f32 = 0.7;
+
+/// Operators with space on both sides
+const SPACED_OPERATORS: &str = "+−=<>±∓×÷·∗⋆∘∙⊕⊖⊗⊙∪∩∖∧∨≠≤≥≪≫≈∼≃≅≡∝≐≜∈∉∋⊂⊆⊊⊃⊇⊋⊄⊈⊥∥∣∤≺≻⪯⪰→←↔⇒⇐⇔⟶⟵⟹⟸⟺↦⇌⇀≢";
+
+/// Something drawn, relative to the baseline origin of its box
+#[derive(Debug, Clone)]
+enum Item {
+    Glyph { x: f32, y: f32, size: f32, text: String, italic: bool, bold: bool },
+    Rule { x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32 },
+}
+
+/// A laid out piece of a formula; y grows downwards from the baseline
+#[derive(Debug, Clone, Default)]
+struct LayoutBox {
+    width: f32,
+    /// Extent above the baseline
+    ascent: f32,
+    /// Extent below the baseline
+    descent: f32,
+    items: Vec<Item>,
+}
+
+impl LayoutBox {
+    fn glyph(text: &str, size: f32, italic: bool, bold: bool) -> Self {
+        LayoutBox {
+            width: text_width(text) * size,
+            ascent: 0.75 * size,
+            descent: 0.25 * size,
+            items: vec![Item::Glyph { x: 0.0, y: 0.0, size, text: text.to_string(), italic, bold }],
+        }
+    }
+
+    fn space(width: f32) -> Self {
+        LayoutBox { width, ..Default::default() }
+    }
+
+    fn rule(&mut self, (x1, y1): (f32, f32), (x2, y2): (f32, f32), size: f32) {
+        self.items.push(Item::Rule { x1, y1, x2, y2, thickness: 0.05 * size });
+    }
+
+    /// Copy `other` into this box with its origin at (`dx`, `dy`)
+    fn place(&mut self, other: LayoutBox, dx: f32, dy: f32) {
+        self.ascent = self.ascent.max(other.ascent - dy);
+        self.descent = self.descent.max(other.descent + dy);
+        self.width = self.width.max(other.width + dx);
+        self.items.extend(other.items.into_iter().map(|item| match item {
+            Item::Glyph { x, y, size, text, italic, bold } => Item::Glyph { x: x + dx, y: y + dy, size, text, italic, bold },
+            Item::Rule { x1, y1, x2, y2, thickness } => Item::Rule {
+                x1: x1 + dx,
+                y1: y1 + dy,
+                x2: x2 + dx,
+                y2: y2 + dy,
+                thickness,
+            },
+        }));
+    }
+
+    /// Lay boxes out side by side on a shared baseline
+    fn row(boxes: impl IntoIterator<Item = LayoutBox>) -> Self {
+        let mut row = LayoutBox::default();
+        for other in boxes {
+            let x = row.width;
+            row.place(other, x, 0.0);
+        }
+        row
+    }
+}
+
+/// Approximate advance width of `text` in em
+fn text_width(text: &str) -> f32 {
+    text.chars()
+        .map(|c| match c {
+            ' ' => 0.25,
+            'i' | 'j' | 'l' | '.' | ',' | ';' | ':' | '!' | '\'' | '|' | '′' => 0.3,
+            'f' | 't' | 'r' | '(' | ')' | '[' | ']' | '{' | '}' => 0.4,
+            'm' | 'w' | 'M' | 'W' => 0.85,
+            'a'..='z' => 0.5,
+            'A'..='Z' | '0'..='9' => 0.6,
+            c if (c as u32) >= 0x2E80 && !('\u{FE00}'..='\u{FE0F}').contains(&c) => 1.0,
+            '\u{300}'..='\u{36F}' => 0.0,
+            _ => 0.75,
+        })
+        .sum()
+}
+
+/// Map letters to their double-struck forms (ℝ, ℕ, ...)
+fn double_struck(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'C' => 'ℂ',
+            'H' => 'ℍ',
+            'N' => 'ℕ',
+            'P' => 'ℙ',
+            'Q' => 'ℚ',
+            'R' => 'ℝ',
+            'Z' => 'ℤ',
+            'A'..='Z' => char::from_u32(0x1D538 + (c as u32 - 'A' as u32)).unwrap_or(c),
+            'a'..='z' => char::from_u32(0x1D552 + (c as u32 - 'a' as u32)).unwrap_or(c),
+            c => c,
+        })
+        .collect()
+}
+
+fn layout(node: &Node, size: f32, display: bool, variant: Option<&'static str>) -> LayoutBox {
+    let bold = variant == Some("bold");
+    let text = |text: &str| match variant {
+        Some("double-struck") => double_struck(text),
+        _ => text.to_string(),
+    };
+    match node {
+        Node::Ident(name) => {
+            let italic = match variant {
+                Some("italic") => true,
+                None | Some("bold") => name.chars().count() == 1 && name.chars().all(char::is_alphabetic),
+                _ => false,
+            };
+            let mut glyph = LayoutBox::glyph(&text(name), size, italic, bold);
+            if name.chars().count() > 1 {
+                // Function names are set apart from their arguments
+                glyph.width += 0.17 * size;
+            }
+            glyph
+        }
+        Node::Number(value) => LayoutBox::glyph(&text(value), size, variant == Some("italic"), bold),
+        Node::Text(value) => LayoutBox::glyph(value, size, variant == Some("italic"), bold),
+        Node::Operator(op) => {
+            let glyph = LayoutBox::glyph(op, size, false, bold);
+            let pad = if SPACED_OPERATORS.contains(op.as_str()) {
+                0.22 * size
+            } else if op == "," || op == ";" {
+                return LayoutBox::row([glyph, LayoutBox::space(0.17 * size)]);
+            } else {
+                0.0
+            };
+            LayoutBox::row([LayoutBox::space(pad), glyph, LayoutBox::space(pad)])
+        }
+        Node::LargeOp { text, limits } => {
+            if text.chars().count() > 1 {
+                let mut glyph = LayoutBox::glyph(text, size, false, bold);
+                glyph.width += 0.17 * size;
+                return glyph;
+            }
+            let scale = if display && *limits { 1.6 } else { 1.3 };
+            let mut glyph = LayoutBox::glyph(text, size * scale, false, bold);
+            // Center the enlarged symbol on the math axis
+            let shift = (glyph.ascent - glyph.descent) / 2.0 - AXIS * size;
+            let mut centered = LayoutBox::space(0.1 * size);
+            glyph.width += 0.1 * size;
+            centered.place(glyph, 0.1 * size, shift);
+            centered
+        }
+        Node::Row(nodes) => LayoutBox::row(nodes.iter().map(|n| layout(n, size, display, variant))),
+        Node::Frac { num, den, line } => {
+            let inner = if display { size } else { size * 0.85 };
+            let num = layout(num, inner, display, variant);
+            let den = layout(den, inner, display, variant);
+            let pad = 0.12 * size;
+            let width = num.width.max(den.width) + 2.0 * pad;
+            let axis = AXIS * size;
+            let gap = 0.15 * size;
+            let mut frac = LayoutBox::space(width);
+            let (num_x, den_x) = ((width - num.width) / 2.0, (width - den.width) / 2.0);
+            let num_y = -(axis + gap + num.descent);
+            let den_y = -axis + gap + den.ascent;
+            frac.place(num, num_x, num_y);
+            frac.place(den, den_x, den_y);
+            if *line {
+                frac.rule((pad / 2.0, -axis), (width - pad / 2.0, -axis), size);
+            }
+            frac
+        }
+        Node::Sqrt { body, index } => {
+            let body = layout(body, size, display, variant);
+            let gap = 0.12 * size;
+            let top = -(body.ascent + gap);
+            let bottom = body.descent;
+            let height = bottom - top;
+            let index = index.as_ref().map(|index| layout(index, size * 0.6, display, variant));
+            let offset = index.as_ref().map_or(0.0, |index| (index.width - 0.25 * size).max(0.0));
+            let mut root = LayoutBox::default();
+            let body_x = offset + 0.55 * size;
+            let end = body_x + body.width + 0.1 * size;
+            root.rule((offset, bottom - 0.4 * height), (offset + 0.2 * size, bottom), size);
+            root.rule((offset + 0.2 * size, bottom), (offset + 0.5 * size, top), size);
+            root.rule((offset + 0.5 * size, top), (end, top), size);
+            root.place(body, body_x, 0.0);
+            if let Some(index) = index {
+                root.place(index, 0.0, bottom - 0.55 * height);
+            }
+            root.ascent = root.ascent.max(-top + 0.05 * size);
+            root
+        }
+        Node::Scripts { base, sub, sup } => {
+            let limits = display && matches!(**base, Node::LargeOp { limits: true, .. });
+            let base = layout(base, size, display, variant);
+            let script_size = size * SCRIPT_SCALE;
+            let sub = sub.as_ref().map(|s| layout(s, script_size, false, variant));
+            let sup = sup.as_ref().map(|s| layout(s, script_size, false, variant));
+            if limits {
+                let width = base.width.max(sub.as_ref().map_or(0.0, |s| s.width)).max(sup.as_ref().map_or(0.0, |s| s.width));
+                let mut stacked = LayoutBox::space(width);
+                if let Some(sup) = sup {
+                    let y = -(base.ascent + sup.descent + 0.05 * size);
+                    stacked.place(sup.clone(), (width - sup.width) / 2.0, y);
+                }
+                if let Some(sub) = sub {
+                    let y = base.descent + sub.ascent + 0.05 * size;
+                    stacked.place(sub.clone(), (width - sub.width) / 2.0, y);
+                }
+                let x = (width - base.width) / 2.0;
+                stacked.place(base, x, 0.0);
+                return stacked;
+            }
+            let x = base.width;
+            let sup_y = -(base.ascent - 0.35 * size).max(0.4 * size);
+            let sub_y = (base.descent + 0.05 * size).max(0.2 * size);
+            let mut scripted = base;
+            let mut width = x;
+            if let Some(sup) = sup {
+                width = width.max(x + sup.width);
+                scripted.place(sup, x, sup_y);
+            }
+            if let Some(sub) = sub {
+                width = width.max(x + sub.width);
+                scripted.place(sub, x, sub_y);
+            }
+            scripted.width = width + 0.05 * size;
+            scripted
+        }
+        Node::Over { base, over, accent } => {
+            let base = layout(base, size, display, variant);
+            let mut stacked = LayoutBox::default();
+            let width = base.width;
+            if *accent && **over == Node::Operator("¯".into()) {
+                let y = -(base.ascent + 0.08 * size);
+                stacked.rule((0.05 * size, y), (width - 0.05 * size, y), size);
+                stacked.place(base, 0.0, 0.0);
+                stacked.ascent += 0.05 * size;
+                return stacked;
+            }
+            let over = layout(over, if *accent { size } else { size * SCRIPT_SCALE }, display, variant);
+            let width = width.max(over.width);
+            let y = if *accent { -(base.ascent - 0.45 * size) } else { -(base.ascent + over.descent + 0.05 * size) };
+            stacked.place(over.clone(), (width - over.width) / 2.0, y);
+            stacked.place(base.clone(), (width - base.width) / 2.0, 0.0);
+            stacked
+        }
+        Node::Under { base, under } => {
+            let base = layout(base, size, display, variant);
+            let mut stacked = LayoutBox::default();
+            if **under == Node::Operator("_".into()) {
+                let y = base.descent + 0.08 * size;
+                stacked.rule((0.0, y), (base.width, y), size);
+                stacked.place(base, 0.0, 0.0);
+                stacked.descent += 0.05 * size;
+                return stacked;
+            }
+            let under = layout(under, size * SCRIPT_SCALE, display, variant);
+            let width = base.width.max(under.width);
+            let y = base.descent + under.ascent + 0.05 * size;
+            stacked.place(under.clone(), (width - under.width) / 2.0, y);
+            stacked.place(base.clone(), (width - base.width) / 2.0, 0.0);
+            stacked
+        }
+        Node::Fenced { open, body, close } => {
+            let body = layout(body, size, display, variant);
+            let height = (body.ascent + body.descent).max(size);
+            // A glyph of font size `height` spans exactly the body
+            let fence = |text: &str| {
+                if text.is_empty() {
+                    return LayoutBox::default();
+                }
+                let mut glyph = LayoutBox::glyph(text, height, false, false);
+                glyph.width = glyph.width.min(0.5 * size) + 0.05 * size;
+                let mut fenced = LayoutBox::default();
+                fenced.place(glyph, 0.0, body.descent.max(0.25 * size) - 0.25 * height);
+                fenced
+            };
+            LayoutBox::row([fence(open), body.clone(), fence(close)])
+        }
+        Node::Table { rows, left_aligned } => {
+            let cells: Vec<Vec<LayoutBox>> = rows
+                .iter()
+                .map(|row| row.iter().map(|cell| layout(cell, size, display, variant)).collect())
+                .collect();
+            let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+            let widths: Vec<f32> = (0..columns)
+                .map(|col| cells.iter().filter_map(|row| row.get(col)).map(|c| c.width).fold(0.0, f32::max))
+                .collect();
+            let (col_gap, row_gap) = (0.8 * size, 0.3 * size);
+            let heights: Vec<(f32, f32)> = cells
+                .iter()
+                .map(|row| {
+                    let ascent = row.iter().map(|c| c.ascent).fold(0.75 * size, f32::max);
+                    let descent = row.iter().map(|c| c.descent).fold(0.25 * size, f32::max);
+                    (ascent, descent)
+                })
+                .collect();
+            let total: f32 = heights.iter().map(|(a, d)| a + d).sum::<f32>() + row_gap * (heights.len().max(1) - 1) as f32;
+            let mut table = LayoutBox::default();
+            let mut y = -(total / 2.0 + AXIS * size);
+            for (row, (ascent, descent)) in cells.into_iter().zip(heights) {
+                let baseline = y + ascent;
+                let mut x = 0.0;
+                for (col, cell) in row.into_iter().enumerate() {
+                    let dx = if *left_aligned { 0.0 } else { (widths[col] - cell.width) / 2.0 };
+                    table.place(cell, x + dx, baseline);
+                    x += widths[col] + col_gap;
+                }
+                y = baseline + descent + row_gap;
+            }
+            table.width = widths.iter().sum::<f32>() + col_gap * columns.saturating_sub(1) as f32;
+            table.ascent = table.ascent.max(total / 2.0 + AXIS * size);
+            table.descent = table.descent.max(total / 2.0 - AXIS * size);
+            table
+        }
+        Node::Space(em) => LayoutBox::space(em * size),
+        Node::Styled { variant, body } => layout(body, size, display, Some(variant)),
+        Node::Phantom(body) => {
+            let mut phantom = layout(body, size, display, variant);
+            phantom.items.clear();
+            phantom
+        }
+        Node::Boxed(body) => {
+            let body = layout(body, size, display, variant);
+            let pad = 0.15 * size;
+            let (left, right) = (0.0, body.width + 2.0 * pad);
+            let (top, bottom) = (-(body.ascent + pad), body.descent + pad);
+            let mut boxed = LayoutBox::default();
+            boxed.rule((left, top), (right, top), size);
+            boxed.rule((right, top), (right, bottom), size);
+            boxed.rule((right, bottom), (left, bottom), size);
+            boxed.rule((left, bottom), (left, top), size);
+            boxed.place(body, pad, 0.0);
+            boxed.width = right;
+            boxed.ascent = -top;
+            boxed.descent = bottom;
+            boxed
+        }
+    }
+}
+
+/// Format a coordinate with at most two decimals
+fn num(value: f32) -> String {
+    let formatted = format!("{:.2}", value);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::FsMediaStore;
+
+    fn inner(formula: &str) -> String {
+        let mathml = to_mathml(formula);
+        let start = mathml.find("<mrow>").unwrap() + "<mrow>".len();
+        let end = mathml.rfind("</mrow><annotation").unwrap();
+        mathml[start..end].to_string()
+    }
+
+    #[test]
+    fn test_mathml_tokens_and_scripts() {
+        assert_eq!(inner("$x^2 + 1$"), "<msup><mrow><mi>x</mi></mrow><mrow><mn>2</mn></mrow></msup><mo>+</mo><mn>1</mn>");
+        assert_eq!(inner("$a_{n-1}$"), "<msub><mrow><mi>a</mi></mrow><mrow><mi>n</mi><mo>−</mo><mn>1</mn></mrow></msub>");
+        assert_eq!(inner("\\alpha \\leq 3.14"), "<mi>α</mi><mo>≤</mo><mn>3.14</mn>");
+        assert_eq!(inner("$\\sin x$"), "<mi>sin</mi><mi>x</mi>");
+        assert_eq!(inner("$f'$"), "<msup><mrow><mi>f</mi></mrow><mrow><mo>′</mo></mrow></msup>");
+        assert_eq!(inner("$a \\not= b$"), "<mi>a</mi><mo>≠</mo><mi>b</mi>");
+        assert_eq!(inner("$\\mathbb{R}$"), "<mi mathvariant=\"double-struck\">R</mi>");
+        assert_eq!(inner("$\\text{km}<1$"), "<mtext>km</mtext><mo>&lt;</mo><mn>1</mn>");
+    }
+
+    #[test]
+    fn test_mathml_structures() {
+        assert_eq!(
+            inner("\\frac12"),
+            "<mfrac><mrow><mn>1</mn></mrow><mrow><mn>2</mn></mrow></mfrac>"
+        );
+        assert_eq!(inner("\\sqrt[3]{x}"), "<mroot><mrow><mi>x</mi></mrow><mrow><mn>3</mn></mrow></mroot>");
+        assert_eq!(inner("\\vec{v}"), "<mover accent=\"true\"><mrow><mi>v</mi></mrow><mrow><mo>→</mo></mrow></mover>");
+        assert_eq!(
+            inner("\\left( x \\right]"),
+            "<mrow><mo fence=\"true\" stretchy=\"true\">(</mo><mi>x</mi><mo fence=\"true\" stretchy=\"true\">]</mo></mrow>"
+        );
+        assert_eq!(
+            inner("\\begin{pmatrix} 1 & 0 \\\\ 0 & 1 \\end{pmatrix}"),
+            "<mrow><mo fence=\"true\" stretchy=\"true\">(</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>0</mn></mtd></mtr>\
+             <mtr><mtd><mn>0</mn></mtd><mtd><mn>1</mn></mtd></mtr></mtable><mo fence=\"true\" stretchy=\"true\">)</mo></mrow>"
+        );
+
+        // Limits go above and below big operators only in display formulas
+        assert!(inner("$$\\sum_{i=1}^n i$$").starts_with("<munderover><mrow><mo largeop=\"true\""));
+        assert!(inner("$\\sum_{i=1}^n i$").starts_with("<msubsup>"));
+        assert!(to_mathml("$$x$$").contains("display=\"block\""));
+        assert!(to_mathml("$a<b$").ends_with("<annotation encoding=\"application/x-tex\">a&lt;b</annotation></semantics></math>"));
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_input() {
+        assert_eq!(inner("$\\foo x$"), "<mtext>\\foo</mtext><mi>x</mi>");
+        for formula in ["$\\frac{1}{2$", "$x}}^$", "\\left(", "\\begin{matrix} 1 &", "\\sqrt[", "\\", "^_", "{{{"] {
+            assert!(to_mathml(formula).ends_with("</math>"), "{}", formula);
+            assert!(to_svg(formula).ends_with("</svg>"), "{}", formula);
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting_is_shown_as_source() {
+        let shallow = format!("{}x{}", "{".repeat(10), "}".repeat(10));
+        assert!(inner(&shallow).contains("<mi>x</mi>"));
+
+        // Test threads have the 2 MiB stack of a Tokio worker
+        let braces = format!("${}x{}$", "{".repeat(20_000), "}".repeat(20_000));
+        let fractions = format!("${}1$", "\\frac".repeat(50_000));
+        let roots = format!("${}x$", "\\sqrt[".repeat(5_000));
+        for formula in [&braces, &fractions, &roots] {
+            let mathml = to_mathml(formula);
+            assert!(mathml.ends_with("</math>"));
+            assert!(to_svg(formula).ends_with("</svg>"));
+        }
+        // Past the limit the rest of the formula is text
+        let text = format!("<mtext>{}", "{".repeat(100));
+        assert!(inner(&braces).contains(&text));
+    }
+
+    #[test]
+    fn test_svg_layout() {
+        let svg = to_svg("$\\frac{a}{b}$");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("font-style=\"italic\">a</text>"));
+        assert!(svg.contains("<line "), "fraction bar: {}", svg);
+        assert!(svg.contains("<title>\\frac{a}{b}</title>"));
+
+        // Stacked content is taller than a single line
+        let height = |svg: &str| -> f32 {
+            let start = svg.find("height=\"").unwrap() + 8;
+            svg[start..].split('"').next().unwrap().parse().unwrap()
+        };
+        assert!(height(&svg) > height(&to_svg("$ab$")));
+        assert!(to_svg("$\\mathbb{R}$").contains(">ℝ</text>"));
+    }
+
+    #[tokio::test]
+    async fn test_render_question() {
+        let question = Question {
+            latex: vec!["$x^2$".to_string(), " $x^2$".to_string(), "$$y$$ % ocr: a.png".to_string()],
+            ..Question::default()
+        };
+        let inline = FormulaRenderer::new(FormulaFormat::MathMl).render_question(&question).await.unwrap();
+        assert_eq!(inline.len(), 2);
+        assert!(inline[0].content.as_deref().unwrap().contains("<msup>"));
+        assert!(inline[1].content.as_deref().unwrap().contains("display=\"block\""));
+        assert_eq!(inline[0].hash, None);
+
+        let root = tempfile::tempdir().unwrap();
+        let store = Arc::new(FsMediaStore::new(root.path()).unwrap());
+        let renderer = FormulaRenderer::new(FormulaFormat::Svg).with_media_store(store.clone());
+        let stored = renderer.render_question(&question).await.unwrap();
+        assert_eq!(stored[0].content, None);
+        let path = stored[0].path.as_deref().unwrap();
+        assert!(path.ends_with(".svg"), "{}", path);
+        let data = store.get(stored[0].hash.as_deref().unwrap()).await.unwrap().unwrap();
+        assert_eq!(String::from_utf8(data).unwrap(), to_svg("$x^2$"));
+
+        // Rendering again reuses the stored artifact
+        let again = renderer.render_question(&question).await.unwrap();
+        assert_eq!(again, stored);
+    }
+}
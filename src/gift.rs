@@ -0,0 +1,607 @@
+//! Moodle GIFT import and export
+//!
+//! GIFT is the plain-text question format Moodle imports and exports. Each
+//! question is a block of text separated from the next by a blank line, with
+//! its answers in braces:
+//!
+//! ```text
+//! // [tag:arithmetic]
+//! ::Q1::What is 2+2? {
+//!     ~3
+//!     =4
+//!     ####4 is 2 doubled.
+//! }
+//! ```
+//!
+//! [`parse_gift`] reads single and multiple choice (`=`/`~`, `~%50%`
+//! weights), true/false, short answer, numerical and essay questions;
+//! matching questions have no equivalent here and are skipped with a
+//! warning. Titles, per-answer feedback and `$CATEGORY` lines are dropped,
+//! and the general feedback (`####`) becomes the analysis. [`write_gift`]
+//! goes the other way for stored questions.
+
+use crate::answer::{FALSE_WORDS, TRUE_WORDS};
+use crate::bidi::{self, export_text};
+use crate::models::{Provenance, Question, QuestionOption, QuestionType};
+use crate::render::strip_option_prefix;
+use anyhow::{anyhow, bail, Result};
+
+/// Characters GIFT reserves, written with a backslash inside text
+const RESERVED: &[char] = &['\\', '~', '=', '#', '{', '}', ':'];
+
+/// Stand-in for the blank of a missing-word question
+const BLANK: &str = "____";
+
+/// Result of reading one GIFT file
+#[derive(Debug, Default)]
+pub struct GiftImport {
+    /// Questions in file order
+    pub questions: Vec<Question>,
+    /// Skipped or unsupported questions
+    pub warnings: Vec<String>,
+}
+
+/// Parse a GIFT file; `source` is recorded as the questions' source file
+pub fn parse_gift(text: &str, source: &str) -> GiftImport {
+    let mut import = GiftImport::default();
+    for (number, (block, tags)) in blocks(text).into_iter().enumerate() {
+        match parse_question(&block) {
+            Ok(Some(mut question)) => {
+                question.tags = tags;
+                question.provenance = Some(Provenance {
+                    source_file: Some(source.to_string()),
+                    ..Provenance::default()
+                });
+                question.sequence = Some(import.questions.len() as u64 + 1);
+                bidi::normalize_question(&mut question);
+                import.questions.push(question);
+            }
+            Ok(None) => import
+                .warnings
+                .push(format!("{} question {}: description without answers, skipped", source, number + 1)),
+            Err(e) => import
+                .warnings
+                .push(format!("{} question {}: {}, skipped", source, number + 1, e)),
+        }
+    }
+    import
+}
+
+/// Split a file into question blocks, each with the tags from its comments
+///
+/// A blank line ends a question unless it falls inside an answer block.
+fn blocks(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut blocks = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    let mut tags = Vec::new();
+    let mut depth = 0i32;
+
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("//") {
+            tags.extend(comment_tags(trimmed));
+            continue;
+        }
+        if lines.is_empty() && trimmed.starts_with("$CATEGORY:") {
+            continue;
+        }
+        if trimmed.is_empty() && depth <= 0 {
+            if !lines.is_empty() {
+                blocks.push((lines.join("\n"), std::mem::take(&mut tags)));
+                lines.clear();
+            }
+            continue;
+        }
+        depth += unescaped(line)
+            .map(|(_, c)| match c {
+                '{' => 1,
+                '}' => -1,
+                _ => 0,
+            })
+            .sum::<i32>();
+        lines.push(line);
+    }
+    if !lines.is_empty() {
+        blocks.push((lines.join("\n"), tags));
+    }
+    blocks
+}
+
+/// Tags from a `// [tag:name]` comment, as Moodle writes them
+fn comment_tags(comment: &str) -> Vec<String> {
+    comment
+        .split("[tag:")
+        .skip(1)
+        .filter_map(|rest| rest.split_once(']'))
+        .map(|(tag, _)| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Parse one question block; `None` for a description (no answers)
+fn parse_question(block: &str) -> Result<Option<Question>> {
+    let mut text = block.trim();
+
+    // ::Title::
+    if let Some(rest) = text.strip_prefix("::") {
+        let end = find_unescaped(rest, "::").ok_or_else(|| anyhow!("unclosed title"))?;
+        text = rest[end + 2..].trim_start();
+    }
+    // [markdown], [html], [plain] or [moodle] text format
+    if let Some(rest) = text.strip_prefix('[') {
+        if let Some((format, rest)) = rest.split_once(']') {
+            if matches!(format, "markdown" | "html" | "plain" | "moodle") {
+                text = rest.trim_start();
+            }
+        }
+    }
+
+    let Some(open) = find_unescaped(text, "{") else {
+        return Ok(None);
+    };
+    let close = find_unescaped(&text[open..], "}").ok_or_else(|| anyhow!("unclosed answer block"))? + open;
+    let (before, after) = (&text[..open], &text[close + 1..]);
+    let stem = if after.trim().is_empty() {
+        unescape(before.trim())
+    } else {
+        unescape(format!("{}{}{}", before, BLANK, after).trim())
+    };
+    if stem.is_empty() {
+        bail!("no question text");
+    }
+
+    let mut body = &text[open + 1..close];
+    let mut question = Question {
+        stem,
+        ..Question::default()
+    };
+    if let Some(feedback) = find_unescaped(body, "####") {
+        question.analysis = Some(unescape(body[feedback + 4..].trim())).filter(|a| !a.is_empty());
+        body = &body[..feedback];
+    }
+    let body = body.trim();
+
+    if body.is_empty() {
+        question.qtype = QuestionType::Subjective;
+    } else if let Some(numeric) = body.strip_prefix('#') {
+        question.qtype = QuestionType::FillInTheBlank;
+        question.answer = Some(numerical_answer(numeric)?);
+    } else if let Some(value) = true_false(body) {
+        question.qtype = QuestionType::TrueFalse;
+        question.answer = Some(if value { "True" } else { "False" }.to_string());
+    } else {
+        let answers = answers(body)?;
+        if answers.iter().all(|a| a.exact) {
+            question.qtype = QuestionType::FillInTheBlank;
+            question.answer = Some(answers.into_iter().map(|a| a.text).collect::<Vec<_>>().join("; "));
+        } else {
+            question.options = answers
+                .iter()
+                .enumerate()
+                .map(|(i, a)| QuestionOption {
+                    content: a.text.clone(),
+                    sort_order: i as i32,
+                    is_correct: a.exact || a.weight.is_some_and(|w| w > 0.0),
+                })
+                .collect();
+            let letters: String = question
+                .options
+                .iter()
+                .enumerate()
+                .filter(|(_, o)| o.is_correct)
+                .map(|(i, _)| (b'A' + (i % 26) as u8) as char)
+                .collect();
+            question.qtype = if letters.len() > 1 {
+                QuestionType::MultipleChoice
+            } else {
+                QuestionType::Choice
+            };
+            question.answer = Some(letters).filter(|l| !l.is_empty());
+        }
+    }
+    Ok(Some(question))
+}
+
+/// One `=` or `~` answer
+struct Answer {
+    /// Written with `=` (correct, or an accepted short answer)
+    exact: bool,
+    /// Percentage from a `%50%` prefix
+    weight: Option<f64>,
+    text: String,
+}
+
+/// Split an answer block into its `=` and `~` answers
+fn answers(body: &str) -> Result<Vec<Answer>> {
+    let starts: Vec<usize> = unescaped(body)
+        .filter(|(_, c)| matches!(c, '=' | '~'))
+        .map(|(i, _)| i)
+        .collect();
+    match starts.first() {
+        Some(&first) if body[..first].trim().is_empty() => {}
+        _ => bail!("answers must start with = or ~"),
+    }
+
+    let mut answers = Vec::with_capacity(starts.len());
+    for (n, &start) in starts.iter().enumerate() {
+        let end = starts.get(n + 1).copied().unwrap_or(body.len());
+        let mut text = body[start + 1..end].trim();
+        if find_unescaped(text, "->").is_some() {
+            bail!("matching questions are not supported");
+        }
+        // Per-answer feedback is dropped
+        if let Some(feedback) = find_unescaped(text, "#") {
+            text = text[..feedback].trim();
+        }
+        let mut weight = None;
+        if let Some(rest) = text.strip_prefix('%') {
+            if let Some((percent, rest)) = rest.split_once('%') {
+                weight = Some(percent.trim().parse::<f64>().map_err(|_| anyhow!("invalid weight %{}%", percent))?);
+                text = rest.trim();
+            }
+        }
+        answers.push(Answer {
+            exact: body[start..].starts_with('='),
+            weight,
+            text: unescape(text),
+        });
+    }
+    Ok(answers)
+}
+
+/// `T`, `TRUE`, `F` or `FALSE`, optionally followed by feedback
+fn true_false(body: &str) -> Option<bool> {
+    let value = match find_unescaped(body, "#") {
+        Some(feedback) => &body[..feedback],
+        None => body,
+    };
+    match value.trim().to_ascii_uppercase().as_str() {
+        "T" | "TRUE" => Some(true),
+        "F" | "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+/// Value of a numerical answer: `3.14:0.01`, `1..5` or `=3:0 =%50%3:1`
+fn numerical_answer(body: &str) -> Result<String> {
+    let first = if body.trim_start().starts_with('=') {
+        answers(body)?.into_iter().next().map(|a| a.text).unwrap_or_default()
+    } else {
+        let value = find_unescaped(body, "#").map_or(body, |feedback| &body[..feedback]);
+        unescape(value.trim())
+    };
+    let value = first.split(':').next().unwrap_or_default().trim();
+    if value.is_empty() {
+        bail!("numerical question without a value");
+    }
+    Ok(value.to_string())
+}
+
+/// Characters of `text` with their byte offsets, skipping escaped ones
+fn unescaped(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut escaped = false;
+    text.char_indices().filter(move |&(_, c)| {
+        if escaped {
+            escaped = false;
+            false
+        } else if c == '\\' {
+            escaped = true;
+            false
+        } else {
+            true
+        }
+    })
+}
+
+/// Byte offset of the first unescaped occurrence of `pattern`
+fn find_unescaped(text: &str, pattern: &str) -> Option<usize> {
+    unescaped(text)
+        .map(|(i, _)| i)
+        .find(|&i| text[i..].starts_with(pattern))
+}
+
+/// Resolve GIFT escapes: `\~`, `\=`, `\#`, `\{`, `\}`, `\:`, `\\` and `\n`
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(next) if RESERVED.contains(&next) => out.push(next),
+            Some(next) => {
+                out.push('\\');
+                out.push(next);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Escape text for GIFT, one line per question
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.trim().chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c if RESERVED.contains(&c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Write questions as a GIFT file
+pub fn write_gift(questions: &[Question]) -> String {
+    questions.iter().map(question_to_gift).collect::<Vec<_>>().join("\n")
+}
+
+/// Write one question as GIFT, ending with a blank line
+///
+/// Stems are Markdown, so they are marked `[markdown]`. A question with a
+/// correct option is written as multiple choice whatever its type; one Moodle
+/// cannot grade (options but no correct one, a true/false answer that is
+/// neither) is written as an essay with its options listed in the text.
+pub fn question_to_gift(question: &Question) -> String {
+    let mut out = String::new();
+    if !question.tags.is_empty() {
+        let tags: Vec<_> = question.tags.iter().map(|t| format!("[tag:{}]", t.replace(']', ""))).collect();
+        out.push_str(&format!("// {}\n", tags.join(" ")));
+    }
+
+    let feedback = question
+        .analysis
+        .as_deref()
+        .filter(|a| !a.trim().is_empty())
+        .map(|a| format!("####{}", escape(&export_text(question, a))))
+        .unwrap_or_default();
+    let stem = escape(&export_text(question, &question.stem));
+    let option = |i: usize| escape(&export_text(question, strip_option_prefix(&question.options[i].content, i)));
+    let essay = |out: &mut String| {
+        out.push_str(&format!("[markdown]{}", stem));
+        for i in 0..question.options.len() {
+            out.push_str(&format!("\\n{}. {}", (b'A' + (i % 26) as u8) as char, option(i)));
+        }
+        out.push_str(&format!(" {{{}}}\n", feedback));
+    };
+
+    let correct = correct_options(question);
+    match question.qtype {
+        _ if !correct.is_empty() => {
+            out.push_str(&format!("[markdown]{} {{\n", stem));
+            let weight = format_weight(100.0 / correct.len() as f64);
+            for i in 0..question.options.len() {
+                let content = option(i);
+                let marker = match (correct.contains(&i), correct.len()) {
+                    (true, 1) => "=".to_string(),
+                    (true, _) => format!("~%{}%", weight),
+                    (false, 1) => "~".to_string(),
+                    (false, _) => "~%-100%".to_string(),
+                };
+                out.push_str(&format!("\t{}{}\n", marker, content));
+            }
+            if !feedback.is_empty() {
+                out.push_str(&format!("\t{}\n", feedback));
+            }
+            out.push_str("}\n");
+        }
+        QuestionType::TrueFalse => {
+            let answer = question.answer.as_deref().unwrap_or_default().trim().to_lowercase();
+            if TRUE_WORDS.contains(&answer.as_str()) {
+                out.push_str(&format!("[markdown]{} {{TRUE{}}}\n", stem, feedback));
+            } else if FALSE_WORDS.contains(&answer.as_str()) {
+                out.push_str(&format!("[markdown]{} {{FALSE{}}}\n", stem, feedback));
+            } else {
+                essay(&mut out);
+            }
+        }
+        QuestionType::FillInTheBlank if question.answer.as_deref().is_some_and(|a| !a.trim().is_empty()) => {
+            let accepted: Vec<_> = question
+                .answer
+                .as_deref()
+                .unwrap_or_default()
+                .split(';')
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .map(|a| format!("={}", escape(a)))
+                .collect();
+            let answers = format!("{{{}{}}}", accepted.join(" "), feedback);
+            // A blank in the stem makes it a missing-word question
+            match stem.split_once(BLANK) {
+                Some((before, after)) => out.push_str(&format!(
+                    "[markdown]{}{}{}\n",
+                    before,
+                    answers,
+                    after.trim_start_matches('_')
+                )),
+                None => out.push_str(&format!("[markdown]{} {}\n", stem, answers)),
+            }
+        }
+        _ => essay(&mut out),
+    }
+    out
+}
+
+/// Indices of the correct options, from their flags or else the answer letters
+pub(crate) fn correct_options(question: &Question) -> Vec<usize> {
+    let flagged: Vec<usize> = question
+        .options
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| o.is_correct)
+        .map(|(i, _)| i)
+        .collect();
+    if !flagged.is_empty() {
+        return flagged;
+    }
+    let answer = question.answer.as_deref().unwrap_or_default();
+    if !answer.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Vec::new();
+    }
+    let mut letters: Vec<usize> = answer
+        .chars()
+        .map(|c| (c.to_ascii_uppercase() as u8 - b'A') as usize)
+        .filter(|&i| i < question.options.len())
+        .collect();
+    letters.sort_unstable();
+    letters.dedup();
+    letters
+}
+
+/// Weight percentage as Moodle writes it (`50`, `33.33333`)
+fn format_weight(weight: f64) -> String {
+    let formatted = format!("{:.5}", weight);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+// Exported from Moodle
+$CATEGORY: $course$/Maths
+
+// [tag:arithmetic] [tag:easy]
+::Q1::What is 2+2? {
+\t~3
+\t=4#Right
+\t####4 is 2 doubled.
+}
+
+::Q2::[markdown]Which are primes? {~%50%2 ~%50%3 ~%-100%4}
+
+The sun rises in the east.{T}
+
+Moodle costs {=nothing =zero} to download.
+
+What is \\{pi\\} to two places? {#3.14:0.005}
+
+Describe a pendulum. {}
+
+This is only a description.
+
+Match them. {=cat -> meow =dog -> woof}
+";
+
+    #[test]
+    fn test_parse_gift() {
+        let import = parse_gift(SAMPLE, "maths.gift");
+        let q = &import.questions;
+        assert_eq!(q.len(), 6);
+
+        assert_eq!(q[0].stem, "What is 2+2?");
+        assert_eq!(q[0].qtype, QuestionType::Choice);
+        assert_eq!(q[0].options.len(), 2);
+        assert_eq!(q[0].options[1].content, "4");
+        assert_eq!(q[0].answer.as_deref(), Some("B"));
+        assert_eq!(q[0].analysis.as_deref(), Some("4 is 2 doubled."));
+        assert_eq!(q[0].tags, ["arithmetic", "easy"]);
+        assert_eq!(q[0].provenance.as_ref().unwrap().source_file.as_deref(), Some("maths.gift"));
+
+        assert_eq!(q[1].qtype, QuestionType::MultipleChoice);
+        assert_eq!(q[1].answer.as_deref(), Some("AB"));
+        assert!(q[1].tags.is_empty());
+
+        assert_eq!(q[2].qtype, QuestionType::TrueFalse);
+        assert_eq!(q[2].answer.as_deref(), Some("True"));
+
+        assert_eq!(q[3].stem, "Moodle costs ____ to download.");
+        assert_eq!(q[3].qtype, QuestionType::FillInTheBlank);
+        assert_eq!(q[3].answer.as_deref(), Some("nothing; zero"));
+
+        assert_eq!(q[4].stem, "What is {pi} to two places?");
+        assert_eq!(q[4].answer.as_deref(), Some("3.14"));
+
+        assert_eq!(q[5].qtype, QuestionType::Subjective);
+        assert_eq!(q.iter().map(|q| q.sequence).collect::<Vec<_>>(), (1..=6).map(Some).collect::<Vec<_>>());
+
+        assert_eq!(
+            import.warnings,
+            [
+                "maths.gift question 7: description without answers, skipped",
+                "maths.gift question 8: matching questions are not supported, skipped",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_gift_round_trip() {
+        let questions = parse_gift(SAMPLE, "maths.gift").questions;
+        let written = write_gift(&questions);
+        assert!(written.starts_with("// [tag:arithmetic] [tag:easy]\n[markdown]What is 2+2? {\n\t~3\n\t=4\n"));
+        assert!(written.contains("\t~%50%2\n\t~%50%3\n\t~%-100%4\n"));
+        assert!(written.contains("[markdown]Moodle costs {=nothing =zero} to download.\n"));
+
+        let reread = parse_gift(&written, "export.gift");
+        assert!(reread.warnings.is_empty(), "{:?}", reread.warnings);
+        assert_eq!(reread.questions.len(), questions.len());
+        for (before, after) in questions.iter().zip(&reread.questions) {
+            assert_eq!(before.stem, after.stem);
+            assert_eq!(before.qtype, after.qtype);
+            assert_eq!(before.answer, after.answer);
+            assert_eq!(before.analysis, after.analysis);
+            assert_eq!(before.tags, after.tags);
+        }
+    }
+
+    #[test]
+    fn test_write_gift_isolates_formulas_in_rtl_questions() {
+        let mut question = Question {
+            stem: "احسب $x^2$ الآن".to_string(),
+            options: vec![
+                QuestionOption { content: "$4$".to_string(), sort_order: 0, is_correct: true },
+                QuestionOption { content: "$8$".to_string(), sort_order: 1, is_correct: false },
+            ],
+            ..Question::default()
+        };
+        bidi::normalize_question(&mut question);
+        let written = question_to_gift(&question);
+        assert!(written.contains("[markdown]احسب \u{2066}$x^2$\u{2069} الآن {\n\t=\u{2066}$4$\u{2069}\n"), "{}", written);
+
+        let reread = parse_gift(&written, "export.gift");
+        assert_eq!(reread.questions[0].stem, question.stem);
+        assert_eq!(reread.questions[0].options[0].content, "$4$");
+        assert_eq!(reread.questions[0].text_direction, question.text_direction);
+    }
+
+    #[test]
+    fn test_write_gift_fallbacks() {
+        let question = Question {
+            qtype: QuestionType::Choice,
+            stem: "Pick one:\n\nsee $x = {1}$".to_string(),
+            options: ["A. yes", "B. no"]
+                .iter()
+                .enumerate()
+                .map(|(i, c)| QuestionOption {
+                    content: c.to_string(),
+                    sort_order: i as i32,
+                    is_correct: false,
+                })
+                .collect(),
+            ..Question::default()
+        };
+        // No correct option: written as an essay listing the options
+        assert_eq!(
+            question_to_gift(&question),
+            "[markdown]Pick one\\:\\n\\nsee $x \\= \\{1\\}$\\nA. yes\\nB. no {}\n"
+        );
+
+        let question = Question {
+            qtype: QuestionType::TrueFalse,
+            stem: "Water is wet.".to_string(),
+            answer: Some("对".to_string()),
+            ..Question::default()
+        };
+        assert_eq!(question_to_gift(&question), "[markdown]Water is wet. {TRUE}\n");
+        assert_eq!(format_weight(100.0 / 3.0), "33.33333");
+    }
+}
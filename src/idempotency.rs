@@ -0,0 +1,350 @@
+//! Idempotency keys for imports
+//!
+//! A client that sends `Idempotency-Key: <key>` with an import can safely
+//! retry it: the first request with a key runs as usual and its response is
+//! kept in an [`IdempotencyStore`]; later requests with the same key get that
+//! response back, marked `Idempotent-Replayed: true`, instead of importing the
+//! questions again. This protects against double-clicked upload buttons and
+//! clients retrying after a timeout.
+//!
+//! Keys are scoped to the authenticated caller and remembered for
+//! [`DEFAULT_IDEMPOTENCY_TTL`]. Reusing a key for a different request is
+//! rejected with `422`, and a retry that arrives while the first request is
+//! still running gets `409`. Failed requests, and requests dropped before
+//! they finish, are not remembered, so they can be retried with the same key.
+
+use crate::api::ApiError;
+use crate::auth::Principal;
+use axum::body::{to_bytes, Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http_body_util::LengthLimitError;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Request header carrying the key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header set on replayed responses
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long a response is kept for replay
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Most responses kept at once; the oldest are dropped first
+pub const DEFAULT_IDEMPOTENCY_CAPACITY: usize = 1000;
+
+/// Longest accepted key
+const MAX_KEY_LEN: usize = 255;
+
+/// Responses of recent requests, by idempotency key
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// SHA-256 of the method, path, query and body
+    fingerprint: [u8; 32],
+    created: Instant,
+    /// `None` while the first request is still running
+    response: Option<StoredResponse>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// What to do with a request carrying a key
+enum Lookup {
+    /// First request with this key: run it
+    Run,
+    Replay(StoredResponse),
+    InFlight,
+    Mismatch,
+}
+
+impl Default for IdempotencyStore {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_IDEMPOTENCY_TTL,
+            capacity: DEFAULT_IDEMPOTENCY_CAPACITY,
+            entries: Mutex::default(),
+        }
+    }
+}
+
+impl IdempotencyStore {
+    /// Create an empty store with the default TTL and capacity
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep responses for `ttl`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Keep at most `capacity` responses
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Number of keys currently remembered
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no keys are remembered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Look `key` up, claiming it for this request if it is new
+    fn begin(&self, key: &str, fingerprint: [u8; 32], now: Instant) -> Lookup {
+        let mut entries = self.lock();
+        entries.retain(|_, entry| now.saturating_duration_since(entry.created) < self.ttl);
+        if let Some(entry) = entries.get(key) {
+            return match &entry.response {
+                _ if entry.fingerprint != fingerprint => Lookup::Mismatch,
+                Some(response) => Lookup::Replay(response.clone()),
+                None => Lookup::InFlight,
+            };
+        }
+
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .filter(|(_, entry)| entry.response.is_some())
+                .min_by_key(|(_, entry)| entry.created)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                fingerprint,
+                created: now,
+                response: None,
+            },
+        );
+        Lookup::Run
+    }
+
+    /// Record the response to the request holding `key`
+    fn finish(&self, key: &str, response: StoredResponse) {
+        if let Some(entry) = self.lock().get_mut(key) {
+            entry.response = Some(response);
+        }
+    }
+
+    /// Forget `key` so the request can be retried
+    fn abandon(&self, key: &str) {
+        self.lock().remove(key);
+    }
+}
+
+/// A request's claim on its key, given up unless its response is recorded
+///
+/// Dropping the claim without [`Claim::finish`] forgets the key, so a
+/// request that fails or whose future is dropped (the client went away, a
+/// timeout fired) leaves the key free for a retry instead of in flight.
+struct Claim<'a> {
+    store: &'a IdempotencyStore,
+    key: String,
+    finished: bool,
+}
+
+impl<'a> Claim<'a> {
+    fn new(store: &'a IdempotencyStore, key: String) -> Self {
+        Self { store, key, finished: false }
+    }
+
+    /// Record `response` for replay and keep the key
+    fn finish(mut self, response: StoredResponse) {
+        self.store.finish(&self.key, response);
+        self.finished = true;
+    }
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.store.abandon(&self.key);
+        }
+    }
+}
+
+/// Middleware replaying the stored response for a repeated idempotency key
+///
+/// Requests without the header pass through untouched. Only successful
+/// responses are kept.
+pub async fn idempotent(State(store): State<Arc<IdempotencyStore>>, request: Request, next: Next) -> Response {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str().map(str::trim) {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+        _ => {
+            return ApiError::InvalidFile(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LEN
+            ))
+            .into_response()
+        }
+    };
+    // Keys of different callers never collide
+    let scoped = match request.extensions().get::<Principal>() {
+        Some(Principal(name)) => format!("{}\n{}", name, key),
+        None => key,
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => return body_error(e).into_response(),
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str());
+    hasher.update(b" ");
+    hasher.update(parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or_default());
+    hasher.update(b"\n");
+    hasher.update(&body);
+    let fingerprint: [u8; 32] = hasher.finalize().into();
+
+    let claim = match store.begin(&scoped, fingerprint, Instant::now()) {
+        Lookup::Run => Claim::new(&store, scoped),
+        Lookup::Replay(stored) => {
+            let mut response = (stored.status, stored.headers, stored.body).into_response();
+            response
+                .headers_mut()
+                .insert(HeaderName::from_static(REPLAYED_HEADER), HeaderValue::from_static("true"));
+            return response;
+        }
+        Lookup::InFlight => {
+            return ApiError::Conflict("A request with this Idempotency-Key is still being processed".to_string())
+                .into_response()
+        }
+        Lookup::Mismatch => {
+            return ApiError::Unprocessable("Idempotency-Key was already used for a different request".to_string())
+                .into_response()
+        }
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if !response.status().is_success() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    match to_bytes(body, usize::MAX).await {
+        Ok(body) => {
+            claim.finish(StoredResponse {
+                status: parts.status,
+                headers: parts.headers.clone(),
+                body: body.clone(),
+            });
+            Response::from_parts(parts, Body::from(body))
+        }
+        Err(e) => ApiError::DatabaseError(format!("Failed to read response: {}", e)).into_response(),
+    }
+}
+
+/// Map a failure reading the request body to the error clients expect
+fn body_error(error: axum::Error) -> ApiError {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&error);
+    while let Some(e) = source {
+        if e.is::<LengthLimitError>() {
+            return ApiError::PayloadTooLarge("Request body is larger than the limit".to_string());
+        }
+        source = e.source();
+    }
+    ApiError::InvalidFile(format!("Failed to read request body: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{middleware, Router};
+    use tower::ServiceExt;
+
+    fn ok(body: &'static str) -> StoredResponse {
+        StoredResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_store_lifecycle() {
+        let store = IdempotencyStore::new().with_ttl(Duration::from_secs(60)).with_capacity(2);
+        let start = Instant::now();
+
+        assert!(matches!(store.begin("a", [1; 32], start), Lookup::Run));
+        assert!(matches!(store.begin("a", [1; 32], start), Lookup::InFlight));
+        store.finish("a", ok("first"));
+        match store.begin("a", [1; 32], start) {
+            Lookup::Replay(response) => assert_eq!(response.body, "first"),
+            _ => panic!("expected a replay"),
+        }
+        assert!(matches!(store.begin("a", [2; 32], start), Lookup::Mismatch));
+
+        // Abandoned keys can be used again
+        let later = start + Duration::from_millis(500);
+        assert!(matches!(store.begin("b", [1; 32], later), Lookup::Run));
+        store.abandon("b");
+        assert!(matches!(store.begin("b", [2; 32], later), Lookup::Run));
+        store.finish("b", ok("second"));
+
+        // At capacity the oldest finished response goes
+        assert!(matches!(store.begin("c", [1; 32], start + Duration::from_secs(1)), Lookup::Run));
+        assert_eq!(store.len(), 2);
+        assert!(matches!(store.begin("a", [1; 32], start + Duration::from_secs(1)), Lookup::Run));
+
+        // Everything expires after the TTL
+        assert!(matches!(store.begin("c", [9; 32], start + Duration::from_secs(120)), Lookup::Run));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_frees_its_key() {
+        let store = Arc::new(IdempotencyStore::new());
+        let app = Router::new()
+            .route("/import", post(std::future::pending::<()>))
+            .layer(middleware::from_fn_with_state(store.clone(), idempotent));
+        let request = || {
+            Request::post("/import")
+                .header(IDEMPOTENCY_KEY_HEADER, "retry-me")
+                .body(Body::from("questions"))
+                .unwrap()
+        };
+
+        // The client gives up while the import is still running
+        let cancelled = tokio::time::timeout(Duration::from_millis(50), app.clone().oneshot(request())).await;
+        assert!(cancelled.is_err());
+        assert!(store.is_empty());
+
+        // Its retry runs the handler again rather than getting a 409 back
+        let retry = tokio::time::timeout(Duration::from_millis(50), app.oneshot(request())).await;
+        assert!(retry.is_err());
+    }
+}
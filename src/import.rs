@@ -0,0 +1,507 @@
+//! Multi-archive import jobs
+//!
+//! Several archives uploaded together are treated as one logical import:
+//! questions are deduplicated across archives, numbered in a single sequence,
+//! share the defaults declared in any archive's manifest, and are
+//! summarized in one consolidated report.
+
+use crate::dedup::content_key;
+use crate::models::{Provenance, Question, QuestionType, TypeSource};
+use crate::processor::{NoProgress, ProgressReporter};
+use crate::zip::{EntryContent, ImageDedupReport, ImageStoreReport, StageMetrics, ZipFileResult, ZipProcessor};
+use anyhow::{anyhow, Result};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::{debug, warn};
+
+/// Names of the optional manifest at the root of an archive, by precedence
+pub const MANIFEST_FILES: &[&str] = &[
+    "manifest.toml",
+    "manifest.json",
+    "manifest.yaml",
+    "manifest.yml",
+    "bank.yaml",
+    "bank.yml",
+];
+
+/// Defaults applied to the questions of an import
+///
+/// ```toml
+/// tags = ["期中"]
+/// preset = "xuekewang-export"
+/// bank = "高一数学期中"
+/// subject = "数学"
+/// default_type = "fill_in_the_blank"
+///
+/// [files."part2/essay.md"]
+/// default_type = "subjective"
+/// tags = ["作文"]
+/// ```
+///
+/// The same keys may be written as JSON or YAML. Tags and the bank apply to
+/// the whole import job; the preset, subject, default type and file
+/// overrides only to the archive that declares them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportManifest {
+    /// Tags added to every imported question
+    pub tags: Vec<String>,
+    /// Parser preset for the archive's Markdown files (see [`crate::presets`])
+    ///
+    /// Unlike tags, the preset only applies to the archive that declares it.
+    pub preset: Option<String>,
+    /// Question bank to import into, by name (created if missing)
+    pub bank: Option<String>,
+    /// Subject, added as the first tag of the archive's questions
+    pub subject: Option<String>,
+    /// Type for questions the parser could only classify as subjective,
+    /// unless their section declares it
+    pub default_type: Option<QuestionType>,
+    /// Overrides for single files or whole directories, keyed by archive path
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, FileOverrides>,
+}
+
+/// Manifest settings for one file or directory of an archive
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileOverrides {
+    /// Subject replacing the archive's
+    pub subject: Option<String>,
+    /// Default type replacing the archive's
+    pub default_type: Option<QuestionType>,
+    /// Tags added to the file's questions
+    pub tags: Vec<String>,
+}
+
+impl ImportManifest {
+    /// Parse a manifest from TOML source
+    pub fn from_toml_str(source: &str) -> Result<Self> {
+        Ok(toml::from_str(source)?)
+    }
+
+    /// Parse a manifest named `name`, picking the syntax from its extension
+    pub fn from_file(name: &str, source: &str) -> Result<Self> {
+        match name.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("toml") => Self::from_toml_str(source),
+            Some("json") => Ok(serde_json::from_str(source)?),
+            Some("yaml" | "yml") => Ok(serde_yaml::from_str(source)?),
+            _ => Err(anyhow!("unknown manifest format: {}", name)),
+        }
+    }
+
+    /// Fold another manifest into this one
+    ///
+    /// Tags are unioned in order and the first bank named wins; the other
+    /// settings are per archive and are not merged.
+    pub fn merge(&mut self, other: &ImportManifest) {
+        for tag in &other.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+        if self.bank.is_none() {
+            self.bank.clone_from(&other.bank);
+        }
+    }
+
+    /// Apply the defaults to a question
+    pub fn apply(&self, question: &mut Question) {
+        for tag in &self.tags {
+            if !question.tags.contains(tag) {
+                question.tags.push(tag.clone());
+            }
+        }
+    }
+
+    /// Apply the archive's subject, default type and file overrides
+    ///
+    /// A directory key (`part2` or `part2/`) covers every file below it; the
+    /// longest matching key wins.
+    pub fn apply_to_archive(&self, question: &mut Question) {
+        let source = question
+            .provenance
+            .as_ref()
+            .and_then(|p| p.source_file.as_deref())
+            .unwrap_or_default();
+        let overrides = self
+            .files
+            .iter()
+            .filter(|(key, _)| {
+                let key = key.trim_end_matches('/');
+                source == key || source.strip_prefix(key).is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|(key, _)| key.trim_end_matches('/').len())
+            .map(|(_, overrides)| overrides);
+
+        let subject = overrides.and_then(|o| o.subject.as_ref()).or(self.subject.as_ref());
+        if let Some(subject) = subject {
+            if !question.tags.contains(subject) {
+                question.tags.insert(0, subject.clone());
+            }
+        }
+        let default_type = overrides.and_then(|o| o.default_type).or(self.default_type);
+        if let Some(qtype) = default_type {
+            if question.qtype == QuestionType::Subjective && question.type_source != Some(TypeSource::Section) {
+                question.qtype = qtype;
+                question.type_source = Some(TypeSource::Default);
+            }
+        }
+        for tag in overrides.iter().flat_map(|o| &o.tags) {
+            if !question.tags.contains(tag) {
+                question.tags.push(tag.clone());
+            }
+        }
+    }
+}
+
+/// Outcome of one archive within an import job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveReport {
+    /// Upload name of the archive
+    pub source: String,
+    /// Per-file results for every Markdown entry
+    pub files: Vec<ZipFileResult>,
+    /// Number of questions kept from this archive
+    pub questions: usize,
+    /// Number of questions dropped as duplicates of earlier ones
+    pub duplicates: usize,
+    /// Identical images within the archive
+    pub image_dedup: ImageDedupReport,
+    /// Images written to the media store
+    #[serde(default)]
+    pub image_store: ImageStoreReport,
+    /// Time spent extracting, parsing and storing images
+    #[serde(default)]
+    pub metrics: StageMetrics,
+    /// Error message if the archive could not be read at all
+    pub error: Option<String>,
+}
+
+/// Consolidated report of an import job
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Per-archive outcomes, in upload order
+    pub archives: Vec<ArchiveReport>,
+    /// Number of questions kept across all archives
+    pub total_questions: usize,
+    /// Number of questions dropped as cross-archive duplicates
+    pub duplicates_skipped: usize,
+    /// Effective defaults applied to every question
+    pub manifest: ImportManifest,
+    /// Warnings from all archives, prefixed with the archive name
+    pub warnings: Vec<String>,
+}
+
+/// Questions, images and report produced by an import job
+#[derive(Debug)]
+pub struct ImportJobResult {
+    /// Deduplicated questions in job order
+    pub questions: Vec<Question>,
+    /// Images from all archives (hash -> content)
+    pub images: HashMap<String, EntryContent>,
+    /// Consolidated report
+    pub report: ImportReport,
+}
+
+/// A set of archives imported together
+#[derive(Debug, Default)]
+pub struct ImportJob {
+    archives: Vec<(Vec<u8>, String)>,
+    defaults: ImportManifest,
+}
+
+impl ImportJob {
+    /// Create an empty job
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an archive to the job
+    pub fn with_archive(mut self, data: Vec<u8>, source: impl Into<String>) -> Self {
+        self.add_archive(data, source);
+        self
+    }
+
+    /// Add an archive to the job
+    pub fn add_archive(&mut self, data: Vec<u8>, source: impl Into<String>) {
+        self.archives.push((data, source.into()));
+    }
+
+    /// Defaults applied in addition to those declared by archive manifests
+    pub fn with_defaults(mut self, defaults: ImportManifest) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Number of archives in the job
+    pub fn len(&self) -> usize {
+        self.archives.len()
+    }
+
+    /// Whether the job has no archives
+    pub fn is_empty(&self) -> bool {
+        self.archives.is_empty()
+    }
+
+    /// Names of the archives, in upload order
+    pub fn sources(&self) -> Vec<String> {
+        self.archives.iter().map(|(_, source)| source.clone()).collect()
+    }
+
+    /// Process all archives and merge them into one result
+    ///
+    /// Up to `max_concurrent` archives are extracted at once, but results are
+    /// merged in upload order so numbering and dedup are deterministic: when
+    /// two archives contain the same question, the first upload wins.
+    pub async fn run(self, processor: &ZipProcessor, max_concurrent: usize) -> Result<ImportJobResult> {
+        self.run_with_progress(processor, max_concurrent, &NoProgress).await
+    }
+
+    /// Like [`ImportJob::run`], reporting each archive to `progress` as soon
+    /// as it has been processed
+    pub async fn run_with_progress(
+        self,
+        processor: &ZipProcessor,
+        max_concurrent: usize,
+        progress: &dyn ProgressReporter,
+    ) -> Result<ImportJobResult> {
+        let results = stream::iter(self.archives)
+            .map(|(data, source)| async move {
+                let result = processor.process_upload(&source, data).await;
+                progress.on_file_done(&source, result.as_ref().map_or(0, |r| r.questions.len()));
+                (result, source)
+            })
+            .buffered(max_concurrent.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut report = ImportReport {
+            manifest: self.defaults,
+            ..ImportReport::default()
+        };
+        for (result, _) in &results {
+            if let Ok(zip_result) = result {
+                if let Some(manifest) = &zip_result.manifest {
+                    report.manifest.merge(manifest);
+                }
+            }
+        }
+
+        let mut questions = Vec::new();
+        let mut images = HashMap::new();
+        // Content key -> (sequence, archive) of the first occurrence
+        let mut seen: HashMap<String, (u64, String)> = HashMap::new();
+
+        for (result, source) in results {
+            let zip_result = match result {
+                Ok(zip_result) => zip_result,
+                Err(e) => {
+                    warn!("Failed to process archive {}: {}", source, e);
+                    report.warnings.push(format!("Failed to process archive {}: {}", source, e));
+                    report.archives.push(ArchiveReport {
+                        source,
+                        files: Vec::new(),
+                        questions: 0,
+                        duplicates: 0,
+                        image_dedup: ImageDedupReport::default(),
+                        image_store: ImageStoreReport::default(),
+                        metrics: StageMetrics::default(),
+                        error: Some(e.to_string()),
+                    });
+                    continue;
+                }
+            };
+
+            let mut kept = 0;
+            let mut duplicates = 0;
+            for mut question in zip_result.questions {
+                let key = content_key(&question);
+                if let Some((sequence, first_source)) = seen.get(&key) {
+                    duplicates += 1;
+                    report.warnings.push(format!(
+                        "{}: duplicate of question #{} from {} skipped",
+                        source, sequence, first_source
+                    ));
+                    continue;
+                }
+
+                let sequence = questions.len() as u64 + 1;
+                seen.insert(key, (sequence, source.clone()));
+
+                let provenance = question.provenance.get_or_insert_with(Provenance::default);
+                provenance.archive = Some(source.clone());
+                provenance.sequence = Some(sequence);
+                if question.sequence.is_some() {
+                    question.sequence = Some(sequence);
+                }
+                report.manifest.apply(&mut question);
+
+                questions.push(question);
+                kept += 1;
+            }
+
+            debug!("Merged {} questions from {} ({} duplicates)", kept, source, duplicates);
+
+            report
+                .warnings
+                .extend(zip_result.warnings.into_iter().map(|w| format!("{}: {}", source, w)));
+            images.extend(zip_result.images);
+            report.duplicates_skipped += duplicates;
+            report.archives.push(ArchiveReport {
+                source,
+                files: zip_result.files,
+                questions: kept,
+                duplicates,
+                image_dedup: zip_result.image_dedup,
+                image_store: zip_result.image_store,
+                metrics: zip_result.metrics,
+                error: None,
+            });
+        }
+
+        report.total_questions = questions.len();
+
+        Ok(ImportJobResult {
+            questions,
+            images,
+            report,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn build_zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut writer = zip::ZipWriter::new(Cursor::new(&mut data));
+            for (name, content) in files {
+                writer
+                    .start_file(*name, zip::write::SimpleFileOptions::default())
+                    .unwrap();
+                writer.write_all(content.as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_import_job_merges_archives() {
+        let first = build_zip(&[
+            ("manifest.toml", "tags = [\"期中\"]"),
+            ("a.md", "# Question 1\n\n答案：A\n\n# Question 2\n\n答案：B"),
+        ]);
+        let second = build_zip(&[("b.md", "#  question   1\n\n答案：A\n\n# Question 3\n\n答案：C")]);
+
+        let result = ImportJob::new()
+            .with_archive(first, "first.zip")
+            .with_archive(second, "second.zip")
+            .with_archive(b"not a zip".to_vec(), "broken.zip")
+            .run(&ZipProcessor::with_workers(2), 2)
+            .await
+            .unwrap();
+
+        let stems: Vec<_> = result.questions.iter().map(|q| q.stem.as_str()).collect();
+        assert_eq!(stems, vec!["Question 1", "Question 2", "Question 3"]);
+
+        let sequences: Vec<_> = result
+            .questions
+            .iter()
+            .map(|q| q.provenance.as_ref().unwrap().sequence.unwrap())
+            .collect();
+        assert_eq!(sequences, vec![1, 2, 3]);
+        assert!(result.questions.iter().zip(1..).all(|(q, i)| q.sequence == Some(i)));
+
+        let last = result.questions[2].provenance.as_ref().unwrap();
+        assert_eq!(last.archive.as_deref(), Some("second.zip"));
+        assert_eq!(last.source_file.as_deref(), Some("b.md"));
+
+        // The manifest in the first archive applies to the whole job
+        assert!(result.questions.iter().all(|q| q.tags == vec!["期中".to_string()]));
+
+        let report = &result.report;
+        assert_eq!(report.total_questions, 3);
+        assert_eq!(report.duplicates_skipped, 1);
+        assert_eq!(report.archives.len(), 3);
+        assert_eq!(report.archives[1].duplicates, 1);
+        assert!(report.archives[2].error.is_some());
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w == "second.zip: duplicate of question #1 from first.zip skipped"));
+    }
+
+    #[test]
+    fn test_manifest_merge_and_apply() {
+        let mut manifest = ImportManifest::from_toml_str("tags = [\"a\", \"b\"]").unwrap();
+        manifest.merge(&ImportManifest {
+            tags: vec!["b".to_string(), "c".to_string()],
+            preset: Some("kaoshibao".to_string()),
+            ..ImportManifest::default()
+        });
+        assert_eq!(manifest.tags, vec!["a", "b", "c"]);
+        assert_eq!(manifest.preset, None);
+
+        let mut question = Question {
+            tags: vec!["c".to_string()],
+            ..Question::default()
+        };
+        manifest.apply(&mut question);
+        assert_eq!(question.tags, vec!["c", "a", "b"]);
+
+        assert!(ImportManifest::from_toml_str("tags = 3").is_err());
+    }
+
+    #[test]
+    fn test_manifest_formats_and_file_overrides() {
+        let json = r#"{"bank": "期中", "subject": "数学", "default_type": "fill_in_the_blank",
+            "files": {"part2": {"default_type": "subjective", "tags": ["作文"]},
+                      "part2/poem.md": {"subject": "语文"}}}"#;
+        let yaml = "bank: 期中\nsubject: 数学\ndefault_type: fill_in_the_blank\nfiles:\n  part2:\n    default_type: subjective\n    tags: [作文]\n  part2/poem.md:\n    subject: 语文\n";
+        let manifest = ImportManifest::from_file("manifest.json", json).unwrap();
+        assert_eq!(ImportManifest::from_file("bank.yaml", yaml).unwrap(), manifest);
+        assert!(ImportManifest::from_file("manifest.ini", "").is_err());
+
+        let question = |source: &str, qtype| {
+            let mut question = Question {
+                qtype,
+                provenance: Some(Provenance {
+                    source_file: Some(source.to_string()),
+                    ..Provenance::default()
+                }),
+                ..Question::default()
+            };
+            manifest.apply_to_archive(&mut question);
+            (question.qtype, question.tags)
+        };
+
+        assert_eq!(question("exam.md", QuestionType::Subjective), (QuestionType::FillInTheBlank, vec!["数学".to_string()]));
+        assert_eq!(question("exam.md", QuestionType::Choice), (QuestionType::Choice, vec!["数学".to_string()]));
+        assert_eq!(
+            question("part2/essay.md", QuestionType::Subjective),
+            (QuestionType::Subjective, vec!["数学".to_string(), "作文".to_string()])
+        );
+        assert_eq!(question("part2/poem.md", QuestionType::Subjective).1, vec!["语文"]);
+        assert_eq!(question("part20/x.md", QuestionType::Subjective).0, QuestionType::FillInTheBlank);
+
+        // Questions under a section declared subjective ("五、简答题") keep it
+        let mut essay = Question {
+            type_source: Some(TypeSource::Section),
+            ..Question::default()
+        };
+        manifest.apply_to_archive(&mut essay);
+        assert_eq!(essay.qtype, QuestionType::Subjective);
+
+        let mut merged = ImportManifest::default();
+        merged.merge(&manifest);
+        merged.merge(&ImportManifest { bank: Some("other".to_string()), ..ImportManifest::default() });
+        assert_eq!(merged.bank.as_deref(), Some("期中"));
+        assert_eq!(merged.subject, None);
+    }
+}
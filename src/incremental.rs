@@ -0,0 +1,268 @@
+//! Incremental re-imports
+//!
+//! When a bank is imported again, [`plan`] compares the new questions with
+//! what the bank already holds, file by file. Each source file gets a hash
+//! of the questions parsed from it; a file whose hash matches the one
+//! recorded at the last import is skipped. Questions of a changed file are
+//! matched to the stored questions of the same file, first by content and
+//! then by similarity, so an edited question keeps its ID and is updated in
+//! place. New questions are inserted and stored ones that are gone from the
+//! file are removed.
+//!
+//! Files missing from the new import are left alone, so part of a bank can
+//! be re-imported without deleting the rest.
+
+use crate::dedup::{content_key, similarity};
+use crate::models::{ImportedSource, Question};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Similarity at or above which a changed question counts as an edit of a
+/// stored one rather than a replacement
+pub const EDIT_SIMILARITY: f64 = 0.6;
+
+/// Changes needed to bring a bank up to date with a re-import
+#[derive(Debug, Default)]
+pub struct IncrementalPlan {
+    /// Questions to store as new records
+    pub inserts: Vec<Question>,
+    /// Questions to write over a stored record with the same ID
+    pub updates: Vec<Question>,
+    /// Stored questions no longer in their source file
+    pub removals: Vec<Uuid>,
+    /// Questions identical to their stored record
+    pub unchanged_questions: usize,
+    /// Files skipped because their hash is unchanged
+    pub unchanged_files: Vec<String>,
+    /// Records to save once the changes are written
+    pub sources: Vec<ImportedSource>,
+}
+
+/// Source file a question was read from, as used to group questions
+pub fn source_key(question: &Question) -> &str {
+    question
+        .provenance
+        .as_ref()
+        .and_then(|p| p.source_file.as_deref())
+        .unwrap_or_default()
+}
+
+/// Hash of everything an import writes for a question, except its identity
+///
+/// IDs, timestamps, numbering and provenance are left out, so the same file
+/// imported twice hashes the same.
+pub fn question_fingerprint(question: &Question) -> String {
+    let content = (
+        question.qtype,
+        &question.stem,
+        &question.options,
+        &question.answer,
+        &question.analysis,
+        &question.images,
+        &question.latex,
+        &question.tags,
+    );
+    // Serializing these plain types cannot fail
+    let json = serde_json::to_string(&content).unwrap_or_default();
+    hex(&Sha256::digest(json.as_bytes()))
+}
+
+/// Hash of the questions parsed from one file, in order
+pub fn source_hash<'a>(questions: impl IntoIterator<Item = &'a Question>) -> String {
+    let mut hasher = Sha256::new();
+    for question in questions {
+        hasher.update(question_fingerprint(question).as_bytes());
+        hasher.update(b"\n");
+    }
+    hex(&hasher.finalize())
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Work out how to apply `questions` to bank `bank_id`
+///
+/// `recorded` holds the bank's source records and `stored` its current
+/// questions. Inserts and updates keep the order of `questions`.
+pub fn plan(
+    bank_id: Uuid,
+    questions: Vec<Question>,
+    recorded: &[ImportedSource],
+    stored: Vec<Question>,
+) -> IncrementalPlan {
+    let recorded: HashMap<&str, &str> = recorded
+        .iter()
+        .filter(|s| s.bank_id == bank_id)
+        .map(|s| (s.source.as_str(), s.content_hash.as_str()))
+        .collect();
+
+    // Group by file, keeping the files in input order
+    let mut files: Vec<(String, Vec<Question>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for question in questions {
+        let key = source_key(&question).to_string();
+        let slot = *index.entry(key.clone()).or_insert_with(|| {
+            files.push((key, Vec::new()));
+            files.len() - 1
+        });
+        files[slot].1.push(question);
+    }
+
+    let mut by_source: HashMap<String, Vec<Question>> = HashMap::new();
+    for question in stored {
+        by_source.entry(source_key(&question).to_string()).or_default().push(question);
+    }
+
+    let mut plan = IncrementalPlan::default();
+    for (source, questions) in files {
+        let hash = source_hash(&questions);
+        if recorded.get(source.as_str()) == Some(&hash.as_str()) {
+            plan.unchanged_questions += questions.len();
+            plan.unchanged_files.push(source);
+            continue;
+        }
+
+        let mut stored = by_source.remove(&source).unwrap_or_default();
+        stored.sort_by_key(|q| (q.sequence, q.created_at));
+        plan.apply_file(questions, stored);
+        plan.sources.push(ImportedSource {
+            bank_id,
+            source,
+            content_hash: hash,
+            imported_at: Utc::now(),
+        });
+    }
+    plan
+}
+
+impl IncrementalPlan {
+    /// Match the new questions of a changed file to its stored ones
+    fn apply_file(&mut self, questions: Vec<Question>, stored: Vec<Question>) {
+        let mut by_content: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, question) in stored.iter().enumerate().rev() {
+            by_content.entry(content_key(question)).or_default().push(i);
+        }
+
+        // Same content first, so reordering a file changes nothing
+        let mut matched: Vec<Option<usize>> = questions
+            .iter()
+            .map(|q| by_content.get_mut(&content_key(q)).and_then(Vec::pop))
+            .collect();
+        let mut used = vec![false; stored.len()];
+        for &i in matched.iter().flatten() {
+            used[i] = true;
+        }
+
+        // Then the most similar leftover, so an edited question keeps its ID
+        let keys: Vec<String> = stored.iter().map(content_key).collect();
+        for (question, slot) in questions.iter().zip(matched.iter_mut()) {
+            if slot.is_some() {
+                continue;
+            }
+            let key = content_key(question);
+            let best = (0..stored.len())
+                .filter(|&i| !used[i])
+                .map(|i| (i, similarity(&key, &keys[i])))
+                .filter(|&(_, score)| score >= EDIT_SIMILARITY)
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((i, _)) = best {
+                used[i] = true;
+                *slot = Some(i);
+            }
+        }
+
+        for (mut question, slot) in questions.into_iter().zip(matched) {
+            let Some(i) = slot else {
+                self.inserts.push(question);
+                continue;
+            };
+            let existing = &stored[i];
+            if question_fingerprint(&question) == question_fingerprint(existing) {
+                self.unchanged_questions += 1;
+                continue;
+            }
+            question.id = existing.id;
+            question.created_at = existing.created_at;
+            self.updates.push(question);
+        }
+
+        self.removals
+            .extend(stored.iter().zip(used).filter(|(_, used)| !used).map(|(q, _)| q.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Provenance;
+
+    fn question(source: &str, stem: &str, answer: &str) -> Question {
+        Question {
+            stem: stem.to_string(),
+            answer: Some(answer.to_string()),
+            provenance: Some(Provenance {
+                source_file: Some(source.to_string()),
+                ..Provenance::default()
+            }),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_first_import_inserts_everything() {
+        let bank = Uuid::new_v4();
+        let questions = vec![question("a.md", "1+1?", "2"), question("b.md", "2+2?", "4")];
+
+        let plan = plan(bank, questions, &[], Vec::new());
+        assert_eq!(plan.inserts.len(), 2);
+        assert!(plan.updates.is_empty() && plan.removals.is_empty());
+        let sources: Vec<_> = plan.sources.iter().map(|s| s.source.as_str()).collect();
+        assert_eq!(sources, ["a.md", "b.md"]);
+    }
+
+    #[test]
+    fn test_plan_reimport() {
+        let bank = Uuid::new_v4();
+        let stored = vec![
+            question("a.md", "1+1?", "2"),
+            question("b.md", "2+2?", "4"),
+            question("b.md", "Capitol of France?", "Paris"),
+            question("b.md", "3+3?", "6"),
+        ];
+        let recorded: Vec<_> = ["a.md", "b.md"]
+            .iter()
+            .map(|source| ImportedSource {
+                bank_id: bank,
+                source: source.to_string(),
+                content_hash: source_hash(stored.iter().filter(|q| source_key(q) == *source)),
+                imported_at: Utc::now(),
+            })
+            .collect();
+
+        // a.md is unchanged; in b.md one answer and one typo are fixed, one
+        // question is dropped and one is added
+        let questions = vec![
+            question("a.md", "1+1?", "2"),
+            question("b.md", "2+2?", "four"),
+            question("b.md", "Capital of France?", "Paris"),
+            question("b.md", "5+5?", "10"),
+        ];
+        let ids: Vec<_> = stored.iter().map(|q| q.id).collect();
+        let plan = plan(bank, questions, &recorded, stored);
+
+        assert_eq!(plan.unchanged_files, ["a.md"]);
+        assert_eq!(plan.unchanged_questions, 1);
+        let updated: Vec<_> = plan.updates.iter().map(|q| q.id).collect();
+        assert_eq!(updated, [ids[1], ids[2]]);
+        assert_eq!(plan.updates[0].answer.as_deref(), Some("four"));
+        assert_eq!(plan.updates[1].stem, "Capital of France?");
+        assert_eq!(plan.inserts.len(), 1);
+        assert_eq!(plan.inserts[0].stem, "5+5?");
+        assert_eq!(plan.removals, [ids[3]]);
+        assert_eq!(plan.sources.len(), 1);
+        assert_eq!(plan.sources[0].source, "b.md");
+    }
+}
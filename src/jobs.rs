@@ -0,0 +1,600 @@
+//! Background import jobs
+//!
+//! [`JobManager`] runs [`SingleMachineProcessor::process`] in the background:
+//! [`JobManager::submit`] returns a job ID at once, and the job's status,
+//! progress and final [`ProcessResult`] can be looked up later. Job records
+//! are written to the repository whenever the status changes, so finished
+//! jobs can still be looked up after a restart. Progress is only kept in
+//! memory while a job runs; [`JobManager::subscribe`] follows it as a stream
+//! of [`JobEvent`]s, and [`JobListener`]s added with
+//! [`JobManager::with_listener`] are told about every job that finishes.
+
+use crate::database::QuestionRepository;
+use crate::processor::{InputSource, ProcessResult, ProcessStage, ProgressReporter, SingleMachineProcessor};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, Notify, Semaphore};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Error recorded for jobs that were still pending when the process stopped
+pub const INTERRUPTED_ERROR: &str = "interrupted by a restart";
+
+/// Events buffered per subscriber before the oldest are dropped
+const EVENT_CAPACITY: usize = 1024;
+
+/// State of a background job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for a free slot
+    Queued,
+    /// Being processed
+    Running,
+    /// Finished; the result is available
+    Completed,
+    /// Finished with an error
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job has finished, successfully or not
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+
+    /// Name stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "completed" => Ok(JobStatus::Completed),
+            "failed" => Ok(JobStatus::Failed),
+            other => Err(anyhow::anyhow!("unknown job status: {}", other)),
+        }
+    }
+}
+
+/// A background job and, once finished, its outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    /// Job ID returned by [`JobManager::submit`]
+    pub id: Uuid,
+    /// Current state
+    pub status: JobStatus,
+    /// Names of the files being imported
+    pub sources: Vec<String>,
+    /// Stage of a running job
+    pub stage: Option<ProcessStage>,
+    /// Estimated completion, from 0 to 100
+    pub progress: f64,
+    /// Outcome of a completed job
+    pub result: Option<ProcessResult>,
+    /// Why a failed job failed
+    pub error: Option<String>,
+    /// When the job was submitted
+    pub created_at: DateTime<Utc>,
+    /// When the status last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+impl JobRecord {
+    /// A queued job importing `sources`
+    pub fn new(sources: Vec<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            status: JobStatus::Queued,
+            sources,
+            stage: None,
+            progress: 0.0,
+            result: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Move to `status`, touching `updated_at`
+    fn transition(&mut self, status: JobStatus) {
+        self.status = status;
+        self.updated_at = Utc::now();
+        if status.is_finished() {
+            self.stage = None;
+        }
+    }
+}
+
+/// Something that happened to a running job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    /// State of the job when a subscriber joined
+    Status {
+        job_id: Uuid,
+        status: JobStatus,
+        stage: Option<ProcessStage>,
+        progress: f64,
+    },
+    /// A stage has started
+    Stage { job_id: Uuid, stage: ProcessStage },
+    /// A file or archive has been parsed
+    FileDone {
+        job_id: Uuid,
+        source: String,
+        /// Questions found in this file
+        questions: usize,
+        /// Files parsed so far
+        files_done: usize,
+        /// Questions found so far, before dedup
+        questions_parsed: usize,
+    },
+    /// Estimated completion, from 0 to 100
+    Progress { job_id: Uuid, percent: f64 },
+    /// A warning from the finished import
+    Warning { job_id: Uuid, message: String },
+    /// The job has finished; its record holds the result or error
+    Finished {
+        job_id: Uuid,
+        status: JobStatus,
+        error: Option<String>,
+    },
+}
+
+impl JobEvent {
+    /// Job the event belongs to
+    pub fn job_id(&self) -> Uuid {
+        match self {
+            JobEvent::Status { job_id, .. }
+            | JobEvent::Stage { job_id, .. }
+            | JobEvent::FileDone { job_id, .. }
+            | JobEvent::Progress { job_id, .. }
+            | JobEvent::Warning { job_id, .. }
+            | JobEvent::Finished { job_id, .. } => *job_id,
+        }
+    }
+
+    /// The first event sent for `job`: its status, or how it finished
+    pub fn snapshot(job: &JobRecord) -> Self {
+        if job.status.is_finished() {
+            JobEvent::Finished {
+                job_id: job.id,
+                status: job.status,
+                error: job.error.clone(),
+            }
+        } else {
+            JobEvent::Status {
+                job_id: job.id,
+                status: job.status,
+                stage: job.stage,
+                progress: job.progress,
+            }
+        }
+    }
+
+    /// Whether no more events follow for the job
+    pub fn is_final(&self) -> bool {
+        matches!(self, JobEvent::Finished { .. })
+    }
+}
+
+/// Told about every job that finishes, for example to send webhooks
+#[async_trait]
+pub trait JobListener: Send + Sync {
+    /// Called once with the final record of a completed or failed job
+    ///
+    /// Runs in its own task, so a slow listener holds up neither the job's
+    /// slot nor other listeners.
+    async fn job_finished(&self, job: &JobRecord);
+}
+
+/// Hand a finished job to each listener
+fn announce(listeners: &[Arc<dyn JobListener>], job: &JobRecord) {
+    for listener in listeners {
+        let listener = listener.clone();
+        let job = job.clone();
+        tokio::spawn(async move { listener.job_finished(&job).await });
+    }
+}
+
+/// Jobs submitted to this manager that are not yet persisted as finished
+type LiveJobs = Arc<Mutex<HashMap<Uuid, JobRecord>>>;
+
+/// Runs imports in the background and tracks them by job ID
+pub struct JobManager<R> {
+    processor: Arc<SingleMachineProcessor<R>>,
+    live: LiveJobs,
+    slots: Arc<Semaphore>,
+    finished: Arc<Notify>,
+    events: broadcast::Sender<JobEvent>,
+    listeners: Vec<Arc<dyn JobListener>>,
+}
+
+impl<R> JobManager<R>
+where
+    R: QuestionRepository + Send + Sync + 'static,
+{
+    /// Create a manager that runs one job at a time with `processor`
+    pub fn new(processor: SingleMachineProcessor<R>) -> Self {
+        Self {
+            processor: Arc::new(processor),
+            live: Arc::default(),
+            slots: Arc::new(Semaphore::new(1)),
+            finished: Arc::new(Notify::new()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Run up to `jobs` jobs at once (default 1); later jobs stay queued
+    ///
+    /// Each job already uses every core, so more only helps when jobs spend
+    /// most of their time waiting on the database.
+    pub fn with_max_running(mut self, jobs: usize) -> Self {
+        self.slots = Arc::new(Semaphore::new(jobs.max(1)));
+        self
+    }
+
+    /// Tell `listener` about every job that finishes from now on
+    pub fn with_listener(mut self, listener: Arc<dyn JobListener>) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// The processor jobs run with, for imports that run in the request
+    pub fn processor(&self) -> &Arc<SingleMachineProcessor<R>> {
+        &self.processor
+    }
+
+    /// Mark jobs left queued or running by an earlier process as failed
+    ///
+    /// Their input was only held in memory, so they cannot be resumed. Call
+    /// once at startup; returns the number of jobs marked.
+    pub async fn recover(&self) -> Result<usize> {
+        let repository = self.processor.repository();
+        let mut marked = 0;
+        for mut job in repository.list_jobs().await? {
+            if job.status.is_finished() || self.live_job(job.id).is_some() {
+                continue;
+            }
+            job.transition(JobStatus::Failed);
+            job.error = Some(INTERRUPTED_ERROR.to_string());
+            repository.save_job(&job).await?;
+            announce(&self.listeners, &job);
+            marked += 1;
+        }
+        if marked > 0 {
+            warn!("Marked {} interrupted import jobs as failed", marked);
+        }
+        Ok(marked)
+    }
+
+    /// Queue `input` for processing and return its job ID
+    ///
+    /// Fails only if the job record cannot be saved.
+    pub async fn submit(&self, input: InputSource) -> Result<Uuid> {
+        let processor = self.processor.clone();
+        self.submit_task(input.sources(), move |progress| async move {
+            processor.process_with_progress(input, progress.as_ref()).await
+        })
+        .await
+    }
+
+    /// Queue `task` as a job importing `sources` and return its job ID
+    ///
+    /// For imports that are not a plain [`SingleMachineProcessor::process`]
+    /// run, such as API uploads with options of their own. The task is given
+    /// a reporter for its progress and shares the running slots with
+    /// [`JobManager::submit`]. Fails only if the job record cannot be saved.
+    pub async fn submit_task<F, Fut>(&self, sources: Vec<String>, task: F) -> Result<Uuid>
+    where
+        F: FnOnce(Arc<dyn ProgressReporter>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<ProcessResult>> + Send + 'static,
+    {
+        let job = JobRecord::new(sources);
+        let id = job.id;
+        self.processor.repository().save_job(&job).await?;
+        self.live.lock().unwrap_or_else(|e| e.into_inner()).insert(id, job);
+        info!("Queued import job {}", id);
+
+        let processor = self.processor.clone();
+        let live = self.live.clone();
+        let slots = self.slots.clone();
+        let finished = self.finished.clone();
+        let events = self.events.clone();
+        let listeners = self.listeners.clone();
+        tokio::spawn(async move {
+            let _permit = slots.acquire_owned().await;
+            let running = update(&live, id, |job| job.transition(JobStatus::Running));
+            if let Some(job) = running {
+                persist(processor.repository().as_ref(), &job).await;
+            }
+
+            let reporter = Arc::new(JobProgress {
+                live: live.clone(),
+                id,
+                events: events.clone(),
+                files_done: AtomicUsize::new(0),
+                questions_parsed: AtomicUsize::new(0),
+            });
+            let outcome = task(reporter).await;
+            let done = update(&live, id, |job| match outcome {
+                Ok(result) => {
+                    job.transition(JobStatus::Completed);
+                    job.progress = 100.0;
+                    job.result = Some(result);
+                }
+                Err(e) => {
+                    warn!("Import job {} failed: {:#}", id, e);
+                    job.transition(JobStatus::Failed);
+                    job.error = Some(format!("{:#}", e));
+                }
+            });
+            if let Some(job) = done {
+                // Once saved, the repository is the source of truth
+                if persist(processor.repository().as_ref(), &job).await {
+                    live.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+                }
+                for warning in job.result.iter().flat_map(|r| &r.warnings) {
+                    let _ = events.send(JobEvent::Warning { job_id: id, message: warning.clone() });
+                }
+                let _ = events.send(JobEvent::snapshot(&job));
+                announce(&listeners, &job);
+            }
+            finished.notify_waiters();
+        });
+
+        Ok(id)
+    }
+
+    /// Current record of a job, or `None` if no such job exists
+    pub async fn get(&self, id: Uuid) -> Result<Option<JobRecord>> {
+        if let Some(job) = self.live_job(id) {
+            return Ok(Some(job));
+        }
+        Ok(self.processor.repository().find_job(id).await?)
+    }
+
+    /// Wait until a job has finished and return its final record
+    pub async fn wait(&self, id: Uuid) -> Result<Option<JobRecord>> {
+        loop {
+            let notified = self.finished.notified();
+            tokio::pin!(notified);
+            // Register before checking, so a job finishing in between is not missed
+            notified.as_mut().enable();
+            match self.get(id).await? {
+                Some(job) if !job.status.is_finished() => notified.await,
+                job => return Ok(job),
+            }
+        }
+    }
+
+    /// Receive the events of every job from now on
+    ///
+    /// Subscribe before looking a job up with [`JobManager::get`], so no
+    /// event between the two is missed. A subscriber that falls more than
+    /// 1024 events behind loses the oldest.
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.events.subscribe()
+    }
+
+    /// Every stored job, newest first, with live progress for running ones
+    pub async fn list(&self) -> Result<Vec<JobRecord>> {
+        let jobs = self.processor.repository().list_jobs().await?;
+        Ok(jobs
+            .into_iter()
+            .map(|job| self.live_job(job.id).unwrap_or(job))
+            .collect())
+    }
+
+    fn live_job(&self, id: Uuid) -> Option<JobRecord> {
+        self.live.lock().unwrap_or_else(|e| e.into_inner()).get(&id).cloned()
+    }
+}
+
+/// Apply `change` to a live job and return the updated record
+fn update(live: &LiveJobs, id: Uuid, change: impl FnOnce(&mut JobRecord)) -> Option<JobRecord> {
+    let mut live = live.lock().unwrap_or_else(|e| e.into_inner());
+    let job = live.get_mut(&id)?;
+    change(job);
+    Some(job.clone())
+}
+
+/// Save a job record, logging failures; returns whether it was saved
+async fn persist<R: QuestionRepository + ?Sized>(repository: &R, job: &JobRecord) -> bool {
+    match repository.save_job(job).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to save import job {}: {}", job.id, e);
+            false
+        }
+    }
+}
+
+/// Copies a running job's progress into its live record and sends it to
+/// subscribers
+struct JobProgress {
+    live: LiveJobs,
+    id: Uuid,
+    events: broadcast::Sender<JobEvent>,
+    files_done: AtomicUsize,
+    questions_parsed: AtomicUsize,
+}
+
+impl JobProgress {
+    fn send(&self, event: JobEvent) {
+        // Nobody may be listening
+        let _ = self.events.send(event);
+    }
+}
+
+impl ProgressReporter for JobProgress {
+    fn on_stage(&self, stage: ProcessStage) {
+        update(&self.live, self.id, |job| job.stage = Some(stage));
+        self.send(JobEvent::Stage { job_id: self.id, stage });
+    }
+
+    fn on_file_done(&self, source: &str, questions: usize) {
+        let files_done = self.files_done.fetch_add(1, Ordering::Relaxed) + 1;
+        let questions_parsed = self.questions_parsed.fetch_add(questions, Ordering::Relaxed) + questions;
+        self.send(JobEvent::FileDone {
+            job_id: self.id,
+            source: source.to_string(),
+            questions,
+            files_done,
+            questions_parsed,
+        });
+    }
+
+    fn on_percent(&self, percent: f64) {
+        update(&self.live, self.id, |job| job.progress = percent);
+        self.send(JobEvent::Progress { job_id: self.id, percent });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MockRepository;
+    use crate::processor::ProcessorConfig;
+
+    fn manager(repository: MockRepository) -> JobManager<MockRepository> {
+        JobManager::new(SingleMachineProcessor::new(repository))
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_wait() {
+        let jobs = manager(MockRepository::new());
+        let input = InputSource::Markdown {
+            content: "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B".to_string(),
+            source: "quiz.md".to_string(),
+        };
+
+        let id = jobs.submit(input).await.unwrap();
+        let job = jobs.wait(id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.sources, ["quiz.md"]);
+        assert_eq!(job.progress, 100.0);
+        assert_eq!(job.result.unwrap().saved_questions, 1);
+
+        // Finished jobs are read back from the repository
+        assert!(jobs.live_job(id).is_none());
+        let stored = jobs.processor.repository().find_job(id).await.unwrap().unwrap();
+        assert_eq!(stored.status, JobStatus::Completed);
+        assert_eq!(jobs.list().await.unwrap().len(), 1);
+        assert!(jobs.get(Uuid::new_v4()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_events() {
+        let jobs = manager(MockRepository::new());
+        let mut events = jobs.subscribe();
+        let input = InputSource::MultipleMarkdown {
+            contents: vec![
+                ("# Q1\n\n# Q2".to_string(), "a.md".to_string()),
+                ("# Q3".to_string(), "b.md".to_string()),
+            ],
+        };
+
+        let id = jobs.submit(input).await.unwrap();
+        let mut received = Vec::new();
+        loop {
+            let event = events.recv().await.unwrap();
+            assert_eq!(event.job_id(), id);
+            let done = event.is_final();
+            received.push(event);
+            if done {
+                break;
+            }
+        }
+
+        assert!(received.contains(&JobEvent::Stage { job_id: id, stage: ProcessStage::Saving }));
+        let mut parsed = received.iter().filter_map(|e| match e {
+            JobEvent::FileDone { files_done, questions_parsed, .. } => Some((*files_done, *questions_parsed)),
+            _ => None,
+        });
+        assert_eq!(parsed.next_back(), Some((2, 3)));
+        assert_eq!(
+            received.last(),
+            Some(&JobEvent::Finished { job_id: id, status: JobStatus::Completed, error: None })
+        );
+        let job = jobs.get(id).await.unwrap().unwrap();
+        assert_eq!(JobEvent::snapshot(&job), received[received.len() - 1]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_job() {
+        let config = ProcessorConfig::default().with_bank_id(Uuid::new_v4());
+        let jobs = JobManager::new(SingleMachineProcessor::with_config(MockRepository::new(), config));
+        let input = InputSource::Gift {
+            content: "::Q1:: 1+1=2 {T}".to_string(),
+            source: "quiz.gift".to_string(),
+        };
+
+        let id = jobs.submit(input).await.unwrap();
+        let job = jobs.wait(id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.error.unwrap().contains("not found"));
+        assert!(job.result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_listeners_hear_finished_jobs() {
+        struct Recorder(tokio::sync::mpsc::UnboundedSender<JobRecord>);
+
+        #[async_trait]
+        impl JobListener for Recorder {
+            async fn job_finished(&self, job: &JobRecord) {
+                let _ = self.0.send(job.clone());
+            }
+        }
+
+        let (sender, mut finished) = tokio::sync::mpsc::unbounded_channel();
+        let jobs = manager(MockRepository::new()).with_listener(Arc::new(Recorder(sender)));
+        let input = InputSource::Markdown {
+            content: "# Q1".to_string(),
+            source: "quiz.md".to_string(),
+        };
+        let id = jobs.submit(input).await.unwrap();
+        let job = finished.recv().await.unwrap();
+        assert_eq!((job.id, job.status), (id, JobStatus::Completed));
+        assert_eq!(job.result.unwrap().saved_questions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_recover_marks_interrupted_jobs() {
+        let repository = MockRepository::new();
+        let mut running = JobRecord::new(vec!["big.zip".to_string()]);
+        running.status = JobStatus::Running;
+        repository.save_job(&running).await.unwrap();
+        let mut done = JobRecord::new(vec!["small.zip".to_string()]);
+        done.status = JobStatus::Completed;
+        repository.save_job(&done).await.unwrap();
+
+        let jobs = manager(repository);
+        assert_eq!(jobs.recover().await.unwrap(), 1);
+        let job = jobs.get(running.id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some(INTERRUPTED_ERROR));
+        assert_eq!(jobs.get(done.id).await.unwrap().unwrap().status, JobStatus::Completed);
+    }
+}
@@ -0,0 +1,117 @@
+//! JSON Lines export and import
+//!
+//! One question per line, serialized exactly as the API returns it, so a
+//! bank can be moved between MD2DB instances or fed to data pipelines
+//! (`jq`, Spark, pandas) without a custom reader. [`export_jsonl`] streams
+//! stored questions to any writer page by page; [`read_jsonl`] reads them
+//! back, reporting malformed lines instead of failing the whole file.
+
+use crate::database::QuestionRepository;
+use crate::export::{export, ExportFormat};
+use crate::models::{migrate_json, Question};
+use anyhow::Result;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite};
+
+pub use crate::export::ExportFilter;
+
+/// Write the questions matching `filter` to `writer`, one JSON object per line
+///
+/// Unfiltered exports read the repository a page at a time, so memory use
+/// does not grow with the number of stored questions. Returns the number of
+/// questions written.
+pub async fn export_jsonl<W>(repo: &dyn QuestionRepository, writer: &mut W, filter: &ExportFilter) -> Result<usize>
+where
+    W: AsyncWrite + Unpin,
+{
+    export(repo, writer, filter, ExportFormat::Jsonl).await
+}
+
+/// Result of reading a JSON Lines file
+#[derive(Debug, Default)]
+pub struct JsonlImport {
+    /// Questions in file order
+    pub questions: Vec<Question>,
+    /// Lines that were skipped, with the reason
+    pub warnings: Vec<String>,
+}
+
+/// Read questions written by [`export_jsonl`] or another MD2DB instance
+///
+/// Lines written by earlier releases are upgraded with
+/// [`migrate_json`](crate::models::migrate_json). Blank lines are ignored; a
+/// line that is not a valid question is skipped with a `line N: ...` warning.
+pub async fn read_jsonl<R: AsyncBufRead + Unpin>(reader: R) -> Result<JsonlImport> {
+    let mut import = JsonlImport::default();
+    let mut lines = reader.lines();
+    let mut number = 0;
+    while let Some(line) = lines.next_line().await? {
+        number += 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let question = serde_json::from_str(line)
+            .map_err(anyhow::Error::from)
+            .and_then(|value| Ok(migrate_json(value)?));
+        match question {
+            Ok(question) => import.questions.push(question),
+            Err(e) => import.warnings.push(format!("line {}: {}", number, e)),
+        }
+    }
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MockRepository;
+    use crate::models::{QuestionBank, QuestionType};
+
+    #[tokio::test]
+    async fn test_export_and_read_jsonl() {
+        let repo = MockRepository::new();
+        let bank = QuestionBank::new("Physics");
+        repo.create_bank(&bank).await.unwrap();
+        let questions: Vec<Question> = (1..=3)
+            .map(|n| Question {
+                qtype: QuestionType::FillInTheBlank,
+                stem: format!("Question {}", n),
+                answer: Some(n.to_string()),
+                tags: vec![if n == 2 { "力学" } else { "光学" }.to_string()],
+                bank_id: (n == 3).then_some(bank.id),
+                ..Question::default()
+            })
+            .collect();
+        repo.save_batch(&questions).await.unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(export_jsonl(&repo, &mut out, &ExportFilter::default()).await.unwrap(), 3);
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.lines().all(|line| line.starts_with('{')));
+
+        let filter = ExportFilter {
+            tag: Some("力学".to_string()),
+            ..ExportFilter::default()
+        };
+        let mut tagged = Vec::new();
+        assert_eq!(export_jsonl(&repo, &mut tagged, &filter).await.unwrap(), 1);
+        let filter = ExportFilter {
+            bank_id: Some(bank.id),
+            ..ExportFilter::default()
+        };
+        let mut banked = Vec::new();
+        assert_eq!(export_jsonl(&repo, &mut banked, &filter).await.unwrap(), 1);
+
+        out.extend_from_slice(b"\n{\"stem\": 1}\nnot json\n");
+        let import = read_jsonl(out.as_slice()).await.unwrap();
+        assert_eq!(import.questions.len(), 3);
+        let mut stems: Vec<_> = import.questions.iter().map(|q| q.stem.as_str()).collect();
+        stems.sort_unstable();
+        assert_eq!(stems, ["Question 1", "Question 2", "Question 3"]);
+        assert_eq!(import.questions.iter().find(|q| q.bank_id.is_some()).unwrap().stem, "Question 3");
+        assert_eq!(import.warnings.len(), 2);
+        assert!(import.warnings[0].starts_with("line 5: "), "{:?}", import.warnings);
+        assert!(import.warnings[1].starts_with("line 6: "), "{:?}", import.warnings);
+    }
+}
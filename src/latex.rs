@@ -0,0 +1,395 @@
+//! LaTeX formula validation and normalization
+//!
+//! Formulas reach [`Question::latex`] from code spans, Word equations and
+//! formula OCR, and appear inline in question text between `$` delimiters.
+//! A formula with an unclosed brace or a misspelled command imports fine
+//! and only fails when the exam client renders it, so this module checks
+//! them at import: [`LatexValidator`] reports unbalanced braces, `\left`
+//! without `\right`, mismatched environments and unknown commands, and
+//! [`normalize_formula`] rewrites `\(...\)` and `\[...\]` delimiters as
+//! `$...$` and `$$...$$`, the form the rest of the crate expects.
+//!
+//! Problems are reported as warnings, like answer problems
+//! ([`crate::answer`]); nothing is rejected.
+
+use crate::answer::preview;
+use crate::bidi::split_math;
+use crate::models::Question;
+use std::collections::BTreeSet;
+
+/// Commands accepted without being configured
+///
+/// Covers what the exam client's renderer supports: Greek letters,
+/// operators, relations, arrows, functions, accents, fonts, spacing and
+/// delimiters.
+const KNOWN_COMMANDS: &[&str] = &[
+    // Greek letters
+    "alpha", "beta", "gamma", "delta", "epsilon", "varepsilon", "zeta", "eta", "theta", "vartheta", "iota",
+    "kappa", "lambda", "mu", "nu", "xi", "pi", "varpi", "rho", "varrho", "sigma", "varsigma", "tau", "upsilon",
+    "phi", "varphi", "chi", "psi", "omega", "Gamma", "Delta", "Theta", "Lambda", "Xi", "Pi", "Sigma", "Upsilon",
+    "Phi", "Psi", "Omega",
+    // Structures
+    "frac", "dfrac", "tfrac", "cfrac", "sqrt", "binom", "dbinom", "tbinom", "choose", "over", "sum", "prod",
+    "coprod", "int", "iint", "iiint", "oint", "bigcup", "bigcap", "bigoplus", "bigotimes", "lim", "limits",
+    "nolimits", "overset", "underset", "stackrel", "overbrace", "underbrace", "boxed", "begin", "end",
+    "substack", "pmod", "bmod", "mod",
+    // Functions
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh", "coth", "log",
+    "ln", "lg", "exp", "max", "min", "sup", "inf", "liminf", "limsup", "det", "dim", "ker", "gcd", "deg", "arg",
+    "Pr", "operatorname",
+    // Operators and symbols
+    "pm", "mp", "times", "div", "cdot", "ast", "star", "circ", "bullet", "oplus", "ominus", "otimes", "odot",
+    "dagger", "cup", "cap", "setminus", "wedge", "vee", "land", "lor", "neg", "lnot", "infty", "partial",
+    "nabla", "forall", "exists", "nexists", "emptyset", "varnothing", "angle", "measuredangle", "triangle",
+    "square", "Box", "blacksquare", "degree", "prime", "ell", "hbar", "imath", "jmath", "Re", "Im", "aleph",
+    "wp", "checkmark", "cdots", "ldots", "dots", "vdots", "ddots", "because", "therefore", "S", "P", "%",
+    // Relations
+    "eq", "neq", "ne", "leq", "le", "geq", "ge", "leqslant", "geqslant", "ll", "gg", "lt", "gt", "approx",
+    "sim", "simeq", "cong", "equiv", "propto", "doteq", "triangleq", "in", "notin", "ni", "subset", "subseteq",
+    "subsetneq", "supset", "supseteq", "supsetneq", "perp", "parallel", "mid", "nmid", "prec", "succ",
+    "preceq", "succeq", "not",
+    // Arrows
+    "to", "gets", "rightarrow", "leftarrow", "leftrightarrow", "Rightarrow", "Leftarrow", "Leftrightarrow",
+    "longrightarrow", "longleftarrow", "Longrightarrow", "Longleftarrow", "longleftrightarrow", "iff",
+    "implies", "impliedby", "mapsto", "uparrow", "downarrow", "updownarrow", "Uparrow", "Downarrow",
+    "nearrow", "searrow", "xrightarrow", "xleftarrow", "rightleftharpoons", "rightharpoonup",
+    // Accents
+    "hat", "widehat", "bar", "overline", "underline", "vec", "overrightarrow", "overleftarrow", "dot", "ddot",
+    "tilde", "widetilde", "acute", "grave", "breve", "check", "mathring",
+    // Fonts and text
+    "mathrm", "mathbf", "mathit", "mathsf", "mathtt", "mathcal", "mathbb", "mathfrak", "mathscr", "boldsymbol",
+    "bm", "text", "textrm", "textbf", "textit", "texttt", "mbox", "rm", "bf", "it", "displaystyle",
+    "textstyle", "scriptstyle", "color", "textcolor",
+    // Spacing
+    "quad", "qquad", "hspace", "vspace", "kern", "mkern", "enspace", "thinspace", "phantom", "hphantom",
+    "vphantom",
+    // Delimiters
+    "left", "right", "middle", "big", "Big", "bigg", "Bigg", "bigl", "bigr", "Bigl", "Bigr", "biggl", "biggr",
+    "langle", "rangle", "lfloor", "rfloor", "lceil", "rceil", "vert", "Vert", "lvert", "rvert", "lVert",
+    "rVert", "backslash", "lbrace", "rbrace", "hline", "cline", "tag",
+];
+
+/// Kind of problem found in a formula
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatexIssueKind {
+    /// Nothing between the delimiters
+    Empty,
+    /// A `{` without its `}`, or the reverse
+    UnbalancedBraces,
+    /// A `\left` without its `\right`, or the reverse
+    UnbalancedDelimiters,
+    /// A `\begin{env}` without the matching `\end{env}`, or the reverse
+    UnbalancedEnvironment,
+    /// A command the renderer does not know
+    UnknownCommand,
+}
+
+impl LatexIssueKind {
+    /// Warning code used in reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            LatexIssueKind::Empty => "EMPTY_FORMULA",
+            LatexIssueKind::UnbalancedBraces => "UNBALANCED_BRACES",
+            LatexIssueKind::UnbalancedDelimiters => "UNBALANCED_DELIMITERS",
+            LatexIssueKind::UnbalancedEnvironment => "UNBALANCED_ENVIRONMENT",
+            LatexIssueKind::UnknownCommand => "UNKNOWN_COMMAND",
+        }
+    }
+}
+
+/// A problem found in one formula
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatexIssue {
+    pub kind: LatexIssueKind,
+    pub message: String,
+    /// The formula as written
+    pub formula: String,
+}
+
+impl std::fmt::Display for LatexIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} in {}", self.kind.code(), self.message, self.formula)
+    }
+}
+
+/// Checks formulas for syntax the renderer would fail on
+#[derive(Debug, Clone, Default)]
+pub struct LatexValidator {
+    /// Commands accepted beyond [`KNOWN_COMMANDS`], such as custom macros
+    extra_commands: BTreeSet<String>,
+}
+
+impl LatexValidator {
+    /// Create a validator accepting the built-in commands
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also accept `commands` (names without the backslash)
+    pub fn with_commands<S: Into<String>>(mut self, commands: impl IntoIterator<Item = S>) -> Self {
+        self.extra_commands.extend(commands.into_iter().map(Into::into));
+        self
+    }
+
+    fn is_known(&self, command: &str) -> bool {
+        KNOWN_COMMANDS.contains(&command) || self.extra_commands.contains(command)
+    }
+
+    /// Problems in `formula`, with or without its delimiters; empty if there
+    /// are none
+    pub fn validate(&self, formula: &str) -> Vec<LatexIssue> {
+        let (body, _, _) = split_formula(formula);
+        let issue = |kind, message: String| LatexIssue {
+            kind,
+            message,
+            formula: formula.trim().to_string(),
+        };
+        if body.is_empty() {
+            return vec![issue(LatexIssueKind::Empty, "empty formula".to_string())];
+        }
+
+        let mut issues = Vec::new();
+        let mut depth = 0usize;
+        let mut stray_closing = 0;
+        let mut left_right = 0i32;
+        let mut environments: Vec<String> = Vec::new();
+        let mut unknown = BTreeSet::new();
+        let mut chars = body.char_indices().peekable();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => stray_closing += 1,
+                '}' => depth -= 1,
+                '\\' => {
+                    let start = idx + 1;
+                    let mut end = start;
+                    while let Some(&(i, next)) = chars.peek() {
+                        if !next.is_ascii_alphabetic() {
+                            break;
+                        }
+                        end = i + next.len_utf8();
+                        chars.next();
+                    }
+                    if end == start {
+                        // Escaped symbol such as \{ or \,
+                        chars.next();
+                        continue;
+                    }
+                    let command = &body[start..end];
+                    match command {
+                        "left" => left_right += 1,
+                        "right" => left_right -= 1,
+                        "begin" | "end" => {
+                            let name = braced_argument(&body[end..]).unwrap_or_default();
+                            if command == "begin" {
+                                environments.push(name.to_string());
+                            } else if environments.last().map(String::as_str) == Some(name) {
+                                environments.pop();
+                            } else {
+                                issues.push(issue(
+                                    LatexIssueKind::UnbalancedEnvironment,
+                                    format!("\\end{{{}}} without \\begin{{{}}}", name, name),
+                                ));
+                            }
+                        }
+                        _ if !self.is_known(command) => {
+                            unknown.insert(command.to_string());
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if depth > 0 || stray_closing > 0 {
+            let message = match (depth, stray_closing) {
+                (0, n) => format!("{} unmatched }}", n),
+                (n, 0) => format!("{} unclosed {{", n),
+                (open, close) => format!("{} unclosed {{ and {} unmatched }}", open, close),
+            };
+            issues.push(issue(LatexIssueKind::UnbalancedBraces, message));
+        }
+        if left_right != 0 {
+            let (more, fewer) = if left_right > 0 { ("left", "right") } else { ("right", "left") };
+            issues.push(issue(
+                LatexIssueKind::UnbalancedDelimiters,
+                format!("{} more \\{} than \\{}", left_right.abs(), more, fewer),
+            ));
+        }
+        for name in environments {
+            issues.push(issue(
+                LatexIssueKind::UnbalancedEnvironment,
+                format!("\\begin{{{}}} without \\end{{{}}}", name, name),
+            ));
+        }
+        if !unknown.is_empty() {
+            let names: Vec<String> = unknown.iter().map(|c| format!("\\{}", c)).collect();
+            issues.push(issue(LatexIssueKind::UnknownCommand, format!("unknown {}", names.join(", "))));
+        }
+        issues
+    }
+
+    /// Problems in the formulas of `question`: its [`Question::latex`] and
+    /// any `$...$` in its text
+    pub fn check_question(&self, question: &Question) -> Vec<LatexIssue> {
+        let mut texts = vec![question.stem.as_str()];
+        texts.extend(question.options.iter().map(|o| o.content.as_str()));
+        texts.extend(question.answer.as_deref());
+        texts.extend(question.analysis.as_deref());
+        let inline = texts
+            .into_iter()
+            .flat_map(split_math)
+            .filter(|(_, is_math)| *is_math)
+            .map(|(formula, _)| formula);
+        let mut seen = BTreeSet::new();
+        question
+            .latex
+            .iter()
+            .map(String::as_str)
+            .chain(inline)
+            .filter(|formula| seen.insert(formula.trim()))
+            .flat_map(|formula| self.validate(formula))
+            .collect()
+    }
+}
+
+/// Split a formula into its body, whether it is displayed, and any
+/// trailing `%` comment
+pub(crate) fn split_formula(formula: &str) -> (&str, bool, Option<&str>) {
+    let formula = formula.trim();
+    let (formula, comment) = match unescaped_percent(formula) {
+        Some(idx) => (formula[..idx].trim_end(), Some(formula[idx..].trim())),
+        None => (formula, None),
+    };
+    let delimited = [("$$", "$$", true), ("\\[", "\\]", true), ("$", "$", false), ("\\(", "\\)", false)]
+        .into_iter()
+        .find_map(|(open, close, display)| {
+            let body = formula.strip_prefix(open)?.strip_suffix(close)?;
+            Some((body.trim(), display))
+        });
+    let (body, display) = delimited.unwrap_or((formula, false));
+    (body, display, comment)
+}
+
+/// Byte offset of the first `%` not escaped as `\%`
+fn unescaped_percent(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '%' if !escaped => return Some(idx),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// The text of a `{...}` argument at the start of `text`
+fn braced_argument(text: &str) -> Option<&str> {
+    let rest = text.trim_start().strip_prefix('{')?;
+    rest.find('}').map(|end| &rest[..end])
+}
+
+/// Rewrite a formula with `$...$` (inline) or `$$...$$` (display) delimiters
+///
+/// `\(...\)` becomes inline and `\[...\]` display; a formula without
+/// delimiters is taken as inline. Space inside the delimiters is trimmed and
+/// a trailing `%` comment is kept.
+pub fn normalize_formula(formula: &str) -> String {
+    let (body, display, comment) = split_formula(formula);
+    let delimiter = if display { "$$" } else { "$" };
+    match comment {
+        Some(comment) => format!("{}{}{} {}", delimiter, body, delimiter, comment),
+        None => format!("{}{}{}", delimiter, body, delimiter),
+    }
+}
+
+/// Normalize the delimiters of every formula in [`Question::latex`],
+/// dropping any that become duplicates
+pub fn normalize_question(question: &mut Question) {
+    let mut seen = BTreeSet::new();
+    question.latex = question
+        .latex
+        .drain(..)
+        .map(|formula| normalize_formula(&formula))
+        .filter(|formula| seen.insert(formula.clone()))
+        .collect();
+}
+
+/// Normalize every question's formulas and check them, returning formatted
+/// warnings
+pub fn validate_formulas(questions: &mut [Question]) -> Vec<String> {
+    let validator = LatexValidator::new();
+    questions
+        .iter_mut()
+        .flat_map(|question| {
+            normalize_question(question);
+            validator
+                .check_question(question)
+                .into_iter()
+                .map(|issue| format!("{} (question: {})", issue, preview(&question.stem)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(formula: &str) -> Vec<LatexIssueKind> {
+        LatexValidator::new().validate(formula).into_iter().map(|i| i.kind).collect()
+    }
+
+    #[test]
+    fn test_valid_formulas() {
+        for formula in [
+            "$x^2$",
+            "$$\\frac{1}{2} + \\sqrt[3]{x}$$",
+            "\\(\\left( \\alpha \\right)\\)",
+            "$\\begin{cases} x & x > 0 \\\\ -x & \\text{otherwise} \\end{cases}$",
+            "$\\{1, 2\\} \\% 50\\,\\mathrm{kg}$",
+            "$x^2 + 1$ % ocr: img/f.png",
+        ] {
+            assert!(kinds(formula).is_empty(), "{}: {:?}", formula, LatexValidator::new().validate(formula));
+        }
+    }
+
+    #[test]
+    fn test_invalid_formulas() {
+        use LatexIssueKind::*;
+        assert_eq!(kinds("$$"), [Empty]);
+        assert_eq!(kinds("$\\frac{1}{2$"), [UnbalancedBraces]);
+        assert_eq!(kinds("$x}$"), [UnbalancedBraces]);
+        assert_eq!(kinds("$\\left( x$"), [UnbalancedDelimiters]);
+        assert_eq!(kinds("$\\begin{matrix} 1 \\end{pmatrix}$"), [UnbalancedEnvironment, UnbalancedEnvironment]);
+        let issues = LatexValidator::new().validate("$\\fracc{1}{2} + \\alpah$");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, "unknown \\alpah, \\fracc");
+        assert!(LatexValidator::new().with_commands(["alpah", "fracc"]).validate("$\\fracc \\alpah$").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_formula() {
+        assert_eq!(normalize_formula("\\( x + 1 \\)"), "$x + 1$");
+        assert_eq!(normalize_formula("\\[x\\]"), "$$x$$");
+        assert_eq!(normalize_formula("$$ x $$"), "$$x$$");
+        assert_eq!(normalize_formula("\\alpha"), "$\\alpha$");
+        assert_eq!(normalize_formula("$x$   % ocr: a.png"), "$x$ % ocr: a.png");
+        assert_eq!(normalize_formula("$50\\%$"), "$50\\%$");
+    }
+
+    #[test]
+    fn test_validate_formulas() {
+        let mut questions = vec![Question {
+            stem: "Solve $\\frac{1}{x$ for x".to_string(),
+            latex: vec!["\\(y\\)".to_string(), "$y$".to_string()],
+            ..Question::default()
+        }];
+        let warnings = validate_formulas(&mut questions);
+        assert_eq!(questions[0].latex, ["$y$"]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("UNBALANCED_BRACES: 1 unclosed { in $\\frac{1}{x$"), "{}", warnings[0]);
+    }
+}
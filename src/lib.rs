@@ -17,20 +17,81 @@
 //! * B. O(log n)
 //! "#;
 //!
-//! let questions = parse_markdown_text(markdown);
+//! let questions = parse_markdown_text(markdown).unwrap();
 //! assert!(!questions.is_empty());
 //! ```
 
+pub mod error;
 pub mod models;
 pub mod parser;
+pub mod scan;
 pub mod database;
 pub mod media;
+pub mod ocr;
 pub mod classifier;
+pub mod tagger;
+pub mod render;
+pub mod bidi;
+pub mod typeset;
+pub mod presets;
+pub mod workdir;
+pub mod config;
+pub mod sample;
+pub mod answer;
+pub mod validate;
+pub mod latex;
+#[cfg(feature = "latex-render")]
+pub mod formula;
+pub mod consistency;
+pub mod preview;
+pub mod transform;
+pub mod gift;
+pub mod qti;
+#[cfg(feature = "anki")]
+pub mod anki;
+#[cfg(feature = "docx")]
+pub mod docx;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+#[cfg(any(feature = "csv", feature = "xlsx"))]
+pub mod tabular;
+pub mod archive;
+pub mod detect;
 pub mod zip;
+pub mod import;
+pub mod export;
+pub mod jsonl;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod markdown;
+pub mod dedup;
+pub mod duplicates;
+pub mod stats;
+pub mod search;
+pub mod similar;
+pub mod incremental;
+pub mod cache;
 pub mod processor;
+pub mod jobs;
+pub mod blocking;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod report;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "distributed")]
+pub mod distributed;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
 pub mod api;
+pub mod auth;
+pub mod body_limit;
+pub mod idempotency;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
-pub use models::{Question, QuestionType, QuestionOption, ImageRef};
+pub use error::Md2DbError;
+pub use models::{Question, QuestionType, QuestionOption, ImageRef, TextDirection};
 
 /// Parse markdown text into questions
 ///
@@ -52,15 +113,15 @@ pub use models::{Question, QuestionType, QuestionOption, ImageRef};
 ///
 /// let questions = parse_markdown_text(markdown);
 /// ```
-pub fn parse_markdown_text(text: &str) -> anyhow::Result<Vec<Question>> {
+pub fn parse_markdown_text(text: &str) -> Result<Vec<Question>, Md2DbError> {
     parser::parse_markdown(text)
 }
 
-/// Parse markdown from a ZIP file bytes
+/// Parse markdown from archive bytes
 ///
-/// Takes the raw bytes of a ZIP file containing Markdown files
-/// and returns a ZipProcessResult with all parsed questions.
-pub async fn parse_markdown_zip(data: &[u8]) -> anyhow::Result<zip::ZipProcessResult> {
+/// Takes the raw bytes of a ZIP (or tar.gz, 7z, RAR) archive containing
+/// Markdown files and returns a ZipProcessResult with all parsed questions.
+pub async fn parse_markdown_zip(data: &[u8]) -> Result<zip::ZipProcessResult, Md2DbError> {
     let processor = zip::ZipProcessor::new();
     processor.process_zip(data.to_vec()).await
 }
@@ -1,14 +1,9 @@
-mod models;
-mod parser;
-mod database;
-mod api;
-mod media;
-mod classifier;
-mod zip;
-mod processor;
-
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use md2db::config::AppConfig;
+use md2db::{api, cache, database, media, models::QuestionType, ocr, parser, presets, sample, workdir, zip};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
@@ -18,8 +13,68 @@ use tower_http::{
     trace::TraceLayer,
 };
 
+/// Convert exam questions from Markdown and other formats into a database
+#[derive(Parser)]
+#[command(name = "md2db", version, about)]
+struct Cli {
+    /// Configuration file; `config.toml` is read if present
+    #[arg(long, global = true, env = "MD2DB_CONFIG")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP API (the default when no command is given)
+    Serve(ServeArgs),
+    /// Convert files into JSON Lines, a SQLite file or PostgreSQL
+    Convert(ConvertArgs),
+    /// Summarize the questions found in a file
+    Inspect(InspectArgs),
+    /// Print the questions parsed from a Markdown file as JSON
+    Parse(ParseArgs),
+    /// Write a sample question bank in Markdown
+    GenerateSample(SampleArgs),
+    /// Permanently remove soft-deleted questions
+    Purge(PurgeArgs),
+    /// Import files dropped into a folder until interrupted
+    #[cfg(feature = "watch")]
+    Watch(WatchArgs),
+}
+
+#[derive(Args, Default)]
+struct ServeArgs {
+    /// Address to listen on, overriding the configuration
+    #[arg(long)]
+    host: Option<String>,
+    /// Port to listen on, overriding the configuration
+    #[arg(long)]
+    port: Option<u16>,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let config = AppConfig::load(cli.config.as_deref())?;
+    match cli.command {
+        Some(Command::Serve(args)) => serve(config, args).await,
+        None => serve(config, ServeArgs::default()).await,
+        Some(Command::Convert(args)) => convert(&config, args).await,
+        Some(Command::Inspect(args)) => inspect(&config, args).await,
+        Some(Command::Parse(args)) => parse_file(&config, args),
+        Some(Command::GenerateSample(args)) => generate_sample(args),
+        Some(Command::Purge(args)) => purge_deleted(&config, args).await,
+        #[cfg(feature = "watch")]
+        Some(Command::Watch(args)) => watch_dir(&config, args).await,
+    }
+}
+
+/// `md2db serve [--host HOST] [--port PORT]`: run the HTTP API
+async fn serve(mut config: AppConfig, args: ServeArgs) -> Result<()> {
+    config.server.host = args.host.unwrap_or(config.server.host);
+    config.server.port = args.port.unwrap_or(config.server.port);
+
     // Initialize tracing
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::INFO)
@@ -30,22 +85,70 @@ async fn main() -> Result<()> {
 
     info!("MD2DB Rust - Starting up...");
 
-    // Get configuration from environment
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()
-        .unwrap_or(8080);
+    // Sweep working directories leaked by previous crashes
+    let workdirs = Arc::new(workdir::WorkdirManager::new(config.workdir_config())?);
+    match workdirs.cleanup_stale() {
+        Ok(0) => {}
+        Ok(n) => info!("Removed {} stale working directories", n),
+        Err(e) => tracing::warn!("Failed to sweep working directories: {}", e),
+    }
+
+    let repository = with_cache(create_repository(&config).await?, &config)?;
 
-    let host = std::env::var("HOST")
-        .unwrap_or_else(|_| "0.0.0.0".to_string());
+    let store = media_store(&config)?;
+    let ocr = formula_ocr(&config)?;
+    let spill = spill_config(&config, workdirs);
 
-    // Use mock repository by default (can be configured for PostgreSQL/MongoDB)
-    let repository: Arc<dyn database::QuestionRepository> =
-        Arc::new(database::MockRepository::new());
+    // One processor, and so one parsing pool, serves every import
+    let mut processor = md2db::processor::SingleMachineProcessor::with_config(
+        repository.clone(),
+        config.processor_config(None)?,
+    );
+    if let Some(store) = &store {
+        processor = processor.with_media_store(store.clone());
+    }
+    if let Some(ocr) = &ocr {
+        processor = processor.with_formula_ocr(ocr.clone());
+    }
+    if let Some(spill) = &spill {
+        processor = processor.with_spill(spill.clone());
+    }
+
+    // Background imports; jobs cut off by a restart cannot be resumed
+    let jobs = md2db::jobs::JobManager::new(processor);
+    #[cfg(feature = "webhooks")]
+    let jobs = match config.webhook_notifier() {
+        Some(notifier) => {
+            info!("Notifying {} webhook URL(s) when imports finish", notifier.urls().len());
+            jobs.with_listener(Arc::new(notifier))
+        }
+        None => jobs,
+    };
+    let jobs = Arc::new(jobs);
+    if let Err(e) = jobs.recover().await {
+        tracing::warn!("Failed to recover import jobs: {}", e);
+    }
 
     // Create API router with repository state
-    let app = api::create_router()
+    let mut app = api::create_router()
         .with_state(repository.clone())
+        .layer(axum::Extension(config.zip_limits()))
+        .layer(axum::Extension(jobs));
+    if let Some(store) = store {
+        app = app.layer(axum::Extension(store));
+    }
+    if let Some(ocr) = ocr {
+        app = app.layer(axum::Extension(ocr));
+    }
+    if let Some(spill) = spill {
+        app = app.layer(axum::Extension(spill));
+    }
+    app = app.layer(axum::Extension(config.body_limits()));
+    if let Some(auth) = config.authenticator() {
+        info!("API authentication enabled ({} keys)", config.auth.keys.len());
+        app = app.layer(axum::middleware::from_fn_with_state(Arc::new(auth), md2db::auth::require_auth));
+    }
+    let app = app
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
@@ -53,7 +156,7 @@ async fn main() -> Result<()> {
         );
 
     // Bind to address
-    let addr = SocketAddr::new(host.parse()?, port);
+    let addr = SocketAddr::new(config.server.host.parse()?, config.server.port);
     info!("Server listening on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
@@ -62,3 +165,559 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Select the repository backend from the configuration
+///
+/// Uses PostgreSQL when a database URL is set, otherwise the in-memory mock
+/// repository.
+async fn create_repository(config: &AppConfig) -> Result<Arc<dyn database::QuestionRepository>> {
+    #[cfg(feature = "postgres")]
+    if let Some(url) = &config.database.url {
+        return connect_postgres(config, url).await;
+    }
+
+    info!("Using in-memory mock repository");
+    Ok(Arc::new(database::MockRepository::new()))
+}
+
+/// Connect to PostgreSQL at `url`
+///
+/// Applies migrations first if `auto_migrate` is set, and takes the schema
+/// layout, pool settings and read replica from the `[database]` settings.
+#[cfg(feature = "postgres")]
+async fn connect_postgres(config: &AppConfig, url: &str) -> Result<Arc<dyn database::QuestionRepository>> {
+    use database::postgres::{PostgresRepository, SchemaMode};
+
+    let mode = if config.normalized_schema() { SchemaMode::Normalized } else { SchemaMode::Json };
+    let pool = config.database_config()?;
+    info!("Database pool: {:?}", pool);
+    let repo = match &config.database.replica_url {
+        Some(replica_url) => {
+            info!("Routing reads to the read replica");
+            PostgresRepository::connect_with_replica(url, replica_url, &pool).await?
+        }
+        None => PostgresRepository::connect(url, &pool).await?,
+    }
+    .with_mode(mode);
+
+    if config.database.auto_migrate {
+        let applied = repo.migrate().await?;
+        info!("Database migrations applied: {:?}", applied);
+    }
+
+    info!("Using PostgreSQL repository ({:?} schema)", repo.mode());
+    Ok(Arc::new(repo))
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn connect_postgres(_config: &AppConfig, _url: &str) -> Result<Arc<dyn database::QuestionRepository>> {
+    anyhow::bail!("PostgreSQL output requires building with --features postgres")
+}
+
+/// Wrap the repository in a read cache when a cache URL is set
+///
+/// `memory` caches in-process; a `redis://` URL shares the cache between
+/// instances and needs the `redis` feature.
+fn with_cache(
+    repository: Arc<dyn database::QuestionRepository>,
+    config: &AppConfig,
+) -> Result<Arc<dyn database::QuestionRepository>> {
+    let Some(url) = &config.cache.url else {
+        return Ok(repository);
+    };
+
+    let backend: Arc<dyn cache::QuestionCache> = if url == "memory" {
+        Arc::new(cache::MemoryCache::new())
+    } else {
+        redis_cache(url)?
+    };
+    let ttl = config
+        .cache
+        .ttl_secs
+        .map(std::time::Duration::from_secs)
+        .unwrap_or(cache::DEFAULT_CACHE_TTL);
+
+    info!("Caching question reads in {} (TTL {:?})", if url == "memory" { "memory" } else { "Redis" }, ttl);
+    Ok(Arc::new(cache::CachedRepository::new(repository, backend).with_ttl(ttl)))
+}
+
+/// Open the filesystem media store, if a directory is configured
+///
+/// Without it, images in uploaded archives are hashed but not kept.
+fn media_store(config: &AppConfig) -> Result<Option<Arc<dyn media::MediaStore>>> {
+    match &config.media.dir {
+        Some(dir) => {
+            info!("Storing extracted images in {}", dir.display());
+            Ok(Some(Arc::new(media::FsMediaStore::new(dir)?)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Spill archive entries above the configured threshold to disk
+///
+/// Entries go to the working directories; a threshold of 0 keeps
+/// everything in memory.
+fn spill_config(config: &AppConfig, workdirs: Arc<workdir::WorkdirManager>) -> Option<zip::SpillConfig> {
+    let threshold = config.spill_threshold()?;
+    info!("Spilling archive entries over {} bytes to {:?}", threshold, workdirs.config().root);
+    Some(zip::SpillConfig::new(workdirs, threshold))
+}
+
+/// Recognize formulas in uploaded images with the configured command, if any
+///
+/// The command gets each image on stdin and prints LaTeX on stdout.
+fn formula_ocr(config: &AppConfig) -> Result<Option<Arc<dyn ocr::FormulaOcr>>> {
+    let Some(command) = config.ocr.command.as_deref() else {
+        return Ok(None);
+    };
+    let timeout = config
+        .ocr
+        .timeout_secs
+        .map(std::time::Duration::from_secs)
+        .unwrap_or(ocr::DEFAULT_OCR_TIMEOUT);
+
+    info!("Recognizing formulas in images with `{}`", command);
+    Ok(Some(Arc::new(ocr::CommandOcr::from_command_line(command)?.with_timeout(timeout))))
+}
+
+#[cfg(feature = "redis")]
+fn redis_cache(url: &str) -> Result<Arc<dyn cache::QuestionCache>> {
+    Ok(Arc::new(cache::redis::RedisCache::new(url)?))
+}
+
+#[cfg(not(feature = "redis"))]
+fn redis_cache(url: &str) -> Result<Arc<dyn cache::QuestionCache>> {
+    anyhow::bail!("Cache URL {} requires building with --features redis", url)
+}
+
+/// Log to stderr, keeping stdout for command output
+fn init_cli_tracing(verbose: bool) {
+    let subscriber = FmtSubscriber::builder()
+        .with_max_level(if verbose { Level::INFO } else { Level::WARN })
+        .with_writer(std::io::stderr)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+}
+
+/// Parser options for `--preset`, else the configured preset, else the defaults
+fn parser_options(config: &AppConfig, preset: Option<&str>) -> Result<parser::ParserOptions> {
+    match preset.or(config.processor.preset.as_deref()) {
+        Some(name) => presets::preset(name),
+        None => Ok(parser::ParserOptions::default()),
+    }
+}
+
+/// Where `md2db convert` writes the questions
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConvertOutput {
+    /// JSON Lines, one question per line
+    Jsonl,
+    /// A SQLite database file
+    Sqlite,
+    /// The PostgreSQL database at `--database-url`
+    Postgres,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// Files or folders to convert; folders are searched recursively and
+    /// `-` reads standard input
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    /// File name standard input is reported under, which also tells plain
+    /// text from Markdown
+    #[arg(long, default_value = "stdin.md")]
+    stdin_name: String,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ConvertOutput::Jsonl)]
+    out: ConvertOutput,
+    /// File to write; JSON Lines go to stdout without one
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// PostgreSQL connection URL for `--out postgres`, overriding the configuration
+    #[arg(long)]
+    database_url: Option<String>,
+    /// Parser preset: default, xuekewang-export, kaoshibao or generic-numbered
+    #[arg(short, long)]
+    preset: Option<String>,
+    /// Skip questions that duplicate one already converted or stored
+    #[arg(long)]
+    dedup: bool,
+    /// Log each step to stderr
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// `md2db convert PATH... [--out jsonl|sqlite|postgres] [--output FILE]`
+///
+/// Runs the same pipeline as the HTTP API on local files. JSON Lines and
+/// SQLite output collect the questions in memory first; PostgreSQL output
+/// writes them as each file is processed. With `-` as a path, standard
+/// input is converted too, so `md2db convert - < exam.md > exam.jsonl`
+/// works in a pipeline; diagnostics always go to stderr.
+async fn convert(app: &AppConfig, args: ConvertArgs) -> Result<()> {
+    use md2db::dedup::DedupOptions;
+    use md2db::jsonl::{export_jsonl, ExportFilter};
+    use md2db::processor::{InputSource, SingleMachineProcessor};
+    use tokio::io::AsyncReadExt;
+
+    init_cli_tracing(args.verbose);
+    if args.out == ConvertOutput::Sqlite && args.output.is_none() {
+        anyhow::bail!("--out sqlite needs --output FILE");
+    }
+    if args.paths.iter().filter(|p| p.as_path() == Path::new("-")).count() > 1 {
+        anyhow::bail!("standard input (-) can only be converted once");
+    }
+    let files = input_files(&args.paths)?;
+
+    let repository: Arc<dyn database::QuestionRepository> = match args.out {
+        ConvertOutput::Postgres => {
+            let url = args
+                .database_url
+                .as_deref()
+                .or(app.database.url.as_deref())
+                .context("--out postgres needs --database-url or a configured database")?;
+            connect_postgres(app, url).await?
+        }
+        _ => Arc::new(database::MockRepository::new()),
+    };
+    let mut config = app.processor_config(args.preset.as_deref())?;
+    if args.dedup {
+        config = config.with_dedup(DedupOptions::default());
+    }
+    let mut processor = SingleMachineProcessor::with_config(repository.clone(), config);
+    if let Some(store) = media_store(app)? {
+        processor = processor.with_media_store(store);
+    }
+
+    let (mut saved, mut duplicates, mut failed) = (0, 0, 0);
+    for file in &files {
+        let (source, data) = if file.path == Path::new("-") {
+            let mut data = Vec::new();
+            tokio::io::stdin().read_to_end(&mut data).await.context("Failed to read standard input")?;
+            (args.stdin_name.clone(), data)
+        } else {
+            let source = file.path.display().to_string();
+            let data = std::fs::read(&file.path).with_context(|| format!("Failed to read {}", source))?;
+            (source, data)
+        };
+        if !file.explicit && !md2db::detect::FormatDetector::detect(&source, &data).is_some_and(|f| f.is_enabled()) {
+            continue;
+        }
+        match processor.process(InputSource::File { data, source: source.clone() }).await {
+            Ok(result) => {
+                saved += result.saved_questions;
+                duplicates += result.duplicate_questions;
+                for warning in &result.warnings {
+                    eprintln!("{}", warning);
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {:#}", source, e);
+            }
+        }
+    }
+
+    match (args.out, &args.output) {
+        (ConvertOutput::Jsonl, Some(path)) => {
+            let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(path).await?);
+            export_jsonl(repository.as_ref(), &mut writer, &ExportFilter::default()).await?;
+        }
+        (ConvertOutput::Jsonl, None) => {
+            let mut writer = tokio::io::BufWriter::new(tokio::io::stdout());
+            export_jsonl(repository.as_ref(), &mut writer, &ExportFilter::default()).await?;
+        }
+        (ConvertOutput::Sqlite, Some(path)) => export_to_sqlite(repository.as_ref(), path).await?,
+        _ => {}
+    }
+
+    eprintln!(
+        "Converted {} questions from {} files ({} duplicates skipped, {} files failed)",
+        saved,
+        files.len(),
+        duplicates,
+        failed
+    );
+    if failed > 0 {
+        anyhow::bail!("{} of {} files could not be converted", failed, files.len());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+async fn export_to_sqlite(repository: &dyn database::QuestionRepository, path: &Path) -> Result<()> {
+    md2db::sqlite::export_sqlite(repository, path, &Default::default()).await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn export_to_sqlite(_repository: &dyn database::QuestionRepository, _path: &Path) -> Result<()> {
+    anyhow::bail!("--out sqlite requires building with --features sqlite")
+}
+
+/// A file to convert
+struct InputFile {
+    path: PathBuf,
+    /// Named on the command line rather than found in a folder
+    explicit: bool,
+}
+
+/// Files named in `paths`, with folders expanded recursively in name order
+///
+/// Hidden files and folders are skipped; `-` is kept as is.
+fn input_files(paths: &[PathBuf]) -> Result<Vec<InputFile>> {
+    fn walk(dir: &Path, files: &mut Vec<InputFile>) -> Result<()> {
+        let mut entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                walk(&path, files)?;
+            } else {
+                files.push(InputFile { path, explicit: false });
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk(path, &mut files)?;
+        } else {
+            files.push(InputFile {
+                path: path.clone(),
+                explicit: true,
+            });
+        }
+    }
+    Ok(files)
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    /// File to inspect
+    file: PathBuf,
+    /// Parser preset: default, xuekewang-export, kaoshibao or generic-numbered
+    #[arg(short, long)]
+    preset: Option<String>,
+}
+
+/// `md2db inspect FILE [--preset NAME]`
+///
+/// Parses a file of any supported format without touching the database
+/// and prints its format, question counts by type, and the warnings and
+/// validation failures an import would report.
+async fn inspect(app: &AppConfig, args: InspectArgs) -> Result<()> {
+    use md2db::database::{ListParams, QuestionRepository};
+    use md2db::processor::{InputSource, SingleMachineProcessor};
+    use md2db::validate::QuestionValidator;
+    use std::collections::BTreeMap;
+
+    init_cli_tracing(false);
+    let source = args.file.display().to_string();
+    let data = std::fs::read(&args.file).with_context(|| format!("Failed to read {}", source))?;
+    let format = md2db::detect::FormatDetector::detect(&source, &data)
+        .with_context(|| format!("{}: not a recognized question file", source))?;
+
+    let config = app.processor_config(args.preset.as_deref())?.with_validation(QuestionValidator::new());
+    let processor = SingleMachineProcessor::with_config(database::MockRepository::new(), config);
+    let result = processor.process(InputSource::File { data, source: source.clone() }).await?;
+
+    let mut questions = processor
+        .repository()
+        .list(&ListParams {
+            limit: Some(database::MAX_PAGE_SIZE),
+            ..ListParams::default()
+        })
+        .await?
+        .items;
+    questions.extend(result.rejected.iter().map(|r| r.question.clone()));
+    let mut by_type: BTreeMap<String, usize> = BTreeMap::new();
+    for question in &questions {
+        let qtype = serde_json::to_value(question.qtype)?.as_str().unwrap_or_default().to_string();
+        *by_type.entry(qtype).or_default() += 1;
+    }
+
+    println!("{}: {}, {} questions", source, format, result.total_questions);
+    for (qtype, count) in &by_type {
+        println!("  {:<16} {}", qtype, count);
+    }
+    println!(
+        "  images {}, formulas {}",
+        result.total_images,
+        questions.iter().map(|q| q.latex.len()).sum::<usize>()
+    );
+    if !result.warnings.is_empty() {
+        println!("Warnings:");
+        for warning in &result.warnings {
+            println!("  {}", warning);
+        }
+    }
+    if !result.rejected.is_empty() {
+        println!("Would be rejected:");
+        for rejected in &result.rejected {
+            let stem: String = rejected.question.stem.lines().next().unwrap_or_default().chars().take(60).collect();
+            println!("  {:?}: {}", stem, rejected.reasons.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Args)]
+struct PurgeArgs {
+    /// Only remove questions deleted at least this many days ago
+    #[arg(long, default_value_t = 30)]
+    older_than_days: i64,
+}
+
+/// `md2db purge [--older-than-days N]`: permanently remove soft-deleted questions
+async fn purge_deleted(config: &AppConfig, args: PurgeArgs) -> Result<()> {
+    let before = chrono::Utc::now() - chrono::Duration::days(args.older_than_days.max(0));
+    let purged = create_repository(config).await?.purge(before).await?;
+    println!("Purged {} questions deleted before {}", purged, before.to_rfc3339());
+
+    Ok(())
+}
+
+#[cfg(feature = "watch")]
+#[derive(Args)]
+struct WatchArgs {
+    /// Folder to watch
+    dir: PathBuf,
+    /// Parser preset: default, xuekewang-export, kaoshibao or generic-numbered
+    #[arg(short, long)]
+    preset: Option<String>,
+    /// Move imported files here
+    #[arg(long)]
+    done: Option<PathBuf>,
+    /// Move files that failed to import here
+    #[arg(long)]
+    failed: Option<PathBuf>,
+    /// Wait this long after the last change to a file before importing it
+    #[arg(long)]
+    settle_secs: Option<u64>,
+}
+
+/// `md2db watch DIR [--preset NAME] [--done DIR] [--failed DIR] [--settle-secs N]`
+///
+/// Imports files dropped into `DIR` into the configured repository until
+/// interrupted, moving each to the done or failed folder.
+#[cfg(feature = "watch")]
+async fn watch_dir(app: &AppConfig, args: WatchArgs) -> Result<()> {
+    use md2db::processor::SingleMachineProcessor;
+    use md2db::watch::DirectoryWatcher;
+
+    let config = app.processor_config(args.preset.as_deref())?;
+
+    let subscriber = FmtSubscriber::builder().with_max_level(Level::INFO).finish();
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+
+    let mut processor = SingleMachineProcessor::with_config(with_cache(create_repository(app).await?, app)?, config);
+    if let Some(store) = media_store(app)? {
+        processor = processor.with_media_store(store);
+    }
+    if let Some(ocr) = formula_ocr(app)? {
+        processor = processor.with_formula_ocr(ocr);
+    }
+
+    let mut watcher = DirectoryWatcher::new(&args.dir, processor);
+    if let Some(done) = args.done {
+        watcher = watcher.with_done_dir(done);
+    }
+    if let Some(failed) = args.failed {
+        watcher = watcher.with_failed_dir(failed);
+    }
+    if let Some(secs) = args.settle_secs {
+        watcher = watcher.with_settle_time(std::time::Duration::from_secs(secs));
+    }
+
+    info!("Watching {} for new files", args.dir.display());
+    watcher.run().await
+}
+
+#[derive(Args)]
+struct SampleArgs {
+    /// Number of questions
+    #[arg(short = 'n', long)]
+    count: Option<usize>,
+    /// Question types to include, comma-separated (e.g. choice,truefalse)
+    #[arg(short, long, value_delimiter = ',')]
+    types: Vec<QuestionType>,
+    /// Random seed, for a reproducible bank
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Leave out images
+    #[arg(long)]
+    no_images: bool,
+    /// Leave out LaTeX formulas
+    #[arg(long)]
+    no_latex: bool,
+    /// File to write instead of stdout
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+/// `md2db generate-sample [--count N] [--types a,b] [--seed S] [--no-images] [--no-latex] [--out FILE]`
+fn generate_sample(args: SampleArgs) -> Result<()> {
+    let mut config = sample::SampleConfig::default();
+    if let Some(count) = args.count {
+        config = config.with_count(count);
+    }
+    if !args.types.is_empty() {
+        config = config.with_types(args.types);
+    }
+    if let Some(seed) = args.seed {
+        config = config.with_seed(seed);
+    }
+    if args.no_images {
+        config = config.with_images(false);
+    }
+    if args.no_latex {
+        config = config.with_latex(false);
+    }
+
+    let markdown = sample::generate_markdown(&config);
+    match args.out {
+        Some(path) => std::fs::write(path, markdown)?,
+        None => print!("{}", markdown),
+    }
+
+    Ok(())
+}
+
+#[derive(Args)]
+struct ParseArgs {
+    /// Markdown file to parse
+    file: PathBuf,
+    /// Parser preset: default, xuekewang-export, kaoshibao or generic-numbered
+    #[arg(short, long)]
+    preset: Option<String>,
+}
+
+/// `md2db parse FILE [--preset NAME]`
+///
+/// Prints the parsed questions as JSON without touching the database, which
+/// is handy for checking which preset fits an export.
+fn parse_file(config: &AppConfig, args: ParseArgs) -> Result<()> {
+    let options = parser_options(config, args.preset.as_deref())?;
+    let path = args.file.display();
+    let data = std::fs::read(&args.file)?;
+    let (markdown, encoding) =
+        zip::decode_text(&data).ok_or_else(|| anyhow::anyhow!("{}: not UTF-8, UTF-16 or GB18030 text", path))?;
+    if encoding != encoding_rs::UTF_8 {
+        eprintln!("{}: decoded as {}", path, encoding.name());
+    }
+    let questions = parser::parse_markdown_with_options(&markdown, &options)?;
+    println!("{}", serde_json::to_string_pretty(&questions)?);
+
+    Ok(())
+}
@@ -0,0 +1,414 @@
+//! Markdown export
+//!
+//! [`MarkdownRenderer`] writes stored questions back out in the canonical
+//! Markdown layout the parser reads: the stem as a heading, image links,
+//! options as a lettered list and `Answer:` / `Analysis:` paragraphs. Text
+//! is escaped so it reads back unchanged, while `$…$` formulas stay
+//! readable. Exported questions can be edited by hand and imported again;
+//! [`MarkdownRenderer::archive`] bundles a file with its local images so the
+//! image links still resolve when the ZIP is re-uploaded.
+
+use crate::bidi::{export_text, split_math};
+use crate::media::{media_path, validate_image, MediaStore};
+use crate::models::{ImageRef, Question};
+use crate::render::{option_letter, strip_option_prefix};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+
+/// Name of the Markdown file inside an exported archive
+pub const ARCHIVE_MARKDOWN_FILE: &str = "questions.md";
+
+/// An exported Markdown archive
+#[derive(Debug)]
+pub struct MarkdownArchive {
+    /// The ZIP file
+    pub data: Vec<u8>,
+    /// Image files bundled next to the Markdown
+    pub media_files: usize,
+    /// Images that could not be bundled
+    pub warnings: Vec<String>,
+}
+
+/// Writes questions as parser-compatible Markdown
+#[derive(Default)]
+pub struct MarkdownRenderer {
+    media_store: Option<Arc<dyn MediaStore>>,
+}
+
+impl MarkdownRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bundle local images from `store` in [`archive`](Self::archive)
+    pub fn with_media_store(mut self, store: Arc<dyn MediaStore>) -> Self {
+        self.media_store = Some(store);
+        self
+    }
+
+    /// Render one question
+    pub fn render(&self, question: &Question) -> String {
+        let mut out = format!("# {}\n", escape_heading(&export_text(question, &question.stem)));
+
+        for image in &question.images {
+            let alt = image.alt().map(|alt| escape_inline(&single_line(alt))).unwrap_or_default();
+            out.push_str(&format!("\n![{}]({})\n", alt, link_destination(&image_link(image))));
+        }
+
+        // Formulas that came from code spans are not part of any text field
+        let texts: Vec<&str> = std::iter::once(question.stem.as_str())
+            .chain(question.options.iter().map(|o| o.content.as_str()))
+            .chain(question.answer.as_deref())
+            .chain(question.analysis.as_deref())
+            .collect();
+        for formula in &question.latex {
+            if !formula.contains('`') && !texts.iter().any(|text| text.contains(formula.as_str())) {
+                out.push_str(&format!("\n`{}`\n", formula));
+            }
+        }
+
+        if !question.options.is_empty() {
+            out.push('\n');
+            for (idx, option) in question.options.iter().enumerate() {
+                let content = single_line(&export_text(question, strip_option_prefix(&option.content, idx)));
+                out.push_str(&format!("* {}. {}\n", option_letter(idx), escape_inline(&content)));
+            }
+        }
+
+        if let Some(answer) = question.answer.as_deref().filter(|a| !a.trim().is_empty()) {
+            out.push_str(&format!("\nAnswer: {}\n", escape_block(answer.trim())));
+        }
+
+        if let Some(analysis) = question.analysis.as_deref().filter(|a| !a.trim().is_empty()) {
+            out.push_str(&format!("\nAnalysis: {}\n", escape_block(&export_text(question, analysis.trim()))));
+        }
+
+        out
+    }
+
+    /// Render questions into one document, separated by blank lines
+    ///
+    /// A question whose section differs from the one before it is preceded
+    /// by the section title as a heading, which the parser reads back.
+    pub fn render_all(&self, questions: &[Question]) -> String {
+        let mut section = None;
+        questions
+            .iter()
+            .map(|question| {
+                let rendered = self.render(question);
+                match question.section.as_deref() {
+                    Some(title) if section != Some(title) => {
+                        section = Some(title);
+                        format!("# {}\n\n{}", escape_heading(title), rendered)
+                    }
+                    _ => rendered,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// ZIP holding [`ARCHIVE_MARKDOWN_FILE`] and the local images it links to
+    ///
+    /// Images are written at their media store path, which the Markdown
+    /// links to, so uploading the archive to `/parse-archive` re-attaches
+    /// them. Images missing from the store keep their link and are reported.
+    pub async fn archive(&self, questions: &[Question]) -> Result<MarkdownArchive> {
+        let mut questions = questions.to_vec();
+        let mut bundled = HashSet::new();
+        let mut files = Vec::new();
+        let mut warnings = Vec::new();
+
+        for question in &mut questions {
+            let id = question.id;
+            for image in &mut question.images {
+                let ImageRef::Local { hash, original_path, stored_path, .. } = image else {
+                    continue;
+                };
+                let data = match &self.media_store {
+                    Some(store) if !hash.is_empty() => store.get(hash).await,
+                    _ => Ok(None),
+                };
+                match data {
+                    Ok(Some(data)) => {
+                        let path = stored_path.clone().unwrap_or_else(|| {
+                            let extension = validate_image(&data)
+                                .map(|info| info.format.to_string())
+                                .unwrap_or_else(|_| "bin".to_string());
+                            media_path(hash, &extension)
+                        });
+                        if bundled.insert(path.clone()) {
+                            files.push((path.clone(), data));
+                        }
+                        *stored_path = Some(path);
+                    }
+                    Ok(None) => warnings.push(format!("{}: image {} is not in the media store", id, original_path)),
+                    Err(e) => warnings.push(format!("{}: failed to read image {}: {}", id, original_path, e)),
+                }
+            }
+        }
+
+        let markdown = self.render_all(&questions);
+        let mut data = Vec::new();
+        {
+            let mut writer = ::zip::ZipWriter::new(Cursor::new(&mut data));
+            let options = ::zip::write::SimpleFileOptions::default();
+            writer.start_file(ARCHIVE_MARKDOWN_FILE, options)?;
+            writer.write_all(markdown.as_bytes())?;
+            for (path, content) in &files {
+                writer.start_file(path.as_str(), options)?;
+                writer.write_all(content)?;
+            }
+            writer.finish()?;
+        }
+
+        Ok(MarkdownArchive {
+            data,
+            media_files: files.len(),
+            warnings,
+        })
+    }
+}
+
+/// Link target for an image: the stored copy if there is one
+fn image_link(image: &ImageRef) -> String {
+    match image {
+        ImageRef::Remote { url, .. } => url.clone(),
+        ImageRef::Local { original_path, stored_path, .. } => {
+            stored_path.clone().unwrap_or_else(|| original_path.clone())
+        }
+    }
+}
+
+/// Wrap a link destination in `<>` when it would otherwise end early
+fn link_destination(link: &str) -> String {
+    if link.contains([' ', '(', ')', '<', '>']) {
+        format!("<{}>", link.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        link.to_string()
+    }
+}
+
+/// Collapse line breaks, which a heading or list item cannot hold
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Escape a stem for use as heading text
+fn escape_heading(stem: &str) -> String {
+    let escaped = escape_inline(&single_line(stem));
+    // A trailing `#` would be read as a closing sequence
+    match escaped.strip_suffix('#') {
+        Some(rest) => format!("{}\\#", rest),
+        None => escaped,
+    }
+}
+
+/// Escape paragraph text that may span lines, so no line starts a block
+fn escape_block(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(idx, line)| {
+            let escaped = escape_inline(line);
+            if idx > 0 && starts_block(&escaped) {
+                format!("\\{}", escaped)
+            } else {
+                escaped
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether a line would open a heading, quote, list, table or setext underline
+fn starts_block(line: &str) -> bool {
+    if line.starts_with(['#', '>', '-', '+', '|', '=']) {
+        return true;
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    digits > 0 && line[digits..].starts_with(['.', ')'])
+}
+
+/// Escape inline Markdown syntax, leaving `$…$` formulas as written
+///
+/// Inside a formula only what Markdown would still interpret is escaped:
+/// emphasis markers, brackets, and backslashes before punctuation.
+fn escape_inline(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (segment, is_math) in split_math(text) {
+        let chars: Vec<char> = segment.chars().collect();
+        for (i, &c) in chars.iter().enumerate() {
+            let escape = if is_math {
+                match c {
+                    '\\' => chars.get(i + 1).is_some_and(|n| n.is_ascii_punctuation()),
+                    // Underscores between letters or digits never start emphasis
+                    '_' => !(i > 0
+                        && chars[i - 1].is_alphanumeric()
+                        && chars.get(i + 1).is_some_and(|n| n.is_alphanumeric())),
+                    '*' | '`' | '[' | ']' | '<' | '&' => true,
+                    _ => false,
+                }
+            } else {
+                matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '&' | '$')
+            };
+            if escape {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::answer::validate_answers;
+    use crate::media::FsMediaStore;
+    use crate::models::{QuestionOption, QuestionType, TextDirection};
+    use crate::parser::parse_markdown;
+    use std::io::Read;
+
+    fn option(content: &str, sort_order: i32) -> QuestionOption {
+        QuestionOption {
+            content: content.to_string(),
+            sort_order,
+            is_correct: false,
+        }
+    }
+
+    #[test]
+    fn test_render_question() {
+        let question = Question {
+            qtype: QuestionType::Choice,
+            stem: "What is $\\frac{1}{2}$ of 4?".to_string(),
+            options: vec![option("A. 1", 0), option("2", 1)],
+            answer: Some("B".to_string()),
+            analysis: Some("Half of four.\n- Two".to_string()),
+            images: vec![ImageRef::Remote { url: "https://example.com/a b.png".to_string(), alt: None }],
+            latex: vec!["$x^2$".to_string(), "$\\frac{1}{2}$".to_string()],
+            ..Question::default()
+        };
+
+        assert_eq!(
+            MarkdownRenderer::new().render(&question),
+            "# What is $\\frac{1}{2}$ of 4?\n\n\
+             ![](<https://example.com/a b.png>)\n\n\
+             `$x^2$`\n\n\
+             * A. 1\n* B. 2\n\n\
+             Answer: B\n\n\
+             Analysis: Half of four.\n\\- Two\n"
+        );
+    }
+
+    #[test]
+    fn test_escape_inline() {
+        assert_eq!(escape_inline("*not* bold [x] $5"), "\\*not\\* bold \\[x\\] \\$5");
+        assert_eq!(escape_inline("$a_1 * b_{n}$ and $\\{x\\}$"), "$a_1 \\* b\\_{n}$ and $\\\\{x\\\\}$");
+        assert_eq!(escape_heading("Rate C#"), "Rate C\\#");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let questions = vec![
+            Question {
+                stem: "Which of *these* is $\\{x \\mid x_1 < 2\\}$? C# 1".to_string(),
+                options: vec![option("A. [1, 2]", 0), option("B. <none>", 1), option("C. 1. first", 2)],
+                answer: Some("B".to_string()),
+                analysis: Some("Use `sets` & $a*b$.".to_string()),
+                section: Some("一、单选题".to_string()),
+                ..Question::default()
+            },
+            Question {
+                stem: "解释 $E = mc^2$ 的含义 #".to_string(),
+                analysis: Some("质能等价".to_string()),
+                latex: vec!["$\\hbar$".to_string()],
+                images: vec![ImageRef::Local {
+                    hash: "abc".to_string(),
+                    original_path: "img/old.png".to_string(),
+                    stored_path: Some("ab/c0/abc.png".to_string()),
+                    alt: Some("Energy [J] vs. mass".to_string()),
+                }],
+                section: Some("二、简答题".to_string()),
+                ..Question::default()
+            },
+            Question {
+                stem: "احسب $x^2$ الآن".to_string(),
+                options: vec![option("A. $x = 1$", 0), option("B. $x = 2$", 1)],
+                analysis: Some("لأن $2^2 = 4$".to_string()),
+                section: Some("二、简答题".to_string()),
+                text_direction: TextDirection::Rtl,
+                ..Question::default()
+            },
+        ];
+
+        let markdown = MarkdownRenderer::new().render_all(&questions);
+        assert!(markdown.contains("# احسب \u{2066}$x^2$\u{2069} الآن"), "{}", markdown);
+        let mut parsed = parse_markdown(&markdown).unwrap();
+        validate_answers(&mut parsed);
+        assert_eq!(parsed.len(), 3, "{}", markdown);
+        for (original, parsed) in questions.iter().zip(&parsed) {
+            assert_eq!(parsed.stem, original.stem, "{}", markdown);
+            assert_eq!(parsed.answer, original.answer);
+            assert_eq!(parsed.analysis, original.analysis);
+            assert_eq!(parsed.latex, original.latex);
+            assert_eq!(parsed.section, original.section);
+            let contents: Vec<_> = parsed.options.iter().map(|o| o.content.as_str()).collect();
+            let expected: Vec<_> = original.options.iter().map(|o| o.content.as_str()).collect();
+            assert_eq!(contents, expected);
+        }
+        assert!(parsed[0].options[1].is_correct);
+        assert!(matches!(
+            &parsed[1].images[..],
+            [ImageRef::Local { original_path, .. }] if original_path == "ab/c0/abc.png"
+        ));
+        assert_eq!(parsed[1].images[0].alt(), Some("Energy [J] vs. mass"));
+    }
+
+    #[tokio::test]
+    async fn test_archive_bundles_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(FsMediaStore::new(dir.path()).unwrap());
+        let png = crate::media::test_png(2, 2);
+        let stored = store.put(&png).await.unwrap();
+
+        let question = Question {
+            stem: "Which shape is shown?".to_string(),
+            images: vec![
+                ImageRef::Local {
+                    hash: stored.hash.clone(),
+                    original_path: "img/shape.png".to_string(),
+                    stored_path: None,
+                    alt: None,
+                },
+                ImageRef::Local {
+                    hash: "ffff".to_string(),
+                    original_path: "img/missing.png".to_string(),
+                    stored_path: None,
+                    alt: None,
+                },
+            ],
+            ..Question::default()
+        };
+
+        let archive = MarkdownRenderer::new()
+            .with_media_store(store)
+            .archive(&[question.clone(), question])
+            .await
+            .unwrap();
+        assert_eq!(archive.media_files, 1);
+        assert_eq!(archive.warnings.len(), 2);
+        assert!(archive.warnings[0].contains("img/missing.png"));
+
+        let mut zip = ::zip::ZipArchive::new(Cursor::new(archive.data)).unwrap();
+        let mut markdown = String::new();
+        zip.by_name(ARCHIVE_MARKDOWN_FILE).unwrap().read_to_string(&mut markdown).unwrap();
+        assert!(markdown.contains(&format!("![]({})", stored.path)), "{}", markdown);
+        assert!(markdown.contains("![](img/missing.png)"));
+        assert_eq!(zip.by_name(&stored.path).unwrap().size(), png.len() as u64);
+    }
+}
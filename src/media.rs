@@ -1,34 +1,73 @@
 //! Media processing for images and ZIP files
 //!
-//! This module handles extraction and processing of media files.
+//! This module handles extraction and processing of media files, and the
+//! content-addressed [`MediaStore`] that persists extracted images.
 
-use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use crate::error::Md2DbError;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde::Serialize;
 use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::io::AsyncRead;
+
+pub use crate::models::ImageRef;
 
 /// Process an image and generate content-addressed storage
 pub fn process_image(img_data: &[u8]) -> Result<ImageRef> {
-    // Calculate SHA-256 hash
-    let mut hasher = Sha256::new();
-    hasher.update(img_data);
-    let hash = hasher.finalize();
-    let hash_hex = format!("{:x}", hash);
+    let hash_hex = content_hash(img_data);
 
     // Detect file extension from magic bytes
     let ext = detect_extension(img_data)?;
 
     let filename = format!("{}.{}", hash_hex, ext);
 
-    // TODO: Check if file already exists (CAS deduplication)
-    // TODO: Save to CAS storage
-
     Ok(ImageRef::Local {
         hash: hash_hex,
         original_path: filename,
+        stored_path: None,
+        alt: None,
     })
 }
 
+/// Hex SHA-256 of raw media content
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Content-addressed location of a file: `ab/cd/abcd….ext`
+///
+/// Two levels of fan-out keep directories small for large banks.
+pub fn media_path(hash: &str, ext: &str) -> String {
+    match (hash.get(0..2), hash.get(2..4)) {
+        (Some(a), Some(b)) => format!("{}/{}/{}.{}", a, b, hash, ext),
+        _ => format!("{}.{}", hash, ext),
+    }
+}
+
+/// MIME type of a file stored with extension `ext`
+pub fn media_type(ext: &str) -> &'static str {
+    match ext.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "avif" => "image/avif",
+        "heic" | "heif" => "image/heic",
+        "tif" | "tiff" => "image/tiff",
+        "mml" => "application/mathml+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// File extensions treated as images when found in an archive
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "bmp", "svg", "avif", "heic", "heif", "tif", "tiff",
+];
+
 /// Detect file extension from magic bytes
 fn detect_extension(data: &[u8]) -> Result<&'static str> {
     if data.len() < 8 {
@@ -36,16 +75,302 @@ fn detect_extension(data: &[u8]) -> Result<&'static str> {
     }
 
     // Check common image format signatures
-    match &data[0..8] {
-        [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A] => Ok("png"),
-        [0xFF, 0xD8, 0xFF, ..] => Ok("jpg"),
-        [0x47, 0x49, 0x46, 0x38, ..] => Ok("gif"),
-        [0x52, 0x49, 0x46, 0x46, ..] => Ok("webp"), // RIFF
-        [0x42, 0x4D, ..] => Ok("bmp"),
-        _ => Ok("bin"),
+    let ext = match &data[0..8] {
+        [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A] => Some("png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("jpg"),
+        [0x47, 0x49, 0x46, 0x38, ..] => Some("gif"),
+        [0x52, 0x49, 0x46, 0x46, ..] => Some("webp"), // RIFF
+        [0x42, 0x4D, ..] => Some("bmp"),
+        [0x49, 0x49, 0x2A, 0x00, ..] | [0x4D, 0x4D, 0x00, 0x2A, ..] => Some("tiff"),
+        _ => None,
+    };
+
+    Ok(ext
+        .or_else(|| detect_heif_brand(data))
+        .or_else(|| is_svg(data).then_some("svg"))
+        .or_else(|| is_mathml(data).then_some("mml"))
+        .unwrap_or("bin"))
+}
+
+/// Tell AVIF from HEIC by the brands of an ISO-BMFF `ftyp` box
+///
+/// Both start with `....ftyp`; the major and compatible brands say which
+/// codec the image uses. Generic `mif1` files default to HEIC unless they
+/// also list an AVIF brand.
+fn detect_heif_brand(data: &[u8]) -> Option<&'static str> {
+    if data.len() < 12 || &data[4..8] != b"ftyp" {
+        return None;
+    }
+    let box_size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let end = box_size.clamp(12, data.len());
+
+    // Major brand, then compatible brands after the minor version
+    let brands: Vec<&[u8]> = std::iter::once(&data[8..12])
+        .chain(data.get(16..end).unwrap_or_default().chunks_exact(4))
+        .collect();
+
+    if brands.iter().any(|b| matches!(*b, b"avif" | b"avis")) {
+        Some("avif")
+    } else if brands
+        .iter()
+        .any(|b| matches!(*b, b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1"))
+    {
+        Some("heic")
+    } else {
+        None
+    }
+}
+
+/// Sniff an SVG document from its first kilobyte
+///
+/// Accepts a bare `<svg` root as well as one preceded by an XML prolog,
+/// doctype or comments.
+fn is_svg(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]).to_ascii_lowercase();
+    let head = head.trim_start();
+
+    head.starts_with("<svg")
+        || ((head.starts_with("<?xml") || head.starts_with("<!doctype svg") || head.starts_with("<!--"))
+            && head.contains("<svg"))
+}
+
+/// Whether `data` is a MathML document, such as a rendered formula
+fn is_mathml(data: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&data[..data.len().min(256)]).to_ascii_lowercase();
+    let head = head.trim_start();
+    head.starts_with("<math") || (head.starts_with("<?xml") && head.contains("<math"))
+}
+
+/// Format and dimensions read from an image header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ImageInfo {
+    /// Detected file extension (`png`, `jpg`, ...)
+    pub format: &'static str,
+    /// Width in pixels, for formats whose header is decoded
+    pub width: Option<u32>,
+    /// Height in pixels, for formats whose header is decoded
+    pub height: Option<u32>,
+}
+
+/// Check that `data` is a readable image
+///
+/// Decodes the header of PNG, JPEG, GIF, BMP and WebP files to read their
+/// dimensions and checks that the file is not cut short. Other recognized
+/// formats only get a signature check. Empty files, unknown formats, zero
+/// dimensions and truncated files are errors.
+pub fn validate_image(data: &[u8]) -> Result<ImageInfo> {
+    if data.is_empty() {
+        bail!("empty file");
+    }
+
+    let format = detect_extension(data)?;
+    let (width, height) = match format {
+        "png" => png_dimensions(data)?,
+        "jpg" => jpeg_dimensions(data)?,
+        "gif" => gif_dimensions(data)?,
+        "bmp" => bmp_dimensions(data)?,
+        "webp" => webp_dimensions(data)?,
+        "svg" => {
+            let text = String::from_utf8_lossy(data).to_ascii_lowercase();
+            if !text.contains("</svg") && !text.trim_end().ends_with("/>") {
+                bail!("truncated SVG (no closing </svg> tag)");
+            }
+            return Ok(ImageInfo { format, width: None, height: None });
+        }
+        "bin" => bail!("unrecognized image format"),
+        _ => return Ok(ImageInfo { format, width: None, height: None }),
+    };
+
+    if width == 0 || height == 0 {
+        bail!("image has zero dimensions ({}x{})", width, height);
+    }
+    Ok(ImageInfo { format, width: Some(width), height: Some(height) })
+}
+
+/// Read `N` bytes at `at`, failing if the header is cut short
+fn header_bytes<const N: usize>(data: &[u8], at: usize) -> Result<[u8; N]> {
+    match data.get(at..at + N) {
+        Some(bytes) => Ok(bytes.try_into()?),
+        None => bail!("truncated header"),
     }
 }
 
+fn png_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    if header_bytes::<4>(data, 12)? != *b"IHDR" {
+        bail!("missing IHDR chunk");
+    }
+    let width = u32::from_be_bytes(header_bytes(data, 16)?);
+    let height = u32::from_be_bytes(header_bytes(data, 20)?);
+
+    // Encoders may pad after IEND, so look for it near the end
+    let tail = &data[data.len().saturating_sub(1024)..];
+    if !tail.windows(4).any(|w| w == b"IEND") {
+        bail!("truncated PNG (missing IEND chunk)");
+    }
+    Ok((width, height))
+}
+
+fn jpeg_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    let mut dimensions = None;
+    let mut pos = 2;
+    loop {
+        let [marker_start, marker] = header_bytes::<2>(data, pos)?;
+        if marker_start != 0xFF {
+            bail!("corrupt JPEG segment at byte {}", pos);
+        }
+        match marker {
+            // Fill bytes and markers without a payload
+            0xFF => pos += 1,
+            0x01 | 0xD0..=0xD8 => pos += 2,
+            // Start of scan: the header is over
+            0xD9 | 0xDA => break,
+            _ => {
+                let length = u16::from_be_bytes(header_bytes(data, pos + 2)?) as usize;
+                // SOF0-SOF15, except DHT, JPG and DAC which share the range
+                if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                    let height = u16::from_be_bytes(header_bytes(data, pos + 5)?);
+                    let width = u16::from_be_bytes(header_bytes(data, pos + 7)?);
+                    dimensions = Some((width as u32, height as u32));
+                }
+                pos += 2 + length;
+            }
+        }
+    }
+
+    let Some(dimensions) = dimensions else {
+        bail!("missing JPEG frame header");
+    };
+    let end = data.iter().rposition(|&b| b != 0).unwrap_or(0);
+    if end < 1 || data[end - 1..=end] != [0xFF, 0xD9] {
+        bail!("truncated JPEG (missing end-of-image marker)");
+    }
+    Ok(dimensions)
+}
+
+fn gif_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    let width = u16::from_le_bytes(header_bytes(data, 6)?);
+    let height = u16::from_le_bytes(header_bytes(data, 8)?);
+    if data.last() != Some(&0x3B) {
+        bail!("truncated GIF (missing trailer)");
+    }
+    Ok((width as u32, height as u32))
+}
+
+fn bmp_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    let declared = u32::from_le_bytes(header_bytes(data, 2)?) as usize;
+    let width = i32::from_le_bytes(header_bytes(data, 18)?);
+    // Negative height means rows are stored top-down
+    let height = i32::from_le_bytes(header_bytes(data, 22)?);
+    if declared > data.len() {
+        bail!("truncated BMP ({} of {} bytes)", data.len(), declared);
+    }
+    Ok((width.unsigned_abs(), height.unsigned_abs()))
+}
+
+fn webp_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    if header_bytes::<4>(data, 8)? != *b"WEBP" {
+        bail!("RIFF file is not a WebP image");
+    }
+    let declared = u32::from_le_bytes(header_bytes(data, 4)?) as usize + 8;
+    if declared > data.len() {
+        bail!("truncated WebP ({} of {} bytes)", data.len(), declared);
+    }
+
+    let u24 = |at: usize| -> Result<u32> {
+        let [a, b, c] = header_bytes::<3>(data, at)?;
+        Ok(u32::from_le_bytes([a, b, c, 0]))
+    };
+    match &header_bytes::<4>(data, 12)? {
+        b"VP8X" => Ok((u24(24)? + 1, u24(27)? + 1)),
+        b"VP8 " => {
+            let width = u16::from_le_bytes(header_bytes(data, 26)?) & 0x3FFF;
+            let height = u16::from_le_bytes(header_bytes(data, 28)?) & 0x3FFF;
+            Ok((width as u32, height as u32))
+        }
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(header_bytes(data, 21)?);
+            Ok(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        other => bail!("unknown WebP chunk {:?}", String::from_utf8_lossy(other)),
+    }
+}
+
+/// Remove identifying metadata from an image before it is stored
+///
+/// JPEGs lose their EXIF/XMP (APP1), IPTC (APP13) and comment segments;
+/// PNGs lose text, EXIF and timestamp chunks and anything after `IEND`.
+/// Pixel data, colour profiles and other formats are left untouched. Note
+/// that EXIF also carries the camera orientation, so phone photos may display
+/// rotated afterwards.
+pub fn strip_metadata(data: &[u8]) -> Result<Vec<u8>> {
+    match detect_extension(data)? {
+        "jpg" => strip_jpeg_metadata(data),
+        "png" => strip_png_metadata(data),
+        _ => Ok(data.to_vec()),
+    }
+}
+
+fn strip_jpeg_metadata(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[0..2]);
+
+    let mut pos = 2;
+    loop {
+        let [marker_start, marker] = header_bytes::<2>(data, pos)?;
+        if marker_start != 0xFF {
+            bail!("corrupt JPEG segment at byte {}", pos);
+        }
+        match marker {
+            0xFF => pos += 1,
+            0x01 | 0xD0..=0xD8 => {
+                out.extend_from_slice(&data[pos..pos + 2]);
+                pos += 2;
+            }
+            // Entropy-coded data and EOI are copied as they are
+            0xD9 | 0xDA => {
+                out.extend_from_slice(&data[pos..]);
+                break;
+            }
+            _ => {
+                let length = u16::from_be_bytes(header_bytes(data, pos + 2)?) as usize;
+                let end = pos + 2 + length;
+                if end > data.len() {
+                    bail!("truncated header");
+                }
+                if !matches!(marker, 0xE1 | 0xED | 0xFE) {
+                    out.extend_from_slice(&data[pos..end]);
+                }
+                pos = end;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn strip_png_metadata(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[0..8]);
+
+    let mut pos = 8;
+    while pos < data.len() {
+        let length = u32::from_be_bytes(header_bytes(data, pos)?) as usize;
+        let kind: [u8; 4] = header_bytes(data, pos + 4)?;
+        let end = pos + 12 + length;
+        if end > data.len() {
+            bail!("truncated PNG chunk {}", String::from_utf8_lossy(&kind));
+        }
+        if !matches!(&kind, b"tEXt" | b"zTXt" | b"iTXt" | b"eXIf" | b"tIME") {
+            out.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+    Ok(out)
+}
+
 /// Normalize a path for cross-platform compatibility
 pub fn normalize_path(path: &Path) -> PathBuf {
     let components: Vec<_> = path
@@ -63,20 +388,235 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     PathBuf::from(path_str)
 }
 
-/// Reference to an image
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum ImageRef {
-    /// Remote URL reference
-    Remote { url: String },
-    /// Local file reference with content hash
-    Local { hash: String, original_path: String },
+/// Resolve an image reference written in `document` to an archive path
+///
+/// Query strings and fragments are dropped, `%XX` escapes decoded, `./` and
+/// `../` segments applied and backslashes treated as separators. Returns
+/// `None` for references that escape the archive root.
+pub fn resolve_reference(document: &str, reference: &str) -> Option<String> {
+    let reference = reference.split(['?', '#']).next().unwrap_or_default();
+    let reference = percent_decode(reference).replace('\\', "/");
+    let document = document.replace('\\', "/");
+    let mut parts: Vec<&str> = Vec::new();
+    if !reference.starts_with('/') {
+        if let Some((dir, _)) = document.rsplit_once('/') {
+            parts.extend(dir.split('/').filter(|p| !p.is_empty() && *p != "."));
+        }
+    }
+
+    for part in reference.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Decode `%XX` escapes, leaving malformed ones as written
+fn percent_decode(text: &str) -> String {
+    if !text.contains('%') {
+        return text.to_string();
+    }
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 3).and_then(|hex| {
+            std::str::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        });
+        match (bytes[i], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Where a stored file ended up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StoredMedia {
+    /// Hex SHA-256 of the content
+    pub hash: String,
+    /// Path relative to the store root
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// False if identical content was already stored
+    pub newly_stored: bool,
+}
+
+/// Stored content opened for reading
+pub struct MediaFile {
+    /// The content
+    pub reader: Pin<Box<dyn AsyncRead + Send>>,
+    /// Size in bytes
+    pub size: u64,
+    /// Extension the content was stored with, as chosen by [`media_path`]
+    pub extension: String,
+}
+
+/// Persistent storage for media referenced by questions
+///
+/// Content is addressed by its SHA-256 hash, so storing the same image twice
+/// keeps a single copy.
+#[async_trait]
+pub trait MediaStore: Send + Sync {
+    /// Store `data`, returning where it lives
+    async fn put(&self, data: &[u8]) -> Result<StoredMedia, Md2DbError>;
+
+    /// Read stored content by its hash
+    async fn get(&self, hash: &str) -> Result<Option<Vec<u8>>, Md2DbError>;
+
+    /// Open stored content by its hash without reading it all
+    ///
+    /// The default reads the whole file with [`MediaStore::get`]; stores
+    /// that can stream should override it.
+    async fn open(&self, hash: &str) -> Result<Option<MediaFile>, Md2DbError> {
+        Ok(self.get(hash).await?.map(|data| MediaFile {
+            size: data.len() as u64,
+            extension: detect_extension(&data).unwrap_or("bin").to_string(),
+            reader: Box::pin(std::io::Cursor::new(data)),
+        }))
+    }
+}
+
+/// Media store on the local filesystem
+///
+/// Files are laid out by [`media_path`] under the root directory. Each file is
+/// written to a temporary name and renamed into place, so readers never see
+/// a partially written image and a crash leaves no corrupt entries behind.
+pub struct FsMediaStore {
+    root: PathBuf,
+}
+
+impl FsMediaStore {
+    /// Open (creating if needed) a store rooted at `root`
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, Md2DbError> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join(".tmp"))
+            .map_err(|e| Md2DbError::media(format!("Failed to create media store {:?}", root), e))?;
+        Ok(Self { root })
+    }
+
+    /// Root directory of the store
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Find the stored file for `hash`, whatever its extension
+    async fn locate(&self, hash: &str) -> Result<Option<PathBuf>> {
+        if hash.len() < 4 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(None);
+        }
+        let dir = self.root.join(&hash[0..2]).join(&hash[2..4]);
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            if name.to_string_lossy().split('.').next() == Some(hash) {
+                return Ok(Some(entry.path()));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn write(&self, data: &[u8]) -> Result<StoredMedia> {
+        let hash = content_hash(data);
+        let path = media_path(&hash, detect_extension(data)?);
+        let target = self.root.join(&path);
+        let stored = |newly_stored| StoredMedia {
+            hash: hash.clone(),
+            path: path.clone(),
+            size: data.len() as u64,
+            newly_stored,
+        };
+
+        if tokio::fs::try_exists(&target).await? {
+            return Ok(stored(false));
+        }
+
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let temp = self.root.join(".tmp").join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::write(&temp, data).await?;
+        if let Err(e) = tokio::fs::rename(&temp, &target).await {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e.into());
+        }
+
+        Ok(stored(true))
+    }
+
+    async fn read(&self, hash: &str) -> Result<Option<Vec<u8>>> {
+        match self.locate(hash).await? {
+            Some(path) => Ok(Some(tokio::fs::read(path).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn open_file(&self, hash: &str) -> Result<Option<MediaFile>> {
+        let Some(path) = self.locate(hash).await? else {
+            return Ok(None);
+        };
+        let file = tokio::fs::File::open(&path).await?;
+        Ok(Some(MediaFile {
+            size: file.metadata().await?.len(),
+            extension: path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default(),
+            reader: Box::pin(file),
+        }))
+    }
+}
+
+#[async_trait]
+impl MediaStore for FsMediaStore {
+    async fn put(&self, data: &[u8]) -> Result<StoredMedia, Md2DbError> {
+        self.write(data).await.map_err(|e| Md2DbError::media("Failed to store media", e))
+    }
+
+    async fn get(&self, hash: &str) -> Result<Option<Vec<u8>>, Md2DbError> {
+        self.read(hash).await.map_err(|e| Md2DbError::media(format!("Failed to read media {}", hash), e))
+    }
+
+    async fn open(&self, hash: &str) -> Result<Option<MediaFile>, Md2DbError> {
+        self.open_file(hash).await.map_err(|e| Md2DbError::media(format!("Failed to open media {}", hash), e))
+    }
+}
+
+/// A minimal valid PNG header of the given size, for tests
+#[cfg(test)]
+pub(crate) fn test_png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = vec![0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+    png.extend_from_slice(&13u32.to_be_bytes());
+    png.extend_from_slice(b"IHDR");
+    png.extend_from_slice(&width.to_be_bytes());
+    png.extend_from_slice(&height.to_be_bytes());
+    png.extend_from_slice(&[8, 6, 0, 0, 0, 0, 0, 0, 0]);
+    png.extend_from_slice(&[0, 0, 0, 0]);
+    png.extend_from_slice(b"IEND");
+    png.extend_from_slice(&[0xAE, 0x42, 0x60, 0x82]);
+    png
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const PNG: [u8; 12] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 1, 2, 3, 4];
+
     #[test]
     fn test_detect_png() {
         let png_header = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
@@ -89,6 +629,105 @@ mod tests {
         assert_eq!(detect_extension(&jpg_header).unwrap(), "jpg");
     }
 
+    #[test]
+    fn test_detect_tiff_heif_and_svg() {
+        assert_eq!(detect_extension(b"II*\0\x08\0\0\0").unwrap(), "tiff");
+        assert_eq!(detect_extension(b"MM\0*\0\0\0\x08").unwrap(), "tiff");
+
+        let avif = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf";
+        assert_eq!(detect_extension(avif).unwrap(), "avif");
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        assert_eq!(detect_extension(heic).unwrap(), "heic");
+        // Generic HEIF container listing an AVIF brand
+        let mif1 = b"\0\0\0\x18ftypmif1\0\0\0\0mif1avif";
+        assert_eq!(detect_extension(mif1).unwrap(), "avif");
+        // An MP4 video is not an image
+        let mp4 = b"\0\0\0\x18ftypisom\0\0\0\0isomiso2";
+        assert_eq!(detect_extension(mp4).unwrap(), "bin");
+
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>";
+        assert_eq!(detect_extension(svg).unwrap(), "svg");
+        let prolog = b"\xEF\xBB\xBF<?xml version=\"1.0\"?>\n<!-- diagram -->\n<svg width=\"10\"/>";
+        assert_eq!(detect_extension(prolog).unwrap(), "svg");
+        let other_xml = b"<?xml version=\"1.0\"?><rss></rss>";
+        assert_eq!(detect_extension(other_xml).unwrap(), "bin");
+        let mathml = b"<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mi>x</mi></math>";
+        assert_eq!(detect_extension(mathml).unwrap(), "mml");
+    }
+
+    #[test]
+    fn test_validate_image_reads_dimensions() {
+        let info = validate_image(&test_png(640, 480)).unwrap();
+        assert_eq!(info, ImageInfo { format: "png", width: Some(640), height: Some(480) });
+
+        let jpeg = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // APP0
+            0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x20, 0x00, 0x40, 0x01, 0x01, 0x11, 0x00, // SOF0 64x32
+            0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, // SOS + scan data
+            0xFF, 0xD9, // EOI
+        ];
+        let info = validate_image(&jpeg).unwrap();
+        assert_eq!((info.width, info.height), (Some(64), Some(32)));
+
+        let gif = b"GIF89a\x0A\x00\x05\x00\x00\x00\x00\x3B";
+        assert_eq!(validate_image(gif).unwrap().width, Some(10));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0A\0\0\0\0\0\0\0\x63\0\0\x31\0\0".to_vec();
+        let size = (webp.len() - 8) as u32;
+        webp[4..8].copy_from_slice(&size.to_le_bytes());
+        let info = validate_image(&webp).unwrap();
+        assert_eq!((info.width, info.height), (Some(100), Some(50)));
+
+        assert_eq!(validate_image(b"<svg viewBox=\"0 0 1 1\"></svg>").unwrap().width, None);
+    }
+
+    #[test]
+    fn test_validate_image_rejects_corruption() {
+        let err = |data: &[u8]| validate_image(data).unwrap_err().to_string();
+
+        assert_eq!(err(b""), "empty file");
+        assert_eq!(err(b"not an image at all"), "unrecognized image format");
+
+        let png = test_png(10, 10);
+        assert_eq!(err(&png[..20]), "truncated header");
+        assert_eq!(err(&png[..png.len() - 12]), "truncated PNG (missing IEND chunk)");
+        assert!(err(&test_png(0, 10)).contains("zero dimensions"));
+
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x20, 0x00, 0x40, 0x01];
+        assert_eq!(err(&jpeg), "truncated header");
+
+        assert_eq!(err(b"GIF89a\x0A\x00\x05\x00\x00\x00"), "truncated GIF (missing trailer)");
+        assert_eq!(err(b"<svg viewBox=\"0 0 1 1\"><rect"), "truncated SVG (no closing </svg> tag)");
+    }
+
+    #[test]
+    fn test_strip_jpeg_metadata() {
+        let app0 = [0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46];
+        let exif = [0xFF, 0xE1, 0x00, 0x08, b'E', b'x', b'i', b'f', b'G', b'P'];
+        let comment = [0xFF, 0xFE, 0x00, 0x04, b'h', b'i'];
+        let sof = [0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x20, 0x00, 0x40, 0x01, 0x01, 0x11, 0x00];
+        let scan = [0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xE1, 0x12, 0xFF, 0xD9];
+        let jpeg = [&[0xFF, 0xD8][..], &app0, &exif, &comment, &sof, &scan].concat();
+
+        let stripped = strip_metadata(&jpeg).unwrap();
+        assert_eq!(stripped, [&[0xFF, 0xD8][..], &app0, &sof, &scan].concat());
+        assert_eq!(validate_image(&stripped).unwrap().width, Some(64));
+    }
+
+    #[test]
+    fn test_strip_png_metadata() {
+        let png = test_png(2, 2);
+        let mut text = 12u32.to_be_bytes().to_vec();
+        text.extend_from_slice(b"tEXtAuthor\0Alice");
+        text.extend_from_slice(&[0; 4]);
+        let with_text = [&png[..33], &text, &png[33..], b"trailing"].concat();
+
+        assert_eq!(strip_metadata(&with_text).unwrap(), png);
+        assert_eq!(strip_metadata(b"GIF89a\x01\x00\x01\x00;").unwrap(), b"GIF89a\x01\x00\x01\x00;");
+        assert!(strip_metadata(&with_text[..40]).is_err());
+    }
+
     #[test]
     fn test_normalize_path() {
         use std::path::Path;
@@ -96,4 +735,45 @@ mod tests {
         let normalized = normalize_path(path);
         assert_eq!(normalized.to_str().unwrap(), "images/photo.png");
     }
+
+    #[test]
+    fn test_resolve_reference() {
+        assert_eq!(resolve_reference("exam.md", "images/a.png").as_deref(), Some("images/a.png"));
+        assert_eq!(resolve_reference("ch1/exam.md", "./img/a.png").as_deref(), Some("ch1/img/a.png"));
+        assert_eq!(resolve_reference("ch1/exam.md", "../shared/a.png").as_deref(), Some("shared/a.png"));
+        assert_eq!(resolve_reference("ch1/exam.md", "/shared/a.png").as_deref(), Some("shared/a.png"));
+        assert_eq!(resolve_reference("ch1\\exam.md", "img\\a.png").as_deref(), Some("ch1/img/a.png"));
+        assert_eq!(resolve_reference("exam.md", "../outside.png"), None);
+        assert_eq!(resolve_reference("exam.md", "img/my%20plot.png").as_deref(), Some("img/my plot.png"));
+        assert_eq!(resolve_reference("exam.md", "img/%E5%9B%BE.png").as_deref(), Some("img/图.png"));
+        assert_eq!(resolve_reference("exam.md", "img/a.png?v=2#zoom").as_deref(), Some("img/a.png"));
+        assert_eq!(resolve_reference("exam.md", "img/100%.png").as_deref(), Some("img/100%.png"));
+    }
+
+    #[tokio::test]
+    async fn test_fs_media_store_deduplicates() {
+        let root = tempfile::tempdir().unwrap();
+        let store = FsMediaStore::new(root.path()).unwrap();
+
+        let first = store.put(&PNG).await.unwrap();
+        assert!(first.newly_stored);
+        assert_eq!(first.path, media_path(&first.hash, "png"));
+        assert!(first.path.starts_with(&format!("{}/{}/", &first.hash[0..2], &first.hash[2..4])));
+        assert_eq!(std::fs::read(root.path().join(&first.path)).unwrap(), PNG);
+
+        let second = store.put(&PNG).await.unwrap();
+        assert!(!second.newly_stored);
+        assert_eq!(second.path, first.path);
+
+        assert_eq!(store.get(&first.hash).await.unwrap(), Some(PNG.to_vec()));
+        assert_eq!(store.get(&content_hash(b"missing")).await.unwrap(), None);
+        assert_eq!(store.get("../etc").await.unwrap(), None);
+
+        let file = store.open(&first.hash).await.unwrap().unwrap();
+        assert_eq!((file.size, media_type(&file.extension)), (PNG.len() as u64, "image/png"));
+        assert!(store.open(&content_hash(b"missing")).await.unwrap().is_none());
+
+        // Nothing is left behind in the staging directory
+        assert_eq!(std::fs::read_dir(root.path().join(".tmp")).unwrap().count(), 0);
+    }
 }
@@ -3,6 +3,7 @@
 //! This module defines the fundamental data structures used throughout
 //! the application, including questions, options, and media references.
 
+use crate::error::Md2DbError;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -18,11 +19,64 @@ pub enum QuestionType {
     /// True/False question
     TrueFalse,
     /// Fill in the blank question
+    #[serde(alias = "fill_in_blank")]
     FillInTheBlank,
     /// Subjective/essay question
     Subjective,
 }
 
+impl QuestionType {
+    /// Every question type
+    pub const ALL: [QuestionType; 5] = [
+        QuestionType::Choice,
+        QuestionType::MultipleChoice,
+        QuestionType::TrueFalse,
+        QuestionType::FillInTheBlank,
+        QuestionType::Subjective,
+    ];
+}
+
+impl std::str::FromStr for QuestionType {
+    type Err = anyhow::Error;
+
+    /// Parse a question type from its snake_case name or a common alias
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "choice" | "single_choice" | "single" => Ok(QuestionType::Choice),
+            "multiple_choice" | "multiplechoice" | "multi" => Ok(QuestionType::MultipleChoice),
+            "true_false" | "truefalse" | "tf" => Ok(QuestionType::TrueFalse),
+            "fill_in_the_blank" | "fill_in_blank" | "fillintheblank" | "fill" | "blank" => Ok(QuestionType::FillInTheBlank),
+            "subjective" | "essay" => Ok(QuestionType::Subjective),
+            other => Err(anyhow::anyhow!("Unknown question type: {}", other)),
+        }
+    }
+}
+
+/// What decided a question's type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeSource {
+    /// The title of the section the question appeared under ("三、判断题")
+    Section,
+    /// The classifier, from the question's own stem and options
+    Classifier,
+    /// A default type given with the import (request or manifest)
+    Default,
+}
+
+/// Dominant writing direction of a question's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextDirection {
+    /// Left-to-right (Latin, CJK, ...)
+    #[default]
+    Ltr,
+    /// Right-to-left (Arabic, Hebrew, ...)
+    Rtl,
+    /// RTL prose mixed with LTR runs
+    Mixed,
+}
+
 /// An option for a multiple choice or true/false question
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuestionOption {
@@ -39,46 +93,140 @@ pub struct QuestionOption {
 #[serde(untagged)]
 pub enum ImageRef {
     /// Remote URL reference
-    Remote { url: String },
+    Remote {
+        url: String,
+        /// Alt text of the image link, if it had any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alt: Option<String>,
+    },
     /// Local file reference with content hash
     Local {
-        /// SHA-256 hash of the image content
+        /// SHA-256 hash of the image content (empty until the file is found)
         hash: String,
         /// Original file path
         original_path: String,
+        /// Location in the media store, relative to its root, once persisted
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stored_path: Option<String>,
+        /// Alt text of the image link, if it had any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alt: Option<String>,
     },
 }
 
+impl ImageRef {
+    /// Alt text describing the image
+    pub fn alt(&self) -> Option<&str> {
+        match self {
+            ImageRef::Remote { alt, .. } | ImageRef::Local { alt, .. } => alt.as_deref(),
+        }
+    }
+}
+
+/// Where a question came from, recorded at import time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Source file (path inside the archive for ZIP imports)
+    pub source_file: Option<String>,
+    /// Compressed size of the source entry in its archive
+    pub compressed_size: Option<u64>,
+    /// Uncompressed size of the source entry
+    pub uncompressed_size: Option<u64>,
+    /// CRC-32 of the source entry as recorded in the archive
+    pub crc32: Option<u32>,
+    /// Modification time of the source entry as recorded in the archive
+    pub modified_at: Option<DateTime<Utc>>,
+    /// Uploaded archive the source file came from (multi-archive imports)
+    pub archive: Option<String>,
+    /// Position of the question within its import job, starting at 1
+    pub sequence: Option<u64>,
+}
+
+/// Version of the [`Question`] JSON layout written by this release
+///
+/// - 0: documents written by the Python implementation (`content`,
+///   `question_type`, options and images as plain strings)
+/// - 1: Rust releases before the field existed
+/// - 2: adds `schema_version`
+///
+/// Bump it when a change needs more than new `#[serde(default)]` fields,
+/// and teach [`migrate_json`] to upgrade the previous layout.
+pub const SCHEMA_VERSION: u32 = 2;
+
 /// A complete question with all its components
+///
+/// Older layouts still deserialize: Python-era key names are accepted as
+/// aliases, options and images may be plain strings, and a missing `id` or
+/// `created_at` is filled in. [`migrate_json`] handles the rest.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Question {
+    /// Layout version the question was written with (see [`SCHEMA_VERSION`])
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
     /// Unique identifier
+    #[serde(default = "Uuid::new_v4")]
     pub id: Uuid,
     /// Type of question
-    #[serde(rename = "type")]
+    #[serde(rename = "type", alias = "question_type")]
     pub qtype: QuestionType,
     /// The question stem/prompt
+    #[serde(alias = "content")]
     pub stem: String,
     /// Options for choice questions
-    #[serde(default)]
+    #[serde(default, deserialize_with = "legacy::options")]
     pub options: Vec<QuestionOption>,
     /// The answer (if applicable)
+    #[serde(default)]
     pub answer: Option<String>,
     /// Detailed explanation/analysis
+    #[serde(default, alias = "explanation")]
     pub analysis: Option<String>,
     /// Images referenced in the question
-    #[serde(default)]
+    #[serde(default, deserialize_with = "legacy::images")]
     pub images: Vec<ImageRef>,
     /// LaTeX formulas extracted from the question
-    #[serde(default)]
+    #[serde(default, alias = "latex_formulas", deserialize_with = "legacy::strings")]
     pub latex: Vec<String>,
+    /// Subject/topic tags (e.g. 数学, 物理, 编程)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Dominant text direction of the stem and options
+    #[serde(default)]
+    pub text_direction: TextDirection,
+    /// Source file information, when known
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    /// Question bank the question belongs to, if any
+    #[serde(default)]
+    pub bank_id: Option<Uuid>,
+    /// Position within its import, in archive entry order then document
+    /// order, starting at 1 (unset when order is not preserved)
+    #[serde(default)]
+    pub sequence: Option<u64>,
+    /// Title of the document section the question appeared under, such as
+    /// "一、单选题"
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Number the question had in its document, as written ("12", "１２")
+    #[serde(default)]
+    pub number: Option<String>,
+    /// What decided `qtype`; unset when the type was given with the
+    /// question or left as parsed
+    #[serde(default)]
+    pub type_source: Option<TypeSource>,
     /// When this question was created/processed
+    #[serde(default = "Utc::now")]
     pub created_at: DateTime<Utc>,
 }
 
+fn current_schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
 impl Default for Question {
     fn default() -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             id: Uuid::new_v4(),
             qtype: QuestionType::Subjective,
             stem: String::new(),
@@ -87,20 +235,537 @@ impl Default for Question {
             analysis: None,
             images: Vec::new(),
             latex: Vec::new(),
+            tags: Vec::new(),
+            text_direction: TextDirection::Ltr,
+            provenance: None,
+            bank_id: None,
+            sequence: None,
+            section: None,
+            number: None,
+            type_source: None,
             created_at: Utc::now(),
         }
     }
 }
 
-/// Result of a classification operation with confidence
+impl Question {
+    /// Start building a question of type `qtype` (see [`QuestionBuilder`])
+    pub fn builder(qtype: QuestionType, stem: impl Into<String>) -> QuestionBuilder {
+        QuestionBuilder::new(qtype, stem)
+    }
+}
+
+/// Builds a [`Question`], checking at [`build`](QuestionBuilder::build) that
+/// it makes sense for its type
+///
+/// ```
+/// use md2db::{Question, QuestionType};
+///
+/// let question = Question::builder(QuestionType::Choice, "1 + 1 = ?")
+///     .with_option("1", false)
+///     .with_option("2", true)
+///     .with_tag("数学")
+///     .build()
+///     .unwrap();
+/// assert_eq!(question.answer.as_deref(), Some("B"));
+///
+/// // A subjective question cannot have options
+/// let invalid = Question::builder(QuestionType::Subjective, "Discuss.")
+///     .with_option("A thought", false)
+///     .build();
+/// assert!(invalid.is_err());
+/// ```
 #[derive(Debug, Clone)]
+pub struct QuestionBuilder {
+    question: Question,
+}
+
+impl QuestionBuilder {
+    /// Start a question of type `qtype` with the given stem
+    pub fn new(qtype: QuestionType, stem: impl Into<String>) -> Self {
+        Self {
+            question: Question {
+                qtype,
+                stem: stem.into(),
+                ..Question::default()
+            },
+        }
+    }
+
+    /// Use `id` instead of a random ID
+    pub fn with_id(mut self, id: Uuid) -> Self {
+        self.question.id = id;
+        self
+    }
+
+    /// Add an option after the ones already added
+    pub fn with_option(mut self, content: impl Into<String>, is_correct: bool) -> Self {
+        let sort_order = self.question.options.len() as i32;
+        self.question.options.push(QuestionOption {
+            content: content.into(),
+            sort_order,
+            is_correct,
+        });
+        self
+    }
+
+    /// Set the answer
+    ///
+    /// Choice questions get the letters of their correct options when no
+    /// answer is set.
+    pub fn with_answer(mut self, answer: impl Into<String>) -> Self {
+        self.question.answer = Some(answer.into());
+        self
+    }
+
+    /// Set the explanation
+    pub fn with_analysis(mut self, analysis: impl Into<String>) -> Self {
+        self.question.analysis = Some(analysis.into());
+        self
+    }
+
+    /// Add a tag
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.question.tags.push(tag.into());
+        self
+    }
+
+    /// Add several tags
+    pub fn with_tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.question.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add an image reference
+    pub fn with_image(mut self, image: ImageRef) -> Self {
+        self.question.images.push(image);
+        self
+    }
+
+    /// Add a LaTeX formula
+    pub fn with_latex(mut self, formula: impl Into<String>) -> Self {
+        self.question.latex.push(formula.into());
+        self
+    }
+
+    /// Put the question in a bank
+    pub fn with_bank(mut self, bank_id: Uuid) -> Self {
+        self.question.bank_id = Some(bank_id);
+        self
+    }
+
+    /// Record where the question came from
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.question.provenance = Some(provenance);
+        self
+    }
+
+    /// Check the question against its type and return it
+    ///
+    /// - every question needs a stem, and options need content
+    /// - single choice: at least two options, exactly one correct
+    /// - multiple choice: at least two options, at least one correct
+    /// - true/false: no options and an answer, or two options with one correct
+    /// - fill-in-the-blank: no options and an answer
+    /// - subjective: no options
+    ///
+    /// The text direction is detected from the stem and options.
+    pub fn build(self) -> Result<Question, Md2DbError> {
+        let mut question = self.question;
+        let invalid = |message: String| Md2DbError::validation("question", message);
+
+        if question.stem.trim().is_empty() {
+            return Err(invalid("the stem is empty".to_string()));
+        }
+        if let Some(idx) = question.options.iter().position(|o| o.content.trim().is_empty()) {
+            return Err(invalid(format!("option {} is empty", idx + 1)));
+        }
+
+        let options = question.options.len();
+        let correct = question.options.iter().filter(|o| o.is_correct).count();
+        let has_answer = question.answer.as_deref().is_some_and(|a| !a.trim().is_empty());
+        let name = type_label(question.qtype);
+        match question.qtype {
+            QuestionType::Choice | QuestionType::MultipleChoice if options < 2 => {
+                return Err(invalid(format!("a {} question needs at least 2 options, got {}", name, options)));
+            }
+            QuestionType::Choice if correct != 1 => {
+                return Err(invalid(format!("a {} question needs exactly 1 correct option, got {}", name, correct)));
+            }
+            QuestionType::MultipleChoice if correct == 0 => {
+                return Err(invalid(format!("a {} question needs a correct option", name)));
+            }
+            QuestionType::TrueFalse if options == 0 && !has_answer => {
+                return Err(invalid(format!("a {} question needs an answer or two options", name)));
+            }
+            QuestionType::TrueFalse if options != 0 && (options != 2 || correct != 1) => {
+                return Err(invalid(format!(
+                    "a {} question needs 2 options with 1 correct, got {} with {} correct",
+                    name, options, correct
+                )));
+            }
+            QuestionType::FillInTheBlank if !has_answer => {
+                return Err(invalid(format!("a {} question needs an answer", name)));
+            }
+            QuestionType::FillInTheBlank | QuestionType::Subjective if options > 0 => {
+                return Err(invalid(format!("a {} question cannot have options", name)));
+            }
+            _ => {}
+        }
+
+        if !has_answer && correct > 0 {
+            let letters: String = question
+                .options
+                .iter()
+                .enumerate()
+                .filter(|(_, o)| o.is_correct)
+                .map(|(idx, _)| crate::render::option_letter(idx))
+                .collect();
+            question.answer = Some(letters);
+        }
+        question.text_direction = crate::bidi::detect_question_direction(&question);
+        Ok(question)
+    }
+}
+
+/// Name of a question type in messages
+fn type_label(qtype: QuestionType) -> &'static str {
+    match qtype {
+        QuestionType::Choice => "single choice",
+        QuestionType::MultipleChoice => "multiple choice",
+        QuestionType::TrueFalse => "true/false",
+        QuestionType::FillInTheBlank => "fill-in-the-blank",
+        QuestionType::Subjective => "subjective",
+    }
+}
+
+/// Load a question from JSON written by this or any earlier release
+///
+/// The layout is taken from `schema_version`, or recognized as a Python-era
+/// document by its `content`/`question_type` keys. Python documents are
+/// upgraded: the Mongo `_id` is dropped, naive timestamps are read as UTC,
+/// an answer list is joined, and the answer is matched against the options
+/// to mark the correct ones (a single correct option makes the question
+/// single choice). The returned question has the current
+/// [`SCHEMA_VERSION`]. Payloads from a newer release are refused.
+///
+/// ```
+/// use md2db::models::{migrate_json, QuestionType, SCHEMA_VERSION};
+///
+/// let legacy = serde_json::json!({
+///     "content": "1 + 1 = ?",
+///     "question_type": "multiple_choice",
+///     "options": ["A. 1", "B. 2"],
+///     "answer": "B",
+///     "explanation": null,
+/// });
+/// let question = migrate_json(legacy).unwrap();
+/// assert_eq!(question.qtype, QuestionType::Choice);
+/// assert_eq!(question.options[1].content, "2");
+/// assert!(question.options[1].is_correct);
+/// assert_eq!(question.schema_version, SCHEMA_VERSION);
+/// ```
+pub fn migrate_json(mut value: serde_json::Value) -> Result<Question, Md2DbError> {
+    let invalid = |message: String| Md2DbError::validation("question JSON", message);
+    if !value.is_object() {
+        return Err(invalid(format!("expected an object, got {}", value_kind(&value))));
+    }
+    let object = value.as_object_mut().expect("checked above");
+
+    let version = match object.get("schema_version") {
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| invalid(format!("schema_version {} is not a version number", v)))?,
+        None if !object.contains_key("stem") && (object.contains_key("content") || object.contains_key("question_type")) => 0,
+        None => 1,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(invalid(format!(
+            "schema_version {} is newer than this release supports ({})",
+            version, SCHEMA_VERSION
+        )));
+    }
+
+    if version == 0 {
+        legacy::upgrade_python(object);
+    }
+    // 1 -> 2 only added `schema_version`; missing fields take their defaults
+    object.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+
+    let mut question: Question = serde_json::from_value(value).map_err(|e| invalid(e.to_string()))?;
+    if version == 0 {
+        crate::answer::validate_answer(&mut question);
+        if question.qtype == QuestionType::MultipleChoice && question.options.iter().filter(|o| o.is_correct).count() == 1 {
+            question.qtype = QuestionType::Choice;
+        }
+        question.text_direction = crate::bidi::detect_question_direction(&question);
+    }
+    Ok(question)
+}
+
+/// JSON type name for messages
+fn value_kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+/// Shapes written by older releases
+mod legacy {
+    use super::{ImageRef, QuestionOption};
+    use chrono::NaiveDateTime;
+    use serde::{Deserialize, Deserializer};
+    use serde_json::{Map, Value};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OptionShape {
+        Text(String),
+        Option(QuestionOption),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ImageShape {
+        Url(String),
+        Image(ImageRef),
+    }
+
+    /// Options as objects, or as labelled strings such as `"A. O(n)"`
+    pub fn options<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<QuestionOption>, D::Error> {
+        let shapes: Option<Vec<OptionShape>> = Option::deserialize(deserializer)?;
+        Ok(shapes
+            .unwrap_or_default()
+            .into_iter()
+            .zip(0..)
+            .map(|(shape, sort_order)| match shape {
+                OptionShape::Option(option) => option,
+                OptionShape::Text(text) => QuestionOption {
+                    content: strip_label(&text).to_string(),
+                    sort_order,
+                    is_correct: false,
+                },
+            })
+            .collect())
+    }
+
+    /// Image references, or bare URLs and paths
+    pub fn images<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<ImageRef>, D::Error> {
+        let shapes: Option<Vec<ImageShape>> = Option::deserialize(deserializer)?;
+        Ok(shapes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|shape| match shape {
+                ImageShape::Image(image) => image,
+                ImageShape::Url(url) => crate::parser::image_ref(&url),
+            })
+            .collect())
+    }
+
+    /// A list of strings that may be `null`
+    pub fn strings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+        Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+    }
+
+    /// Rewrite a Python-era document into the version 1 layout
+    pub fn upgrade_python(object: &mut Map<String, Value>) {
+        object.remove("_id");
+        if let Some(Value::Array(parts)) = object.get("answer") {
+            let joined: String = parts.iter().filter_map(Value::as_str).collect();
+            object.insert("answer".to_string(), Value::String(joined));
+        }
+        if let Some(created_at) = object.remove("created_at") {
+            if let Some(created_at) = timestamp(created_at) {
+                object.insert("created_at".to_string(), created_at);
+            }
+        }
+    }
+
+    /// A timestamp as written by `datetime.utcnow()` or Mongo extended JSON,
+    /// with an explicit UTC offset
+    fn timestamp(value: Value) -> Option<Value> {
+        let value = match value {
+            Value::Object(mut date) => date.remove("$date")?,
+            value => value,
+        };
+        match value {
+            Value::String(text) if text.parse::<NaiveDateTime>().is_ok() => Some(Value::String(format!("{}Z", text))),
+            Value::String(text) => Some(Value::String(text)),
+            Value::Number(millis) => {
+                let created_at = chrono::DateTime::from_timestamp_millis(millis.as_i64()?)?;
+                Some(Value::String(created_at.to_rfc3339()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Option text without a leading `A.`-style label
+    fn strip_label(text: &str) -> &str {
+        let trimmed = text.trim();
+        let mut chars = trimmed.chars();
+        match (chars.next(), chars.next()) {
+            (Some(letter), Some('.' | '．' | '、' | ')')) if letter.is_ascii_uppercase() => chars.as_str().trim_start(),
+            _ => trimmed,
+        }
+    }
+}
+
+/// Partial update of a stored question; fields left out are kept
+///
+/// An empty `answer` or `analysis` clears it. New `options` replace the old
+/// ones in order, with no option marked correct until the answer is matched
+/// against them again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuestionPatch {
+    #[serde(default)]
+    pub stem: Option<String>,
+    #[serde(default, rename = "type")]
+    pub qtype: Option<QuestionType>,
+    /// Option contents, in display order
+    #[serde(default)]
+    pub options: Option<Vec<String>>,
+    /// Correct answer, such as option letters (`"B"`, `"AC"`)
+    #[serde(default)]
+    pub answer: Option<String>,
+    #[serde(default)]
+    pub analysis: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+}
+
+impl QuestionPatch {
+    /// Whether the patch changes the options or the answer
+    pub fn changes_answer(&self) -> bool {
+        self.options.is_some() || self.answer.is_some()
+    }
+
+    /// Write the patched fields into `question`
+    pub fn apply(self, question: &mut Question) {
+        let text = |value: String| Some(value).filter(|v| !v.trim().is_empty());
+        if let Some(stem) = self.stem {
+            question.stem = stem;
+        }
+        if let Some(qtype) = self.qtype {
+            question.qtype = qtype;
+        }
+        if let Some(options) = self.options {
+            question.options = options
+                .into_iter()
+                .zip(0..)
+                .map(|(content, sort_order)| QuestionOption {
+                    content,
+                    sort_order,
+                    is_correct: false,
+                })
+                .collect();
+        }
+        if let Some(answer) = self.answer {
+            question.answer = text(answer);
+        }
+        if let Some(analysis) = self.analysis {
+            question.analysis = text(analysis);
+        }
+        if let Some(tags) = self.tags {
+            question.tags = tags;
+        }
+    }
+}
+
+/// A named collection of questions, such as one exam or textbook chapter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionBank {
+    /// Unique identifier
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    /// Display name
+    pub name: String,
+    /// Free-form description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Labels for finding the bank (e.g. subject or grade)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When the bank was created
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl QuestionBank {
+    /// Create a bank with the given name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            description: None,
+            tags: Vec::new(),
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Set the description
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the tags
+    pub fn with_tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// Content hash of a source file as last imported into a bank
+///
+/// Incremental imports compare against it to skip files that have not
+/// changed since.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedSource {
+    /// Bank the file was imported into
+    pub bank_id: Uuid,
+    /// Source file, as recorded in question provenance
+    pub source: String,
+    /// Hash of the questions parsed from the file
+    pub content_hash: String,
+    /// When the file was last imported
+    pub imported_at: DateTime<Utc>,
+}
+
+/// A prior version of a question, kept when the question is updated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionRevision {
+    /// Question the revision belongs to
+    pub question_id: Uuid,
+    /// Revision number, starting at 1 for the first replaced version
+    pub revision: i64,
+    /// Who made the change that replaced this version, when known
+    pub actor: Option<String>,
+    /// When this version was replaced
+    pub created_at: DateTime<Utc>,
+    /// The question as it was before the change
+    pub question: Question,
+}
+
+/// Result of a classification operation with confidence
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClassificationResult {
     /// The determined question type
+    #[serde(rename = "type")]
     pub qtype: QuestionType,
     /// Confidence score (0.0 to 1.0)
     pub confidence: f32,
     /// Whether this result needs manual review
     pub needs_review: bool,
+    /// What decided the type, once the result is applied to a question
+    #[serde(default)]
+    pub source: Option<TypeSource>,
 }
 
 impl ClassificationResult {
@@ -111,9 +776,16 @@ impl ClassificationResult {
             qtype,
             confidence,
             needs_review,
+            source: None,
         }
     }
 
+    /// Record what decided the type
+    pub fn with_source(mut self, source: TypeSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+
     /// Create a high-confidence result
     pub fn certain(qtype: QuestionType) -> Self {
         Self::new(qtype, 1.0)
@@ -124,3 +796,130 @@ impl ClassificationResult {
         Self::new(qtype, confidence)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_enforces_type_invariants() {
+        let choice = Question::builder(QuestionType::Choice, "Which is prime?")
+            .with_option("4", false)
+            .with_option("7", true)
+            .with_analysis("7 has no divisors but 1 and itself")
+            .with_tags(["数学", "质数"])
+            .build()
+            .unwrap();
+        assert_eq!(choice.options[1].sort_order, 1);
+        assert_eq!(choice.answer.as_deref(), Some("B"));
+        assert_eq!(choice.tags, ["数学", "质数"]);
+
+        let multi = Question::builder(QuestionType::MultipleChoice, "Which are even?")
+            .with_option("2", true)
+            .with_option("3", false)
+            .with_option("4", true)
+            .build()
+            .unwrap();
+        assert_eq!(multi.answer.as_deref(), Some("AC"));
+
+        let blank = Question::builder(QuestionType::FillInTheBlank, "1 + 1 = ____").with_answer("2").build().unwrap();
+        assert!(blank.options.is_empty());
+        let tf = Question::builder(QuestionType::TrueFalse, "The earth is round.").with_answer("对").build().unwrap();
+        assert_eq!(tf.answer.as_deref(), Some("对"));
+
+        let error = |builder: QuestionBuilder| builder.build().unwrap_err().to_string();
+        assert_eq!(error(QuestionBuilder::new(QuestionType::Subjective, "  ")), "question: the stem is empty");
+        assert_eq!(
+            error(Question::builder(QuestionType::Subjective, "Discuss.").with_option("x", false)),
+            "question: a subjective question cannot have options"
+        );
+        assert_eq!(
+            error(Question::builder(QuestionType::Choice, "Pick").with_option("a", true).with_option("b", true)),
+            "question: a single choice question needs exactly 1 correct option, got 2"
+        );
+        assert!(error(Question::builder(QuestionType::Choice, "Pick").with_option("a", true)).contains("at least 2"));
+        assert!(error(Question::builder(QuestionType::MultipleChoice, "Pick").with_option("a", false).with_option("b", false))
+            .contains("needs a correct option"));
+        assert!(error(Question::builder(QuestionType::FillInTheBlank, "1 + 1 = ____")).contains("needs an answer"));
+        assert!(error(Question::builder(QuestionType::TrueFalse, "Sure?").with_option("对", true)).contains("2 options"));
+        assert!(error(Question::builder(QuestionType::Choice, "Pick").with_option("a", true).with_option(" ", false))
+            .contains("option 2 is empty"));
+        assert!(matches!(
+            Question::builder(QuestionType::Subjective, "").build(),
+            Err(Md2DbError::ValidationError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_migrate_json_upgrades_older_layouts() {
+        // Written by the Python implementation and read back from Mongo
+        let python = serde_json::json!({
+            "_id": { "$oid": "65a1f0c2e4b0a1b2c3d4e5f6" },
+            "content": "Which are prime?",
+            "question_type": "multiple_choice",
+            "options": ["A. 2", "B. 4", "C. 7"],
+            "answer": ["A", "C"],
+            "explanation": "2 and 7 have no other divisors",
+            "images": ["https://example.com/primes.png", "images/sieve.png"],
+            "latex_formulas": null,
+            "created_at": "2024-03-01T08:30:00.123456",
+        });
+        let question = migrate_json(python).unwrap();
+        assert_eq!(question.schema_version, SCHEMA_VERSION);
+        assert_eq!(question.qtype, QuestionType::MultipleChoice);
+        assert_eq!(question.stem, "Which are prime?");
+        let options: Vec<(&str, bool)> = question.options.iter().map(|o| (o.content.as_str(), o.is_correct)).collect();
+        assert_eq!(options, [("2", true), ("4", false), ("7", true)]);
+        assert_eq!(question.answer.as_deref(), Some("AC"));
+        assert_eq!(question.analysis.as_deref(), Some("2 and 7 have no other divisors"));
+        assert!(matches!(&question.images[0], ImageRef::Remote { url, .. } if url == "https://example.com/primes.png"));
+        assert!(matches!(&question.images[1], ImageRef::Local { original_path, .. } if original_path == "images/sieve.png"));
+        assert!(question.latex.is_empty());
+        assert_eq!(question.created_at.to_rfc3339(), "2024-03-01T08:30:00.123456+00:00");
+
+        let blank = migrate_json(serde_json::json!({
+            "content": "1 + 1 = ____",
+            "question_type": "fill_in_blank",
+            "answer": "2",
+            "created_at": { "$date": 1709281800000i64 },
+        }))
+        .unwrap();
+        assert_eq!(blank.qtype, QuestionType::FillInTheBlank);
+        assert_eq!(blank.created_at.timestamp(), 1709281800);
+
+        // Rust releases before schema_version: later fields take defaults
+        let id = Uuid::new_v4();
+        let v1 = serde_json::json!({
+            "id": id,
+            "type": "choice",
+            "stem": "1 + 1 = ?",
+            "options": [
+                { "content": "1", "sort_order": 0, "is_correct": false },
+                { "content": "2", "sort_order": 1, "is_correct": true },
+            ],
+            "answer": "B",
+            "analysis": null,
+            "images": [],
+            "latex": [],
+            "created_at": "2025-01-01T00:00:00Z",
+        });
+        let question = migrate_json(v1.clone()).unwrap();
+        assert_eq!((question.id, question.schema_version), (id, SCHEMA_VERSION));
+        assert!(question.tags.is_empty() && question.bank_id.is_none());
+        // Plain deserialization accepts it too
+        assert_eq!(serde_json::from_value::<Question>(v1).unwrap().id, id);
+
+        // A current question round-trips unchanged
+        let current = serde_json::to_value(&question).unwrap();
+        assert_eq!(current["schema_version"], SCHEMA_VERSION);
+        assert_eq!(serde_json::to_value(migrate_json(current.clone()).unwrap()).unwrap(), current);
+
+        let error = |value: serde_json::Value| migrate_json(value).unwrap_err().to_string();
+        assert_eq!(
+            error(serde_json::json!({ "schema_version": SCHEMA_VERSION + 1, "stem": "?", "type": "choice" })),
+            format!("question JSON: schema_version {} is newer than this release supports ({})", SCHEMA_VERSION + 1, SCHEMA_VERSION)
+        );
+        assert_eq!(error(serde_json::json!(["stem"])), "question JSON: expected an object, got an array");
+        assert!(error(serde_json::json!({ "stem": 1, "type": "choice" })).starts_with("question JSON: "));
+    }
+}
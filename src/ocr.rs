@@ -0,0 +1,248 @@
+//! Formula recognition for scanned images
+//!
+//! Exam scans often contain formulas only as pictures. A [`FormulaOcr`]
+//! backend turns such an image into LaTeX; recognized formulas are appended
+//! to [`Question::latex`] with a marker naming the source image, so they are
+//! searchable and can be told apart from formulas written in the Markdown.
+
+use crate::models::{ImageRef, Question};
+use crate::zip::EntryContent;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Marker separating a recognized formula from the image it came from
+pub const OCR_MARKER: &str = "% ocr: ";
+
+/// Recognizes LaTeX in an image
+#[async_trait]
+pub trait FormulaOcr: Send + Sync {
+    /// Return the LaTeX shown in `image`, or `None` if it holds no formula
+    async fn recognize(&self, image: &[u8]) -> Result<Option<String>>;
+}
+
+/// Format a recognized formula for [`Question::latex`]
+///
+/// Produces `$latex$ % ocr: path`; the LaTeX comment keeps the formula
+/// itself valid while recording where it came from.
+pub fn ocr_formula(latex: &str, source: &str) -> String {
+    format!("${}$ {}{}", latex.trim().trim_matches('$').trim(), OCR_MARKER, source)
+}
+
+/// Whether a formula was recognized from an image rather than written
+pub fn is_ocr_formula(formula: &str) -> bool {
+    formula.contains(OCR_MARKER)
+}
+
+/// Run `ocr` on the local images of `questions`
+///
+/// `images` maps content hashes to image bytes, as in
+/// [`ZipProcessResult::images`](crate::zip::ZipProcessResult::images).
+/// Each distinct image is recognized once. Failures are returned as warnings
+/// and leave the question unchanged.
+pub async fn recognize_formulas(
+    ocr: &dyn FormulaOcr,
+    questions: &mut [Question],
+    images: &HashMap<String, EntryContent>,
+) -> Vec<String> {
+    let mut recognized: HashMap<String, Option<String>> = HashMap::new();
+    let mut warnings = Vec::new();
+
+    for question in questions {
+        let mut formulas = Vec::new();
+        for image in &question.images {
+            let ImageRef::Local { hash, original_path, .. } = image else {
+                continue;
+            };
+            let Some(content) = images.get(hash) else {
+                continue;
+            };
+
+            if !recognized.contains_key(hash) {
+                let recognized_latex = match content.read() {
+                    Ok(data) => ocr.recognize(&data).await,
+                    Err(e) => Err(e.into()),
+                };
+                let latex = match recognized_latex {
+                    Ok(latex) => latex.filter(|l| !l.trim().is_empty()),
+                    Err(e) => {
+                        warnings.push(format!("Formula OCR failed for {}: {}", original_path, e));
+                        None
+                    }
+                };
+                recognized.insert(hash.clone(), latex);
+            }
+            if let Some(latex) = &recognized[hash] {
+                formulas.push(ocr_formula(latex, original_path));
+            }
+        }
+
+        for formula in formulas {
+            if !question.latex.contains(&formula) {
+                question.latex.push(formula);
+            }
+        }
+    }
+    warnings
+}
+
+/// Default time allowed for one recognition command
+pub const DEFAULT_OCR_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Recognize formulas with an external command
+///
+/// The image is written to the command's stdin and its trimmed stdout is
+/// taken as the LaTeX; empty output means no formula. This fits most
+/// LaTeX-OCR tools and small wrapper scripts around HTTP services.
+pub struct CommandOcr {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandOcr {
+    /// Run `program` with `args` for every image
+    pub fn new(program: impl Into<String>, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            timeout: DEFAULT_OCR_TIMEOUT,
+        }
+    }
+
+    /// Parse a shell-style command line (`pix2tex --stdin`), split on whitespace
+    pub fn from_command_line(command: &str) -> Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().context("Empty OCR command")?;
+        Ok(Self::new(program, parts))
+    }
+
+    /// Kill the command if it runs longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl FormulaOcr for CommandOcr {
+    async fn recognize(&self, image: &[u8]) -> Result<Option<String>> {
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", self.program))?;
+
+        // Feed stdin concurrently so a chatty command cannot deadlock us
+        let mut stdin = child.stdin.take().context("OCR command has no stdin")?;
+        let image = image.to_vec();
+        let writer = tokio::spawn(async move {
+            let _ = stdin.write_all(&image).await;
+        });
+
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .with_context(|| format!("{} timed out after {:?}", self.program, self.timeout))??;
+        let _ = writer.await;
+
+        if !output.status.success() {
+            bail!(
+                "{} exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let latex = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok((!latex.is_empty()).then_some(latex))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct FixedOcr {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl FormulaOcr for FixedOcr {
+        async fn recognize(&self, image: &[u8]) -> Result<Option<String>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match image {
+                b"formula" => Ok(Some("x^2 + 1".to_string())),
+                b"broken" => bail!("service unavailable"),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    fn local(hash: &str, path: &str) -> ImageRef {
+        ImageRef::Local {
+            hash: hash.to_string(),
+            original_path: path.to_string(),
+            stored_path: None,
+            alt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recognize_formulas() {
+        let images = HashMap::from([
+            ("f".to_string(), b"formula".to_vec().into()),
+            ("p".to_string(), b"photo".to_vec().into()),
+            ("b".to_string(), b"broken".to_vec().into()),
+        ]);
+        let mut first = Question {
+            latex: vec!["$y$".to_string()],
+            images: vec![local("f", "img/f.png"), local("p", "img/p.png"), local("", "missing.png")],
+            ..Default::default()
+        };
+        first.images.push(ImageRef::Remote { url: "https://example.com/x.png".to_string(), alt: None });
+        let second = Question {
+            images: vec![local("f", "img/f.png"), local("b", "img/b.png")],
+            ..Default::default()
+        };
+        let mut questions = vec![first, second];
+
+        let ocr = FixedOcr { calls: AtomicUsize::new(0) };
+        let warnings = recognize_formulas(&ocr, &mut questions, &images).await;
+
+        assert_eq!(questions[0].latex, ["$y$", "$x^2 + 1$ % ocr: img/f.png"]);
+        assert_eq!(questions[1].latex, ["$x^2 + 1$ % ocr: img/f.png"]);
+        assert!(is_ocr_formula(&questions[1].latex[0]));
+        assert!(!is_ocr_formula(&questions[0].latex[0]));
+        assert_eq!(warnings, ["Formula OCR failed for img/b.png: service unavailable"]);
+        // The shared image is recognized once
+        assert_eq!(ocr.calls.load(Ordering::SeqCst), 3);
+
+        // Running again does not duplicate formulas
+        recognize_formulas(&ocr, &mut questions, &images).await;
+        assert_eq!(questions[0].latex.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_ocr() {
+        let echo = CommandOcr::new("sh", ["-c", "cat >/dev/null; printf '%s\\n' '\\frac{a}{b}'"]);
+        assert_eq!(echo.recognize(b"image").await.unwrap().as_deref(), Some("\\frac{a}{b}"));
+
+        let silent = CommandOcr::from_command_line("cat /dev/null").unwrap();
+        assert_eq!(silent.recognize(b"image").await.unwrap(), None);
+
+        let failing = CommandOcr::new("sh", ["-c", "echo boom >&2; exit 3"]);
+        let err = failing.recognize(b"image").await.unwrap_err().to_string();
+        assert!(err.contains("boom"), "{}", err);
+
+        let slow = CommandOcr::new("sleep", ["5"]).with_timeout(Duration::from_millis(50));
+        assert!(slow.recognize(b"image").await.unwrap_err().to_string().contains("timed out"));
+    }
+}
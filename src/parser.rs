@@ -3,113 +3,718 @@
 //! This module uses pulldown-cmark to parse Markdown and extract questions
 //! using an AST-based approach.
 
-use crate::models::{Question, QuestionOption};
-use anyhow::Result;
+use crate::bidi;
+use crate::classifier::section_type;
+use crate::models::{ImageRef, Question, QuestionOption, TypeSource};
+use crate::scan::TextScanner;
+use crate::typeset::is_cjk;
+use crate::error::Result;
 use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Paragraph prefixes that introduce an answer
+const ANSWER_PREFIXES: &[&str] = &[
+    "【答案】", "正确答案：", "正确答案:", "答案：", "答案:", "Answer:", "Answer：", "answer:",
+];
+
+/// Paragraph prefixes that introduce an analysis/explanation
+const ANALYSIS_PREFIXES: &[&str] = &[
+    "【解析】", "解析：", "解析:", "Analysis:", "Explanation:",
+];
+
+/// Characters that end a complete sentence
+const TERMINAL_PUNCTUATION: &[char] = &[
+    '.', '?', '!', ':', ';', ')', '。', '？', '！', '：', '；', '）', '」', '”',
+];
+
+/// Whether a paragraph is a display formula ("$$ … $$")
+fn is_display_math(text: &str) -> bool {
+    text.len() > 4 && text.starts_with("$$") && text.ends_with("$$")
+}
+
+/// Whether joining two fragments needs a space (Latin on both sides)
+fn needs_space(before: &str, after: &str) -> bool {
+    let last = before.chars().last();
+    let first = after.chars().next();
+    matches!((last, first), (Some(a), Some(b)) if !is_cjk(a) && !is_cjk(b))
+}
+
+/// Marker groups of [`MarkdownParser::markers`]
+const ANSWER: usize = 0;
+const ANALYSIS: usize = 1;
+
+/// Separators accepted after an option letter ("A." / "A、" / "A．" / "A)")
+pub(crate) const OPTION_SEPARATORS: &[char] = &['.', '．', '、', ')', '）', ':', '：'];
+
+/// Whether a line starts with an option marker such as "A.", "B、" or "c)"
+fn starts_with_option_marker(line: &str) -> bool {
+    let mut chars = line.chars();
+    match (chars.next(), chars.next()) {
+        (Some('A'..='H'), Some(sep)) => OPTION_SEPARATORS.contains(&sep),
+        // Lowercase only with a parenthesis: "e.g." is not option E
+        (Some('a'..='h'), Some(sep)) => matches!(sep, ')' | '）'),
+        _ => false,
+    }
+}
+
+/// Split a paragraph whose lines each start with the next option letter
+/// ("A) 3\nB) 4" or "a) 3\nb) 4") into one string per line
+///
+/// Such a paragraph is a lettered list, which Markdown has no syntax for.
+fn lettered_items(text: &str) -> Option<Vec<String>> {
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let first = *lines.first()?.as_bytes().first()?;
+    if lines.len() < 2 || !matches!(first, b'A' | b'a') {
+        return None;
+    }
+    let lettered = lines.iter().enumerate().all(|(idx, line)| {
+        usize::from(line.as_bytes()[0]) == usize::from(first) + idx && starts_with_option_marker(line)
+    });
+    lettered.then(|| lines.iter().map(|line| line.to_string()).collect())
+}
+
+/// Rewrite an answer given as item numbers ("2", "1、3") as option letters
+///
+/// The options were numbered by an ordered list starting at `start`, whose
+/// first item is option `first` of `count`. Returns `None` unless every
+/// number names one of those options.
+fn letters_from_numbers(answer: &str, start: u64, first: usize, count: usize) -> Option<String> {
+    if answer.chars().any(char::is_alphabetic) {
+        return None;
+    }
+    let numbers = answer.split(|c: char| !c.is_ascii_digit()).filter(|n| !n.is_empty());
+    let letters: String = numbers
+        .map(|number| {
+            let offset = number.parse::<u64>().ok()?.checked_sub(start)?;
+            let idx = first + usize::try_from(offset).ok()?;
+            (idx < count.min(26)).then(|| (b'A' + idx as u8) as char)
+        })
+        .collect::<Option<_>>()?;
+    (!letters.is_empty()).then_some(letters)
+}
+
+/// Split a line like "A．1 B．2 C．3" into one string per option
+///
+/// Returns `None` if the line does not start with an option marker.
+fn split_inline_options(line: &str) -> Option<Vec<String>> {
+    if !starts_with_option_marker(line) {
+        return None;
+    }
+
+    let mut options = Vec::new();
+    let mut start = 0;
+    let mut next_letter = (line.as_bytes()[0] + 1) as char;
+    let mut prev_space = false;
+    for (idx, c) in line.char_indices() {
+        if prev_space && c == next_letter && starts_with_option_marker(&line[idx..]) {
+            options.push(line[start..idx].trim().to_string());
+            start = idx;
+            next_letter = (next_letter as u8 + 1) as char;
+        }
+        prev_space = c.is_whitespace();
+    }
+    options.push(line[start..].trim().to_string());
+    Some(options)
+}
+
+/// Find lettered options written after the stem on the same line
+/// ("下列哪个是质数？A．4 B．6 C．7")
+///
+/// The run must start at "A" after a space or punctuation and hold at least
+/// two options. Returns the length of the stem before it and one string per
+/// option.
+fn split_stem_options(stem: &str) -> Option<(usize, Vec<String>)> {
+    let mut prev = None;
+    for (idx, c) in stem.char_indices() {
+        let after_break = prev.is_some_and(|p: char| p.is_whitespace() || !p.is_alphanumeric());
+        if c == 'A' && after_break {
+            let stem_len = stem[..idx].trim_end().len();
+            match split_inline_options(&stem[idx..]) {
+                Some(options) if options.len() >= 2 && stem_len > 0 => return Some((stem_len, options)),
+                _ => {}
+            }
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+/// Split a leading question number ("1." / "12、" / "３．" / "4)") from a line
+///
+/// Returns the number as written and the stem after it.
+fn split_question_number(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    // Deeper indentation is a nested list or code, not a question
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+
+    let digits_end = trimmed
+        .char_indices()
+        .find(|(_, c)| !(c.is_ascii_digit() || ('０'..='９').contains(c)))
+        .map(|(idx, _)| idx)?;
+    if digits_end == 0 {
+        return None;
+    }
+
+    let (number, rest) = trimmed.split_at(digits_end);
+    let sep = rest.chars().next()?;
+    let stem = &rest[sep.len_utf8()..];
+    // "3.14" is a number, not "3." followed by a stem
+    let decimal = sep == '.' && stem.starts_with(|c: char| c.is_ascii_digit());
+    if !matches!(sep, '.' | '．' | '、' | ')' | '）') || decimal {
+        return None;
+    }
+
+    let stem = stem.trim();
+    (!stem.is_empty()).then_some((number, stem))
+}
+
+/// Whether a line is a section title such as "一、选择题"
+fn is_section_title(line: &str) -> bool {
+    let trimmed = line.trim();
+    let numeral_end = trimmed
+        .char_indices()
+        .find(|(_, c)| !"一二三四五六七八九十".contains(*c))
+        .map(|(idx, _)| idx)
+        .unwrap_or(trimmed.len());
+    numeral_end > 0 && trimmed[numeral_end..].starts_with(['、', '．', '.'])
+}
+
+/// Put `question` under `section`, which sets its type when the title
+/// declares one ("三、判断题")
+fn enter_section(question: &mut Question, section: &Option<String>) {
+    question.section.clone_from(section);
+    if let Some(qtype) = section.as_deref().and_then(section_type) {
+        question.qtype = qtype;
+        question.type_source = Some(TypeSource::Section);
+    }
+}
+
+/// Tunable parser behavior
+///
+/// Named bundles for common export formats are available in
+/// [`crate::presets`].
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// Rejoin stems that an export hard-wrapped into several paragraphs
+    ///
+    /// A paragraph is treated as a continuation when the stem so far has no
+    /// terminal punctuation and the paragraph starts with a lowercase Latin
+    /// letter or a CJK character.
+    pub merge_stem_fragments: bool,
+    /// Start a new question at lines numbered "1." / "1、" / "１．"; headings
+    /// without a number are then section titles
+    pub numbered_questions: bool,
+    /// Treat paragraph lines starting with "A." / "A、" / "A．" as options,
+    /// and split options written on the stem's own line ("…？A．4 B．6")
+    /// off the stem
+    pub inline_options: bool,
+    /// Deepest heading level that starts a new question
+    pub question_heading_level: u32,
+    /// Paragraph prefixes that introduce an answer
+    pub answer_prefixes: Vec<String>,
+    /// Paragraph prefixes that introduce an analysis
+    pub analysis_prefixes: Vec<String>,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            merge_stem_fragments: true,
+            numbered_questions: false,
+            inline_options: false,
+            question_heading_level: 3,
+            answer_prefixes: ANSWER_PREFIXES.iter().map(|p| p.to_string()).collect(),
+            analysis_prefixes: ANALYSIS_PREFIXES.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl ParserOptions {
+    /// Enable or disable stem fragment merging
+    pub fn with_merge_stem_fragments(mut self, enabled: bool) -> Self {
+        self.merge_stem_fragments = enabled;
+        self
+    }
+
+    /// Enable or disable numbered question boundaries
+    pub fn with_numbered_questions(mut self, enabled: bool) -> Self {
+        self.numbered_questions = enabled;
+        self
+    }
+
+    /// Enable or disable options written as plain paragraph lines
+    pub fn with_inline_options(mut self, enabled: bool) -> Self {
+        self.inline_options = enabled;
+        self
+    }
+
+    /// Replace the answer prefix table
+    pub fn with_answer_prefixes<S: Into<String>>(mut self, prefixes: impl IntoIterator<Item = S>) -> Self {
+        self.answer_prefixes = prefixes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Replace the analysis prefix table
+    pub fn with_analysis_prefixes<S: Into<String>>(mut self, prefixes: impl IntoIterator<Item = S>) -> Self {
+        self.analysis_prefixes = prefixes.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// Lines of the input a question was parsed from
+///
+/// Lines are numbered from 1 and both ends are included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// How the items of a list are marked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListKind {
+    /// "-" / "*" bullets
+    Bullet,
+    /// Numbers, starting from the one given
+    Ordered(u64),
+    /// Option letters on plain lines ("A)", "b)")
+    Lettered,
+}
 
 /// The main Markdown parser
+///
+/// Questions are built in place and moved out when finished, and the text
+/// buffers keep their capacity from one paragraph to the next, so parsing a
+/// large file allocates little beyond the questions themselves.
 pub struct MarkdownParser {
+    options: ParserOptions,
+    /// Answer and analysis prefixes from `options`, as groups [`ANSWER`]
+    /// and [`ANALYSIS`]
+    markers: TextScanner,
     questions: Vec<Question>,
     current_question: Question,
+    /// Text of the paragraph, heading or list item being read
     current_text: String,
-    in_list: bool,
+    /// A paragraph segment being joined, reused across paragraphs
+    segment: String,
+    /// Lists open around the current event; only items of the outermost
+    /// are collected, with nested lists joined into them
+    list_depth: usize,
+    /// Kind of the outermost list
+    list_kind: ListKind,
+    /// Whether the outermost list explains the answer rather than giving
+    /// options, because it directly follows the answer or analysis
+    list_in_analysis: bool,
+    /// Whether the last block read was an answer or analysis paragraph
+    after_marker: bool,
     list_items: Vec<String>,
+    /// Number of the first item and index of the first option, for options
+    /// read from an ordered list, so numeric answers can be read as letters
+    option_numbers: Option<(u64, usize)>,
     latex_formulas: Vec<String>,
+    /// Whether the events read are an image's alt text
+    in_image: bool,
+    /// Whether the current stem came from a paragraph (rather than a heading)
+    stem_from_paragraph: bool,
+    /// Title of the section being read ("一、单选题"), given to each question
+    section: Option<String>,
+    /// Byte range of the parsed text covered by the current question so far
+    current_range: Option<Range<usize>>,
+    /// Byte range of each finished question, in parsed text
+    ranges: Vec<Range<usize>>,
+    spans: Vec<SourceSpan>,
 }
 
 impl MarkdownParser {
     /// Create a new parser instance
     pub fn new() -> Self {
+        Self::with_options(ParserOptions::default())
+    }
+
+    /// Create a new parser instance with custom options
+    pub fn with_options(options: ParserOptions) -> Self {
+        let markers = TextScanner::new([&options.answer_prefixes, &options.analysis_prefixes]);
         Self {
+            options,
+            markers,
             questions: Vec::new(),
             current_question: Question::default(),
             current_text: String::new(),
-            in_list: false,
+            segment: String::new(),
+            list_depth: 0,
+            list_kind: ListKind::Bullet,
+            list_in_analysis: false,
+            after_marker: false,
             list_items: Vec::new(),
+            option_numbers: None,
             latex_formulas: Vec::new(),
+            in_image: false,
+            stem_from_paragraph: false,
+            section: None,
+            current_range: None,
+            ranges: Vec::new(),
+            spans: Vec::new(),
         }
     }
 
     /// Parse Markdown content and extract questions
     pub fn parse(&mut self, markdown: &str) -> Result<&[Question]> {
-        let parser = Parser::new(markdown);
+        let (markdown, line_map): (Cow<str>, _) = if self.options.numbered_questions {
+            let (rewritten, map) = self.headings_from_numbers(markdown);
+            (Cow::Owned(rewritten), Some(map))
+        } else {
+            (Cow::Borrowed(markdown), None)
+        };
+        let markdown = markdown.as_ref();
+        let parser = Parser::new(markdown).into_offset_iter();
+        self.current_range = None;
 
-        for event in parser {
+        for (event, range) in parser {
             match event {
                 Event::Start(Tag::Heading { level, .. }) => {
                     self.on_heading_start(level as i32);
                 }
                 Event::End(TagEnd::Heading(_)) => {
-                    self.on_heading_end();
+                    let is_section = self.on_heading_end();
+                    if is_section {
+                        // A section title belongs to no question's lines
+                        self.current_range = None;
+                        continue;
+                    }
+                }
+                // Paragraphs of a loose list belong to its item
+                Event::Start(Tag::Paragraph) if self.list_depth > 0 => {
+                    self.current_text.push('\n');
                 }
                 Event::Start(Tag::Paragraph) => {
                     self.current_text.clear();
                 }
-                Event::End(TagEnd::Paragraph) => {
+                Event::End(TagEnd::Paragraph) if self.list_depth == 0 => {
                     self.on_paragraph_end();
                 }
+                // Alt text describes the image, it is not question text
+                Event::Text(text) if self.in_image => {
+                    self.on_image_alt(&text);
+                }
                 Event::Text(text) => {
                     self.current_text.push_str(&text);
                 }
+                Event::SoftBreak | Event::HardBreak => {
+                    self.current_text.push('\n');
+                }
                 Event::Code(code) => {
                     self.on_code(&code);
                 }
-                Event::Start(Tag::List(_)) => {
-                    self.in_list = true;
+                Event::Start(Tag::List(start)) => {
+                    self.list_depth += 1;
+                    if self.list_depth == 1 {
+                        self.on_list_start(start.map_or(ListKind::Bullet, ListKind::Ordered));
+                    }
                 }
                 Event::End(TagEnd::List(_)) => {
-                    self.in_list = false;
-                    self.on_list_end();
+                    self.list_depth = self.list_depth.saturating_sub(1);
+                    if self.list_depth == 0 {
+                        self.on_list_end();
+                    }
+                }
+                Event::Start(Tag::Item) if self.list_depth > 1 => {
+                    self.current_text.push('\n');
                 }
                 Event::Start(Tag::Item) => {
                     self.current_text.clear();
                 }
-                Event::End(TagEnd::Item) => {
-                    if self.in_list {
-                        self.list_items.push(self.current_text.clone());
-                    }
+                Event::End(TagEnd::Item) if self.list_depth == 1 => {
+                    let item = self.join_lines(self.current_text.lines());
+                    self.list_items.push(item);
+                }
+                Event::Start(Tag::Image { dest_url, .. }) => {
+                    self.current_question.images.push(image_ref(&dest_url));
+                    self.in_image = true;
                 }
-                Event::Start(Tag::Image { .. }) => {
-                    // TODO: Extract image URLs
+                Event::End(TagEnd::Image) => {
+                    self.in_image = false;
                 }
                 _ => {}
             }
+            // After the event, so a heading that finished the previous
+            // question starts the range of the next
+            let current = self.current_range.get_or_insert(range.clone());
+            current.end = current.end.max(range.end);
         }
 
         // Don't forget the last question
         self.finalize_question();
 
+        let lines = LineIndex::new(markdown);
+        let first = self.spans.len();
+        self.spans.extend(self.ranges[first..].iter().map(|range| {
+            let start = lines.line_of(range.start);
+            let end = lines.line_of(range.end.saturating_sub(1).max(range.start));
+            match &line_map {
+                Some(map) => SourceSpan {
+                    start_line: map[start].start + 1,
+                    end_line: map[end].end + 1,
+                },
+                None => SourceSpan {
+                    start_line: start + 1,
+                    end_line: end + 1,
+                },
+            }
+        }));
+
         Ok(&self.questions)
     }
 
+    /// Source lines of each question parsed so far, in the same order
+    pub fn spans(&self) -> &[SourceSpan] {
+        &self.spans
+    }
+
     fn on_heading_start(&mut self, level: i32) {
         // New question detected (typically headings indicate question boundaries)
-        if level <= 3 && !self.current_question.stem.is_empty() {
+        if level as u32 <= self.options.question_heading_level && !self.current_question.stem.is_empty() {
             self.finalize_question();
         }
         self.current_text.clear();
+        self.after_marker = false;
     }
 
-    fn on_heading_end(&mut self) {
-        // Heading text becomes the question stem
-        if !self.current_text.is_empty() {
-            self.current_question.stem = self.current_text.trim().to_string();
+    /// Returns whether the heading was a section title rather than a stem
+    fn on_heading_end(&mut self) -> bool {
+        let text = self.current_text.trim();
+        // Where numbers start questions, a heading without one ("## Multiple
+        // Choice") titles a section too
+        let unnumbered = self.options.numbered_questions && !text.is_empty() && split_question_number(text).is_none();
+        if is_section_title(text) || unnumbered {
+            // Section titles group the questions after them
+            let title = text.to_string();
+            self.finalize_question();
+            self.section = Some(title);
+            return true;
         }
+
+        // Heading text becomes the question stem; numbers that only mark
+        // question boundaries are kept apart from it
+        if !text.is_empty() {
+            let (number, text) = match split_question_number(text) {
+                Some((number, stem)) if self.options.numbered_questions => (Some(number), stem),
+                Some((number, _)) => (Some(number), text),
+                None => (None, text),
+            };
+            let question = &mut self.current_question;
+            question.number = number.map(str::to_string);
+            question.stem.clear();
+            question.stem.push_str(text);
+        }
+        false
+    }
+
+    /// Join soft-wrapped lines, with a space only between Latin text
+    fn join_lines<'a>(&self, lines: impl Iterator<Item = &'a str>) -> String {
+        let mut joined = String::new();
+        for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+            if needs_space(&joined, line) {
+                joined.push(' ');
+            }
+            joined.push_str(line);
+        }
+        joined
+    }
+
+    /// Rewrite numbered question lines as headings so they start new questions
+    ///
+    /// Lines wrapped directly under a numbered stem are folded into its
+    /// heading, and section titles ("一、选择题") become headings of their
+    /// own. Also returns, for each line written, the range of input lines
+    /// it came from.
+    fn headings_from_numbers(&self, markdown: &str) -> (String, Vec<Range<usize>>) {
+        let mut out = String::with_capacity(markdown.len() + 64);
+        let mut map = Vec::new();
+        let mut heading: Option<(String, Range<usize>)> = None;
+        for (number, line) in markdown.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some((stem, lines)) = heading.as_mut() {
+                let structural = trimmed.starts_with(['#', '-', '*', '+', '>', '|', '!', '`', '$']);
+                if !trimmed.is_empty()
+                    && !structural
+                    && !self.is_marker_line(trimmed)
+                    && split_question_number(line).is_none()
+                    && !is_section_title(line)
+                {
+                    if needs_space(stem, trimmed) {
+                        stem.push(' ');
+                    }
+                    stem.push_str(trimmed);
+                    lines.end = number;
+                    continue;
+                }
+                out.push_str(stem);
+                out.push('\n');
+                map.push(lines.clone());
+                heading = None;
+            }
+
+            if is_section_title(line) {
+                out.push_str("\n# ");
+                out.push_str(trimmed);
+                out.push('\n');
+                map.push(number..number);
+                map.push(number..number);
+            } else if split_question_number(line).is_some() {
+                // Blank line first so the heading never continues a list item
+                out.push('\n');
+                map.push(number..number);
+                heading = Some((format!("### {}", trimmed), number..number));
+            } else {
+                out.push_str(line);
+                out.push('\n');
+                map.push(number..number);
+            }
+        }
+        if let Some((stem, lines)) = heading {
+            out.push_str(&stem);
+            out.push('\n');
+            map.push(lines);
+        }
+        // The parser may report the end of the text, past the last line
+        map.push(map.last().cloned().unwrap_or(0..0));
+        (out, map)
+    }
+
+    /// Whether a line starts an answer, analysis or (if enabled) option
+    fn is_marker_line(&self, line: &str) -> bool {
+        self.markers.strip_prefix(line).is_some()
+            || (self.options.inline_options && starts_with_option_marker(line))
     }
 
     fn on_paragraph_end(&mut self) {
+        // A paragraph may hold several parts ("A．…\nB．…\n答案：A"); split it
+        // at marker lines and keep ordinary soft-wrapped lines together.
+        // Both buffers are taken so the segments can borrow them while the
+        // question is updated, and put back to keep their capacity.
+        let text = std::mem::take(&mut self.current_text);
+        if let Some(items) = lettered_items(&text) {
+            self.on_list_start(ListKind::Lettered);
+            self.list_items = items;
+            self.on_list_end();
+            self.current_text = text;
+            return;
+        }
+        let mut segment = std::mem::take(&mut self.segment);
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if !segment.is_empty() && self.is_marker_line(line) {
+                self.on_segment(&segment);
+                segment.clear();
+            }
+            if needs_space(&segment, line) {
+                segment.push(' ');
+            }
+            segment.push_str(line);
+        }
+        if !segment.is_empty() {
+            self.on_segment(&segment);
+        }
+
+        segment.clear();
+        self.segment = segment;
+        self.current_text = text;
+    }
+
+    fn on_segment(&mut self, text: &str) {
+        match self.markers.strip_prefix(text) {
+            Some((ANSWER, answer)) => {
+                self.current_question.answer = Some(answer.trim().to_string());
+                self.after_marker = true;
+                return;
+            }
+            Some((ANALYSIS, analysis)) => {
+                self.current_question.analysis = Some(analysis.trim().to_string());
+                self.after_marker = true;
+                return;
+            }
+            _ => self.after_marker = false,
+        }
+        if self.options.inline_options {
+            if let Some(options) = split_inline_options(text) {
+                for content in options {
+                    let sort_order = self.current_question.options.len() as i32;
+                    self.current_question.options.push(QuestionOption {
+                        content,
+                        sort_order,
+                        is_correct: false,
+                    });
+                }
+                return;
+            }
+        }
+
+        if self.is_stem_continuation(text) {
+            let stem = &mut self.current_question.stem;
+            if needs_space(stem, text) {
+                stem.push(' ');
+            }
+            stem.push_str(text);
+            return;
+        }
+
+        // A display formula under the stem ("$$ … $$") belongs to it
+        let q = &mut self.current_question;
+        if is_display_math(text) && !q.stem.is_empty() && q.options.is_empty() && q.answer.is_none() && q.analysis.is_none() {
+            q.stem.push('\n');
+            q.stem.push_str(text);
+            return;
+        }
+
         // Paragraph text after heading gets appended to stem
-        if !self.current_text.is_empty() && self.current_question.stem.is_empty() {
-            self.current_question.stem = self.current_text.trim().to_string();
-        } else if !self.current_text.is_empty() {
+        if !text.is_empty() && self.current_question.stem.is_empty() {
+            self.current_question.stem = text.to_string();
+            self.stem_from_paragraph = true;
+        } else if !text.is_empty() {
             // Additional paragraphs (could be analysis/answer)
-            let text = self.current_text.trim();
             if self.current_question.analysis.is_none() {
                 self.current_question.analysis = Some(text.to_string());
             }
         }
     }
 
+    /// Whether a paragraph continues a stem that was hard-wrapped by an export
+    fn is_stem_continuation(&self, text: &str) -> bool {
+        let q = &self.current_question;
+        if !self.options.merge_stem_fragments
+            || !self.stem_from_paragraph
+            || text.is_empty()
+            || !q.options.is_empty()
+            || q.answer.is_some()
+            || q.analysis.is_some()
+        {
+            return false;
+        }
+
+        let ends_terminal = q
+            .stem
+            .trim_end()
+            .chars()
+            .last()
+            .is_some_and(|c| TERMINAL_PUNCTUATION.contains(&c));
+        let starts_continuation = text
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || is_cjk(c));
+
+        !ends_terminal && starts_continuation
+    }
+
+    /// Add to the alt text of the image being read
+    fn on_image_alt(&mut self, text: &str) {
+        if let Some(ImageRef::Remote { alt, .. } | ImageRef::Local { alt, .. }) = self.current_question.images.last_mut() {
+            alt.get_or_insert_with(String::new).push_str(text);
+        }
+    }
+
     fn on_code(&mut self, code: &str) {
         // Check if it's a LaTeX formula
         let trimmed = code.trim();
@@ -121,23 +726,91 @@ impl MarkdownParser {
         }
     }
 
+    fn on_list_start(&mut self, kind: ListKind) {
+        self.list_kind = kind;
+        // Only right after a marked answer or analysis: a plain paragraph
+        // after the stem may introduce the options. A numbered list that
+        // does not start at 1 continues the questions ("2. …"), whose
+        // numbers the default options leave as list items.
+        let numbered_question = matches!(kind, ListKind::Ordered(start) if start > 1);
+        self.list_in_analysis = self.after_marker && !numbered_question;
+    }
+
     fn on_list_end(&mut self) {
-        // Process list items as options
-        for (idx, item) in self.list_items.drain(..).enumerate() {
+        let question = &mut self.current_question;
+        if self.list_in_analysis {
+            // Steps of a worked solution, kept as list lines
+            let analysis = question.analysis.get_or_insert_with(String::new);
+            for (idx, item) in self.list_items.drain(..).enumerate() {
+                if !analysis.is_empty() {
+                    analysis.push('\n');
+                }
+                match self.list_kind {
+                    ListKind::Bullet => analysis.push_str(&format!("- {}", item)),
+                    ListKind::Ordered(start) => analysis.push_str(&format!("{}. {}", start + idx as u64, item)),
+                    ListKind::Lettered => analysis.push_str(&item),
+                }
+            }
+            return;
+        }
+
+        self.after_marker = false;
+        if let ListKind::Ordered(start) = self.list_kind {
+            self.option_numbers.get_or_insert((start, question.options.len()));
+        }
+        // Process list items as options; they were trimmed when joined
+        for content in self.list_items.drain(..) {
             let option = QuestionOption {
-                content: item.trim().to_string(),
-                sort_order: idx as i32,
+                content,
+                sort_order: question.options.len() as i32,
                 is_correct: false, // Will be determined later
             };
-            self.current_question.options.push(option);
+            question.options.push(option);
         }
     }
 
+    /// Whether options, answers or formulas were read that no stem claimed
+    #[cfg(feature = "parallel")]
+    fn has_dangling_content(&self) -> bool {
+        let q = &self.current_question;
+        !q.options.is_empty()
+            || !q.images.is_empty()
+            || q.answer.is_some()
+            || q.analysis.is_some()
+            || !self.latex_formulas.is_empty()
+    }
+
     fn finalize_question(&mut self) {
         if !self.current_question.stem.is_empty() {
-            self.current_question.latex = self.latex_formulas.drain(..).collect();
-            self.questions.push(self.current_question.clone());
-            self.current_question = Question::default();
+            let mut question = std::mem::take(&mut self.current_question);
+            question.latex = std::mem::take(&mut self.latex_formulas);
+            if let (Some((start, first)), Some(answer)) = (self.option_numbers.take(), &mut question.answer) {
+                if let Some(letters) = letters_from_numbers(answer, start, first, question.options.len()) {
+                    *answer = letters;
+                }
+            }
+            // Like option lines, only where the format writes options inline:
+            // plain Markdown gives options as lists, so its stems stay whole
+            if self.options.inline_options && question.options.is_empty() {
+                if let Some((stem_len, options)) = split_stem_options(&question.stem) {
+                    question.stem.truncate(stem_len);
+                    question.options = options
+                        .into_iter()
+                        .enumerate()
+                        .map(|(idx, content)| QuestionOption {
+                            content,
+                            sort_order: idx as i32,
+                            is_correct: false,
+                        })
+                        .collect();
+                }
+            }
+            enter_section(&mut question, &self.section);
+            bidi::normalize_question(&mut question);
+            self.ranges.push(self.current_range.take().unwrap_or_default());
+            self.questions.push(question);
+            self.stem_from_paragraph = false;
+            self.after_marker = false;
         }
     }
 }
@@ -148,16 +821,205 @@ impl Default for MarkdownParser {
     }
 }
 
+/// Finds the line a byte offset falls on
+struct LineIndex {
+    /// Byte offset at which each line starts
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(text: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { starts }
+    }
+
+    /// Zero-based line of `offset`
+    fn line_of(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset) - 1
+    }
+}
+
+/// Reference for an image link: remote for URLs, local for archive paths
+///
+/// Local references carry no hash until the file is found (see
+/// [`crate::zip::ZipProcessor`]).
+pub(crate) fn image_ref(url: &str) -> ImageRef {
+    if url.contains("://") || url.starts_with("//") || url.starts_with("data:") {
+        ImageRef::Remote { url: url.to_string(), alt: None }
+    } else {
+        ImageRef::Local {
+            hash: String::new(),
+            original_path: url.to_string(),
+            stored_path: None,
+            alt: None,
+        }
+    }
+}
+
 /// Convenience function to parse Markdown and get questions
 pub fn parse_markdown(markdown: &str) -> Result<Vec<Question>> {
-    let mut parser = MarkdownParser::new();
+    parse_markdown_with_options(markdown, &ParserOptions::default())
+}
+
+/// Parse Markdown with custom parser options
+pub fn parse_markdown_with_options(markdown: &str, options: &ParserOptions) -> Result<Vec<Question>> {
+    let mut parser = MarkdownParser::with_options(options.clone());
+    parser.parse(markdown)?;
+    Ok(std::mem::take(&mut parser.questions))
+}
+
+/// Split Markdown into runs of whole questions, each at least `min_bytes` long
+///
+/// Blocks start at question boundaries found by a line scan rather than a
+/// full parse: headings that start a question and follow a blank line, or
+/// with `numbered_questions` also numbered lines. Fenced code is never
+/// split. The blocks are consecutive slices of `markdown`.
+pub fn split_question_blocks<'a>(markdown: &'a str, options: &ParserOptions, min_bytes: usize) -> Vec<&'a str> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut fence: Option<(char, usize)> = None;
+    let mut after_blank = true;
+    for line in markdown.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+        let run = |c: char| trimmed.chars().take_while(|&d| d == c).count();
+        let fence_marker = ['`', '~'].into_iter().find(|&c| indent <= 3 && run(c) >= 3);
+
+        if fence.is_none() && offset - start >= min_bytes && is_block_boundary(line, after_blank, options) {
+            blocks.push(&markdown[start..offset]);
+            start = offset;
+        }
+
+        match (fence, fence_marker) {
+            (None, Some(c)) => fence = Some((c, run(c))),
+            (Some((c, len)), Some(d)) if c == d && run(c) >= len && trimmed[run(c)..].trim().is_empty() => fence = None,
+            _ => {}
+        }
+        after_blank = trimmed.is_empty();
+        offset += line.len();
+    }
+    if start < markdown.len() || blocks.is_empty() {
+        blocks.push(&markdown[start..]);
+    }
+    blocks
+}
+
+/// Whether the parser would start a new question at `line`
+fn is_block_boundary(line: &str, after_blank: bool, options: &ParserOptions) -> bool {
+    // Numbered lines become level 3 headings, preceded by a blank line
+    if options.numbered_questions && options.question_heading_level >= 3 && split_question_number(line).is_some() {
+        return true;
+    }
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    after_blank
+        && line.len() - trimmed.len() <= 3
+        && (1..=6).contains(&level)
+        && level as u32 <= options.question_heading_level
+        && trimmed[level..].starts_with([' ', '\t', '\r', '\n'])
+}
+
+/// Parse a large Markdown file as blocks in parallel on the current Rayon pool
+///
+/// The file is cut with [`split_question_blocks`] and the questions of each
+/// block are returned in document order, as [`parse_markdown_with_options`]
+/// would. Questions before a block's first section title take the section
+/// the previous block ended in. Content that a block leaves without a stem
+/// would have joined the next block's first question, so the file is then
+/// parsed whole.
+#[cfg(feature = "parallel")]
+pub fn parse_markdown_parallel(markdown: &str, options: &ParserOptions, min_block_bytes: usize) -> Result<Vec<Question>> {
+    use rayon::prelude::*;
+
+    let blocks = split_question_blocks(markdown, options, min_block_bytes);
+    if blocks.len() < 2 {
+        return parse_markdown_with_options(markdown, options);
+    }
+
+    let parsed = blocks
+        .par_iter()
+        .map(|block| {
+            let mut parser = MarkdownParser::with_options(options.clone());
+            parser.parse(block)?;
+            let dangling = parser.has_dangling_content();
+            Ok((std::mem::take(&mut parser.questions), parser.section, dangling))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if parsed[..parsed.len() - 1].iter().any(|(_, _, dangling)| *dangling) {
+        return parse_markdown_with_options(markdown, options);
+    }
+    let mut questions = Vec::new();
+    let mut section: Option<String> = None;
+    for (mut block, last_section, _) in parsed {
+        for question in block.iter_mut().take_while(|q| q.section.is_none()) {
+            enter_section(question, &section);
+        }
+        questions.append(&mut block);
+        if last_section.is_some() {
+            section = last_section;
+        }
+    }
+    Ok(questions)
+}
+
+/// Parse Markdown, pairing each question with the lines it came from
+pub fn parse_markdown_with_spans(markdown: &str, options: &ParserOptions) -> Result<Vec<(Question, SourceSpan)>> {
+    let mut parser = MarkdownParser::with_options(options.clone());
     parser.parse(markdown)?;
-    Ok(parser.questions.drain(..).collect())
+    let spans = std::mem::take(&mut parser.spans);
+    Ok(std::mem::take(&mut parser.questions).into_iter().zip(spans).collect())
+}
+
+/// Escape characters Markdown would read as inline syntax
+///
+/// For text converted from other formats (Word, PDF), which should reach the
+/// parser as written.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '$') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Convert plain text to Markdown that reads back as the same text
+///
+/// Inline syntax is escaped everywhere and block syntax (`#`, `>`, `-`, `+`,
+/// `|`) at the start of a line; line breaks are kept.
+pub fn text_to_markdown(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let escaped = escape_markdown(line);
+            match escaped.chars().next() {
+                Some('#' | '>' | '-' | '+' | '|') => format!("\\{}", escaped),
+                _ => escaped,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse a plain-text question file
+///
+/// Text files have no headings or lists, so questions start at their
+/// numbers (`1.`, `2、`) and options at their letters, as with the
+/// `generic-numbered` preset; the keyword tables come from `options`.
+pub fn parse_text_with_options(text: &str, options: &ParserOptions) -> Result<Vec<Question>> {
+    let options = options.clone().with_numbered_questions(true).with_inline_options(true);
+    parse_markdown_with_options(&text_to_markdown(text), &options)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::QuestionType;
 
     #[test]
     fn test_parse_simple_question() {
@@ -168,4 +1030,316 @@ mod tests {
         assert_eq!(questions[0].stem, "What is 2+2?");
         assert_eq!(questions[0].options.len(), 3);
     }
+
+    #[test]
+    fn test_parse_answer_and_analysis() {
+        let markdown = "# 1+1=?\n\n* A. 1\n* B. 2\n\n答案：B\n\n解析：基本加法。";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions[0].answer.as_deref(), Some("B"));
+        assert_eq!(questions[0].analysis.as_deref(), Some("基本加法。"));
+    }
+
+    #[test]
+    fn test_reused_buffers_keep_paragraphs_apart() {
+        let options = ParserOptions::default().with_inline_options(true);
+        let markdown = "# First?\n\nA．1\nB．2\n答案：A\n\n解析：one\n\n# Second?\n\nA．3\nB．4\n\n答案：B";
+        let questions = parse_markdown_with_options(markdown, &options).unwrap();
+        assert_eq!(questions.len(), 2);
+        let contents: Vec<_> = questions[1].options.iter().map(|o| o.content.as_str()).collect();
+        assert_eq!(contents, ["A．3", "B．4"]);
+        assert_eq!(questions[0].answer.as_deref(), Some("A"));
+        assert_eq!(questions[0].analysis.as_deref(), Some("one"));
+        assert_eq!(questions[1].answer.as_deref(), Some("B"));
+        assert!(questions[1].analysis.is_none());
+    }
+
+    #[test]
+    fn test_options_in_stem_line() {
+        assert_eq!(
+            split_stem_options("最小的数是（　　）A．－2 B．0 C．1"),
+            Some((27, vec!["A．－2".to_string(), "B．0".to_string(), "C．1".to_string()]))
+        );
+        // One letter alone, a word ending in "A", or nothing before the run
+        assert_eq!(split_stem_options("Take vitamin A. Then rest."), None);
+        assert_eq!(split_stem_options("Is DNA. B. safe"), None);
+        assert_eq!(split_stem_options("A．1 B．2"), None);
+
+        let numbered = ParserOptions::default().with_numbered_questions(true).with_inline_options(true);
+        let text = "1. 下列哪个是质数？ A. 4 B. 6 C. 7 D. 9\n答案：C\n2. 已有选项的题 A. x B. y\nA. 1\nB. 2\n";
+        let questions = parse_markdown_with_options(text, &numbered).unwrap();
+        assert_eq!(questions[0].stem, "下列哪个是质数？");
+        let contents: Vec<_> = questions[0].options.iter().map(|o| (o.sort_order, o.content.as_str())).collect();
+        assert_eq!(contents, [(0, "A. 4"), (1, "B. 6"), (2, "C. 7"), (3, "D. 9")]);
+        assert_eq!(questions[0].answer.as_deref(), Some("C"));
+        // Stems of questions that have options are left alone
+        assert_eq!(questions[1].stem, "已有选项的题 A. x B. y");
+        assert_eq!(questions[1].options.len(), 2);
+    }
+
+    #[test]
+    fn test_stem_options_need_inline_options() {
+        // The default options read options from lists only, so a lettered
+        // run stays part of the stem
+        let questions = parse_markdown("# 1+1=? A. 1 B. 2\n\n答案：B").unwrap();
+        assert_eq!(questions[0].stem, "1+1=? A. 1 B. 2");
+        assert!(questions[0].options.is_empty());
+        assert_eq!(questions[0].answer.as_deref(), Some("B"));
+
+        let questions = parse_markdown("1+1=? A. 1 B. 2\n\n* A. 1\n* B. 2").unwrap();
+        assert_eq!(questions[0].stem, "1+1=? A. 1 B. 2");
+        assert_eq!(questions[0].options.len(), 2);
+
+        let inline = ParserOptions::default().with_inline_options(true);
+        let questions = parse_markdown_with_options("# 1+1=? A. 1 B. 2\n\n答案：B", &inline).unwrap();
+        assert_eq!(questions[0].stem, "1+1=?");
+        assert_eq!(questions[0].options.len(), 2);
+    }
+
+    #[test]
+    fn test_ordered_and_lettered_options() {
+        let contents = |q: &Question| q.options.iter().map(|o| (o.sort_order, o.content.clone())).collect::<Vec<_>>();
+
+        // Numeric answers count from the list's first number
+        let markdown = "# Which is prime?\n\n0. 4\n1. 7\n\n答案：1\n\n# Which are even?\n\n3. 2\n4. 3\n5. 4\n\n答案：3、5";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(contents(&questions[0]), [(0, "4".to_string()), (1, "7".to_string())]);
+        assert_eq!(questions[0].answer.as_deref(), Some("B"));
+        assert_eq!(questions[1].answer.as_deref(), Some("AC"));
+
+        // Markdown has no lettered lists, but lines lettered in order are one
+        let markdown = "# 1+1=?\n\na) 1\nb) 2\n\n答案：b\n\n# 2+2=?\n\nA) 4\nC) 5";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(contents(&questions[0]), [(0, "a) 1".to_string()), (1, "b) 2".to_string())]);
+        assert_eq!(questions[0].answer.as_deref(), Some("b"));
+        assert!(questions[1].options.is_empty());
+
+        // Nested lists and loose items stay inside their option
+        let markdown = "# Pick one\n\n* A. first\n  * detail\n\n* B. second\n\n  more\n";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(contents(&questions[0]), [(0, "A. first detail".to_string()), (1, "B. second more".to_string())]);
+        assert!(questions[0].analysis.is_none());
+    }
+
+    #[test]
+    fn test_lists_after_answer_are_analysis() {
+        let markdown = "# 2x = 4，求 x\n\n* A. 1\n* B. 2\n\n答案：B\n\n解析：分两步\n\n1. 两边除以 2\n2. 得 x = 2\n\n* 另解：代入\n\na) 代入 1 不成立\nb) 代入 2 成立\n";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions[0].options.len(), 2);
+        assert_eq!(
+            questions[0].analysis.as_deref(),
+            Some("分两步\n1. 两边除以 2\n2. 得 x = 2\n- 另解：代入\na) 代入 1 不成立\nb) 代入 2 成立")
+        );
+
+        // A plain paragraph after the stem still leaves the list as options
+        let questions = parse_markdown("# Which?\n\nChoose one.\n\n1. x\n2. y").unwrap();
+        assert_eq!(questions[0].options.len(), 2);
+    }
+
+    #[test]
+    fn test_numbered_question_after_answer_is_not_analysis() {
+        let markdown = "1. 1+1=?\n\nA. 1\nB. 2\n\n答案：B\n\n2. 2+2=?\n\nA. 4\nB. 5\n\n答案：A\n\n3. 3+3=?\n\n答案：6\n";
+        // Default options read the numbered stems as list items, but never
+        // fold the next question into the previous answer's analysis
+        let questions = parse_markdown(&format!("# 单选题\n\n{}", markdown)).unwrap();
+        assert!(questions[0].analysis.is_none());
+        let contents: Vec<_> = questions[0].options.iter().map(|o| o.content.as_str()).collect();
+        assert!(contents.contains(&"2+2=?") && contents.contains(&"3+3=?"));
+
+        let numbered = ParserOptions::default().with_numbered_questions(true);
+        let questions = parse_markdown_with_options(markdown, &numbered).unwrap();
+        let parsed: Vec<_> = questions
+            .iter()
+            .map(|q| (q.stem.as_str(), q.options.len(), q.answer.as_deref(), q.analysis.as_deref()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [("1+1=?", 2, Some("B"), None), ("2+2=?", 2, Some("A"), None), ("3+3=?", 0, Some("6"), None)]
+        );
+    }
+
+    #[test]
+    fn test_split_question_blocks() {
+        let options = ParserOptions::default();
+        let markdown = "# Q1?\n\n* A. 1\n\n```\n\n# not a question\n```\n\n# Q2?\n#### detail\n\n# Q3?\n";
+        let blocks = split_question_blocks(markdown, &options, 1);
+        assert_eq!(blocks, ["# Q1?\n\n* A. 1\n\n```\n\n# not a question\n```\n\n", "# Q2?\n#### detail\n\n", "# Q3?\n"]);
+        assert_eq!(blocks.concat(), markdown);
+        assert_eq!(split_question_blocks(markdown, &options, 1 << 20), [markdown]);
+
+        let numbered = ParserOptions::default().with_numbered_questions(true);
+        let blocks = split_question_blocks("1. First?\nA. x\n2. Second?\n", &numbered, 1);
+        assert_eq!(blocks, ["1. First?\nA. x\n", "2. Second?\n"]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parse_markdown_parallel_matches_sequential() {
+        use crate::sample::{generate_markdown, SampleConfig};
+        use crate::testing::snapshot;
+
+        let markdown = generate_markdown(&SampleConfig::default().with_count(200).with_latex(true).with_images(true));
+        for options in [ParserOptions::default(), ParserOptions::default().with_numbered_questions(true)] {
+            assert!(split_question_blocks(&markdown, &options, 512).len() > 10);
+            let sequential = parse_markdown_with_options(&markdown, &options).unwrap();
+            let parallel = parse_markdown_parallel(&markdown, &options, 512).unwrap();
+            assert_eq!(snapshot(&parallel), snapshot(&sequential));
+        }
+
+        // Options under an empty heading belong to the next question
+        let markdown = "# \n\n* A. 1\n* B. 2\n\n# Which?\n";
+        let questions = parse_markdown_parallel(markdown, &ParserOptions::default(), 1).unwrap();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].options.len(), 2);
+
+        // Sections carry over into later blocks
+        let markdown = "# Q0?\n\n# 一、单选题\n\n# Q1?\n\n# Q2?\n\n# 二、判断题\n\n# Q3?\n";
+        let questions = parse_markdown_parallel(markdown, &ParserOptions::default(), 1).unwrap();
+        let sections: Vec<_> = questions.iter().map(|q| q.section.as_deref()).collect();
+        assert_eq!(sections, [None, Some("一、单选题"), Some("一、单选题"), Some("二、判断题")]);
+    }
+
+    #[test]
+    fn test_sections_and_numbers() {
+        let numbered = ParserOptions::default().with_numbered_questions(true).with_inline_options(true);
+        let text = "一、单选题\n1. 1+1=?\nA. 1\nB. 2\n２．2+2=?\nA. 4\n二、判断题\n3、地球是圆的\n";
+        let questions = parse_markdown_with_options(text, &numbered).unwrap();
+        let parsed: Vec<_> = questions
+            .iter()
+            .map(|q| (q.section.as_deref(), q.number.as_deref(), q.stem.as_str()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                (Some("一、单选题"), Some("1"), "1+1=?"),
+                (Some("一、单选题"), Some("２"), "2+2=?"),
+                (Some("二、判断题"), Some("3"), "地球是圆的"),
+            ]
+        );
+
+        // Section headings are not questions; heading stems keep their number
+        let markdown = "## 一、单选题\n\n### 1. Which?\n\n* A. x\n* B. y\n";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].section.as_deref(), Some("一、单选题"));
+        assert_eq!(questions[0].number.as_deref(), Some("1"));
+        assert_eq!(questions[0].stem, "1. Which?");
+    }
+
+    #[test]
+    fn test_section_types() {
+        let numbered = ParserOptions::default().with_numbered_questions(true);
+        let text = "1. 概述题\n二、判断题\n2. 地球是圆的\n3. 水往低处流\n三、材料\n4. 阅读下文\n";
+        let questions = parse_markdown_with_options(text, &numbered).unwrap();
+        let types: Vec<_> = questions.iter().map(|q| (q.qtype, q.type_source)).collect();
+        assert_eq!(
+            types,
+            [
+                (QuestionType::Subjective, None),
+                (QuestionType::TrueFalse, Some(TypeSource::Section)),
+                (QuestionType::TrueFalse, Some(TypeSource::Section)),
+                // A section that names no type ends the previous one
+                (QuestionType::Subjective, None),
+            ]
+        );
+
+        // With numbered questions, unnumbered headings title sections
+        let markdown = "# Sample Exam\n\n## True/False\n\n1. Paris is in France.\n\nAnswer: True\n";
+        let questions = parse_markdown_with_options(markdown, &numbered).unwrap();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].section.as_deref(), Some("True/False"));
+        assert_eq!(questions[0].qtype, QuestionType::TrueFalse);
+    }
+
+    #[test]
+    fn test_merge_stem_fragments() {
+        let markdown = "下列关于排序算法的说法\n\n中正确的是？\n\n* A. 快排稳定\n* B. 归并稳定";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions[0].stem, "下列关于排序算法的说法中正确的是？");
+        assert!(questions[0].analysis.is_none());
+
+        let markdown = "Which of the following sorting\n\nalgorithms is stable?";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions[0].stem, "Which of the following sorting algorithms is stable?");
+    }
+
+    #[test]
+    fn test_merge_stem_fragments_respects_punctuation_and_toggle() {
+        let markdown = "What is 2+2?\n\nthis paragraph is analysis";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions[0].stem, "What is 2+2?");
+        assert_eq!(questions[0].analysis.as_deref(), Some("this paragraph is analysis"));
+
+        let markdown = "Which of the following sorting\n\nalgorithms is stable?";
+        let options = ParserOptions::default().with_merge_stem_fragments(false);
+        let questions = parse_markdown_with_options(markdown, &options).unwrap();
+        assert_eq!(questions[0].stem, "Which of the following sorting");
+    }
+
+    #[test]
+    fn test_parse_rtl_question() {
+        let markdown = "# ما هو ناتج $2+2$؟\n\n* أ. ٣\n* ب. ٤";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].text_direction, crate::models::TextDirection::Rtl);
+        assert_eq!(questions[0].stem, "ما هو ناتج $2+2$؟");
+    }
+
+    #[test]
+    fn test_parse_image_references() {
+        let markdown = "# Which shape is shown?\n\n![diagram](images/q1.png)\n\n\
+* A. Circle\n* B. Square\n\n# Name this landmark\n\n![photo](https://example.com/a.jpg)";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions.len(), 2);
+        match &questions[0].images[..] {
+            [ImageRef::Local { hash, original_path, stored_path, .. }] => {
+                assert!(hash.is_empty());
+                assert_eq!(original_path, "images/q1.png");
+                assert!(stored_path.is_none());
+            }
+            other => panic!("unexpected images: {:?}", other),
+        }
+        assert!(matches!(&questions[1].images[..], [ImageRef::Remote { url, .. }] if url == "https://example.com/a.jpg"));
+        assert_eq!(questions[0].images[0].alt(), Some("diagram"));
+        assert_eq!(questions[1].images[0].alt(), Some("photo"));
+        assert_eq!(parse_markdown("# Q\n\n![](a.png)").unwrap()[0].images[0].alt(), None);
+        // Alt text is not question text
+        assert!(questions.iter().all(|q| q.analysis.is_none()));
+    }
+
+    #[test]
+    fn test_display_formula_joins_stem() {
+        let markdown = "# Evaluate the integral:\n\n$$\n\\int_0^1 x \\mathrm{d}x\n$$\n\nAnswer: 1/2\n\nExplanation: Area of a triangle.";
+        let questions = parse_markdown(markdown).unwrap();
+        assert_eq!(questions[0].stem, "Evaluate the integral:\n$$ \\int_0^1 x \\mathrm{d}x $$");
+        assert_eq!(questions[0].analysis.as_deref(), Some("Area of a triangle."));
+    }
+
+    #[test]
+    fn test_source_spans() {
+        let markdown = "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B\n\n# Name a prime\n";
+        let parsed = parse_markdown_with_spans(markdown, &ParserOptions::default()).unwrap();
+        let spans: Vec<_> = parsed.iter().map(|(_, span)| (span.start_line, span.end_line)).collect();
+        assert_eq!(spans, [(1, 6), (8, 8)]);
+
+        // Numbered questions are rewritten before parsing; spans still point
+        // at the input, including lines folded into a stem
+        let text = "一、选择题\n1. Which is\nthe largest?\nA. 1\nB. 2\n\n2. Name a prime\n";
+        let options = ParserOptions::default().with_numbered_questions(true).with_inline_options(true);
+        let parsed = parse_markdown_with_spans(text, &options).unwrap();
+        assert_eq!(parsed[0].0.stem, "Which is the largest?");
+        let spans: Vec<_> = parsed.iter().map(|(_, span)| (span.start_line, span.end_line)).collect();
+        assert_eq!(spans, [(2, 5), (7, 7)]);
+    }
+
+    #[test]
+    fn test_parse_plain_text() {
+        let text = "1. Which is 2*3?\nA. 5\nB. 6\nAnswer: B\n\n2. # is a comment marker in shell scripts.\nAnswer: True\n";
+        let questions = parse_text_with_options(text, &ParserOptions::default()).unwrap();
+        assert_eq!(questions.len(), 2);
+        assert_eq!(questions[0].stem, "Which is 2*3?");
+        assert_eq!(questions[0].options.len(), 2);
+        assert_eq!(questions[0].answer.as_deref(), Some("B"));
+        assert_eq!(questions[1].stem, "# is a comment marker in shell scripts.");
+        assert_eq!(text_to_markdown("# 1\n- a_b"), "\\# 1\n\\- a\\_b");
+    }
 }
@@ -0,0 +1,479 @@
+//! PDF text extraction
+//!
+//! [`PdfExtractor`] reads the text layer of digitally generated PDFs (exam
+//! papers exported from Word or LaTeX) and hands it to the Markdown parser
+//! as plain text, so PDF question banks go through the same pipeline as
+//! Markdown files.
+//!
+//! Text is put in reading order page by page: spans are grouped into lines
+//! by their baseline, lines are read top to bottom and spans left to right,
+//! and a wide vertical gap starts a new paragraph. Multi-column layouts are
+//! read across the columns. Pages with no text but an image are reported as
+//! scans that need OCR; their content is not recovered.
+
+use crate::parser::{parse_markdown_with_options, text_to_markdown, ParserOptions};
+use crate::presets;
+use anyhow::{anyhow, Context, Result};
+use lopdf::content::Content;
+use lopdf::{Document, Encoding, Object};
+use std::collections::BTreeMap;
+
+/// Text extracted from one page
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfPage {
+    /// Page number, starting at 1
+    pub number: u32,
+    /// Text in reading order; paragraphs are separated by blank lines
+    pub text: String,
+}
+
+/// Text extracted from a PDF
+#[derive(Debug, Clone, Default)]
+pub struct PdfDocument {
+    /// Pages with text, in order
+    pub pages: Vec<PdfPage>,
+    /// Pages that need OCR and text that could not be decoded
+    pub warnings: Vec<String>,
+}
+
+impl PdfDocument {
+    /// The text of all pages as Markdown, with Markdown syntax escaped
+    pub fn markdown(&self) -> String {
+        self.pages
+            .iter()
+            .map(|page| text_to_markdown(&page.text))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Parser options for extracted text
+///
+/// PDF exam papers number their questions and options as plain text, so this
+/// is the `generic-numbered` preset.
+pub fn default_options() -> ParserOptions {
+    presets::preset("generic-numbered").unwrap_or_default()
+}
+
+/// Extracts text from PDFs and parses it into questions
+#[derive(Debug, Clone)]
+pub struct PdfExtractor {
+    options: ParserOptions,
+}
+
+impl PdfExtractor {
+    /// Create an extractor using [`default_options`]
+    pub fn new() -> Self {
+        Self {
+            options: default_options(),
+        }
+    }
+
+    /// Use these parser options for the extracted text
+    pub fn with_options(options: ParserOptions) -> Self {
+        Self { options }
+    }
+
+    /// Extract the text of every page
+    pub fn extract(&self, data: &[u8]) -> Result<PdfDocument> {
+        extract(data)
+    }
+
+    /// Extract the text and parse it into questions
+    pub fn parse(&self, data: &[u8]) -> Result<Vec<crate::models::Question>> {
+        let document = extract(data)?;
+        Ok(parse_markdown_with_options(&document.markdown(), &self.options)?)
+    }
+}
+
+impl Default for PdfExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extract the text of every page of a PDF
+pub fn extract(data: &[u8]) -> Result<PdfDocument> {
+    let document = Document::load_mem(data).context("not a PDF document")?;
+    if document.is_encrypted() {
+        return Err(anyhow!("encrypted PDFs are not supported"));
+    }
+
+    let mut result = PdfDocument::default();
+    for (number, page_id) in document.get_pages() {
+        let spans = match page_spans(&document, page_id, number, &mut result.warnings) {
+            Ok(spans) => spans,
+            Err(e) => {
+                result.warnings.push(format!("page {}: text could not be read: {}", number, e));
+                continue;
+            }
+        };
+        let text = reading_order(spans);
+        if !text.trim().is_empty() {
+            result.pages.push(PdfPage { number, text });
+        } else if has_images(&document, page_id) {
+            result.warnings.push(format!(
+                "page {}: no text layer, the page looks like a scanned image and needs OCR",
+                number
+            ));
+        }
+    }
+    Ok(result)
+}
+
+/// Whether a page draws any image
+fn has_images(document: &Document, page_id: lopdf::ObjectId) -> bool {
+    let Ok(content) = document.get_and_decode_page_content(page_id) else {
+        return false;
+    };
+    // Image XObjects in the page's resources, including inherited ones
+    let mut images = Vec::new();
+    if let Ok((own, inherited)) = document.get_page_resources(page_id) {
+        let inherited = inherited.into_iter().filter_map(|id| document.get_dictionary(id).ok());
+        for resources in own.into_iter().chain(inherited) {
+            let Ok(xobjects) = resources.get_deref(b"XObject", document).and_then(Object::as_dict) else {
+                continue;
+            };
+            for (name, xobject) in xobjects.iter() {
+                let subtype = document
+                    .dereference(xobject)
+                    .and_then(|(_, object)| object.as_stream())
+                    .and_then(|stream| stream.dict.get(b"Subtype"))
+                    .and_then(Object::as_name);
+                if subtype.is_ok_and(|subtype| subtype == b"Image") {
+                    images.push(name.clone());
+                }
+            }
+        }
+    }
+
+    content.operations.iter().any(|op| match op.operator.as_str() {
+        // Inline images live in the content stream rather than the resources
+        "BI" => true,
+        "Do" => op.operands.first().and_then(|name| name.as_name().ok()).is_some_and(|name| images.iter().any(|image| image == name)),
+        _ => false,
+    })
+}
+
+/// A run of text drawn at one position
+#[derive(Debug, Clone)]
+struct Span {
+    x: f32,
+    y: f32,
+    /// Rendered font size
+    size: f32,
+    /// Estimated end of the text along the baseline
+    end: f32,
+    text: String,
+}
+
+/// Affine transform `[a b c d e f]`, as in PDF content streams
+#[derive(Debug, Clone, Copy)]
+struct Matrix([f32; 6]);
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn translate(x: f32, y: f32) -> Self {
+        Matrix([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    /// `self` applied first, then `other`
+    fn then(self, other: Matrix) -> Self {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+        Matrix([
+            a * a2 + b * c2,
+            a * b2 + b * d2,
+            c * a2 + d * c2,
+            c * b2 + d * d2,
+            e * a2 + f * c2 + e2,
+            e * b2 + f * d2 + f2,
+        ])
+    }
+}
+
+/// Numeric operands of an operation; `None` if any is not a number
+fn numbers(operands: &[Object]) -> Option<Vec<f32>> {
+    operands.iter().map(|o| o.as_float().ok()).collect()
+}
+
+/// Spans drawn by a page's content stream
+///
+/// Widths are estimated (half an em per ASCII character, one em otherwise)
+/// rather than read from font metrics; they only decide word spacing and the
+/// order of spans that share a position.
+fn page_spans(
+    document: &Document,
+    page_id: lopdf::ObjectId,
+    number: u32,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Span>> {
+    let mut encodings: BTreeMap<Vec<u8>, Option<Encoding>> = BTreeMap::new();
+    for (name, font) in document.get_page_fonts(page_id)? {
+        let encoding = font.get_font_encoding(document).ok();
+        if encoding.is_none() {
+            warnings.push(format!(
+                "page {}: font {} has no usable encoding; its text is skipped",
+                number,
+                String::from_utf8_lossy(&name)
+            ));
+        }
+        encodings.insert(name, encoding);
+    }
+    let content = Content::decode(&document.get_page_content(page_id)?)?;
+
+    let mut spans = Vec::new();
+    let mut ctm = Matrix::IDENTITY;
+    let mut stack = Vec::new();
+    let (mut tm, mut tlm) = (Matrix::IDENTITY, Matrix::IDENTITY);
+    let mut leading = 0.0;
+    let mut font_size = 0.0;
+    let mut encoding: Option<&Encoding> = None;
+
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        match operation.operator.as_str() {
+            "q" => stack.push(ctm),
+            "Q" => ctm = stack.pop().unwrap_or(Matrix::IDENTITY),
+            "cm" => {
+                if let Some([a, b, c, d, e, f]) = numbers(operands).and_then(|n| <[f32; 6]>::try_from(n).ok()) {
+                    ctm = Matrix([a, b, c, d, e, f]).then(ctm);
+                }
+            }
+            "BT" => {
+                tm = Matrix::IDENTITY;
+                tlm = Matrix::IDENTITY;
+            }
+            "Tf" => {
+                encoding = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| encodings.get(name))
+                    .and_then(Option::as_ref);
+                font_size = operands.get(1).and_then(|s| s.as_float().ok()).unwrap_or(0.0);
+            }
+            "TL" => leading = operands.first().and_then(|l| l.as_float().ok()).unwrap_or(0.0),
+            "Td" | "TD" => {
+                if let Some([x, y]) = numbers(operands).and_then(|n| <[f32; 2]>::try_from(n).ok()) {
+                    if operation.operator == "TD" {
+                        leading = -y;
+                    }
+                    tlm = Matrix::translate(x, y).then(tlm);
+                    tm = tlm;
+                }
+            }
+            "Tm" => {
+                if let Some([a, b, c, d, e, f]) = numbers(operands).and_then(|n| <[f32; 6]>::try_from(n).ok()) {
+                    tlm = Matrix([a, b, c, d, e, f]);
+                    tm = tlm;
+                }
+            }
+            "T*" | "'" | "\"" => {
+                tlm = Matrix::translate(0.0, -leading).then(tlm);
+                tm = tlm;
+                if operation.operator != "T*" {
+                    let text = operands.last().map(std::slice::from_ref).unwrap_or_default();
+                    show(text, encoding, font_size, &mut tm, ctm, &mut spans);
+                }
+            }
+            "Tj" | "TJ" => show(operands, encoding, font_size, &mut tm, ctm, &mut spans),
+            _ => {}
+        }
+    }
+    Ok(spans)
+}
+
+/// Record the text of a `Tj`/`TJ` operation and advance the text matrix
+fn show(
+    operands: &[Object],
+    encoding: Option<&Encoding>,
+    font_size: f32,
+    tm: &mut Matrix,
+    ctm: Matrix,
+    spans: &mut Vec<Span>,
+) {
+    let Some(encoding) = encoding else {
+        return;
+    };
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) => {
+                let Ok(text) = Document::decode_text(encoding, bytes) else {
+                    continue;
+                };
+                let advance: f32 = text.chars().map(|c| if c.is_ascii() { 0.5 } else { 1.0 }).sum::<f32>() * font_size;
+                let start = tm.then(ctm);
+                let end = Matrix::translate(advance, 0.0).then(*tm).then(ctm);
+                let size = font_size * start.0[2].hypot(start.0[3]);
+                spans.push(Span {
+                    x: start.0[4],
+                    y: start.0[5],
+                    size: if size > 0.0 { size } else { font_size.abs().max(1.0) },
+                    end: end.0[4],
+                    text,
+                });
+                *tm = Matrix::translate(advance, 0.0).then(*tm);
+            }
+            // Adjustments in TJ arrays, in thousandths of an em; large
+            // negative ones separate words
+            Object::Integer(_) | Object::Real(_) => {
+                let adjustment = operand.as_float().unwrap_or(0.0);
+                let shift = -adjustment / 1000.0 * font_size;
+                *tm = Matrix::translate(shift, 0.0).then(*tm);
+                if adjustment < -200.0 {
+                    if let Some(span) = spans.last_mut() {
+                        span.text.push(' ');
+                    }
+                }
+            }
+            Object::Array(items) => show(items, Some(encoding), font_size, tm, ctm, spans),
+            _ => {}
+        }
+    }
+}
+
+/// Lay spans out as lines and paragraphs in reading order
+fn reading_order(mut spans: Vec<Span>) -> String {
+    spans.retain(|span| !span.text.trim().is_empty());
+    // Top to bottom; stable, so spans at one height keep stream order
+    spans.sort_by(|a, b| b.y.total_cmp(&a.y));
+
+    let mut lines: Vec<(f32, f32, Vec<Span>)> = Vec::new();
+    for span in spans {
+        match lines.last_mut() {
+            Some((y, size, line)) if (*y - span.y).abs() <= size.max(span.size) * 0.5 => {
+                *size = size.max(span.size);
+                line.push(span);
+            }
+            _ => lines.push((span.y, span.size, vec![span])),
+        }
+    }
+
+    let mut text = String::new();
+    let mut previous: Option<(f32, f32)> = None;
+    for (y, size, mut line) in lines {
+        line.sort_by(|a, b| a.x.total_cmp(&b.x));
+        let mut joined = String::new();
+        let mut end: Option<f32> = None;
+        for span in line {
+            if end.is_some_and(|end| span.x - end > span.size * 0.2) && !joined.ends_with(' ') {
+                joined.push(' ');
+            }
+            end = Some(span.end);
+            joined.push_str(&span.text);
+        }
+
+        if let Some((previous_y, previous_size)) = previous {
+            text.push('\n');
+            if previous_y - y > previous_size.max(size) * 1.8 {
+                text.push('\n');
+            }
+        }
+        text.push_str(joined.trim());
+        previous = Some((y, size));
+    }
+    text
+}
+
+/// Build a one-font PDF with a page per content stream
+#[cfg(test)]
+pub(crate) fn build_pdf(pages: &[&str]) -> Vec<u8> {
+    use lopdf::{dictionary, Stream};
+
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let font_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let image_id = document.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 1,
+            "Height" => 1,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        },
+        vec![0],
+    ));
+    let resources_id = document.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+        "XObject" => dictionary! { "Im1" => image_id },
+    });
+
+    let kids: Vec<Object> = pages
+        .iter()
+        .map(|content| {
+            let content_id = document.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+            document
+                .add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                })
+                .into()
+        })
+        .collect();
+    document.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        }),
+    );
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    document.trailer.set("Root", catalog_id);
+
+    let mut data = Vec::new();
+    document.save_to(&mut data).unwrap();
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_in_reading_order() {
+        // Drawn out of order: the answer first, option B before option A
+        let page = "BT /F1 12 Tf 72 640 Td (Answer: B) Tj ET \
+                    BT /F1 12 Tf 72 700 Td (1. Which is prime?) Tj ET \
+                    BT /F1 12 Tf 150 685 Td (B. 7) Tj ET \
+                    BT /F1 12 Tf 72 685 Td (A. 4) Tj ET \
+                    BT /F1 12 Tf 72 600 Td [(2. Name a) -250 (#) -250 (sign) ] TJ 0 -14 TD (A. *) Tj T* (B. +) Tj ET";
+        let scanned = "q 500 0 0 700 50 50 cm /Im1 Do Q";
+        let data = build_pdf(&[page, scanned, ""]);
+
+        let document = PdfExtractor::new().extract(&data).unwrap();
+        assert_eq!(document.pages.len(), 1);
+        assert_eq!(
+            document.pages[0].text,
+            "1. Which is prime?\nA. 4 B. 7\n\nAnswer: B\n\n2. Name a # sign\nA. *\nB. +"
+        );
+        assert_eq!(
+            document.warnings,
+            ["page 2: no text layer, the page looks like a scanned image and needs OCR"]
+        );
+        assert!(document.markdown().ends_with("2. Name a # sign\nA. \\*\nB. +"));
+
+        let questions = PdfExtractor::new().parse(&data).unwrap();
+        assert_eq!(questions.len(), 2);
+        assert_eq!(questions[0].stem, "Which is prime?");
+        assert_eq!(questions[0].options.len(), 2);
+        assert_eq!(questions[0].answer.as_deref(), Some("B"));
+        assert_eq!(questions[1].options.len(), 2);
+
+        assert!(extract(b"not a pdf").is_err());
+        assert_eq!(text_to_markdown("- 2 is negative"), "\\- 2 is negative");
+    }
+}
@@ -0,0 +1,133 @@
+//! Named parser presets for common export formats
+//!
+//! Question banks exported from the major Chinese vendors each follow their
+//! own layout: numbered stems, options written as plain lines, and answer and
+//! analysis markers such as `【答案】` or `正确答案：`. A preset bundles the
+//! [`ParserOptions`] that parse one of these layouts, so an import only has
+//! to name the format instead of tuning the parser by hand.
+
+use crate::parser::ParserOptions;
+use anyhow::{bail, Result};
+
+/// Names accepted by [`preset`]
+pub const PRESET_NAMES: &[&str] = &["default", "xuekewang-export", "kaoshibao", "generic-numbered"];
+
+/// Look up a parser preset by name (case-insensitive)
+pub fn preset(name: &str) -> Result<ParserOptions> {
+    let options = match name.trim().to_lowercase().as_str() {
+        "default" => ParserOptions::default(),
+        // 学科网 Word/Markdown export: "1．题干" with 【答案】/【解析】 blocks
+        "xuekewang-export" | "xuekewang" | "zxxk" => numbered()
+            .with_answer_prefixes(["【答案】", "答案："])
+            .with_analysis_prefixes(["【解析】", "【分析】", "【详解】", "【点睛】"]),
+        // 考试宝 export: "1、题干" with 正确答案：/答案解析：
+        "kaoshibao" => numbered()
+            .with_answer_prefixes(["正确答案：", "正确答案:", "答案：", "答案:"])
+            .with_analysis_prefixes(["答案解析：", "试题解析：", "解析：", "答案解析:", "解析:"]),
+        "generic-numbered" => numbered(),
+        other => bail!(
+            "Unknown parser preset '{}' (expected one of: {})",
+            other,
+            PRESET_NAMES.join(", ")
+        ),
+    };
+    Ok(options)
+}
+
+/// Numbered questions with inline options and the default keyword tables
+fn numbered() -> ParserOptions {
+    ParserOptions::default()
+        .with_numbered_questions(true)
+        .with_inline_options(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_markdown_with_options;
+
+    fn contents(question: &crate::models::Question) -> Vec<&str> {
+        question.options.iter().map(|o| o.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_xuekewang_export() {
+        let markdown = "一、选择题\n\n\
+1．下列各数中，最小的数是（　　）\n\
+A．－2 B．0 C．1 D．3\n\n\
+【答案】A\n\n\
+【解析】负数小于零和正数，\n\
+故最小的数是－2．\n\n\
+2．计算 3.14 × 2 的结果是（　　）\n\n\
+A．6.28\n\
+B．6.14\n\n\
+【答案】A\n\
+【详解】直接相乘即可．\n";
+
+        let questions = parse_markdown_with_options(markdown, &preset("xuekewang-export").unwrap()).unwrap();
+        assert_eq!(questions.len(), 2);
+
+        assert_eq!(questions[0].stem, "下列各数中，最小的数是（　　）");
+        assert_eq!(contents(&questions[0]), vec!["A．－2", "B．0", "C．1", "D．3"]);
+        assert_eq!(questions[0].answer.as_deref(), Some("A"));
+        assert_eq!(
+            questions[0].analysis.as_deref(),
+            Some("负数小于零和正数，故最小的数是－2．")
+        );
+
+        assert_eq!(questions[1].stem, "计算 3.14 × 2 的结果是（　　）");
+        assert_eq!(contents(&questions[1]), vec!["A．6.28", "B．6.14"]);
+        assert_eq!(questions[1].answer.as_deref(), Some("A"));
+        assert_eq!(questions[1].analysis.as_deref(), Some("直接相乘即可．"));
+    }
+
+    #[test]
+    fn test_kaoshibao() {
+        let markdown = "1、HTTP 默认使用的端口是？\n\
+A、21\n\
+B、80\n\
+C、443\n\
+正确答案：B\n\
+答案解析：HTTP 默认端口为 80。\n\n\
+2、TCP 是面向连接的协议。\n\
+正确答案：正确\n";
+
+        let questions = parse_markdown_with_options(markdown, &preset("kaoshibao").unwrap()).unwrap();
+        assert_eq!(questions.len(), 2);
+
+        assert_eq!(questions[0].stem, "HTTP 默认使用的端口是？");
+        assert_eq!(contents(&questions[0]), vec!["A、21", "B、80", "C、443"]);
+        assert_eq!(questions[0].answer.as_deref(), Some("B"));
+        assert_eq!(questions[0].analysis.as_deref(), Some("HTTP 默认端口为 80。"));
+
+        assert_eq!(questions[1].stem, "TCP 是面向连接的协议。");
+        assert!(questions[1].options.is_empty());
+        assert_eq!(questions[1].answer.as_deref(), Some("正确"));
+    }
+
+    #[test]
+    fn test_generic_numbered() {
+        let markdown = "1) Which planet is largest?\n\
+A) Mars\n\
+B) Jupiter\n\
+Answer: B\n\n\
+2) Water boils at 100 degrees\n\
+Celsius at sea level.\n\
+Answer: True\n";
+
+        let questions = parse_markdown_with_options(markdown, &preset("generic-numbered").unwrap()).unwrap();
+        assert_eq!(questions.len(), 2);
+        assert_eq!(questions[0].stem, "Which planet is largest?");
+        assert_eq!(contents(&questions[0]), vec!["A) Mars", "B) Jupiter"]);
+        assert_eq!(questions[0].answer.as_deref(), Some("B"));
+        assert_eq!(questions[1].stem, "Water boils at 100 degrees Celsius at sea level.");
+        assert_eq!(questions[1].answer.as_deref(), Some("True"));
+    }
+
+    #[test]
+    fn test_unknown_preset() {
+        assert!(preset("Default").is_ok());
+        let err = preset("nope").unwrap_err().to_string();
+        assert!(err.contains("nope") && err.contains("kaoshibao"));
+    }
+}
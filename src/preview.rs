@@ -0,0 +1,165 @@
+//! Live preview of Markdown being edited
+//!
+//! [`preview_markdown`] runs the steps of an import that only look at the
+//! text (parsing, answer and formula checks, classification and validation) and reports
+//! each question with the lines it came from and what an import would say
+//! about it. Nothing touches the repository, so an editor can call it on
+//! every keystroke and highlight problems next to the source.
+
+use crate::answer::{validate_answer, AnswerIssueKind};
+use crate::classifier::{apply_classification, Language};
+use crate::latex::{normalize_question, LatexValidator};
+use crate::models::{ClassificationResult, Question, QuestionType};
+use crate::parser::{parse_markdown_with_spans, ParserOptions, SourceSpan};
+use crate::validate::QuestionValidator;
+use anyhow::Result;
+use serde::Serialize;
+
+/// How serious a [`Diagnostic`] is, most serious first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The question would be rejected by an import
+    Error,
+    /// The question would be imported but should be looked at
+    Warning,
+    /// Something an import would change on its own
+    Info,
+}
+
+/// A problem found in one question
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier, such as `MISSING_ANSWER`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// A parsed question with its source lines and diagnostics
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewQuestion {
+    /// The question as an import would store it
+    pub question: Question,
+    /// Input lines it was parsed from
+    pub span: SourceSpan,
+    /// Detected type and confidence; zero confidence when the classifier
+    /// could not place it
+    pub classification: ClassificationResult,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Result of previewing a document
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    pub count: usize,
+    pub questions: Vec<PreviewQuestion>,
+    /// Number of questions an import would reject
+    pub errors: usize,
+    /// Number of questions with warnings only
+    pub warnings: usize,
+}
+
+/// Parse `markdown` and diagnose each question without saving anything
+///
+/// Questions take the classifier's type, or `default_type` when it cannot
+/// place them.
+pub fn preview_markdown(
+    markdown: &str,
+    options: &ParserOptions,
+    language: Option<Language>,
+    default_type: Option<QuestionType>,
+) -> Result<Preview> {
+    let validator = QuestionValidator::new();
+    let latex = LatexValidator::new();
+    let questions: Vec<PreviewQuestion> = parse_markdown_with_spans(markdown, options)?
+        .into_iter()
+        .map(|(mut question, span)| {
+            let mut diagnostics = Vec::new();
+            if let Some(issue) = validate_answer(&mut question) {
+                let severity = match issue.kind {
+                    AnswerIssueKind::Missing => Severity::Warning,
+                    AnswerIssueKind::Invalid => Severity::Error,
+                    AnswerIssueKind::Corrected => Severity::Info,
+                    AnswerIssueKind::StemLetters => Severity::Warning,
+                };
+                diagnostics.push(Diagnostic::new(severity, issue.kind.code(), issue.message));
+            }
+            normalize_question(&mut question);
+            for issue in latex.check_question(&question) {
+                let message = format!("{} in {}", issue.message, issue.formula);
+                diagnostics.push(Diagnostic::new(Severity::Warning, issue.kind.code(), message));
+            }
+
+            let classification = apply_classification(&mut question, language, default_type);
+            if classification.needs_review {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    "NEEDS_REVIEW",
+                    format!("type detected with {:.0}% confidence", classification.confidence * 100.0),
+                ));
+            }
+
+            // The answer checks above already cover a missing answer
+            let missing_answer = diagnostics.iter().any(|d| d.code == AnswerIssueKind::Missing.code());
+            for reason in validator.check(&question) {
+                if missing_answer && reason == "no correct answer" {
+                    continue;
+                }
+                diagnostics.push(Diagnostic::new(Severity::Error, "INVALID_QUESTION", reason));
+            }
+
+            PreviewQuestion {
+                question,
+                span,
+                classification,
+                diagnostics,
+            }
+        })
+        .collect();
+
+    let worst = |q: &PreviewQuestion| q.diagnostics.iter().map(|d| d.severity).min();
+    Ok(Preview {
+        count: questions.len(),
+        errors: questions.iter().filter(|q| worst(q) == Some(Severity::Error)).count(),
+        warnings: questions.iter().filter(|q| worst(q) == Some(Severity::Warning)).count(),
+        questions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_diagnostics() {
+        let markdown = "# 下列哪个是质数？\n\n* A. 4\n* B. 7\n\n答案：B\n\n# Which is even?\n\n* A. 3\n* B. 5\n\nAnswer: E\n";
+        let preview = preview_markdown(markdown, &ParserOptions::default(), None, None).unwrap();
+        assert_eq!(preview.count, 2);
+
+        let prime = &preview.questions[0];
+        assert_eq!(prime.span, SourceSpan { start_line: 1, end_line: 6 });
+        assert_eq!(prime.question.answer.as_deref(), Some("B"));
+        assert!(prime.question.options[1].is_correct);
+        assert!(prime.diagnostics.iter().all(|d| d.severity != Severity::Error), "{:?}", prime.diagnostics);
+
+        let even = &preview.questions[1];
+        assert_eq!(even.span.start_line, 8);
+        assert!(even.diagnostics.iter().any(|d| d.code == "INVALID_ANSWER" && d.severity == Severity::Error));
+        assert_eq!(preview.errors, 1);
+
+        let preview = preview_markdown("# Simplify $\\frac{1}{2$\n\nAnswer: 1/2\n", &ParserOptions::default(), None, None).unwrap();
+        let diagnostic = preview.questions[0].diagnostics.iter().find(|d| d.code == "UNBALANCED_BRACES").unwrap();
+        assert_eq!(diagnostic.severity, Severity::Warning);
+    }
+}
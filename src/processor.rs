@@ -7,7 +7,8 @@
 //! # Architecture
 //!
 //! The processor uses a hybrid concurrency model:
-//! - **Rayon**: For CPU-intensive parsing operations (Markdown parsing, LaTeX extraction)
+//! - **Rayon**: For CPU-intensive parsing operations (Markdown parsing, LaTeX extraction),
+//!   on a thread pool owned by each processor rather than the global one
 //! - **Tokio**: For I/O-intensive operations (file reading, ZIP extraction, database writes)
 //! - **Semaphore**: For controlling concurrency and preventing resource exhaustion
 //!
@@ -15,22 +16,47 @@
 //!
 //! - Automatic CPU core detection
 //! - Batched database writes to reduce transaction overhead
+//! - Optional all-or-nothing imports through an [`ImportSession`](crate::database::ImportSession)
 //! - Parallel parsing with work-stealing scheduler
 //! - Content-addressed storage for image deduplication
 //! - Backpressure-aware async stream processing
+//!
+//! Long imports can report their progress through a [`ProgressReporter`]
+//! passed to [`SingleMachineProcessor::process_with_progress`].
 
+use crate::answer::validate_answers;
+use crate::consistency::{ConsistencyChecker, ConsistencyReport};
 use crate::database::QuestionRepository;
-use crate::models::Question;
-use crate::parser::{parse_markdown, MarkdownParser};
-use crate::zip::{ZipEntry, ZipProcessor};
+use crate::dedup::{DedupOptions, SaveOutcome};
+use crate::duplicates::{BatchDedup, DuplicateCluster};
+use crate::gift::parse_gift;
+use crate::import::{ImportJob, ImportJobResult, ImportReport};
+use crate::incremental::{self, IncrementalPlan};
+use crate::latex::validate_formulas;
+use crate::media::MediaStore;
+use crate::models::{Provenance, Question};
+use crate::ocr::FormulaOcr;
+use crate::parser::{parse_markdown_parallel, parse_markdown_with_options, ParserOptions};
+#[cfg(any(feature = "csv", feature = "xlsx"))]
+use crate::tabular::{ColumnMapping, TabularImporter};
+use crate::report::QuestionBreakdown;
+use crate::tagger::TopicTagger;
+use crate::transform::{Transform, TransformChain};
+use crate::validate::{QuestionValidator, RejectedQuestion};
+use crate::zip::{
+    elapsed_ms, ConcurrencyGauge, EntryContent, ImageStoreReport, SpillConfig, StageMetrics, ZipLimits,
+    ZipProcessResult, ZipProcessor,
+};
 use anyhow::{Context, Result};
 use futures::stream::{self, StreamExt};
-use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::sync::Semaphore;
 use tracing::{debug, info, warn};
+use uuid::Uuid;
 
 /// Configuration for the single-machine processor
 #[derive(Debug, Clone)]
@@ -43,6 +69,36 @@ pub struct ProcessorConfig {
     pub batch_size: usize,
     /// Maximum concurrent ZIP file processing (defaults to 4)
     pub max_concurrent_zips: usize,
+    /// Skip questions already stored (defaults to off)
+    pub dedup: Option<DedupOptions>,
+    /// Parser options for Markdown input (archive manifests may name a preset instead)
+    pub parser_options: ParserOptions,
+    /// Save through one import session, rolling back everything if a batch fails (defaults to off)
+    pub transactional: bool,
+    /// Question bank imported questions are assigned to (defaults to none)
+    pub bank_id: Option<Uuid>,
+    /// Strip EXIF/GPS and text metadata from archive images before storing them (defaults to off)
+    pub strip_image_metadata: bool,
+    /// Keep questions in input order and number them (defaults to on)
+    pub preserve_order: bool,
+    /// Re-import into `bank_id` by file, skipping unchanged files and
+    /// updating changed questions in place (defaults to off)
+    pub incremental: bool,
+    /// Reject invalid questions instead of saving them (defaults to off)
+    pub validator: Option<QuestionValidator>,
+    /// Report answers that do not fit their question type (defaults to off)
+    pub consistency: Option<ConsistencyChecker>,
+    /// Run everything but the writes and report what would be saved (defaults to off)
+    pub dry_run: bool,
+    /// Rewrites applied to each question before validation and saving (defaults to none)
+    pub transforms: TransformChain,
+    /// Find questions repeated across the files of one import (defaults to off)
+    pub batch_dedup: Option<BatchDedup>,
+    /// Limits on what one archive may extract
+    pub limits: ZipLimits,
+    /// Parse Markdown files larger than this in blocks of about this many
+    /// bytes, in parallel (defaults to 256 KiB; `None` parses each file whole)
+    pub split_block_bytes: Option<usize>,
 }
 
 impl Default for ProcessorConfig {
@@ -56,6 +112,20 @@ impl Default for ProcessorConfig {
             max_io_workers: cpu_cores * 2,
             batch_size: 100,
             max_concurrent_zips: 4,
+            dedup: None,
+            parser_options: ParserOptions::default(),
+            transactional: false,
+            bank_id: None,
+            strip_image_metadata: false,
+            preserve_order: true,
+            incremental: false,
+            validator: None,
+            consistency: None,
+            dry_run: false,
+            transforms: TransformChain::new(),
+            batch_dedup: None,
+            limits: ZipLimits::default(),
+            split_block_bytes: Some(DEFAULT_SPLIT_BLOCK_BYTES),
         }
     }
 }
@@ -84,35 +154,249 @@ impl ProcessorConfig {
         self.max_concurrent_zips = max.max(1);
         self
     }
+
+    /// Create a new configuration that deduplicates against stored questions
+    pub fn with_dedup(mut self, options: DedupOptions) -> Self {
+        self.dedup = Some(options);
+        self
+    }
+
+    /// Create a new configuration with specified parser options
+    pub fn with_parser_options(mut self, options: ParserOptions) -> Self {
+        self.parser_options = options;
+        self
+    }
+
+    /// Create a new configuration that commits or rolls back each import as a whole
+    pub fn with_transactional(mut self, enabled: bool) -> Self {
+        self.transactional = enabled;
+        self
+    }
+
+    /// Create a new configuration that assigns imported questions to a bank
+    pub fn with_bank_id(mut self, bank_id: Uuid) -> Self {
+        self.bank_id = Some(bank_id);
+        self
+    }
+
+    /// Create a new configuration that scrubs image metadata before storage
+    pub fn with_strip_image_metadata(mut self, enabled: bool) -> Self {
+        self.strip_image_metadata = enabled;
+        self
+    }
+
+    /// Create a new configuration that keeps (or gives up) input order for throughput
+    pub fn with_preserve_order(mut self, enabled: bool) -> Self {
+        self.preserve_order = enabled;
+        self
+    }
+
+    /// Create a new configuration that re-imports the bank incrementally (see [`crate::incremental`])
+    pub fn with_incremental(mut self, enabled: bool) -> Self {
+        self.incremental = enabled;
+        self
+    }
+
+    /// Create a new configuration that checks questions with `validator` before saving
+    pub fn with_validation(mut self, validator: QuestionValidator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Create a new configuration that audits questions with `checker` before saving,
+    /// reporting in [`ProcessResult::inconsistent`] without rejecting anything
+    pub fn with_consistency_check(mut self, checker: ConsistencyChecker) -> Self {
+        self.consistency = Some(checker);
+        self
+    }
+
+    /// Create a new configuration that parses and checks input without saving anything
+    ///
+    /// Set this before attaching a media store, which a dry run leaves out.
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Create a new configuration that runs `transform` on every question, after
+    /// any added before it (see [`crate::transform`])
+    pub fn with_transform(mut self, transform: impl Transform + 'static) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Create a new configuration that reports, or drops, questions repeated
+    /// within the import (see [`crate::duplicates`])
+    pub fn with_batch_dedup(mut self, dedup: BatchDedup) -> Self {
+        self.batch_dedup = Some(dedup);
+        self
+    }
+
+    /// Create a new configuration with these archive extraction limits
+    pub fn with_limits(mut self, limits: ZipLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Create a new configuration that splits large Markdown files into
+    /// blocks of this size, or parses them whole with `None`
+    pub fn with_split_block_bytes(mut self, bytes: Option<usize>) -> Self {
+        self.split_block_bytes = bytes;
+        self
+    }
+}
+
+/// Default for [`ProcessorConfig::split_block_bytes`]
+pub const DEFAULT_SPLIT_BLOCK_BYTES: usize = 256 * 1024;
+
+/// Number of questions returned in [`ProcessResult::sample_questions`]
+pub const DRY_RUN_SAMPLE_SIZE: usize = 5;
+
+/// Where the time of a run went and how much concurrency it used
+///
+/// Meant for tuning [`ProcessorConfig`]: worker counts that never reach
+/// their peak can be lowered, and the slowest stage shows where more would
+/// help. Images are stored while files are parsed, and archives of a
+/// multi-archive job are processed side by side with their times summed,
+/// so the stages can add up to more than `processing_time_ms`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineMetrics {
+    /// Unpacking archives and converting documents, in milliseconds
+    pub extraction_ms: u64,
+    /// Parsing question files, in milliseconds
+    pub parsing_ms: u64,
+    /// Tagging, validating and planning incremental changes, in milliseconds
+    pub classification_ms: u64,
+    /// Hashing and storing images, in milliseconds
+    pub media_ms: u64,
+    /// Writing to the database, in milliseconds
+    pub db_ms: u64,
+    /// Questions processed per second of the whole run
+    pub questions_per_sec: f64,
+    /// Batches written successfully
+    pub batches_saved: usize,
+    /// Batches that failed or were rolled back
+    pub batches_failed: usize,
+    /// Most files parsed at once
+    pub peak_parse_workers: usize,
+    /// Most batches written at once
+    pub peak_db_workers: usize,
 }
 
 /// Result of a processing operation
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessResult {
     /// Total number of questions processed
     pub total_questions: usize,
-    /// Number of questions successfully saved
+    /// Number of questions successfully saved (inserted or updated)
     pub saved_questions: usize,
+    /// Number of saved questions that updated an existing record
+    pub updated_questions: usize,
+    /// Number of saved questions stored as new records
+    #[serde(default)]
+    pub added_questions: usize,
+    /// Number of stored questions removed because they are gone from their
+    /// file (incremental imports only)
+    #[serde(default)]
+    pub removed_questions: usize,
+    /// Number of questions identical to their stored record and not written
+    /// (incremental imports only)
+    #[serde(default)]
+    pub unchanged_questions: usize,
+    /// Files skipped because they have not changed since the last import
+    /// (incremental imports only)
+    #[serde(default)]
+    pub unchanged_files: Vec<String>,
+    /// Number of questions skipped as duplicates of stored ones or of others
+    /// in the same import
+    pub duplicate_questions: usize,
     /// Number of questions that failed to save
     pub failed_questions: usize,
     /// Number of images processed
     pub total_images: usize,
+    /// Number of images written to the media store
+    #[serde(default)]
+    pub stored_images: usize,
+    /// Number of images the media store already held
+    #[serde(default)]
+    pub deduplicated_images: usize,
+    /// Number of images that could not be stored (see the warnings)
+    #[serde(default)]
+    pub failed_images: usize,
     /// Warnings generated during processing
     pub warnings: Vec<String>,
     /// Processing time in milliseconds
     pub processing_time_ms: u64,
+    /// Consolidated report when several archives were imported as one job
+    pub import_report: Option<ImportReport>,
+    /// Per-question save outcomes, in input order (only with dedup enabled)
+    pub outcomes: Vec<SaveOutcome>,
+    /// Questions that failed validation and were not saved (only with a validator)
+    #[serde(default)]
+    pub rejected: Vec<RejectedQuestion>,
+    /// Questions of each type and those needing review, for the import report
+    #[serde(default)]
+    pub breakdown: QuestionBreakdown,
+    /// Questions whose answer does not fit their type, one report each
+    /// (only with a consistency checker)
+    #[serde(default)]
+    pub inconsistent: Vec<ConsistencyReport>,
+    /// Questions repeated within the import (only with batch dedup)
+    #[serde(default)]
+    pub duplicate_clusters: Vec<DuplicateCluster>,
+    /// Import session the questions were saved in (transactional imports only)
+    pub session_id: Option<Uuid>,
+    /// Whether the import session was rolled back, leaving nothing saved
+    pub rolled_back: bool,
+    /// Whether this was a dry run; the counts are then what would have been saved
+    #[serde(default)]
+    pub dry_run: bool,
+    /// The first questions that would have been saved (dry runs only)
+    #[serde(default)]
+    pub sample_questions: Vec<Question>,
+    /// IDs of the stored questions (API upload jobs only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub question_ids: Vec<Uuid>,
+    /// Every question imported (API upload jobs only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub questions: Vec<Question>,
+    /// Per-stage timing, throughput and concurrency
+    #[serde(default)]
+    pub metrics: PipelineMetrics,
 }
 
 impl ProcessResult {
     /// Create a new empty result
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             total_questions: 0,
             saved_questions: 0,
+            updated_questions: 0,
+            added_questions: 0,
+            removed_questions: 0,
+            unchanged_questions: 0,
+            unchanged_files: Vec::new(),
+            duplicate_questions: 0,
             failed_questions: 0,
             total_images: 0,
+            stored_images: 0,
+            deduplicated_images: 0,
+            failed_images: 0,
             warnings: Vec::new(),
             processing_time_ms: 0,
+            import_report: None,
+            outcomes: Vec::new(),
+            rejected: Vec::new(),
+            breakdown: QuestionBreakdown::default(),
+            inconsistent: Vec::new(),
+            duplicate_clusters: Vec::new(),
+            session_id: None,
+            rolled_back: false,
+            dry_run: false,
+            sample_questions: Vec::new(),
+            question_ids: Vec::new(),
+            questions: Vec::new(),
+            metrics: PipelineMetrics::default(),
         }
     }
 
@@ -131,7 +415,10 @@ impl ProcessResult {
         if self.total_questions == 0 {
             100.0
         } else {
-            (self.saved_questions as f64 / self.total_questions as f64) * 100.0
+            // Skipped duplicates and unchanged questions are already stored,
+            // so they count as successes
+            let ok = self.saved_questions + self.duplicate_questions + self.unchanged_questions;
+            (ok as f64 / self.total_questions as f64) * 100.0
         }
     }
 }
@@ -145,8 +432,160 @@ pub enum InputSource {
     MultipleMarkdown { contents: Vec<(String, String)> },
     /// ZIP file data
     Zip { data: Vec<u8>, source: String },
-    /// Multiple ZIP files
+    /// ZIP file on disk, read without loading it into memory
+    ZipFile { path: PathBuf, source: String },
+    /// Multiple ZIP files, imported as one job with cross-archive dedup
     MultipleZip { files: Vec<(Vec<u8>, String)> },
+    /// Moodle GIFT file content
+    Gift { content: String, source: String },
+    /// Any supported file, recognized by its content (see [`crate::detect`])
+    File { data: Vec<u8>, source: String },
+    /// Spreadsheet with one question per row; the format comes from `source`
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    Tabular {
+        data: Vec<u8>,
+        source: String,
+        mapping: ColumnMapping,
+    },
+}
+
+impl InputSource {
+    /// Names of the files (or archives) in the input
+    pub fn sources(&self) -> Vec<String> {
+        match self {
+            InputSource::Markdown { source, .. }
+            | InputSource::Zip { source, .. }
+            | InputSource::ZipFile { source, .. }
+            | InputSource::Gift { source, .. }
+            | InputSource::File { source, .. } => vec![source.clone()],
+            #[cfg(any(feature = "csv", feature = "xlsx"))]
+            InputSource::Tabular { source, .. } => vec![source.clone()],
+            InputSource::MultipleMarkdown { contents } => contents.iter().map(|(_, source)| source.clone()).collect(),
+            InputSource::MultipleZip { files } => files.iter().map(|(_, source)| source.clone()).collect(),
+        }
+    }
+
+    /// Number of files (or archives) in the input
+    fn file_count(&self) -> usize {
+        match self {
+            InputSource::MultipleMarkdown { contents } => contents.len(),
+            InputSource::MultipleZip { files } => files.len(),
+            _ => 1,
+        }
+    }
+}
+
+/// Stage of a [`SingleMachineProcessor`] run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessStage {
+    /// Reading and parsing the input files
+    Parsing,
+    /// Assigning topic tags (only with a tagger)
+    Tagging,
+    /// Writing questions to the repository
+    Saving,
+    /// Everything has been saved
+    Done,
+}
+
+/// Receives progress while a [`SingleMachineProcessor`] processes an input
+///
+/// Every method does nothing by default, so a reporter implements only what
+/// it shows. Calls may come from several tasks, but never at the same time.
+pub trait ProgressReporter: Send + Sync {
+    /// A stage has started
+    fn on_stage(&self, _stage: ProcessStage) {}
+
+    /// A file or archive yielded `questions` questions (before dedup)
+    fn on_file_done(&self, _source: &str, _questions: usize) {}
+
+    /// `done` of `total` questions have been saved, skipped or failed
+    fn on_batch_saved(&self, _done: usize, _total: usize) {}
+
+    /// Estimated completion of the whole run, from 0 to 100; never decreases
+    ///
+    /// Parsing counts for the first half by files done, saving for the
+    /// second half by questions done.
+    fn on_percent(&self, _percent: f64) {}
+}
+
+/// Reporter that ignores all progress
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {}
+
+/// Share of the percent estimate given to parsing
+const PARSE_PERCENT: f64 = 50.0;
+
+/// Counts the progress of one run and passes it on with a percent estimate
+struct ProgressTracker<'a> {
+    reporter: &'a dyn ProgressReporter,
+    files: usize,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Default)]
+struct ProgressState {
+    files_done: usize,
+    questions: usize,
+    questions_done: usize,
+    percent: f64,
+}
+
+impl<'a> ProgressTracker<'a> {
+    fn new(reporter: &'a dyn ProgressReporter, files: usize) -> Self {
+        Self {
+            reporter,
+            files,
+            state: Mutex::new(ProgressState::default()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProgressState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Report a new stage
+    fn stage(&self, stage: ProcessStage) {
+        let mut state = self.lock();
+        self.reporter.on_stage(stage);
+        if stage == ProcessStage::Done {
+            self.percent(&mut state, 100.0);
+        }
+    }
+
+    /// Report the start of saving `questions` questions
+    fn saving(&self, questions: usize) {
+        let mut state = self.lock();
+        state.questions = questions;
+        self.reporter.on_stage(ProcessStage::Saving);
+        self.percent(&mut state, PARSE_PERCENT);
+    }
+
+    /// Report `count` more questions saved, skipped or failed
+    fn batch_saved(&self, count: usize) {
+        let mut state = self.lock();
+        state.questions_done += count;
+        self.reporter.on_batch_saved(state.questions_done, state.questions);
+        let done = state.questions_done as f64 / state.questions.max(1) as f64;
+        self.percent(&mut state, PARSE_PERCENT + (100.0 - PARSE_PERCENT) * done);
+    }
+
+    fn percent(&self, state: &mut ProgressState, percent: f64) {
+        state.percent = percent.clamp(state.percent, 100.0);
+        self.reporter.on_percent(state.percent);
+    }
+}
+
+impl ProgressReporter for ProgressTracker<'_> {
+    fn on_file_done(&self, source: &str, questions: usize) {
+        let mut state = self.lock();
+        state.files_done += 1;
+        self.reporter.on_file_done(source, questions);
+        let done = state.files_done as f64 / self.files.max(1) as f64;
+        self.percent(&mut state, PARSE_PERCENT * done);
+    }
 }
 
 /// Single-machine multi-core processor
@@ -164,6 +603,13 @@ pub struct SingleMachineProcessor<R> {
     cpu_semaphore: Arc<Semaphore>,
     /// Semaphore for limiting I/O-intensive work
     io_semaphore: Arc<Semaphore>,
+    /// Optional topic tagger applied to every parsed question
+    tagger: Option<Arc<TopicTagger>>,
+    /// Thread pool for parsing, sized to `max_cpu_workers`
+    ///
+    /// `None` if the pool could not be started; parsing then runs on
+    /// Tokio's blocking threads.
+    pool: Option<Arc<rayon::ThreadPool>>,
 }
 
 impl<R> SingleMachineProcessor<R>
@@ -180,16 +626,22 @@ where
         let cpu_workers = config.max_cpu_workers;
         let io_workers = config.max_io_workers;
 
-        // Configure Rayon thread pool
-        rayon::ThreadPoolBuilder::new()
+        // Each processor gets its own pool, so processors with different
+        // worker counts can coexist and the host's global pool is left alone
+        let pool = rayon::ThreadPoolBuilder::new()
             .num_threads(cpu_workers)
-            .build_global()
-            .unwrap_or_else(|e| {
-                warn!("Failed to set global Rayon thread pool: {}", e);
-            });
+            .thread_name(|i| format!("md2db-parse-{}", i))
+            .build()
+            .map(Arc::new)
+            .map_err(|e| warn!("Failed to start Rayon thread pool: {}", e))
+            .ok();
 
         // Configure ZIP processor
-        let zip_processor = ZipProcessor::with_workers(cpu_workers);
+        let zip_processor = ZipProcessor::with_workers(cpu_workers)
+            .with_parser_options(config.parser_options.clone())
+            .with_strip_metadata(config.strip_image_metadata)
+            .with_preserve_order(config.preserve_order)
+            .with_limits(config.limits);
 
         Self {
             repository: Arc::new(repository),
@@ -197,6 +649,66 @@ where
             zip_processor,
             cpu_semaphore: Arc::new(Semaphore::new(cpu_workers)),
             io_semaphore: Arc::new(Semaphore::new(io_workers)),
+            tagger: None,
+            pool,
+        }
+    }
+
+    /// Attach a topic tagger that assigns subject tags before saving
+    pub fn with_topic_tagger(mut self, tagger: TopicTagger) -> Self {
+        self.tagger = Some(Arc::new(tagger));
+        self
+    }
+
+    /// Persist images extracted from ZIP archives in `store`
+    ///
+    /// Ignored for dry runs, which write nothing.
+    pub fn with_media_store(mut self, store: Arc<dyn MediaStore>) -> Self {
+        if !self.config.dry_run {
+            self.zip_processor = self.zip_processor.with_media_store(store);
+        }
+        self
+    }
+
+    /// Recognize formulas in images referenced from ZIP archives
+    pub fn with_formula_ocr(mut self, ocr: Arc<dyn FormulaOcr>) -> Self {
+        self.zip_processor = self.zip_processor.with_formula_ocr(ocr);
+        self
+    }
+
+    /// Write large archive entries to working directories instead of memory
+    pub fn with_spill(mut self, spill: SpillConfig) -> Self {
+        self.zip_processor = self.zip_processor.with_spill(spill);
+        self
+    }
+
+    /// A processor for one import with its own `config`, sharing this one's
+    /// repository, thread pool, semaphores and services
+    ///
+    /// Starting a processor starts a thread pool, so a server keeps one and
+    /// derives a processor per request. Worker counts stay those of this
+    /// processor; a dry-run `config` drops the media store.
+    pub fn reconfigured(&self, mut config: ProcessorConfig) -> Self {
+        config.max_cpu_workers = self.config.max_cpu_workers;
+        config.max_io_workers = self.config.max_io_workers;
+
+        let mut zip_processor = self.zip_processor.clone()
+            .with_parser_options(config.parser_options.clone())
+            .with_strip_metadata(config.strip_image_metadata)
+            .with_preserve_order(config.preserve_order)
+            .with_limits(config.limits);
+        if config.dry_run {
+            zip_processor = zip_processor.without_media_store();
+        }
+
+        Self {
+            repository: self.repository.clone(),
+            config,
+            zip_processor,
+            cpu_semaphore: self.cpu_semaphore.clone(),
+            io_semaphore: self.io_semaphore.clone(),
+            tagger: self.tagger.clone(),
+            pool: self.pool.clone(),
         }
     }
 
@@ -205,37 +717,260 @@ where
     /// This is the main entry point for processing operations. It automatically
     /// selects the appropriate processing strategy based on the input type.
     pub async fn process(&self, input: InputSource) -> Result<ProcessResult> {
+        self.process_with_progress(input, &NoProgress).await
+    }
+
+    /// Process an input source like [`SingleMachineProcessor::process`],
+    /// reporting stages, finished files and saved batches to `reporter`
+    pub async fn process_with_progress(
+        &self,
+        input: InputSource,
+        reporter: &dyn ProgressReporter,
+    ) -> Result<ProcessResult> {
         let start = std::time::Instant::now();
+        let progress = ProgressTracker::new(reporter, input.file_count());
 
         info!("Starting processing with config: {:?}", self.config);
 
-        let (questions, images, warnings) = match input {
+        if let Some(bank_id) = self.config.bank_id {
+            if self.repository.find_bank(bank_id).await?.is_none() {
+                anyhow::bail!("Question bank {} not found", bank_id);
+            }
+        }
+        let incremental = match self.config.bank_id {
+            Some(bank_id) if self.config.incremental => Some(bank_id),
+            None if self.config.incremental => anyhow::bail!("Incremental imports need a question bank"),
+            _ => None,
+        };
+
+        progress.stage(ProcessStage::Parsing);
+        let parse_start = Instant::now();
+        let parse_gauge = ConcurrencyGauge::default();
+        let mut import_report = None;
+        let mut image_store = ImageStoreReport::default();
+        // Archive pipelines time their own stages
+        let mut stages = None;
+        let (mut questions, images, warnings) = match input {
             InputSource::Markdown { content, source } => {
-                self.process_single_markdown(content, source).await?
+                let mut parsed = self.process_single_markdown(content, &source).await?;
+                record_source(&mut parsed.0, &source);
+                progress.on_file_done(&source, parsed.0.len());
+                parsed
             }
             InputSource::MultipleMarkdown { contents } => {
-                self.process_multiple_markdown(contents).await?
+                self.process_multiple_markdown(contents, &progress, &parse_gauge).await?
             }
             InputSource::Zip { data, source } => {
-                self.process_single_zip(data, source).await?
+                let zip_result = self.process_single_zip(data, &source).await?;
+                progress.on_file_done(&source, zip_result.questions.len());
+                image_store = zip_result.image_store;
+                stages = Some(zip_result.metrics);
+                (zip_result.questions, zip_result.images, zip_result.warnings)
+            }
+            InputSource::ZipFile { path, source } => {
+                debug!("Processing ZIP file {:?} from disk: {}", path, source);
+                let zip_result = self.zip_processor.process_zip_file(&path).await?;
+                progress.on_file_done(&source, zip_result.questions.len());
+                image_store = zip_result.image_store;
+                stages = Some(zip_result.metrics);
+                (zip_result.questions, zip_result.images, zip_result.warnings)
             }
             InputSource::MultipleZip { files } => {
-                self.process_multiple_zips(files).await?
+                let job = self.process_multiple_zips(files, &progress).await?;
+                let mut metrics = StageMetrics::default();
+                for archive in &job.report.archives {
+                    image_store.merge(&archive.image_store);
+                    metrics.merge(&archive.metrics);
+                }
+                stages = Some(metrics);
+                let warnings = job.report.warnings.clone();
+                import_report = Some(job.report);
+                (job.questions, job.images, warnings)
+            }
+            InputSource::Gift { content, source } => {
+                let parsed = self.process_gift(content, source.clone()).await?;
+                progress.on_file_done(&source, parsed.0.len());
+                parsed
+            }
+            InputSource::File { data, source } => {
+                debug!("Processing file: {}", source);
+                let mut result = self.zip_processor.process_detected(&source, data).await?;
+                record_source(&mut result.questions, &source);
+                progress.on_file_done(&source, result.questions.len());
+                image_store = result.image_store;
+                stages = Some(result.metrics);
+                (result.questions, result.images, result.warnings)
+            }
+            #[cfg(any(feature = "csv", feature = "xlsx"))]
+            InputSource::Tabular { data, source, mapping } => {
+                let parsed = self.process_table(data, source.clone(), mapping).await?;
+                progress.on_file_done(&source, parsed.0.len());
+                parsed
             }
         };
 
+        // Files read outside the archive pipeline are parsed one at a time,
+        // except Markdown batches, which count their own workers
+        let stages = stages.unwrap_or_else(|| StageMetrics {
+            parsing_ms: elapsed_ms(parse_start),
+            peak_parse_workers: parse_gauge.peak().max(1),
+            ..StageMetrics::default()
+        });
+
+        let classify_start = Instant::now();
+        if let Some(tagger) = &self.tagger {
+            progress.stage(ProcessStage::Tagging);
+            for question in &mut questions {
+                tagger.apply(question);
+            }
+        }
+        if let Some(bank_id) = self.config.bank_id {
+            for question in &mut questions {
+                question.bank_id = Some(bank_id);
+            }
+        }
+        if !self.config.transforms.is_empty() {
+            let chain = self.config.transforms.clone();
+            questions = self
+                .run_parse(move || questions.into_iter().map(|q| chain.apply(q)).collect())
+                .await
+                .context("Failed to transform questions")?;
+        }
+
+        let mut duplicate_clusters = Vec::new();
+        let mut batch_duplicates = 0;
+        if let Some(dedup) = &self.config.batch_dedup {
+            let before = questions.len();
+            (questions, duplicate_clusters) = dedup.apply(questions);
+            batch_duplicates = before - questions.len();
+            if !duplicate_clusters.is_empty() {
+                info!(
+                    "Found {} questions repeated within the import ({} dropped)",
+                    duplicate_clusters.len(),
+                    batch_duplicates
+                );
+            }
+        }
+
+        let mut inconsistent = Vec::new();
+        if let Some(checker) = &self.config.consistency {
+            inconsistent = checker.audit(&questions);
+            inconsistent.retain(|report| !report.is_consistent());
+            if !inconsistent.is_empty() {
+                info!("Found {} questions with inconsistent answers", inconsistent.len());
+            }
+        }
+
+        let mut rejected = Vec::new();
+        if let Some(validator) = &self.config.validator {
+            (questions, rejected) = validator.partition(questions);
+            if !rejected.is_empty() {
+                info!("Rejected {} invalid questions", rejected.len());
+            }
+        }
+        let breakdown = QuestionBreakdown::new(&questions);
+
+        let mut plan = None;
+        if let Some(bank_id) = incremental {
+            let recorded = self.repository.list_sources(bank_id).await?;
+            let stored = self.repository.find_by_bank(bank_id).await?;
+            let mut changes = incremental::plan(bank_id, questions, &recorded, stored);
+            info!(
+                "Incremental import into bank {}: {} new, {} changed, {} removed, {} unchanged files",
+                bank_id,
+                changes.inserts.len(),
+                changes.updates.len(),
+                changes.removals.len(),
+                changes.unchanged_files.len()
+            );
+            questions = std::mem::take(&mut changes.inserts);
+            plan = Some(changes);
+        }
+
+        let classification_ms = elapsed_ms(classify_start);
+
         // Save questions to database in batches
-        let saved = self.save_questions_batched(questions).await?;
+        let db_start = Instant::now();
+        let changes = plan.as_ref().map_or(0, |p| p.updates.len() + p.removals.len());
+        progress.saving(questions.len() + changes);
+        let mut sample_questions = Vec::new();
+        let (saved, applied) = if self.config.dry_run {
+            info!("Dry run: not saving {} questions", questions.len());
+            let saved = BatchSaveResult {
+                total: questions.len(),
+                ..BatchSaveResult::default()
+            };
+            sample_questions = questions.into_iter().take(DRY_RUN_SAMPLE_SIZE).collect();
+            (saved, plan.map(IncrementalResult::preview).unwrap_or_default())
+        } else {
+            let saved = if self.config.transactional {
+                self.save_questions_in_session(questions, &progress).await?
+            } else {
+                self.save_questions_batched(questions, &progress).await?
+            };
+            let applied = match plan {
+                Some(plan) => self.apply_incremental(plan, &saved, &progress).await,
+                None => IncrementalResult::default(),
+            };
+            (saved, applied)
+        };
+        let db_ms = if self.config.dry_run { 0 } else { elapsed_ms(db_start) };
+        progress.stage(ProcessStage::Done);
 
         let elapsed = start.elapsed();
-        let result = ProcessResult {
-            total_questions: saved.total + saved.failed,
-            saved_questions: saved.total,
-            failed_questions: saved.failed,
-            total_images: images.len(),
-            warnings,
-            processing_time_ms: elapsed.as_millis() as u64,
+        let mut result = ProcessResult::new();
+        result.total_questions = saved.total
+            + saved.duplicates
+            + saved.failed
+            + applied.updated
+            + applied.failed
+            + applied.unchanged_questions
+            + rejected.len()
+            + batch_duplicates;
+        result.saved_questions = saved.total + applied.updated;
+        result.updated_questions = saved.updated + applied.updated;
+        result.added_questions = saved.total - saved.updated;
+        result.removed_questions = applied.removed;
+        result.unchanged_questions = applied.unchanged_questions;
+        result.unchanged_files = applied.unchanged_files;
+        result.duplicate_questions = saved.duplicates + batch_duplicates;
+        result.failed_questions = saved.failed + applied.failed;
+        result.outcomes = saved.outcomes;
+        result.rejected = rejected;
+        result.breakdown = breakdown;
+        result.inconsistent = inconsistent;
+        result.duplicate_clusters = duplicate_clusters;
+        result.total_images = images.len();
+        result.stored_images = image_store.stored;
+        result.deduplicated_images = image_store.already_stored;
+        result.failed_images = image_store.failed;
+        for warning in warnings.into_iter().chain(applied.warnings) {
+            result.add_warning(warning);
+        }
+        result.processing_time_ms = elapsed.as_millis() as u64;
+        result.import_report = import_report;
+        result.session_id = saved.session_id;
+        result.dry_run = self.config.dry_run;
+        result.sample_questions = sample_questions;
+        result.metrics = PipelineMetrics {
+            extraction_ms: stages.extraction_ms,
+            parsing_ms: stages.parsing_ms,
+            classification_ms,
+            media_ms: stages.media_ms,
+            db_ms,
+            questions_per_sec: match elapsed.as_secs_f64() {
+                secs if secs > 0.0 => result.total_questions as f64 / secs,
+                _ => 0.0,
+            },
+            batches_saved: saved.batches_saved,
+            batches_failed: saved.batches_failed,
+            peak_parse_workers: stages.peak_parse_workers,
+            peak_db_workers: saved.peak_workers,
         };
+        if let (Some(id), Some(reason)) = (saved.session_id, saved.rollback_reason) {
+            result.rolled_back = true;
+            result.add_warning(format!("Import session {} rolled back: {}", id, reason));
+        }
 
         info!(
             "Processing complete: {} questions saved, {} failed in {}ms",
@@ -247,59 +982,101 @@ where
         Ok(result)
     }
 
+    /// Run CPU-bound parse work on this processor's thread pool
+    ///
+    /// The work is handed over from a blocking Tokio thread so the runtime
+    /// is never stalled; a panic comes back as the join error.
+    async fn run_parse<T, F>(&self, work: F) -> std::result::Result<T, tokio::task::JoinError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || match pool {
+            Some(pool) => pool.install(work),
+            None => work(),
+        })
+        .await
+    }
+
     /// Process a single Markdown file
     async fn process_single_markdown(
         &self,
         content: String,
-        source: String,
-    ) -> Result<(Vec<Question>, HashMap<String, Vec<u8>>, Vec<String>)> {
+        source: &str,
+    ) -> Result<(Vec<Question>, HashMap<String, EntryContent>, Vec<String>)> {
         debug!("Processing single Markdown file: {}", source);
 
-        let questions = tokio::task::spawn_blocking(move || {
-            parse_markdown(&content)
-        })
-        .await
-        .context("Failed to parse Markdown")??;
+        let options = self.config.parser_options.clone();
+        let split = self.config.split_block_bytes;
+        let mut questions = self
+            .run_parse(move || parse_file(&content, &options, split))
+            .await
+            .context("Failed to parse Markdown")??;
 
         debug!("Parsed {} questions from Markdown", questions.len());
 
-        Ok((questions, HashMap::new(), Vec::new()))
+        let warnings = validate_answers(&mut questions)
+            .into_iter()
+            .chain(validate_formulas(&mut questions))
+            .map(|w| format!("{}: {}", source, w))
+            .collect();
+
+        Ok((questions, HashMap::new(), warnings))
     }
 
     /// Process multiple Markdown files in parallel using Rayon
     async fn process_multiple_markdown(
         &self,
         contents: Vec<(String, String)>,
-    ) -> Result<(Vec<Question>, HashMap<String, Vec<u8>>, Vec<String>)> {
+        progress: &ProgressTracker<'_>,
+        gauge: &ConcurrencyGauge,
+    ) -> Result<(Vec<Question>, HashMap<String, EntryContent>, Vec<String>)> {
         info!("Processing {} Markdown files in parallel", contents.len());
 
         let semaphore = self.cpu_semaphore.clone();
         let cpu_workers = self.config.max_cpu_workers;
 
-        let results = stream::iter(contents)
-            .map(|(content, source)| {
-                let sem = semaphore.clone();
-                async move {
-                    let _permit = sem.acquire().await.unwrap();
-
-                    tokio::task::spawn_blocking(move || {
-                        let result = parse_markdown(&content);
+        let parses = stream::iter(contents).map(|(content, source)| {
+            let sem = semaphore.clone();
+            let options = self.config.parser_options.clone();
+            let split = self.config.split_block_bytes;
+            async move {
+                let _permit = sem.acquire().await.unwrap();
+                let _running = gauge.enter();
+
+                let parsed = self
+                    .run_parse(move || {
+                        let result = parse_file(&content, &options, split);
                         (result, source)
                     })
-                    .await
+                    .await;
+                if let Ok((result, source)) = &parsed {
+                    progress.on_file_done(source, result.as_ref().map_or(0, Vec::len));
                 }
-            })
-            .buffer_unordered(cpu_workers)
-            .collect::<Vec<_>>()
-            .await;
+                parsed
+            }
+        });
+        let results = if self.config.preserve_order {
+            parses.buffered(cpu_workers).collect::<Vec<_>>().await
+        } else {
+            parses.buffer_unordered(cpu_workers).collect::<Vec<_>>().await
+        };
 
         let mut all_questions = Vec::new();
         let mut warnings = Vec::new();
 
         for result in results {
             match result {
-                Ok((Ok(questions), source)) => {
+                Ok((Ok(mut questions), source)) => {
                     debug!("Parsed {} questions from {}", questions.len(), source);
+                    record_source(&mut questions, &source);
+                    warnings.extend(
+                        validate_answers(&mut questions)
+                            .into_iter()
+                            .chain(validate_formulas(&mut questions))
+                            .map(|w| format!("{}: {}", source, w)),
+                    );
                     all_questions.extend(questions);
                 }
                 Ok((Err(e), source)) => {
@@ -313,15 +1090,17 @@ where
             }
         }
 
+        if self.config.preserve_order {
+            for (question, sequence) in all_questions.iter_mut().zip(1..) {
+                question.sequence = Some(sequence);
+            }
+        }
+
         Ok((all_questions, HashMap::new(), warnings))
     }
 
     /// Process a single ZIP file
-    async fn process_single_zip(
-        &self,
-        data: Vec<u8>,
-        source: String,
-    ) -> Result<(Vec<Question>, HashMap<String, Vec<u8>>, Vec<String>)> {
+    async fn process_single_zip(&self, data: Vec<u8>, source: &str) -> Result<ZipProcessResult> {
         debug!("Processing single ZIP file: {}", source);
 
         let zip_result = self.zip_processor.process_zip(data).await?;
@@ -332,70 +1111,87 @@ where
             zip_result.images.len()
         );
 
-        Ok((
-            zip_result.questions,
-            zip_result.images,
-            zip_result.warnings,
-        ))
+        Ok(zip_result)
     }
 
-    /// Process multiple ZIP files in parallel
-    async fn process_multiple_zips(
+    /// Read questions from a Moodle GIFT file
+    async fn process_gift(
         &self,
-        files: Vec<(Vec<u8>, String)>,
-    ) -> Result<(Vec<Question>, HashMap<String, Vec<u8>>, Vec<String>)> {
-        info!("Processing {} ZIP files in parallel", files.len());
+        content: String,
+        source: String,
+    ) -> Result<(Vec<Question>, HashMap<String, EntryContent>, Vec<String>)> {
+        debug!("Processing GIFT file: {}", source);
 
-        let semaphore = self.cpu_semaphore.clone();
-        let max_concurrent = self.config.max_concurrent_zips;
+        let import = self
+            .run_parse(move || parse_gift(&content, &source))
+            .await
+            .context("Failed to parse GIFT")?;
 
-        let results = stream::iter(files)
-            .map(|(data, source)| {
-                let sem = semaphore.clone();
-                let processor = &self.zip_processor;
-                async move {
-                    let _permit = sem.acquire().await.unwrap();
-                    processor.process_zip(data).await
-                }
-            })
-            .buffer_unordered(max_concurrent)
-            .collect::<Vec<_>>()
-            .await;
+        debug!("Parsed {} questions from GIFT", import.questions.len());
 
-        let mut all_questions = Vec::new();
-        let mut all_images = HashMap::new();
-        let mut all_warnings = Vec::new();
+        Ok((import.questions, HashMap::new(), import.warnings))
+    }
 
-        for result in results {
-            match result {
-                Ok(zip_result) => {
-                    all_questions.extend(zip_result.questions);
-                    all_images.extend(zip_result.images);
-                    all_warnings.extend(zip_result.warnings);
-                }
-                Err(e) => {
-                    warn!("Failed to process ZIP: {}", e);
-                    all_warnings.push(format!("Failed to process ZIP: {}", e));
-                }
-            }
+    /// Read questions from a spreadsheet
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    async fn process_table(
+        &self,
+        data: Vec<u8>,
+        source: String,
+        mapping: ColumnMapping,
+    ) -> Result<(Vec<Question>, HashMap<String, EntryContent>, Vec<String>)> {
+        debug!("Processing spreadsheet: {}", source);
+
+        let import = self
+            .run_parse(move || TabularImporter::new().with_mapping(mapping).import(&source, &data))
+            .await
+            .context("Failed to read spreadsheet")??;
+
+        debug!("Read {} questions from spreadsheet", import.questions.len());
+
+        Ok((import.questions, HashMap::new(), import.warnings))
+    }
+
+    /// Process multiple ZIP files as a single import job
+    ///
+    /// Archives are extracted in parallel and merged in input order with
+    /// cross-archive dedup, one numbering sequence and shared manifest defaults.
+    async fn process_multiple_zips(
+        &self,
+        files: Vec<(Vec<u8>, String)>,
+        progress: &ProgressTracker<'_>,
+    ) -> Result<ImportJobResult> {
+        info!("Processing {} ZIP files as one import job", files.len());
+
+        let mut job = ImportJob::new();
+        for (data, source) in files {
+            job.add_archive(data, source);
         }
+        let result = job
+            .run_with_progress(&self.zip_processor, self.config.max_concurrent_zips, progress)
+            .await?;
 
         debug!(
-            "Total from all ZIPs: {} questions, {} images",
-            all_questions.len(),
-            all_images.len()
+            "Total from all ZIPs: {} questions, {} images, {} duplicates skipped",
+            result.questions.len(),
+            result.images.len(),
+            result.report.duplicates_skipped
         );
 
-        Ok((all_questions, all_images, all_warnings))
+        Ok(result)
     }
 
     /// Save questions to database in batches
-    async fn save_questions_batched(&self, questions: Vec<Question>) -> Result<BatchSaveResult> {
+    ///
+    /// With dedup enabled, batches are saved one at a time so that each batch
+    /// sees the questions stored by the previous ones.
+    async fn save_questions_batched(
+        &self,
+        questions: Vec<Question>,
+        progress: &ProgressTracker<'_>,
+    ) -> Result<BatchSaveResult> {
         if questions.is_empty() {
-            return Ok(BatchSaveResult {
-                total: 0,
-                failed: 0,
-            });
+            return Ok(BatchSaveResult::default());
         }
 
         info!(
@@ -407,6 +1203,9 @@ where
         let semaphore = self.io_semaphore.clone();
         let batch_size = self.config.batch_size;
         let repository = self.repository.clone();
+        let dedup = self.config.dedup.clone();
+        let concurrency = if dedup.is_some() { 1 } else { self.config.max_io_workers };
+        let gauge = ConcurrencyGauge::default();
 
         // Split questions into batches
         let batches: Vec<_> = questions
@@ -419,78 +1218,459 @@ where
             .map(|(batch_idx, batch)| {
                 let sem = semaphore.clone();
                 let repo = repository.clone();
+                let dedup = dedup.clone();
+                let gauge = &gauge;
                 async move {
                     let _permit = sem.acquire().await.unwrap();
-
-                    match repo.save_batch(&batch).await {
-                        Ok(ids) => {
+                    let running = gauge.enter();
+
+                    let saved = match &dedup {
+                        Some(options) => repo
+                            .save_batch_dedup(&batch, options)
+                            .await
+                            .map(BatchSaveResult::from_outcomes),
+                        None => repo.save_batch(&batch).await.map(|ids| BatchSaveResult {
+                            total: ids.len(),
+                            ..BatchSaveResult::default()
+                        }),
+                    };
+                    drop(running);
+
+                    let result = match saved {
+                        Ok(result) => {
                             debug!(
-                                "Saved batch {} with {} questions",
+                                "Saved batch {} with {} questions ({} duplicates)",
                                 batch_idx,
-                                ids.len()
+                                result.total,
+                                result.duplicates
                             );
                             BatchSaveResult {
-                                total: ids.len(),
-                                failed: 0,
+                                batches_saved: 1,
+                                ..result
                             }
                         }
                         Err(e) => {
                             warn!("Failed to save batch {}: {}", batch_idx, e);
                             BatchSaveResult {
-                                total: 0,
                                 failed: batch.len(),
+                                batches_failed: 1,
+                                ..BatchSaveResult::default()
                             }
                         }
-                    }
+                    };
+                    progress.batch_saved(batch.len());
+                    result
                 }
             })
-            .buffer_unordered(self.config.max_io_workers)
+            // Ordered so per-question outcomes line up with the input
+            .buffered(concurrency)
             .collect::<Vec<_>>()
             .await;
 
-        let mut total_saved = 0;
-        let mut total_failed = 0;
-
+        let mut combined = BatchSaveResult::default();
         for result in results {
-            total_saved += result.total;
-            total_failed += result.failed;
+            combined.total += result.total;
+            combined.updated += result.updated;
+            combined.duplicates += result.duplicates;
+            combined.failed += result.failed;
+            combined.batches_saved += result.batches_saved;
+            combined.batches_failed += result.batches_failed;
+            combined.outcomes.extend(result.outcomes);
         }
+        combined.peak_workers = gauge.peak();
 
-        Ok(BatchSaveResult {
-            total: total_saved,
-            failed: total_failed,
-        })
-    }
-
-    /// Get the processor configuration
-    pub fn config(&self) -> &ProcessorConfig {
-        &self.config
+        Ok(combined)
     }
 
-    /// Get the number of available CPU cores
-    pub fn cpu_cores(&self) -> usize {
-        self.config.max_cpu_workers
-    }
-}
+    /// Save all questions in one import session
+    ///
+    /// Batches are written in order; if any batch fails the session is rolled
+    /// back and every question is reported as failed.
+    async fn save_questions_in_session(
+        &self,
+        questions: Vec<Question>,
+        progress: &ProgressTracker<'_>,
+    ) -> Result<BatchSaveResult> {
+        if questions.is_empty() {
+            return Ok(BatchSaveResult::default());
+        }
 
-/// Result of a batch save operation
-#[derive(Debug)]
-struct BatchSaveResult {
-    /// Number of successfully saved questions
-    total: usize,
-    /// Number of failed questions
-    failed: usize,
-}
+        let _permit = self.io_semaphore.acquire().await?;
+        let mut session = self.repository.begin_import().await?;
+        let session_id = session.id();
+        info!("Saving {} questions in import session {}", questions.len(), session_id);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::database::MockRepository;
+        let batches = questions.len().div_ceil(self.config.batch_size);
+        let mut combined = BatchSaveResult {
+            session_id: Some(session_id),
+            peak_workers: 1,
+            ..BatchSaveResult::default()
+        };
+        let mut failure = None;
+        for (batch_idx, batch) in questions.chunks(self.config.batch_size).enumerate() {
+            let saved = match &self.config.dedup {
+                Some(options) => session
+                    .save_batch_dedup(batch, options)
+                    .await
+                    .map(BatchSaveResult::from_outcomes),
+                None => session.save_batch(batch).await.map(|ids| BatchSaveResult {
+                    total: ids.len(),
+                    ..BatchSaveResult::default()
+                }),
+            };
+
+            match saved {
+                Ok(result) => {
+                    debug!("Saved batch {} in session {}", batch_idx, session_id);
+                    progress.batch_saved(batch.len());
+                    combined.total += result.total;
+                    combined.updated += result.updated;
+                    combined.duplicates += result.duplicates;
+                    combined.batches_saved += 1;
+                    combined.outcomes.extend(result.outcomes);
+                }
+                Err(e) => {
+                    failure = Some(format!("batch {} failed: {}", batch_idx, e));
+                    break;
+                }
+            }
+        }
 
-    fn create_test_markdown() -> String {
-        r#"# What is 2+2?
+        let failure = match failure {
+            None => match session.commit().await {
+                Ok(written) => {
+                    info!("Committed import session {} ({} questions)", session_id, written);
+                    return Ok(combined);
+                }
+                Err(e) => format!("commit failed: {}", e),
+            },
+            Some(failure) => {
+                if let Err(e) = session.rollback().await {
+                    warn!("Failed to roll back import session {}: {}", session_id, e);
+                }
+                failure
+            }
+        };
 
-* A. 3
+        warn!("Import session {} rolled back: {}", session_id, failure);
+        // Nothing written survives the rollback
+        Ok(BatchSaveResult {
+            failed: questions.len(),
+            batches_failed: batches,
+            peak_workers: 1,
+            session_id: Some(session_id),
+            rollback_reason: Some(failure),
+            ..BatchSaveResult::default()
+        })
+    }
+
+    /// Write the updates and removals of an incremental import
+    ///
+    /// Runs after the new questions are saved and is skipped if their import
+    /// session was rolled back. Source hashes are only recorded when every
+    /// write succeeded, so a failed import is retried in full next time.
+    async fn apply_incremental(
+        &self,
+        plan: IncrementalPlan,
+        saved: &BatchSaveResult,
+        progress: &ProgressTracker<'_>,
+    ) -> IncrementalResult {
+        let mut applied = IncrementalResult {
+            unchanged_questions: plan.unchanged_questions,
+            unchanged_files: plan.unchanged_files,
+            ..IncrementalResult::default()
+        };
+        if saved.rollback_reason.is_some() {
+            applied.failed = plan.updates.len();
+            return applied;
+        }
+
+        for question in &plan.updates {
+            match self.repository.update(question).await {
+                Ok(true) => applied.updated += 1,
+                Ok(false) => {
+                    applied.failed += 1;
+                    applied.warnings.push(format!("Question {} disappeared before it could be updated", question.id));
+                }
+                Err(e) => {
+                    warn!("Failed to update question {}: {}", question.id, e);
+                    applied.failed += 1;
+                }
+            }
+        }
+        progress.batch_saved(plan.updates.len());
+
+        let mut removal_failed = false;
+        if !plan.removals.is_empty() {
+            match self.repository.delete_batch(&plan.removals).await {
+                Ok(removed) => applied.removed = removed as usize,
+                Err(e) => {
+                    warn!("Failed to remove {} questions: {}", plan.removals.len(), e);
+                    applied.warnings.push(format!("Failed to remove {} questions: {}", plan.removals.len(), e));
+                    removal_failed = true;
+                }
+            }
+            progress.batch_saved(plan.removals.len());
+        }
+
+        if saved.failed > 0 || applied.failed > 0 || removal_failed {
+            return applied;
+        }
+        for source in &plan.sources {
+            if let Err(e) = self.repository.save_source(source).await {
+                warn!("Failed to record import of {}: {}", source.source, e);
+                applied.warnings.push(format!("{}: not recorded, so it will be imported again: {}", source.source, e));
+            }
+        }
+        applied
+    }
+
+    /// Get the repository questions are saved to
+    pub fn repository(&self) -> &Arc<R> {
+        &self.repository
+    }
+
+    /// Get the processor configuration
+    pub fn config(&self) -> &ProcessorConfig {
+        &self.config
+    }
+
+    /// Get the number of available CPU cores
+    pub fn cpu_cores(&self) -> usize {
+        self.config.max_cpu_workers
+    }
+}
+
+/// Parse one Markdown file, in parallel blocks if it is larger than `split`
+///
+/// Runs inside [`SingleMachineProcessor::run_parse`], so the blocks share
+/// the processor's pool with the other files of the job.
+fn parse_file(content: &str, options: &ParserOptions, split: Option<usize>) -> crate::error::Result<Vec<Question>> {
+    match split {
+        Some(bytes) if content.len() > bytes => parse_markdown_parallel(content, options, bytes),
+        _ => parse_markdown_with_options(content, options),
+    }
+}
+
+/// Record `source` as the file questions without provenance came from
+fn record_source(questions: &mut [Question], source: &str) {
+    for question in questions {
+        let provenance = question.provenance.get_or_insert_with(Provenance::default);
+        if provenance.source_file.is_none() {
+            provenance.source_file = Some(source.to_string());
+        }
+    }
+}
+
+/// Outcome of the updates and removals of an incremental import
+#[derive(Debug, Default)]
+struct IncrementalResult {
+    /// Number of questions updated in place
+    updated: usize,
+    /// Number of updates that failed
+    failed: usize,
+    /// Number of questions removed
+    removed: usize,
+    /// Number of questions left as they were
+    unchanged_questions: usize,
+    /// Files skipped as unchanged
+    unchanged_files: Vec<String>,
+    /// Problems worth reporting
+    warnings: Vec<String>,
+}
+
+impl IncrementalResult {
+    /// What applying `plan` would do, without writing it
+    fn preview(plan: IncrementalPlan) -> Self {
+        Self {
+            updated: plan.updates.len(),
+            removed: plan.removals.len(),
+            unchanged_questions: plan.unchanged_questions,
+            unchanged_files: plan.unchanged_files,
+            ..Self::default()
+        }
+    }
+}
+
+/// Result of a batch save operation
+#[derive(Debug, Default)]
+struct BatchSaveResult {
+    /// Number of successfully saved questions
+    total: usize,
+    /// Number of saved questions that updated an existing record
+    updated: usize,
+    /// Number of questions skipped as duplicates
+    duplicates: usize,
+    /// Number of failed questions
+    failed: usize,
+    /// Per-question outcomes (dedup saves only)
+    outcomes: Vec<SaveOutcome>,
+    /// Import session used for the save, if any
+    session_id: Option<Uuid>,
+    /// Why the import session was rolled back
+    rollback_reason: Option<String>,
+    /// Number of batches written
+    batches_saved: usize,
+    /// Number of batches that failed or were rolled back
+    batches_failed: usize,
+    /// Most batches written at once
+    peak_workers: usize,
+}
+
+impl BatchSaveResult {
+    /// Summarize the outcomes of a deduplicating save
+    fn from_outcomes(outcomes: Vec<SaveOutcome>) -> Self {
+        let mut result = Self::default();
+        for outcome in &outcomes {
+            match outcome {
+                SaveOutcome::Inserted { .. } => result.total += 1,
+                SaveOutcome::Updated { .. } => {
+                    result.total += 1;
+                    result.updated += 1;
+                }
+                SaveOutcome::DuplicateSkipped { .. } => result.duplicates += 1,
+            }
+        }
+        result.outcomes = outcomes;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{ImportSession, ListParams, MockRepository, Page};
+    use crate::error::{Md2DbError, Result};
+    use crate::parser::parse_markdown;
+    use async_trait::async_trait;
+
+    /// Repository whose import sessions fail on the second batch
+    struct FlakyRepository(MockRepository);
+
+    struct FlakySession {
+        inner: Box<dyn ImportSession>,
+        batches: usize,
+    }
+
+    #[async_trait]
+    impl ImportSession for FlakySession {
+        fn id(&self) -> Uuid {
+            self.inner.id()
+        }
+
+        async fn save_batch(&mut self, questions: &[Question]) -> Result<Vec<Uuid>> {
+            self.batches += 1;
+            if self.batches >= 2 {
+                return Err(Md2DbError::repository("", "connection reset"));
+            }
+            self.inner.save_batch(questions).await
+        }
+
+        async fn save_batch_dedup(
+            &mut self,
+            questions: &[Question],
+            options: &DedupOptions,
+        ) -> Result<Vec<SaveOutcome>> {
+            self.inner.save_batch_dedup(questions, options).await
+        }
+
+        async fn commit(self: Box<Self>) -> Result<usize> {
+            self.inner.commit().await
+        }
+
+        async fn rollback(self: Box<Self>) -> Result<()> {
+            self.inner.rollback().await
+        }
+    }
+
+    #[async_trait]
+    impl QuestionRepository for FlakyRepository {
+        async fn save_batch(&self, questions: &[Question]) -> Result<Vec<Uuid>> {
+            self.0.save_batch(questions).await
+        }
+        async fn save_batch_dedup(&self, questions: &[Question], options: &DedupOptions) -> Result<Vec<SaveOutcome>> {
+            self.0.save_batch_dedup(questions, options).await
+        }
+        async fn find_by_id(&self, id: Uuid) -> Result<Option<Question>> {
+            self.0.find_by_id(id).await
+        }
+        async fn find_by_type(&self, qtype: &crate::models::QuestionType) -> Result<Vec<Question>> {
+            self.0.find_by_type(qtype).await
+        }
+        async fn find_by_tag(&self, tag: &str) -> Result<Vec<Question>> {
+            self.0.find_by_tag(tag).await
+        }
+        async fn list(&self, params: &ListParams) -> Result<Page<Question>> {
+            self.0.list(params).await
+        }
+        async fn update_as(&self, question: &Question, actor: Option<&str>) -> Result<bool> {
+            self.0.update_as(question, actor).await
+        }
+        async fn history(&self, id: Uuid) -> Result<Vec<crate::models::QuestionRevision>> {
+            self.0.history(id).await
+        }
+        async fn delete(&self, id: Uuid) -> Result<bool> {
+            self.0.delete(id).await
+        }
+        async fn delete_batch(&self, ids: &[Uuid]) -> Result<u64> {
+            self.0.delete_batch(ids).await
+        }
+        async fn restore(&self, id: Uuid) -> Result<bool> {
+            self.0.restore(id).await
+        }
+        async fn purge(&self, before: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+            self.0.purge(before).await
+        }
+        async fn create_bank(&self, bank: &crate::models::QuestionBank) -> Result<Uuid> {
+            self.0.create_bank(bank).await
+        }
+        async fn find_bank(&self, id: Uuid) -> Result<Option<crate::models::QuestionBank>> {
+            self.0.find_bank(id).await
+        }
+        async fn list_banks(&self) -> Result<Vec<crate::models::QuestionBank>> {
+            self.0.list_banks().await
+        }
+        async fn assign_to_bank(&self, bank_id: Uuid, question_ids: &[Uuid]) -> Result<u64> {
+            self.0.assign_to_bank(bank_id, question_ids).await
+        }
+        async fn find_by_bank(&self, bank_id: Uuid) -> Result<Vec<Question>> {
+            self.0.find_by_bank(bank_id).await
+        }
+        async fn begin_import(&self) -> Result<Box<dyn ImportSession>> {
+            Ok(Box::new(FlakySession {
+                inner: self.0.begin_import().await?,
+                batches: 0,
+            }))
+        }
+        async fn save_job(&self, job: &crate::jobs::JobRecord) -> Result<()> {
+            self.0.save_job(job).await
+        }
+
+        async fn find_job(&self, id: Uuid) -> Result<Option<crate::jobs::JobRecord>> {
+            self.0.find_job(id).await
+        }
+
+        async fn list_jobs(&self) -> Result<Vec<crate::jobs::JobRecord>> {
+            self.0.list_jobs().await
+        }
+
+        async fn save_source(&self, source: &crate::models::ImportedSource) -> Result<()> {
+            self.0.save_source(source).await
+        }
+
+        async fn list_sources(&self, bank_id: Uuid) -> Result<Vec<crate::models::ImportedSource>> {
+            self.0.list_sources(bank_id).await
+        }
+
+        async fn ping(&self) -> Result<()> {
+            self.0.ping().await
+        }
+    }
+
+    fn create_test_markdown() -> String {
+        r#"# What is 2+2?
+
+* A. 3
 * B. 4
 * C. 5
 
@@ -509,6 +1689,7 @@ mod tests {
         assert!(config.max_io_workers > 0);
         assert_eq!(config.batch_size, 100);
         assert_eq!(config.max_concurrent_zips, 4);
+        assert!(config.preserve_order);
     }
 
     #[test]
@@ -517,12 +1698,28 @@ mod tests {
             .with_cpu_workers(8)
             .with_io_workers(16)
             .with_batch_size(50)
-            .with_max_concurrent_zips(2);
+            .with_max_concurrent_zips(2)
+            .with_preserve_order(false);
 
         assert_eq!(config.max_cpu_workers, 8);
         assert_eq!(config.max_io_workers, 16);
         assert_eq!(config.batch_size, 50);
         assert_eq!(config.max_concurrent_zips, 2);
+        assert!(!config.preserve_order);
+    }
+
+    #[test]
+    fn test_reconfigured_processor_shares_the_pool() {
+        let processor = SingleMachineProcessor::with_config(
+            MockRepository::new(),
+            ProcessorConfig::default().with_cpu_workers(2),
+        );
+        let derived = processor.reconfigured(ProcessorConfig::default().with_cpu_workers(8).with_dry_run(true));
+
+        assert!(derived.config().dry_run);
+        assert_eq!(derived.config().max_cpu_workers, 2);
+        assert!(Arc::ptr_eq(derived.repository(), processor.repository()));
+        assert!(Arc::ptr_eq(derived.pool.as_ref().unwrap(), processor.pool.as_ref().unwrap()));
     }
 
     #[test]
@@ -587,6 +1784,35 @@ mod tests {
         assert_eq!(result.success_rate(), 100.0);
     }
 
+    #[cfg(feature = "csv")]
+    #[tokio::test]
+    async fn test_process_tabular() {
+        let processor = SingleMachineProcessor::new(MockRepository::new());
+        let input = InputSource::Tabular {
+            data: b"stem,optionA,optionB,answer\n1+1=?,2,3,A\n2+2=?,4,5,C\n".to_vec(),
+            source: "bank.csv".to_string(),
+            mapping: ColumnMapping::default(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+        assert_eq!(result.saved_questions, 2);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].starts_with("bank.csv row 3: INVALID_ANSWER"), "{:?}", result.warnings);
+    }
+
+    #[tokio::test]
+    async fn test_process_gift() {
+        let processor = SingleMachineProcessor::new(MockRepository::new());
+        let input = InputSource::Gift {
+            content: "What is 2+2? {~3 =4}\n\nMatch. {=a -> b =c -> d}\n".to_string(),
+            source: "quiz.gift".to_string(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+        assert_eq!(result.saved_questions, 1);
+        assert_eq!(result.warnings, ["quiz.gift question 2: matching questions are not supported, skipped"]);
+    }
+
     #[tokio::test]
     async fn test_process_multiple_markdown() {
         let repo = MockRepository::new();
@@ -606,8 +1832,476 @@ mod tests {
         assert!(result.is_success());
     }
 
+    /// Reporter that records every call
+    #[derive(Default)]
+    struct RecordingReporter {
+        events: Mutex<Vec<String>>,
+        percents: Mutex<Vec<f64>>,
+    }
+
+    impl ProgressReporter for RecordingReporter {
+        fn on_stage(&self, stage: ProcessStage) {
+            self.events.lock().unwrap().push(format!("{:?}", stage));
+        }
+
+        fn on_file_done(&self, source: &str, questions: usize) {
+            self.events.lock().unwrap().push(format!("{} {}", source, questions));
+        }
+
+        fn on_batch_saved(&self, done: usize, total: usize) {
+            self.events.lock().unwrap().push(format!("{}/{}", done, total));
+        }
+
+        fn on_percent(&self, percent: f64) {
+            self.percents.lock().unwrap().push(percent);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_reports_progress() {
+        let config = ProcessorConfig::default().with_batch_size(3).with_io_workers(1);
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let contents = vec![
+            (create_test_markdown(), "test1.md".to_string()),
+            (create_test_markdown(), "test2.md".to_string()),
+        ];
+
+        let reporter = RecordingReporter::default();
+        let result = processor
+            .process_with_progress(InputSource::MultipleMarkdown { contents }, &reporter)
+            .await
+            .unwrap();
+        assert_eq!(result.saved_questions, 4);
+
+        let mut events = reporter.events.into_inner().unwrap();
+        // Files finish in any order
+        events[1..3].sort();
+        assert_eq!(events, ["Parsing", "test1.md 2", "test2.md 2", "Saving", "3/4", "4/4", "Done"]);
+        let percents = reporter.percents.into_inner().unwrap();
+        assert_eq!(percents, [25.0, 50.0, 50.0, 87.5, 100.0, 100.0]);
+    }
+
+    #[tokio::test]
+    async fn test_process_with_dedup() {
+        let repo = MockRepository::new();
+        let config = ProcessorConfig::default()
+            .with_batch_size(1)
+            .with_dedup(DedupOptions::exact_only());
+        let processor = SingleMachineProcessor::with_config(repo, config);
+
+        let contents = vec![
+            (create_test_markdown(), "test1.md".to_string()),
+            (create_test_markdown(), "test2.md".to_string()),
+        ];
+        let result = processor
+            .process(InputSource::MultipleMarkdown { contents })
+            .await
+            .unwrap();
+
+        assert_eq!(result.total_questions, 4);
+        assert_eq!(result.saved_questions, 2);
+        assert_eq!(result.duplicate_questions, 2);
+        assert_eq!(result.outcomes.len(), 4);
+        assert!(result.is_success());
+        assert_eq!(result.success_rate(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_process_with_parser_preset() {
+        let config = ProcessorConfig::default()
+            .with_parser_options(crate::presets::preset("xuekewang-export").unwrap());
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+
+        let input = InputSource::Markdown {
+            content: "1．1 + 1 = ?\nA．1 B．2\n\n【答案】B\n\n2．2 + 2 = ?\nA．4 B．5\n\n【答案】A\n".to_string(),
+            source: "zxxk.md".to_string(),
+        };
+        let result = processor.process(input).await.unwrap();
+
+        assert_eq!(result.saved_questions, 2);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[tokio::test]
+    async fn test_process_into_bank() {
+        let bank = crate::models::QuestionBank::new("Midterm");
+        let config = ProcessorConfig::default().with_bank_id(bank.id);
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let input = || InputSource::Markdown {
+            content: create_test_markdown(),
+            source: "test.md".to_string(),
+        };
+
+        let err = processor.process(input()).await.unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+
+        processor.repository().create_bank(&bank).await.unwrap();
+        let result = processor.process(input()).await.unwrap();
+        assert_eq!(result.saved_questions, 2);
+        let in_bank = processor.repository().find_by_bank(bank.id).await.unwrap();
+        assert_eq!(in_bank.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_process_dry_run() {
+        let config = ProcessorConfig::default()
+            .with_dry_run(true)
+            .with_validation(QuestionValidator::new().with_require_answer(false));
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let input = InputSource::Markdown {
+            content: format!("{}\n\n# \u{7}", create_test_markdown()),
+            source: "test.md".to_string(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+        assert!(result.dry_run);
+        assert_eq!(result.saved_questions, 2);
+        assert_eq!(result.rejected.len(), 1);
+        assert_eq!(result.sample_questions.len(), 2);
+        assert_eq!(result.sample_questions[0].stem, "What is 2+2?");
+        let stored = processor.repository().list(&ListParams::default()).await.unwrap();
+        assert!(stored.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_process_incremental() {
+        let bank = crate::models::QuestionBank::new("Weekly");
+        let config = ProcessorConfig::default().with_bank_id(bank.id).with_incremental(true);
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        processor.repository().create_bank(&bank).await.unwrap();
+        let input = |second: &str| InputSource::MultipleMarkdown {
+            contents: vec![
+                (create_test_markdown(), "week1.md".to_string()),
+                (second.to_string(), "week2.md".to_string()),
+            ],
+        };
+
+        let first = processor
+            .process(input("# What is 3+3?\n\n* A. 5\n* B. 6\n\nAnswer: A\n\n# Name a prime nmuber\n\nAnswer: 7"))
+            .await
+            .unwrap();
+        assert_eq!((first.added_questions, first.total_questions), (4, 4));
+        let stored = processor.repository().find_by_bank(bank.id).await.unwrap();
+        let id_of = |stem: &str| stored.iter().find(|q| q.stem.contains(stem)).unwrap().id;
+        let edited_id = id_of("3+3");
+
+        // week1.md is unchanged; week2.md fixes an answer and a typo and adds a question
+        let second = processor
+            .process(input("# What is 3+3?\n\n* A. 5\n* B. 6\n\nAnswer: B\n\n# Name a prime number\n\nAnswer: 7\n\n# Name a colour\n\nAnswer: red"))
+            .await
+            .unwrap();
+        assert_eq!(second.unchanged_files, ["week1.md"]);
+        assert_eq!(second.unchanged_questions, 2);
+        assert_eq!(second.updated_questions, 2);
+        assert_eq!(second.added_questions, 1);
+        assert_eq!(second.removed_questions, 0);
+        assert!(second.is_success());
+
+        let edited = processor.repository().find_by_id(edited_id).await.unwrap().unwrap();
+        assert_eq!(edited.answer.as_deref(), Some("B"));
+        assert_eq!(processor.repository().find_by_bank(bank.id).await.unwrap().len(), 5);
+
+        // Dropping questions from a file removes them
+        let third = processor.process(input("# Name a colour\n\nAnswer: red")).await.unwrap();
+        assert_eq!(third.unchanged_files, ["week1.md"]);
+        assert_eq!(third.removed_questions, 2);
+        assert!(processor.repository().find_by_id(edited_id).await.unwrap().is_none());
+
+        let unbanked = ProcessorConfig::default().with_incremental(true);
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), unbanked);
+        assert!(processor.process(input("")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_process_stores_images() {
+        use crate::media::{FsMediaStore, StoredMedia};
+        use std::io::Write;
+
+        struct BrokenStore;
+
+        #[async_trait]
+        impl MediaStore for BrokenStore {
+            async fn put(&self, _data: &[u8]) -> Result<StoredMedia> {
+                Err(Md2DbError::media("", "disk full"))
+            }
+            async fn get(&self, _hash: &str) -> Result<Option<Vec<u8>>> {
+                Ok(None)
+            }
+        }
+
+        let mut data = Vec::new();
+        {
+            let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(&mut data));
+            let options = ::zip::write::SimpleFileOptions::default();
+            writer.start_file("exam.md", options).unwrap();
+            writer.write_all(b"# Which plot?\n\n![a](a.png)\n\n![b](b.png)").unwrap();
+            writer.start_file("a.png", options).unwrap();
+            writer.write_all(&crate::media::test_png(4, 3)).unwrap();
+            writer.start_file("b.png", options).unwrap();
+            writer.write_all(&crate::media::test_png(2, 2)).unwrap();
+            writer.finish().unwrap();
+        }
+        let input = || InputSource::Zip {
+            data: data.clone(),
+            source: "exam.zip".to_string(),
+        };
+
+        let root = tempfile::tempdir().unwrap();
+        let store: Arc<dyn MediaStore> = Arc::new(FsMediaStore::new(root.path()).unwrap());
+        let processor = SingleMachineProcessor::new(MockRepository::new()).with_media_store(store);
+        let first = processor.process(input()).await.unwrap();
+        assert_eq!((first.stored_images, first.deduplicated_images, first.failed_images), (2, 0, 0));
+        let second = processor.process(input()).await.unwrap();
+        assert_eq!((second.stored_images, second.deduplicated_images, second.failed_images), (0, 2, 0));
+
+        let processor = SingleMachineProcessor::new(MockRepository::new()).with_media_store(Arc::new(BrokenStore));
+        let broken = processor.process(input()).await.unwrap();
+        assert_eq!(broken.failed_images, 2);
+        assert_eq!(broken.saved_questions, 1);
+        assert_eq!(broken.warnings.iter().filter(|w| w.contains("disk full")).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_process_metrics() {
+        let config = ProcessorConfig::default().with_batch_size(1).with_cpu_workers(2);
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let contents = (0..3)
+            .map(|i| (format!("# Question {}?\n\n* A. 1\n* B. 2", i), format!("q{}.md", i)))
+            .collect();
+
+        let result = processor.process(InputSource::MultipleMarkdown { contents }).await.unwrap();
+        let metrics = &result.metrics;
+        assert_eq!(metrics.batches_saved, 3);
+        assert_eq!(metrics.batches_failed, 0);
+        assert!((1..=2).contains(&metrics.peak_parse_workers));
+        assert!(metrics.peak_db_workers >= 1);
+        assert!(metrics.questions_per_sec > 0.0);
+        assert_eq!(metrics.extraction_ms, 0);
+
+        let mut data = Vec::new();
+        {
+            use std::io::Write;
+            let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(&mut data));
+            for name in ["a.md", "b.md"] {
+                writer.start_file(name, ::zip::write::SimpleFileOptions::default()).unwrap();
+                writer.write_all(format!("# From {}?\n\n* A. 1\n* B. 2", name).as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        let input = InputSource::Zip {
+            data,
+            source: "exam.zip".to_string(),
+        };
+        let result = processor.process(input).await.unwrap();
+        assert_eq!(result.metrics.batches_saved, 2);
+        assert!(result.metrics.peak_parse_workers >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_process_transactional() {
+        let config = ProcessorConfig::default().with_batch_size(1).with_transactional(true);
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let input = InputSource::Markdown {
+            content: create_test_markdown(),
+            source: "test.md".to_string(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+        assert_eq!(result.saved_questions, 2);
+        assert!(result.session_id.is_some());
+        assert!(!result.rolled_back);
+        let stored = processor.repository().list(&ListParams::default()).await.unwrap();
+        assert_eq!(stored.total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_process_transactional_rolls_back_on_failure() {
+        let config = ProcessorConfig::default().with_batch_size(1).with_transactional(true);
+        let processor = SingleMachineProcessor::with_config(FlakyRepository(MockRepository::new()), config);
+        let input = InputSource::Markdown {
+            content: create_test_markdown(),
+            source: "test.md".to_string(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+        assert!(result.rolled_back);
+        assert_eq!(result.saved_questions, 0);
+        assert_eq!(result.failed_questions, 2);
+        assert!(result.warnings.iter().any(|w| w.contains("rolled back: batch 1 failed: connection reset")));
+        assert_eq!(result.metrics.batches_saved, 0);
+        assert_eq!(result.metrics.batches_failed, 2);
+
+        // The first batch was saved before the failure, but nothing is left behind
+        let stored = processor.repository().list(&ListParams::default()).await.unwrap();
+        assert_eq!(stored.total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_process_validates_answers() {
+        let processor = SingleMachineProcessor::new(MockRepository::new());
+
+        let input = InputSource::Markdown {
+            content: "# Q1\n\n* A. 1\n* B. 2\n\n答案：2\n\n# Q2\n\n* A. 1\n* B. 2\n\n答案：Z".to_string(),
+            source: "answers.md".to_string(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.warnings[0].starts_with("answers.md: CORRECTED_ANSWER"));
+        assert!(result.warnings[1].starts_with("answers.md: INVALID_ANSWER"));
+    }
+
+    #[tokio::test]
+    async fn test_process_runs_transforms() {
+        use crate::transform::{AddTags, NormalizeText, StableIds};
+
+        let ids = StableIds::new("bank");
+        let config = ProcessorConfig::default()
+            .with_transform(NormalizeText::new())
+            .with_transform(AddTags::new(["imported"]))
+            .with_transform(ids.clone());
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let input = || InputSource::Markdown {
+            content: "# What is 2+2?   \n\n* A. 3\n* B. 4".to_string(),
+            source: "quiz.md".to_string(),
+        };
+
+        let result = processor.process(input()).await.unwrap();
+        assert_eq!(result.saved_questions, 1);
+        let stored = processor.repository().list(&ListParams::default()).await.unwrap().items;
+        assert_eq!(stored[0].tags, ["imported"]);
+        assert_eq!(stored[0].stem, "What is 2+2?");
+        assert_eq!(stored[0].id, ids.id_for(&stored[0]));
+    }
+
+    #[tokio::test]
+    async fn test_process_drops_repeats_across_files() {
+        let config = ProcessorConfig::default().with_batch_dedup(BatchDedup::new().with_drop(true));
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let contents = ["unit1.md", "unit2.md", "review.md"]
+            .iter()
+            .enumerate()
+            .map(|(i, source)| (format!("# {}. Which sort is stable?\n\n* A. Quick\n* B. Merge", i + 1), source.to_string()))
+            .collect();
+
+        let result = processor.process(InputSource::MultipleMarkdown { contents }).await.unwrap();
+        assert_eq!(result.total_questions, 3);
+        assert_eq!(result.saved_questions, 1);
+        assert_eq!(result.duplicate_questions, 2);
+        assert_eq!(result.duplicate_clusters.len(), 1);
+        let sources: Vec<_> = result.duplicate_clusters[0].duplicates.iter().map(|m| m.source.as_str()).collect();
+        assert_eq!(sources.len(), 2);
+        assert!(!sources.contains(&result.duplicate_clusters[0].kept.source.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_process_rejects_invalid_questions() {
+        let config = ProcessorConfig::default().with_validation(QuestionValidator::new());
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let input = InputSource::Markdown {
+            content: "# What is 1+1?\n\n* A. 1\n* B. 2\n\n答案：B\n\n# What is 2+2?\n\n* A. 3\n* B. 4\n\n答案：Z".to_string(),
+            source: "answers.md".to_string(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+        assert_eq!(result.total_questions, 2);
+        assert_eq!(result.saved_questions, 1);
+        assert_eq!(result.rejected.len(), 1);
+        assert_eq!(result.rejected[0].question.stem, "What is 2+2?");
+        assert_eq!(result.rejected[0].reasons, ["no correct answer"]);
+        assert_eq!(result.success_rate(), 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_process_reports_inconsistent_answers() {
+        // Markdown questions are untyped until classified
+        struct AsChoice;
+        impl Transform for AsChoice {
+            fn apply(&self, question: Question) -> Question {
+                Question {
+                    qtype: crate::models::QuestionType::Choice,
+                    ..question
+                }
+            }
+        }
+
+        let config = ProcessorConfig::default()
+            .with_transform(AsChoice)
+            .with_consistency_check(ConsistencyChecker::new());
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+        let input = InputSource::Markdown {
+            content: "# What is 1+1?\n\n* A. 1\n* B. 2\n\n答案：B\n\n# Which are even?\n\n* A. 2\n* B. 4\n\n答案：AB".to_string(),
+            source: "answers.md".to_string(),
+        };
+
+        let result = processor.process(input).await.unwrap();
+        assert_eq!(result.saved_questions, 2);
+        assert_eq!(result.inconsistent.len(), 1);
+        assert_eq!(result.inconsistent[0].issues[0].kind, crate::consistency::ConsistencyIssueKind::SeveralCorrectOptions);
+    }
+
+    #[tokio::test]
+    async fn test_process_with_topic_tagger() {
+        let processor = SingleMachineProcessor::new(MockRepository::new())
+            .with_topic_tagger(TopicTagger::default());
+
+        let input = InputSource::Markdown {
+            content: "# 求函数的导数\n\n* A. 1\n* B. 2".to_string(),
+            source: "math.md".to_string(),
+        };
+
+        processor.process(input).await.unwrap();
+
+        let repo = processor.repository();
+        assert_eq!(repo.find_by_tag("数学").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_processors_have_their_own_pools() {
+        let small = SingleMachineProcessor::with_config(
+            MockRepository::new(),
+            ProcessorConfig::default().with_cpu_workers(1),
+        );
+        let large = SingleMachineProcessor::with_config(
+            MockRepository::new(),
+            ProcessorConfig::default().with_cpu_workers(3),
+        );
+
+        let threads = |p: &SingleMachineProcessor<MockRepository>| p.pool.as_ref().unwrap().current_num_threads();
+        assert_eq!(threads(&small), 1);
+        assert_eq!(threads(&large), 3);
+        assert_eq!(small.run_parse(rayon::current_num_threads).await.unwrap(), 1);
+        assert_eq!(large.run_parse(rayon::current_num_threads).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_process_splits_large_markdown() {
+        use crate::sample::{generate_markdown, SampleConfig};
+
+        let markdown = generate_markdown(&SampleConfig::default().with_count(300));
+        let config = ProcessorConfig::default().with_cpu_workers(3).with_split_block_bytes(Some(1024));
+        let processor = SingleMachineProcessor::with_config(MockRepository::new(), config);
+
+        let whole = parse_file(&markdown, &ParserOptions::default(), None).unwrap();
+        let content = markdown.clone();
+        let split = processor
+            .run_parse(move || parse_file(&content, &ParserOptions::default(), Some(1024)))
+            .await
+            .unwrap()
+            .unwrap();
+        let stems = |questions: &[Question]| questions.iter().map(|q| q.stem.clone()).collect::<Vec<_>>();
+        assert_eq!(stems(&split), stems(&whole));
+
+        let input = InputSource::Markdown { content: markdown, source: "large.md".to_string() };
+        let result = processor.process(input).await.unwrap();
+        assert_eq!(result.saved_questions, whole.len());
+    }
+
     #[test]
     fn test_parse_markdown_parallel() {
+        use rayon::prelude::*;
+
         // Test that Rayon parallel parsing works
         let contents: Vec<_> = (0..10)
             .map(|i| {
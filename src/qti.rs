@@ -0,0 +1,279 @@
+//! IMS QTI 1.2 export
+//!
+//! Writes questions as a QTI 1.2 `questestinterop` object bank, the format
+//! Canvas, Blackboard and most other learning platforms import question
+//! banks from. Each question becomes one `item` whose `question_type`
+//! metadata follows Canvas: single and multiple answer choice, true/false,
+//! short answer for fill-in-the-blank, and essay for everything else. The
+//! document is written as a header, items and a footer, so it can be
+//! streamed one page of questions at a time.
+
+use crate::answer::{FALSE_WORDS, TRUE_WORDS};
+use crate::bidi::export_text;
+use crate::gift::correct_options;
+use crate::models::{Question, QuestionType};
+use crate::render::{option_letter, strip_option_prefix};
+
+/// Start of a QTI document, up to the first item
+pub const QTI_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<questestinterop xmlns=\"http://www.imsglobal.org/xsd/ims_qtiasiv1p2\">\n\
+  <objectbank ident=\"md2db\">\n";
+
+/// End of a QTI document, after the last item
+pub const QTI_FOOTER: &str = "  </objectbank>\n</questestinterop>\n";
+
+/// Write questions as a complete QTI document
+pub fn write_qti(questions: &[Question]) -> String {
+    let mut out = String::from(QTI_HEADER);
+    for question in questions {
+        out.push_str(&question_to_qti(question));
+    }
+    out.push_str(QTI_FOOTER);
+    out
+}
+
+/// Write one question as a QTI `item`
+///
+/// Questions with correct options are written as choice questions whatever
+/// their type, like the GIFT export. A true/false question without options
+/// gets `True` and `False` choices; one whose answer is neither, and a
+/// fill-in-the-blank question without an answer, are written as essays.
+pub fn question_to_qti(question: &Question) -> String {
+    let correct = correct_options(question);
+    let options: Vec<_> = question
+        .options
+        .iter()
+        .enumerate()
+        .map(|(i, o)| export_text(question, strip_option_prefix(&o.content, i)))
+        .collect();
+    let options: Vec<&str> = options.iter().map(AsRef::as_ref).collect();
+    let answer = question.answer.as_deref().map(str::trim).filter(|a| !a.is_empty());
+
+    let item = Item::new(question);
+    match question.qtype {
+        _ if !correct.is_empty() => {
+            let kind = if correct.len() > 1 {
+                "multiple_answers_question"
+            } else {
+                "multiple_choice_question"
+            };
+            item.choice(kind, &options, &correct)
+        }
+        QuestionType::TrueFalse if question.options.is_empty() => {
+            let answer = answer.unwrap_or_default().to_lowercase();
+            if TRUE_WORDS.contains(&answer.as_str()) {
+                item.choice("true_false_question", &["True", "False"], &[0])
+            } else if FALSE_WORDS.contains(&answer.as_str()) {
+                item.choice("true_false_question", &["True", "False"], &[1])
+            } else {
+                item.essay(&options)
+            }
+        }
+        QuestionType::FillInTheBlank if answer.is_some() => {
+            let accepted: Vec<&str> = answer
+                .unwrap_or_default()
+                .split(';')
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .collect();
+            item.short_answer(&accepted)
+        }
+        _ => item.essay(&options),
+    }
+}
+
+/// An item being written
+struct Item<'a> {
+    question: &'a Question,
+}
+
+impl<'a> Item<'a> {
+    fn new(question: &'a Question) -> Self {
+        Self { question }
+    }
+
+    fn open(&self, kind: &str) -> String {
+        let title: String = self.question.stem.lines().next().unwrap_or_default().chars().take(80).collect();
+        let mut out = format!(
+            "    <item ident=\"{}\" title=\"{}\">\n      <itemmetadata>\n        <qtimetadata>\n",
+            self.question.id,
+            escape(&title)
+        );
+        out.push_str(&field("question_type", kind));
+        if !self.question.tags.is_empty() {
+            out.push_str(&field("tags", &self.question.tags.join(",")));
+        }
+        out.push_str("        </qtimetadata>\n      </itemmetadata>\n      <presentation>\n");
+        out.push_str(&format!("        {}\n", material(&export_text(self.question, &self.question.stem))));
+        out
+    }
+
+    fn close(&self, mut out: String) -> String {
+        if let Some(analysis) = self.question.analysis.as_deref().filter(|a| !a.trim().is_empty()) {
+            out.push_str(&format!(
+                "      <itemfeedback ident=\"general_fb\">\n        <flow_mat>\n          {}\n        </flow_mat>\n      </itemfeedback>\n",
+                material(&export_text(self.question, analysis))
+            ));
+        }
+        out.push_str("    </item>\n");
+        out
+    }
+
+    fn choice(self, kind: &str, options: &[&str], correct: &[usize]) -> String {
+        let mut out = self.open(kind);
+        let cardinality = if correct.len() > 1 { "Multiple" } else { "Single" };
+        out.push_str(&format!(
+            "        <response_lid ident=\"response1\" rcardinality=\"{}\">\n          <render_choice>\n",
+            cardinality
+        ));
+        for (i, option) in options.iter().enumerate() {
+            out.push_str(&format!(
+                "            <response_label ident=\"{}\">\n              {}\n            </response_label>\n",
+                option_letter(i),
+                material(option)
+            ));
+        }
+        out.push_str("          </render_choice>\n        </response_lid>\n      </presentation>\n");
+
+        // Full marks only for exactly the correct options
+        let mut condition = String::new();
+        for i in 0..options.len() {
+            let equal = format!("<varequal respident=\"response1\">{}</varequal>", option_letter(i));
+            match (correct.contains(&i), correct.len() > 1) {
+                (true, _) => condition.push_str(&equal),
+                (false, true) => condition.push_str(&format!("<not>{}</not>", equal)),
+                (false, false) => {}
+            }
+        }
+        if correct.len() > 1 {
+            condition = format!("<and>{}</and>", condition);
+        }
+        out.push_str(&resprocessing(&condition));
+        self.close(out)
+    }
+
+    fn short_answer(self, accepted: &[&str]) -> String {
+        let mut out = self.open("short_answer_question");
+        out.push_str(
+            "        <response_str ident=\"response1\" rcardinality=\"Single\">\n          <render_fib>\n            <response_label ident=\"answer1\" rshuffle=\"No\"/>\n          </render_fib>\n        </response_str>\n      </presentation>\n",
+        );
+        let condition: String = accepted
+            .iter()
+            .map(|a| format!("<varequal respident=\"response1\">{}</varequal>", escape(a)))
+            .collect();
+        out.push_str(&resprocessing(&condition));
+        self.close(out)
+    }
+
+    fn essay(self, options: &[&str]) -> String {
+        let mut out = self.open("essay_question");
+        // Options that cannot be graded are kept in the text
+        for (i, option) in options.iter().enumerate() {
+            out.push_str(&format!("        {}\n", material(&format!("{}. {}", option_letter(i), option))));
+        }
+        out.push_str(
+            "        <response_str ident=\"response1\" rcardinality=\"Single\">\n          <render_fib>\n            <response_label ident=\"answer1\" rshuffle=\"No\"/>\n          </render_fib>\n        </response_str>\n      </presentation>\n",
+        );
+        self.close(out)
+    }
+}
+
+fn field(label: &str, entry: &str) -> String {
+    format!(
+        "          <qtimetadatafield>\n            <fieldlabel>{}</fieldlabel>\n            <fieldentry>{}</fieldentry>\n          </qtimetadatafield>\n",
+        label,
+        escape(entry)
+    )
+}
+
+fn material(text: &str) -> String {
+    format!("<material><mattext texttype=\"text/plain\">{}</mattext></material>", escape(text.trim()))
+}
+
+fn resprocessing(condition: &str) -> String {
+    format!(
+        "      <resprocessing>\n        <outcomes>\n          <decvar maxvalue=\"100\" minvalue=\"0\" varname=\"SCORE\" vartype=\"Decimal\"/>\n        </outcomes>\n        <respcondition continue=\"No\">\n          <conditionvar>{}</conditionvar>\n          <setvar action=\"Set\" varname=\"SCORE\">100</setvar>\n        </respcondition>\n      </resprocessing>\n",
+        condition
+    )
+}
+
+/// Escape text for XML content and attributes, dropping characters XML forbids
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    fn question(qtype: QuestionType, stem: &str, options: &[(&str, bool)], answer: Option<&str>) -> Question {
+        Question {
+            qtype,
+            stem: stem.to_string(),
+            options: options
+                .iter()
+                .zip(0..)
+                .map(|((content, is_correct), sort_order)| QuestionOption {
+                    content: content.to_string(),
+                    sort_order,
+                    is_correct: *is_correct,
+                })
+                .collect(),
+            answer: answer.map(str::to_string),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn test_question_types() {
+        let single = question_to_qti(&question(QuestionType::Choice, "2 < 3?", &[("A. yes", true), ("B. no", false)], None));
+        assert!(single.contains("<fieldentry>multiple_choice_question</fieldentry>"));
+        assert!(single.contains("2 &lt; 3?"));
+        assert!(single.contains("<mattext texttype=\"text/plain\">yes</mattext>"));
+        assert!(single.contains("<conditionvar><varequal respident=\"response1\">A</varequal></conditionvar>"));
+
+        let multiple = question_to_qti(&question(QuestionType::MultipleChoice, "Primes?", &[("2", false), ("3", false), ("4", false)], Some("AB")));
+        assert!(multiple.contains("multiple_answers_question"));
+        assert!(multiple.contains("rcardinality=\"Multiple\""));
+        assert!(multiple.contains(
+            "<and><varequal respident=\"response1\">A</varequal><varequal respident=\"response1\">B</varequal><not><varequal respident=\"response1\">C</varequal></not></and>"
+        ));
+
+        let true_false = question_to_qti(&question(QuestionType::TrueFalse, "Water is wet", &[], Some("对")));
+        assert!(true_false.contains("true_false_question"));
+        assert!(true_false.contains("<conditionvar><varequal respident=\"response1\">A</varequal></conditionvar>"));
+
+        let blank = question_to_qti(&question(QuestionType::FillInTheBlank, "2+2 = ____", &[], Some("4; four")));
+        assert!(blank.contains("short_answer_question"));
+        assert!(blank.contains("<varequal respident=\"response1\">4</varequal><varequal respident=\"response1\">four</varequal>"));
+
+        let essay = question_to_qti(&question(QuestionType::Subjective, "Discuss", &[], None));
+        assert!(essay.contains("essay_question"));
+        assert!(!essay.contains("resprocessing"));
+    }
+
+    #[test]
+    fn test_document() {
+        let mut q = question(QuestionType::Subjective, "Explain \u{7}recursion", &[], None);
+        q.analysis = Some("See SICP".to_string());
+        q.tags = vec!["cs".to_string()];
+        let doc = write_qti(&[q.clone()]);
+        assert!(doc.starts_with(QTI_HEADER) && doc.ends_with(QTI_FOOTER));
+        assert!(doc.contains(&format!("<item ident=\"{}\" title=\"Explain recursion\">", q.id)));
+        assert!(doc.contains("<fieldentry>cs</fieldentry>"));
+        assert!(doc.contains("<itemfeedback ident=\"general_fb\">"));
+    }
+}
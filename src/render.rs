@@ -0,0 +1,408 @@
+//! Question rendering for alternative delivery formats
+//!
+//! This module turns stored questions into accessible plain text suitable for
+//! screen readers: LaTeX formulas are spoken as words, images are announced
+//! inline and options are enumerated with their letters.
+
+use crate::bidi;
+use crate::models::{ImageRef, Question, QuestionType, TextDirection};
+use crate::parser::OPTION_SEPARATORS;
+use anyhow::{anyhow, Result};
+
+/// Supported render output formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// Accessible plain text
+    Text,
+}
+
+impl std::str::FromStr for RenderFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" | "plain" => Ok(RenderFormat::Text),
+            other => Err(anyhow!("Unsupported render format: {}", other)),
+        }
+    }
+}
+
+/// Render a question in the requested format
+pub fn render(question: &Question, format: RenderFormat) -> String {
+    match format {
+        RenderFormat::Text => render_text(question),
+    }
+}
+
+/// Render a question as accessible plain text
+pub fn render_text(question: &Question) -> String {
+    // In RTL text, spoken formulas are isolated so they are not reordered
+    let isolate = question.text_direction != TextDirection::Ltr;
+    let speak = |text: &str| speak_inline_math_with(text, isolate);
+    let mut out = String::new();
+
+    out.push_str(&format!("Question ({}): ", type_label(question.qtype)));
+    out.push_str(&speak(&question.stem));
+    out.push('\n');
+
+    for (idx, image) in question.images.iter().enumerate() {
+        let label = match image.alt().map(str::trim).filter(|alt| !alt.is_empty()) {
+            Some(alt) => speak(alt),
+            None => image_label(image),
+        };
+        out.push_str(&format!("Image {}: {}\n", idx + 1, label));
+    }
+
+    if !question.options.is_empty() {
+        out.push_str(&format!("{} options:\n", question.options.len()));
+        for (idx, option) in question.options.iter().enumerate() {
+            let letter = option_letter(idx);
+            let content = strip_option_prefix(&option.content, idx);
+            out.push_str(&format!("Option {}: {}\n", letter, speak(content)));
+        }
+    }
+
+    if let Some(answer) = &question.answer {
+        out.push_str(&format!("Answer: {}\n", speak(answer)));
+    }
+
+    if let Some(analysis) = &question.analysis {
+        out.push_str(&format!("Explanation: {}\n", speak(analysis)));
+    }
+
+    out
+}
+
+/// Human-readable label for a question type
+fn type_label(qtype: QuestionType) -> &'static str {
+    match qtype {
+        QuestionType::Choice => "single choice",
+        QuestionType::MultipleChoice => "multiple choice",
+        QuestionType::TrueFalse => "true or false",
+        QuestionType::FillInTheBlank => "fill in the blank",
+        QuestionType::Subjective => "open answer",
+    }
+}
+
+/// Name an image without alt text by its file name or URL
+fn image_label(image: &ImageRef) -> String {
+    match image {
+        ImageRef::Remote { url, .. } => url.clone(),
+        ImageRef::Local { original_path, .. } => original_path
+            .rsplit('/')
+            .next()
+            .unwrap_or(original_path)
+            .to_string(),
+    }
+}
+
+/// Letter for the option at `idx` (A, B, ..., Z, then numbers)
+pub(crate) fn option_letter(idx: usize) -> String {
+    if idx < 26 {
+        ((b'A' + idx as u8) as char).to_string()
+    } else {
+        (idx + 1).to_string()
+    }
+}
+
+/// Remove the marker ("A." / "B、" / "c)") of the option at `idx` from its text
+///
+/// Only that option's own letter is stripped, so text such as "U.S. dollars"
+/// is kept whole. As in the parser, a lowercase letter only counts before a
+/// parenthesis: "e.g. apples" is not marked E.
+pub(crate) fn strip_option_prefix(content: &str, idx: usize) -> &str {
+    let trimmed = content.trim_start();
+    let letter = option_letter(idx);
+    let (rest, lowercase) = match trimmed.strip_prefix(letter.as_str()) {
+        Some(rest) => (rest, false),
+        None => match trimmed.strip_prefix(letter.to_ascii_lowercase().as_str()) {
+            Some(rest) => (rest, true),
+            None => return trimmed,
+        },
+    };
+    let mut chars = rest.chars();
+    let marked = match chars.next() {
+        Some(sep) if lowercase => matches!(sep, ')' | '）'),
+        Some(sep) => OPTION_SEPARATORS.contains(&sep),
+        None => false,
+    };
+    if marked {
+        chars.as_str().trim_start()
+    } else {
+        trimmed
+    }
+}
+
+/// Replace `$...$` and `$$...$$` spans in text with their spoken form
+pub fn speak_inline_math(text: &str) -> String {
+    speak_inline_math_with(text, false)
+}
+
+fn speak_inline_math_with(text: &str, isolate: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        let delim = if rest[start..].starts_with("$$") { "$$" } else { "$" };
+        let body_start = start + delim.len();
+        match rest[body_start..].find(delim) {
+            Some(len) => {
+                out.push_str(&rest[..start]);
+                let spoken = latex_to_speech(&rest[body_start..body_start + len]);
+                if isolate {
+                    out.push(bidi::LRI);
+                    out.push_str(&spoken);
+                    out.push(bidi::PDI);
+                } else {
+                    out.push_str(&spoken);
+                }
+                rest = &rest[body_start + len + delim.len()..];
+            }
+            None => break,
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Deepest nesting of groups and command arguments spoken
+///
+/// Each level is a recursive call, so a crafted formula ("{{{…}}}") could
+/// otherwise overflow the stack; deeper formulas are read out as written.
+const MAX_SPEECH_NESTING: usize = 64;
+
+/// Convert a LaTeX formula into words a screen reader can pronounce
+pub fn latex_to_speech(latex: &str) -> String {
+    let latex = latex.trim().trim_matches('$');
+    let chars: Vec<char> = latex.chars().collect();
+    let mut pos = 0;
+    match speak_sequence(&chars, &mut pos, None, 0) {
+        Some(spoken) => spoken.split_whitespace().collect::<Vec<_>>().join(" "),
+        None => latex.to_string(),
+    }
+}
+
+/// Speak tokens until the closing character (if any) is reached
+///
+/// Returns `None` once nested deeper than [`MAX_SPEECH_NESTING`].
+fn speak_sequence(chars: &[char], pos: &mut usize, close: Option<char>, depth: usize) -> Option<String> {
+    if depth > MAX_SPEECH_NESTING {
+        return None;
+    }
+    let mut out = String::new();
+
+    while *pos < chars.len() {
+        let c = chars[*pos];
+        if Some(c) == close {
+            *pos += 1;
+            break;
+        }
+        *pos += 1;
+
+        match c {
+            '\\' => out.push_str(&speak_command(chars, pos, depth + 1)?),
+            '{' => out.push_str(&speak_sequence(chars, pos, Some('}'), depth + 1)?),
+            '^' => {
+                let exponent = speak_argument(chars, pos, depth + 1)?;
+                match exponent.trim() {
+                    "2" => out.push_str(" squared "),
+                    "3" => out.push_str(" cubed "),
+                    e => out.push_str(&format!(" to the power of {} ", e)),
+                }
+            }
+            '_' => out.push_str(&format!(" sub {} ", speak_argument(chars, pos, depth + 1)?)),
+            '+' => out.push_str(" plus "),
+            '-' => out.push_str(" minus "),
+            '*' => out.push_str(" times "),
+            '/' => out.push_str(" divided by "),
+            '=' => out.push_str(" equals "),
+            '<' => out.push_str(" less than "),
+            '>' => out.push_str(" greater than "),
+            '(' | '[' => out.push_str(" open paren "),
+            ')' | ']' => out.push_str(" close paren "),
+            ',' => out.push_str(", "),
+            c => out.push(c),
+        }
+    }
+
+    Some(out)
+}
+
+/// Speak a single argument: a braced group or one character/command
+fn speak_argument(chars: &[char], pos: &mut usize, depth: usize) -> Option<String> {
+    while *pos < chars.len() && chars[*pos] == ' ' {
+        *pos += 1;
+    }
+    match chars.get(*pos) {
+        Some('{') => {
+            *pos += 1;
+            speak_sequence(chars, pos, Some('}'), depth)
+        }
+        Some('\\') => {
+            *pos += 1;
+            speak_command(chars, pos, depth)
+        }
+        Some(&c) => {
+            *pos += 1;
+            Some(c.to_string())
+        }
+        None => Some(String::new()),
+    }
+}
+
+/// Speak a backslash command whose name starts at `pos`
+fn speak_command(chars: &[char], pos: &mut usize, depth: usize) -> Option<String> {
+    if depth > MAX_SPEECH_NESTING {
+        return None;
+    }
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos].is_ascii_alphabetic() {
+        *pos += 1;
+    }
+    if *pos == start {
+        // Escaped symbol such as \{ or \%
+        return Some(chars.get(*pos).map(|c| {
+            *pos += 1;
+            c.to_string()
+        }).unwrap_or_default());
+    }
+    let name: String = chars[start..*pos].iter().collect();
+
+    let spoken = match name.as_str() {
+        "frac" | "dfrac" | "tfrac" => {
+            let num = speak_argument(chars, pos, depth + 1)?;
+            let den = speak_argument(chars, pos, depth + 1)?;
+            format!(" the fraction {} over {} ", num, den)
+        }
+        "sqrt" => {
+            if chars.get(*pos) == Some(&'[') {
+                *pos += 1;
+                let degree = speak_sequence(chars, pos, Some(']'), depth + 1)?;
+                let radicand = speak_argument(chars, pos, depth + 1)?;
+                format!(" the {} root of {} ", degree.trim(), radicand)
+            } else {
+                format!(" the square root of {} ", speak_argument(chars, pos, depth + 1)?)
+            }
+        }
+        "left" | "right" | "displaystyle" | "mathrm" | "text" | "mathbf" | "quad" | "," => " ".to_string(),
+        "times" | "cdot" => " times ".to_string(),
+        "div" => " divided by ".to_string(),
+        "pm" => " plus or minus ".to_string(),
+        "leq" | "le" => " less than or equal to ".to_string(),
+        "geq" | "ge" => " greater than or equal to ".to_string(),
+        "neq" | "ne" => " not equal to ".to_string(),
+        "approx" => " approximately equals ".to_string(),
+        "to" | "rightarrow" => " approaches ".to_string(),
+        "infty" => " infinity ".to_string(),
+        "sum" => " the sum ".to_string(),
+        "int" => " the integral ".to_string(),
+        "lim" => " the limit ".to_string(),
+        "partial" => " partial ".to_string(),
+        "degree" | "circ" => " degrees ".to_string(),
+        other => format!(" {} ", other),
+    };
+    Some(spoken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    #[test]
+    fn test_latex_to_speech() {
+        assert_eq!(latex_to_speech("$x^2$"), "x squared");
+        assert_eq!(latex_to_speech("\\frac{1}{2}"), "the fraction 1 over 2");
+        assert_eq!(latex_to_speech("\\sqrt{x+1}"), "the square root of x plus 1");
+        assert_eq!(latex_to_speech("a_{n} \\leq \\pi"), "a sub n less than or equal to pi");
+        assert_eq!(latex_to_speech("e^{i\\theta}"), "e to the power of i theta");
+    }
+
+    #[test]
+    fn test_deeply_nested_formula_is_read_as_written() {
+        let shallow = format!("{}x{}", "{".repeat(10), "}".repeat(10));
+        assert_eq!(latex_to_speech(&shallow), "x");
+
+        // Test threads have the 2 MiB stack of a Tokio worker
+        let nested = format!("{}x{}", "{".repeat(20_000), "}".repeat(20_000));
+        assert_eq!(latex_to_speech(&nested), nested);
+        let fractions = format!("{}1", "\\frac".repeat(50_000));
+        assert_eq!(latex_to_speech(&fractions), fractions);
+        let question = Question { stem: format!("${}$", nested), ..Default::default() };
+        assert!(render_text(&question).contains(&nested));
+    }
+
+    #[test]
+    fn test_speak_inline_math() {
+        assert_eq!(
+            speak_inline_math("求 $x^2 = 4$ 的解"),
+            "求 x squared equals 4 的解"
+        );
+        assert_eq!(speak_inline_math("price is $5"), "price is $5");
+    }
+
+    #[test]
+    fn test_render_text() {
+        let question = Question {
+            qtype: QuestionType::Choice,
+            stem: "What is $\\frac{1}{2}$ of 4?".to_string(),
+            options: vec![
+                QuestionOption { content: "A. 1".to_string(), sort_order: 0, is_correct: false },
+                QuestionOption { content: "B. 2".to_string(), sort_order: 1, is_correct: true },
+            ],
+            images: vec![
+                ImageRef::Local {
+                    hash: "abc".to_string(),
+                    original_path: "images/diagram.png".to_string(),
+                    stored_path: None,
+                    alt: None,
+                },
+                ImageRef::Remote {
+                    url: "https://example.com/plot.png".to_string(),
+                    alt: Some("Graph of $x^2$".to_string()),
+                },
+            ],
+            answer: Some("B".to_string()),
+            ..Default::default()
+        };
+
+        let text = render_text(&question);
+        assert!(text.starts_with("Question (single choice): What is the fraction 1 over 2 of 4?"));
+        assert!(text.contains("Image 1: diagram.png\nImage 2: Graph of x squared\n"), "{}", text);
+        assert!(text.contains("2 options:\nOption A: 1\nOption B: 2\n"));
+        assert!(text.contains("Answer: B"));
+    }
+
+    #[test]
+    fn test_render_text_rtl_isolates_formulas() {
+        let question = Question {
+            stem: "احسب $x^2$".to_string(),
+            text_direction: TextDirection::Rtl,
+            ..Default::default()
+        };
+
+        let text = render_text(&question);
+        assert!(text.contains("احسب \u{2066}x squared\u{2069}"));
+    }
+
+    #[test]
+    fn test_strip_option_prefix() {
+        assert_eq!(strip_option_prefix("A. 1", 0), "1");
+        assert_eq!(strip_option_prefix(" B、 2", 1), "2");
+        assert_eq!(strip_option_prefix("c) 3", 2), "3");
+        assert_eq!(strip_option_prefix("28. last", 27), "last");
+        // Only the option's own marker
+        assert_eq!(strip_option_prefix("U.S. dollars", 0), "U.S. dollars");
+        assert_eq!(strip_option_prefix("B. 2", 0), "B. 2");
+        assert_eq!(strip_option_prefix("e.g. apples", 4), "e.g. apples");
+        assert_eq!(strip_option_prefix("E. e.g. apples", 4), "e.g. apples");
+        assert_eq!(strip_option_prefix("Apples", 0), "Apples");
+    }
+
+    #[test]
+    fn test_render_format_parse() {
+        assert_eq!("text".parse::<RenderFormat>().unwrap(), RenderFormat::Text);
+        assert!("pdf".parse::<RenderFormat>().is_err());
+    }
+}
@@ -0,0 +1,501 @@
+//! Human-readable import reports
+//!
+//! Every finished import job can be summarized as a Markdown or HTML page
+//! for the people checking a migration: how many questions of each type
+//! were imported, the warnings grouped by what they are about, the
+//! questions the classifier was unsure of and the questions rejected by
+//! validation, with the reasons.
+//!
+//! The report is built from the job's stored [`ProcessResult`], so it can be
+//! downloaded at any time after the job finished. The counts per type and
+//! the low-confidence questions are recorded in the result as a
+//! [`QuestionBreakdown`] when the import runs, since the questions
+//! themselves are not kept.
+
+use crate::classifier::classify_question;
+use crate::jobs::{JobRecord, JobStatus};
+use crate::models::{Question, QuestionType};
+use crate::processor::ProcessResult;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// Characters of a stem shown in a report
+const STEM_PREVIEW_CHARS: usize = 80;
+
+/// Output format of a report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// File extension of the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+
+    /// MIME type of the format
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "text/markdown; charset=utf-8",
+            ReportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            other => Err(anyhow::anyhow!("unknown report format: {} (expected markdown or html)", other)),
+        }
+    }
+}
+
+/// A question the classifier could not confidently type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LowConfidenceQuestion {
+    pub id: Uuid,
+    /// Type the question was imported as
+    #[serde(rename = "type")]
+    pub qtype: QuestionType,
+    /// Classifier confidence, from 0 to 1; 0 when it found no type at all
+    pub confidence: f32,
+    /// File the question came from
+    pub source: Option<String>,
+    pub stem: String,
+}
+
+/// Questions of an import by type, and those that need a second look
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuestionBreakdown {
+    /// Questions of each type, keyed by its API name; types with no
+    /// questions are left out
+    pub by_type: BTreeMap<String, usize>,
+    /// Questions the classifier flagged for review, in import order
+    pub low_confidence: Vec<LowConfidenceQuestion>,
+}
+
+impl QuestionBreakdown {
+    /// Count `questions` by type and classify each one again to find those
+    /// needing review
+    pub fn new(questions: &[Question]) -> Self {
+        let mut breakdown = Self::default();
+        for question in questions {
+            *breakdown.by_type.entry(crate::stats::type_name(question.qtype)).or_default() += 1;
+
+            let confidence = match classify_question(question, None) {
+                Some(result) if !result.needs_review => continue,
+                Some(result) => result.confidence,
+                None => 0.0,
+            };
+            breakdown.low_confidence.push(LowConfidenceQuestion {
+                id: question.id,
+                qtype: question.qtype,
+                confidence,
+                source: question.provenance.as_ref().and_then(|p| p.source_file.clone()),
+                stem: question.stem.clone(),
+            });
+        }
+        breakdown
+    }
+}
+
+/// What a warning is about, for grouping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WarningCategory {
+    Archives,
+    Manifest,
+    Encoding,
+    Parsing,
+    Images,
+    Formulas,
+    Answers,
+    Saving,
+    Other,
+}
+
+impl WarningCategory {
+    /// Categorize a warning by the words in it
+    pub fn of(warning: &str) -> Self {
+        let lower = warning.to_lowercase();
+        let has = |words: &[&str]| words.iter().any(|w| lower.contains(w));
+        if has(&["manifest"]) {
+            WarningCategory::Manifest
+        } else if has(&["formula", "latex", "ocr"]) {
+            WarningCategory::Formulas
+        } else if has(&["image", "media"]) {
+            WarningCategory::Images
+        } else if has(&["decoded as", "gb18030", "encoding"]) {
+            WarningCategory::Encoding
+        } else if has(&["archive", "worker"]) {
+            WarningCategory::Archives
+        } else if has(&["parse", "convert", "sheet", "row ", "line ", "page "]) {
+            WarningCategory::Parsing
+        } else if has(&["answer"]) {
+            WarningCategory::Answers
+        } else if has(&["rolled back", "not recorded", "remove", "updated", "save"]) {
+            WarningCategory::Saving
+        } else {
+            WarningCategory::Other
+        }
+    }
+
+    /// Heading of the category in a report
+    pub fn title(self) -> &'static str {
+        match self {
+            WarningCategory::Archives => "Archives",
+            WarningCategory::Manifest => "Manifest",
+            WarningCategory::Encoding => "Encoding",
+            WarningCategory::Parsing => "Parsing",
+            WarningCategory::Images => "Images",
+            WarningCategory::Formulas => "Formulas",
+            WarningCategory::Answers => "Answers",
+            WarningCategory::Saving => "Saving",
+            WarningCategory::Other => "Other",
+        }
+    }
+}
+
+/// Group warnings by category, keeping their order within each
+pub fn group_warnings(warnings: &[String]) -> BTreeMap<WarningCategory, Vec<&str>> {
+    let mut groups: BTreeMap<WarningCategory, Vec<&str>> = BTreeMap::new();
+    for warning in warnings {
+        groups.entry(WarningCategory::of(warning)).or_default().push(warning);
+    }
+    groups
+}
+
+/// Render the report of a finished job
+pub fn render_job_report(job: &JobRecord, format: ReportFormat) -> String {
+    let document = build(job);
+    match format {
+        ReportFormat::Markdown => document.to_markdown(),
+        ReportFormat::Html => document.to_html(&format!("Import report {}", job.id)),
+    }
+}
+
+/// Lay out the report of `job`
+fn build(job: &JobRecord) -> Document {
+    let mut doc = Document::default();
+    doc.push(Block::Heading(1, format!("Import report {}", job.id)));
+    doc.push(Block::Table {
+        header: vec!["Job", ""],
+        rows: vec![
+            vec!["Status".to_string(), job.status.as_str().to_string()],
+            vec!["Sources".to_string(), job.sources.join(", ")],
+            vec!["Submitted".to_string(), job.created_at.to_rfc3339()],
+            vec!["Finished".to_string(), job.updated_at.to_rfc3339()],
+        ],
+    });
+    if job.status == JobStatus::Failed {
+        let error = job.error.as_deref().unwrap_or("unknown error");
+        doc.push(Block::Paragraph(format!("The import failed: {}", error)));
+    }
+    if let Some(result) = &job.result {
+        build_result(&mut doc, result);
+    }
+    doc
+}
+
+fn build_result(doc: &mut Document, result: &ProcessResult) {
+    if result.dry_run {
+        doc.push(Block::Paragraph("This was a dry run; nothing was saved.".to_string()));
+    }
+
+    doc.push(Block::Heading(2, "Summary".to_string()));
+    let counts = [
+        ("Found", result.total_questions),
+        ("Saved", result.saved_questions),
+        ("Updated", result.updated_questions),
+        ("Duplicates skipped", result.duplicate_questions),
+        ("Rejected", result.rejected.len()),
+        ("Failed to save", result.failed_questions),
+            ];
+    doc.push(Block::Table {
+        header: vec!["Questions", "Count"],
+        rows: counts.iter().map(|(name, n)| vec![name.to_string(), n.to_string()]).collect(),
+    });
+    doc.push(Block::Paragraph(format!(
+        "{} images, {} warnings, processed in {} ms.",
+        result.total_images,
+        result.warnings.len(),
+        result.processing_time_ms
+    )));
+
+    doc.push(Block::Heading(2, "Questions by type".to_string()));
+    if result.breakdown.by_type.is_empty() {
+        doc.push(Block::Paragraph("No questions were imported.".to_string()));
+    } else {
+        doc.push(Block::Table {
+            header: vec!["Type", "Count"],
+            rows: result
+                .breakdown
+                .by_type
+                .iter()
+                .map(|(qtype, n)| vec![qtype.clone(), n.to_string()])
+                .collect(),
+        });
+    }
+
+    doc.push(Block::Heading(2, "Warnings".to_string()));
+    if result.warnings.is_empty() {
+        doc.push(Block::Paragraph("None.".to_string()));
+    }
+    for (category, warnings) in group_warnings(&result.warnings) {
+        doc.push(Block::Heading(3, format!("{} ({})", category.title(), warnings.len())));
+        doc.push(Block::List(warnings.iter().map(|w| w.to_string()).collect()));
+    }
+
+    doc.push(Block::Heading(2, "Low-confidence questions".to_string()));
+    let low_confidence = &result.breakdown.low_confidence;
+    if low_confidence.is_empty() {
+        doc.push(Block::Paragraph("None.".to_string()));
+    } else {
+        doc.push(Block::Table {
+            header: vec!["Type", "Confidence", "Source", "Stem"],
+            rows: low_confidence
+                .iter()
+                .map(|q| {
+                    vec![
+                        crate::stats::type_name(q.qtype),
+                        format!("{:.0}%", q.confidence * 100.0),
+                        q.source.clone().unwrap_or_default(),
+                        preview(&q.stem),
+                    ]
+                })
+                .collect(),
+        });
+    }
+
+    doc.push(Block::Heading(2, "Rejected questions".to_string()));
+    if result.rejected.is_empty() {
+        doc.push(Block::Paragraph("None.".to_string()));
+    } else {
+        doc.push(Block::Table {
+            header: vec!["Source", "Stem", "Reasons"],
+            rows: result
+                .rejected
+                .iter()
+                .map(|r| {
+                    let source = r.question.provenance.as_ref().and_then(|p| p.source_file.clone());
+                    vec![source.unwrap_or_default(), preview(&r.question.stem), r.reasons.join("; ")]
+                })
+                .collect(),
+        });
+    }
+}
+
+/// First line of a stem, shortened for a table cell
+fn preview(stem: &str) -> String {
+    let line = stem.trim().lines().next().unwrap_or_default();
+    let mut chars = line.chars();
+    let mut shown: String = chars.by_ref().take(STEM_PREVIEW_CHARS).collect();
+    if chars.next().is_some() {
+        shown.push('…');
+    }
+    shown
+}
+
+/// A report, independent of the format it is written in
+#[derive(Debug, Default)]
+struct Document {
+    blocks: Vec<Block>,
+}
+
+#[derive(Debug)]
+enum Block {
+    Heading(u8, String),
+    Paragraph(String),
+    List(Vec<String>),
+    Table { header: Vec<&'static str>, rows: Vec<Vec<String>> },
+}
+
+impl Document {
+    fn push(&mut self, block: Block) {
+        self.blocks.push(block);
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for block in &self.blocks {
+            match block {
+                Block::Heading(level, text) => {
+                    let _ = writeln!(out, "{} {}\n", "#".repeat(*level as usize), text);
+                }
+                Block::Paragraph(text) => {
+                    let _ = writeln!(out, "{}\n", text);
+                }
+                Block::List(items) => {
+                    for item in items {
+                        let _ = writeln!(out, "- {}", item.replace('\n', " "));
+                    }
+                    out.push('\n');
+                }
+                Block::Table { header, rows } => {
+                    let _ = writeln!(out, "| {} |", header.join(" | "));
+                    let _ = writeln!(out, "|{}", "---|".repeat(header.len()));
+                    for row in rows {
+                        let cells: Vec<String> = row.iter().map(|c| markdown_cell(c)).collect();
+                        let _ = writeln!(out, "| {} |", cells.join(" | "));
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        out
+    }
+
+    fn to_html(&self, title: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "<!DOCTYPE html>");
+        let _ = writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">");
+        let _ = writeln!(out, "<title>{}</title>", escape_html(title));
+        let _ = writeln!(
+            out,
+            "<style>body{{font-family:sans-serif;max-width:60em;margin:auto}}\
+             table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:.2em .5em;text-align:left}}</style>"
+        );
+        let _ = writeln!(out, "</head>\n<body>");
+        for block in &self.blocks {
+            match block {
+                Block::Heading(level, text) => {
+                    let _ = writeln!(out, "<h{0}>{1}</h{0}>", level, escape_html(text));
+                }
+                Block::Paragraph(text) => {
+                    let _ = writeln!(out, "<p>{}</p>", escape_html(text));
+                }
+                Block::List(items) => {
+                    let _ = writeln!(out, "<ul>");
+                    for item in items {
+                        let _ = writeln!(out, "<li>{}</li>", escape_html(item));
+                    }
+                    let _ = writeln!(out, "</ul>");
+                }
+                Block::Table { header, rows } => {
+                    let _ = writeln!(out, "<table>");
+                    let cells: String = header.iter().map(|h| format!("<th>{}</th>", escape_html(h))).collect();
+                    let _ = writeln!(out, "<tr>{}</tr>", cells);
+                    for row in rows {
+                        let cells: String = row.iter().map(|c| format!("<td>{}</td>", escape_html(c))).collect();
+                        let _ = writeln!(out, "<tr>{}</tr>", cells);
+                    }
+                    let _ = writeln!(out, "</table>");
+                }
+            }
+        }
+        let _ = writeln!(out, "</body>\n</html>");
+        out
+    }
+}
+
+/// Make text safe inside a Markdown table cell
+fn markdown_cell(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|").replace('\n', " ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Provenance, QuestionOption};
+    use crate::validate::RejectedQuestion;
+
+    fn question(stem: &str, qtype: QuestionType) -> Question {
+        Question { stem: stem.to_string(), qtype, ..Question::default() }
+    }
+
+    fn finished_job() -> JobRecord {
+        let choice = Question {
+            options: vec![
+                QuestionOption { content: "O(n)".to_string(), sort_order: 0, is_correct: true },
+                QuestionOption { content: "O(log n)".to_string(), sort_order: 1, is_correct: false },
+            ],
+            ..question("[单选] 以下哪个是算法的时间复杂度？", QuestionType::Choice)
+        };
+        let vague = Question {
+            provenance: Some(Provenance { source_file: Some("ch1.md".to_string()), ..Provenance::default() }),
+            ..question("Discuss a | b <b>", QuestionType::Subjective)
+        };
+
+        let mut result = ProcessResult::new();
+        result.total_questions = 3;
+        result.saved_questions = 2;
+        result.breakdown = QuestionBreakdown::new(&[choice, vague]);
+        result.warnings = vec![
+            "ch1.md: image not found in archive: a.png".to_string(),
+            "Failed to parse ch2.md: bad table".to_string(),
+            "ch1.md: image b.png could not be read".to_string(),
+        ];
+        result.rejected = vec![RejectedQuestion {
+            question: question("", QuestionType::Choice),
+            reasons: vec!["empty stem".to_string(), "no correct answer".to_string()],
+        }];
+
+        let mut job = JobRecord::new(vec!["exam.zip".to_string()]);
+        job.status = JobStatus::Completed;
+        job.result = Some(result);
+        job
+    }
+
+    #[test]
+    fn test_breakdown_and_warning_groups() {
+        let job = finished_job();
+        let breakdown = &job.result.as_ref().unwrap().breakdown;
+        assert_eq!(breakdown.by_type.get("choice"), Some(&1));
+        assert_eq!(breakdown.by_type.get("subjective"), Some(&1));
+        assert_eq!(breakdown.low_confidence.len(), 1);
+        assert_eq!(breakdown.low_confidence[0].source.as_deref(), Some("ch1.md"));
+
+        let groups = group_warnings(&job.result.as_ref().unwrap().warnings);
+        assert_eq!(groups[&WarningCategory::Images].len(), 2);
+        assert_eq!(groups[&WarningCategory::Parsing].len(), 1);
+        assert_eq!(WarningCategory::of("Ignoring manifest preset: x"), WarningCategory::Manifest);
+        assert_eq!(WarningCategory::of("something odd"), WarningCategory::Other);
+    }
+
+    #[test]
+    fn test_render_markdown_and_html() {
+        let job = finished_job();
+        let markdown = render_job_report(&job, ReportFormat::Markdown);
+        assert!(markdown.starts_with(&format!("# Import report {}", job.id)));
+        assert!(markdown.contains("| choice | 1 |"));
+        assert!(markdown.contains("### Images (2)"));
+        assert!(markdown.contains("Discuss a \\| b <b>"));
+        assert!(markdown.contains("empty stem; no correct answer"));
+
+        let html = render_job_report(&job, ReportFormat::Html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h3>Parsing (1)</h3>"));
+        assert!(html.contains("Discuss a | b &lt;b&gt;"));
+        assert!(!html.contains("<b>"));
+
+        let mut failed = JobRecord::new(vec!["broken.zip".to_string()]);
+        failed.status = JobStatus::Failed;
+        failed.error = Some("not an archive".to_string());
+        let markdown = render_job_report(&failed, ReportFormat::Markdown);
+        assert!(markdown.contains("The import failed: not an archive"));
+        assert!(!markdown.contains("## Summary"));
+
+        assert_eq!("HTML".parse::<ReportFormat>().unwrap(), ReportFormat::Html);
+        assert_eq!("md".parse::<ReportFormat>().unwrap(), ReportFormat::Markdown);
+        assert!("pdf".parse::<ReportFormat>().is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! Synthetic question bank generation
+//!
+//! Produces realistic, license-free Markdown question banks for demos, load
+//! tests and reproducible bug reports. Output is fully determined by the
+//! configuration (including the seed), so the same command always yields the
+//! same document.
+
+use crate::models::QuestionType;
+
+/// Configuration for sample generation
+#[derive(Debug, Clone)]
+pub struct SampleConfig {
+    /// Number of questions to generate
+    pub count: usize,
+    /// Question types to cycle through
+    pub types: Vec<QuestionType>,
+    /// Seed for the deterministic generator
+    pub seed: u64,
+    /// Whether to embed image references in some stems
+    pub with_images: bool,
+    /// Whether to embed LaTeX formulas in some stems and options
+    pub with_latex: bool,
+}
+
+impl Default for SampleConfig {
+    fn default() -> Self {
+        Self {
+            count: 10,
+            types: vec![
+                QuestionType::Choice,
+                QuestionType::MultipleChoice,
+                QuestionType::TrueFalse,
+                QuestionType::FillInTheBlank,
+                QuestionType::Subjective,
+            ],
+            seed: 42,
+            with_images: true,
+            with_latex: true,
+        }
+    }
+}
+
+impl SampleConfig {
+    /// Set the number of questions
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Set the question types to generate
+    pub fn with_types(mut self, types: Vec<QuestionType>) -> Self {
+        if !types.is_empty() {
+            self.types = types;
+        }
+        self
+    }
+
+    /// Set the generator seed
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Enable or disable image references
+    pub fn with_images(mut self, enabled: bool) -> Self {
+        self.with_images = enabled;
+        self
+    }
+
+    /// Enable or disable LaTeX content
+    pub fn with_latex(mut self, enabled: bool) -> Self {
+        self.with_latex = enabled;
+        self
+    }
+}
+
+/// Small deterministic PRNG (SplitMix64) so output is reproducible without extra deps
+struct SampleRng(u64);
+
+impl SampleRng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+const SUBJECTS: &[&str] = &["算法", "数据结构", "操作系统", "计算机网络", "数据库"];
+const CONCEPTS: &[&str] = &["快速排序", "二叉搜索树", "哈希表", "进程调度", "TCP 拥塞控制", "B+ 树索引"];
+const FORMULAS: &[&str] = &[
+    "$O(n \\log n)$",
+    "$x^2 + y^2 = z^2$",
+    "$\\frac{a}{b}$",
+    "$\\sum_{i=1}^{n} i = \\frac{n(n+1)}{2}$",
+    "$\\sqrt{2}$",
+];
+
+/// Generate a Markdown question bank
+pub fn generate_markdown(config: &SampleConfig) -> String {
+    let mut rng = SampleRng(config.seed);
+    let mut out = String::new();
+
+    for i in 0..config.count {
+        let qtype = config.types[i % config.types.len().max(1)];
+        let number = i + 1;
+        let subject = *rng.pick(SUBJECTS);
+        let concept = *rng.pick(CONCEPTS);
+        let formula = if config.with_latex && rng.chance(30) {
+            format!(" {}", rng.pick(FORMULAS))
+        } else {
+            String::new()
+        };
+
+        match qtype {
+            QuestionType::Choice => {
+                out.push_str(&format!(
+                    "# {}. 关于{}中的{}，以下哪一项正确？{}\n\n",
+                    number, subject, concept, formula
+                ));
+                push_image(&mut out, &mut rng, config, number);
+                let answer = rng.below(4) as usize;
+                push_options(&mut out, &mut rng, config, 4);
+                out.push_str(&format!("答案：{}\n\n", letter(answer)));
+            }
+            QuestionType::MultipleChoice => {
+                out.push_str(&format!(
+                    "# {}. [多选] 关于{}，下列哪些说法正确？{}\n\n",
+                    number, concept, formula
+                ));
+                push_image(&mut out, &mut rng, config, number);
+                push_options(&mut out, &mut rng, config, 4);
+                let first = rng.below(2) as usize;
+                out.push_str(&format!("答案：{}{}\n\n", letter(first), letter(first + 2)));
+            }
+            QuestionType::TrueFalse => {
+                out.push_str(&format!(
+                    "# {}. [判断] {}的平均时间复杂度为{}。\n\n",
+                    number,
+                    concept,
+                    if config.with_latex { "$O(\\log n)$" } else { "对数级" }
+                ));
+                out.push_str("* A. 正确\n* B. 错误\n\n");
+                out.push_str(&format!("答案：{}\n\n", if rng.chance(50) { "A" } else { "B" }));
+            }
+            QuestionType::FillInTheBlank => {
+                out.push_str(&format!(
+                    "# {}. [填空] {}的核心思想是 ______。{}\n\n",
+                    number, concept, formula
+                ));
+                out.push_str("答案：分治\n\n");
+            }
+            QuestionType::Subjective => {
+                out.push_str(&format!(
+                    "# {}. 请简述{}在{}中的应用。{}\n\n",
+                    number, concept, subject, formula
+                ));
+                push_image(&mut out, &mut rng, config, number);
+                out.push_str(&format!("解析：{}通过合理的数据组织降低了操作代价。\n\n", concept));
+            }
+        }
+    }
+
+    out
+}
+
+fn push_image(out: &mut String, rng: &mut SampleRng, config: &SampleConfig, number: usize) {
+    if config.with_images && rng.chance(25) {
+        out.push_str(&format!("![第{}题配图](images/q{}.png)\n\n", number, number));
+    }
+}
+
+fn push_options(out: &mut String, rng: &mut SampleRng, config: &SampleConfig, count: usize) {
+    for idx in 0..count {
+        let content = if config.with_latex && rng.chance(20) {
+            rng.pick(FORMULAS).to_string()
+        } else {
+            format!("{}的第{}种描述", rng.pick(CONCEPTS), idx + 1)
+        };
+        out.push_str(&format!("* {}. {}\n", letter(idx), content));
+    }
+    out.push('\n');
+}
+
+fn letter(idx: usize) -> char {
+    (b'A' + (idx % 26) as u8) as char
+}
+
+/// Generate the numbered plain-paragraph format used by the parser benchmarks
+///
+/// Questions cycle through choice, true/false, fill-in-the-blank and
+/// subjective-with-LaTeX shapes.
+pub fn generate_numbered(count: usize) -> String {
+    let mut questions = String::new();
+
+    for i in 0..count {
+        match i % 4 {
+            0 => {
+                questions.push_str(&format!(
+                    "{}. What is {} + {}?\n\n\
+                     A. {}\n\
+                     B. {}\n\
+                     C. {}\n\
+                     D. {}\n\n\
+                     Answer: B\n\n",
+                    i + 1,
+                    i,
+                    i,
+                    i,
+                    i * 2,
+                    i * 3,
+                    i * 4
+                ));
+            }
+            1 => {
+                questions.push_str(&format!(
+                    "{}. The value of {} + {} equals {}.\n\n\
+                     True\n\n",
+                    i + 1,
+                    i,
+                    i,
+                    i * 2
+                ));
+            }
+            2 => {
+                questions.push_str(&format!(
+                    "{}. The capital of country {} is _____.\n\n\
+                     Answer: Capital {}\n\n",
+                    i + 1, i, i
+                ));
+            }
+            _ => {
+                questions.push_str(&format!(
+                    "{}. Explain the mathematical formula: $x^2 + y^2 = z^2$\n\n\
+                     Answer: This is the Pythagorean theorem.\n\n",
+                    i + 1
+                ));
+            }
+        }
+    }
+
+    questions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_markdown;
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let config = SampleConfig::default().with_count(20);
+        assert_eq!(generate_markdown(&config), generate_markdown(&config));
+        assert_ne!(
+            generate_markdown(&config),
+            generate_markdown(&config.clone().with_seed(7))
+        );
+    }
+
+    #[test]
+    fn test_generated_bank_parses() {
+        let config = SampleConfig::default().with_count(25);
+        let questions = parse_markdown(&generate_markdown(&config)).unwrap();
+        assert_eq!(questions.len(), 25);
+    }
+
+    #[test]
+    fn test_generate_selected_types() {
+        let config = SampleConfig::default()
+            .with_count(4)
+            .with_types(vec![QuestionType::TrueFalse])
+            .with_latex(false)
+            .with_images(false);
+        let markdown = generate_markdown(&config);
+        assert_eq!(markdown.matches("[判断]").count(), 4);
+        assert!(!markdown.contains('$'));
+        assert!(!markdown.contains("!["));
+    }
+
+    #[test]
+    fn test_generate_numbered() {
+        let markdown = generate_numbered(8);
+        assert!(markdown.starts_with("1. What is 0 + 0?"));
+        assert!(markdown.contains("8. Explain the mathematical formula"));
+    }
+}
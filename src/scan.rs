@@ -0,0 +1,155 @@
+//! Single-pass keyword scanning
+//!
+//! Classifying a question and reading its answer lines both ask whether a
+//! text contains, or starts with, any of dozens of keywords. Asking each
+//! keyword with `str::contains` walks the text once per keyword. A
+//! [`TextScanner`] compiles all keyword groups into one Aho-Corasick
+//! automaton and finds every occurrence in a single pass.
+//!
+//! Prefixes are only compared at the start of a text, where a table of
+//! possible first bytes turns most lines away before any keyword is tried.
+//!
+//! ```
+//! use md2db::scan::TextScanner;
+//!
+//! let scanner = TextScanner::new([vec!["多选", "all that apply"], vec!["判断", "true or false"]]);
+//! let hits = scanner.scan("select all that apply");
+//! assert!(hits.any(0));
+//! assert!(!hits.any(1));
+//! assert_eq!(scanner.strip_prefix("判断：1 + 1 = 2"), Some((1, "：1 + 1 = 2")));
+//! ```
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// Keyword groups compiled for searching in one pass
+#[derive(Debug, Clone)]
+pub struct TextScanner {
+    /// Reports every occurrence; built by the first [`TextScanner::scan`]
+    automaton: OnceLock<AhoCorasick>,
+    /// Whether some keyword starts with each byte
+    first_bytes: [bool; 256],
+    /// Keyword of each pattern, group by group
+    keywords: Vec<String>,
+    /// Patterns of each group
+    groups: Vec<Range<usize>>,
+}
+
+impl TextScanner {
+    /// Scanner for `groups` of keywords, numbered from 0 in order
+    pub fn new<G, S>(groups: impl IntoIterator<Item = G>) -> Self
+    where
+        G: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut keywords: Vec<String> = Vec::new();
+        let mut ranges = Vec::new();
+        for members in groups {
+            let start = keywords.len();
+            keywords.extend(members.into_iter().map(|k| k.as_ref().to_string()));
+            ranges.push(start..keywords.len());
+        }
+
+        let mut first_bytes = [false; 256];
+        for keyword in &keywords {
+            match keyword.as_bytes().first() {
+                Some(&byte) => first_bytes[byte as usize] = true,
+                // An empty keyword is a prefix of everything
+                None => first_bytes = [true; 256],
+            }
+        }
+        Self { automaton: OnceLock::new(), first_bytes, keywords, groups: ranges }
+    }
+
+    /// Every keyword that occurs anywhere in `text`
+    pub fn scan(&self, text: &str) -> ScanHits<'_> {
+        // Standard semantics so overlapping keywords ("多选" and "多选题")
+        // are all reported
+        let automaton = self.automaton.get_or_init(|| {
+            AhoCorasickBuilder::new()
+                .match_kind(MatchKind::Standard)
+                .build(&self.keywords)
+                .expect("keyword tables are small enough for an automaton")
+        });
+        let mut matched = vec![false; self.keywords.len()];
+        for found in automaton.find_overlapping_iter(text) {
+            matched[found.pattern().as_usize()] = true;
+        }
+        ScanHits { scanner: self, matched }
+    }
+
+    /// The group of the keyword `text` starts with, and the text after it
+    ///
+    /// When several keywords are prefixes of `text`, the one listed first
+    /// wins, as if the groups were tried one keyword at a time.
+    pub fn strip_prefix<'t>(&self, text: &'t str) -> Option<(usize, &'t str)> {
+        if let Some(&first) = text.as_bytes().first() {
+            if !self.first_bytes[first as usize] {
+                return None;
+            }
+        }
+        let (pattern, rest) = self
+            .keywords
+            .iter()
+            .enumerate()
+            .find_map(|(pattern, keyword)| Some((pattern, text.strip_prefix(keyword.as_str())?)))?;
+        let group = self.groups.partition_point(|range| range.end <= pattern);
+        Some((group, rest))
+    }
+}
+
+/// The keywords [`TextScanner::scan`] found in a text
+#[derive(Debug, Clone)]
+pub struct ScanHits<'s> {
+    scanner: &'s TextScanner,
+    /// Whether each pattern occurred
+    matched: Vec<bool>,
+}
+
+impl<'s> ScanHits<'s> {
+    /// Whether any keyword of `group` occurred
+    pub fn any(&self, group: usize) -> bool {
+        self.keywords(group).next().is_some()
+    }
+
+    /// The keywords of `group` that occurred, in the order they were listed
+    pub fn keywords(&self, group: usize) -> impl Iterator<Item = &'s str> + '_ {
+        let scanner = self.scanner;
+        scanner
+            .groups
+            .get(group)
+            .cloned()
+            .unwrap_or_default()
+            .filter(move |&pattern| self.matched[pattern])
+            .map(move |pattern| scanner.keywords[pattern].as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_reports_overlapping_keywords() {
+        let scanner = TextScanner::new([vec!["多选", "多选题", "选题"], vec!["判断"], vec![]]);
+        let hits = scanner.scan("本题为多选题");
+        assert_eq!(hits.keywords(0).collect::<Vec<_>>(), ["多选", "多选题", "选题"]);
+        assert!(!hits.any(1));
+        assert!(!hits.any(2));
+        assert!(!hits.any(7));
+    }
+
+    #[test]
+    fn test_strip_prefix_prefers_first_listed() {
+        let scanner = TextScanner::new([vec!["答案", "答案："], vec!["正确答案："]]);
+        assert_eq!(scanner.strip_prefix("答案：B"), Some((0, "：B")));
+        assert_eq!(scanner.strip_prefix("正确答案：B"), Some((1, "B")));
+        assert_eq!(scanner.strip_prefix("本题答案：B"), None);
+        assert_eq!(scanner.strip_prefix(""), None);
+
+        let scanner = TextScanner::new([vec!["Answer:"], vec![""]]);
+        assert_eq!(scanner.strip_prefix("Answer: B"), Some((0, " B")));
+        assert_eq!(scanner.strip_prefix("anything"), Some((1, "anything")));
+    }
+}
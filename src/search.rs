@@ -0,0 +1,248 @@
+//! Full-text search over questions
+//!
+//! A [`SearchQuery`] is split into terms at whitespace; a question matches
+//! when every term occurs in its stem, options, answer, analysis or tags,
+//! ignoring case. Matches are scored by how often the terms occur, with hits
+//! in the stem counting most.
+//!
+//! Teachers working in both languages often type Chinese terms in pinyin.
+//! With the `pinyin` feature, Latin terms also match Chinese text through
+//! its toneless pinyin, starting at a syllable: `suanfa` finds `算法` and
+//! `fuza` finds `复杂度`. Chinese terms still match only Chinese text.
+
+use crate::models::Question;
+use serde::Serialize;
+
+/// Weight of a hit in the stem
+const STEM_WEIGHT: u32 = 3;
+/// Weight of a hit in the tags
+const TAG_WEIGHT: u32 = 2;
+/// Weight of a hit in the options, answer or analysis
+const BODY_WEIGHT: u32 = 1;
+
+/// A question matching a search, with its relevance
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    /// Higher is more relevant
+    pub score: u32,
+    pub question: Question,
+}
+
+/// Search terms, parsed from what the user typed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    terms: Vec<String>,
+}
+
+impl SearchQuery {
+    /// Parse a query; terms are separated by whitespace
+    pub fn parse(query: &str) -> Self {
+        let mut terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        terms.dedup();
+        Self { terms }
+    }
+
+    /// Whether there is nothing to search for
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Relevance of `question`, or `None` if some term does not occur in it
+    pub fn score(&self, question: &Question) -> Option<u32> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut fields = vec![(SearchText::new(&question.stem), STEM_WEIGHT)];
+        fields.extend(question.options.iter().map(|o| (SearchText::new(&o.content), BODY_WEIGHT)));
+        fields.extend(question.answer.iter().chain(&question.analysis).map(|t| (SearchText::new(t), BODY_WEIGHT)));
+        fields.extend(question.tags.iter().map(|t| (SearchText::new(t), TAG_WEIGHT)));
+
+        let mut score = 0;
+        for term in &self.terms {
+            let hits: u32 = fields.iter().map(|(text, weight)| text.count(term) as u32 * weight).sum();
+            if hits == 0 {
+                return None;
+            }
+            score += hits;
+        }
+        Some(score)
+    }
+}
+
+/// Keeps the best matches of a search while questions are fed to it
+#[derive(Debug)]
+pub struct SearchResults {
+    query: SearchQuery,
+    limit: usize,
+    hits: Vec<SearchHit>,
+}
+
+impl SearchResults {
+    /// Collect up to `limit` matches of `query`
+    pub fn new(query: SearchQuery, limit: usize) -> Self {
+        Self { query, limit, hits: Vec::new() }
+    }
+
+    /// Score `questions`, keeping those that match
+    pub fn extend(&mut self, questions: impl IntoIterator<Item = Question>) {
+        for question in questions {
+            if let Some(score) = self.query.score(&question) {
+                self.hits.push(SearchHit { score, question });
+            }
+        }
+        // Trim as we go so a large bank does not pile up in memory
+        if self.hits.len() > self.limit * 2 {
+            self.sort();
+            self.hits.truncate(self.limit);
+        }
+    }
+
+    /// Best first; ties go to the oldest question
+    fn sort(&mut self) {
+        self.hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.question.created_at.cmp(&b.question.created_at))
+                .then(a.question.id.cmp(&b.question.id))
+        });
+    }
+
+    /// The best matches, most relevant first
+    pub fn finish(mut self) -> Vec<SearchHit> {
+        self.sort();
+        self.hits.truncate(self.limit);
+        self.hits
+    }
+}
+
+/// Search `questions` for `query`, returning up to `limit` matches
+pub fn search(questions: impl IntoIterator<Item = Question>, query: &str, limit: usize) -> Vec<SearchHit> {
+    let mut results = SearchResults::new(SearchQuery::parse(query), limit);
+    results.extend(questions);
+    results.finish()
+}
+
+/// One field of a question, prepared for matching
+struct SearchText {
+    lower: String,
+    /// The text with Chinese characters spelled in pinyin, and the byte
+    /// offsets where a term may start matching it
+    #[cfg(feature = "pinyin")]
+    pinyin: Option<(String, Vec<usize>)>,
+}
+
+impl SearchText {
+    fn new(text: &str) -> Self {
+        Self {
+            lower: text.to_lowercase(),
+            #[cfg(feature = "pinyin")]
+            pinyin: pinyin::spell(text),
+        }
+    }
+
+    /// Occurrences of `term`
+    fn count(&self, term: &str) -> usize {
+        let direct = self.lower.matches(term).count();
+        #[cfg(feature = "pinyin")]
+        if let Some((spelled, starts)) = &self.pinyin {
+            if direct == 0 && term.is_ascii() {
+                return spelled
+                    .match_indices(term)
+                    .filter(|(idx, _)| starts.binary_search(idx).is_ok())
+                    .count();
+            }
+        }
+        direct
+    }
+}
+
+#[cfg(feature = "pinyin")]
+mod pinyin {
+    /// Whether `c` is a CJK ideograph
+    pub fn is_han(c: char) -> bool {
+        matches!(c as u32,
+            0x4E00..=0x9FFF      // CJK Unified Ideographs
+            | 0x3400..=0x4DBF    // Extension A
+            | 0xF900..=0xFAFF    // Compatibility Ideographs
+            | 0x20000..=0x2FA1F  // Extensions B-F and supplement
+        )
+    }
+
+    /// Spell the Chinese characters of `text` in lowercase toneless pinyin
+    ///
+    /// Returns the spelled text with the offsets of every syllable and word
+    /// start, or `None` when the text has no Chinese characters.
+    pub fn spell(text: &str) -> Option<(String, Vec<usize>)> {
+        if !text.chars().any(is_han) {
+            return None;
+        }
+        let mut spelled = String::with_capacity(text.len() * 2);
+        let mut starts = Vec::new();
+        let mut in_word = false;
+        for c in text.chars() {
+            if is_han(c) {
+                let syllable = any_ascii::any_ascii_char(c);
+                if !syllable.is_empty() {
+                    starts.push(spelled.len());
+                    spelled.push_str(&syllable.to_ascii_lowercase());
+                }
+                in_word = false;
+            } else {
+                if c.is_alphanumeric() && !in_word {
+                    starts.push(spelled.len());
+                }
+                in_word = c.is_alphanumeric();
+                spelled.extend(c.to_lowercase());
+            }
+        }
+        Some((spelled, starts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    fn question(stem: &str) -> Question {
+        Question { stem: stem.to_string(), ..Question::default() }
+    }
+
+    #[test]
+    fn test_search_terms_and_scoring() {
+        let sorting = Question {
+            options: vec![QuestionOption { content: "快速排序".to_string(), sort_order: 0, is_correct: true }],
+            ..question("Which sort is O(n log n)?")
+        };
+        let questions = vec![question("算法的时间复杂度"), sorting, question("Sort the list: SORT")];
+
+        let hits = search(questions.clone(), "sort", 10);
+        let stems: Vec<&str> = hits.iter().map(|h| h.question.stem.as_str()).collect();
+        assert_eq!(stems, ["Sort the list: SORT", "Which sort is O(n log n)?"]);
+        assert_eq!(hits[0].score, 2 * STEM_WEIGHT);
+
+        // Every term must occur, in any field
+        assert_eq!(search(questions.clone(), "sort 排序", 10).len(), 1);
+        assert_eq!(search(questions.clone(), "复杂度", 10)[0].question.stem, "算法的时间复杂度");
+        assert!(search(questions.clone(), "sort graph", 10).is_empty());
+        assert!(search(questions.clone(), "   ", 10).is_empty());
+        assert_eq!(search(questions, "sort", 1).len(), 1);
+    }
+
+    #[cfg(feature = "pinyin")]
+    #[test]
+    fn test_pinyin_search() {
+        let questions = vec![question("算法的时间复杂度"), question("Which sort is O(n log n)?"), question("数组")];
+
+        let stems = |query: &str| -> Vec<String> {
+            search(questions.clone(), query, 10).into_iter().map(|h| h.question.stem).collect()
+        };
+        assert_eq!(stems("suanfa"), ["算法的时间复杂度"]);
+        assert_eq!(stems("SuanFa fuza"), ["算法的时间复杂度"]);
+        assert_eq!(stems("shuzu"), ["数组"]);
+        // Terms start at a syllable, not inside one
+        assert!(stems("uanfa").is_empty());
+        // Latin text still matches directly
+        assert_eq!(stems("sort"), ["Which sort is O(n log n)?"]);
+    }
+}
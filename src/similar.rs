@@ -0,0 +1,161 @@
+//! Finding stored questions similar to a given one
+//!
+//! Content teams importing a new bank want to know whether a question, or a
+//! variant of it with different numbers or wording, was imported before.
+//! [`SimilarityIndex`] compares questions on their normalized stems (see
+//! [`crate::duplicates::normalized_stem`]) using TF-IDF weighted character
+//! bigrams and cosine similarity: bigrams that occur in most stems, such as
+//! `下列` or `th`, count for little, so two stems sharing the unusual parts
+//! of their wording rank highest. Unlike [`crate::dedup`], which only looks
+//! for near-identical text, this ranks every question, so variants with a
+//! changed number or option still show up.
+
+use crate::duplicates::normalized_stem;
+use crate::models::Question;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A stored question and how similar it is to the one searched for
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarQuestion {
+    /// Cosine similarity of the stems (0.0..=1.0)
+    pub similarity: f64,
+    pub question: Question,
+}
+
+/// Two adjacent characters of a normalized stem
+type Bigram = (char, char);
+
+/// TF-IDF weights of a stem's bigrams, with the vector's length
+struct Vector {
+    weights: HashMap<Bigram, f64>,
+    norm: f64,
+}
+
+/// Character bigram counts of a normalized stem
+fn bigrams(question: &Question) -> HashMap<Bigram, u32> {
+    let chars: Vec<char> = normalized_stem(question).chars().collect();
+    let mut counts = HashMap::new();
+    if let [only] = chars[..] {
+        // A one-character stem still has a term to compare
+        *counts.entry((only, only)).or_default() += 1;
+    }
+    for pair in chars.windows(2) {
+        *counts.entry((pair[0], pair[1])).or_default() += 1;
+    }
+    counts
+}
+
+/// TF-IDF vectors of a set of questions, ready to be compared
+pub struct SimilarityIndex {
+    questions: Vec<Question>,
+    vectors: Vec<Vector>,
+    /// Number of questions each bigram occurs in
+    document_frequency: HashMap<Bigram, usize>,
+}
+
+impl SimilarityIndex {
+    /// Index `questions`; their bigram frequencies weight every comparison
+    pub fn new(questions: Vec<Question>) -> Self {
+        let counts: Vec<_> = questions.iter().map(bigrams).collect();
+        let mut document_frequency = HashMap::new();
+        for terms in &counts {
+            for term in terms.keys() {
+                *document_frequency.entry(*term).or_default() += 1;
+            }
+        }
+        let mut index = Self { questions, vectors: Vec::new(), document_frequency };
+        index.vectors = counts.into_iter().map(|terms| index.weigh(terms)).collect();
+        index
+    }
+
+    /// Number of indexed questions
+    pub fn len(&self) -> usize {
+        self.questions.len()
+    }
+
+    /// Whether nothing is indexed
+    pub fn is_empty(&self) -> bool {
+        self.questions.is_empty()
+    }
+
+    /// Smoothed inverse document frequency of `term`
+    fn idf(&self, term: &Bigram) -> f64 {
+        let df = self.document_frequency.get(term).copied().unwrap_or(0);
+        ((1 + self.questions.len()) as f64 / (1 + df) as f64).ln() + 1.0
+    }
+
+    fn weigh(&self, terms: HashMap<Bigram, u32>) -> Vector {
+        let weights: HashMap<_, _> = terms
+            .into_iter()
+            .map(|(term, count)| {
+                let weight = count as f64 * self.idf(&term);
+                (term, weight)
+            })
+            .collect();
+        let norm = weights.values().map(|w| w * w).sum::<f64>().sqrt();
+        Vector { weights, norm }
+    }
+
+    /// The `limit` indexed questions most similar to `question`, most similar
+    /// first
+    ///
+    /// `question` itself (by ID) and questions sharing no bigram with it are
+    /// left out.
+    pub fn most_similar(&self, question: &Question, limit: usize) -> Vec<SimilarQuestion> {
+        let target = self.weigh(bigrams(question));
+        if target.norm == 0.0 {
+            return Vec::new();
+        }
+        let mut scored: Vec<(usize, f64)> = self
+            .vectors
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.questions[*idx].id != question.id)
+            .filter_map(|(idx, vector)| {
+                let dot: f64 = target.weights.iter().filter_map(|(term, w)| vector.weights.get(term).map(|v| v * w)).sum();
+                (dot > 0.0).then(|| (idx, (dot / (vector.norm * target.norm)).min(1.0)))
+            })
+            .collect();
+        scored.sort_by(|a, b| {
+            b.1.total_cmp(&a.1)
+                .then(self.questions[a.0].created_at.cmp(&self.questions[b.0].created_at))
+        });
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(idx, similarity)| SimilarQuestion { similarity, question: self.questions[idx].clone() })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(stem: &str) -> Question {
+        Question { stem: stem.to_string(), ..Question::default() }
+    }
+
+    #[test]
+    fn test_most_similar_ranks_variants_first() {
+        let target = question("12. 下列关于快速排序的说法正确的是");
+        let variant = question("下列关于快速排序的说法中，正确的是");
+        let related = question("下列关于归并排序的说法正确的是");
+        let unrelated = question("What is the capital of France?");
+        let index = SimilarityIndex::new(vec![unrelated, related.clone(), target.clone(), variant.clone()]);
+        assert_eq!(index.len(), 4);
+
+        let similar = index.most_similar(&target, 10);
+        let ids: Vec<_> = similar.iter().map(|s| s.question.id).collect();
+        assert_eq!(ids, [variant.id, related.id]);
+        assert!(similar[0].similarity > similar[1].similarity);
+        assert!(similar.iter().all(|s| (0.0..=1.0).contains(&s.similarity)));
+
+        assert_eq!(index.most_similar(&target, 1).len(), 1);
+        // Numbering, case and punctuation do not matter
+        let copy = question("下列关于快速排序的说法正确的是？");
+        assert!((index.most_similar(&copy, 1)[0].similarity - 1.0).abs() < 1e-9);
+        assert!(index.most_similar(&question("？"), 10).is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! SQLite export
+//!
+//! [`export_sqlite`] writes stored questions into a single SQLite file, for
+//! users who want a database without running PostgreSQL. The `questions`
+//! table follows the PostgreSQL JSON layout: scalar fields get their own
+//! columns and options, images, LaTeX, tags and provenance are stored as
+//! JSON text. Exporting into an existing file adds to it, replacing rows
+//! with the same ID.
+
+use crate::database::QuestionRepository;
+use crate::export::{ExportFilter, ExportPages};
+use crate::models::Question;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS questions (
+    id             TEXT PRIMARY KEY,
+    type           TEXT NOT NULL,
+    stem           TEXT NOT NULL,
+    answer         TEXT,
+    analysis       TEXT,
+    options        TEXT NOT NULL DEFAULT '[]',
+    images         TEXT NOT NULL DEFAULT '[]',
+    latex          TEXT NOT NULL DEFAULT '[]',
+    tags           TEXT NOT NULL DEFAULT '[]',
+    text_direction TEXT NOT NULL DEFAULT 'ltr',
+    provenance     TEXT,
+    bank_id        TEXT,
+    sequence       INTEGER,
+    section        TEXT,
+    number         TEXT,
+    type_source    TEXT,
+    created_at     TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_questions_type ON questions (type);
+CREATE INDEX IF NOT EXISTS idx_questions_bank ON questions (bank_id, sequence);
+";
+
+/// Columns added after the first release, with their types
+const ADDED_COLUMNS: &[(&str, &str)] = &[("section", "TEXT"), ("number", "TEXT"), ("type_source", "TEXT")];
+
+/// Write the questions matching `filter` to the SQLite file at `path`
+///
+/// The file and table are created if needed. Returns the number of
+/// questions written.
+pub async fn export_sqlite(repo: &dyn QuestionRepository, path: impl AsRef<Path>, filter: &ExportFilter) -> Result<usize> {
+    let questions = ExportPages::new(repo, filter).collect().await?;
+
+    let path: PathBuf = path.as_ref().to_path_buf();
+    tokio::task::spawn_blocking(move || write_questions(&path, &questions))
+        .await
+        .context("SQLite export task failed")?
+}
+
+fn write_questions(path: &Path, questions: &[Question]) -> Result<usize> {
+    let mut conn = Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    conn.execute_batch(SCHEMA)?;
+    add_missing_columns(&conn)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR REPLACE INTO questions
+                (id, type, stem, answer, analysis, options, images, latex, tags, text_direction, provenance, bank_id, sequence, section, number, type_source, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        )?;
+        for q in questions {
+            insert.execute(params![
+                q.id.to_string(),
+                name(&q.qtype)?,
+                q.stem,
+                q.answer,
+                q.analysis,
+                serde_json::to_string(&q.options)?,
+                serde_json::to_string(&q.images)?,
+                serde_json::to_string(&q.latex)?,
+                serde_json::to_string(&q.tags)?,
+                name(&q.text_direction)?,
+                q.provenance.as_ref().map(serde_json::to_string).transpose()?,
+                q.bank_id.map(|id| id.to_string()),
+                q.sequence.map(|s| s as i64),
+                q.section,
+                q.number,
+                q.type_source.as_ref().map(name).transpose()?,
+                q.created_at.to_rfc3339(),
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(questions.len())
+}
+
+/// Upgrade a table written by an earlier release, which lacks newer columns
+fn add_missing_columns(conn: &Connection) -> Result<()> {
+    let mut columns = conn.prepare("SELECT name FROM pragma_table_info('questions')")?;
+    let existing = columns
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (column, kind) in ADDED_COLUMNS {
+        if !existing.iter().any(|name| name == column) {
+            conn.execute_batch(&format!("ALTER TABLE questions ADD COLUMN {} {}", column, kind))?;
+        }
+    }
+    Ok(())
+}
+
+/// Serialized name of a unit enum variant, without JSON quotes
+fn name(value: &impl serde::Serialize) -> Result<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(name) => Ok(name),
+        other => Ok(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MockRepository;
+    use crate::models::QuestionOption;
+
+    #[tokio::test]
+    async fn test_export_sqlite() {
+        let repo = MockRepository::new();
+        let question = Question {
+            stem: "2+2?".to_string(),
+            options: vec![QuestionOption {
+                content: "4".to_string(),
+                sort_order: 0,
+                is_correct: true,
+            }],
+            tags: vec!["math".to_string()],
+            ..Question::default()
+        };
+        repo.save_batch(&[question.clone(), Question::default()]).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bank.sqlite");
+        assert_eq!(export_sqlite(&repo, &path, &ExportFilter::default()).await.unwrap(), 2);
+        // Exporting again replaces the rows instead of adding copies
+        export_sqlite(&repo, &path, &ExportFilter::default()).await.unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM questions", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+        let (qtype, stem, options, tags): (String, String, String, String) = conn
+            .query_row(
+                "SELECT type, stem, options, tags FROM questions WHERE id = ?1",
+                [question.id.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(qtype, name(&question.qtype).unwrap());
+        assert!(!qtype.starts_with('"'));
+        assert_eq!(stem, "2+2?");
+        let options: Vec<QuestionOption> = serde_json::from_str(&options).unwrap();
+        assert_eq!(options[0].content, "4");
+        assert_eq!(tags, r#"["math"]"#);
+    }
+
+    #[tokio::test]
+    async fn test_export_sqlite_upgrades_older_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bank.sqlite");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(&SCHEMA.replace("section        TEXT,", "").replace("number         TEXT,", ""))
+            .unwrap();
+
+        let repo = MockRepository::new();
+        let question = Question {
+            stem: "2+2?".to_string(),
+            section: Some("一、单选题".to_string()),
+            number: Some("1".to_string()),
+            ..Question::default()
+        };
+        repo.save_batch(&[question]).await.unwrap();
+        assert_eq!(export_sqlite(&repo, &path, &ExportFilter::default()).await.unwrap(), 1);
+
+        let conn = Connection::open(&path).unwrap();
+        let (section, number): (String, String) = conn
+            .query_row("SELECT section, number FROM questions", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((section.as_str(), number.as_str()), ("一、单选题", "1"));
+    }
+}
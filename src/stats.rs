@@ -0,0 +1,209 @@
+//! Statistics over stored questions
+//!
+//! [`QuestionStats`] summarizes a repository for dashboards tracking a
+//! migration: how many questions of each type, in each bank, flagged for
+//! review by the classifier and written in each language, and how many were
+//! imported each day. Repositories compute it with
+//! [`QuestionRepository::stats`](crate::database::QuestionRepository::stats).
+
+use crate::classifier::{classify_question, Language};
+use crate::models::{Question, QuestionBank, QuestionType};
+use crate::typeset::is_cjk;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Counts over the stored questions
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QuestionStats {
+    /// Number of stored questions
+    pub total: u64,
+    /// Questions of each type, keyed by its API name; every type is listed
+    pub by_type: BTreeMap<String, u64>,
+    /// Questions in each bank, largest first; `bank_id` is null for
+    /// questions in no bank
+    pub by_bank: Vec<BankCount>,
+    /// Questions the classifier is or is not confident about
+    pub review: ReviewCounts,
+    /// Questions by the language of their stem: `zh`, `en` or `other`
+    pub by_language: BTreeMap<String, u64>,
+    /// Questions imported each day (UTC), oldest first
+    pub imports: Vec<DailyCount>,
+}
+
+/// Number of questions in a bank
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BankCount {
+    pub bank_id: Option<Uuid>,
+    /// Name of the bank, when it still exists
+    pub name: Option<String>,
+    pub count: u64,
+}
+
+/// Questions split by the classifier's confidence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ReviewCounts {
+    /// Detected with low confidence, or not at all
+    pub needs_review: u64,
+    /// Detected with high confidence
+    pub confident: u64,
+}
+
+/// Number of questions created on a day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DailyCount {
+    pub date: NaiveDate,
+    pub count: u64,
+}
+
+/// Builds [`QuestionStats`] one question at a time
+#[derive(Debug, Default)]
+pub struct StatsCollector {
+    total: u64,
+    by_type: BTreeMap<String, u64>,
+    by_bank: BTreeMap<Option<Uuid>, u64>,
+    review: ReviewCounts,
+    by_language: BTreeMap<String, u64>,
+    imports: BTreeMap<NaiveDate, u64>,
+}
+
+impl StatsCollector {
+    /// Start with no questions counted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a question
+    pub fn add(&mut self, question: &Question) {
+        self.total += 1;
+        *self.by_type.entry(type_name(question.qtype)).or_default() += 1;
+        *self.by_bank.entry(question.bank_id).or_default() += 1;
+
+        match classify_question(question, None) {
+            Some(result) if !result.needs_review => self.review.confident += 1,
+            _ => self.review.needs_review += 1,
+        }
+
+        let language = match detect_language(&question.stem) {
+            Some(Language::Zh) => "zh",
+            Some(Language::En) => "en",
+            None => "other",
+        };
+        *self.by_language.entry(language.to_string()).or_default() += 1;
+        *self.imports.entry(question.created_at.date_naive()).or_default() += 1;
+    }
+
+    /// The counts so far, naming banks from `banks`
+    pub fn finish(self, banks: &[QuestionBank]) -> QuestionStats {
+        let mut by_type = self.by_type;
+        for qtype in QuestionType::ALL {
+            by_type.entry(type_name(qtype)).or_default();
+        }
+
+        let mut by_bank: Vec<BankCount> = self
+            .by_bank
+            .into_iter()
+            .map(|(bank_id, count)| BankCount {
+                bank_id,
+                name: bank_id.and_then(|id| banks.iter().find(|b| b.id == id)).map(|b| b.name.clone()),
+                count,
+            })
+            .collect();
+        by_bank.sort_by_key(|b| std::cmp::Reverse(b.count));
+
+        QuestionStats {
+            total: self.total,
+            by_type,
+            by_bank,
+            review: self.review,
+            by_language: self.by_language,
+            imports: self
+                .imports
+                .into_iter()
+                .map(|(date, count)| DailyCount { date, count })
+                .collect(),
+        }
+    }
+}
+
+/// API name of a question type
+pub(crate) fn type_name(qtype: QuestionType) -> String {
+    serde_json::to_value(qtype)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Language a text is written in, judged by its script
+///
+/// Any CJK character makes it Chinese; otherwise Latin letters make it
+/// English. Text with neither, such as a bare formula, has no language.
+pub fn detect_language(text: &str) -> Option<Language> {
+    if text.chars().any(is_cjk) {
+        Some(Language::Zh)
+    } else if text.chars().any(|c| c.is_ascii_alphabetic()) {
+        Some(Language::En)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_collect_stats() {
+        let bank = QuestionBank::new("Physics");
+        let choice = Question {
+            qtype: QuestionType::Choice,
+            stem: "下列说法正确的是".to_string(),
+            options: ["A. 对", "B. 错", "C. 都对", "D. 都错"]
+                .iter()
+                .zip(0..)
+                .map(|(content, sort_order)| QuestionOption {
+                    content: content.to_string(),
+                    sort_order,
+                    is_correct: false,
+                })
+                .collect(),
+            bank_id: Some(bank.id),
+            created_at: Utc.with_ymd_and_hms(2026, 3, 2, 8, 0, 0).unwrap(),
+            ..Question::default()
+        };
+        let essay = Question {
+            stem: "Discuss".to_string(),
+            created_at: Utc.with_ymd_and_hms(2026, 3, 1, 8, 0, 0).unwrap(),
+            ..Question::default()
+        };
+        let formula = Question {
+            stem: "$1 + 1 = 2$".to_string(),
+            created_at: Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap(),
+            bank_id: Some(bank.id),
+            ..essay.clone()
+        };
+
+        let mut collector = StatsCollector::new();
+        for question in [&choice, &essay, &formula] {
+            collector.add(question);
+        }
+        let stats = collector.finish(std::slice::from_ref(&bank));
+
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.by_type["choice"], 1);
+        assert_eq!(stats.by_type["subjective"], 2);
+        assert_eq!(stats.by_type["true_false"], 0);
+        assert_eq!(stats.by_bank[0], BankCount { bank_id: Some(bank.id), name: Some("Physics".to_string()), count: 2 });
+        assert_eq!(stats.by_bank[1].bank_id, None);
+        assert_eq!(stats.review.needs_review + stats.review.confident, 3);
+        assert!(stats.review.confident >= 1);
+        assert_eq!(stats.by_language["zh"], 1);
+        assert_eq!(stats.by_language["en"], 1);
+        assert_eq!(stats.by_language["other"], 1);
+        let days: Vec<_> = stats.imports.iter().map(|d| (d.date.to_string(), d.count)).collect();
+        assert_eq!(days, [("2026-03-01".to_string(), 1), ("2026-03-02".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,672 @@
+//! Spreadsheet import and export
+//!
+//! Many question banks are kept as spreadsheets with one question per row:
+//! a stem column, one column per option, then answer and analysis.
+//! [`TabularImporter`] reads CSV/TSV (`csv` feature) and Excel or
+//! OpenDocument workbooks (`xlsx` feature) and builds questions directly,
+//! without going through the Markdown parser. [`write_xlsx`] goes the other
+//! way, so reviewers can check a parsed batch in a spreadsheet.
+//!
+//! Columns are found by header name through a [`ColumnMapping`]. Each entry
+//! lists alternative headers separated by `|`, compared ignoring case,
+//! spaces, `_` and `-`, so the default mapping accepts both `optionA` and
+//! `Option A` as well as `选项A`.
+
+use crate::answer::validate_answers;
+use crate::bidi;
+use crate::latex::validate_formulas;
+use crate::models::{Provenance, Question, QuestionOption, QuestionType};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Spreadsheet formats accepted by [`TabularImporter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    /// Comma-separated values
+    Csv,
+    /// Tab-separated values
+    Tsv,
+    /// Excel (`.xlsx`, `.xlsm`, `.xls`, `.xlsb`) or OpenDocument (`.ods`) workbook
+    Workbook,
+}
+
+impl TableFormat {
+    /// Every format, enabled or not
+    pub const ALL: [TableFormat; 3] = [TableFormat::Csv, TableFormat::Tsv, TableFormat::Workbook];
+
+    /// Format of a file, from its extension
+    pub fn from_name(name: &str) -> Option<Self> {
+        let lower = name.to_lowercase();
+        let (_, extension) = lower.rsplit_once('.')?;
+        TableFormat::ALL
+            .into_iter()
+            .find(|format| format.extensions().contains(&extension))
+    }
+
+    /// File extensions of this format, without the dot
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            TableFormat::Csv => &["csv"],
+            TableFormat::Tsv => &["tsv", "tab"],
+            TableFormat::Workbook => &["xlsx", "xlsm", "xls", "xlsb", "ods"],
+        }
+    }
+
+    /// Whether this build can read the format
+    pub fn is_enabled(self) -> bool {
+        match self {
+            TableFormat::Csv | TableFormat::Tsv => cfg!(feature = "csv"),
+            TableFormat::Workbook => cfg!(feature = "xlsx"),
+        }
+    }
+}
+
+/// Extensions of every format this build can read, with the dot
+pub fn supported_extensions() -> Vec<String> {
+    TableFormat::ALL
+        .into_iter()
+        .filter(|format| format.is_enabled())
+        .flat_map(|format| format.extensions().iter().map(|ext| format!(".{}", ext)))
+        .collect()
+}
+
+/// Which headers hold which part of a question
+///
+/// Each value lists alternative header names separated by `|`. Only the stem
+/// column is required; missing option, answer, analysis, type and tags
+/// columns are left empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnMapping {
+    /// Question stem
+    pub stem: String,
+    /// Option columns, in option order
+    pub options: Vec<String>,
+    /// Answer: option letters, 1-based numbers or free text
+    pub answer: Option<String>,
+    /// Analysis or explanation
+    pub analysis: Option<String>,
+    /// Question type (`choice`, `single_choice`, `单选`, `判断题`, ...)
+    #[serde(rename = "type")]
+    pub question_type: Option<String>,
+    /// Tags, separated by `,`, `;`, `、` or `|`
+    pub tags: Option<String>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            stem: "stem|question|题干|题目".to_string(),
+            options: ('A'..='H')
+                .map(|letter| format!("option{0}|{0}|选项{0}", letter))
+                .collect(),
+            answer: Some("answer|答案|正确答案".to_string()),
+            analysis: Some("analysis|explanation|解析|答案解析".to_string()),
+            question_type: Some("type|题型".to_string()),
+            tags: Some("tags|标签|知识点".to_string()),
+        }
+    }
+}
+
+/// Header comparison key: lowercase without spaces, `_` or `-`
+fn header_key(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Column positions resolved against one header row
+#[derive(Debug)]
+struct Columns {
+    stem: usize,
+    options: Vec<usize>,
+    answer: Option<usize>,
+    analysis: Option<usize>,
+    question_type: Option<usize>,
+    tags: Option<usize>,
+}
+
+impl ColumnMapping {
+    fn find(headers: &[String], names: &str) -> Option<usize> {
+        let keys: Vec<_> = headers.iter().map(|h| header_key(h)).collect();
+        names
+            .split('|')
+            .map(header_key)
+            .filter(|name| !name.is_empty())
+            .find_map(|name| keys.iter().position(|key| *key == name))
+    }
+
+    fn resolve(&self, headers: &[String]) -> Result<Columns> {
+        let stem = Self::find(headers, &self.stem).ok_or_else(|| {
+            anyhow!(
+                "no stem column (expected one of: {}; found: {})",
+                self.stem.replace('|', ", "),
+                headers.join(", ")
+            )
+        })?;
+        let optional = |names: &Option<String>| names.as_deref().and_then(|names| Self::find(headers, names));
+        Ok(Columns {
+            stem,
+            options: self.options.iter().filter_map(|names| Self::find(headers, names)).collect(),
+            answer: optional(&self.answer),
+            analysis: optional(&self.analysis),
+            question_type: optional(&self.question_type),
+            tags: optional(&self.tags),
+        })
+    }
+}
+
+/// Questions read from a spreadsheet
+#[derive(Debug, Clone, Default)]
+pub struct TableImport {
+    /// Questions in row order
+    pub questions: Vec<Question>,
+    /// Skipped rows, unknown types and answer problems
+    pub warnings: Vec<String>,
+}
+
+/// Builds questions from spreadsheet rows
+#[derive(Debug, Clone, Default)]
+pub struct TabularImporter {
+    mapping: ColumnMapping,
+    sheet: Option<String>,
+}
+
+impl TabularImporter {
+    /// Create an importer with the default column mapping
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a custom column mapping
+    pub fn with_mapping(mut self, mapping: ColumnMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Read only this sheet of a workbook (default: every sheet)
+    pub fn with_sheet(mut self, sheet: impl Into<String>) -> Self {
+        self.sheet = Some(sheet.into());
+        self
+    }
+
+    /// Import a spreadsheet, choosing the format from `name`
+    pub fn import(&self, name: &str, data: &[u8]) -> Result<TableImport> {
+        let format = TableFormat::from_name(name)
+            .ok_or_else(|| anyhow!("{} is not a spreadsheet (expected {})", name, supported_extensions().join(", ")))?;
+        match format {
+            #[cfg(feature = "csv")]
+            TableFormat::Csv => self.import_delimited(name, data, b','),
+            #[cfg(feature = "csv")]
+            TableFormat::Tsv => self.import_delimited(name, data, b'\t'),
+            #[cfg(feature = "xlsx")]
+            TableFormat::Workbook => self.import_workbook(name, data),
+            #[allow(unreachable_patterns)]
+            format => Err(anyhow!("{} files are not supported by this build", format.extensions()[0])),
+        }
+    }
+
+    /// Import CSV (or, with `delimiter` b'\t', TSV) data
+    ///
+    /// Text may be UTF-8, UTF-16 or GB18030, as saved by Excel on Chinese
+    /// Windows.
+    #[cfg(feature = "csv")]
+    pub fn import_delimited(&self, source: &str, data: &[u8], delimiter: u8) -> Result<TableImport> {
+        let (text, _) = crate::zip::decode_text(data).ok_or_else(|| anyhow!("{}: unrecognized text encoding", source))?;
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes());
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            rows.push(record?.iter().map(str::to_string).collect());
+        }
+
+        let mut import = TableImport::default();
+        self.import_rows(source, rows, &mut import)?;
+        Ok(import)
+    }
+
+    /// Import every sheet of a workbook (or the one chosen with
+    /// [`TabularImporter::with_sheet`])
+    ///
+    /// Each sheet has its own header row. Sheets without a stem column are
+    /// skipped with a warning; it is an error if no sheet has one.
+    #[cfg(feature = "xlsx")]
+    pub fn import_workbook(&self, source: &str, data: &[u8]) -> Result<TableImport> {
+        use calamine::Reader;
+
+        let mut workbook = calamine::open_workbook_auto_from_rs(std::io::Cursor::new(data))
+            .map_err(|e| anyhow!("{}: not a readable workbook: {}", source, e))?;
+        let sheets = match &self.sheet {
+            Some(sheet) if workbook.sheet_names().contains(sheet) => vec![sheet.clone()],
+            Some(sheet) => return Err(anyhow!("{}: no sheet named {}", source, sheet)),
+            None => workbook.sheet_names(),
+        };
+
+        let mut import = TableImport::default();
+        let mut errors = Vec::new();
+        for sheet in sheets {
+            let range = workbook
+                .worksheet_range(&sheet)
+                .map_err(|e| anyhow!("{}/{}: {}", source, sheet, e))?;
+            if range.is_empty() {
+                continue;
+            }
+            let rows = range
+                .rows()
+                .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+                .collect();
+            let sheet_source = format!("{}/{}", source, sheet);
+            if let Err(e) = self.import_rows(&sheet_source, rows, &mut import) {
+                import.warnings.push(format!("Skipping sheet {}: {}", sheet, e));
+                errors.push(e);
+            }
+        }
+        if import.questions.is_empty() && !errors.is_empty() {
+            return Err(errors.remove(0));
+        }
+        Ok(import)
+    }
+
+    /// Build questions from a header row and data rows
+    fn import_rows(&self, source: &str, rows: Vec<Vec<String>>, import: &mut TableImport) -> Result<()> {
+        let mut rows = rows.into_iter().enumerate().skip_while(|(_, row)| row.iter().all(|c| c.trim().is_empty()));
+        let Some((_, headers)) = rows.next() else {
+            return Ok(());
+        };
+        let columns = self.mapping.resolve(&headers)?;
+
+        let start = import.questions.len();
+        for (index, row) in rows {
+            // Spreadsheet row numbers count from 1
+            let number = index + 1;
+            let cell = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+            };
+            if row.iter().all(|c| c.trim().is_empty()) {
+                continue;
+            }
+            let Some(stem) = cell(Some(columns.stem)) else {
+                import.warnings.push(format!("{} row {}: no stem, skipped", source, number));
+                continue;
+            };
+
+            let mut question = Question {
+                stem: stem.to_string(),
+                options: columns
+                    .options
+                    .iter()
+                    .filter_map(|&c| cell(Some(c)))
+                    .enumerate()
+                    .map(|(i, content)| QuestionOption {
+                        content: content.to_string(),
+                        sort_order: i as i32,
+                        is_correct: false,
+                    })
+                    .collect(),
+                answer: cell(columns.answer).map(str::to_string),
+                analysis: cell(columns.analysis).map(str::to_string),
+                tags: cell(columns.tags).map(split_tags).unwrap_or_default(),
+                provenance: Some(Provenance {
+                    source_file: Some(source.to_string()),
+                    ..Provenance::default()
+                }),
+                ..Question::default()
+            };
+
+            import.warnings.extend(
+                validate_answers(std::slice::from_mut(&mut question))
+                    .into_iter()
+                    .chain(validate_formulas(std::slice::from_mut(&mut question)))
+                    .map(|w| format!("{} row {}: {}", source, number, w)),
+            );
+            question.qtype = match cell(columns.question_type) {
+                Some(name) => parse_type(name).unwrap_or_else(|| {
+                    import
+                        .warnings
+                        .push(format!("{} row {}: unknown question type {}", source, number, name));
+                    infer_type(&question)
+                }),
+                None => infer_type(&question),
+            };
+            bidi::normalize_question(&mut question);
+            import.questions.push(question);
+        }
+
+        for (question, sequence) in import.questions[start..].iter_mut().zip(start as u64 + 1..) {
+            question.sequence = Some(sequence);
+        }
+        Ok(())
+    }
+}
+
+/// Question type from a type column, accepting common Chinese names
+fn parse_type(name: &str) -> Option<QuestionType> {
+    if let Ok(qtype) = name.parse() {
+        return Some(qtype);
+    }
+    let name = name.trim().trim_end_matches('题');
+    match name {
+        "单选" | "单项选择" | "选择" => Some(QuestionType::Choice),
+        "多选" | "多项选择" | "不定项选择" => Some(QuestionType::MultipleChoice),
+        "判断" => Some(QuestionType::TrueFalse),
+        "填空" => Some(QuestionType::FillInTheBlank),
+        "简答" | "问答" | "主观" | "论述" | "解答" | "计算" => Some(QuestionType::Subjective),
+        _ => None,
+    }
+}
+
+/// Type of a question without a type column: choice if it has options
+fn infer_type(question: &Question) -> QuestionType {
+    match question.options.iter().filter(|o| o.is_correct).count() {
+        _ if question.options.is_empty() => QuestionType::Subjective,
+        0 | 1 => QuestionType::Choice,
+        _ => QuestionType::MultipleChoice,
+    }
+}
+
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split([',', '，', ';', '；', '、', '|'])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Longest text Excel accepts in one cell
+#[cfg(feature = "xlsx")]
+const MAX_CELL_CHARS: usize = 32_767;
+
+/// Write questions as an Excel workbook, one row per question
+///
+/// Besides the stored fields, each row has the type the classifier detects
+/// and its confidence, so rows whose stored type looks wrong stand out. The
+/// option, answer, analysis, type and tags headers match the default
+/// [`ColumnMapping`], so an edited sheet can be imported again.
+#[cfg(feature = "xlsx")]
+pub fn write_xlsx(questions: &[Question]) -> Result<Vec<u8>> {
+    use crate::bidi::export_text;
+    use crate::classifier::classify;
+    use crate::render::{option_letter, strip_option_prefix};
+    use rust_xlsxwriter::{Format, Workbook};
+
+    let option_count = questions.iter().map(|q| q.options.len()).max().unwrap_or(0).max(4);
+    let mut headers = vec!["ID".to_string(), "Type".to_string(), "Detected Type".to_string(), "Confidence".to_string(), "Stem".to_string()];
+    headers.extend((0..option_count).map(|i| format!("Option {}", option_letter(i))));
+    headers.extend(["Answer", "Analysis", "Tags", "Source File"].map(str::to_string));
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Questions")?;
+    let bold = Format::new().set_bold();
+    for (col, header) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, header, &bold)?;
+    }
+
+    let type_name = |qtype: QuestionType| serde_json::to_value(qtype).ok().and_then(|v| v.as_str().map(str::to_string));
+    for (question, row) in questions.iter().zip(1u32..) {
+        let options: Vec<String> = question.options.iter().map(|o| o.content.clone()).collect();
+        let detected = classify(&question.stem, &options);
+
+        let mut cells = vec![
+            Some(question.id.to_string()),
+            type_name(question.qtype),
+            detected.as_ref().and_then(|result| type_name(result.qtype)),
+            None,
+            Some(export_text(question, &question.stem).into_owned()),
+        ];
+        cells.extend((0..option_count).map(|i| {
+            question.options.get(i).map(|o| export_text(question, strip_option_prefix(&o.content, i)).into_owned())
+        }));
+        cells.extend([
+            question.answer.clone(),
+            question.analysis.as_deref().map(|a| export_text(question, a).into_owned()),
+            (!question.tags.is_empty()).then(|| question.tags.join(", ")),
+            question.provenance.as_ref().and_then(|p| p.source_file.clone()),
+        ]);
+
+        for (col, cell) in cells.iter().enumerate() {
+            if let Some(text) = cell.as_deref().filter(|text| !text.is_empty()) {
+                let text: String = text.chars().take(MAX_CELL_CHARS).collect();
+                sheet.write_string(row, col as u16, text)?;
+            }
+        }
+        if let Some(result) = &detected {
+            sheet.write_number(row, 3, f64::from(result.confidence))?;
+        }
+    }
+
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, questions.len() as u32, headers.len() as u16 - 1)?;
+    sheet.set_column_width(0, 38)?;
+    sheet.set_column_width(4, 60)?;
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// Build an `.xlsx` workbook of inline-string cells, one sheet per entry
+#[cfg(all(test, feature = "xlsx"))]
+pub(crate) fn build_xlsx(sheets: &[(&str, &[&[&str]])]) -> Vec<u8> {
+    use std::io::{Cursor, Write};
+
+    let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let mut data = Vec::new();
+    {
+        let mut writer = ::zip::ZipWriter::new(Cursor::new(&mut data));
+        let options = ::zip::write::SimpleFileOptions::default();
+        let mut add = |name: &str, content: String| {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        };
+
+        let overrides: String = (1..=sheets.len())
+            .map(|i| format!(r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#, i))
+            .collect();
+        add(
+            "[Content_Types].xml",
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>{}</Types>"#,
+                overrides
+            ),
+        );
+        add(
+            "_rels/.rels",
+            r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#.to_string(),
+        );
+        let entries: String = sheets
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| format!(r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#, escape(name), i + 1, i + 1))
+            .collect();
+        add(
+            "xl/workbook.xml",
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>{}</sheets></workbook>"#,
+                entries
+            ),
+        );
+        let relationships: String = (1..=sheets.len())
+            .map(|i| format!(r#"<Relationship Id="rId{0}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{0}.xml"/>"#, i))
+            .collect();
+        add(
+            "xl/_rels/workbook.xml.rels",
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{}</Relationships>"#,
+                relationships
+            ),
+        );
+        for (i, (_, rows)) in sheets.iter().enumerate() {
+            let rows: String = rows
+                .iter()
+                .enumerate()
+                .map(|(r, cells)| {
+                    let cells: String = cells
+                        .iter()
+                        .enumerate()
+                        .map(|(c, value)| {
+                            format!(
+                                r#"<c r="{}{}" t="inlineStr"><is><t>{}</t></is></c>"#,
+                                (b'A' + c as u8) as char,
+                                r + 1,
+                                escape(value)
+                            )
+                        })
+                        .collect();
+                    format!(r#"<row r="{}">{}</row>"#, r + 1, cells)
+                })
+                .collect();
+            add(
+                &format!("xl/worksheets/sheet{}.xml", i + 1),
+                format!(
+                    r#"<?xml version="1.0" encoding="UTF-8"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>{}</sheetData></worksheet>"#,
+                    rows
+                ),
+            );
+        }
+        writer.finish().unwrap();
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_import_csv() {
+        let csv = "Stem,Option A,Option B,Option C,Option D,Answer,Analysis,Tags\n\
+                   \"Which is prime, 4 or 7?\",4,7,,,B,7 has no divisors,\"math, primes\"\n\
+                   ,,,,,,,\n\
+                   ,1,2,,,A,,\n\
+                   Pick the even numbers,2,3,4,,\"A,C\",,\n\
+                   Explain recursion,,,,,,,\n";
+        let import = TabularImporter::new().import("bank.csv", csv.as_bytes()).unwrap();
+
+        assert_eq!(import.questions.len(), 3);
+        let prime = &import.questions[0];
+        assert_eq!(prime.stem, "Which is prime, 4 or 7?");
+        assert_eq!(prime.options.len(), 2);
+        assert!(prime.options[1].is_correct);
+        assert_eq!(prime.qtype, QuestionType::Choice);
+        assert_eq!(prime.analysis.as_deref(), Some("7 has no divisors"));
+        assert_eq!(prime.tags, ["math", "primes"]);
+        assert_eq!(prime.provenance.as_ref().unwrap().source_file.as_deref(), Some("bank.csv"));
+        assert_eq!(import.questions[1].qtype, QuestionType::MultipleChoice);
+        assert_eq!(import.questions[1].answer.as_deref(), Some("AC"));
+        assert_eq!(import.questions[2].qtype, QuestionType::Subjective);
+        assert_eq!(
+            import.questions.iter().map(|q| q.sequence).collect::<Vec<_>>(),
+            [Some(1), Some(2), Some(3)]
+        );
+        assert_eq!(import.warnings, ["bank.csv row 4: no stem, skipped"]);
+
+        // GBK-encoded export with Chinese headers and a custom mapping
+        let (gbk, _, _) = encoding_rs::GBK.encode("题目\t甲\t乙\t题型\t正确答案\n地球是圆的\t对\t错\t判断题\tA\n");
+        let mapping = ColumnMapping {
+            options: vec!["甲".to_string(), "乙".to_string()],
+            ..ColumnMapping::default()
+        };
+        let import = TabularImporter::new().with_mapping(mapping).import("bank.tsv", &gbk).unwrap();
+        assert_eq!(import.questions[0].stem, "地球是圆的");
+        assert_eq!(import.questions[0].qtype, QuestionType::TrueFalse);
+        assert_eq!(import.questions[0].options.len(), 2);
+
+        let err = TabularImporter::new().import("bank.csv", b"name,value\nx,1\n").unwrap_err();
+        assert!(err.to_string().starts_with("no stem column"), "{}", err);
+        assert!(TabularImporter::new().import("bank.txt", b"").is_err());
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn test_import_xlsx_sheets() {
+        let data = build_xlsx(&[
+            ("单选", &[&["题干", "选项A", "选项B", "答案"], &["1+1=?", "2", "3", "A"]]),
+            ("notes", &[&["comment"], &["not questions"]]),
+            ("简答", &[&["题干", "题型", "答案"], &["证明勾股定理", "简答题", "略"]]),
+        ]);
+
+        let import = TabularImporter::new().import("bank.xlsx", &data).unwrap();
+        assert_eq!(import.questions.len(), 2);
+        assert_eq!(import.questions[0].qtype, QuestionType::Choice);
+        assert_eq!(
+            import.questions[1].provenance.as_ref().unwrap().source_file.as_deref(),
+            Some("bank.xlsx/简答")
+        );
+        assert_eq!(import.questions[1].qtype, QuestionType::Subjective);
+        assert_eq!(import.questions[1].sequence, Some(2));
+        assert_eq!(import.warnings.len(), 1);
+        assert!(import.warnings[0].starts_with("Skipping sheet notes: no stem column"));
+
+        let only = TabularImporter::new().with_sheet("简答").import("bank.xlsx", &data).unwrap();
+        assert_eq!(only.questions.len(), 1);
+        assert!(TabularImporter::new().with_sheet("missing").import("bank.xlsx", &data).is_err());
+        assert!(TabularImporter::new().with_sheet("notes").import("bank.xlsx", &data).is_err());
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn test_write_xlsx_round_trip() {
+        let questions = vec![
+            Question {
+                qtype: QuestionType::MultipleChoice,
+                stem: "Which are prime?".to_string(),
+                options: ["A. 2", "B. 4", "C. 5"]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, content)| QuestionOption {
+                        content: content.to_string(),
+                        sort_order: i as i32,
+                        is_correct: i != 1,
+                    })
+                    .collect(),
+                answer: Some("AC".to_string()),
+                tags: vec!["math".to_string(), "primes".to_string()],
+                ..Question::default()
+            },
+            Question {
+                qtype: QuestionType::FillInTheBlank,
+                stem: "The capital of France is ____".to_string(),
+                answer: Some("Paris".to_string()),
+                analysis: Some("x".repeat(MAX_CELL_CHARS + 10)),
+                ..Question::default()
+            },
+        ];
+
+        let data = write_xlsx(&questions).unwrap();
+        let import = TabularImporter::new().import("export.xlsx", &data).unwrap();
+        assert_eq!(import.questions.len(), 2);
+        let first = &import.questions[0];
+        assert_eq!(first.qtype, QuestionType::MultipleChoice);
+        let contents: Vec<_> = first.options.iter().map(|o| o.content.as_str()).collect();
+        assert_eq!(contents, ["2", "4", "5"]);
+        assert_eq!(first.answer.as_deref(), Some("AC"));
+        assert_eq!(first.tags, ["math", "primes"]);
+        let second = &import.questions[1];
+        assert_eq!(second.qtype, QuestionType::FillInTheBlank);
+        assert_eq!(second.analysis.as_ref().unwrap().len(), MAX_CELL_CHARS);
+
+        use calamine::{Data, Reader};
+        let mut workbook = calamine::open_workbook_auto_from_rs(std::io::Cursor::new(data)).unwrap();
+        let range = workbook.worksheet_range("Questions").unwrap();
+        assert_eq!(range.get_value((0, 2)), Some(&Data::String("Detected Type".to_string())));
+        assert_eq!(range.get_value((2, 2)), Some(&Data::String("fill_in_the_blank".to_string())));
+        assert!(matches!(range.get_value((2, 3)), Some(Data::Float(c)) if *c > 0.5));
+    }
+
+    #[test]
+    fn test_table_format_from_name() {
+        assert_eq!(TableFormat::from_name("Bank.XLSX"), Some(TableFormat::Workbook));
+        assert_eq!(TableFormat::from_name("bank.tsv"), Some(TableFormat::Tsv));
+        assert_eq!(TableFormat::from_name("bank.md"), None);
+        assert_eq!(parse_type("多选题"), Some(QuestionType::MultipleChoice));
+        assert_eq!(parse_type("true_false"), Some(QuestionType::TrueFalse));
+        assert_eq!(parse_type("?"), None);
+    }
+}
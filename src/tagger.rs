@@ -0,0 +1,219 @@
+//! Subject/topic tagging for questions
+//!
+//! This module assigns subject tags (数学, 物理, 编程, ...) to questions using
+//! keyword and LaTeX-symbol heuristics. Taxonomies are described in TOML so
+//! deployments can tune them without recompiling:
+//!
+//! ```toml
+//! [[topic]]
+//! tag = "数学"
+//! keywords = ["函数", "方程", "derivative"]
+//! latex_symbols = ["\\frac", "\\int"]
+//! min_matches = 1
+//! ```
+
+use crate::models::Question;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Built-in taxonomy used when no file is configured
+const DEFAULT_TAXONOMY: &str = r#"
+[[topic]]
+tag = "数学"
+keywords = ["函数", "方程", "不等式", "导数", "积分", "三角形", "概率", "数列", "集合", "向量", "矩阵",
+            "equation", "derivative", "integral", "triangle", "probability", "matrix"]
+latex_symbols = ["\\frac", "\\int", "\\sum", "\\sqrt", "\\lim", "\\sin", "\\cos", "\\log", "^2"]
+
+[[topic]]
+tag = "物理"
+keywords = ["速度", "加速度", "电流", "电压", "电阻", "磁场", "力学", "能量", "质量", "摩擦力", "牛顿",
+            "velocity", "acceleration", "voltage", "current", "momentum", "newton"]
+latex_symbols = ["\\omega", "\\Omega", "\\lambda", "\\vec", "\\Delta"]
+
+[[topic]]
+tag = "编程"
+keywords = ["算法", "复杂度", "编程", "代码", "函数调用", "数组", "链表", "指针", "递归", "变量",
+            "algorithm", "complexity", "array", "pointer", "recursion", "compile", "python", "rust"]
+latex_symbols = ["O(n", "\\log n"]
+"#;
+
+/// A single topic rule within a taxonomy
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopicRule {
+    /// Tag assigned when the rule matches
+    pub tag: String,
+    /// Keywords searched in stem, options and analysis (case-insensitive)
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// LaTeX commands or fragments searched in extracted formulas and text
+    #[serde(default)]
+    pub latex_symbols: Vec<String>,
+    /// Minimum number of distinct matches required to assign the tag
+    #[serde(default = "default_min_matches")]
+    pub min_matches: usize,
+}
+
+fn default_min_matches() -> usize {
+    1
+}
+
+/// A complete taxonomy as loaded from TOML
+#[derive(Debug, Clone, Deserialize)]
+pub struct Taxonomy {
+    /// Topic rules, evaluated independently
+    #[serde(rename = "topic", default)]
+    pub topics: Vec<TopicRule>,
+}
+
+/// Heuristic topic tagger
+#[derive(Debug, Clone)]
+pub struct TopicTagger {
+    taxonomy: Taxonomy,
+}
+
+impl TopicTagger {
+    /// Create a tagger from an already-loaded taxonomy
+    pub fn new(taxonomy: Taxonomy) -> Self {
+        let mut taxonomy = taxonomy;
+        for topic in &mut taxonomy.topics {
+            for keyword in &mut topic.keywords {
+                *keyword = keyword.to_lowercase();
+            }
+        }
+        Self { taxonomy }
+    }
+
+    /// Parse a taxonomy from a TOML string
+    pub fn from_toml_str(toml_str: &str) -> Result<Self> {
+        let taxonomy: Taxonomy = toml::from_str(toml_str).context("Invalid topic taxonomy")?;
+        Ok(Self::new(taxonomy))
+    }
+
+    /// Load a taxonomy from a TOML file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read taxonomy file {:?}", path))?;
+        Self::from_toml_str(&content)
+    }
+
+    /// Get the loaded taxonomy
+    pub fn taxonomy(&self) -> &Taxonomy {
+        &self.taxonomy
+    }
+
+    /// Compute the tags that apply to a question
+    pub fn tag(&self, question: &Question) -> Vec<String> {
+        let mut text = question.stem.to_lowercase();
+        for option in &question.options {
+            text.push('\n');
+            text.push_str(&option.content.to_lowercase());
+        }
+        if let Some(analysis) = &question.analysis {
+            text.push('\n');
+            text.push_str(&analysis.to_lowercase());
+        }
+
+        self.taxonomy
+            .topics
+            .iter()
+            .filter(|topic| {
+                let keyword_hits = topic.keywords.iter().filter(|kw| text.contains(kw.as_str())).count();
+                let latex_hits = topic
+                    .latex_symbols
+                    .iter()
+                    .filter(|sym| {
+                        question.latex.iter().any(|f| f.contains(sym.as_str()))
+                            || question.stem.contains(sym.as_str())
+                    })
+                    .count();
+                keyword_hits + latex_hits >= topic.min_matches.max(1)
+            })
+            .map(|topic| topic.tag.clone())
+            .collect()
+    }
+
+    /// Tag a question in place, merging with any tags it already has
+    pub fn apply(&self, question: &mut Question) {
+        for tag in self.tag(question) {
+            if !question.tags.contains(&tag) {
+                question.tags.push(tag);
+            }
+        }
+    }
+}
+
+impl Default for TopicTagger {
+    fn default() -> Self {
+        Self::from_toml_str(DEFAULT_TAXONOMY).expect("built-in taxonomy is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    fn question(stem: &str) -> Question {
+        Question {
+            stem: stem.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_taxonomy_tags() {
+        let tagger = TopicTagger::default();
+        assert_eq!(tagger.tag(&question("求函数 f(x) 的导数")), vec!["数学"]);
+        assert_eq!(tagger.tag(&question("物体的加速度是多少？")), vec!["物理"]);
+        assert_eq!(tagger.tag(&question("以下哪个算法的复杂度最低？")), vec!["编程"]);
+        assert!(tagger.tag(&question("今天天气怎么样？")).is_empty());
+    }
+
+    #[test]
+    fn test_latex_symbols_and_options() {
+        let tagger = TopicTagger::default();
+        let mut q = question("计算下式");
+        q.latex = vec!["$\\int_0^1 x dx$".to_string()];
+        assert_eq!(tagger.tag(&q), vec!["数学"]);
+
+        let mut q = question("Choose one");
+        q.options = vec![QuestionOption {
+            content: "A. Recursion".to_string(),
+            sort_order: 0,
+            is_correct: false,
+        }];
+        assert_eq!(tagger.tag(&q), vec!["编程"]);
+    }
+
+    #[test]
+    fn test_custom_taxonomy_min_matches() {
+        let tagger = TopicTagger::from_toml_str(
+            r#"
+            [[topic]]
+            tag = "化学"
+            keywords = ["分子", "原子"]
+            min_matches = 2
+            "#,
+        )
+        .unwrap();
+
+        assert!(tagger.tag(&question("分子是什么")).is_empty());
+        assert_eq!(tagger.tag(&question("分子与原子的区别")), vec!["化学"]);
+    }
+
+    #[test]
+    fn test_apply_merges_tags() {
+        let tagger = TopicTagger::default();
+        let mut q = question("求方程的解");
+        q.tags = vec!["数学".to_string(), "期末".to_string()];
+        tagger.apply(&mut q);
+        assert_eq!(q.tags, vec!["数学", "期末"]);
+    }
+
+    #[test]
+    fn test_invalid_taxonomy() {
+        assert!(TopicTagger::from_toml_str("topic = 3").is_err());
+    }
+}
@@ -0,0 +1,554 @@
+//! Regression harness for the parser
+//!
+//! Three kinds of checks keep parser refactors safe:
+//!
+//! - A golden corpus: a directory of real-world Markdown fixtures, each with
+//!   the questions it should produce saved next to it as JSON. [`Corpus`]
+//!   parses every fixture and reports the first difference from its
+//!   snapshot. Run with `MD2DB_UPDATE_SNAPSHOTS=1` to accept the current
+//!   output after an intended change.
+//! - Property tests: [`strategies`] generates random question documents,
+//!   and [`check_parse`] parses one on its own thread, reporting a panic or a
+//!   parse that does not finish in time.
+//! - Fuzzing: [`fuzz`] holds the entry points of the cargo-fuzz targets in
+//!   `fuzz/`, and replays inputs that once crashed them.
+//!
+//! The crate runs all three in its own tests. Downstream
+//! crates can enable the `testing` feature to run them on their fixtures.
+
+use crate::models::Question;
+use crate::parser::{parse_markdown_with_options, ParserOptions};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Environment variable that switches [`Corpus::run`] to updating snapshots
+pub const UPDATE_ENV: &str = "MD2DB_UPDATE_SNAPSHOTS";
+
+/// How long a fixture may take to parse
+const FIXTURE_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Questions as stored in a snapshot: their JSON without the random `id` and
+/// the `created_at` timestamp
+pub fn snapshot(questions: &[Question]) -> Value {
+    let mut value = serde_json::to_value(questions).expect("questions serialize to JSON");
+    if let Value::Array(questions) = &mut value {
+        for question in questions.iter_mut().filter_map(Value::as_object_mut) {
+            question.remove("id");
+            question.remove("created_at");
+        }
+    }
+    value
+}
+
+/// A way the parser broke rather than returned
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParserDefect {
+    /// The parser panicked with this message
+    #[error("parser panicked: {0}")]
+    Panicked(String),
+    /// The parser was still running at the deadline
+    #[error("parser did not finish within {0:?}")]
+    TimedOut(Duration),
+}
+
+/// Parse `markdown` on a separate thread, giving up after `deadline`
+///
+/// A parse error is a normal outcome and is returned as the inner result;
+/// only a panic or a parse that outlives `deadline` is a [`ParserDefect`].
+/// A timed-out parse keeps its thread until it finishes, if ever.
+pub fn check_parse(
+    markdown: &str,
+    options: &ParserOptions,
+    deadline: Duration,
+) -> Result<crate::error::Result<Vec<Question>>, ParserDefect> {
+    let (markdown, options) = (markdown.to_string(), options.clone());
+    guarded(deadline, move || parse_markdown_with_options(&markdown, &options))
+}
+
+/// Run `f` on a separate thread, reporting a panic or a missed deadline
+fn guarded<T: Send + 'static>(deadline: Duration, f: impl FnOnce() -> T + Send + 'static) -> Result<T, ParserDefect> {
+    let (sender, receiver) = mpsc::channel();
+    let worker = std::thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+    match receiver.recv_timeout(deadline) {
+        Ok(result) => Ok(result),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(ParserDefect::TimedOut(deadline)),
+        // The sender was dropped without sending: the thread panicked
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            let panic = worker.join().expect_err("worker thread exited without a result");
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(ParserDefect::Panicked(message))
+        }
+    }
+}
+
+/// A directory of `NAME.md` fixtures with `NAME.json` snapshots
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    dir: PathBuf,
+    options: ParserOptions,
+    update: bool,
+}
+
+/// A fixture whose output did not match its snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorpusFailure {
+    /// File name of the fixture
+    pub fixture: String,
+    /// What went wrong
+    pub reason: String,
+}
+
+/// Outcome of [`Corpus::run`]
+#[derive(Debug, Clone, Default)]
+pub struct CorpusReport {
+    /// Fixtures parsed
+    pub checked: usize,
+    /// Fixtures whose snapshot was written (update mode only)
+    pub updated: Vec<String>,
+    /// Fixtures that failed
+    pub failures: Vec<CorpusFailure>,
+}
+
+impl CorpusReport {
+    /// Whether every fixture matched its snapshot
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for CorpusReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} fixtures, {} failed", self.checked, self.failures.len())?;
+        if !self.updated.is_empty() {
+            write!(f, ", {} snapshots updated", self.updated.len())?;
+        }
+        for failure in &self.failures {
+            write!(f, "\n  {}: {}", failure.fixture, failure.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl Corpus {
+    /// The fixtures in `dir`, parsed with default options
+    ///
+    /// Snapshots are updated instead of checked when [`UPDATE_ENV`] is set
+    /// to anything but `0`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let update = std::env::var(UPDATE_ENV).is_ok_and(|v| !v.is_empty() && v != "0");
+        Self {
+            dir: dir.into(),
+            options: ParserOptions::default(),
+            update,
+        }
+    }
+
+    /// Parse the fixtures with `options`
+    pub fn with_parser_options(mut self, options: ParserOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Write snapshots from the current output instead of checking them
+    pub fn with_update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Parse every fixture and compare it with (or write) its snapshot
+    ///
+    /// Fails only if the directory or a file cannot be read or written;
+    /// mismatches are collected in the report.
+    pub fn run(&self) -> Result<CorpusReport> {
+        let mut fixtures: Vec<PathBuf> = std::fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read corpus {}", self.dir.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        fixtures.retain(|path| path.extension().is_some_and(|ext| ext == "md"));
+        fixtures.sort();
+
+        let mut report = CorpusReport::default();
+        for fixture in fixtures {
+            report.checked += 1;
+            let name = fixture.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            match self.check(&fixture)? {
+                Check::Matched => {}
+                Check::Updated => report.updated.push(name),
+                Check::Failed(reason) => report.failures.push(CorpusFailure { fixture: name, reason }),
+            }
+        }
+        Ok(report)
+    }
+
+    fn check(&self, fixture: &Path) -> Result<Check> {
+        let markdown = std::fs::read_to_string(fixture)
+            .with_context(|| format!("Failed to read fixture {}", fixture.display()))?;
+        let questions = match check_parse(&markdown, &self.options, FIXTURE_DEADLINE) {
+            Ok(Ok(questions)) => questions,
+            Ok(Err(e)) => return Ok(Check::Failed(format!("parse error: {}", e))),
+            Err(defect) => return Ok(Check::Failed(defect.to_string())),
+        };
+        let actual = snapshot(&questions);
+
+        let path = fixture.with_extension("json");
+        if self.update {
+            let json = serde_json::to_string_pretty(&actual)? + "\n";
+            if std::fs::read_to_string(&path).ok().as_deref() == Some(json.as_str()) {
+                return Ok(Check::Matched);
+            }
+            std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+            return Ok(Check::Updated);
+        }
+
+        let expected: Value = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("Invalid snapshot {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Check::Failed(format!("no snapshot; run with {}=1 to create it", UPDATE_ENV)));
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Ok(match first_difference(&expected, &actual, "questions") {
+            None => Check::Matched,
+            Some(difference) => Check::Failed(difference),
+        })
+    }
+}
+
+/// Result of checking one fixture
+enum Check {
+    Matched,
+    Updated,
+    Failed(String),
+}
+
+/// The first place `actual` differs from `expected`, as `path: expected ..., got ...`
+fn first_difference(expected: &Value, actual: &Value, path: &str) -> Option<String> {
+    match (expected, actual) {
+        (Value::Array(e), Value::Array(a)) => {
+            let mut common = e.iter().zip(a).enumerate();
+            if let Some(difference) = common.find_map(|(i, (e, a))| first_difference(e, a, &format!("{}[{}]", path, i))) {
+                return Some(difference);
+            }
+            (e.len() != a.len()).then(|| format!("{}: expected {} items, got {}", path, e.len(), a.len()))
+        }
+        (Value::Object(e), Value::Object(a)) => {
+            let mut keys: Vec<&String> = e.keys().chain(a.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|key| {
+                let (e, a) = (e.get(key).unwrap_or(&Value::Null), a.get(key).unwrap_or(&Value::Null));
+                first_difference(e, a, &format!("{}.{}", path, key))
+            })
+        }
+        (e, a) if e == a => None,
+        (e, a) => Some(format!("{}: expected {}, got {}", path, e, a)),
+    }
+}
+
+/// Proptest strategies for Markdown question documents
+///
+/// [`document`] builds documents from the pieces real exams use: section
+/// headings, numbered stems, lettered options in several styles, answer and
+/// analysis lines, formulas and images. [`noisy_document`] splices Markdown
+/// punctuation and arbitrary characters into one to reach the edge cases.
+pub mod strategies {
+    use proptest::prelude::*;
+
+    /// A section heading naming a question type, in Chinese or English
+    pub fn heading() -> impl Strategy<Value = String> {
+        let title = prop::sample::select(vec![
+            "单选题", "多选题", "判断题", "填空题", "简答题", "Multiple Choice", "True/False", "Fill in the Blank",
+            "Subjective", "第一部分",
+        ]);
+        ("#{1,3}", title).prop_map(|(level, title)| format!("{} {}", level, title))
+    }
+
+    /// Prose, optionally with a formula, an image or a blank
+    pub fn text() -> impl Strategy<Value = String> {
+        let words = prop::collection::vec("[a-zA-Z0-9]{1,8}|[\u{4e00}-\u{4e50}]{1,4}", 1..8);
+        let extra = prop_oneof![
+            Just(String::new()),
+            Just(" ____".to_string()),
+            Just("（　）".to_string()),
+            "[a-z0-9+^=]{1,6}".prop_map(|f| format!(" ${}$", f)),
+            "[a-z0-9+^=]{1,6}".prop_map(|f| format!("\n\n$$\n{}\n$$", f)),
+            "[a-z]{1,6}".prop_map(|name| format!(" ![{0}]({0}.png)", name)),
+        ];
+        (words, extra).prop_map(|(words, extra)| words.join(" ") + &extra)
+    }
+
+    /// A question stem, numbered or as a heading
+    pub fn stem() -> impl Strategy<Value = String> {
+        let prefix = prop_oneof![
+            Just(String::new()),
+            (1..100u32).prop_map(|n| format!("{}. ", n)),
+            (1..100u32).prop_map(|n| format!("{}、", n)),
+            Just("# ".to_string()),
+        ];
+        (prefix, text(), prop::sample::select(vec!["", "?", "？", "。"]))
+            .prop_map(|(prefix, text, end)| format!("{}{}{}", prefix, text, end))
+    }
+
+    /// Two to six lettered options in one of the common layouts
+    pub fn options() -> impl Strategy<Value = String> {
+        let layout = prop::sample::select(vec![("* ", ". ", "\n"), ("", "．", "\n"), ("", "、", "  "), ("- ", ") ", "\n")]);
+        (layout, prop::collection::vec(text(), 2..=6)).prop_map(|((bullet, separator, join), options)| {
+            options
+                .iter()
+                .enumerate()
+                .map(|(i, option)| format!("{}{}{}{}", bullet, (b'A' + i as u8) as char, separator, option))
+                .collect::<Vec<_>>()
+                .join(join)
+        })
+    }
+
+    /// An answer line
+    pub fn answer() -> impl Strategy<Value = String> {
+        let prefix = prop::sample::select(vec!["答案：", "【答案】", "Answer: ", "正确答案:"]);
+        let value = prop_oneof!["[A-F]{1,3}", Just("正确".to_string()), Just("False".to_string()), text()];
+        (prefix, value).prop_map(|(prefix, value)| format!("{}{}", prefix, value))
+    }
+
+    /// An analysis line
+    pub fn analysis() -> impl Strategy<Value = String> {
+        (prop::sample::select(vec!["解析：", "【解析】", "Explanation: "]), text())
+            .prop_map(|(prefix, text)| format!("{}{}", prefix, text))
+    }
+
+    /// One question: a stem with optional options, answer and analysis
+    pub fn question() -> impl Strategy<Value = String> {
+        (stem(), prop::option::of(options()), prop::option::of(answer()), prop::option::of(analysis())).prop_map(
+            |(stem, options, answer, analysis)| {
+                [Some(stem), options, answer, analysis].into_iter().flatten().collect::<Vec<_>>().join("\n\n")
+            },
+        )
+    }
+
+    /// A document of up to eight questions, some under section headings
+    pub fn document() -> impl Strategy<Value = String> {
+        let section = (prop::option::of(heading()), prop::collection::vec(question(), 1..4));
+        prop::collection::vec(section, 1..4).prop_map(|sections| {
+            let mut blocks = Vec::new();
+            for (heading, questions) in sections {
+                blocks.extend(heading);
+                blocks.extend(questions);
+            }
+            blocks.join("\n\n")
+        })
+    }
+
+    /// A [`document`] with Markdown punctuation and arbitrary characters
+    /// inserted at random places
+    pub fn noisy_document() -> impl Strategy<Value = String> {
+        let noise = prop_oneof![
+            prop::sample::select(vec![
+                "#", "*", "`", "```", "$", "$$", "[", "]", "(", ")", "!", ">", "|", "-", "_", "\n", "\n\n", "\\", "<br>",
+                "【", "】", "：", "．", "\u{200f}", "\u{feff}",
+            ])
+            .prop_map(str::to_string),
+            any::<char>().prop_map(String::from),
+        ];
+        (document(), prop::collection::vec((any::<prop::sample::Index>(), noise), 0..12)).prop_map(|(mut doc, noise)| {
+            for (at, piece) in noise {
+                let boundaries: Vec<usize> = doc.char_indices().map(|(i, _)| i).chain([doc.len()]).collect();
+                doc.insert_str(*at.get(&boundaries), &piece);
+            }
+            doc
+        })
+    }
+}
+
+/// Entry points for the cargo-fuzz targets in `fuzz/`
+///
+/// Each function feeds arbitrary bytes to one ingestion path for untrusted
+/// uploads and ignores the outcome: errors are expected, only panics and
+/// hangs are bugs. Inputs that once crashed a target are kept in
+/// `fuzz/regressions/TARGET/` and replayed by the crate's tests through
+/// [`replay`].
+pub mod fuzz {
+    use super::{guarded, ParserDefect};
+    use std::time::Duration;
+
+    /// A fuzz target: takes arbitrary bytes, must not panic or hang
+    pub type Target = fn(&[u8]);
+
+    /// Fuzz targets by name, matching the binaries in `fuzz/Cargo.toml`
+    pub const TARGETS: &[(&str, Target)] = &[
+        ("parse_markdown", parse_markdown),
+        ("zip_entries", zip_entries),
+        ("gift", gift),
+        #[cfg(feature = "docx")]
+        ("docx", docx),
+        #[cfg(feature = "pdf")]
+        ("pdf", pdf),
+        #[cfg(any(feature = "csv", feature = "xlsx"))]
+        ("tabular", tabular),
+        #[cfg(feature = "latex-render")]
+        ("formula", formula),
+    ];
+
+    /// Markdown, decoded lossily so every input reaches the parser
+    pub fn parse_markdown(data: &[u8]) {
+        let _ = crate::parse_markdown_text(&String::from_utf8_lossy(data));
+    }
+
+    /// Archive extraction, then text decoding of every entry
+    pub fn zip_entries(data: &[u8]) {
+        if let Ok((entries, _)) = crate::zip::ZipProcessor::new().extract_entries(data) {
+            for entry in entries {
+                let _ = entry.decode_text();
+            }
+        }
+    }
+
+    /// Moodle GIFT
+    pub fn gift(data: &[u8]) {
+        let _ = crate::gift::parse_gift(&String::from_utf8_lossy(data), "fuzz.gift");
+    }
+
+    /// Word documents
+    #[cfg(feature = "docx")]
+    pub fn docx(data: &[u8]) {
+        let _ = crate::docx::DocxParser::new().parse(data);
+    }
+
+    /// PDF text extraction
+    #[cfg(feature = "pdf")]
+    pub fn pdf(data: &[u8]) {
+        let _ = crate::pdf::PdfExtractor::new().parse(data);
+    }
+
+    /// Spreadsheets; the first byte picks the format
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    pub fn tabular(data: &[u8]) {
+        let Some((&selector, data)) = data.split_first() else { return };
+        let name = ["fuzz.csv", "fuzz.tsv", "fuzz.xlsx"][selector as usize % 3];
+        let _ = crate::tabular::TabularImporter::new().import(name, data);
+    }
+
+    /// Formula rendering, which stored `latex` entries reach unchecked
+    #[cfg(feature = "latex-render")]
+    pub fn formula(data: &[u8]) {
+        let formula = String::from_utf8_lossy(data);
+        let _ = crate::formula::to_mathml(&formula);
+        let _ = crate::formula::to_svg(&formula);
+    }
+
+    /// Run the target named `target` on `data` as the fuzzer would, reporting
+    /// a panic or a run longer than `deadline`
+    ///
+    /// Returns `None` for an unknown target.
+    pub fn replay(target: &str, data: &[u8], deadline: Duration) -> Option<Result<(), ParserDefect>> {
+        let (_, run) = TARGETS.iter().find(|(name, _)| *name == target)?;
+        let (run, data) = (*run, data.to_vec());
+        Some(guarded(deadline, move || run(&data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const DEADLINE: Duration = Duration::from_secs(5);
+
+    fn corpus_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus")
+    }
+
+    #[test]
+    fn test_golden_corpus() {
+        // The fixtures number their questions and run options along stem lines
+        let options = ParserOptions::default().with_numbered_questions(true).with_inline_options(true);
+        let report = Corpus::new(corpus_dir()).with_parser_options(options).run().unwrap();
+        assert!(report.is_success(), "{}", report);
+        assert!(report.checked >= 5, "{}", report);
+    }
+
+    #[test]
+    fn test_corpus_reports_and_updates_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("exam.md"), "# 1+1=?\n\n* A. 1\n* B. 2\n\n答案：B").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not a fixture").unwrap();
+
+        let report = Corpus::new(dir.path()).with_update(false).run().unwrap();
+        assert_eq!(report.checked, 1);
+        assert!(report.failures[0].reason.starts_with("no snapshot"));
+
+        let report = Corpus::new(dir.path()).with_update(true).run().unwrap();
+        assert_eq!(report.updated, ["exam.md"]);
+        let report = Corpus::new(dir.path()).with_update(false).run().unwrap();
+        assert!(report.is_success(), "{}", report);
+
+        // Change the expected answer so the snapshot no longer matches
+        let path = dir.path().join("exam.json");
+        let edited = std::fs::read_to_string(&path).unwrap().replace("\"answer\": \"B\"", "\"answer\": \"A\"");
+        std::fs::write(&path, edited).unwrap();
+        let report = Corpus::new(dir.path()).with_update(false).run().unwrap();
+        assert_eq!(report.failures[0].reason, "questions[0].answer: expected \"A\", got \"B\"");
+        assert!(report.to_string().contains("exam.md: questions[0].answer"));
+    }
+
+    #[test]
+    fn test_check_parse_reports_defects() {
+        let parsed = check_parse("# 1+1=?\n\n答案：2", &ParserOptions::default(), DEADLINE).unwrap().unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(snapshot(&parsed)[0].get("id"), None);
+
+        assert_eq!(
+            first_difference(&serde_json::json!([1, 2]), &serde_json::json!([1]), "questions"),
+            Some("questions: expected 2 items, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_replay_fuzz_regressions() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions");
+        let mut replayed = 0;
+        for target in std::fs::read_dir(&root).unwrap() {
+            let target = target.unwrap().path();
+            let name = target.file_name().unwrap().to_string_lossy().into_owned();
+            for input in std::fs::read_dir(&target).unwrap() {
+                let input = input.unwrap().path();
+                let data = std::fs::read(&input).unwrap();
+                // Targets for features left out of this build are skipped
+                if let Some(outcome) = fuzz::replay(&name, &data, DEADLINE) {
+                    assert!(outcome.is_ok(), "{}: {}", input.display(), outcome.unwrap_err());
+                    replayed += 1;
+                }
+            }
+        }
+        assert!(replayed > 0);
+        assert!(fuzz::replay("no_such_target", b"", DEADLINE).is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parser_handles_generated_documents(markdown in strategies::document()) {
+            let outcome = check_parse(&markdown, &ParserOptions::default(), DEADLINE);
+            prop_assert!(outcome.is_ok(), "{}\n---\n{}", outcome.unwrap_err(), markdown);
+        }
+
+        #[test]
+        fn prop_parser_handles_noisy_documents(markdown in strategies::noisy_document()) {
+            let outcome = check_parse(&markdown, &ParserOptions::default(), DEADLINE);
+            prop_assert!(outcome.is_ok(), "{}\n---\n{:?}", outcome.unwrap_err(), markdown);
+        }
+
+        #[test]
+        fn prop_parser_handles_arbitrary_text(markdown in "\\PC{0,400}") {
+            let outcome = check_parse(&markdown, &ParserOptions::default(), DEADLINE);
+            prop_assert!(outcome.is_ok(), "{}\n---\n{:?}", outcome.unwrap_err(), markdown);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Post-processing of parsed questions
+//!
+//! A [`Transform`] rewrites one question at a time. The processor runs a
+//! [`TransformChain`] after parsing and tagging and before validation and
+//! saving, so a custom pipeline is a matter of configuration
+//! ([`crate::processor::ProcessorConfig::with_transform`]) rather than
+//! changes to the processor. Built-in transforms:
+//!
+//! - [`NormalizeText`]: tidy whitespace, optionally apply typesetting rules
+//! - [`StableIds`]: derive IDs from content so re-imports keep them
+//! - [`AddTags`]: add fixed tags to every question
+//! - [`LocalizeImages`]: point remote images at a local mirror
+
+use crate::incremental::{question_fingerprint, source_key};
+use crate::media::content_hash;
+use crate::models::{ImageRef, Question};
+use crate::typeset::{normalize_text, TypesetOptions};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::{Builder, Uuid};
+
+/// A rewrite applied to every parsed question
+pub trait Transform: Send + Sync {
+    /// Return the rewritten question
+    fn apply(&self, question: Question) -> Question;
+
+    /// Name shown in logs and debug output
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>().rsplit("::").next().unwrap_or("transform")
+    }
+}
+
+/// Transforms run in order on each question
+#[derive(Clone, Default)]
+pub struct TransformChain {
+    transforms: Vec<Arc<dyn Transform>>,
+}
+
+impl TransformChain {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `transform` to the chain
+    pub fn with(mut self, transform: impl Transform + 'static) -> Self {
+        self.push(transform);
+        self
+    }
+
+    /// Append `transform` to the chain
+    pub fn push(&mut self, transform: impl Transform + 'static) {
+        self.transforms.push(Arc::new(transform));
+    }
+
+    /// Whether the chain has no transforms
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Run every transform on `question`
+    pub fn apply(&self, question: Question) -> Question {
+        self.transforms.iter().fold(question, |question, t| t.apply(question))
+    }
+}
+
+impl fmt::Debug for TransformChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.transforms.iter().map(|t| t.name())).finish()
+    }
+}
+
+/// Tidies whitespace in every text field
+///
+/// Line endings become `\n`, trailing spaces are dropped from each line, runs
+/// of blank lines are cut to one and the ends are trimmed. With
+/// [`NormalizeText::with_typeset`] the locale's punctuation and spacing rules
+/// are applied as well.
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeText {
+    typeset: Option<TypesetOptions>,
+}
+
+impl NormalizeText {
+    /// Normalize whitespace only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also apply typesetting rules (see [`crate::typeset`])
+    pub fn with_typeset(mut self, options: TypesetOptions) -> Self {
+        self.typeset = Some(options);
+        self
+    }
+
+    fn text(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut blank = false;
+        for line in text.lines().map(str::trim_end) {
+            if line.is_empty() {
+                blank = !out.is_empty();
+                continue;
+            }
+            if !out.is_empty() {
+                out.push_str(if blank { "\n\n" } else { "\n" });
+            }
+            out.push_str(line);
+            blank = false;
+        }
+        let out = out.trim_start().to_string();
+        match &self.typeset {
+            Some(options) => normalize_text(&out, options),
+            None => out,
+        }
+    }
+}
+
+impl Transform for NormalizeText {
+    fn apply(&self, mut question: Question) -> Question {
+        question.stem = self.text(&question.stem);
+        for option in &mut question.options {
+            option.content = self.text(&option.content);
+        }
+        question.answer = question.answer.map(|a| self.text(&a));
+        question.analysis = question.analysis.map(|a| self.text(&a));
+        question
+    }
+}
+
+/// Derives each question's ID from its content
+///
+/// The ID is a hash of the namespace, the source file and everything the
+/// question says, so importing the same file again yields the same IDs.
+/// Identical questions in one file get the same ID; combine with dedup to
+/// keep one of them.
+#[derive(Debug, Clone, Default)]
+pub struct StableIds {
+    namespace: String,
+}
+
+impl StableIds {
+    /// IDs unique to `namespace`, such as a bank ID or an institution name
+    pub fn new(namespace: impl Into<String>) -> Self {
+        Self {
+            namespace: namespace.into(),
+        }
+    }
+
+    /// ID of `question` in this namespace
+    pub fn id_for(&self, question: &Question) -> Uuid {
+        let mut hasher = Sha256::new();
+        for part in [self.namespace.as_str(), source_key(question), &question_fingerprint(question)] {
+            hasher.update(part.as_bytes());
+            hasher.update(b"\0");
+        }
+        let digest = hasher.finalize();
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        Builder::from_custom_bytes(bytes).into_uuid()
+    }
+}
+
+impl Transform for StableIds {
+    fn apply(&self, mut question: Question) -> Question {
+        question.id = self.id_for(&question);
+        question
+    }
+}
+
+/// Adds fixed tags to every question, skipping ones it already has
+#[derive(Debug, Clone, Default)]
+pub struct AddTags {
+    tags: Vec<String>,
+}
+
+impl AddTags {
+    /// Add `tags` to every question
+    pub fn new(tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            tags: tags.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Transform for AddTags {
+    fn apply(&self, mut question: Question) -> Question {
+        for tag in &self.tags {
+            if !question.tags.contains(tag) {
+                question.tags.push(tag.clone());
+            }
+        }
+        question
+    }
+}
+
+/// Replaces remote images with copies in a local mirror
+///
+/// A remote URL starting with a mirrored prefix is looked up under the
+/// mirror directory; when the file is there, the image becomes a local
+/// reference with its content hash. Images missing from the mirror stay
+/// remote.
+#[derive(Debug, Clone, Default)]
+pub struct LocalizeImages {
+    mirrors: Vec<(String, PathBuf)>,
+}
+
+impl LocalizeImages {
+    /// Localize nothing until a mirror is added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up URLs starting with `prefix` under `dir`
+    pub fn with_mirror(mut self, prefix: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        self.mirrors.push((prefix.into(), dir.into()));
+        self
+    }
+
+    fn localize(&self, url: &str) -> Option<ImageRef> {
+        let (prefix, dir) = self.mirrors.iter().find(|(prefix, _)| url.starts_with(prefix.as_str()))?;
+        let relative = url[prefix.len()..].split(['?', '#']).next().unwrap_or_default();
+        // Keep lookups inside the mirror
+        if relative.split('/').any(|part| part == "..") {
+            return None;
+        }
+        let path = dir.join(relative.trim_start_matches('/'));
+        let data = std::fs::read(&path).ok()?;
+        Some(ImageRef::Local {
+            hash: content_hash(&data),
+            original_path: path.to_string_lossy().into_owned(),
+            stored_path: None,
+            alt: None,
+        })
+    }
+}
+
+impl Transform for LocalizeImages {
+    fn apply(&self, mut question: Question) -> Question {
+        for image in &mut question.images {
+            if let ImageRef::Remote { url, .. } = image {
+                if let Some(local) = self.localize(url) {
+                    *image = local;
+                }
+            }
+        }
+        question
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+    use crate::typeset::ExportLocale;
+
+    fn question(stem: &str) -> Question {
+        Question {
+            stem: stem.to_string(),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        let mut q = question("  What is 2+2?  \r\n\r\n\r\n  Show work. \n");
+        q.options.push(QuestionOption {
+            content: " 4 ".to_string(),
+            sort_order: 0,
+            is_correct: true,
+        });
+        let q = NormalizeText::new().apply(q);
+        assert_eq!(q.stem, "What is 2+2?\n\n  Show work.");
+        assert_eq!(q.options[0].content, "4");
+
+        let q = NormalizeText::new()
+            .with_typeset(TypesetOptions::new(ExportLocale::En))
+            .apply(question("What is 2+2？"));
+        assert_eq!(q.stem, "What is 2+2?");
+    }
+
+    #[test]
+    fn test_chain_in_order() {
+        let chain = TransformChain::new()
+            .with(AddTags::new(["midterm", "math"]))
+            .with(StableIds::new("bank-1"));
+        let mut q = question("1+1?");
+        q.tags.push("math".to_string());
+
+        let first = chain.apply(q.clone());
+        assert_eq!(first.tags, ["math", "midterm"]);
+        // Same content, same ID; different content or namespace, another one
+        assert_eq!(chain.apply(q.clone()).id, first.id);
+        assert_ne!(chain.apply(question("2+2?")).id, first.id);
+        assert_ne!(StableIds::new("bank-2").apply(first.clone()).id, first.id);
+        assert_eq!(format!("{:?}", chain), r#"["AddTags", "StableIds"]"#);
+    }
+
+    #[test]
+    fn test_localize_images() {
+        let mirror = tempfile::tempdir().unwrap();
+        std::fs::create_dir(mirror.path().join("img")).unwrap();
+        std::fs::write(mirror.path().join("img/a.png"), b"png").unwrap();
+        let localize = LocalizeImages::new().with_mirror("https://cdn.example.com/", mirror.path());
+
+        let mut q = question("Which plot?");
+        for url in ["https://cdn.example.com/img/a.png?v=2", "https://cdn.example.com/img/b.png", "https://other.org/a.png"] {
+            q.images.push(ImageRef::Remote { url: url.to_string(), alt: None });
+        }
+        let q = localize.apply(q);
+        match &q.images[0] {
+            ImageRef::Local { hash, original_path, .. } => {
+                assert_eq!(hash, &content_hash(b"png"));
+                assert!(original_path.ends_with("a.png"));
+            }
+            other => panic!("expected a local image, got {:?}", other),
+        }
+        assert!(matches!(&q.images[1], ImageRef::Remote { .. }));
+        assert!(matches!(&q.images[2], ImageRef::Remote { .. }));
+    }
+}
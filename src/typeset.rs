@@ -0,0 +1,278 @@
+//! Locale-aware typesetting for exported questions
+//!
+//! Publishers expect Chinese papers to use full-width punctuation and English
+//! papers to use half-width punctuation, with a space between CJK and Latin
+//! runs ("pangu" spacing). This module applies those rules at export time so
+//! stored questions keep their original text.
+
+use crate::bidi::split_math;
+use crate::models::Question;
+use anyhow::{anyhow, Result};
+
+/// Target locale of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportLocale {
+    /// Simplified Chinese: full-width punctuation after CJK text
+    Zh,
+    /// English: half-width punctuation everywhere
+    En,
+}
+
+impl std::str::FromStr for ExportLocale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.to_ascii_lowercase().replace('_', "-");
+        match lower.as_str() {
+            "zh" | "cn" | "chinese" => Ok(ExportLocale::Zh),
+            "en" | "english" => Ok(ExportLocale::En),
+            other if other.starts_with("zh-") => Ok(ExportLocale::Zh),
+            other if other.starts_with("en-") => Ok(ExportLocale::En),
+            other => Err(anyhow!("Unsupported export locale: {}", other)),
+        }
+    }
+}
+
+/// Typesetting rules applied to exported text
+#[derive(Debug, Clone)]
+pub struct TypesetOptions {
+    pub locale: ExportLocale,
+    /// Convert punctuation to the locale's width
+    pub punctuation: bool,
+    /// Insert a space between CJK and Latin/digit runs
+    pub spacing: bool,
+}
+
+impl TypesetOptions {
+    /// All rules enabled for `locale`
+    pub fn new(locale: ExportLocale) -> Self {
+        Self {
+            locale,
+            punctuation: true,
+            spacing: true,
+        }
+    }
+
+    /// Enable or disable punctuation width conversion
+    pub fn with_punctuation(mut self, enabled: bool) -> Self {
+        self.punctuation = enabled;
+        self
+    }
+
+    /// Enable or disable CJK/Latin spacing
+    pub fn with_spacing(mut self, enabled: bool) -> Self {
+        self.spacing = enabled;
+        self
+    }
+}
+
+/// Whether a character belongs to a CJK script
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0x3040..=0x30FF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+/// Return a copy of the question with all display text normalized
+pub fn normalize_question(question: &Question, options: &TypesetOptions) -> Question {
+    let mut normalized = question.clone();
+    normalized.stem = normalize_text(&question.stem, options);
+    for option in &mut normalized.options {
+        option.content = normalize_text(&option.content, options);
+    }
+    normalized.analysis = question
+        .analysis
+        .as_deref()
+        .map(|analysis| normalize_text(analysis, options));
+    normalized
+}
+
+/// Normalize punctuation and spacing of a piece of text
+///
+/// `$...$` formulas are left untouched; for spacing purposes they count as
+/// Latin runs.
+pub fn normalize_text(text: &str, options: &TypesetOptions) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for (segment, is_math) in split_math(text) {
+        if is_math {
+            if options.spacing && out.chars().last().is_some_and(is_cjk) {
+                out.push(' ');
+            }
+            out.push_str(segment);
+            continue;
+        }
+
+        let converted = if !options.punctuation {
+            segment.to_string()
+        } else {
+            match options.locale {
+                ExportLocale::Zh => to_full_width(segment, out.chars().last()),
+                ExportLocale::En => to_half_width(segment),
+            }
+        };
+
+        for c in converted.chars() {
+            if options.spacing {
+                if let Some(prev) = out.chars().last() {
+                    let boundary = (is_cjk(prev) && (is_latin(c) || c == '$'))
+                        || ((is_latin(prev) || prev == '$') && is_cjk(c));
+                    if boundary {
+                        out.push(' ');
+                    }
+                }
+            }
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+/// Latin letters and digits that get separated from CJK text
+fn is_latin(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+}
+
+/// Convert half-width punctuation that follows CJK text to full width
+fn to_full_width(text: &str, before: Option<char>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let chars: Vec<char> = text.chars().collect();
+    let mut prev = before;
+    let mut skip_space = false;
+
+    for (idx, &c) in chars.iter().enumerate() {
+        if skip_space && c == ' ' {
+            continue;
+        }
+        skip_space = false;
+
+        let after_cjk = prev.is_some_and(is_cjk);
+        let next_cjk = chars[idx + 1..]
+            .iter()
+            .find(|c| **c != ' ')
+            .is_some_and(|c| is_cjk(*c));
+
+        let mapped = match c {
+            ',' if after_cjk => Some('，'),
+            '.' if after_cjk => Some('。'),
+            ';' if after_cjk => Some('；'),
+            ':' if after_cjk => Some('：'),
+            '?' if after_cjk => Some('？'),
+            '!' if after_cjk => Some('！'),
+            '(' if after_cjk || next_cjk => Some('（'),
+            ')' if after_cjk => Some('）'),
+            _ => None,
+        };
+
+        match mapped {
+            Some(full) => {
+                // Full-width punctuation carries its own spacing
+                while out.ends_with(' ') {
+                    out.pop();
+                }
+                out.push(full);
+                skip_space = true;
+                // Treat the converted mark as CJK so runs like "?!" convert together
+                prev = Some('中');
+            }
+            None => {
+                out.push(c);
+                prev = Some(c);
+            }
+        }
+    }
+
+    out
+}
+
+/// Convert full-width punctuation and alphanumerics to half width
+fn to_half_width(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let mapped = match c {
+            '。' => '.',
+            '、' => ',',
+            '“' | '”' | '「' | '」' => '"',
+            '‘' | '’' => '\'',
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        };
+        out.push(mapped);
+
+        let converted = mapped != c;
+        let needs_space = converted
+            && matches!(mapped, ',' | '.' | ';' | ':' | '?' | '!' | ')')
+            && chars.peek().is_some_and(|next| next.is_alphanumeric());
+        if needs_space {
+            out.push(' ');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    #[test]
+    fn test_zh_punctuation_and_spacing() {
+        let options = TypesetOptions::new(ExportLocale::Zh);
+        assert_eq!(
+            normalize_text("使用Rust语言编写,速度快.", &options),
+            "使用 Rust 语言编写，速度快。"
+        );
+        assert_eq!(normalize_text("求$x^2$的值(单位:米)?", &options), "求 $x^2$ 的值（单位：米）？");
+        // Numbers and Latin punctuation are left alone
+        assert_eq!(normalize_text("圆周率约为3.14", &options), "圆周率约为 3.14");
+        assert_eq!(normalize_text("A. 3", &options), "A. 3");
+    }
+
+    #[test]
+    fn test_en_half_width() {
+        let options = TypesetOptions::new(ExportLocale::En);
+        assert_eq!(
+            normalize_text("Which is stable？Merge sort，quick sort。", &options),
+            "Which is stable? Merge sort, quick sort."
+        );
+        assert_eq!(normalize_text("（ａ）１２３", &options), "(a) 123");
+    }
+
+    #[test]
+    fn test_toggles() {
+        let spacing_only = TypesetOptions::new(ExportLocale::Zh).with_punctuation(false);
+        assert_eq!(normalize_text("使用Rust,", &spacing_only), "使用 Rust,");
+
+        let punctuation_only = TypesetOptions::new(ExportLocale::Zh).with_spacing(false);
+        assert_eq!(normalize_text("使用Rust编写.", &punctuation_only), "使用Rust编写。");
+    }
+
+    #[test]
+    fn test_normalize_question_leaves_original() {
+        let question = Question {
+            stem: "下列哪个是Rust关键字?".to_string(),
+            options: vec![QuestionOption {
+                content: "A. fn".to_string(),
+                sort_order: 0,
+                is_correct: false,
+            }],
+            ..Question::default()
+        };
+
+        let normalized = normalize_question(&question, &TypesetOptions::new(ExportLocale::Zh));
+        assert_eq!(normalized.stem, "下列哪个是 Rust 关键字？");
+        assert_eq!(normalized.options[0].content, "A. fn");
+        assert_eq!(question.stem, "下列哪个是Rust关键字?");
+    }
+
+    #[test]
+    fn test_locale_from_str() {
+        assert_eq!("zh-CN".parse::<ExportLocale>().unwrap(), ExportLocale::Zh);
+        assert_eq!("en_US".parse::<ExportLocale>().unwrap(), ExportLocale::En);
+        assert!("fr".parse::<ExportLocale>().is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Question validation before persistence
+//!
+//! [`QuestionValidator`] checks each parsed question for problems that would
+//! make it unusable once stored: an empty stem, an option count that does
+//! not fit the question type, a missing correct answer on an objective
+//! question, or control characters left over from a bad export. Questions
+//! that fail are set aside with their reasons instead of being saved.
+//!
+//! A question with options is checked as a choice question whatever its
+//! type, since Markdown imports leave classification to later steps.
+
+use crate::models::{Question, QuestionType};
+use serde::{Deserialize, Serialize};
+
+/// Default maximum number of options on a choice question (A to Z)
+pub const DEFAULT_MAX_OPTIONS: usize = 26;
+
+/// A question that failed validation, with every reason it failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedQuestion {
+    /// The question as parsed
+    pub question: Question,
+    /// Why it was rejected
+    pub reasons: Vec<String>,
+}
+
+/// Checks questions before they are saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuestionValidator {
+    /// Most options a choice question may have
+    pub max_options: usize,
+    /// Whether objective questions need a correct answer
+    pub require_answer: bool,
+}
+
+impl Default for QuestionValidator {
+    fn default() -> Self {
+        Self {
+            max_options: DEFAULT_MAX_OPTIONS,
+            require_answer: true,
+        }
+    }
+}
+
+impl QuestionValidator {
+    /// Create a validator with the default rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow up to `max` options on choice questions (at least 2)
+    pub fn with_max_options(mut self, max: usize) -> Self {
+        self.max_options = max.max(2);
+        self
+    }
+
+    /// Require (or not) a correct answer on objective questions
+    pub fn with_require_answer(mut self, required: bool) -> Self {
+        self.require_answer = required;
+        self
+    }
+
+    /// Reasons `question` is invalid; empty if it is valid
+    pub fn check(&self, question: &Question) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if question.stem.trim().is_empty() {
+            reasons.push("empty stem".to_string());
+        }
+
+        let options = question.options.len();
+        if question.qtype == QuestionType::TrueFalse {
+            if options != 0 && options != 2 {
+                reasons.push(format!("{} options on a true/false question, expected 0 or 2", options));
+            }
+        } else if is_choice(question) && !(2..=self.max_options).contains(&options) {
+            reasons.push(format!("{} options, expected 2 to {}", options, self.max_options));
+        }
+
+        if self.require_answer && !has_answer(question) {
+            reasons.push("no correct answer".to_string());
+        }
+
+        let mut fields = vec![("stem", question.stem.as_str())];
+        fields.extend(question.answer.as_deref().map(|a| ("answer", a)));
+        fields.extend(question.analysis.as_deref().map(|a| ("analysis", a)));
+        for (name, text) in fields {
+            if has_control_chars(text) {
+                reasons.push(format!("control characters in the {}", name));
+            }
+        }
+        for (i, option) in question.options.iter().enumerate() {
+            if has_control_chars(&option.content) {
+                reasons.push(format!("control characters in option {}", i + 1));
+            }
+        }
+        reasons
+    }
+
+    /// Split questions into valid ones and rejected ones, keeping their order
+    pub fn partition(&self, questions: Vec<Question>) -> (Vec<Question>, Vec<RejectedQuestion>) {
+        let mut valid = Vec::with_capacity(questions.len());
+        let mut rejected = Vec::new();
+        for question in questions {
+            let reasons = self.check(&question);
+            if reasons.is_empty() {
+                valid.push(question);
+            } else {
+                rejected.push(RejectedQuestion { question, reasons });
+            }
+        }
+        (valid, rejected)
+    }
+}
+
+/// Whether a question is answered by picking options
+fn is_choice(question: &Question) -> bool {
+    match question.qtype {
+        QuestionType::Choice | QuestionType::MultipleChoice => true,
+        QuestionType::TrueFalse => false,
+        _ => !question.options.is_empty(),
+    }
+}
+
+/// Whether an objective question says which answer is correct
+///
+/// Choice questions need an option marked correct, which answer validation
+/// does for answers that match an option.
+fn has_answer(question: &Question) -> bool {
+    let answered = question.answer.as_deref().is_some_and(|a| !a.trim().is_empty());
+    let marked = question.options.iter().any(|o| o.is_correct);
+    if is_choice(question) {
+        return marked;
+    }
+    match question.qtype {
+        QuestionType::TrueFalse => answered || marked,
+        QuestionType::FillInTheBlank => answered,
+        _ => true,
+    }
+}
+
+/// Control characters other than line breaks and tabs
+fn has_control_chars(text: &str) -> bool {
+    text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QuestionOption;
+
+    fn choice(stem: &str, options: &[&str], answer: Option<&str>) -> Question {
+        Question {
+            qtype: QuestionType::Choice,
+            stem: stem.to_string(),
+            options: options
+                .iter()
+                .zip(0..)
+                .map(|(content, sort_order)| QuestionOption {
+                    content: content.to_string(),
+                    sort_order,
+                    is_correct: answer.and_then(|a| a.bytes().next()) == Some(b'A' + sort_order as u8),
+                })
+                .collect(),
+            answer: answer.map(str::to_string),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn test_check_reasons() {
+        let validator = QuestionValidator::new();
+        assert!(validator.check(&choice("2+2?", &["3", "4"], Some("B"))).is_empty());
+        assert_eq!(validator.check(&choice(" ", &["3", "4"], Some("B"))), ["empty stem"]);
+        assert_eq!(validator.check(&choice("2+2?", &["4"], Some("A"))), ["1 options, expected 2 to 26"]);
+        assert_eq!(validator.check(&choice("2+2?", &["3", "4"], None)), ["no correct answer"]);
+        assert_eq!(
+            validator.check(&choice("2+2?\u{7}", &["3", "4\u{0}"], Some("B"))),
+            ["control characters in the stem", "control characters in option 2"]
+        );
+
+        // An answer that matches no option marks none correct
+        let mut unmatched = choice("2+2?", &["3", "4"], None);
+        unmatched.answer = Some("Z".to_string());
+        assert_eq!(validator.check(&unmatched), ["no correct answer"]);
+
+        let essay = Question {
+            stem: "Discuss.\n\tBe brief.".to_string(),
+            ..Question::default()
+        };
+        assert!(validator.check(&essay).is_empty());
+        let blank = Question {
+            qtype: QuestionType::FillInTheBlank,
+            stem: "2+2 = ___".to_string(),
+            ..Question::default()
+        };
+        assert_eq!(validator.check(&blank), ["no correct answer"]);
+        assert!(validator.clone().with_require_answer(false).check(&blank).is_empty());
+    }
+
+    #[test]
+    fn test_partition_keeps_order() {
+        let questions = vec![
+            choice("1+1?", &["2", "3"], Some("A")),
+            choice("", &[], None),
+            choice("2+2?", &["3", "4"], Some("B")),
+        ];
+        let (valid, rejected) = QuestionValidator::new().partition(questions);
+        let stems: Vec<_> = valid.iter().map(|q| q.stem.as_str()).collect();
+        assert_eq!(stems, ["1+1?", "2+2?"]);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].reasons.len(), 3);
+    }
+}
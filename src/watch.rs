@@ -0,0 +1,359 @@
+//! Folder ingest
+//!
+//! [`DirectoryWatcher`] imports every file dropped into a folder: once a file
+//! has stopped growing it is processed like an upload to `/import` (its
+//! format is recognized from its content) and moved to a `done` or `failed`
+//! subfolder. A failed file gets a `.error.txt` note next to it saying why,
+//! so it can be fixed and dropped in again.
+
+use crate::database::QuestionRepository;
+use crate::processor::{InputSource, ProcessResult, SingleMachineProcessor};
+use anyhow::{anyhow, Context, Result};
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Subfolder imported files are moved to
+pub const DEFAULT_DONE_DIR: &str = "done";
+
+/// Subfolder files that could not be imported are moved to
+pub const DEFAULT_FAILED_DIR: &str = "failed";
+
+/// How long a file must stay the same size before it is imported
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Suffixes of partial downloads and editor temporaries, which are left alone
+const TEMPORARY_SUFFIXES: &[&str] = &[".tmp", ".part", ".partial", ".crdownload", ".download"];
+
+/// What happened to one file
+#[derive(Debug)]
+pub enum IngestOutcome {
+    /// Imported and moved to the done folder
+    Imported {
+        /// Where the file was moved
+        file: PathBuf,
+        /// Outcome of the import
+        result: Box<ProcessResult>,
+    },
+    /// Moved to the failed folder
+    Failed {
+        /// Where the file was moved
+        file: PathBuf,
+        /// Why the import failed, also written to the `.error.txt` note
+        error: String,
+    },
+}
+
+/// Imports files as they appear in a folder
+pub struct DirectoryWatcher<R> {
+    dir: PathBuf,
+    done_dir: PathBuf,
+    failed_dir: PathBuf,
+    settle_time: Duration,
+    processor: SingleMachineProcessor<R>,
+}
+
+impl<R> DirectoryWatcher<R>
+where
+    R: QuestionRepository + Send + Sync,
+{
+    /// Watch `dir`, importing files with `processor`
+    pub fn new(dir: impl Into<PathBuf>, processor: SingleMachineProcessor<R>) -> Self {
+        let dir = dir.into();
+        Self {
+            done_dir: dir.join(DEFAULT_DONE_DIR),
+            failed_dir: dir.join(DEFAULT_FAILED_DIR),
+            dir,
+            settle_time: DEFAULT_SETTLE_TIME,
+            processor,
+        }
+    }
+
+    /// Move imported files to `dir` instead of the `done` subfolder
+    pub fn with_done_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.done_dir = dir.into();
+        self
+    }
+
+    /// Move failed files to `dir` instead of the `failed` subfolder
+    pub fn with_failed_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.failed_dir = dir.into();
+        self
+    }
+
+    /// Wait until a file has kept its size for `time` before importing it
+    pub fn with_settle_time(mut self, time: Duration) -> Self {
+        self.settle_time = time;
+        self
+    }
+
+    /// Processor the files are imported with
+    pub fn processor(&self) -> &SingleMachineProcessor<R> {
+        &self.processor
+    }
+
+    /// Import every file currently in the folder, in name order
+    pub async fn scan(&self) -> Result<Vec<IngestOutcome>> {
+        self.create_dirs()?;
+        let mut outcomes = Vec::new();
+        for path in self.candidates()? {
+            outcomes.push(self.ingest(&path).await?);
+        }
+        Ok(outcomes)
+    }
+
+    /// Watch the folder until an error occurs, importing files as they settle
+    ///
+    /// Files already in the folder are imported first. Errors importing a
+    /// file only move it to the failed folder; this returns if the folder
+    /// can no longer be watched or a file cannot be moved.
+    pub async fn run(&self) -> Result<()> {
+        self.create_dirs()?;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+            Err(e) => warn!("Folder watch error: {}", e),
+        })?;
+        watcher.watch(&self.dir, RecursiveMode::NonRecursive)?;
+        info!("Watching {:?} for new files", self.dir);
+
+        // Path -> size when last checked and when it last changed
+        let mut pending: HashMap<PathBuf, (Option<u64>, Instant)> = self
+            .candidates()?
+            .into_iter()
+            .map(|path| (path, (None, Instant::now())))
+            .collect();
+        let mut tick = tokio::time::interval((self.settle_time / 4).max(Duration::from_millis(10)));
+        loop {
+            tokio::select! {
+                path = rx.recv() => {
+                    let path = path.ok_or_else(|| anyhow!("folder watcher stopped"))?;
+                    if is_candidate(&path) {
+                        pending.insert(path, (None, Instant::now()));
+                    }
+                }
+                _ = tick.tick(), if !pending.is_empty() => {
+                    for path in self.settled(&mut pending) {
+                        pending.remove(&path);
+                        self.ingest(&path).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Import one file and move it to the done or failed folder
+    pub async fn ingest(&self, path: &Path) -> Result<IngestOutcome> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow!("{:?} is not a file", path))?;
+
+        let error = match tokio::fs::read(path).await {
+            Ok(data) => {
+                let input = InputSource::File { data, source: name.clone() };
+                match self.processor.process(input).await {
+                    Ok(result) => match failure(&result) {
+                        None => {
+                            let file = move_into(path, &self.done_dir)?;
+                            info!(
+                                "Imported {} ({} saved, {} duplicates)",
+                                name, result.saved_questions, result.duplicate_questions
+                            );
+                            return Ok(IngestOutcome::Imported { file, result: Box::new(result) });
+                        }
+                        Some(error) => error,
+                    },
+                    Err(e) => format!("{:#}", e),
+                }
+            }
+            Err(e) => format!("failed to read file: {}", e),
+        };
+
+        warn!("Failed to import {}: {}", name, error);
+        let file = move_into(path, &self.failed_dir)?;
+        let note = file.with_file_name(format!(
+            "{}.error.txt",
+            file.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        std::fs::write(&note, format!("{}\n", error)).with_context(|| format!("Failed to write {:?}", note))?;
+        Ok(IngestOutcome::Failed { file, error })
+    }
+
+    fn create_dirs(&self) -> Result<()> {
+        for dir in [&self.done_dir, &self.failed_dir] {
+            std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        }
+        Ok(())
+    }
+
+    /// Files in the folder that should be imported, in name order
+    fn candidates(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).with_context(|| format!("Failed to read {:?}", self.dir))? {
+            let path = entry?.path();
+            if is_candidate(&path) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Pending files whose size has not changed for the settle time
+    fn settled(&self, pending: &mut HashMap<PathBuf, (Option<u64>, Instant)>) -> Vec<PathBuf> {
+        let mut ready = Vec::new();
+        pending.retain(|path, (size, changed)| {
+            // Moved away or deleted before it settled
+            let Ok(metadata) = std::fs::metadata(path) else {
+                return false;
+            };
+            if !metadata.is_file() {
+                return false;
+            }
+            if *size != Some(metadata.len()) {
+                *size = Some(metadata.len());
+                *changed = Instant::now();
+            } else if changed.elapsed() >= self.settle_time {
+                ready.push(path.clone());
+            }
+            true
+        });
+        ready.sort();
+        ready
+    }
+}
+
+/// Whether `path` is a regular file that is not hidden or a temporary
+fn is_candidate(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let lower = name.to_lowercase();
+    path.is_file()
+        && !name.starts_with('.')
+        && !name.starts_with('~')
+        && !TEMPORARY_SUFFIXES.iter().any(|suffix| lower.ends_with(suffix))
+}
+
+/// Why an import that ran should still count as failed
+fn failure(result: &ProcessResult) -> Option<String> {
+    if result.rolled_back || result.failed_questions > 0 {
+        return Some(format!(
+            "{} of {} questions could not be saved: {}",
+            result.failed_questions,
+            result.total_questions,
+            result.warnings.join("; ")
+        ));
+    }
+    if result.total_questions == 0 {
+        let mut error = "no questions found".to_string();
+        if !result.warnings.is_empty() {
+            error = format!("{}: {}", error, result.warnings.join("; "));
+        }
+        return Some(error);
+    }
+    None
+}
+
+/// Move `path` into `dir`, numbering the name if it is taken; returns the new path
+fn move_into(path: &Path, dir: &Path) -> Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| anyhow!("{:?} is not a file", path))?;
+    let mut target = dir.join(name);
+    let (stem, extension) = match name.to_string_lossy().split_once('.') {
+        Some((stem, extension)) => (stem.to_string(), format!(".{}", extension)),
+        None => (name.to_string_lossy().into_owned(), String::new()),
+    };
+    let mut n = 1;
+    while target.exists() {
+        target = dir.join(format!("{}-{}{}", stem, n, extension));
+        n += 1;
+    }
+
+    if std::fs::rename(path, &target).is_err() {
+        // Across file systems a rename fails; copy instead
+        std::fs::copy(path, &target).with_context(|| format!("Failed to move {:?} to {:?}", path, target))?;
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MockRepository;
+
+    const QUESTION: &str = "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B\n";
+
+    fn watcher(dir: &Path) -> DirectoryWatcher<MockRepository> {
+        DirectoryWatcher::new(dir, SingleMachineProcessor::new(MockRepository::new()))
+            .with_settle_time(Duration::from_millis(50))
+    }
+
+    #[tokio::test]
+    async fn test_scan_moves_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("exam.md"), QUESTION).unwrap();
+        std::fs::write(dir.path().join("notes.bin"), [0, 1, 2, 0xff]).unwrap();
+        std::fs::write(dir.path().join("empty.md"), "").unwrap();
+        std::fs::write(dir.path().join(".exam.md.swp"), QUESTION).unwrap();
+        std::fs::write(dir.path().join("upload.zip.part"), QUESTION).unwrap();
+        std::fs::create_dir(dir.path().join("done")).unwrap();
+        std::fs::write(dir.path().join("done/exam.md"), "imported earlier").unwrap();
+
+        let watcher = watcher(dir.path());
+        let outcomes = watcher.scan().await.unwrap();
+        assert_eq!(outcomes.len(), 3);
+
+        match &outcomes[1] {
+            IngestOutcome::Imported { file, result } => {
+                assert_eq!(file, &dir.path().join("done/exam-1.md"));
+                assert_eq!(result.saved_questions, 1);
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        let note = std::fs::read_to_string(dir.path().join("failed/notes.bin.error.txt")).unwrap();
+        assert!(note.contains("unrecognized file format"), "{}", note);
+        let note = std::fs::read_to_string(dir.path().join("failed/empty.md.error.txt")).unwrap();
+        assert_eq!(note, "no questions found\n");
+
+        assert!(dir.path().join(".exam.md.swp").exists());
+        assert!(dir.path().join("upload.zip.part").exists());
+        let stored = watcher.processor().repository().list(&Default::default()).await.unwrap();
+        assert_eq!(stored.items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_imports_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("first.md"), QUESTION).unwrap();
+        let watcher = std::sync::Arc::new(watcher(dir.path()));
+        let task = tokio::spawn({
+            let watcher = watcher.clone();
+            async move { watcher.run().await }
+        });
+
+        let wait_for = |path: PathBuf| async move {
+            for _ in 0..200 {
+                if path.exists() {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(25)).await;
+            }
+            false
+        };
+        assert!(wait_for(dir.path().join("done/first.md")).await);
+        std::fs::write(dir.path().join("second.md"), QUESTION.replace("2+2", "3+3")).unwrap();
+        assert!(wait_for(dir.path().join("done/second.md")).await);
+        task.abort();
+
+        let stored = watcher.processor().repository().list(&Default::default()).await.unwrap();
+        assert_eq!(stored.items.len(), 2);
+    }
+}
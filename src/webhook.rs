@@ -0,0 +1,339 @@
+//! Webhook notifications for finished imports
+//!
+//! [`WebhookNotifier`] is a [`JobListener`]: when a background import
+//! completes or fails it POSTs a [`WebhookPayload`] (job ID, counts and a
+//! summary of the warnings) to every configured URL, so an LMS can start its
+//! follow-up work without polling `/imports/:id`.
+//!
+//! With a secret, each request is signed: `X-MD2DB-Signature` carries
+//! `sha256=` and the hex HMAC-SHA256 of `<timestamp>.<body>`, where the
+//! timestamp is the `X-MD2DB-Timestamp` header in Unix seconds. Receivers
+//! should recompute it and reject old timestamps. Deliveries that fail with
+//! a network error, `408`, `429` or a server error are retried with
+//! exponential backoff; other client errors are not.
+
+use crate::jobs::{JobListener, JobRecord, JobStatus};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Header carrying the payload signature
+pub const SIGNATURE_HEADER: &str = "x-md2db-signature";
+
+/// Header carrying the Unix time the payload was signed at
+pub const TIMESTAMP_HEADER: &str = "x-md2db-timestamp";
+
+/// Header naming the event, as in the payload's `event`
+pub const EVENT_HEADER: &str = "x-md2db-event";
+
+/// Default number of delivery attempts per URL
+pub const DEFAULT_WEBHOOK_ATTEMPTS: u32 = 5;
+
+/// Warnings listed in a payload; the rest are only counted
+const MAX_WARNINGS: usize = 10;
+
+/// Longest wait between attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Body of a webhook request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// `import.completed` or `import.failed`
+    pub event: String,
+    pub job_id: Uuid,
+    pub status: JobStatus,
+    /// Files the job imported
+    pub sources: Vec<String>,
+    /// Question counts of a completed job
+    pub counts: Option<ImportCounts>,
+    pub warnings: WarningSummary,
+    /// Why a failed job failed
+    pub error: Option<String>,
+    /// When the job was submitted
+    pub created_at: DateTime<Utc>,
+    /// When the job finished
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Question counts of a finished import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportCounts {
+    pub total_questions: usize,
+    pub saved_questions: usize,
+    pub added_questions: usize,
+    pub updated_questions: usize,
+    pub duplicate_questions: usize,
+    pub failed_questions: usize,
+    pub rejected_questions: usize,
+    pub total_images: usize,
+}
+
+/// How many warnings an import had, with the first few
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarningSummary {
+    pub count: usize,
+    /// At most the first ten
+    pub messages: Vec<String>,
+}
+
+impl WebhookPayload {
+    /// Payload announcing a finished job
+    pub fn from_job(job: &JobRecord) -> Self {
+        let event = match job.status {
+            JobStatus::Completed => "import.completed",
+            _ => "import.failed",
+        };
+        let warnings = job.result.as_ref().map(|r| r.warnings.as_slice()).unwrap_or_default();
+        Self {
+            event: event.to_string(),
+            job_id: job.id,
+            status: job.status,
+            sources: job.sources.clone(),
+            counts: job.result.as_ref().map(|r| ImportCounts {
+                total_questions: r.total_questions,
+                saved_questions: r.saved_questions,
+                added_questions: r.added_questions,
+                updated_questions: r.updated_questions,
+                duplicate_questions: r.duplicate_questions,
+                failed_questions: r.failed_questions,
+                rejected_questions: r.rejected.len(),
+                total_images: r.total_images,
+            }),
+            warnings: WarningSummary {
+                count: warnings.len(),
+                messages: warnings.iter().take(MAX_WARNINGS).cloned().collect(),
+            },
+            error: job.error.clone(),
+            created_at: job.created_at,
+            finished_at: job.updated_at,
+        }
+    }
+}
+
+/// Sends [`WebhookPayload`]s for finished jobs to a list of URLs
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    urls: Vec<String>,
+    secret: Option<Vec<u8>>,
+    max_attempts: u32,
+    backoff: Duration,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    /// Notify each of `urls`, unsigned, with the default retries
+    pub fn new(urls: Vec<String>) -> Self {
+        Self {
+            urls,
+            secret: None,
+            max_attempts: DEFAULT_WEBHOOK_ATTEMPTS,
+            backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sign requests with `secret`
+    pub fn with_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.secret = Some(secret.as_ref().to_vec());
+        self
+    }
+
+    /// Try each delivery up to `attempts` times (at least once)
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait `backoff` before the first retry, doubling for each one after
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Give up on an attempt after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// URLs notified
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Send `payload` to every URL at once, retrying failures
+    ///
+    /// Returns the outcome for each URL, in order.
+    pub async fn send(&self, payload: &WebhookPayload) -> Vec<Result<()>> {
+        let body = match serde_json::to_string(payload) {
+            Ok(body) => body,
+            Err(e) => return self.urls.iter().map(|_| Err(anyhow::anyhow!("{}", e))).collect(),
+        };
+        let deliveries = self.urls.iter().map(|url| self.deliver(url, &payload.event, &body));
+        futures::future::join_all(deliveries).await
+    }
+
+    async fn deliver(&self, url: &str, event: &str, body: &str) -> Result<()> {
+        let mut backoff = self.backoff;
+        for attempt in 1..=self.max_attempts {
+            let timestamp = Utc::now().timestamp().to_string();
+            let mut request = self
+                .client
+                .post(url)
+                .timeout(self.timeout)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event)
+                .header(TIMESTAMP_HEADER, &timestamp)
+                .body(body.to_string());
+            if let Some(secret) = &self.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &timestamp, body));
+            }
+
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry = status.is_server_error() || matches!(status.as_u16(), 408 | 429);
+                    if !retry {
+                        bail!("{} answered {}", url, status);
+                    }
+                    format!("{} answered {}", url, status)
+                }
+                Err(e) => format!("{}: {}", url, e),
+            };
+            if attempt == self.max_attempts {
+                bail!("{} (gave up after {} attempts)", error, attempt);
+            }
+            warn!("Webhook delivery failed, retrying in {:?}: {}", backoff, error);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+        unreachable!("at least one attempt is made")
+    }
+}
+
+#[async_trait]
+impl JobListener for WebhookNotifier {
+    async fn job_finished(&self, job: &JobRecord) {
+        let payload = WebhookPayload::from_job(job);
+        for (url, outcome) in self.urls.iter().zip(self.send(&payload).await) {
+            match outcome {
+                Ok(()) => info!("Sent {} for job {} to {}", payload.event, job.id, url),
+                Err(e) => warn!("Webhook for job {} not delivered: {:#}", job.id, e),
+            }
+        }
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `<timestamp>.<body>`
+pub fn sign(secret: &[u8], timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::ProcessResult;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use std::sync::{Arc, Mutex};
+
+    /// Requests received by a test receiver: headers and body
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    /// Serve a receiver that fails its first `failures` requests
+    async fn receiver(failures: usize) -> (String, Received) {
+        let received: Received = Arc::default();
+        let log = received.clone();
+        let app = axum::Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| {
+                let log = log.clone();
+                async move {
+                    let mut log = log.lock().unwrap();
+                    log.push((headers, body));
+                    if log.len() <= failures {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        StatusCode::NO_CONTENT
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    fn completed_job() -> JobRecord {
+        let mut job = JobRecord::new(vec!["exam.zip".to_string()]);
+        job.status = JobStatus::Completed;
+        let mut result = ProcessResult::new();
+        result.total_questions = 12;
+        result.saved_questions = 11;
+        result.warnings = (0..12).map(|i| format!("warning {}", i)).collect();
+        job.result = Some(result);
+        job
+    }
+
+    #[test]
+    fn test_payload() {
+        let payload = WebhookPayload::from_job(&completed_job());
+        assert_eq!(payload.event, "import.completed");
+        assert_eq!(payload.counts.unwrap().saved_questions, 11);
+        assert_eq!(payload.warnings.count, 12);
+        assert_eq!(payload.warnings.messages.len(), MAX_WARNINGS);
+
+        let mut failed = JobRecord::new(vec!["exam.zip".to_string()]);
+        failed.status = JobStatus::Failed;
+        failed.error = Some("bank not found".to_string());
+        let payload = WebhookPayload::from_job(&failed);
+        assert_eq!(payload.event, "import.failed");
+        assert!(payload.counts.is_none());
+        assert_eq!(payload.error.as_deref(), Some("bank not found"));
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery_with_retries() {
+        let (url, received) = receiver(2).await;
+        let notifier = WebhookNotifier::new(vec![url])
+            .with_secret("s3cret")
+            .with_backoff(Duration::from_millis(10));
+        let job = completed_job();
+        notifier.job_finished(&job).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        let (headers, body) = &received[2];
+        assert_eq!(headers[EVENT_HEADER], "import.completed");
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), sign(b"s3cret", timestamp, body));
+        let payload: WebhookPayload = serde_json::from_str(body).unwrap();
+        assert_eq!(payload.job_id, job.id);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up() {
+        let (url, received) = receiver(usize::MAX).await;
+        let notifier = WebhookNotifier::new(vec![url])
+            .with_max_attempts(2)
+            .with_backoff(Duration::from_millis(10));
+        let outcomes = notifier.send(&WebhookPayload::from_job(&completed_job())).await;
+        assert!(outcomes[0].as_ref().unwrap_err().to_string().contains("gave up after 2 attempts"));
+        assert_eq!(received.lock().unwrap().len(), 2);
+        assert!(received.lock().unwrap()[0].0.get(SIGNATURE_HEADER).is_none());
+    }
+}
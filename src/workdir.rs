@@ -0,0 +1,266 @@
+//! Temporary file and working directory management
+//!
+//! Streaming uploads, archive extraction and spill-to-disk all need scratch
+//! space. This module hands out per-task working directories under a single
+//! configurable root, removes them when their guard is dropped, sweeps
+//! leftovers from crashed processes on startup, and checks free disk space
+//! before large writes.
+
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// Prefix of every working directory created by MD2DB
+const WORKDIR_PREFIX: &str = "md2db-";
+
+/// Configuration for working directory management
+#[derive(Debug, Clone)]
+pub struct WorkdirConfig {
+    /// Root directory under which working directories are created
+    pub root: PathBuf,
+    /// Minimum free space (bytes) that must remain after a reservation
+    pub min_free_bytes: u64,
+    /// Working directories older than this are considered leaked
+    pub stale_after: Duration,
+}
+
+impl Default for WorkdirConfig {
+    fn default() -> Self {
+        Self {
+            root: std::env::temp_dir().join("md2db"),
+            min_free_bytes: 256 * 1024 * 1024,
+            stale_after: Duration::from_secs(6 * 60 * 60),
+        }
+    }
+}
+
+impl WorkdirConfig {
+    /// Build a configuration from `MD2DB_TMPDIR` and `MD2DB_MIN_FREE_MB`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(root) = std::env::var("MD2DB_TMPDIR") {
+            config.root = PathBuf::from(root);
+        }
+        if let Some(mb) = std::env::var("MD2DB_MIN_FREE_MB").ok().and_then(|v| v.parse::<u64>().ok()) {
+            config.min_free_bytes = mb * 1024 * 1024;
+        }
+        config
+    }
+
+    /// Set the root directory
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Set the minimum free space to keep available
+    pub fn with_min_free_bytes(mut self, bytes: u64) -> Self {
+        self.min_free_bytes = bytes;
+        self
+    }
+
+    /// Set the age after which leftover directories are swept
+    pub fn with_stale_after(mut self, age: Duration) -> Self {
+        self.stale_after = age;
+        self
+    }
+}
+
+/// Hands out working directories under the configured root
+#[derive(Debug)]
+pub struct WorkdirManager {
+    config: WorkdirConfig,
+}
+
+impl WorkdirManager {
+    /// Create a manager, creating the root directory if needed
+    pub fn new(config: WorkdirConfig) -> Result<Self> {
+        std::fs::create_dir_all(&config.root)
+            .with_context(|| format!("Failed to create work root {:?}", config.root))?;
+        Ok(Self { config })
+    }
+
+    /// Get the manager configuration
+    pub fn config(&self) -> &WorkdirConfig {
+        &self.config
+    }
+
+    /// Create a new working directory for `purpose` (e.g. "extract", "upload")
+    ///
+    /// The directory and its contents are removed when the returned guard drops.
+    pub fn create(&self, purpose: &str) -> Result<Workdir> {
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("{}{}-", WORKDIR_PREFIX, purpose))
+            .tempdir_in(&self.config.root)
+            .with_context(|| format!("Failed to create working directory in {:?}", self.config.root))?;
+        debug!("Created working directory {:?}", dir.path());
+        Ok(Workdir { dir })
+    }
+
+    /// Ensure `required` bytes can be written without dropping below the free-space floor
+    pub fn ensure_space(&self, required: u64) -> Result<()> {
+        match available_space(&self.config.root) {
+            Some(available) => {
+                let needed = required.saturating_add(self.config.min_free_bytes);
+                if available < needed {
+                    bail!(
+                        "Insufficient disk space in {:?}: {} bytes available, {} required (including {} reserve)",
+                        self.config.root,
+                        available,
+                        needed,
+                        self.config.min_free_bytes
+                    );
+                }
+                Ok(())
+            }
+            // Unknown on this platform; let the write itself fail if space runs out
+            None => Ok(()),
+        }
+    }
+
+    /// Remove working directories left behind by crashed processes
+    ///
+    /// Returns the number of directories removed.
+    pub fn cleanup_stale(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut removed = 0;
+
+        for entry in std::fs::read_dir(&self.config.root)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if !name.to_string_lossy().starts_with(WORKDIR_PREFIX) {
+                continue;
+            }
+
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+
+            if age >= self.config.stale_after {
+                match std::fs::remove_dir_all(entry.path()) {
+                    Ok(()) => removed += 1,
+                    Err(e) => warn!("Failed to remove stale working directory {:?}: {}", entry.path(), e),
+                }
+            }
+        }
+
+        if removed > 0 {
+            debug!("Removed {} stale working directories", removed);
+        }
+        Ok(removed)
+    }
+}
+
+/// A working directory that is deleted when dropped
+#[derive(Debug)]
+pub struct Workdir {
+    dir: tempfile::TempDir,
+}
+
+impl Workdir {
+    /// Path of the working directory
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Path for a file inside the working directory
+    pub fn file_path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// Create (truncate) a file inside the working directory
+    pub fn create_file(&self, name: &str) -> Result<(PathBuf, File)> {
+        let path = self.file_path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+        Ok((path, file))
+    }
+
+    /// Keep the directory on disk and return its path
+    pub fn persist(self) -> PathBuf {
+        self.dir.keep()
+    }
+}
+
+/// Free space available to unprivileged users on the filesystem holding `path`
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid NUL-terminated string and stat is a valid out pointer
+    let rc = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if rc != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Free space available on the filesystem holding `path`
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(root: &Path) -> WorkdirManager {
+        WorkdirManager::new(WorkdirConfig::default().with_root(root).with_min_free_bytes(0)).unwrap()
+    }
+
+    #[test]
+    fn test_workdir_removed_on_drop() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = manager(root.path());
+
+        let path = {
+            let workdir = manager.create("extract").unwrap();
+            let (file_path, _) = workdir.create_file("nested/a.md").unwrap();
+            assert!(file_path.exists());
+            workdir.path().to_path_buf()
+        };
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_cleanup_stale() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = WorkdirManager::new(
+            WorkdirConfig::default()
+                .with_root(root.path())
+                .with_stale_after(Duration::ZERO),
+        )
+        .unwrap();
+
+        let leaked = manager.create("upload").unwrap().persist();
+        std::fs::create_dir(root.path().join("unrelated")).unwrap();
+
+        assert_eq!(manager.cleanup_stale().unwrap(), 1);
+        assert!(!leaked.exists());
+        assert!(root.path().join("unrelated").exists());
+    }
+
+    #[test]
+    fn test_ensure_space() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = manager(root.path());
+
+        assert!(manager.ensure_space(1).is_ok());
+        if available_space(root.path()).is_some() {
+            assert!(manager.ensure_space(u64::MAX / 2).is_err());
+        }
+    }
+}
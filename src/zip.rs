@@ -1,17 +1,211 @@
 //! ZIP file processing for batch question imports
 //!
-//! This module handles ZIP extraction and parallel processing
-//! of multiple Markdown files with associated images.
+//! This module handles archive extraction and parallel processing
+//! of multiple Markdown files with associated images. Despite the name,
+//! every format in [`crate::archive`] goes through the same pipeline.
 
-use crate::media::process_image;
-use crate::models::Question;
-use crate::parser::parse_markdown;
+use crate::answer::validate_answers;
+use crate::archive::{archive_processor, ArchiveFormat, DETECT_LEN};
+use crate::detect::{FormatDetector, ImportFormat};
+use crate::error::Md2DbError;
+use crate::gift::parse_gift;
+use crate::import::{ImportManifest, MANIFEST_FILES};
+use crate::latex::validate_formulas;
+use crate::media::{
+    content_hash, resolve_reference, strip_metadata, validate_image, MediaStore, IMAGE_EXTENSIONS,
+};
+use crate::models::{ImageRef, Provenance, Question};
+use crate::ocr::{recognize_formulas, FormulaOcr};
+use crate::parser::{parse_markdown_with_options, parse_text_with_options, ParserOptions};
+use crate::presets;
+#[cfg(any(feature = "csv", feature = "xlsx"))]
+use crate::tabular::TabularImporter;
+use crate::workdir::{Workdir, WorkdirManager};
 use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use encoding_rs::Encoding;
 use futures::stream::{self, StreamExt};
-use std::collections::HashMap;
-use std::io::{Cursor, Read};
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+/// Archive-level metadata of a ZIP entry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZipEntryMetadata {
+    /// Size of the entry as stored in the archive
+    pub compressed_size: u64,
+    /// Size of the entry after decompression
+    pub uncompressed_size: u64,
+    /// CRC-32 recorded in the archive
+    pub crc32: u32,
+    /// Modification time recorded in the archive (no timezone; interpreted as UTC)
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+impl ZipEntryMetadata {
+    /// Read metadata from an archive entry
+    pub(crate) fn from_zip_file(file: &zip::read::ZipFile<'_>) -> Self {
+        let modified_at = file.last_modified().and_then(|dt| {
+            NaiveDate::from_ymd_opt(dt.year() as i32, dt.month() as u32, dt.day() as u32)?
+                .and_hms_opt(dt.hour() as u32, dt.minute() as u32, dt.second() as u32)
+                .map(|naive| naive.and_utc())
+        });
+
+        Self {
+            compressed_size: file.compressed_size(),
+            uncompressed_size: file.size(),
+            crc32: file.crc32(),
+            modified_at,
+        }
+    }
+}
+
+/// Decode text written by common exam tooling
+///
+/// Accepts UTF-8 (with or without BOM), BOM-marked UTF-16, and GB18030,
+/// the superset of GBK and GB2312 used by Chinese Windows editors. Returns
+/// `None` if the bytes are valid in none of them.
+pub fn decode_text(data: &[u8]) -> Option<(Cow<'_, str>, &'static Encoding)> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(data) {
+        let (text, malformed) = encoding.decode_without_bom_handling(&data[bom_len..]);
+        return (!malformed).then_some((text, encoding));
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        return Some((Cow::Borrowed(text), encoding_rs::UTF_8));
+    }
+    encoding_rs::GB18030
+        .decode_without_bom_handling_and_without_replacement(data)
+        .map(|text| (text, encoding_rs::GB18030))
+}
+
+/// Name of an archive entry, recovering GBK names written without the UTF-8 flag
+///
+/// The ZIP reader falls back to CP437 for names not flagged as UTF-8, which
+/// garbles names from Chinese Windows tools. Returns the name and whether it
+/// was decoded as GB18030.
+pub(crate) fn entry_name(raw: &[u8], fallback: &str) -> (String, bool) {
+    if let Ok(name) = std::str::from_utf8(raw) {
+        return (name.to_string(), false);
+    }
+    match encoding_rs::GB18030.decode_without_bom_handling_and_without_replacement(raw) {
+        Some(name) => (name.into_owned(), true),
+        None => (fallback.to_string(), false),
+    }
+}
+
+/// Content of an extracted entry, in memory or spilled to disk
+///
+/// In-memory content is reference-counted, so cloning an entry or moving
+/// its content into [`ZipProcessResult::images`] never copies the bytes.
+#[derive(Debug, Clone)]
+pub enum EntryContent {
+    /// Held in memory
+    Memory(Bytes),
+    /// Written to a working directory because it was large
+    Spilled(Arc<SpilledFile>),
+}
+
+/// An entry written to a working directory during extraction
+///
+/// The directory is removed once the last spilled file referring to it is
+/// dropped.
+#[derive(Debug)]
+pub struct SpilledFile {
+    path: PathBuf,
+    size: u64,
+    workdir: Arc<Workdir>,
+}
+
+impl SpilledFile {
+    /// Location of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl EntryContent {
+    /// Size in bytes
+    pub fn len(&self) -> u64 {
+        match self {
+            EntryContent::Memory(data) => data.len() as u64,
+            EntryContent::Spilled(file) => file.size,
+        }
+    }
+
+    /// Whether the content is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the content lives on disk
+    pub fn is_spilled(&self) -> bool {
+        matches!(self, EntryContent::Spilled(_))
+    }
+
+    /// The content as bytes, reading spilled files from disk
+    pub fn read(&self) -> Result<Cow<'_, [u8]>, Md2DbError> {
+        match self {
+            EntryContent::Memory(data) => Ok(Cow::Borrowed(data)),
+            EntryContent::Spilled(file) => std::fs::read(&file.path)
+                .map(Cow::Owned)
+                .map_err(|e| Md2DbError::zip(format!("Failed to read spilled entry {:?}", file.path), e)),
+        }
+    }
+
+    /// Replace the content, keeping it on disk if it was spilled
+    fn replace(&self, data: Vec<u8>) -> Result<EntryContent> {
+        match self {
+            EntryContent::Memory(_) => Ok(EntryContent::Memory(data.into())),
+            EntryContent::Spilled(file) => {
+                std::fs::write(&file.path, &data)?;
+                Ok(EntryContent::Spilled(Arc::new(SpilledFile {
+                    path: file.path.clone(),
+                    size: data.len() as u64,
+                    workdir: file.workdir.clone(),
+                })))
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for EntryContent {
+    fn from(data: Vec<u8>) -> Self {
+        EntryContent::Memory(data.into())
+    }
+}
+
+impl From<Bytes> for EntryContent {
+    fn from(data: Bytes) -> Self {
+        EntryContent::Memory(data)
+    }
+}
+
+/// Where large entries go during extraction
+#[derive(Debug, Clone)]
+pub struct SpillConfig {
+    /// Working directories for spilled entries
+    pub workdirs: Arc<WorkdirManager>,
+    /// Entries larger than this many bytes are written to disk
+    pub threshold: u64,
+}
+
+impl SpillConfig {
+    /// Spill entries above `threshold` bytes into working directories from `workdirs`
+    pub fn new(workdirs: Arc<WorkdirManager>, threshold: u64) -> Self {
+        Self { workdirs, threshold }
+    }
+}
+
+/// Default size above which entries are spilled to disk (16 MiB)
+pub const DEFAULT_SPILL_THRESHOLD: u64 = 16 << 20;
 
 /// Entry extracted from a ZIP file
 #[derive(Debug, Clone)]
@@ -19,54 +213,370 @@ pub struct ZipEntry {
     /// Path of the file within the ZIP
     pub path: PathBuf,
     /// Raw file content
-    pub content: Vec<u8>,
+    pub content: EntryContent,
     /// Whether this is a Markdown file
     pub is_markdown: bool,
+    /// Whether this is a plain-text file, parsed by question numbering
+    pub is_text: bool,
     /// Whether this is an image file
     pub is_image: bool,
+    /// Whether this is a Word document, converted to Markdown when the
+    /// `docx` feature is enabled
+    pub is_docx: bool,
+    /// Whether this is a PDF, whose text is extracted when the `pdf` feature
+    /// is enabled
+    pub is_pdf: bool,
+    /// Archive metadata (sizes, CRC, modification time)
+    pub metadata: ZipEntryMetadata,
 }
 
 impl ZipEntry {
     /// Create a new ZIP entry
-    pub fn new(path: PathBuf, content: Vec<u8>) -> Self {
+    pub fn new(path: PathBuf, content: impl Into<EntryContent>) -> Self {
+        let content = content.into();
         let path_str = path.to_string_lossy().to_lowercase();
         let is_markdown = path_str.ends_with(".md");
-        let is_image = path_str.ends_with(".png")
-            || path_str.ends_with(".jpg")
-            || path_str.ends_with(".jpeg")
-            || path_str.ends_with(".gif")
-            || path_str.ends_with(".webp");
+        let is_text = path_str.ends_with(".txt");
+        let is_docx = path_str.ends_with(".docx");
+        let is_pdf = path_str.ends_with(".pdf");
+        let is_image = path_str
+            .rsplit_once('.')
+            .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext));
+
+        let metadata = ZipEntryMetadata {
+            compressed_size: content.len(),
+            uncompressed_size: content.len(),
+            ..Default::default()
+        };
 
         Self {
             path,
             content,
             is_markdown,
+            is_text,
             is_image,
+            is_docx,
+            is_pdf,
+            metadata,
+        }
+    }
+
+    /// Attach archive metadata to the entry
+    pub fn with_metadata(mut self, metadata: ZipEntryMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Provenance record for questions parsed from this entry
+    pub fn provenance(&self) -> Provenance {
+        Provenance {
+            source_file: Some(self.path.to_string_lossy().into_owned()),
+            compressed_size: Some(self.metadata.compressed_size),
+            uncompressed_size: Some(self.metadata.uncompressed_size),
+            crc32: Some(self.metadata.crc32),
+            modified_at: self.metadata.modified_at,
+            ..Provenance::default()
         }
     }
 
     /// Get the file content as a string
-    pub fn as_string(&self) -> Result<String> {
-        String::from_utf8(self.content.clone())
-            .map_err(|e| anyhow!("Invalid UTF-8 in file {:?}: {}", self.path, e))
+    ///
+    /// Copies the text; prefer [`ZipEntry::as_str`], which borrows it.
+    pub fn as_string(&self) -> Result<String, Md2DbError> {
+        self.as_str().map(Cow::into_owned)
+    }
+
+    /// Get the file content as text
+    ///
+    /// UTF-8 content held in memory is borrowed, not copied. Spilled files
+    /// and other encodings are decoded into an owned string.
+    pub fn as_str(&self) -> Result<Cow<'_, str>, Md2DbError> {
+        self.decode_text().map(|(text, _)| text)
+    }
+
+    /// Decode the file content as text, returning the encoding if not UTF-8
+    ///
+    /// See [`decode_text`] for the encodings recognized. Borrows like
+    /// [`ZipEntry::as_str`].
+    pub fn decode_text(&self) -> Result<(Cow<'_, str>, Option<&'static str>), Md2DbError> {
+        let decoded = match self.content.read()? {
+            Cow::Borrowed(data) => decode_text(data),
+            Cow::Owned(data) => decode_owned_text(data),
+        };
+        match decoded {
+            Some((text, encoding)) => Ok((text, (encoding != encoding_rs::UTF_8).then(|| encoding.name()))),
+            None => Err(Md2DbError::parse(
+                format!("Invalid text in file {:?}", self.path),
+                "not UTF-8, UTF-16 or GB18030",
+            )),
+        }
+    }
+}
+
+/// [`decode_text`] for bytes already owned, reusing them for UTF-8 text
+fn decode_owned_text(mut data: Vec<u8>) -> Option<(Cow<'static, str>, &'static Encoding)> {
+    if Encoding::for_bom(&data).is_none() {
+        match String::from_utf8(data) {
+            Ok(text) => return Some((Cow::Owned(text), encoding_rs::UTF_8)),
+            Err(e) => data = e.into_bytes(),
+        }
+    }
+    decode_text(&data).map(|(text, encoding)| (Cow::Owned(text.into_owned()), encoding))
+}
+
+/// Per-file outcome of processing a ZIP archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZipFileResult {
+    /// Path of the file within the ZIP
+    pub path: String,
+    /// Archive metadata of the entry
+    pub metadata: ZipEntryMetadata,
+    /// Number of questions parsed from the file
+    pub questions: usize,
+    /// Error message if the file could not be processed
+    pub error: Option<String>,
+}
+
+/// Images whose content appeared more than once in an archive
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageDedupReport {
+    /// Image files whose content matched an earlier file
+    pub duplicate_files: usize,
+    /// Bytes not stored because of those matches
+    pub bytes_saved: u64,
+    /// Images found under several paths or referenced by several questions
+    pub shared: Vec<SharedImage>,
+}
+
+/// One image content shared by several files or questions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedImage {
+    /// Content hash
+    pub hash: String,
+    /// Size of one copy in bytes
+    pub size: u64,
+    /// Archive paths holding this content, sorted
+    pub paths: Vec<String>,
+    /// Questions referencing this content, in parse order
+    pub question_ids: Vec<Uuid>,
+}
+
+impl ImageDedupReport {
+    /// Build the report from archive paths (path -> hash) and linked questions
+    fn build(hashes: &HashMap<String, String>, images: &HashMap<String, EntryContent>, questions: &[Question]) -> Self {
+        let mut paths: HashMap<&str, Vec<String>> = HashMap::new();
+        for (path, hash) in hashes {
+            paths.entry(hash.as_str()).or_default().push(path.clone());
+        }
+        let mut referenced: HashMap<&str, Vec<Uuid>> = HashMap::new();
+        for question in questions {
+            for image in &question.images {
+                if let ImageRef::Local { hash, .. } = image {
+                    let ids = referenced.entry(hash.as_str()).or_default();
+                    if !hash.is_empty() && ids.last() != Some(&question.id) {
+                        ids.push(question.id);
+                    }
+                }
+            }
+        }
+
+        let mut report = Self::default();
+        for (hash, mut paths) in paths {
+            let size = images.get(hash).map_or(0, EntryContent::len);
+            let question_ids = referenced.remove(hash).unwrap_or_default();
+            if paths.len() < 2 && question_ids.len() < 2 {
+                continue;
+            }
+
+            report.duplicate_files += paths.len() - 1;
+            report.bytes_saved += size * (paths.len() as u64 - 1);
+            paths.sort();
+            report.shared.push(SharedImage {
+                hash: hash.to_string(),
+                size,
+                paths,
+                question_ids,
+            });
+        }
+        report.shared.sort_by(|a, b| a.paths.cmp(&b.paths));
+        report
+    }
+}
+
+/// What happened to the images handed to the media store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageStoreReport {
+    /// Images written to the store
+    pub stored: usize,
+    /// Images the store already held, so nothing was written
+    pub already_stored: usize,
+    /// Images that could not be stored
+    pub failed: usize,
+}
+
+impl ImageStoreReport {
+    /// Add the counts of `other`
+    pub fn merge(&mut self, other: &Self) {
+        self.stored += other.stored;
+        self.already_stored += other.already_stored;
+        self.failed += other.failed;
+    }
+}
+
+/// Time spent in each stage of processing an upload
+///
+/// Images are hashed and stored while files are parsed, so the stages can
+/// add up to more than the upload took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageMetrics {
+    /// Unpacking the archive and converting documents, in milliseconds
+    pub extraction_ms: u64,
+    /// Decoding and parsing question files, in milliseconds
+    pub parsing_ms: u64,
+    /// Hashing and storing images, in milliseconds
+    pub media_ms: u64,
+    /// Most files parsed at once
+    pub peak_parse_workers: usize,
+}
+
+impl StageMetrics {
+    /// Add the times of `other` and keep the higher peak
+    pub fn merge(&mut self, other: &Self) {
+        self.extraction_ms += other.extraction_ms;
+        self.parsing_ms += other.parsing_ms;
+        self.media_ms += other.media_ms;
+        self.peak_parse_workers = self.peak_parse_workers.max(other.peak_parse_workers);
     }
 }
 
+/// Counts tasks running at once and remembers the most seen
+#[derive(Debug, Default)]
+pub(crate) struct ConcurrencyGauge {
+    active: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl ConcurrencyGauge {
+    /// Count a task as running until the guard is dropped
+    pub(crate) fn enter(&self) -> GaugeGuard<'_> {
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(active, Ordering::SeqCst);
+        GaugeGuard(self)
+    }
+
+    /// Most tasks that ran at once
+    pub(crate) fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+}
+
+/// A task counted by a [`ConcurrencyGauge`]
+pub(crate) struct GaugeGuard<'a>(&'a ConcurrencyGauge);
+
+impl Drop for GaugeGuard<'_> {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Milliseconds elapsed since `start`
+pub(crate) fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/// File an internal error from archive processing as a [`Md2DbError::ZipError`],
+/// unless it already has a category
+fn archive_error(error: anyhow::Error) -> Md2DbError {
+    Md2DbError::wrap(error, |e| Md2DbError::zip("", e))
+}
+
 /// Result of processing a ZIP file
 #[derive(Debug)]
 pub struct ZipProcessResult {
     /// All parsed questions
     pub questions: Vec<Question>,
     /// Images that were processed (hash -> content)
-    pub images: HashMap<String, Vec<u8>>,
+    pub images: HashMap<String, EntryContent>,
+    /// Storage saved by identical images and questions sharing them
+    pub image_dedup: ImageDedupReport,
+    /// Images written to the media store (all zero without one)
+    pub image_store: ImageStoreReport,
+    /// Time spent extracting, parsing and storing images
+    pub metrics: StageMetrics,
+    /// Per-file results for every Markdown entry
+    pub files: Vec<ZipFileResult>,
+    /// Import defaults declared in the archive's manifest, if any
+    pub manifest: Option<ImportManifest>,
     /// Warnings generated during processing
     pub warnings: Vec<String>,
 }
 
+impl ZipProcessResult {
+    /// Result of a file read without the archive pipeline (no images or files)
+    fn standalone(questions: Vec<Question>, warnings: Vec<String>) -> Self {
+        Self {
+            questions,
+            images: HashMap::new(),
+            image_dedup: ImageDedupReport::default(),
+            image_store: ImageStoreReport::default(),
+            metrics: StageMetrics::default(),
+            files: Vec::new(),
+            manifest: None,
+            warnings,
+        }
+    }
+}
+
+/// Default number of nested archive levels that are opened
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 3;
+
+/// Default limit on bytes extracted from one upload (1 GiB)
+pub const DEFAULT_MAX_EXTRACTED_BYTES: u64 = 1 << 30;
+
+/// Limits on what one upload may extract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZipLimits {
+    /// How many levels of archives inside archives are opened (0 disables)
+    pub max_nesting_depth: usize,
+    /// Total uncompressed bytes extracted, nested archives included
+    pub max_extracted_bytes: u64,
+}
+
+impl Default for ZipLimits {
+    fn default() -> Self {
+        Self {
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            max_extracted_bytes: DEFAULT_MAX_EXTRACTED_BYTES,
+        }
+    }
+}
+
 /// ZIP processor with configurable parallelism
+#[derive(Clone)]
 pub struct ZipProcessor {
     /// Maximum number of concurrent file processing tasks
     max_workers: usize,
+    /// Parser options used unless the archive's manifest names a preset
+    parser_options: ParserOptions,
+    /// Where extracted images are persisted, if anywhere
+    media_store: Option<Arc<dyn MediaStore>>,
+    /// Strip EXIF and text metadata from images before hashing
+    strip_metadata: bool,
+    /// Recognizes formulas in referenced images, if configured
+    formula_ocr: Option<Arc<dyn FormulaOcr>>,
+    /// How many levels of archives inside archives are opened
+    max_nesting_depth: usize,
+    /// Total uncompressed bytes extracted from one upload, nested archives included
+    max_extracted_bytes: u64,
+    /// Write large entries to disk instead of memory, if configured
+    spill: Option<SpillConfig>,
+    /// Return questions in archive order and number them
+    preserve_order: bool,
+    /// Recognize uploads by content instead of by file name
+    detect_format: bool,
+    /// Reads spreadsheets recognized by format detection
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    table_importer: TabularImporter,
 }
 
 impl ZipProcessor {
@@ -77,59 +587,424 @@ impl ZipProcessor {
             .map(|n| n.get())
             .unwrap_or(4);
 
-        Self {
-            max_workers: workers,
-        }
+        Self::with_workers(workers)
     }
 
     /// Create a ZIP processor with specific worker count
     pub fn with_workers(workers: usize) -> Self {
         Self {
             max_workers: workers,
+            parser_options: ParserOptions::default(),
+            media_store: None,
+            strip_metadata: false,
+            formula_ocr: None,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            max_extracted_bytes: DEFAULT_MAX_EXTRACTED_BYTES,
+            spill: None,
+            preserve_order: true,
+            detect_format: false,
+            #[cfg(any(feature = "csv", feature = "xlsx"))]
+            table_importer: TabularImporter::new(),
         }
     }
 
-    /// Process a ZIP file from raw bytes
-    pub async fn process_zip(&self, zip_data: Vec<u8>) -> Result<ZipProcessResult> {
+    /// Use these parser options for archives without a manifest preset
+    pub fn with_parser_options(mut self, options: ParserOptions) -> Self {
+        self.parser_options = options;
+        self
+    }
+
+    /// Persist extracted images to `store` and record where they went
+    pub fn with_media_store(mut self, store: Arc<dyn MediaStore>) -> Self {
+        self.media_store = Some(store);
+        self
+    }
+
+    /// Stop persisting extracted images
+    pub(crate) fn without_media_store(mut self) -> Self {
+        self.media_store = None;
+        self
+    }
+
+    /// Strip identifying metadata (EXIF, GPS, PNG text) from images
+    ///
+    /// Off by default. Hashes and stored files reflect the scrubbed content.
+    pub fn with_strip_metadata(mut self, enabled: bool) -> Self {
+        self.strip_metadata = enabled;
+        self
+    }
+
+    /// Recognize formulas in images referenced by questions
+    ///
+    /// Results are appended to each question's `latex` (see [`crate::ocr`]).
+    pub fn with_formula_ocr(mut self, ocr: Arc<dyn FormulaOcr>) -> Self {
+        self.formula_ocr = Some(ocr);
+        self
+    }
+
+    /// Open ZIP files inside the archive up to `depth` levels deep (0 disables)
+    pub fn with_max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth;
+        self
+    }
+
+    /// Fail an upload that would extract more than `bytes` in total
+    pub fn with_max_extracted_bytes(mut self, bytes: u64) -> Self {
+        self.max_extracted_bytes = bytes;
+        self
+    }
+
+    /// Apply both extraction limits at once
+    pub fn with_limits(self, limits: ZipLimits) -> Self {
+        self.with_max_nesting_depth(limits.max_nesting_depth)
+            .with_max_extracted_bytes(limits.max_extracted_bytes)
+    }
+
+    /// Stream entries above the spill threshold to disk during extraction
+    ///
+    /// Images are then loaded one at a time while hashing and storing, so
+    /// memory use no longer grows with the size of the archive.
+    pub fn with_spill(mut self, spill: SpillConfig) -> Self {
+        self.spill = Some(spill);
+        self
+    }
+
+    /// Keep questions in archive entry order, then document order (on by default)
+    ///
+    /// Each question's [`sequence`](Question::sequence) records its position.
+    /// Turning this off lets a slow file hold back no others, at the cost of
+    /// question order varying between runs.
+    pub fn with_preserve_order(mut self, enabled: bool) -> Self {
+        self.preserve_order = enabled;
+        self
+    }
+
+    /// Recognize each upload by its content (see [`crate::detect`])
+    ///
+    /// Off by default, so [`ZipProcessor::process_upload`] goes by the file
+    /// name. When on, single Markdown, text, GIFT and spreadsheet files are
+    /// accepted as well as documents and archives.
+    pub fn with_format_detection(mut self, enabled: bool) -> Self {
+        self.detect_format = enabled;
+        self
+    }
+
+    /// Read spreadsheets found by format detection with `importer`
+    #[cfg(any(feature = "csv", feature = "xlsx"))]
+    pub fn with_table_importer(mut self, importer: TabularImporter) -> Self {
+        self.table_importer = importer;
+        self
+    }
+
+    /// Process an archive from raw bytes
+    ///
+    /// The format is recognized from the leading bytes; anything unrecognized
+    /// is read as ZIP, which also covers self-extracting archives.
+    pub async fn process_zip(&self, zip_data: Vec<u8>) -> Result<ZipProcessResult, Md2DbError> {
         // Extract all entries using tokio task for blocking I/O
-        let entries = tokio::task::spawn_blocking(move || {
-            Self::extract_all_entries_sync(zip_data)
+        let start = Instant::now();
+        let mut extraction = self.extraction();
+        let (entries, warnings) = tokio::task::spawn_blocking(move || {
+            let mut warnings = Vec::new();
+            Self::extract_all_entries_sync(Cursor::new(zip_data), "", 0, &mut extraction, &mut warnings)
+                .map(|entries| (entries, warnings))
+        })
+        .await
+        .map_err(|e| Md2DbError::zip("", e))?
+        .map_err(archive_error)?;
+
+        self.process_extracted(entries, warnings, start).await.map_err(archive_error)
+    }
+
+    /// Extract the entries of an archive without parsing them
+    ///
+    /// Applies the same nesting and size limits as [`process_zip`](Self::process_zip)
+    /// and returns the entries with any warnings. Runs on the calling thread.
+    pub fn extract_entries(&self, data: &[u8]) -> Result<(Vec<ZipEntry>, Vec<String>), Md2DbError> {
+        let mut warnings = Vec::new();
+        let entries = Self::extract_all_entries_sync(Cursor::new(data), "", 0, &mut self.extraction(), &mut warnings)
+            .map_err(archive_error)?;
+        Ok((entries, warnings))
+    }
+
+    /// Process an archive on disk without loading it into memory
+    pub async fn process_zip_file(&self, path: impl AsRef<Path>) -> Result<ZipProcessResult, Md2DbError> {
+        let path = path.as_ref().to_path_buf();
+        let start = Instant::now();
+        let mut extraction = self.extraction();
+        let (entries, warnings) = tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&path).map_err(|e| Md2DbError::zip(format!("Failed to open {:?}", path), e))?;
+            let mut warnings = Vec::new();
+            Self::extract_all_entries_sync(file, "", 0, &mut extraction, &mut warnings)
+                .map(|entries| (entries, warnings))
+                .map_err(archive_error)
         })
-        .await??;
+        .await
+        .map_err(|e| Md2DbError::zip("", e))??;
+
+        self.process_extracted(entries, warnings, start).await.map_err(archive_error)
+    }
+
+    /// Process a single Word document
+    ///
+    /// The document goes through the same pipeline as one inside an archive:
+    /// its images are hashed and stored, and its questions record `name` as
+    /// their source file. Unlike inside an archive, a document that cannot
+    /// be converted is an error.
+    #[cfg(feature = "docx")]
+    pub async fn process_docx(&self, name: &str, data: Vec<u8>) -> Result<ZipProcessResult, Md2DbError> {
+        let start = Instant::now();
+        let mut warnings = Vec::new();
+        let entries = convert_docx(ZipEntry::new(PathBuf::from(name), data), &mut warnings)
+            .map_err(|e| Md2DbError::wrap(e, |e| Md2DbError::parse("", e)))?;
+        self.process_extracted(entries, warnings, start).await.map_err(archive_error)
+    }
+
+    /// Process a single PDF
+    ///
+    /// Like [`ZipProcessor::process_docx`], the questions record `name` as
+    /// their source file and a PDF that cannot be read is an error. Pages
+    /// that need OCR are reported as warnings.
+    #[cfg(feature = "pdf")]
+    pub async fn process_pdf(&self, name: &str, data: Vec<u8>) -> Result<ZipProcessResult, Md2DbError> {
+        let start = Instant::now();
+        let mut warnings = Vec::new();
+        let entries = convert_pdf(ZipEntry::new(PathBuf::from(name), data), &mut warnings)
+            .map_err(|e| Md2DbError::wrap(e, |e| Md2DbError::parse("", e)))?;
+        self.process_extracted(entries, warnings, start).await.map_err(archive_error)
+    }
 
-        // Separate Markdown files and images
+    /// Process an upload named `name`: a Word document, a PDF or an archive
+    ///
+    /// With [format detection](ZipProcessor::with_format_detection) any
+    /// supported file is accepted, whatever its name.
+    #[cfg_attr(not(any(feature = "docx", feature = "pdf")), allow(unused_variables))]
+    pub async fn process_upload(&self, name: &str, data: Vec<u8>) -> Result<ZipProcessResult, Md2DbError> {
+        if self.detect_format {
+            return self.process_detected(name, data).await;
+        }
+        let lower = name.to_lowercase();
+        #[cfg(feature = "docx")]
+        if lower.ends_with(".docx") {
+            return self.process_docx(name, data).await;
+        }
+        #[cfg(feature = "pdf")]
+        if lower.ends_with(".pdf") {
+            return self.process_pdf(name, data).await;
+        }
+        self.process_zip(data).await
+    }
+
+    /// Process a file in the format recognized from its content
+    ///
+    /// Any format in [`crate::detect`] is accepted; the name only breaks ties
+    /// and is recorded as the questions' source file.
+    pub async fn process_detected(&self, name: &str, data: Vec<u8>) -> Result<ZipProcessResult, Md2DbError> {
+        let Some(format) = FormatDetector::detect(name, &data) else {
+            return Err(Md2DbError::zip(name, "unrecognized file format"));
+        };
+        if !format.is_enabled() {
+            return Err(Md2DbError::zip(name, format!("{} files are not supported by this build", format)));
+        }
+        match format {
+            #[cfg(feature = "docx")]
+            ImportFormat::Docx => self.process_docx(name, data).await,
+            #[cfg(feature = "pdf")]
+            ImportFormat::Pdf => self.process_pdf(name, data).await,
+            ImportFormat::Markdown | ImportFormat::Text => {
+                // Whatever the name says, the content decides how it is parsed
+                let mut entry = ZipEntry::new(PathBuf::from(name), data);
+                entry.is_markdown = format == ImportFormat::Markdown;
+                entry.is_text = format == ImportFormat::Text;
+                entry.is_image = false;
+                entry.is_docx = false;
+                entry.is_pdf = false;
+                self.process_entries(vec![entry], Vec::new()).await.map_err(archive_error)
+            }
+            ImportFormat::Gift => {
+                let entry = ZipEntry::new(PathBuf::from(name), data);
+                let text = entry.as_str()?;
+                let import = parse_gift(&text, name);
+                Ok(ZipProcessResult::standalone(import.questions, import.warnings))
+            }
+            #[cfg(any(feature = "csv", feature = "xlsx"))]
+            format if format.is_table() => {
+                let importer = self.table_importer.clone();
+                let source = name.to_string();
+                let import = tokio::task::spawn_blocking(move || match format {
+                    #[cfg(feature = "csv")]
+                    ImportFormat::Csv => importer.import_delimited(&source, &data, b','),
+                    #[cfg(feature = "csv")]
+                    ImportFormat::Tsv => importer.import_delimited(&source, &data, b'\t'),
+                    #[cfg(feature = "xlsx")]
+                    ImportFormat::Workbook => importer.import_workbook(&source, &data),
+                    _ => unreachable!("{} is enabled", format),
+                })
+                .await
+                .map_err(|e| Md2DbError::zip("", e))?
+                .map_err(|e| Md2DbError::wrap(e, |e| Md2DbError::parse("", e)))?;
+                Ok(ZipProcessResult::standalone(import.questions, import.warnings))
+            }
+            ImportFormat::Archive(_) => self.process_zip(data).await,
+            #[allow(unreachable_patterns)]
+            format => Err(Md2DbError::zip(name, format!("{} files are not supported by this build", format))),
+        }
+    }
+
+    /// Fresh extraction state for one upload
+    fn extraction(&self) -> Extraction {
+        Extraction {
+            max_depth: self.max_nesting_depth,
+            budget: self.max_extracted_bytes,
+            remaining: self.max_extracted_bytes,
+            spill: self.spill.clone(),
+            workdir: None,
+            spilled: 0,
+        }
+    }
+
+    /// Process entries extracted since `start`, counting that as extraction
+    async fn process_extracted(
+        &self,
+        entries: Vec<ZipEntry>,
+        warnings: Vec<String>,
+        start: Instant,
+    ) -> Result<ZipProcessResult> {
+        let extraction_ms = elapsed_ms(start);
+        let mut result = self.process_entries(entries, warnings).await?;
+        result.metrics.extraction_ms += extraction_ms;
+        Ok(result)
+    }
+
+    /// Parse, hash, store and link the extracted entries of one upload
+    async fn process_entries(&self, entries: Vec<ZipEntry>, extract_warnings: Vec<String>) -> Result<ZipProcessResult> {
+        let mut warnings = Vec::new();
+        let mut manifests = MANIFEST_FILES
+            .iter()
+            .filter_map(|name| entries.iter().find(|e| e.path == Path::new(name)).map(|e| (*name, e)));
+        let manifest = match manifests.next() {
+            Some((name, entry)) => match entry.as_str().map_err(Into::into).and_then(|s| ImportManifest::from_file(name, &s)) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    warnings.push(format!("Ignoring invalid {}: {}", name, e));
+                    None
+                }
+            },
+            None => None,
+        };
+        warnings.extend(manifests.map(|(name, _)| format!("Ignoring {}: another manifest takes precedence", name)));
+        warnings.extend(extract_warnings);
+        let mut metrics = StageMetrics::default();
+        #[cfg(any(feature = "docx", feature = "pdf"))]
+        let entries = {
+            let start = Instant::now();
+            let entries = convert_documents(entries, &mut warnings);
+            metrics.extraction_ms = elapsed_ms(start);
+            entries
+        };
+
+        // A preset named by the archive overrides the processor's options
+        let preset = manifest.as_ref().and_then(|m| m.preset.as_deref());
+        let parser_options = match preset.map(presets::preset) {
+            Some(Ok(options)) => options,
+            Some(Err(e)) => {
+                warnings.push(format!("Ignoring manifest preset: {}", e));
+                self.parser_options.clone()
+            }
+            None => self.parser_options.clone(),
+        };
+
+        // Separate Markdown and text files from images
         let (md_entries, image_entries): (Vec<_>, Vec<_>) = entries
             .into_iter()
-            .partition(|e| e.is_markdown);
+            .partition(|e| e.is_markdown || e.is_text);
 
-        // Process images and Markdown files in parallel
-        let (images_result, questions_result) = tokio::join!(
-            self.process_images(image_entries),
-            self.process_markdown_files(md_entries)
-        );
+        // Hash and store images while the Markdown files are parsed
+        let images = async {
+            let start = Instant::now();
+            let (image_paths, mut warnings) = self.process_images(image_entries).await?;
+            let mut images = HashMap::with_capacity(image_paths.len());
+            let mut hashes = HashMap::with_capacity(image_paths.len());
+            for (path, hash, content) in image_paths {
+                hashes.insert(path, hash.clone());
+                images.insert(hash, content);
+            }
+            let (stored, report) = match &self.media_store {
+                Some(store) => self.store_images(store.as_ref(), &images, &mut warnings).await,
+                None => (HashMap::new(), ImageStoreReport::default()),
+            };
+            anyhow::Ok((images, hashes, stored, report, warnings, elapsed_ms(start)))
+        };
+        let gauge = ConcurrencyGauge::default();
+        let questions = async {
+            let start = Instant::now();
+            let parsed = self.process_markdown_files(md_entries, &parser_options, &gauge).await;
+            (parsed, elapsed_ms(start))
+        };
+        let (images_result, (questions_result, parsing_ms)) = tokio::join!(images, questions);
 
-        let images = images_result?;
-        let questions = questions_result?;
+        let (images, hashes, stored, image_store, image_warnings, media_ms) = images_result?;
+        warnings.extend(image_warnings);
+        let (mut questions, files, answer_warnings) = questions_result?;
+        metrics.parsing_ms = parsing_ms;
+        metrics.media_ms = media_ms;
+        metrics.peak_parse_workers = gauge.peak();
+        if let Some(manifest) = &manifest {
+            for question in &mut questions {
+                manifest.apply_to_archive(question);
+            }
+        }
+        warnings.extend(link_images(&mut questions, &hashes, &stored));
+        if let Some(ocr) = &self.formula_ocr {
+            warnings.extend(recognize_formulas(ocr.as_ref(), &mut questions, &images).await);
+        }
+        let image_dedup = ImageDedupReport::build(&hashes, &images, &questions);
+
+        warnings.extend(
+            files
+                .iter()
+                .filter_map(|f| f.error.as_ref().map(|e| format!("Failed to parse {}: {}", f.path, e))),
+        );
+        warnings.extend(answer_warnings);
 
         Ok(ZipProcessResult {
             questions,
             images,
-            warnings: Vec::new(),
+            image_dedup,
+            image_store,
+            metrics,
+            files,
+            manifest,
+            warnings,
         })
     }
 
-    /// Extract all entries from a ZIP archive (synchronous)
-    fn extract_all_entries_sync(zip_data: Vec<u8>) -> Result<Vec<ZipEntry>> {
-        use zip::read::ZipArchive;
-
-        let cursor = Cursor::new(zip_data);
-        let mut archive = ZipArchive::new(cursor)?;
+    /// Extract all entries from an archive (synchronous)
+    ///
+    /// Archives found inside are extracted recursively, with their entries
+    /// named `inner.zip/path`; byte-split volumes (`x.zip.001`, `x.zip.002`, …)
+    /// are joined first. A nested archive that cannot be read is skipped with a
+    /// warning, while exceeding the byte budget fails the whole upload.
+    fn extract_all_entries_sync<R: Read + Seek>(
+        mut reader: R,
+        prefix: &str,
+        depth: usize,
+        extraction: &mut Extraction,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<ZipEntry>> {
+        let mut head = Vec::with_capacity(DETECT_LEN);
+        (&mut reader).take(DETECT_LEN as u64).read_to_end(&mut head)?;
+        reader.rewind()?;
+        let format = ArchiveFormat::detect(&head).unwrap_or(ArchiveFormat::Zip);
 
         let mut entries = Vec::new();
+        let mut volumes: BTreeMap<String, Vec<(u32, EntryContent)>> = BTreeMap::new();
 
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let path = PathBuf::from(file.name());
+        archive_processor(format)?.for_each_file(&mut reader, &mut |file| {
+            if file.gb18030_name {
+                warnings.push(format!("Decoded entry name {}{} as GB18030", prefix, file.name));
+            }
+            let path = PathBuf::from(file.name);
 
             // Skip directories and macOS metadata files
             let path_str = path.to_string_lossy();
@@ -137,22 +1012,64 @@ impl ZipProcessor {
                 || path_str.starts_with("__MACOSX/")
                 || path_str.starts_with("._")
             {
-                continue;
+                return Ok(());
             }
 
-            // Read file content
-            let mut content = Vec::new();
-            file.read_to_end(&mut content)?;
+            let content = extraction.read_entry(file.reader, file.size)?;
+
+            let name = format!("{}{}", prefix, path_str);
+            if let Some((base, part)) = archive_volume(&name) {
+                volumes.entry(base).or_default().push((part, content));
+                return Ok(());
+            }
+            entries.push(ZipEntry::new(PathBuf::from(name), content).with_metadata(file.metadata));
+            Ok(())
+        })?;
+
+        for (name, parts) in volumes {
+            let content = match join_volumes(parts) {
+                Ok(content) => content,
+                Err(e) => {
+                    warnings.push(format!("Skipping nested archive {}: {}", name, e));
+                    continue;
+                }
+            };
+            if depth >= extraction.max_depth {
+                warnings.push(format!(
+                    "Skipping nested archive {}: nested deeper than {} levels",
+                    name, extraction.max_depth
+                ));
+                continue;
+            }
 
-            entries.push(ZipEntry::new(path, content));
+            let inner_prefix = format!("{}/", name);
+            let inner = match &content {
+                EntryContent::Memory(data) => {
+                    Self::extract_all_entries_sync(Cursor::new(data), &inner_prefix, depth + 1, extraction, warnings)
+                }
+                EntryContent::Spilled(file) => std::fs::File::open(&file.path)
+                    .map_err(Into::into)
+                    .and_then(|f| Self::extract_all_entries_sync(f, &inner_prefix, depth + 1, extraction, warnings)),
+            };
+            match inner {
+                Ok(inner) => entries.extend(inner),
+                Err(e) if extraction.remaining == 0 => return Err(e),
+                Err(e) => warnings.push(format!("Skipping nested archive {}: {}", name, e)),
+            }
         }
 
         Ok(entries)
     }
 
-    /// Process image entries with content-addressed storage
-    async fn process_images(&self, image_entries: Vec<ZipEntry>) -> Result<HashMap<String, Vec<u8>>> {
+    /// Hash image entries, returning `(archive path, hash, content)` for each
+    ///
+    /// Entries that look like images are decoded far enough to catch empty,
+    /// truncated or otherwise corrupt files; those are left out and reported
+    /// in the returned warnings. Contents are moved, not copied, so unless
+    /// metadata is stripped an image keeps the buffer it was extracted into.
+    async fn process_images(&self, image_entries: Vec<ZipEntry>) -> Result<(Vec<(String, String, EntryContent)>, Vec<String>)> {
         let semaphore = std::sync::Arc::new(Semaphore::new(self.max_workers));
+        let strip = self.strip_metadata;
 
         let results = stream::iter(image_entries)
             .map(|entry| {
@@ -161,11 +1078,11 @@ impl ZipProcessor {
                     // Acquire permit to limit concurrency
                     let _permit = sem.acquire().await.unwrap();
 
-                    // Process image (compute hash, detect format)
-                    if let Ok(crate::media::ImageRef::Local { hash, .. }) = process_image(&entry.content) {
-                        Some((hash, entry.content))
-                    } else {
-                        None
+                    let path = entry.path.to_string_lossy().replace('\\', "/");
+                    match Self::hash_entry(entry.content, entry.is_image, strip) {
+                        Ok((hash, content)) => Ok((path, hash, content)),
+                        Err(e) if entry.is_image => Err(format!("Skipping corrupt image {}: {}", path, e)),
+                        Err(e) => Err(format!("Skipping unreadable file {}: {}", path, e)),
                     }
                 }
             })
@@ -173,37 +1090,404 @@ impl ZipProcessor {
             .collect::<Vec<_>>()
             .await;
 
-        Ok(results.into_iter().filter_map(|x| x).collect())
+        let mut images = Vec::with_capacity(results.len());
+        let mut warnings = Vec::new();
+        for result in results {
+            match result {
+                Ok(image) => images.push(image),
+                Err(warning) => warnings.push(warning),
+            }
+        }
+        // Completion order varies; keep warnings stable
+        warnings.sort();
+        Ok((images, warnings))
     }
 
-    /// Process Markdown files in parallel
-    async fn process_markdown_files(&self, md_entries: Vec<ZipEntry>) -> Result<Vec<Question>> {
-        let semaphore = std::sync::Arc::new(Semaphore::new(self.max_workers));
-
-        let results = stream::iter(md_entries)
-            .map(|entry| {
-                let sem = semaphore.clone();
-                async move {
-                    // Acquire permit to limit concurrency
-                    let _permit = sem.acquire().await.unwrap();
+    /// Validate, optionally scrub, and hash one entry
+    ///
+    /// Spilled entries are read back for the duration of the call only.
+    fn hash_entry(content: EntryContent, is_image: bool, strip: bool) -> Result<(String, EntryContent)> {
+        let data = content.read()?;
+        if is_image {
+            validate_image(&data)?;
+            if strip {
+                let stripped = strip_metadata(&data)?;
+                let hash = content_hash(&stripped);
+                return Ok((hash, content.replace(stripped)?));
+            }
+        }
+        let hash = content_hash(&data);
+        drop(data);
+        Ok((hash, content))
+    }
 
-                    // Parse the Markdown file
-                    let content = entry.as_string()?;
-                    parse_markdown(&content)
-                }
+    /// Persist images to the media store; returns stored paths by hash
+    ///
+    /// Up to `max_workers` images are written at once. An image that cannot
+    /// be stored is reported as a warning and left without a stored path.
+    async fn store_images(
+        &self,
+        store: &dyn MediaStore,
+        images: &HashMap<String, EntryContent>,
+        warnings: &mut Vec<String>,
+    ) -> (HashMap<String, String>, ImageStoreReport) {
+        let puts: Vec<_> = images
+            .iter()
+            .map(|(hash, content)| async move {
+                let put = match content.read() {
+                    Ok(data) => store.put(&data).await,
+                    Err(e) => Err(e),
+                };
+                (hash, put)
             })
+            .collect();
+        let puts = stream::iter(puts)
             .buffer_unordered(self.max_workers)
             .collect::<Vec<_>>()
             .await;
 
+        let mut stored = HashMap::with_capacity(images.len());
+        let mut report = ImageStoreReport::default();
+        for (hash, put) in puts {
+            match put {
+                Ok(media) => {
+                    if media.newly_stored {
+                        report.stored += 1;
+                    } else {
+                        report.already_stored += 1;
+                    }
+                    stored.insert(hash.clone(), media.path);
+                }
+                Err(e) => {
+                    report.failed += 1;
+                    warnings.push(format!("Failed to store image {}: {}", hash, e));
+                }
+            }
+        }
+        (stored, report)
+    }
+
+    /// Process Markdown and plain-text files in parallel
+    ///
+    /// A file that fails to decode or parse is recorded in its per-file result
+    /// instead of failing the whole archive.
+    async fn process_markdown_files(
+        &self,
+        md_entries: Vec<ZipEntry>,
+        options: &ParserOptions,
+        gauge: &ConcurrencyGauge,
+    ) -> Result<(Vec<Question>, Vec<ZipFileResult>, Vec<String>)> {
+        let semaphore = std::sync::Arc::new(Semaphore::new(self.max_workers));
+
+        let parses = stream::iter(md_entries).map(|entry| {
+            let sem = semaphore.clone();
+            async move {
+                // Acquire permit to limit concurrency
+                let _permit = sem.acquire().await.unwrap();
+                let _running = gauge.enter();
+
+                // Decode and parse the file
+                let parsed = entry.decode_text().and_then(|(content, encoding)| {
+                    let questions = if entry.is_text {
+                        parse_text_with_options(&content, options)
+                    } else {
+                        parse_markdown_with_options(&content, options)
+                    };
+                    questions.map(|questions| (questions, encoding))
+                });
+                (entry, parsed)
+            }
+        });
+        let results = if self.preserve_order {
+            parses.buffered(self.max_workers).collect::<Vec<_>>().await
+        } else {
+            parses.buffer_unordered(self.max_workers).collect::<Vec<_>>().await
+        };
+
         // Collect all questions from all files
         let mut all_questions = Vec::new();
-        for result in results {
-            all_questions.extend(result?);
+        let mut files = Vec::with_capacity(results.len());
+        let mut warnings = Vec::new();
+        for (entry, parsed) in results {
+            let path = entry.path.to_string_lossy().into_owned();
+            match parsed {
+                Ok((mut questions, encoding)) => {
+                    if let Some(encoding) = encoding {
+                        warnings.push(format!("{}: decoded as {}", path, encoding));
+                    }
+                    warnings.extend(
+                        validate_answers(&mut questions)
+                            .into_iter()
+                            .chain(validate_formulas(&mut questions))
+                            .map(|w| format!("{}: {}", path, w)),
+                    );
+                    let provenance = entry.provenance();
+                    for question in &mut questions {
+                        question.provenance = Some(provenance.clone());
+                    }
+                    files.push(ZipFileResult {
+                        path,
+                        metadata: entry.metadata,
+                        questions: questions.len(),
+                        error: None,
+                    });
+                    all_questions.extend(questions);
+                }
+                Err(e) => files.push(ZipFileResult {
+                    path,
+                    metadata: entry.metadata,
+                    questions: 0,
+                    error: Some(e.to_string()),
+                }),
+            }
+        }
+
+        if self.preserve_order {
+            for (question, sequence) in all_questions.iter_mut().zip(1..) {
+                question.sequence = Some(sequence);
+            }
+        }
+
+        Ok((all_questions, files, warnings))
+    }
+}
+
+/// Limits and scratch space shared by an archive and everything nested in it
+struct Extraction {
+    max_depth: usize,
+    budget: u64,
+    remaining: u64,
+    spill: Option<SpillConfig>,
+    /// Created on the first spilled entry
+    workdir: Option<Arc<Workdir>>,
+    spilled: usize,
+}
+
+/// Most bytes reserved for an entry before any of it has been read
+const ENTRY_PREALLOCATION: u64 = 64 * 1024;
+
+impl Extraction {
+    fn exceeded(&mut self) -> anyhow::Error {
+        self.remaining = 0;
+        anyhow!("archive expands to more than {} bytes", self.budget)
+    }
+
+    /// Read one entry into memory, or to disk if it is above the spill threshold
+    ///
+    /// Neither the declared size nor the data is trusted to stay in budget.
+    fn read_entry(&mut self, file: &mut dyn Read, declared: u64) -> Result<EntryContent> {
+        if declared > self.remaining {
+            return Err(self.exceeded());
+        }
+        let mut limited = file.take(self.remaining + 1);
+
+        let content = match &self.spill {
+            Some(spill) if declared > spill.threshold => {
+                spill.workdirs.ensure_space(declared)?;
+                let workdir = match &self.workdir {
+                    Some(workdir) => workdir.clone(),
+                    None => self.workdir.insert(Arc::new(spill.workdirs.create("extract")?)).clone(),
+                };
+                self.spilled += 1;
+                let (path, mut out) = workdir.create_file(&format!("entry-{}", self.spilled))?;
+                let size = std::io::copy(&mut limited, &mut out)?;
+                EntryContent::Spilled(Arc::new(SpilledFile { path, size, workdir }))
+            }
+            _ => {
+                // The declared size only sizes the first allocation, up to a
+                // cap, so a forged header cannot reserve the whole budget
+                let mut data = Vec::with_capacity(declared.min(ENTRY_PREALLOCATION) as usize);
+                limited.read_to_end(&mut data)?;
+                EntryContent::Memory(data.into())
+            }
+        };
+
+        if content.len() > self.remaining {
+            return Err(self.exceeded());
+        }
+        self.remaining -= content.len();
+        Ok(content)
+    }
+}
+
+/// Recognize a (part of a) nested archive by name
+///
+/// Returns the archive name and the part number: `x.zip` (or any other
+/// archive name) is the only or last part, `x.zip.001` a byte-split volume,
+/// and `x.z01` an Info-ZIP split volume that precedes `x.zip`.
+fn archive_volume(name: &str) -> Option<(String, u32)> {
+    if ArchiveFormat::from_name(name).is_some() {
+        return Some((name.to_string(), u32::MAX));
+    }
+
+    let (stem, ext) = name.rsplit_once('.')?;
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if ext.len() == 3 && digits(ext) && stem.to_ascii_lowercase().ends_with(".zip") {
+        return Some((stem.to_string(), ext.parse().ok()?));
+    }
+    match ext.strip_prefix(['z', 'Z']) {
+        Some(number) if number.len() == 2 && digits(number) => {
+            Some((format!("{}.zip", stem), number.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Concatenate the parts of a split archive in order
+///
+/// Byte-split volumes (`x.zip.001`, as written by 7-Zip and `split`) join
+/// into a plain archive. Info-ZIP spans keep per-volume offsets that the
+/// ZIP reader cannot follow, so those are rejected with a hint instead.
+fn join_volumes(mut parts: Vec<(u32, EntryContent)>) -> Result<EntryContent> {
+    parts.sort_by_key(|(part, _)| *part);
+    if parts.len() > 1 && parts.iter().any(|(part, _)| *part == u32::MAX) {
+        return Err(anyhow!("Info-ZIP split archives are not supported; rejoin them with `zip -s 0`"));
+    }
+    for (expected, (part, _)) in (1..).zip(&parts) {
+        if *part != expected && *part != u32::MAX {
+            return Err(anyhow!("missing volume {}", expected));
+        }
+    }
+
+    if parts.len() == 1 {
+        return Ok(parts.remove(0).1);
+    }
+    let mut joined = Vec::with_capacity(parts.iter().map(|(_, content)| content.len() as usize).sum());
+    for (_, content) in &parts {
+        joined.extend_from_slice(&content.read()?);
+    }
+    Ok(EntryContent::Memory(joined.into()))
+}
+
+/// Replace Word documents and PDFs with Markdown (and embedded images)
+///
+/// Documents that cannot be converted are dropped with a warning.
+#[cfg(any(feature = "docx", feature = "pdf"))]
+fn convert_documents(entries: Vec<ZipEntry>, warnings: &mut Vec<String>) -> Vec<ZipEntry> {
+    let mut converted = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let path = entry.path.to_string_lossy().into_owned();
+        let result = match entry {
+            #[cfg(feature = "docx")]
+            entry if entry.is_docx => convert_docx(entry, warnings),
+            #[cfg(feature = "pdf")]
+            entry if entry.is_pdf => convert_pdf(entry, warnings),
+            entry => {
+                converted.push(entry);
+                continue;
+            }
+        };
+        match result {
+            Ok(entries) => converted.extend(entries),
+            Err(e) => warnings.push(format!("Failed to convert {}: {}", path, e)),
         }
+    }
+    converted
+}
+
+/// Convert one Word document into a Markdown entry and its images
+///
+/// The Markdown keeps the document's path, so questions record the `.docx`
+/// as their source; images go to `<name>.docx.media/` beside it, which is
+/// where the converted references point.
+#[cfg(feature = "docx")]
+fn convert_docx(entry: ZipEntry, warnings: &mut Vec<String>) -> Result<Vec<ZipEntry>> {
+    let path = entry.path.to_string_lossy().into_owned();
+    let file_name = entry.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let media_dir = format!("{}.media", file_name);
+    let document = crate::docx::convert(&entry.content.read()?, &media_dir)?;
+
+    warnings.extend(document.warnings.into_iter().map(|w| format!("{}: {}", path, w)));
+    let mut entries: Vec<_> = document
+        .images
+        .into_iter()
+        .map(|(name, data)| ZipEntry::new(entry.path.with_file_name(&media_dir).join(name), data))
+        .collect();
+    entries.push(ZipEntry {
+        content: document.markdown.into_bytes().into(),
+        is_markdown: true,
+        is_docx: false,
+        ..entry
+    });
+    Ok(entries)
+}
+
+/// Convert one PDF into a Markdown entry holding its text
+///
+/// The entry keeps the PDF's path, so questions record it as their source.
+#[cfg(feature = "pdf")]
+fn convert_pdf(entry: ZipEntry, warnings: &mut Vec<String>) -> Result<Vec<ZipEntry>> {
+    let path = entry.path.to_string_lossy().into_owned();
+    let document = crate::pdf::extract(&entry.content.read()?)?;
+    warnings.extend(document.warnings.iter().map(|w| format!("{}: {}", path, w)));
+    Ok(vec![ZipEntry {
+        content: document.markdown().into_bytes().into(),
+        is_markdown: true,
+        is_pdf: false,
+        ..entry
+    }])
+}
+
+/// Point local image references at the archive entries they name
+///
+/// References are resolved relative to the question's source file. Found
+/// images get their content hash and, if persisted, their stored path;
+/// returns a warning for each reference missing from the archive.
+fn link_images(
+    questions: &mut [Question],
+    hashes: &HashMap<String, String>,
+    stored: &HashMap<String, String>,
+) -> Vec<String> {
+    // Archives made on Windows or macOS often differ from their Markdown in
+    // letter case only; fall back to that when the match is unambiguous
+    let mut folded: HashMap<String, Option<&str>> = HashMap::new();
+    for path in hashes.keys() {
+        folded
+            .entry(path.to_lowercase())
+            .and_modify(|p| *p = None)
+            .or_insert(Some(path.as_str()));
+    }
+
+    let mut warnings = Vec::new();
+    for question in questions {
+        let source = question
+            .provenance
+            .as_ref()
+            .and_then(|p| p.source_file.clone())
+            .unwrap_or_default();
+
+        for image in &mut question.images {
+            let ImageRef::Local { hash, original_path, stored_path, .. } = image else {
+                continue;
+            };
+            let Some(path) = resolve_reference(&source, original_path) else {
+                warnings.push(format!("{}: image path escapes the archive: {}", source, original_path));
+                continue;
+            };
 
-        Ok(all_questions)
+            let found = match hashes.get(&path) {
+                Some(found) => Some(found),
+                None => match folded.get(&path.to_lowercase()) {
+                    Some(Some(actual)) => {
+                        warnings.push(format!(
+                            "{}: image {} matched {} ignoring case",
+                            source, original_path, actual
+                        ));
+                        hashes.get(*actual)
+                    }
+                    _ => None,
+                },
+            };
+            match found {
+                Some(found) => {
+                    hash.clone_from(found);
+                    *stored_path = stored.get(found).cloned();
+                }
+                None => warnings.push(format!("{}: image not found in archive: {}", source, original_path)),
+            }
+        }
     }
+    warnings
 }
 
 impl Default for ZipProcessor {
@@ -226,9 +1510,17 @@ mod tests {
         assert!(!png_entry.is_markdown);
         assert!(png_entry.is_image);
 
+        for name in ["diagram.svg", "photo.HEIC", "photo.avif", "scan.tif"] {
+            assert!(ZipEntry::new(PathBuf::from(name), Vec::new()).is_image, "{}", name);
+        }
+
         let txt_entry = ZipEntry::new(PathBuf::from("readme.txt"), b"Hello".to_vec());
         assert!(!txt_entry.is_markdown);
+        assert!(txt_entry.is_text);
         assert!(!txt_entry.is_image);
+
+        assert!(ZipEntry::new(PathBuf::from("Exam.DOCX"), Vec::new()).is_docx);
+        assert!(ZipEntry::new(PathBuf::from("exam.pdf"), Vec::new()).is_pdf);
     }
 
     #[test]
@@ -238,6 +1530,598 @@ mod tests {
         assert_eq!(s, "Hello, world!");
     }
 
+    #[test]
+    fn test_zip_entry_as_str_borrows_utf8() {
+        let data = Bytes::from_static("# 题目\n\nAnswer: A".as_bytes());
+        let entry = ZipEntry::new(PathBuf::from("exam.md"), data.clone());
+        let text = entry.as_str().unwrap();
+        assert!(matches!(text, Cow::Borrowed(_)));
+        assert_eq!(text.as_ptr(), data.as_ptr());
+
+        // Other encodings still decode into an owned string
+        let (gbk, _, _) = encoding_rs::GBK.encode("题目");
+        let entry = ZipEntry::new(PathBuf::from("exam.md"), gbk.into_owned());
+        assert_eq!(entry.decode_text().unwrap(), (Cow::Owned("题目".to_string()), Some("gb18030")));
+    }
+
+    #[tokio::test]
+    async fn test_process_images_keeps_payloads_shared() {
+        let png = Bytes::from(crate::media::test_png(4, 3));
+        let entry = ZipEntry::new(PathBuf::from("a.png"), png.clone());
+
+        let (images, warnings) = ZipProcessor::new().process_images(vec![entry]).await.unwrap();
+        assert!(warnings.is_empty());
+        let [(path, _, EntryContent::Memory(kept))] = &images[..] else {
+            panic!("expected one in-memory image, got {:?}", images);
+        };
+        assert_eq!(path, "a.png");
+        assert_eq!(kept.as_ptr(), png.as_ptr());
+    }
+
+    fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut data = Vec::new();
+        {
+            let mut writer = zip::ZipWriter::new(Cursor::new(&mut data));
+            let options = zip::write::SimpleFileOptions::default()
+                .last_modified_time(zip::DateTime::from_date_and_time(2024, 3, 15, 10, 30, 0).unwrap());
+            for (name, content) in files {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(content).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_records_entry_metadata() {
+        let data = build_zip(&[
+            ("exam.md", "# Question 1\n\n* A. 1\n* B. 2".as_bytes()),
+            ("broken.md", &[0xFF, 0xFE, 0x00]),
+        ]);
+
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+
+        assert_eq!(result.questions.len(), 1);
+        assert_eq!(result.files.len(), 2);
+        // One parse failure plus a missing answer for the parsed question
+        assert_eq!(result.warnings.len(), 2);
+
+        let exam = result.files.iter().find(|f| f.path == "exam.md").unwrap();
+        assert_eq!(exam.questions, 1);
+        assert!(exam.error.is_none());
+        assert_eq!(exam.metadata.crc32, crc_of(b"# Question 1\n\n* A. 1\n* B. 2"));
+        assert_eq!(
+            exam.metadata.modified_at.unwrap().to_rfc3339(),
+            "2024-03-15T10:30:00+00:00"
+        );
+
+        let provenance = result.questions[0].provenance.as_ref().unwrap();
+        assert_eq!(provenance.source_file.as_deref(), Some("exam.md"));
+        assert_eq!(provenance.crc32, Some(exam.metadata.crc32));
+        assert_eq!(provenance.uncompressed_size, Some(exam.metadata.uncompressed_size));
+
+        let broken = result.files.iter().find(|f| f.path == "broken.md").unwrap();
+        assert!(broken.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_parses_text_files() {
+        let text = "1. Which is 2*3?\r\nA. 5\r\nB. 6\r\nAnswer: B\r\n\r\n2. Name a prime.\r\n";
+        let data = build_zip(&[("dump/exam.txt", text.as_bytes()), ("exam.md", b"# Question 1\n\nAnswer: x")]);
+
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+
+        assert_eq!(result.files.len(), 2);
+        let exam = result.files.iter().find(|f| f.path == "dump/exam.txt").unwrap();
+        assert_eq!(exam.questions, 2);
+        let from_text: Vec<_> = result
+            .questions
+            .iter()
+            .filter(|q| q.provenance.as_ref().and_then(|p| p.source_file.as_deref()) == Some("dump/exam.txt"))
+            .collect();
+        assert_eq!(from_text[0].stem, "Which is 2*3?");
+        assert_eq!(from_text[0].answer.as_deref(), Some("B"));
+        assert_eq!(from_text[1].stem, "Name a prime.");
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_uses_manifest_preset() {
+        let exam = "1、1 + 1 = ?\nA、1\nB、2\n正确答案：B\n".as_bytes();
+        let with_preset = build_zip(&[("manifest.toml", b"preset = \"kaoshibao\""), ("exam.md", exam)]);
+        let result = ZipProcessor::new().process_zip(with_preset).await.unwrap();
+        assert_eq!(result.questions.len(), 1);
+        assert_eq!(result.questions[0].stem, "1 + 1 = ?");
+        assert_eq!(result.questions[0].options.len(), 2);
+        assert_eq!(result.questions[0].answer.as_deref(), Some("B"));
+
+        let unknown = build_zip(&[("manifest.toml", b"preset = \"nope\""), ("exam.md", exam)]);
+        let result = ZipProcessor::new().process_zip(unknown).await.unwrap();
+        assert!(result.warnings.iter().any(|w| w.starts_with("Ignoring manifest preset")));
+        assert!(result.questions[0].options.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_applies_yaml_manifest() {
+        let bank = "bank: 期中\nsubject: 物理\ndefault_type: fill_in_the_blank\nfiles:\n  essays/:\n    default_type: subjective\n";
+        let data = build_zip(&[
+            ("bank.yaml", bank.as_bytes()),
+            ("manifest.json", b"{}"),
+            ("exam.md", b"# State the value of g"),
+            ("essays/a.md", b"# Describe a pendulum"),
+        ]);
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+
+        assert_eq!(result.manifest, Some(ImportManifest::default()));
+        assert_eq!(result.warnings, ["Ignoring bank.yaml: another manifest takes precedence"]);
+        // manifest.json wins over bank.yaml, and declares nothing
+        assert!(result.questions.iter().all(|q| q.tags.is_empty()));
+
+        let data = build_zip(&[
+            ("bank.yaml", bank.as_bytes()),
+            ("exam.md", b"# State the value of g"),
+            ("essays/a.md", b"# Describe a pendulum"),
+        ]);
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+        assert_eq!(result.manifest.as_ref().unwrap().bank.as_deref(), Some("期中"));
+        let types: Vec<_> = result.questions.iter().map(|q| (q.qtype, q.tags.clone())).collect();
+        assert_eq!(
+            types,
+            [
+                (crate::models::QuestionType::FillInTheBlank, vec!["物理".to_string()]),
+                (crate::models::QuestionType::Subjective, vec!["物理".to_string()]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_persists_images() {
+        use crate::media::FsMediaStore;
+
+        let png = &crate::media::test_png(4, 3)[..];
+        let exam = "# Question 1\n\n![plot](./img/plot.png)\n\n![gone](missing.png)\n".as_bytes();
+        let data = build_zip(&[("ch1/exam.md", exam), ("ch1/img/plot.png", png)]);
+
+        let root = tempfile::tempdir().unwrap();
+        let store = Arc::new(FsMediaStore::new(root.path()).unwrap());
+        let result = ZipProcessor::new()
+            .with_media_store(store)
+            .process_zip(data)
+            .await
+            .unwrap();
+
+        let images = &result.questions[0].images;
+        assert_eq!(images.len(), 2);
+        let ImageRef::Local { hash, stored_path, .. } = &images[0] else {
+            panic!("expected a local image");
+        };
+        assert_eq!(hash, &content_hash(png));
+        let stored_path = stored_path.as_deref().unwrap();
+        assert_eq!(std::fs::read(root.path().join(stored_path)).unwrap(), png);
+
+        let ImageRef::Local { hash, stored_path, .. } = &images[1] else {
+            panic!("expected a local image");
+        };
+        assert!(hash.is_empty());
+        assert!(stored_path.is_none());
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w == "ch1/exam.md: image not found in archive: missing.png"));
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_reports_corrupt_images() {
+        let png = crate::media::test_png(4, 3);
+        let data = build_zip(&[
+            ("exam.md", b"# Question 1\n\n![a](a.png)\n\nAnswer: A"),
+            ("a.png", &png),
+            ("empty.png", b""),
+            ("cut.png", &png[..png.len() - 12]),
+        ]);
+
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+
+        assert_eq!(result.images.len(), 1);
+        let corrupt: Vec<_> = result.warnings.iter().filter(|w| w.starts_with("Skipping corrupt image")).collect();
+        assert_eq!(
+            corrupt,
+            [
+                "Skipping corrupt image cut.png: truncated PNG (missing IEND chunk)",
+                "Skipping corrupt image empty.png: empty file",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_strips_image_metadata() {
+        let png = crate::media::test_png(4, 3);
+        let mut text = 9u32.to_be_bytes().to_vec();
+        text.extend_from_slice(b"tEXtGPS\x0051.5N\0\0\0\0");
+        let tagged = [&png[..33], &text, &png[33..]].concat();
+        let data = build_zip(&[("exam.md", b"# Question 1\n\n![a](a.png)"), ("a.png", &tagged)]);
+
+        let kept = ZipProcessor::new().process_zip(data.clone()).await.unwrap();
+        assert_eq!(*kept.images.values().next().unwrap().read().unwrap(), tagged[..]);
+
+        let result = ZipProcessor::new().with_strip_metadata(true).process_zip(data).await.unwrap();
+        let (hash, content) = result.images.iter().next().unwrap();
+        assert_eq!(*content.read().unwrap(), png[..]);
+        assert_eq!(hash, &crate::media::content_hash(&png));
+        let ImageRef::Local { hash: linked, .. } = &result.questions[0].images[0] else {
+            panic!("expected a local image");
+        };
+        assert_eq!(linked, hash);
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_reports_duplicate_images() {
+        let png = crate::media::test_png(4, 3);
+        let other = crate::media::test_png(8, 8);
+        let data = build_zip(&[
+            ("a.md", b"# Question 1\n\n![](img/a.png)\n\n# Question 2\n\n![](img/copy.png)"),
+            ("b.md", b"# Question 3\n\n![](img/other.png)"),
+            ("img/a.png", &png),
+            ("img/copy.png", &png),
+            ("img/other.png", &other),
+        ]);
+
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+        let report = &result.image_dedup;
+
+        assert_eq!(report.duplicate_files, 1);
+        assert_eq!(report.bytes_saved, png.len() as u64);
+        assert_eq!(report.shared.len(), 1);
+        let shared = &report.shared[0];
+        assert_eq!(shared.paths, ["img/a.png", "img/copy.png"]);
+        assert_eq!(shared.size, png.len() as u64);
+
+        let mut expected: Vec<_> = result
+            .questions
+            .iter()
+            .filter(|q| q.stem != "Question 3")
+            .map(|q| q.id)
+            .collect();
+        let mut ids = shared.question_ids.clone();
+        expected.sort();
+        ids.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_extracts_nested_archives() {
+        let png = crate::media::test_png(2, 2);
+        let inner = build_zip(&[("ch1/q.md", b"# Inner question\n\n![](fig.png)"), ("ch1/fig.png", &png)]);
+        let innermost = build_zip(&[("deep.md", b"# Deep question")]);
+        let middle = build_zip(&[("mid.md", b"# Middle question"), ("deeper.zip", &innermost)]);
+        let data = build_zip(&[
+            ("exam.md", b"# Outer question"),
+            ("bundle.zip", &inner),
+            ("nested.zip", &middle),
+        ]);
+
+        let result = ZipProcessor::new().with_max_nesting_depth(1).process_zip(data).await.unwrap();
+
+        let mut sources: Vec<_> = result
+            .questions
+            .iter()
+            .map(|q| q.provenance.as_ref().unwrap().source_file.clone().unwrap())
+            .collect();
+        sources.sort();
+        assert_eq!(sources, ["bundle.zip/ch1/q.md", "exam.md", "nested.zip/mid.md"]);
+
+        let inner_question = result.questions.iter().find(|q| q.stem == "Inner question").unwrap();
+        assert!(matches!(&inner_question.images[0], ImageRef::Local { hash, .. } if !hash.is_empty()));
+        assert!(result
+            .warnings
+            .contains(&"Skipping nested archive nested.zip/deeper.zip: nested deeper than 1 levels".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_joins_split_volumes() {
+        let inner = build_zip(&[("split.md", b"# Split question")]);
+        let (head, tail) = inner.split_at(inner.len() / 2);
+        let data = build_zip(&[("parts/set.zip.001", head), ("parts/set.zip.002", tail)]);
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+        assert_eq!(result.questions.len(), 1);
+        assert_eq!(result.files[0].path, "parts/set.zip/split.md");
+
+        let gap = build_zip(&[("set.zip.001", head), ("set.zip.003", tail)]);
+        let result = ZipProcessor::new().process_zip(gap).await.unwrap();
+        assert!(result.questions.is_empty());
+        assert_eq!(result.warnings, ["Skipping nested archive set.zip: missing volume 2"]);
+
+        let spanned = build_zip(&[("exam.z01", head), ("exam.zip", tail)]);
+        let result = ZipProcessor::new().process_zip(spanned).await.unwrap();
+        assert!(result.warnings[0].starts_with("Skipping nested archive exam.zip: Info-ZIP split archives"));
+
+        assert_eq!(archive_volume("a/B.ZIP"), Some(("a/B.ZIP".to_string(), u32::MAX)));
+        assert_eq!(archive_volume("b.z02"), Some(("b.zip".to_string(), 2)));
+        assert_eq!(archive_volume("notes.md"), None);
+        assert_eq!(archive_volume("scan.z"), None);
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_enforces_extraction_budget() {
+        let big = vec![b'x'; 4096];
+        let inner = build_zip(&[("big.txt", &big)]);
+        let data = build_zip(&[("exam.md", b"# Question"), ("inner.zip", &inner)]);
+
+        let err = ZipProcessor::new()
+            .with_max_extracted_bytes(2048)
+            .process_zip(data.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "archive expands to more than 2048 bytes");
+
+        assert!(ZipProcessor::new().with_max_extracted_bytes(16384).process_zip(data).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_spills_large_entries() {
+        use crate::workdir::{WorkdirConfig, WorkdirManager};
+
+        let root = tempfile::tempdir().unwrap();
+        let workdirs = Arc::new(WorkdirManager::new(WorkdirConfig::default().with_root(root.path()).with_min_free_bytes(0)).unwrap());
+        let spill = SpillConfig::new(workdirs, 64);
+
+        // A PNG with 128 bytes of pixel data, over the spill threshold
+        let png = crate::media::test_png(4, 3);
+        let mut idat = 128u32.to_be_bytes().to_vec();
+        idat.extend_from_slice(b"IDAT");
+        idat.extend_from_slice(&[0; 132]);
+        let png = [&png[..33], &idat, &png[33..]].concat();
+
+        let exam = format!("# Question 1\n\n![](img/a.png)\n\n{}", "padding ".repeat(16));
+        let inner = build_zip(&[("inner.md", exam.as_bytes()), ("img/a.png", &png)]);
+        let data = build_zip(&[("exam.md", b"# Small"), ("bundle.zip", &inner)]);
+        let archive = root.path().join("upload.zip");
+        std::fs::write(&archive, &data).unwrap();
+
+        let result = ZipProcessor::new()
+            .with_spill(spill)
+            .process_zip_file(&archive)
+            .await
+            .unwrap();
+
+        // The nested archive, its Markdown and the image were all spilled
+        assert_eq!(result.questions.len(), 2);
+        let image = result.images.values().next().unwrap();
+        assert!(image.is_spilled());
+        assert_eq!(*image.read().unwrap(), png[..]);
+        let inner_question = result.questions.iter().find(|q| q.stem == "Question 1").unwrap();
+        assert!(matches!(&inner_question.images[0], ImageRef::Local { hash, .. } if !hash.is_empty()));
+
+        let workdirs: Vec<_> = std::fs::read_dir(root.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with("md2db-extract-"))
+            .collect();
+        assert_eq!(workdirs.len(), 1);
+
+        // The working directory goes away with the last spilled entry
+        drop(result);
+        assert!(!workdirs[0].path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_spilled_images_are_hashed_and_scrubbed_on_disk() {
+        use crate::workdir::{WorkdirConfig, WorkdirManager};
+
+        let root = tempfile::tempdir().unwrap();
+        let workdirs = Arc::new(WorkdirManager::new(WorkdirConfig::default().with_root(root.path()).with_min_free_bytes(0)).unwrap());
+
+        let png = crate::media::test_png(4, 3);
+        let mut text = 9u32.to_be_bytes().to_vec();
+        text.extend_from_slice(b"tEXtGPS\x0051.5N\0\0\0\0");
+        let tagged = [&png[..33], &text, &png[33..]].concat();
+        let data = build_zip(&[("exam.md", b"# Question 1\n\n![](a.png)"), ("a.png", &tagged)]);
+
+        let result = ZipProcessor::new()
+            .with_spill(SpillConfig::new(workdirs, 32))
+            .with_strip_metadata(true)
+            .process_zip(data)
+            .await
+            .unwrap();
+
+        let (hash, content) = result.images.iter().next().unwrap();
+        assert!(content.is_spilled());
+        assert_eq!(content.len(), png.len() as u64);
+        assert_eq!(*content.read().unwrap(), png[..]);
+        assert_eq!(hash, &content_hash(&png));
+    }
+
+    #[test]
+    fn test_decode_text() {
+        let (text, encoding) = decode_text("数学 题".as_bytes()).unwrap();
+        assert_eq!((text.as_ref(), encoding.name()), ("数学 题", "UTF-8"));
+
+        let (gbk, _, _) = encoding_rs::GBK.encode("下列哪个是质数？");
+        let (text, encoding) = decode_text(&gbk).unwrap();
+        assert_eq!((text.as_ref(), encoding.name()), ("下列哪个是质数？", "gb18030"));
+
+        let utf16: Vec<u8> = [0xFF, 0xFE].into_iter().chain("题".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        assert_eq!(decode_text(&utf16).unwrap().0, "题");
+
+        assert!(decode_text(&[0xFF, 0x80, 0x00]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_decodes_gbk_names_and_content() {
+        let (gbk_exam, _, _) = encoding_rs::GBK.encode("# 第一题\n\n1 + 1 = ?\n\n* A. 1\n* B. 2\n\n答案：B");
+        let data = build_zip(&[("XXXX.md", &gbk_exam)]);
+
+        // Rewrite the ASCII placeholder name as raw GBK, as Windows tools do
+        let (gbk_name, _, _) = encoding_rs::GBK.encode("数学");
+        let mut patched = Vec::with_capacity(data.len());
+        let mut rest = &data[..];
+        while let Some(at) = rest.windows(4).position(|w| w == b"XXXX") {
+            patched.extend_from_slice(&rest[..at]);
+            patched.extend_from_slice(&gbk_name);
+            rest = &rest[at + 4..];
+        }
+        patched.extend_from_slice(rest);
+
+        let result = ZipProcessor::new().process_zip(patched).await.unwrap();
+
+        assert_eq!(result.files[0].path, "数学.md");
+        assert_eq!(result.questions.len(), 1);
+        assert_eq!(result.questions[0].stem, "第一题");
+        assert_eq!(result.questions[0].options.len(), 2);
+        assert!(result.warnings.contains(&"Decoded entry name 数学.md as GB18030".to_string()));
+        assert!(result.warnings.contains(&"数学.md: decoded as gb18030".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_links_images_by_relative_path() {
+        let png = crate::media::test_png(2, 2);
+        let other = crate::media::test_png(3, 3);
+        let exam = "# Question 1\n\n![](../shared/My%20Plot.png)\n\n\
+# Question 2\n\n![](Figures/B.PNG)\n\n# Question 3\n\n![](../../etc/passwd.png)";
+        let data = build_zip(&[
+            ("ch1/exam.md", exam.as_bytes()),
+            ("shared/My Plot.png", &png),
+            ("ch1/figures/b.png", &other),
+        ]);
+
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+        let hash_of = |i: usize| match &result.questions[i].images[0] {
+            ImageRef::Local { hash, .. } => hash.clone(),
+            other => panic!("unexpected image {:?}", other),
+        };
+
+        assert_eq!(hash_of(0), content_hash(&png));
+        assert_eq!(hash_of(1), content_hash(&other));
+        assert!(hash_of(2).is_empty());
+        assert!(result
+            .warnings
+            .contains(&"ch1/exam.md: image Figures/B.PNG matched ch1/figures/b.png ignoring case".to_string()));
+        assert!(result
+            .warnings
+            .contains(&"ch1/exam.md: image path escapes the archive: ../../etc/passwd.png".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_process_tar_gz_with_nested_zip() {
+        let inner = build_zip(&[("b.md", b"# Question 2")]);
+        let data = crate::archive::build_tar_gz(&[
+            ("exam/a.md", b"# Question 1\n\n![](img/a.png)"),
+            ("exam/img/a.png", &crate::media::test_png(1, 1)),
+            ("exam/inner.zip", &inner),
+        ]);
+
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+        let mut sources: Vec<_> = result
+            .questions
+            .iter()
+            .map(|q| q.provenance.as_ref().unwrap().source_file.clone().unwrap())
+            .collect();
+        sources.sort();
+        assert_eq!(sources, ["exam/a.md", "exam/inner.zip/b.md"]);
+        assert_eq!(result.images.len(), 1);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        let a = result.questions.iter().find(|q| q.stem == "Question 1").unwrap();
+        assert_eq!(
+            a.provenance.as_ref().unwrap().modified_at.map(|t| t.timestamp()),
+            Some(1_700_000_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_zip_preserves_question_order() {
+        let files: Vec<_> = (1..=8)
+            .map(|i| {
+                // Larger files first, so they would finish last if unordered
+                let padding = "filler text ".repeat((9 - i) * 2000);
+                (format!("{:02}.md", i), format!("# Q{}a\n\n{}\n\n# Q{}b", i, padding, i))
+            })
+            .collect();
+        let files: Vec<_> = files.iter().map(|(n, c)| (n.as_str(), c.as_bytes())).collect();
+        let data = build_zip(&files);
+
+        let result = ZipProcessor::with_workers(4).process_zip(data.clone()).await.unwrap();
+        let stems: Vec<_> = result.questions.iter().map(|q| q.stem.split('\n').next().unwrap().to_string()).collect();
+        let expected: Vec<_> = (1..=8).flat_map(|i| [format!("Q{}a", i), format!("Q{}b", i)]).collect();
+        assert_eq!(stems, expected);
+        let sequences: Vec<_> = result.questions.iter().map(|q| q.sequence).collect();
+        assert_eq!(sequences, (1..=16).map(Some).collect::<Vec<_>>());
+
+        let unordered = ZipProcessor::with_workers(4)
+            .with_preserve_order(false)
+            .process_zip(data)
+            .await
+            .unwrap();
+        assert_eq!(unordered.questions.len(), 16);
+        assert!(unordered.questions.iter().all(|q| q.sequence.is_none()));
+    }
+
+    #[cfg(feature = "pdf")]
+    #[tokio::test]
+    async fn test_process_zip_extracts_pdf_text() {
+        let page = "BT /F1 12 Tf 72 700 Td (1. Which is prime?) Tj 0 -14 Td (A. 4 B. 7) Tj ET";
+        let pdf = crate::pdf::build_pdf(&[page, "q 10 0 0 10 0 0 cm /Im1 Do Q"]);
+        let data = build_zip(&[("papers/exam.pdf", &pdf), ("broken.pdf", b"%PDF-1.4")]);
+        let processor = ZipProcessor::new().with_parser_options(presets::preset("generic-numbered").unwrap());
+
+        let result = processor.process_zip(data).await.unwrap();
+        assert_eq!(result.questions.len(), 1);
+        assert_eq!(result.questions[0].options.len(), 2);
+        assert_eq!(
+            result.questions[0].provenance.as_ref().unwrap().source_file.as_deref(),
+            Some("papers/exam.pdf")
+        );
+        assert!(result.warnings.contains(
+            &"papers/exam.pdf: page 2: no text layer, the page looks like a scanned image and needs OCR".to_string()
+        ));
+        assert!(result.warnings.iter().any(|w| w.starts_with("Failed to convert broken.pdf")));
+
+        let single = processor.process_upload("Exam.PDF", pdf).await.unwrap();
+        assert_eq!(single.questions.len(), 1);
+        assert!(processor.process_pdf("broken.pdf", b"%PDF-1.4".to_vec()).await.is_err());
+    }
+
+    #[cfg(feature = "docx")]
+    #[tokio::test]
+    async fn test_process_zip_converts_docx() {
+        let body = r#"<w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:t>Which shape?</w:t></w:r></w:p>
+            <w:p><w:r><w:drawing><a:blip r:embed="rId5"/></w:drawing></w:r></w:p>"#;
+        let png = crate::media::test_png(1, 1);
+        let docx = crate::docx::build_docx(body, None, Some(&png));
+        let data = build_zip(&[("ch1/exam.docx", &docx), ("broken.docx", b"not a document")]);
+
+        let result = ZipProcessor::new().process_zip(data).await.unwrap();
+        assert_eq!(result.questions.len(), 1);
+        let question = &result.questions[0];
+        assert_eq!(question.stem, "Which shape?");
+        assert_eq!(
+            question.provenance.as_ref().unwrap().source_file.as_deref(),
+            Some("ch1/exam.docx")
+        );
+        match &question.images[0] {
+            ImageRef::Local { hash, original_path, .. } => {
+                assert_eq!(original_path, "exam.docx.media/image1.png");
+                assert_eq!(hash, &content_hash(&png));
+            }
+            other => panic!("unexpected image {:?}", other),
+        }
+        assert_eq!(result.images.len(), 1);
+        assert!(result.warnings.iter().any(|w| w.starts_with("Failed to convert broken.docx")));
+
+        let single = ZipProcessor::new().process_docx("exam.docx", docx).await.unwrap();
+        assert_eq!(single.questions.len(), 1);
+        assert_eq!(single.images.len(), 1);
+        assert!(ZipProcessor::new().process_docx("broken.docx", b"x".to_vec()).await.is_err());
+    }
+
+    fn crc_of(data: &[u8]) -> u32 {
+        let zip = build_zip(&[("x", data)]);
+        let mut archive = zip::ZipArchive::new(Cursor::new(zip)).unwrap();
+        let crc = archive.by_index(0).unwrap().crc32();
+        crc
+    }
+
     #[tokio::test]
     async fn test_zip_processor_creation() {
         let processor = ZipProcessor::new();
@@ -246,4 +2130,28 @@ mod tests {
         let custom = ZipProcessor::with_workers(8);
         assert_eq!(custom.max_workers, 8);
     }
+
+    #[tokio::test]
+    async fn test_process_upload_detects_format() {
+        let processor = ZipProcessor::new().with_format_detection(true);
+        let markdown = b"# What is 2+2?\n\n* A. 3\n* B. 4".to_vec();
+        let result = processor.process_upload("exam.zip", markdown.clone()).await.unwrap();
+        assert_eq!(result.questions.len(), 1);
+        assert_eq!(
+            result.questions[0].provenance.as_ref().unwrap().source_file.as_deref(),
+            Some("exam.zip")
+        );
+
+        let gift = processor.process_upload("quiz", b"::Q1:: 1+1=2 {T}".to_vec()).await.unwrap();
+        assert_eq!(gift.questions[0].answer.as_deref(), Some("True"));
+
+        let archive = build_zip(&[("exam.md", &markdown)]);
+        assert_eq!(processor.process_upload("upload", archive).await.unwrap().questions.len(), 1);
+        let err = processor.process_upload("blob", vec![0, 1, 2, 0xff]).await.unwrap_err();
+        assert!(matches!(err, Md2DbError::ZipError { .. }), "{:?}", err);
+        assert_eq!(err.to_string(), "blob: unrecognized file format");
+        // Without detection the name decides, and this is no archive
+        let err = ZipProcessor::new().process_upload("exam.zip", markdown).await.unwrap_err();
+        assert_eq!(err.kind(), "zip_error");
+    }
 }
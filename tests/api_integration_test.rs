@@ -5,7 +5,6 @@
 use axum::{
     body::Body,
     http::{StatusCode, Method},
-    response::IntoResponse,
 };
 use md2db::api::create_router;
 use md2db::database::MockRepository;
@@ -96,6 +95,82 @@ async fn test_parse_endpoint_simple_question() {
 
     assert_eq!(json["count"], 1);
     assert_eq!(json["questions"][0]["stem"], "What is 2+2?");
+    assert!(json["session_id"].as_str().unwrap().parse::<uuid::Uuid>().is_ok());
+}
+
+#[tokio::test]
+async fn test_parse_endpoint_dry_run() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse?dry_run=true",
+        Some(serde_json::json!({ "markdown": "# Q1\n\n# Q2" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["dry_run"], true);
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["question_ids"][0], json["questions"][0]["id"]);
+    assert!(json.get("session_id").is_none());
+
+    // Nothing was stored
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 0);
+}
+
+#[tokio::test]
+async fn test_preview_endpoint() {
+    let app = create_test_app().await;
+
+    let markdown = "# Which is prime?\n\n* A. 4\n* B. 7\n\nAnswer: B\n\n# Which is even?\n\n* A. 3\n* B. 5\n\nAnswer: E\n";
+    let response = make_request(&app, Method::POST, "/preview", Some(serde_json::json!({ "markdown": markdown }))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["errors"], 1);
+    assert_eq!(json["questions"][0]["span"], serde_json::json!({ "start_line": 1, "end_line": 6 }));
+    assert_eq!(json["questions"][0]["question"]["answer"], "B");
+    assert!(json["questions"][0]["classification"]["confidence"].is_number());
+    assert_eq!(json["questions"][1]["span"]["start_line"], 8);
+    let codes: Vec<_> = json["questions"][1]["diagnostics"].as_array().unwrap().iter().map(|d| d["code"].clone()).collect();
+    assert!(codes.contains(&serde_json::json!("INVALID_ANSWER")), "{:?}", codes);
+
+    // Nothing was stored
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 0);
+}
+
+#[tokio::test]
+async fn test_validate_endpoint() {
+    let app = create_test_app().await;
+
+    let markdown = "# Pick one\n\n* A. 1\n* B. 2\n\nAnswer: AB\n\n# Pick another\n\n* A. 1\n* B. 2\n\nAnswer: B\n";
+    let response = make_request(&app, Method::POST, "/parse", Some(serde_json::json!({ "markdown": markdown, "default_type": "choice" }))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = make_request(&app, Method::POST, "/validate", Some(serde_json::json!({}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["checked"], 2);
+    assert_eq!(json["inconsistent"], 1);
+    let issues: Vec<_> = json["reports"].as_array().unwrap().iter().map(|r| r["issues"].as_array().unwrap().len()).collect();
+    assert_eq!(issues.iter().sum::<usize>(), 1);
+
+    let missing = uuid::Uuid::new_v4();
+    let response = make_request(&app, Method::POST, "/validate", Some(serde_json::json!({ "question_ids": [missing] }))).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
 #[tokio::test]
@@ -197,3 +272,1629 @@ async fn test_error_response_invalid_method() {
             || response.status() == StatusCode::NOT_FOUND
     );
 }
+
+#[tokio::test]
+async fn test_render_question_text() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is $x^2$ when x = 3?\n\n* A. 6\n* B. 9" })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = json["question_ids"][0].as_str().unwrap().to_string();
+
+    let uri = format!("/questions/{}/render?format=text", id);
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/plain; charset=utf-8"
+    );
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("x squared"));
+    assert!(text.contains("Option B: 9"));
+}
+
+#[tokio::test]
+async fn test_render_question_not_found() {
+    let app = create_test_app().await;
+
+    let uri = format!("/questions/{}/render", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_list_questions_pagination() {
+    let app = create_test_app().await;
+
+    let markdown = "# Q1\n\n# Q2\n\n# Q3";
+    make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": markdown })),
+    )
+    .await;
+
+    let response = make_request(&app, Method::GET, "/questions?limit=2", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["total"], 3);
+    assert_eq!(json["items"].as_array().unwrap().len(), 2);
+    let cursor = json["next_cursor"].as_str().unwrap().to_string();
+
+    let uri = format!("/questions?limit=2&cursor={}", cursor);
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["items"].as_array().unwrap().len(), 1);
+    assert!(json["next_cursor"].is_null());
+
+    let response = make_request(&app, Method::GET, "/questions?cursor=bogus", None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_question_and_filter_by_type() {
+    let app = create_test_app().await;
+
+    let markdown = "# Pick one\n\n* A. 3\n* B. 4\n\n# Explain\n\n# Pick again\n\n* A. x\n* B. y";
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": markdown })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = json["questions"][0]["id"].as_str().unwrap().to_string();
+    let qtype = json["questions"][0]["type"].as_str().unwrap().to_string();
+
+    let response = make_request(&app, Method::GET, &format!("/questions/{}", id), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["stem"], "Pick one");
+
+    let uri = format!("/questions/{}", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let uri = format!("/questions?type={}&limit=1&offset=1", qtype);
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 3);
+    assert_eq!(json["items"].as_array().unwrap().len(), 1);
+    assert_eq!(json["items"][0]["type"], qtype.as_str());
+
+    let response = make_request(&app, Method::GET, "/questions?type=true_false", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 0);
+
+    let response = make_request(&app, Method::GET, "/questions?type=essay", None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_update_and_delete_question() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# Wat is 2+2?\n\n* A. 3\n* B. 4" })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let mut question = json["questions"][0].clone();
+    let id = question["id"].as_str().unwrap().to_string();
+    let uri = format!("/questions/{}", id);
+
+    question["stem"] = serde_json::json!("What is 2+2?");
+    let response = make_request(&app, Method::PUT, &uri, Some(question.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["items"][0]["stem"], "What is 2+2?");
+
+    let response = make_request(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = make_request(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = make_request(&app, Method::PUT, &uri, Some(question)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let restore = format!("{}/restore", uri);
+    let response = make_request(&app, Method::POST, &restore, None).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = make_request(&app, Method::POST, &restore, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 1);
+}
+
+#[tokio::test]
+async fn test_question_history() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# Wat is 2+2?\n\n* A. 3\n* B. 4" })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let mut question = json["questions"][0].clone();
+    let id = question["id"].as_str().unwrap().to_string();
+
+    question["stem"] = serde_json::json!("What is 2+2?");
+    let request = axum::http::Request::builder()
+        .method(Method::PUT)
+        .uri(format!("/questions/{}", id))
+        .header("content-type", "application/json")
+        .header("x-actor", "reviewer@example.com")
+        .body(Body::from(question.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = make_request(&app, Method::GET, &format!("/questions/{}/history", id), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let history: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(history.as_array().unwrap().len(), 1);
+    assert_eq!(history[0]["revision"], 1);
+    assert_eq!(history[0]["actor"], "reviewer@example.com");
+    assert_eq!(history[0]["question"]["stem"], "Wat is 2+2?");
+
+    let missing = format!("/questions/{}/history", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &missing, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_question_banks() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/banks",
+        Some(serde_json::json!({ "name": "Physics midterm", "tags": ["物理"] })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let bank: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let bank_id = bank["id"].as_str().unwrap().to_string();
+    assert_eq!(bank["name"], "Physics midterm");
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# Q1\n\n* A. 1\n* B. 2", "bank_id": bank_id })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["questions"][0]["bank_id"], bank_id.as_str());
+
+    // Questions imported without a bank can be moved in afterwards
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# Q2\n\n* A. 1\n* B. 2" })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let uri = format!("/banks/{}/questions", bank_id);
+    let response = make_request(
+        &app,
+        Method::POST,
+        &uri,
+        Some(serde_json::json!({ "question_ids": json["question_ids"] })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["assigned"], 1);
+
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+
+    let response = make_request(&app, Method::GET, "/banks", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+
+    let missing = uuid::Uuid::new_v4();
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# Q3", "bank_id": missing })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = make_request(&app, Method::GET, &format!("/banks/{}", missing), None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_export_gift() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = make_request(&app, Method::GET, "/export/gift", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/plain; charset=utf-8");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let gift = String::from_utf8(body.to_vec()).unwrap();
+    assert_eq!(gift, "[markdown]What is 2+2? {\n\t~3\n\t=4\n}\n");
+
+    let uri = format!("/export/gift?bank_id={}", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[cfg(feature = "docx")]
+#[tokio::test]
+async fn test_export_docx() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B\n\n# Name a prime\n\nAnswer: 7" })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let ids = json["question_ids"].as_array().unwrap();
+
+    let request = serde_json::json!({
+        "question_ids": [ids[1], ids[0]],
+        "layout": { "title": "Quiz", "answer_key": true },
+    });
+    let response = make_request(&app, Method::POST, "/export/docx", Some(request)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let document = md2db::docx::convert(&body, "media").unwrap();
+    assert!(
+        document.markdown.starts_with("# Quiz\n\n1. Name a prime\n\n"),
+        "{}",
+        document.markdown
+    );
+    assert!(document.markdown.contains("2. What is 2+2?\n\nA. 3\n\nB. 4"));
+    assert!(document.markdown.ends_with("# Answer Key\n\n1. 7\n\n2. B"));
+
+    let request = serde_json::json!({ "question_ids": [uuid::Uuid::new_v4()] });
+    let response = make_request(&app, Method::POST, "/export/docx", Some(request)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[cfg(feature = "xlsx")]
+#[tokio::test]
+async fn test_export_xlsx() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = make_request(&app, Method::GET, "/export/xlsx", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let mut workbook = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+    assert!(workbook.by_name("xl/worksheets/sheet1.xml").is_ok());
+
+    let uri = format!("/export/xlsx?bank_id={}", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[cfg(feature = "anki")]
+#[tokio::test]
+async fn test_export_anki() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = make_request(&app, Method::GET, "/export/anki?deck=Arithmetic", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/octet-stream");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let mut package = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+    assert!(package.by_name("collection.anki2").is_ok());
+    assert!(package.by_name("media").is_ok());
+
+    let uri = format!("/export/anki?bank_id={}", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_markdown_round_trip() {
+    let app = create_test_app().await;
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is $2*2$?\n\n* A. 3\n* B. 4\n\nAnswer: B\n\nAnalysis: Double *two*." })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let original = json["questions"][0].clone();
+    let id = json["question_ids"][0].as_str().unwrap().to_string();
+
+    let response = make_request(&app, Method::GET, &format!("/questions/{}/markdown", id), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/markdown; charset=utf-8");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let markdown = String::from_utf8(body.to_vec()).unwrap();
+    assert!(markdown.starts_with("# What is $2\\*2$?\n"), "{}", markdown);
+
+    let target = create_test_app().await;
+    let response = make_request(&target, Method::POST, "/parse", Some(serde_json::json!({ "markdown": markdown }))).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let reparsed = &json["questions"][0];
+    for field in ["stem", "options", "answer", "analysis"] {
+        assert_eq!(reparsed[field], original[field], "{}", field);
+    }
+
+    let response = make_request(&app, Method::GET, "/export/markdown", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/zip");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+    let mut exported = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("questions.md").unwrap(), &mut exported).unwrap();
+    assert_eq!(exported, markdown);
+
+    let uri = format!("/questions/{}/markdown", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_jsonl_round_trip() {
+    let source = create_test_app().await;
+    let response = make_request(
+        &source,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B\n\n# Name a prime" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = make_request(&source, Method::GET, "/export/jsonl", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.iter().filter(|&&b| b == b'\n').count(), 2);
+
+    // A bank that only exists on the source instance is dropped
+    let mut lines = String::from_utf8(body.to_vec()).unwrap();
+    let mut moved: serde_json::Value = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+    moved["bank_id"] = serde_json::json!(uuid::Uuid::new_v4());
+    lines.push_str(&format!("{}\nnot json\n", moved));
+
+    let target = create_test_app().await;
+    let request = axum::http::Request::builder()
+        .method(Method::POST)
+        .uri("/import/jsonl")
+        .header("content-type", "application/x-ndjson")
+        .body(Body::from(lines))
+        .unwrap();
+    let response = target.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["questions"][0]["answer"], "B");
+    assert_ne!(json["questions"][0]["id"], moved["id"]);
+    assert!(json["questions"][2]["bank_id"].is_null());
+    let warnings = json["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].as_str().unwrap().starts_with("line 4: "));
+    assert!(warnings[1].as_str().unwrap().contains("imported without a bank"));
+
+    let uri = format!("/export/jsonl?bank_id={}", uuid::Uuid::new_v4());
+    let response = make_request(&target, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_bulk_export_formats() {
+    let app = create_test_app().await;
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B\n\n# Name a prime" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body_of = |response: axum::response::Response| async move {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    };
+
+    let response = make_request(&app, Method::GET, "/export", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    assert_eq!(body_of(response).await.lines().count(), 2);
+
+    let response = make_request(&app, Method::GET, "/export?format=gift", None).await;
+    assert_eq!(response.headers()["content-type"], "text/plain; charset=utf-8");
+    assert!(body_of(response).await.starts_with("[markdown]What is 2+2? {\n\t~3\n\t=4\n}\n\n"));
+
+    // Without a format the Accept header decides
+    let request = axum::http::Request::builder()
+        .uri("/export")
+        .header("accept", "application/xml")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/xml");
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"questions.qti.xml\""
+    );
+    let qti = body_of(response).await;
+    assert!(qti.starts_with(md2db::qti::QTI_HEADER) && qti.ends_with(md2db::qti::QTI_FOOTER));
+    assert_eq!(qti.matches("<item ").count(), 2);
+    assert!(qti.contains("multiple_choice_question"));
+
+    let response = make_request(&app, Method::GET, "/export?format=jsonl&type=choice", None).await;
+    assert_eq!(body_of(response).await, "");
+
+    let response = make_request(&app, Method::GET, "/export?format=csv", None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let uri = format!("/export?format=qti&bank_id={}", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_media_endpoint() {
+    let root = tempfile::tempdir().unwrap();
+    let store: Arc<dyn md2db::media::MediaStore> = Arc::new(md2db::media::FsMediaStore::new(root.path()).unwrap());
+    let png = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 1, 2, 3, 4];
+    let stored = store.put(&png).await.unwrap();
+    let app = create_test_app().await.layer(axum::Extension(store));
+
+    let uri = format!("/media/{}", stored.hash);
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = format!("\"{}\"", stored.hash);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    assert!(response.headers()["cache-control"].to_str().unwrap().contains("immutable"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.as_ref(), png);
+
+    // A cached copy is revalidated without sending the image again
+    let conditional = |uri: &str, tags: &str| {
+        axum::http::Request::builder()
+            .uri(uri)
+            .header("if-none-match", tags)
+            .body(Body::empty())
+            .unwrap()
+    };
+    for tags in [etag.clone(), format!("W/{}", etag), format!("\"other\", {}", etag), "*".to_string()] {
+        let response = app.clone().oneshot(conditional(&uri, &tags)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", tags);
+        assert_eq!(response.headers()["etag"], etag.as_str());
+        assert!(response.headers()["cache-control"].to_str().unwrap().contains("immutable"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+    }
+    let response = app.clone().oneshot(conditional(&uri, "\"other\"")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // Hashes are matched case-insensitively
+    let upper = format!("/media/{}", stored.hash.to_ascii_uppercase());
+    let response = app.clone().oneshot(conditional(&upper, &etag)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let missing = format!("/media/{}", "0".repeat(64));
+    let response = make_request(&app, Method::GET, &missing, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = app.clone().oneshot(conditional(&missing, "*")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Anything but 64 hex digits is rejected before the store is asked
+    let malformed = [
+        "/media/..%2Fetc".to_string(),
+        format!("/media/{}", &stored.hash[..63]),
+        format!("/media/{}0", stored.hash),
+        format!("/media/{}", "g".repeat(64)),
+        format!("/media/{}.png", stored.hash),
+    ];
+    for uri in &malformed {
+        let response = app.clone().oneshot(conditional(uri, "*")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "not_found", "{}", uri);
+    }
+
+    // Without a media store nothing is served
+    let response = make_request(&create_test_app().await, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[cfg(feature = "latex-render")]
+#[tokio::test]
+async fn test_question_formulas() {
+    let app = create_test_app().await;
+    let question = md2db::Question { latex: vec!["$\\frac{1}{2}$".to_string()], ..Default::default() };
+    let request = axum::http::Request::builder()
+        .method(Method::POST)
+        .uri("/import/jsonl?keep_ids=true")
+        .header("content-type", "application/jsonl")
+        .body(Body::from(serde_json::to_string(&question).unwrap()))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+    let uri = format!("/questions/{}/formulas", question.id);
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json[0]["format"], "mathml");
+    assert!(json[0]["content"].as_str().unwrap().contains("<mfrac>"));
+
+    // With a media store the SVG is stored and served from /media
+    let root = tempfile::tempdir().unwrap();
+    let store: Arc<dyn md2db::media::MediaStore> = Arc::new(md2db::media::FsMediaStore::new(root.path()).unwrap());
+    let app = app.layer(axum::Extension(store));
+    let response = make_request(&app, Method::GET, &format!("{}?format=svg", uri), None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json[0].get("content").is_none());
+    let media = format!("/media/{}", json[0]["hash"].as_str().unwrap());
+    let response = make_request(&app, Method::GET, &media, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+
+    let response = make_request(&app, Method::GET, &format!("{}?format=png", uri), None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_idempotency_keys() {
+    let app = create_test_app().await;
+    let parse = |key: &str, markdown: &str| {
+        axum::http::Request::builder()
+            .method(Method::POST)
+            .uri("/parse")
+            .header("content-type", "application/json")
+            .header("idempotency-key", key)
+            .body(Body::from(serde_json::json!({ "markdown": markdown }).to_string()))
+            .unwrap()
+    };
+    let json = |response: axum::response::Response| async move {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    let first = app.clone().oneshot(parse("k1", "# Q1")).await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get("idempotent-replayed").is_none());
+    let first = json(first).await;
+
+    // A retry gets the original response and imports nothing
+    let retry = app.clone().oneshot(parse("k1", "# Q1")).await.unwrap();
+    assert_eq!(retry.status(), StatusCode::OK);
+    assert_eq!(retry.headers()["idempotent-replayed"], "true");
+    assert_eq!(json(retry).await["question_ids"], first["question_ids"]);
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    assert_eq!(json(response).await["total"], 1);
+
+    let reused = app.clone().oneshot(parse("k1", "# Q2")).await.unwrap();
+    assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let other = app.clone().oneshot(parse("k2", "# Q1")).await.unwrap();
+    assert!(other.headers().get("idempotent-replayed").is_none());
+
+    // Uploads are keyed the same way
+    let data = build_zip(&[("exam.md", "# Zipped")]);
+    let upload = || {
+        let mut request = upload_request("/parse-zip", "exam.zip", &data);
+        request.headers_mut().insert("idempotency-key", "upload-1".parse().unwrap());
+        request
+    };
+    let response = app.clone().oneshot(upload()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(upload()).await.unwrap();
+    assert_eq!(response.headers()["idempotent-replayed"], "true");
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    assert_eq!(json(response).await["total"], 3);
+}
+
+#[tokio::test]
+async fn test_stats() {
+    let app = create_test_app().await;
+    let response = make_request(&app, Method::GET, "/stats", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 0);
+    assert_eq!(json["by_type"]["choice"], 0);
+
+    let response = make_request(&app, Method::POST, "/banks", Some(serde_json::json!({ "name": "Physics" }))).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let bank: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let markdown = "# What is 2+2?\n\n* A. 3\n* B. 4\n\nAnswer: B\n\n# 简述牛顿第一定律";
+    let request = serde_json::json!({ "markdown": markdown, "bank_id": bank["id"] });
+    make_request(&app, Method::POST, "/parse", Some(request)).await;
+    make_request(&app, Method::POST, "/parse", Some(serde_json::json!({ "markdown": "# Name a prime" }))).await;
+
+    let response = make_request(&app, Method::GET, "/stats", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 3);
+    assert_eq!(json["by_type"]["subjective"], 3);
+    assert_eq!(json["by_bank"][0]["name"], "Physics");
+    assert_eq!(json["by_bank"][0]["count"], 2);
+    assert!(json["by_bank"][1]["bank_id"].is_null());
+    let review = &json["review"];
+    assert_eq!(review["needs_review"].as_u64().unwrap() + review["confident"].as_u64().unwrap(), 3);
+    assert_eq!(json["by_language"]["en"], 2);
+    assert_eq!(json["by_language"]["zh"], 1);
+    let imported: u64 = json["imports"].as_array().unwrap().iter().map(|d| d["count"].as_u64().unwrap()).sum();
+    assert_eq!(imported, 3);
+}
+
+#[tokio::test]
+async fn test_search() {
+    let app = create_test_app().await;
+    let markdown = "# 算法的时间复杂度是多少？\n\n# Which sorting algorithm is stable?\n\n* A. Quicksort\n* B. Merge sort";
+    make_request(&app, Method::POST, "/parse", Some(serde_json::json!({ "markdown": markdown }))).await;
+
+    let search = |query: &str| {
+        let app = app.clone();
+        let uri = format!("/search?{}", query);
+        async move {
+            let response = make_request(&app, Method::GET, &uri, None).await;
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+        }
+    };
+
+    let (status, hits) = search("q=sort").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hits.as_array().unwrap().len(), 1);
+    assert_eq!(hits[0]["question"]["stem"], "Which sorting algorithm is stable?");
+    assert!(hits[0]["score"].as_u64().unwrap() > 0);
+
+    let (_, hits) = search("q=%E5%A4%8D%E6%9D%82%E5%BA%A6").await;
+    assert_eq!(hits[0]["question"]["stem"], "算法的时间复杂度是多少？");
+    let (_, hits) = search("q=sort&type=true_false").await;
+    assert!(hits.as_array().unwrap().is_empty());
+    #[cfg(feature = "pinyin")]
+    {
+        let (_, hits) = search("q=suanfa").await;
+        assert_eq!(hits[0]["question"]["stem"], "算法的时间复杂度是多少？");
+    }
+
+    let (status, _) = search("q=").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = search(&format!("q=sort&bank_id={}", uuid::Uuid::new_v4())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_similar_questions() {
+    let app = create_test_app().await;
+    let markdown = "# 下列关于快速排序的说法正确的是\n\n# 下列关于快速排序的说法中，正确的是\n\n# What is the capital of France?";
+    let response = make_request(&app, Method::POST, "/parse", Some(serde_json::json!({ "markdown": markdown }))).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let ids = json["question_ids"].as_array().unwrap();
+
+    let uri = format!("/questions/{}/similar", ids[0].as_str().unwrap());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let similar: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(similar.as_array().unwrap().len(), 1);
+    assert_eq!(similar[0]["question"]["id"], ids[1]);
+    assert!(similar[0]["similarity"].as_f64().unwrap() > 0.5);
+
+    let uri = format!("/questions/{}/similar", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_render_question_with_locale() {
+    let app = create_test_app().await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# 下列哪个是Rust关键字?\n\n* A. fn\n* B. def" })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = json["question_ids"][0].as_str().unwrap().to_string();
+
+    let uri = format!("/questions/{}/render?locale=zh", id);
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("下列哪个是 Rust 关键字？"));
+
+    let uri = format!("/questions/{}/render?locale=fr", id);
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Build a ZIP archive in memory
+fn build_zip(files: &[(&str, &str)]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut data = Vec::new();
+    {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(&mut data));
+        for (name, content) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+    data
+}
+
+#[tokio::test]
+async fn test_parse_zip_multiple_archives_as_one_job() {
+    let app = create_test_app().await;
+    let boundary = "md2db-test-boundary";
+
+    let mut body = Vec::new();
+    for (filename, markdown) in [
+        ("a.zip", "# Question 1\n\n# Question 2"),
+        ("b.zip", "# Question 2\n\n# Question 3"),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: application/zip\r\n\r\n",
+                boundary, filename
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&build_zip(&[("exam.md", markdown)]));
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    let request = axum::http::Request::builder()
+        .method(Method::POST)
+        .uri("/parse-zip")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["duplicates_skipped"], 1);
+    assert_eq!(json["archives"].as_array().unwrap().len(), 2);
+    assert_eq!(json["files"].as_array().unwrap().len(), 2);
+    assert_eq!(json["questions"][2]["stem"], "Question 3");
+    assert_eq!(json["questions"][2]["provenance"]["archive"], "b.zip");
+    assert_eq!(json["questions"][2]["provenance"]["sequence"], 3);
+}
+
+#[tokio::test]
+async fn test_parse_files_uploads_several_markdown_files() {
+    let app = create_test_app().await;
+    let request = |files: &[(&str, &str)]| {
+        let boundary = "md2db-test-boundary";
+        let mut body = String::new();
+        for (filename, markdown) in files {
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: text/markdown\r\n\r\n{}\r\n",
+                boundary, filename, markdown
+            ));
+        }
+        body.push_str(&format!("--{}--\r\n", boundary));
+        axum::http::Request::builder()
+            .method(Method::POST)
+            .uri("/parse-files")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let files = [
+        ("chapter1.md", "# Question 1\n\n# Question 2"),
+        ("chapter2.markdown", "# Question 3"),
+    ];
+    let response = app.clone().oneshot(request(&files)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total_questions"], 3);
+    assert_eq!(json["saved_questions"], 3);
+
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 3);
+
+    let response = app.clone().oneshot(request(&[("notes.txt", "# Question 4")])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "invalid_file");
+}
+
+#[tokio::test]
+async fn test_parse_files_uses_the_server_processor() {
+    use md2db::jobs::JobManager;
+    use md2db::processor::{ProcessorConfig, SingleMachineProcessor};
+
+    let repository: Arc<dyn md2db::database::QuestionRepository> = Arc::new(MockRepository::new());
+    let config = ProcessorConfig::default().with_transactional(true);
+    let jobs = Arc::new(JobManager::new(SingleMachineProcessor::with_config(repository.clone(), config)));
+    let app = create_router()
+        .with_state(repository)
+        .layer(axum::Extension(jobs));
+
+    let boundary = "md2db-test-boundary";
+    let body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"exam.md\"\r\n\
+         Content-Type: text/markdown\r\n\r\n# Question 1\r\n--{b}--\r\n",
+        b = boundary
+    );
+    let request = axum::http::Request::builder()
+        .method(Method::POST)
+        .uri("/parse-files")
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["saved_questions"], 1);
+    assert!(json["session_id"].is_string(), "the configured transactional import was not used: {}", json);
+}
+
+#[tokio::test]
+async fn test_background_import() {
+    use md2db::jobs::JobManager;
+    use md2db::processor::SingleMachineProcessor;
+
+    let repository: Arc<dyn md2db::database::QuestionRepository> = Arc::new(MockRepository::new());
+    let jobs = Arc::new(JobManager::new(SingleMachineProcessor::new(repository.clone())));
+    let app = create_router()
+        .with_state(repository)
+        .layer(axum::Extension(jobs));
+
+    let data = build_zip(&[("exam.md", "# Question 1\n\n# Question 2")]);
+    let response = app.clone().oneshot(upload_request("/imports", "exam.zip", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = json["job_id"].as_str().unwrap().to_string();
+
+    let status_uri = format!("/imports/{}", id);
+    let mut status = serde_json::Value::Null;
+    for _ in 0..100 {
+        let response = make_request(&app, Method::GET, &status_uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        status = serde_json::from_slice(&body).unwrap();
+        if status["status"] == "completed" || status["status"] == "failed" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(status["status"], "completed");
+    assert_eq!(status["sources"][0], "exam.zip");
+    assert_eq!(status["progress"], 100.0);
+    assert_eq!(status["count"], 2);
+
+    let response = make_request(&app, Method::GET, &format!("/imports/{}/result", id), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["question_ids"].as_array().unwrap().len(), 2);
+    assert_eq!(json["questions"][1]["stem"], "Question 2");
+    assert_eq!(json["archives"][0]["source"], "exam.zip");
+    assert_eq!(json["files"].as_array().unwrap().len(), 1);
+
+    let response = make_request(&app, Method::GET, &format!("/imports/{}/report", id), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/markdown"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report = String::from_utf8(body.to_vec()).unwrap();
+    assert!(report.starts_with(&format!("# Import report {}", id)));
+    assert!(report.contains("## Questions by type"));
+    assert!(report.contains("## Low-confidence questions"));
+
+    let response = make_request(&app, Method::GET, &format!("/imports/{}/report?format=html", id), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/html"));
+    let response = make_request(&app, Method::GET, &format!("/imports/{}/report?format=pdf", id), None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let uri = format!("/imports/{}", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Servers without a job manager do not offer background imports
+    let response = create_test_app()
+        .await
+        .oneshot(upload_request("/imports", "exam.zip", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_background_import_progress_over_websocket() {
+    use futures::StreamExt;
+    use md2db::jobs::JobManager;
+    use md2db::processor::SingleMachineProcessor;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let repository: Arc<dyn md2db::database::QuestionRepository> = Arc::new(MockRepository::new());
+    let jobs = Arc::new(JobManager::new(SingleMachineProcessor::new(repository.clone())));
+    let app = create_router()
+        .with_state(repository)
+        .layer(axum::Extension(jobs));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move {
+        axum::serve(listener, server).await.unwrap();
+    });
+
+    let data = build_zip(&[("exam.md", "# Question 1\n\n# Question 2")]);
+    let response = app.clone().oneshot(upload_request("/imports", "exam.zip", &data)).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let id = json["job_id"].as_str().unwrap().to_string();
+
+    let url = format!("ws://{}/imports/{}/ws", addr, id);
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let mut events = Vec::new();
+    while let Some(message) = socket.next().await {
+        match message.unwrap() {
+            Message::Text(text) => events.push(serde_json::from_str::<serde_json::Value>(&text).unwrap()),
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    // The job may finish before the socket opens; it always ends the same way
+    let last = events.last().unwrap();
+    assert_eq!(last["event"], "finished");
+    assert_eq!(last["status"], "completed");
+    assert_eq!(last["job_id"], id.as_str());
+    assert!(events.iter().all(|e| e["job_id"] == id.as_str()));
+
+    let uri = format!("/imports/{}/ws", uuid::Uuid::new_v4());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    assert_ne!(response.status(), StatusCode::OK);
+}
+
+/// Multipart request uploading one file to `uri`
+fn upload_request(uri: &str, filename: &str, data: &[u8]) -> axum::http::Request<Body> {
+    let boundary = "md2db-test-boundary";
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"archive\"; filename=\"{}\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\n",
+        boundary, filename
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    axum::http::Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_parse_archive_accepts_tar_gz() {
+    let app = create_test_app().await;
+
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    let markdown = b"# Question 1\n\n# Question 2";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(markdown.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, "exam/exam.md", &markdown[..]).unwrap();
+    let data = builder.into_inner().unwrap().finish().unwrap();
+
+    let response = app.clone().oneshot(upload_request("/parse-archive", "exam.tgz", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["questions"][0]["provenance"]["source_file"], "exam/exam.md");
+
+    let response = app.clone().oneshot(upload_request("/parse-archive", "exam.docx", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_parse_archive_imports_into_manifest_bank() {
+    let app = create_test_app().await;
+    let manifest = r#"{"bank": "Midterm", "subject": "Physics"}"#;
+
+    for stem in ["Question 1", "Question 2"] {
+        let data = build_zip(&[("manifest.json", manifest), ("exam.md", &format!("# {}", stem))]);
+        let response = app.clone().oneshot(upload_request("/parse-archive", "exam.zip", &data)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["questions"][0]["tags"][0], "Physics");
+    }
+
+    let response = make_request(&app, Method::GET, "/banks", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let banks: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(banks.as_array().unwrap().len(), 1);
+    assert_eq!(banks[0]["name"], "Midterm");
+
+    let uri = format!("/banks/{}/questions", banks[0]["id"].as_str().unwrap());
+    let response = make_request(&app, Method::GET, &uri, None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+}
+
+#[cfg(feature = "docx")]
+#[tokio::test]
+async fn test_parse_docx_endpoint() {
+    let app = create_test_app().await;
+    let document = r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+        <w:p><w:r><w:t>1. Which is prime?</w:t></w:r></w:p>
+        <w:p><w:r><w:t>A. 4 B. 7</w:t></w:r></w:p>
+        <w:p><w:r><w:t>答案：B</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+    let data = build_zip(&[("word/document.xml", document)]);
+
+    let response = app.clone().oneshot(upload_request("/parse-docx", "exam.docx", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["questions"][0]["provenance"]["source_file"], "exam.docx");
+    assert_eq!(json["questions"][0]["options"].as_array().unwrap().len(), 2);
+
+    let response = app.clone().oneshot(upload_request("/parse-docx", "exam.zip", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = app.clone().oneshot(upload_request("/parse-docx", "broken.docx", b"not a document")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[cfg(feature = "pdf")]
+#[tokio::test]
+async fn test_parse_pdf_endpoint() {
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let font_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let content = "BT /F1 12 Tf 72 700 Td (1. Which is prime?) Tj 0 -14 Td (A. 4 B. 7) Tj 0 -14 Td (Answer: B) Tj ET";
+    let content_id = document.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+    let page_id = document.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+    });
+    document.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! { "Type" => "Pages", "Count" => 1, "Kids" => vec![page_id.into()] }),
+    );
+    let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    document.trailer.set("Root", catalog_id);
+    let mut data = Vec::new();
+    document.save_to(&mut data).unwrap();
+
+    let app = create_test_app().await;
+    let response = app.clone().oneshot(upload_request("/parse-pdf", "exam.pdf", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["questions"][0]["provenance"]["source_file"], "exam.pdf");
+    assert_eq!(json["questions"][0]["answer"], "B");
+
+    let response = app.clone().oneshot(upload_request("/parse-pdf", "exam.docx", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Multipart body with a file and extra text fields
+#[cfg(feature = "csv")]
+fn multipart_request(uri: &str, filename: &str, data: &[u8], fields: &[(&str, &str)]) -> axum::http::Request<Body> {
+    let boundary = "----md2dbtableboundary";
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value).as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+            boundary, filename
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    axum::http::Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[cfg(feature = "csv")]
+#[tokio::test]
+async fn test_import_table_endpoint() {
+    let app = create_test_app().await;
+    let csv = "Question,First,Second,Key,Topic\n1+1=?,2,3,A,arithmetic\n2+3=?,4,5,B,arithmetic\n";
+    let mapping = r#"{"stem": "question", "options": ["first", "second"], "answer": "key", "tags": "topic"}"#;
+
+    let request = multipart_request("/import/table", "bank.csv", csv.as_bytes(), &[("mapping", mapping)]);
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["questions"][1]["answer"], "B");
+    assert_eq!(json["questions"][1]["options"][1]["is_correct"], true);
+    assert_eq!(json["questions"][0]["tags"][0], "arithmetic");
+    assert_eq!(json["questions"][0]["type"], "choice");
+
+    let response = app.clone().oneshot(multipart_request("/import/table", "bank.csv", b"name,value\nx,1\n", &[])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = app.clone().oneshot(multipart_request("/import/table", "bank.md", csv.as_bytes(), &[])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[cfg(feature = "csv")]
+#[tokio::test]
+async fn test_import_detects_format() {
+    let app = create_test_app().await;
+    let csv = "Question,First,Second,Key\n1+1=?,2,3,A\n";
+    let mapping = r#"{"stem": "question", "options": ["first", "second"], "answer": "key"}"#;
+    let uploads = [
+        ("upload", &b"# What is 2+2?\n\n* A. 3\n* B. 4"[..], None, "What is 2+2?"),
+        ("bank.txt", csv.as_bytes(), Some(mapping), "1+1=?"),
+        ("export", &b"::Q1:: The sun is a star. {T}"[..], None, "The sun is a star."),
+    ];
+
+    for (filename, data, mapping, stem) in uploads {
+        let fields: Vec<_> = mapping.into_iter().map(|m| ("mapping", m)).collect();
+        let response = app.clone().oneshot(multipart_request("/import", filename, data, &fields)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", filename);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["count"], 1, "{}", filename);
+        assert_eq!(json["questions"][0]["stem"], stem);
+    }
+
+    let response = app.clone().oneshot(multipart_request("/import", "blob.zip", &[0, 1, 2, 0xff], &[])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_parse_endpoint_dedup() {
+    let app = create_test_app().await;
+    let markdown = "# What is 2+2?\n\n* A. 3\n* B. 4";
+
+    make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": markdown })),
+    )
+    .await;
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": markdown, "dedup": true })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["count"], 0);
+    assert_eq!(json["outcomes"][0]["outcome"], "duplicate_skipped");
+    assert_eq!(json["outcomes"][0]["similarity"], 1.0);
+
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 1);
+}
+
+#[tokio::test]
+async fn test_parse_endpoint_with_preset() {
+    let app = create_test_app().await;
+    let markdown = "1、HTTP 默认端口是？\nA、80\nB、443\n正确答案：A\n";
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": markdown, "preset": "kaoshibao" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["questions"][0]["stem"], "HTTP 默认端口是？");
+    assert_eq!(json["questions"][0]["options"].as_array().unwrap().len(), 2);
+    assert_eq!(json["questions"][0]["answer"], "A");
+
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": markdown, "preset": "unknown" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_key_authentication_and_rate_limit() {
+    use md2db::auth::{require_auth, Authenticator};
+
+    let auth = Authenticator::new().with_key("ci", "k-123").with_rate_limit(2);
+    let app = create_test_app()
+        .await
+        .layer(axum::middleware::from_fn_with_state(Arc::new(auth), require_auth));
+    let parse = |key: Option<&str>| {
+        let mut builder = axum::http::Request::builder()
+            .method(Method::POST)
+            .uri("/parse")
+            .header("content-type", "application/json");
+        if let Some(key) = key {
+            builder = builder.header("x-api-key", key);
+        }
+        builder
+            .body(Body::from(serde_json::json!({ "markdown": "# 1+1?\n\n* A. 2" }).to_string()))
+            .unwrap()
+    };
+
+    // Changes need a key; reads stay open
+    let response = app.clone().oneshot(parse(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "unauthorized");
+    let response = app.clone().oneshot(parse(Some("wrong"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app.clone().oneshot(parse(Some("k-123"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bearer = axum::http::Request::builder()
+        .method(Method::POST)
+        .uri("/parse")
+        .header("content-type", "application/json")
+        .header("authorization", "Bearer k-123")
+        .body(Body::from(serde_json::json!({ "markdown": "# 2+2?" }).to_string()))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(bearer).await.unwrap().status(), StatusCode::OK);
+
+    // The third request within a minute is over the limit
+    let response = app.clone().oneshot(parse(Some("k-123"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "30");
+}
+
+#[tokio::test]
+async fn test_body_limits_and_content_types() {
+    use md2db::body_limit::BodyLimits;
+
+    let limits = BodyLimits::default().with_json(64).with_upload(1024);
+    let app = create_test_app().await.layer(axum::Extension(limits));
+    let error = |response: axum::http::Response<Body>| async move {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["error"].as_str().unwrap().to_string()
+    };
+
+    // Wrong content type, rejected before parsing
+    let request = axum::http::Request::builder()
+        .method(Method::POST)
+        .uri("/parse")
+        .header("content-type", "text/plain")
+        .body(Body::from("# 1+1?"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(error(response).await, "unsupported_media_type");
+    let response = app
+        .clone()
+        .oneshot(upload_request("/parse", "a.md", b"# 1+1?"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    // JSON bodies have the small cap, uploads the large one
+    let markdown = "# Question\n".repeat(10);
+    let response = make_request(&app, Method::POST, "/parse", Some(serde_json::json!({ "markdown": markdown }))).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(error(response).await, "payload_too_large");
+    let response = make_request(&app, Method::POST, "/parse", Some(serde_json::json!({ "markdown": "# 1+1?" }))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let archive = build_zip(&[("a.md", "# Question 1")]);
+    let response = app.clone().oneshot(upload_request("/parse-zip", "a.zip", &archive)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // JSON Lines files are uploads: over the JSON cap, under the upload one
+    let jsonl = |lines: String| {
+        axum::http::Request::builder()
+            .method(Method::POST)
+            .uri("/import/jsonl?dry_run=true")
+            .header("content-type", "application/x-ndjson")
+            .body(Body::from(lines))
+            .unwrap()
+    };
+    let line = serde_json::to_string(&md2db::Question::default()).unwrap();
+    assert!(line.len() > 64 && line.len() < 1024);
+    let response = app.clone().oneshot(jsonl(line)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(jsonl("{}\n".repeat(600))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(error(response).await, "payload_too_large");
+    let (parts, _) = jsonl(String::new()).into_parts();
+    let chunks: Vec<Result<&str, std::io::Error>> = (0..600).map(|_| Ok("{}\n")).collect();
+    let request = axum::http::Request::from_parts(parts, Body::from_stream(futures::stream::iter(chunks)));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // A body of unknown length is stopped once it passes the cap
+    let big = upload_request("/parse-zip", "big.zip", &vec![0u8; 4096]);
+    let (parts, body) = big.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    let chunks: Vec<Result<Vec<u8>, std::io::Error>> = body.chunks(512).map(|c| Ok(c.to_vec())).collect();
+    let request = axum::http::Request::from_parts(parts, Body::from_stream(futures::stream::iter(chunks)));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(error(response).await, "payload_too_large");
+}
+
+#[tokio::test]
+async fn test_parse_with_classification_options() {
+    let app = create_test_app().await;
+    let markdown = "# Which number is prime?\n\n* A. 2\n* B. 3\n* C. 4\n* D. 5\n\n# Explain recursion";
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({
+            "markdown": markdown,
+            "classify": true,
+            "language": "en",
+            "default_type": "fill_in_the_blank",
+            "dry_run": true,
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["dry_run"], true);
+    assert_eq!(json["questions"][0]["type"], "choice");
+    assert_eq!(json["questions"][1]["type"], "fill_in_the_blank");
+    let classifications = json["classifications"].as_array().unwrap();
+    assert_eq!(classifications[0]["question_id"], json["questions"][0]["id"]);
+    assert_eq!(classifications[0]["type"], "choice");
+    assert!(classifications[0]["confidence"].as_f64().unwrap() > 0.5);
+    assert_eq!(classifications[1]["confidence"], 0.0);
+    assert_eq!(classifications[1]["needs_review"], true);
+    let response = make_request(&app, Method::GET, "/questions", None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 0);
+
+    // Without classify, default_type sets every question's type
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": markdown, "default_type": "choice", "dedupe": true })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["questions"][1]["type"], "choice");
+    assert!(json.get("classifications").is_none());
+    assert_eq!(json["outcomes"].as_array().unwrap().len(), 2);
+
+    let response = make_request(&app, Method::POST, "/parse", Some(serde_json::json!({ "markdown": markdown, "language": "fr" }))).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_patch_question() {
+    let app = create_test_app().await;
+    let response = make_request(
+        &app,
+        Method::POST,
+        "/parse",
+        Some(serde_json::json!({ "markdown": "# Wat is 2+2?\n\n* A. 3\n* B. 4" })),
+    )
+    .await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let uri = format!("/questions/{}", json["questions"][0]["id"].as_str().unwrap());
+    let patch = |body: serde_json::Value| {
+        let app = app.clone();
+        let uri = uri.clone();
+        async move {
+            let request = axum::http::Request::builder()
+                .method(Method::PATCH)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .header("x-actor", "reviewer@example.com")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+        }
+    };
+
+    // Fix the typo and pick the correct option; the rest is kept
+    let (status, question) = patch(serde_json::json!({ "stem": "What is 2+2?", "answer": "b", "type": "choice" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(question["stem"], "What is 2+2?");
+    assert_eq!(question["type"], "choice");
+    assert_eq!(question["answer"], "B");
+    assert_eq!(question["options"][0]["is_correct"], false);
+    assert_eq!(question["options"][1]["is_correct"], true);
+
+    // Changes that break the rules of the type are rejected
+    let (status, error) = patch(serde_json::json!({ "answer": "D" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error["error"], "validation_error");
+    let (status, _) = patch(serde_json::json!({ "options": ["4"] })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = patch(serde_json::json!({ "stem": " " })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = patch(serde_json::json!({ "colour": "red" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = make_request(&app, Method::GET, &format!("{}/history", uri), None).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let history: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(history.as_array().unwrap().len(), 1);
+    assert_eq!(history[0]["actor"], "reviewer@example.com");
+    assert_eq!(history[0]["question"]["stem"], "Wat is 2+2?");
+
+    let missing = format!("/questions/{}", uuid::Uuid::new_v4());
+    let request = axum::http::Request::builder()
+        .method(Method::PATCH)
+        .uri(missing)
+        .header("content-type", "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+}
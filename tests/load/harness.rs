@@ -0,0 +1,227 @@
+//! In-process load harness
+//!
+//! Spawns the API on an ephemeral port backed by a mock repository and
+//! drives it with a configurable number of concurrent HTTP clients.
+
+use hyper::{Body, Client, Method, Request, StatusCode};
+use md2db::api::create_router;
+use md2db::database::{MockRepository, QuestionRepository};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Load test parameters, overridable through environment variables
+#[derive(Debug, Clone)]
+pub struct LoadConfig {
+    /// Number of in-flight requests (`MD2DB_LOAD_CONCURRENCY`)
+    pub concurrency: usize,
+    /// Total number of requests per scenario (`MD2DB_LOAD_REQUESTS`)
+    pub requests: usize,
+    /// p95 latency budget in milliseconds (`MD2DB_LOAD_P95_MS`)
+    pub p95_budget_ms: u64,
+    /// Allowed resident memory growth in MiB (`MD2DB_LOAD_RSS_MB`)
+    pub rss_budget_mb: u64,
+}
+
+impl LoadConfig {
+    /// Read configuration from the environment, falling back to CI-friendly defaults
+    pub fn from_env() -> Self {
+        fn var(name: &str, default: u64) -> u64 {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        }
+
+        Self {
+            concurrency: var("MD2DB_LOAD_CONCURRENCY", 8).max(1) as usize,
+            requests: var("MD2DB_LOAD_REQUESTS", 200).max(1) as usize,
+            p95_budget_ms: var("MD2DB_LOAD_P95_MS", 500),
+            rss_budget_mb: var("MD2DB_LOAD_RSS_MB", 256),
+        }
+    }
+}
+
+/// Latency statistics for one scenario
+#[derive(Debug)]
+pub struct LoadReport {
+    pub requests: usize,
+    pub failures: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl LoadReport {
+    fn from_samples(mut samples: Vec<Duration>, failures: usize, total: Duration) -> Self {
+        samples.sort();
+        let pick = |pct: f64| -> Duration {
+            if samples.is_empty() {
+                return Duration::ZERO;
+            }
+            let idx = ((samples.len() as f64 * pct).ceil() as usize).saturating_sub(1);
+            samples[idx.min(samples.len() - 1)]
+        };
+
+        Self {
+            requests: samples.len(),
+            failures,
+            p50: pick(0.50),
+            p95: pick(0.95),
+            max: samples.last().copied().unwrap_or_default(),
+            total,
+        }
+    }
+
+    /// Requests per second over the whole scenario
+    pub fn throughput(&self) -> f64 {
+        self.requests as f64 / self.total.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// A prepared request body that can be replayed many times
+#[derive(Clone)]
+pub struct RequestTemplate {
+    pub method: Method,
+    pub path: &'static str,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+/// Start the API server on an ephemeral local port
+pub async fn spawn_server() -> SocketAddr {
+    let repository: Arc<dyn QuestionRepository> = Arc::new(MockRepository::new());
+    let app = create_router().with_state(repository);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    addr
+}
+
+/// Drive `template` against the server with the configured concurrency
+pub async fn run(addr: SocketAddr, config: &LoadConfig, template: RequestTemplate) -> LoadReport {
+    let client = Client::new();
+    let semaphore = Arc::new(Semaphore::new(config.concurrency));
+    let uri = format!("http://{}{}", addr, template.path);
+    let started = Instant::now();
+
+    let mut handles = Vec::with_capacity(config.requests);
+    for _ in 0..config.requests {
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let client = client.clone();
+        let uri = uri.clone();
+        let template = template.clone();
+
+        handles.push(tokio::spawn(async move {
+            let _permit = permit;
+            let request = Request::builder()
+                .method(template.method)
+                .uri(uri)
+                .header("content-type", template.content_type)
+                .body(Body::from(template.body))
+                .unwrap();
+
+            let begin = Instant::now();
+            let ok = match client.request(request).await {
+                Ok(response) => {
+                    let status = response.status();
+                    hyper::body::to_bytes(response.into_body()).await.is_ok()
+                        && status == StatusCode::OK
+                }
+                Err(_) => false,
+            };
+            (begin.elapsed(), ok)
+        }));
+    }
+
+    let mut samples = Vec::with_capacity(handles.len());
+    let mut failures = 0;
+    for handle in handles {
+        let (elapsed, ok) = handle.await.unwrap();
+        if !ok {
+            failures += 1;
+        }
+        samples.push(elapsed);
+    }
+
+    LoadReport::from_samples(samples, failures, started.elapsed())
+}
+
+/// Markdown document with `count` numbered choice questions
+pub fn sample_markdown(count: usize) -> String {
+    let mut markdown = String::new();
+    for i in 0..count {
+        markdown.push_str(&format!(
+            "# 第{}题：以下哪个是算法 {} 的时间复杂度？\n\n* A. O(n)\n* B. O(log n)\n* C. O(n^2)\n* D. $O(n \\log n)$\n\n",
+            i + 1,
+            i
+        ));
+    }
+    markdown
+}
+
+/// JSON body for `POST /parse`
+pub fn parse_template(questions: usize) -> RequestTemplate {
+    RequestTemplate {
+        method: Method::POST,
+        path: "/parse",
+        content_type: "application/json".to_string(),
+        body: serde_json::to_vec(&serde_json::json!({ "markdown": sample_markdown(questions) }))
+            .unwrap(),
+    }
+}
+
+/// Multipart body for `POST /parse-zip` containing `files` markdown documents
+pub fn parse_zip_template(files: usize, questions_per_file: usize) -> RequestTemplate {
+    let mut archive = Vec::new();
+    {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(&mut archive));
+        let options = zip::write::SimpleFileOptions::default();
+        for i in 0..files {
+            writer.start_file(format!("exam_{}.md", i), options).unwrap();
+            writer
+                .write_all(sample_markdown(questions_per_file).as_bytes())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    let boundary = "md2db-load-boundary";
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"bank.zip\"\r\nContent-Type: application/zip\r\n\r\n",
+            boundary
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&archive);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    RequestTemplate {
+        method: Method::POST,
+        path: "/parse-zip",
+        content_type: format!("multipart/form-data; boundary={}", boundary),
+        body,
+    }
+}
+
+/// Resident set size of the current process in KiB (Linux only)
+pub fn resident_memory_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find(|line| line.starts_with("VmRSS:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
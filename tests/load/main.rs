@@ -0,0 +1,69 @@
+//! Latency and memory budget tests for the API request path
+//!
+//! Each scenario runs against an in-process server. Scale it up locally with
+//! e.g. `MD2DB_LOAD_REQUESTS=5000 MD2DB_LOAD_CONCURRENCY=64 cargo test --release --test load -- --nocapture`.
+
+mod harness;
+
+use harness::{LoadConfig, LoadReport};
+use std::time::Duration;
+
+fn assert_within_budget(name: &str, config: &LoadConfig, report: &LoadReport) {
+    println!(
+        "{}: {} requests, {} failed, p50={:?} p95={:?} max={:?}, {:.1} req/s",
+        name,
+        report.requests,
+        report.failures,
+        report.p50,
+        report.p95,
+        report.max,
+        report.throughput()
+    );
+
+    assert_eq!(report.failures, 0, "{}: requests failed", name);
+    assert!(
+        report.p95 <= Duration::from_millis(config.p95_budget_ms),
+        "{}: p95 latency {:?} exceeds budget of {}ms",
+        name,
+        report.p95,
+        config.p95_budget_ms
+    );
+}
+
+fn assert_memory_budget(name: &str, config: &LoadConfig, before: Option<u64>) {
+    if let (Some(before), Some(after)) = (before, harness::resident_memory_kb()) {
+        let growth_mb = after.saturating_sub(before) / 1024;
+        println!("{}: RSS grew by {} MiB", name, growth_mb);
+        assert!(
+            growth_mb <= config.rss_budget_mb,
+            "{}: RSS grew by {} MiB, budget is {} MiB",
+            name,
+            growth_mb,
+            config.rss_budget_mb
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_parse_latency_budget() {
+    let config = LoadConfig::from_env();
+    let addr = harness::spawn_server().await;
+    let before = harness::resident_memory_kb();
+
+    let report = harness::run(addr, &config, harness::parse_template(20)).await;
+
+    assert_within_budget("POST /parse", &config, &report);
+    assert_memory_budget("POST /parse", &config, before);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_parse_zip_latency_budget() {
+    let config = LoadConfig::from_env();
+    let addr = harness::spawn_server().await;
+    let before = harness::resident_memory_kb();
+
+    let report = harness::run(addr, &config, harness::parse_zip_template(5, 20)).await;
+
+    assert_within_budget("POST /parse-zip", &config, &report);
+    assert_memory_budget("POST /parse-zip", &config, before);
+}